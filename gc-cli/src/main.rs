@@ -2,17 +2,77 @@
 
 use anyhow::{Result, bail};
 use clap::Parser;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, mpsc};
+use tracing_subscriber::EnvFilter;
 
-use gc::counter::count_gate_types;
-use gc::evaluator::evaluate_circuit;
-use gc::garbler::{WireLabels, garble_circuit};
+use gc::aiger::import_aiger;
+use gc::and_depth::analyze_and_depth;
+use gc::bandwidth::{LinkStats, MeteredStream, TransferMeter};
+use gc::binary_circuit::{
+    BinaryCircuitHeader, BinaryCircuitReader, BinaryCircuitWriter, GateIndex,
+    convert_binary_to_bristol, convert_bristol_to_binary,
+};
+use gc::blif::import_blif;
+use gc::bus::{Bus, BusLayout, BusValue};
+use gc::channel::Channel;
+use gc::chunked_parse::ChunkedCircuit;
+use gc::circuits::{
+    carry_lookahead_adder, comparator, equality, multiplier, mux_tree, ripple_carry_adder,
+};
+use gc::compose::{WiringSpec, compose_circuits};
+use gc::constants::{BINARY_CIRCUIT_MAGIC, LOWERED_STREAM_BUFFER_SIZE, ZSTD_COMPRESSION_LEVEL};
+use gc::counter::{count_gate_types, count_gate_types_parallel};
+use gc::dead_gates::eliminate_dead_gates;
+use gc::decoder::{DecodingInfo, check_outputs, decode_buses, decode_outputs};
+use gc::error::GcError;
+use gc::evaluator::{
+    EvaluationOptions, EvaluationResult, GarbledTableReader, TableAccess,
+    evaluate_circuit_from_reader, evaluate_circuit_layered, evaluate_circuit_with_options,
+};
+use gc::fused_analysis::analyze_fused;
+use gc::garbler::{GarblerBuilder, WireLabels};
+use gc::gate::{Circuit, GateType, WireId, hash_circuit};
+use gc::input_assignment::InputAssignment;
+use gc::json_circuit::JsonCircuit;
+use gc::layering::{LayerReport, analyze_layers, profile_layers};
+use gc::lowering::{LoweringReader, scan_gates};
 use gc::memory_simulation::simulate_memory_usage;
-use gc::ot_simulation::simulate_ot;
+use gc::ot_extension::{OtExtensionBackend, OtSecurity};
+use gc::ot_remote::{receive_ot, send_ot};
+use gc::ot_simulation::{
+    EncodedInputs, OtChoices, OtSenderMessage, select_inputs, simulate_ot, simulate_ot_streaming,
+};
+use gc::ot_transcript::{OtParty, OtTranscript, verify_ot_transcript};
+use gc::parser::BristolWriter;
+use gc::partition::InputPartition;
+use gc::plain::{PlainResult, evaluate_plain, input_bits_from_ot};
+use gc::progress::TerminalProgress;
+use gc::random_circuit::{FanOut, RandomCircuit, RandomCircuitParams};
+use gc::renumber::compact_wires;
+use gc::rpc::{GarblerService, evaluate_with_garbler};
+use gc::scale_mamba::import_scale_mamba;
+use gc::session::{Artifact, SessionManifest};
+use gc::shard::ShardedTableWriter;
 use gc::single_use_analyzer::analyze_single_use_gates;
+use gc::sink::{CompressedTableWriter, GarbledTableSink, TableWriter};
+use gc::stacked::{StackedCircuit, StackedMaterial, evaluate_stacked, garble_stacked};
 use gc::stream::BufferedLineStream;
-use gc::wire_analyzer::{WireUsageReport, analyze_wire_usage};
+use gc::table_transfer::{receive_tables, resume_point, send_tables};
+use gc::tls::{PeerTrust, TlsClient, TlsIdentity, TlsServer};
+use gc::topological::{check_topological_order, topological_sort};
+use gc::two_party::{TwoPartyOptions, TwoPartyReport, connect_and_evaluate, garble_and_serve};
+use gc::verifier::verify_garbled_tables;
+use gc::websocket::WebSocketStream;
+use gc::wire_analyzer::{
+    DEFAULT_TOP_FAN_OUT_WIRES, UsageStorage, WireUsageReport, analyze_circuit, analyze_fan_out,
+    analyze_wire_usage, analyze_wire_usage_parallel, analyze_wire_usage_with_storage,
+};
+use gc::wire_lifetime::{NO_GATE, analyze_wire_lifetimes};
 
 /// High-performance Bristol circuit file analyzer
 #[derive(Parser, Debug)]
@@ -21,6 +81,41 @@ use gc::wire_analyzer::{WireUsageReport, analyze_wire_usage};
 #[command(version)]
 #[command(subcommand_required = true)]
 struct Args {
+    /// Filter for library telemetry written to stderr
+    #[arg(
+        long = "log",
+        global = true,
+        help = "Tracing filter for telemetry on stderr, e.g. 'info' or 'gc=debug' (default: RUST_LOG, else 'warn')"
+    )]
+    log: Option<String>,
+    /// Session manifest to check inputs against and record outputs in
+    #[arg(
+        long = "manifest",
+        global = true,
+        help = "Session manifest JSON: check the files this command reads against their recorded digests, and record the ones it writes"
+    )]
+    manifest: Option<PathBuf>,
+    /// Named input and output buses of the circuit
+    #[arg(
+        long = "buses",
+        global = true,
+        help = "Bus layout JSON naming input and output buses: inputs and --garbler-inputs may then name input buses, and output buses are printed as integers"
+    )]
+    bus_layout: Option<PathBuf>,
+    /// Skip blank and comment lines in circuit files
+    #[arg(
+        long = "lenient",
+        global = true,
+        help = "Skip blank lines and comment lines (starting with # or //) in the circuit file instead of failing on them"
+    )]
+    lenient: bool,
+    /// Memory-map circuit files instead of reading them through a buffer
+    #[arg(
+        long = "mmap-circuit",
+        global = true,
+        help = "Memory-map circuit files and parse them straight from the mapped bytes, instead of reading them through a 1 GB buffer"
+    )]
+    mmap_circuit: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -30,13 +125,19 @@ enum Commands {
     /// Count occurrences of each gate type
     Count {
         /// Path to the Bristol circuit file
-        #[arg(help = "Bristol circuit file to process")]
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
         file: PathBuf,
+        /// Scan the circuit on several threads
+        #[arg(
+            long = "threads",
+            help = "Scan the circuit on this many threads, in chunks of whole lines of the memory-mapped file (0 for one per core)"
+        )]
+        threads: Option<usize>,
     },
     /// Analyze wire usage patterns and connectivity
     WireAnalysis {
         /// Path to the Bristol circuit file
-        #[arg(help = "Bristol circuit file to process")]
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
         file: PathBuf,
         /// Output file for wire analysis (default: <input>.wire_analysis)
         #[arg(
@@ -45,19 +146,98 @@ enum Commands {
             help = "Output file for wire analysis results"
         )]
         output: Option<PathBuf>,
+        /// Store usage counts sparsely for circuits with gaps in their wire IDs
+        #[arg(
+            long = "sparse",
+            help = "Store usage counts in a hash map instead of one byte per wire ID"
+        )]
+        sparse: bool,
+        /// Scan the circuit on several threads
+        #[arg(
+            long = "threads",
+            help = "Scan the circuit on this many threads, in chunks of whole lines of the memory-mapped file (0 for one per core)"
+        )]
+        threads: Option<usize>,
+        /// Also export the fan-out distribution and the highest fan-out wires
+        #[arg(
+            long = "fan-out",
+            help = "Make a second pass for the exact fan-out histogram and highest fan-out wires, saved to <input>.fan_out.json and <input>.fan_out.csv"
+        )]
+        fan_out: bool,
+        /// Number of highest fan-out wires to list
+        #[arg(
+            long = "top",
+            default_value_t = DEFAULT_TOP_FAN_OUT_WIRES,
+            requires = "fan_out",
+            help = "Number of highest fan-out wires to list"
+        )]
+        top: usize,
+    },
+    /// Compute dependency layers for parallel evaluation
+    LayerAnalysis {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
+        file: PathBuf,
+        /// Output file for layer analysis (default: <input>.layers)
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Output file for layer analysis results"
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Count the gates and AND gates in each dependency layer
+    LayerProfile {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
+        file: PathBuf,
+        /// Output file for the layer profile (default: <input>.layer_profile.csv)
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Output CSV file for the gates and AND gates of every layer"
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Compute the AND depth of a circuit and of each of its outputs
+    AndDepth {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
+        file: PathBuf,
+        /// Output file for the AND depth report (default: <input>.and_depth.json)
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Output JSON file for the AND depth of every output"
+        )]
+        output: Option<PathBuf>,
     },
     /// Garble a Bristol circuit file using the provided seed
     Garble {
         /// Path to the Bristol circuit file
-        #[arg(help = "Bristol circuit file to process")]
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
         file: PathBuf,
-        /// Binary file containing wire usage analysis
+        /// Binary file containing wire usage analysis (computed on the fly if omitted)
         #[arg(
             short = 'w',
             long = "wire-analysis",
-            help = "Binary file containing wire usage analysis"
+            help = "Binary file containing wire usage analysis; analyzed on the fly if omitted"
         )]
-        wire_analysis_file: PathBuf,
+        wire_analysis_file: Option<PathBuf>,
+        /// Garble in a single pass without wire analysis, keeping every label in memory
+        #[arg(
+            long = "no-wire-analysis",
+            conflicts_with = "wire_analysis_file",
+            help = "Garble in a single pass without wire analysis, keeping every label in memory"
+        )]
+        no_wire_analysis: bool,
+        /// Rewrite NAND, OR, NOR, XNOR and INV gates into XOR and AND as the circuit is read
+        #[arg(
+            long = "lower-gates",
+            conflicts_with = "wire_analysis_file",
+            help = "Rewrite NAND, OR, NOR, XNOR and INV gates into XOR and AND while reading the circuit"
+        )]
+        lower_gates: bool,
         /// File containing seed for the garbling process
         #[arg(
             short = 's',
@@ -68,6 +248,61 @@ enum Commands {
         /// Output file for garbled circuit (default: <input>.garbled)
         #[arg(short = 'o', long = "output", help = "Output file for garbled circuit")]
         output: Option<PathBuf>,
+        /// Compress garbled tables with zstd
+        #[arg(long = "compress", help = "Compress garbled tables with zstd")]
+        compress: bool,
+        /// Write wire labels in the compact binary format instead of JSON
+        #[arg(
+            long = "binary-labels",
+            help = "Write wire labels in the compact binary format instead of JSON"
+        )]
+        binary_labels: bool,
+        /// Write garbling statistics to this JSON file
+        #[arg(
+            long = "stats-json",
+            help = "Write garbling statistics (gates, timing, memory) to a JSON file"
+        )]
+        stats_json: Option<PathBuf>,
+        /// Input wires owned by the garbler, e.g. `0..64,128`
+        #[arg(
+            long = "garbler-inputs",
+            conflicts_with = "input_partition_file",
+            help = "Input wire ranges owned by the garbler, e.g. '0..64,128' (others go through OT)"
+        )]
+        garbler_inputs: Option<String>,
+        /// JSON file assigning input wires to the garbler
+        #[arg(
+            long = "input-partition",
+            help = "JSON input partition file listing the garbler's input wire ranges"
+        )]
+        input_partition_file: Option<PathBuf>,
+        /// Split the garbled tables into this many shard files
+        #[arg(
+            long = "shards",
+            help = "Split garbled tables into N shard files described by <output>.json"
+        )]
+        shards: Option<u64>,
+        /// Directories to spread shard files over
+        #[arg(
+            long = "shard-dirs",
+            value_delimiter = ',',
+            requires = "shards",
+            help = "Comma-separated directories to spread shard files over (default: next to the manifest)"
+        )]
+        shard_dirs: Vec<PathBuf>,
+        /// Worker threads hashing AND gates
+        #[arg(
+            long = "hash-threads",
+            help = "Worker threads hashing AND gates while the circuit is parsed, 0 to hash inline (default: up to 4, one per spare core)"
+        )]
+        hash_threads: Option<usize>,
+        /// Memory budget for wire labels, e.g. `512M` or `8G`
+        #[arg(
+            long = "max-memory",
+            value_parser = parse_byte_size,
+            help = "Memory budget for wire labels, e.g. '512M' or '8G'; less recently used labels spill to a temp file"
+        )]
+        max_memory: Option<u64>,
     },
     /// Simulate OT protocol to select input wire labels
     OtSimulate {
@@ -75,253 +310,3457 @@ enum Commands {
         #[arg(
             short = 'w',
             long = "wire-labels",
-            help = "Wire labels JSON file from garbler"
+            help = "Wire labels file from garbler (JSON or binary)"
         )]
         wire_labels_file: PathBuf,
         /// File containing seed for OT simulation
         #[arg(
             short = 's',
             long = "seed-file",
+            required_unless_present = "inputs_file",
+            conflicts_with = "inputs_file",
             help = "File containing seed for OT simulation"
         )]
-        seed_file: PathBuf,
-        /// Output file for OT results (default: <input>.ot.json)
+        seed_file: Option<PathBuf>,
+        /// Input bits to select instead of random ones
+        #[arg(
+            short = 'i',
+            long = "inputs",
+            help = "Input bits as a JSON object of wire ID to boolean, or a hex number whose bit i is the i-th input wire"
+        )]
+        inputs_file: Option<PathBuf>,
+        /// Output file for OT results (default: <input>.ot.json, or <input>.ot.bin with --binary)
         #[arg(
             short = 'o',
             long = "output",
             help = "Output file for OT simulation results"
         )]
         output: Option<PathBuf>,
+        /// Encoded garbler inputs to use instead of random garbler bits
+        #[arg(
+            short = 'g',
+            long = "garbler-inputs",
+            help = "Encoded garbler inputs file (JSON or binary) from encode-inputs"
+        )]
+        garbler_inputs_file: Option<PathBuf>,
+        /// Write OT results in the compact binary format instead of JSON
+        #[arg(
+            long = "binary",
+            help = "Write OT results in the compact binary format instead of JSON"
+        )]
+        binary: bool,
+        /// Write each selected label as it is drawn instead of building the result in memory
+        #[arg(
+            long = "stream",
+            requires = "seed_file",
+            conflicts_with = "garbler_inputs_file",
+            help = "Write each selected label as it is drawn, keeping memory bounded for millions of inputs"
+        )]
+        stream: bool,
+    },
+    /// Serve OT for the evaluator's input labels over TCP (garbler side)
+    OtSend {
+        /// Wire labels file from garbler output
+        #[arg(
+            short = 'w',
+            long = "wire-labels",
+            help = "Wire labels file from garbler (JSON or binary)"
+        )]
+        wire_labels_file: PathBuf,
+        /// Address to listen on for the evaluator
+        #[arg(
+            short = 'l',
+            long = "listen",
+            default_value = "127.0.0.1:7741",
+            help = "Address to listen on for the evaluator: host:port, ws://host:port/path, unix:<path>, or - for stdin/stdout"
+        )]
+        listen: String,
+        /// File containing seed for the garbler's OT secrets
+        #[arg(
+            short = 's',
+            long = "seed-file",
+            help = "File containing a 32-byte seed for the garbler's OT secrets"
+        )]
+        seed_file: PathBuf,
+        /// Encoded garbler inputs to send along with the OT
+        #[arg(
+            short = 'g',
+            long = "garbler-inputs",
+            help = "Encoded garbler inputs file (JSON or binary) from encode-inputs"
+        )]
+        garbler_inputs_file: Option<PathBuf>,
+        /// OT security level, which must match the evaluator's
+        #[arg(
+            long = "security",
+            default_value_t = OtSecurity::SemiHonest,
+            help = "OT security level: semi-honest or malicious (adds the KOS check)"
+        )]
+        security: OtSecurity,
+        /// OT extension backend, which must match the evaluator's
+        #[arg(
+            long = "extension",
+            default_value_t = OtExtensionBackend::Iknp,
+            help = "OT extension backend: iknp or softspoken-K with K in 1, 2, 4, 8 (sends K times less than IKNP)"
+        )]
+        extension: OtExtensionBackend,
+        /// File to record every exchanged message to, for ot-verify
+        #[arg(
+            long = "transcript",
+            help = "Record every exchanged message to this file, for ot-verify"
+        )]
+        transcript: Option<PathBuf>,
+        /// Write OT statistics to this JSON file
+        #[arg(
+            long = "stats-json",
+            help = "Write OT statistics (inputs, bytes exchanged, timing) to a JSON file"
+        )]
+        stats_json: Option<PathBuf>,
+        #[command(flatten)]
+        tls: TlsServerArgs,
+    },
+    /// Obtain input labels for the evaluator's bits over TCP (evaluator side)
+    OtReceive {
+        /// Address of the garbler running ot-send
+        #[arg(
+            short = 'c',
+            long = "connect",
+            default_value = "127.0.0.1:7741",
+            help = "Address of the garbler running ot-send: host:port, ws://host:port/path, unix:<path>, or - for stdin/stdout"
+        )]
+        connect: String,
+        /// The evaluator's input bits as a JSON object of wire ID to boolean
+        #[arg(
+            short = 'i',
+            long = "inputs",
+            help = "JSON file mapping the evaluator's input wire IDs to booleans"
+        )]
+        inputs_file: PathBuf,
+        /// File containing seed for the evaluator's OT secrets
+        #[arg(
+            short = 's',
+            long = "seed-file",
+            help = "File containing a 32-byte seed for the evaluator's OT secrets"
+        )]
+        seed_file: PathBuf,
+        /// OT security level, which must match the garbler's
+        #[arg(
+            long = "security",
+            default_value_t = OtSecurity::SemiHonest,
+            help = "OT security level: semi-honest or malicious (adds the KOS check)"
+        )]
+        security: OtSecurity,
+        /// OT extension backend, which must match the garbler's
+        #[arg(
+            long = "extension",
+            default_value_t = OtExtensionBackend::Iknp,
+            help = "OT extension backend: iknp or softspoken-K with K in 1, 2, 4, 8 (sends K times less than IKNP)"
+        )]
+        extension: OtExtensionBackend,
+        /// File to record every exchanged message to, for ot-verify
+        #[arg(
+            long = "transcript",
+            help = "Record every exchanged message to this file, for ot-verify"
+        )]
+        transcript: Option<PathBuf>,
+        /// Write OT statistics to this JSON file
+        #[arg(
+            long = "stats-json",
+            help = "Write OT statistics (inputs, bytes exchanged, timing) to a JSON file"
+        )]
+        stats_json: Option<PathBuf>,
+        /// Output file for OT results (default: `<inputs>.ot.json`, or `<inputs>.ot.bin` with --binary)
+        #[arg(short = 'o', long = "output", help = "Output file for OT results")]
+        output: Option<PathBuf>,
+        /// Write OT results in the compact binary format instead of JSON
+        #[arg(
+            long = "binary",
+            help = "Write OT results in the compact binary format instead of JSON"
+        )]
+        binary: bool,
+        #[command(flatten)]
+        tls: TlsClientArgs,
     },
-    /// Evaluate a garbled circuit using OT-selected input labels
-    Evaluate {
-        /// Path to the Bristol circuit file
-        #[arg(help = "Bristol circuit file to process")]
-        file: PathBuf,
-        /// Binary file containing wire usage analysis
+    /// Check a recorded OT transcript against the committed wire labels and revealed seeds
+    OtVerify {
+        /// Wire labels file from garbler output
         #[arg(
             short = 'w',
-            long = "wire-analysis",
-            help = "Binary file containing wire usage analysis"
+            long = "wire-labels",
+            help = "Wire labels file from garbler (JSON or binary)"
         )]
-        wire_analysis_file: PathBuf,
-        /// OT simulation results file
+        wire_labels_file: PathBuf,
+        /// Transcript recorded by ot-send or ot-receive
         #[arg(
             short = 't',
+            long = "transcript",
+            help = "OT transcript recorded with --transcript"
+        )]
+        transcript_file: PathBuf,
+        /// Seed the garbler used in ot-send
+        #[arg(
+            long = "garbler-seed",
+            help = "File containing the 32-byte seed the garbler used in ot-send"
+        )]
+        garbler_seed_file: PathBuf,
+        /// Seed the evaluator used in ot-receive, to replay its side too
+        #[arg(
+            long = "evaluator-seed",
+            requires = "ot_result_file",
+            help = "File containing the 32-byte seed the evaluator used in ot-receive, to replay its side too"
+        )]
+        evaluator_seed_file: Option<PathBuf>,
+        /// OT result the evaluator obtained
+        #[arg(
             long = "ot-result",
-            help = "OT simulation results JSON file"
+            requires = "evaluator_seed_file",
+            help = "OT result file (JSON or binary) the evaluator obtained in ot-receive"
         )]
-        ot_result_file: PathBuf,
-        /// Garbled tables binary file
+        ot_result_file: Option<PathBuf>,
+    },
+    /// Garble a circuit for a connected evaluator and run the whole session (garbler side)
+    Serve {
+        /// Input circuit file, or - to read it from stdin
+        input: PathBuf,
+        /// Address to listen on for the evaluator
         #[arg(
-            short = 'g',
-            long = "garbled-tables",
-            help = "Garbled tables binary file from garbler"
+            short = 'l',
+            long = "listen",
+            default_value = "127.0.0.1:7741",
+            help = "Address to listen on for the evaluator: host:port, ws://host:port/path, unix:<path>, or - for stdin/stdout"
         )]
-        garbled_tables_file: PathBuf,
-        /// Output file for evaluation results (default: <input>.eval.json)
+        listen: String,
+        /// File containing seed for garbling and the garbler's OT secrets
+        #[arg(
+            short = 's',
+            long = "seed-file",
+            help = "File containing a 32-byte seed for garbling and the garbler's OT secrets"
+        )]
+        seed_file: PathBuf,
+        /// Input wires owned by the garbler, e.g. `0..64,128`
+        #[arg(
+            long = "garbler-inputs",
+            conflicts_with = "input_partition_file",
+            help = "Input wire ranges owned by the garbler, e.g. '0..64,128' (others go through OT)"
+        )]
+        garbler_inputs: Option<String>,
+        /// JSON file assigning input wires to the garbler
+        #[arg(
+            long = "input-partition",
+            help = "JSON input partition file listing the garbler's input wire ranges"
+        )]
+        input_partition_file: Option<PathBuf>,
+        /// The garbler's input bits as a JSON object of wire ID to boolean
+        #[arg(
+            short = 'i',
+            long = "inputs",
+            help = "JSON file mapping the garbler's input wire IDs to booleans"
+        )]
+        inputs_file: Option<PathBuf>,
+        /// OT security level, which must match the evaluator's
+        #[arg(
+            long = "security",
+            default_value_t = OtSecurity::SemiHonest,
+            help = "OT security level: semi-honest or malicious (adds the KOS check)"
+        )]
+        security: OtSecurity,
+        /// OT extension backend, which must match the evaluator's
+        #[arg(
+            long = "extension",
+            default_value_t = OtExtensionBackend::Iknp,
+            help = "OT extension backend: iknp or softspoken-K with K in 1, 2, 4, 8 (sends K times less than IKNP)"
+        )]
+        extension: OtExtensionBackend,
+        /// Write session statistics to this JSON file
+        #[arg(
+            long = "stats-json",
+            help = "Write the outputs and session statistics (tables, bytes exchanged, timing) to a JSON file"
+        )]
+        stats_json: Option<PathBuf>,
+        #[command(flatten)]
+        tls: TlsServerArgs,
+        #[command(flatten)]
+        link: LinkArgs,
+    },
+    /// Evaluate a circuit garbled by a garbler running serve (evaluator side)
+    Connect {
+        /// Input circuit file, which must match the garbler's, or - to read it from stdin
+        input: PathBuf,
+        /// Address of the garbler running serve
+        #[arg(
+            short = 'c',
+            long = "connect",
+            default_value = "127.0.0.1:7741",
+            help = "Address of the garbler running serve: host:port, ws://host:port/path, unix:<path>, or - for stdin/stdout"
+        )]
+        connect: String,
+        /// The evaluator's input bits as a JSON object of wire ID to boolean
+        #[arg(
+            short = 'i',
+            long = "inputs",
+            help = "JSON file mapping the evaluator's input wire IDs to booleans"
+        )]
+        inputs_file: Option<PathBuf>,
+        /// File containing seed for the evaluator's OT secrets
+        #[arg(
+            short = 's',
+            long = "seed-file",
+            help = "File containing a 32-byte seed for the evaluator's OT secrets"
+        )]
+        seed_file: PathBuf,
+        /// OT security level, which must match the garbler's
+        #[arg(
+            long = "security",
+            default_value_t = OtSecurity::SemiHonest,
+            help = "OT security level: semi-honest or malicious (adds the KOS check)"
+        )]
+        security: OtSecurity,
+        /// OT extension backend, which must match the garbler's
+        #[arg(
+            long = "extension",
+            default_value_t = OtExtensionBackend::Iknp,
+            help = "OT extension backend: iknp or softspoken-K with K in 1, 2, 4, 8 (sends K times less than IKNP)"
+        )]
+        extension: OtExtensionBackend,
+        /// Output file for the decoded evaluation result
         #[arg(
             short = 'o',
             long = "output",
-            help = "Output file for evaluation results"
+            help = "Write the decoded evaluation result to this JSON file"
         )]
         output: Option<PathBuf>,
+        /// Write session statistics to this JSON file
+        #[arg(
+            long = "stats-json",
+            help = "Write the outputs and session statistics (tables, bytes exchanged, timing) to a JSON file"
+        )]
+        stats_json: Option<PathBuf>,
+        #[command(flatten)]
+        tls: TlsClientArgs,
+        #[command(flatten)]
+        link: LinkArgs,
     },
-    /// Simulate memory usage during circuit execution
-    MemorySimulation {
-        /// Path to the Bristol circuit file
-        #[arg(help = "Bristol circuit file to process")]
-        file: PathBuf,
-        /// Binary file containing wire usage analysis
+    /// Garble a circuit for an evaluator calling the gRPC service (garbler side)
+    RpcServe {
+        /// Input circuit file, or - to read it from stdin
+        input: PathBuf,
+        /// Address to serve the gRPC service on
         #[arg(
-            short = 'w',
-            long = "wire-analysis",
-            help = "Binary file containing wire usage analysis"
+            short = 'l',
+            long = "listen",
+            default_value = "127.0.0.1:7742",
+            help = "Address to serve the gRPC service on"
         )]
-        wire_analysis_file: PathBuf,
-        /// Output file for simulation results (default: <input>.memory.csv)
+        listen: String,
+        /// File containing seed for garbling and the garbler's OT secrets
+        #[arg(
+            short = 's',
+            long = "seed-file",
+            help = "File containing a 32-byte seed for garbling and the garbler's OT secrets"
+        )]
+        seed_file: PathBuf,
+        /// Input wires owned by the garbler, e.g. `0..64,128`
+        #[arg(
+            long = "garbler-inputs",
+            conflicts_with = "input_partition_file",
+            help = "Input wire ranges owned by the garbler, e.g. '0..64,128' (others go through OT)"
+        )]
+        garbler_inputs: Option<String>,
+        /// JSON file assigning input wires to the garbler
+        #[arg(
+            long = "input-partition",
+            help = "JSON input partition file listing the garbler's input wire ranges"
+        )]
+        input_partition_file: Option<PathBuf>,
+        /// The garbler's input bits as a JSON object of wire ID to boolean
+        #[arg(
+            short = 'i',
+            long = "inputs",
+            help = "JSON file mapping the garbler's input wire IDs to booleans"
+        )]
+        inputs_file: Option<PathBuf>,
+        /// OT security level, which must match the evaluator's
+        #[arg(
+            long = "security",
+            default_value_t = OtSecurity::SemiHonest,
+            help = "OT security level: semi-honest or malicious (adds the KOS check)"
+        )]
+        security: OtSecurity,
+        /// OT extension backend, which must match the evaluator's
+        #[arg(
+            long = "extension",
+            default_value_t = OtExtensionBackend::Iknp,
+            help = "OT extension backend: iknp or softspoken-K with K in 1, 2, 4, 8 (sends K times less than IKNP)"
+        )]
+        extension: OtExtensionBackend,
+    },
+    /// Evaluate a circuit garbled by a garbler running rpc-serve (evaluator side)
+    RpcConnect {
+        /// Input circuit file, which must match the garbler's, or - to read it from stdin
+        input: PathBuf,
+        /// URI of the garbler running rpc-serve
+        #[arg(
+            short = 'c',
+            long = "connect",
+            default_value = "http://127.0.0.1:7742",
+            help = "URI of the garbler running rpc-serve"
+        )]
+        connect: String,
+        /// The evaluator's input bits as a JSON object of wire ID to boolean
+        #[arg(
+            short = 'i',
+            long = "inputs",
+            help = "JSON file mapping the evaluator's input wire IDs to booleans"
+        )]
+        inputs_file: Option<PathBuf>,
+        /// File containing seed for the evaluator's OT secrets
+        #[arg(
+            short = 's',
+            long = "seed-file",
+            help = "File containing a 32-byte seed for the evaluator's OT secrets"
+        )]
+        seed_file: PathBuf,
+        /// OT security level, which must match the garbler's
+        #[arg(
+            long = "security",
+            default_value_t = OtSecurity::SemiHonest,
+            help = "OT security level: semi-honest or malicious (adds the KOS check)"
+        )]
+        security: OtSecurity,
+        /// OT extension backend, which must match the garbler's
+        #[arg(
+            long = "extension",
+            default_value_t = OtExtensionBackend::Iknp,
+            help = "OT extension backend: iknp or softspoken-K with K in 1, 2, 4, 8 (sends K times less than IKNP)"
+        )]
+        extension: OtExtensionBackend,
+        /// Output file for the evaluation result
         #[arg(
             short = 'o',
             long = "output",
-            help = "Output CSV file for memory simulation results"
+            help = "Write the evaluation result to this JSON file"
         )]
         output: Option<PathBuf>,
     },
-    /// Analyze single-use wire gate types
-    SingleUseAnalysis {
-        /// Path to the Bristol circuit file
-        #[arg(help = "Bristol circuit file to process")]
-        file: PathBuf,
-        /// Binary file containing wire usage analysis
+    /// Serve a garbled tables file to evaluators running fetch-tables (garbler side)
+    SendTables {
+        /// Garbled tables file to send
+        input: PathBuf,
+        /// Address to listen on for the evaluator
+        #[arg(
+            short = 'l',
+            long = "listen",
+            default_value = "127.0.0.1:7741",
+            help = "Address to listen on for the evaluator: host:port, ws://host:port/path, unix:<path>, or - for stdin/stdout"
+        )]
+        listen: String,
+        /// Write transfer statistics to this JSON file
+        #[arg(
+            long = "stats-json",
+            help = "Write the statistics of the completed transfer (tables, bytes sent, timing) to a JSON file"
+        )]
+        stats_json: Option<PathBuf>,
+        #[command(flatten)]
+        tls: TlsServerArgs,
+        #[command(flatten)]
+        link: LinkArgs,
+    },
+    /// Download a garbled tables file from a garbler running send-tables, resuming if interrupted (evaluator side)
+    FetchTables {
+        /// Where to write the garbled tables file
+        output: PathBuf,
+        /// Address of the garbler running send-tables
+        #[arg(
+            short = 'c',
+            long = "connect",
+            default_value = "127.0.0.1:7741",
+            help = "Address of the garbler running send-tables: host:port, ws://host:port/path, unix:<path>, or - for stdin/stdout"
+        )]
+        connect: String,
+        /// Reconnections after a dropped connection before giving up
+        #[arg(
+            long = "retries",
+            default_value_t = 5,
+            help = "Reconnect and resume this many times after the connection drops"
+        )]
+        retries: u32,
+        /// Seconds to wait before reconnecting
+        #[arg(
+            long = "retry-delay",
+            default_value_t = 2,
+            help = "Seconds to wait before reconnecting"
+        )]
+        retry_delay: u64,
+        /// Write transfer statistics to this JSON file
+        #[arg(
+            long = "stats-json",
+            help = "Write the statistics of the final transfer (tables, bytes received, timing) to a JSON file"
+        )]
+        stats_json: Option<PathBuf>,
+        #[command(flatten)]
+        tls: TlsClientArgs,
+        #[command(flatten)]
+        link: LinkArgs,
+    },
+    /// Write the evaluator's OT choices for a split OT simulation (evaluator side)
+    OtChoose {
+        /// The evaluator's input bits as a JSON object of wire ID to boolean
+        #[arg(
+            short = 'i',
+            long = "inputs",
+            help = "JSON file mapping the evaluator's input wire IDs to booleans"
+        )]
+        inputs_file: PathBuf,
+        /// File containing seed for the pads masking the chosen labels
+        #[arg(
+            short = 's',
+            long = "seed-file",
+            help = "File containing a 32-byte seed for the pads masking the chosen labels"
+        )]
+        seed_file: PathBuf,
+        /// Output file for the choices (default: `<inputs>.choices.json`)
+        #[arg(short = 'o', long = "output", help = "Output file for the OT choices")]
+        output: Option<PathBuf>,
+    },
+    /// Answer the evaluator's OT choices with masked labels (garbler side)
+    OtRespond {
+        /// Wire labels file from garbler output
         #[arg(
             short = 'w',
-            long = "wire-analysis",
-            help = "Binary file containing wire usage analysis"
+            long = "wire-labels",
+            help = "Wire labels file from garbler (JSON or binary)"
         )]
-        wire_analysis_file: PathBuf,
-        /// Output file for analysis results (default: <input>.single_use.json)
+        wire_labels_file: PathBuf,
+        /// Choices file from ot-choose
+        #[arg(short = 'c', long = "choices", help = "OT choices file from ot-choose")]
+        choices_file: PathBuf,
+        /// Encoded garbler inputs to send along with the labels
+        #[arg(
+            short = 'g',
+            long = "garbler-inputs",
+            help = "Encoded garbler inputs file (JSON or binary) from encode-inputs"
+        )]
+        garbler_inputs_file: Option<PathBuf>,
+        /// Output file for the sender message (default: `<labels>.ot-message.json`)
         #[arg(
             short = 'o',
             long = "output",
-            help = "Output JSON file for single-use analysis results"
+            help = "Output file for the OT sender message"
         )]
         output: Option<PathBuf>,
     },
-}
+    /// Unmask the garbler's OT answer into an OT result (evaluator side)
+    OtFinish {
+        /// Choices file from ot-choose
+        #[arg(short = 'c', long = "choices", help = "OT choices file from ot-choose")]
+        choices_file: PathBuf,
+        /// Sender message file from ot-respond
+        #[arg(
+            short = 'm',
+            long = "message",
+            help = "OT sender message file from ot-respond"
+        )]
+        message_file: PathBuf,
+        /// Output file for OT results (default: `<choices>.ot.json`, or `<choices>.ot.bin` with --binary)
+        #[arg(short = 'o', long = "output", help = "Output file for OT results")]
+        output: Option<PathBuf>,
+        /// Write OT results in the compact binary format instead of JSON
+        #[arg(
+            long = "binary",
+            help = "Write OT results in the compact binary format instead of JSON"
+        )]
+        binary: bool,
+    },
+    /// Encode the garbler's own input bits as wire labels for the evaluator
+    EncodeInputs {
+        /// Wire labels file from garbler output
+        #[arg(
+            short = 'w',
+            long = "wire-labels",
+            help = "Wire labels file from garbler (JSON or binary)"
+        )]
+        wire_labels_file: PathBuf,
+        /// The garbler's input bits as a JSON object of wire ID to boolean
+        #[arg(
+            short = 'i',
+            long = "inputs",
+            help = "JSON file mapping the garbler's input wire IDs to booleans"
+        )]
+        inputs_file: PathBuf,
+        /// Output file for the encoded inputs (default: `<labels>.inputs.json`)
+        #[arg(short = 'o', long = "output", help = "Output file for encoded inputs")]
+        output: Option<PathBuf>,
+        /// Write encoded inputs in the compact binary format instead of JSON
+        #[arg(
+            long = "binary",
+            help = "Write encoded inputs in the compact binary format instead of JSON"
+        )]
+        binary: bool,
+    },
+    /// Evaluate a garbled circuit using OT-selected input labels
+    Evaluate {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
+        file: PathBuf,
+        /// Binary file containing wire usage analysis (computed on the fly if omitted)
+        #[arg(
+            short = 'w',
+            long = "wire-analysis",
+            help = "Binary file containing wire usage analysis; analyzed on the fly if omitted"
+        )]
+        wire_analysis_file: Option<PathBuf>,
+        /// Rewrite NAND, OR, NOR, XNOR and INV gates into XOR and AND as the circuit is read
+        #[arg(
+            long = "lower-gates",
+            conflicts_with_all = ["wire_analysis_file", "layers_file"],
+            help = "Rewrite NAND, OR, NOR, XNOR and INV gates into XOR and AND while reading the circuit"
+        )]
+        lower_gates: bool,
+        /// OT simulation results file
+        #[arg(
+            short = 't',
+            long = "ot-result",
+            help = "OT results file (JSON or binary)"
+        )]
+        ot_result_file: PathBuf,
+        /// Garbled tables binary file, or `-` to stream them from stdin
+        #[arg(
+            short = 'g',
+            long = "garbled-tables",
+            help = "Garbled tables binary file from garbler, or '-' to read from stdin"
+        )]
+        garbled_tables_file: PathBuf,
+        /// Memory-map the garbled tables file instead of streaming it
+        #[arg(long = "mmap", help = "Memory-map the garbled tables file")]
+        mmap: bool,
+        /// Read garbled tables ahead on a background thread
+        #[arg(
+            long = "prefetch",
+            conflicts_with = "mmap",
+            help = "Read garbled tables ahead on a background thread"
+        )]
+        prefetch: bool,
+        /// Layer analysis file; evaluates each layer in parallel when given
+        #[arg(
+            long = "layers",
+            help = "Layer analysis file for parallel layered evaluation"
+        )]
+        layers_file: Option<PathBuf>,
+        /// Decoding information file to check output labels against and decode them with
+        #[arg(
+            long = "check",
+            help = "Decoding information JSON file to check output labels against and decode them into bits"
+        )]
+        check_file: Option<PathBuf>,
+        /// Write evaluation statistics to this JSON file
+        #[arg(
+            long = "stats-json",
+            help = "Write evaluation statistics (gates, timing, memory) to a JSON file"
+        )]
+        stats_json: Option<PathBuf>,
+        /// Write results in the compact binary format instead of JSON
+        #[arg(
+            long = "binary",
+            help = "Write results in the compact binary format instead of JSON"
+        )]
+        binary: bool,
+        /// Memory budget for wire labels, e.g. `512M` or `8G`
+        #[arg(
+            long = "max-memory",
+            value_parser = parse_byte_size,
+            conflicts_with = "layers_file",
+            help = "Memory budget for wire labels, e.g. '512M' or '8G'; less recently used labels spill to a temp file"
+        )]
+        max_memory: Option<u64>,
+        /// Output file for evaluation results (default: <input>.eval.json, or <input>.eval.bin with --binary)
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Output file for evaluation results"
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Evaluate a circuit in the clear on known input bits
+    PlainEvaluate {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
+        file: PathBuf,
+        /// OT simulation results to take the input bits from
+        #[arg(
+            short = 't',
+            long = "ot-result",
+            conflicts_with = "inputs_file",
+            required_unless_present = "inputs_file",
+            help = "OT results file (JSON or binary) to take input bits from"
+        )]
+        ot_result_file: Option<PathBuf>,
+        /// Wire labels file telling the bits of the garbler's input labels in the OT result
+        #[arg(
+            short = 'w',
+            long = "wire-labels",
+            requires = "ot_result_file",
+            help = "Wire labels file from garbler (JSON or binary), to read the bits of garbler inputs in the OT result"
+        )]
+        wire_labels_file: Option<PathBuf>,
+        /// Input bits as a JSON object of wire ID to boolean
+        #[arg(
+            short = 'i',
+            long = "inputs",
+            help = "JSON file mapping input wire IDs to booleans"
+        )]
+        inputs_file: Option<PathBuf>,
+        /// Evaluation results to compare against
+        #[arg(
+            long = "compare",
+            help = "Decoded evaluation results file (JSON or binary) to compare output bits against"
+        )]
+        compare_file: Option<PathBuf>,
+        /// Output file for plain results (default: <input>.plain.json)
+        #[arg(short = 'o', long = "output", help = "Output file for plain results")]
+        output: Option<PathBuf>,
+    },
+    /// Export output decoding information from the garbler's wire labels
+    DecodingInfo {
+        /// Wire labels file from garbler output
+        #[arg(
+            short = 'w',
+            long = "wire-labels",
+            help = "Wire labels file from garbler (JSON or binary)"
+        )]
+        wire_labels_file: PathBuf,
+        /// Output file for decoding information (default: <input>.decoding.json)
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Output file for decoding information"
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Decode evaluation results into bit values
+    Decode {
+        /// Evaluation results file (JSON or binary)
+        #[arg(
+            short = 'e',
+            long = "eval-result",
+            help = "Evaluation results file (JSON or binary)"
+        )]
+        eval_result_file: PathBuf,
+        /// Decoding information file from the garbler
+        #[arg(
+            short = 'd',
+            long = "decoding-info",
+            help = "Decoding information JSON file from garbler"
+        )]
+        decoding_info_file: PathBuf,
+        /// Output file for decoded results (default: <input>.decoded.json)
+        #[arg(short = 'o', long = "output", help = "Output file for decoded results")]
+        output: Option<PathBuf>,
+        /// Output buses to assemble into integers
+        #[arg(
+            short = 'b',
+            long = "bus",
+            help = "Output bus to print as an integer: name=first..last[:le|:be]"
+        )]
+        output_buses: Vec<Bus>,
+    },
+    /// Verify garbled tables by decrypting them with both labels of every input
+    Verify {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
+        file: PathBuf,
+        /// Binary file containing wire usage analysis
+        #[arg(
+            short = 'w',
+            long = "wire-analysis",
+            help = "Binary file containing wire usage analysis"
+        )]
+        wire_analysis_file: PathBuf,
+        /// Wire labels file from garbler output
+        #[arg(
+            short = 'l',
+            long = "wire-labels",
+            help = "Wire labels file from garbler (JSON or binary)"
+        )]
+        wire_labels_file: PathBuf,
+        /// Garbled tables binary file
+        #[arg(
+            short = 'g',
+            long = "garbled-tables",
+            help = "Garbled tables binary file from garbler"
+        )]
+        garbled_tables_file: PathBuf,
+    },
+    /// Garble a circuit with conditionals, stacking the tables of their branches
+    GarbleStacked {
+        /// Path to the stacked circuit file
+        #[arg(help = "Stacked circuit file: Bristol gates, COND lines and BRANCH sections")]
+        file: PathBuf,
+        /// File containing seed for the garbling process
+        #[arg(
+            short = 's',
+            long = "seed-file",
+            help = "File containing seed for the garbling process"
+        )]
+        seed_file: PathBuf,
+        /// Output file for the stacked material (default: <input>.material)
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Output file for the stacked material"
+        )]
+        output: Option<PathBuf>,
+        /// Input wires owned by the garbler, e.g. `0..64,128`
+        #[arg(
+            long = "garbler-inputs",
+            help = "Input wire ranges owned by the garbler, e.g. '0..64,128' (others go through OT)"
+        )]
+        garbler_inputs: Option<String>,
+    },
+    /// Evaluate a circuit with conditionals garbled by garble-stacked
+    EvaluateStacked {
+        /// Path to the stacked circuit file
+        #[arg(help = "Stacked circuit file: Bristol gates, COND lines and BRANCH sections")]
+        file: PathBuf,
+        /// OT simulation results file
+        #[arg(
+            short = 't',
+            long = "ot-result",
+            help = "OT results file (JSON or binary)"
+        )]
+        ot_result_file: PathBuf,
+        /// Stacked material file from the garbler
+        #[arg(
+            short = 'g',
+            long = "garbled-material",
+            help = "Stacked material file from garbler"
+        )]
+        material_file: PathBuf,
+        /// Output file for evaluation results (default: <input>.eval.json)
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Output file for evaluation results"
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Simulate memory usage during circuit execution
+    MemorySimulation {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
+        file: PathBuf,
+        /// Binary file containing wire usage analysis
+        #[arg(
+            short = 'w',
+            long = "wire-analysis",
+            help = "Binary file containing wire usage analysis"
+        )]
+        wire_analysis_file: PathBuf,
+        /// Output file for simulation results (default: <input>.memory.csv)
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Output CSV file for memory simulation results"
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Analyze single-use wire gate types
+    SingleUseAnalysis {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
+        file: PathBuf,
+        /// Binary file containing wire usage analysis
+        #[arg(
+            short = 'w',
+            long = "wire-analysis",
+            help = "Binary file containing wire usage analysis"
+        )]
+        wire_analysis_file: PathBuf,
+        /// Output file for analysis results (default: <input>.single_use.json)
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Output JSON file for single-use analysis results"
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Count gates, analyze wire usage and single-use wires, and simulate memory in one pass
+    Analyze {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
+        file: PathBuf,
+        /// Path prefix of the output files (default: the input path)
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Path prefix of the output files, each getting the extension of its analysis"
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Find the gate writing and the gate last reading every wire, and the wires held at the peak
+    WireLifetime {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
+        file: PathBuf,
+        /// Path prefix of the output files (default: the input path)
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Path prefix of the lifetimes file (.lifetimes) and its CSV summary (.lifetimes.csv)"
+        )]
+        output: Option<PathBuf>,
+        /// Number of wires held at the peak to print
+        #[arg(
+            long = "peak-wires",
+            default_value_t = 10,
+            help = "Number of wires held at the peak to print, longest held first"
+        )]
+        peak_wires: usize,
+    },
+    /// Convert a circuit from another toolchain's format into a Bristol circuit file
+    Import {
+        /// Path to the circuit to convert
+        #[arg(help = "Circuit file to convert, or - to read it from stdin")]
+        input: PathBuf,
+        /// Format of the circuit to convert
+        #[arg(
+            short = 'f',
+            long = "format",
+            help = "Format of the input circuit: scale-mamba, aiger (.aag or .aig), blif or json"
+        )]
+        format: ImportFormat,
+        /// Output Bristol circuit file
+        #[arg(short = 'o', long = "output", help = "Output Bristol circuit file")]
+        output: PathBuf,
+    },
+    /// Convert a Bristol circuit to the compact binary format, or a binary circuit back to Bristol
+    Convert {
+        /// Path to the circuit file
+        #[arg(help = "Bristol or binary circuit file to convert, or - to read it from stdin")]
+        file: PathBuf,
+        /// Output file (default: <input>.bin for Bristol input, <input>.bristol for binary input)
+        #[arg(short = 'o', long = "output", help = "Output circuit file")]
+        output: Option<PathBuf>,
+        /// Also write a gate index next to the binary circuit
+        #[arg(
+            long = "index",
+            help = "Also write a gate index for seeking to <output>.idx (Bristol input only)"
+        )]
+        index: bool,
+    },
+    /// Print the header of a binary circuit and a range of its gates
+    Inspect {
+        /// Path to the binary circuit file
+        #[arg(help = "Binary circuit file to inspect")]
+        file: PathBuf,
+        /// First gate to print
+        #[arg(long = "gate", default_value = "0", help = "First gate to print")]
+        gate: u64,
+        /// Number of gates to print
+        #[arg(
+            short = 'n',
+            long = "count",
+            default_value = "10",
+            help = "Number of gates to print"
+        )]
+        count: u64,
+        /// Gate index of the file (default: <input>.idx, if it exists)
+        #[arg(
+            long = "index",
+            help = "Gate index to seek with (default: <input>.idx, if it exists)"
+        )]
+        index: Option<PathBuf>,
+    },
+    /// Print the canonical fingerprint of a circuit
+    Hash {
+        /// Path to the circuit file
+        #[arg(help = "Bristol or binary circuit file to hash, or - to read it from stdin")]
+        file: PathBuf,
+        /// Garbled tables to check against the circuit
+        #[arg(
+            long = "tables",
+            help = "Also check that these garbled tables (file or shard manifest) were garbled for the circuit"
+        )]
+        tables: Option<PathBuf>,
+    },
+    /// Write a Bristol circuit as structured JSON
+    Export {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to process, or - to read it from stdin")]
+        file: PathBuf,
+        /// Output file for the JSON circuit (default: <input>.circuit.json)
+        #[arg(short = 'o', long = "output", help = "Output JSON circuit file")]
+        output: Option<PathBuf>,
+    },
+    /// Drop unused wires and renumber the rest into a gap-free range
+    Compact {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to compact, or - to read it from stdin")]
+        file: PathBuf,
+        /// Output Bristol circuit file (default: <input>.compact.bristol)
+        #[arg(short = 'o', long = "output", help = "Output Bristol circuit file")]
+        output: Option<PathBuf>,
+        /// Output file for the new input and output wire IDs (default: <input>.remap.json)
+        #[arg(
+            long = "remap",
+            help = "Output JSON file mapping old input and output wire IDs to new ones"
+        )]
+        remap: Option<PathBuf>,
+    },
+    /// Stitch two circuits into one, feeding outputs of the first into inputs of the second
+    Compose {
+        /// Path to the circuit computed first
+        #[arg(help = "Bristol circuit computed first, or - to read it from stdin")]
+        first: PathBuf,
+        /// Path to the circuit reading outputs of the first
+        #[arg(help = "Bristol circuit reading outputs of the first")]
+        second: PathBuf,
+        /// Wiring spec connecting the two circuits
+        #[arg(
+            short = 'w',
+            long = "wiring",
+            help = "JSON wiring spec connecting outputs of the first circuit to inputs of the second"
+        )]
+        wiring: PathBuf,
+        /// Output Bristol circuit file
+        #[arg(short = 'o', long = "output", help = "Output Bristol circuit file")]
+        output: PathBuf,
+        /// Output file for the new input and output wire IDs (default: `<output>.remap.json`)
+        #[arg(
+            long = "remap",
+            help = "Output JSON file mapping the input and output wire IDs of both circuits to new ones"
+        )]
+        remap: Option<PathBuf>,
+    },
+    /// Remove gates whose outputs never reach a primary output
+    Prune {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to prune, or - to read it from stdin")]
+        file: PathBuf,
+        /// Binary file containing wire usage analysis (computed on the fly if omitted)
+        #[arg(
+            short = 'w',
+            long = "wire-analysis",
+            help = "Binary file containing wire usage analysis; analyzed on the fly if omitted"
+        )]
+        wire_analysis_file: Option<PathBuf>,
+        /// Output Bristol circuit file (default: <input>.pruned.bristol)
+        #[arg(short = 'o', long = "output", help = "Output Bristol circuit file")]
+        output: Option<PathBuf>,
+        /// Output file for the eliminated gates (default: <input>.dead_gates.json)
+        #[arg(
+            long = "report",
+            help = "Output JSON file listing the eliminated gates"
+        )]
+        report: Option<PathBuf>,
+    },
+    /// Rewrite NAND, OR, NOR, XNOR and INV gates into the XOR/AND basis
+    Lower {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to lower, or - to read it from stdin")]
+        file: PathBuf,
+        /// Output Bristol circuit file (default: <input>.lowered.bristol)
+        #[arg(short = 'o', long = "output", help = "Output Bristol circuit file")]
+        output: Option<PathBuf>,
+    },
+    /// Write a generated standard component as a Bristol circuit file
+    Generate {
+        /// Component to generate
+        #[arg(
+            help = "Component to generate: ripple-adder, lookahead-adder, multiplier, comparator, equality or mux"
+        )]
+        component: Component,
+        /// Operand width in bits, or number of select bits for mux
+        #[arg(
+            short = 'w',
+            long = "width",
+            default_value_t = 64,
+            help = "Operand width in bits, or number of select bits for mux"
+        )]
+        width: u64,
+        /// Output Bristol circuit file
+        #[arg(short = 'o', long = "output", help = "Output Bristol circuit file")]
+        output: PathBuf,
+    },
+    /// Write a random circuit for benchmarks and fuzzing
+    Random {
+        /// Number of gates
+        #[arg(
+            short = 'n',
+            long = "gates",
+            default_value_t = 1_000_000,
+            help = "Number of gates"
+        )]
+        gates: u64,
+        /// Number of primary input wires
+        #[arg(
+            long = "inputs",
+            default_value_t = 128,
+            help = "Number of primary input wires"
+        )]
+        inputs: u64,
+        /// Number of primary output wires
+        #[arg(
+            long = "outputs",
+            default_value_t = 128,
+            help = "Number of primary output wires"
+        )]
+        outputs: u64,
+        /// Fraction of AND gates
+        #[arg(
+            long = "and-ratio",
+            default_value_t = 0.3,
+            help = "Fraction of AND gates; the others are XOR gates"
+        )]
+        and_ratio: f64,
+        /// Number of gate layers
+        #[arg(
+            long = "depth",
+            default_value_t = 1000,
+            help = "Number of gate layers, the longest path through the circuit"
+        )]
+        depth: u64,
+        /// How gates pick the wires they read
+        #[arg(
+            long = "fan-out",
+            default_value = "uniform",
+            help = "How gates pick the wires they read: uniform, local:<window> or skewed:<exponent>"
+        )]
+        fan_out: FanOut,
+        /// Seed of the generator
+        #[arg(long = "seed", default_value_t = 0, help = "Seed of the generator")]
+        seed: u64,
+        /// Write the compact binary format instead of Bristol text
+        #[arg(
+            long = "binary",
+            help = "Write the compact binary format instead of Bristol text"
+        )]
+        binary: bool,
+        /// Output circuit file
+        #[arg(short = 'o', long = "output", help = "Output circuit file")]
+        output: PathBuf,
+    },
+    /// Check that every gate comes after the gates writing its inputs
+    CheckOrder {
+        /// Path to the Bristol circuit file
+        #[arg(help = "Bristol circuit file to check, or - to read it from stdin")]
+        file: PathBuf,
+        /// Write the circuit with its gates sorted topologically
+        #[arg(
+            long = "reorder",
+            help = "Write the circuit with its gates sorted into topological order"
+        )]
+        reorder: bool,
+        /// Output Bristol circuit file (default: <input>.sorted.bristol)
+        #[arg(
+            short = 'o',
+            long = "output",
+            requires = "reorder",
+            help = "Output Bristol circuit file for --reorder"
+        )]
+        output: Option<PathBuf>,
+    },
+}
+
+impl Commands {
+    /// The circuit file the command reads, if any, and whether it reads it
+    /// in a single pass, so that a circuit on stdin can be streamed
+    fn circuit_mut(&mut self) -> Option<(&mut PathBuf, bool)> {
+        match self {
+            Commands::Count { file, threads } => Some((file, threads.is_none())),
+            Commands::WireAnalysis {
+                file,
+                threads,
+                fan_out,
+                ..
+            } => Some((file, threads.is_none() && !*fan_out)),
+            Commands::LayerAnalysis { file, .. }
+            | Commands::LayerProfile { file, .. }
+            | Commands::AndDepth { file, .. }
+            | Commands::PlainEvaluate { file, .. }
+            | Commands::Verify { file, .. }
+            | Commands::MemorySimulation { file, .. }
+            | Commands::SingleUseAnalysis { file, .. }
+            | Commands::Analyze { file, .. }
+            | Commands::WireLifetime { file, .. }
+            | Commands::Import { input: file, .. }
+            | Commands::Convert { file, .. }
+            | Commands::Hash { file, .. }
+            | Commands::Export { file, .. }
+            | Commands::Compact { file, .. }
+            | Commands::Compose { first: file, .. }
+            | Commands::Prune { file, .. } => Some((file, true)),
+            Commands::Garble {
+                file,
+                wire_analysis_file,
+                no_wire_analysis,
+                lower_gates,
+                shards,
+                ..
+            } => Some((
+                file,
+                (wire_analysis_file.is_some() || *no_wire_analysis)
+                    && shards.is_none()
+                    && !*lower_gates,
+            )),
+            Commands::Evaluate {
+                file,
+                wire_analysis_file,
+                lower_gates,
+                ..
+            } => Some((file, wire_analysis_file.is_some() && !*lower_gates)),
+            Commands::Serve { input, .. }
+            | Commands::Connect { input, .. }
+            | Commands::RpcServe { input, .. }
+            | Commands::RpcConnect { input, .. } => Some((input, false)),
+            Commands::Lower { file, .. } => Some((file, false)),
+            Commands::CheckOrder { file, reorder, .. } => Some((file, !*reorder)),
+            _ => None,
+        }
+    }
+}
+
+/// Send the library's tracing events to stderr
+///
+/// `filter` takes precedence over `RUST_LOG`; without either only warnings
+/// are shown so the default output is unchanged.
+fn init_tracing(filter: Option<&str>) -> Result<()> {
+    let filter = match filter {
+        Some(filter) => EnvFilter::try_new(filter)?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+    Ok(())
+}
+
+/// TLS options of the listening party
+#[derive(clap::Args, Debug)]
+struct TlsServerArgs {
+    /// Certificate chain presented to the evaluator
+    #[arg(
+        long = "tls-cert",
+        requires = "tls_key",
+        help = "PEM certificate chain to present to the evaluator; enables TLS"
+    )]
+    tls_cert: Option<PathBuf>,
+    /// Private key of the certificate
+    #[arg(
+        long = "tls-key",
+        requires = "tls_cert",
+        help = "PEM private key of --tls-cert"
+    )]
+    tls_key: Option<PathBuf>,
+    /// Roots the evaluator's certificate must chain to
+    #[arg(
+        long = "tls-client-ca",
+        requires = "tls_cert",
+        conflicts_with = "tls_client_pins",
+        help = "Require an evaluator certificate issued by a root in this PEM file"
+    )]
+    tls_client_ca: Option<PathBuf>,
+    /// Fingerprints of the accepted evaluator certificates
+    #[arg(
+        long = "tls-client-pin",
+        requires = "tls_cert",
+        help = "Require an evaluator certificate with this SHA-256 fingerprint (repeatable)"
+    )]
+    tls_client_pins: Vec<String>,
+}
+
+/// TLS options of the connecting party
+#[derive(clap::Args, Debug)]
+struct TlsClientArgs {
+    /// Roots the garbler's certificate must chain to
+    #[arg(
+        long = "tls-ca",
+        conflicts_with = "tls_pins",
+        help = "Connect over TLS, trusting a garbler certificate issued by a root in this PEM file"
+    )]
+    tls_ca: Option<PathBuf>,
+    /// Fingerprints of the accepted garbler certificates
+    #[arg(
+        long = "tls-pin",
+        help = "Connect over TLS, trusting the garbler certificate with this SHA-256 fingerprint (repeatable)"
+    )]
+    tls_pins: Vec<String>,
+    /// Name the garbler's certificate must carry
+    #[arg(
+        long = "tls-server-name",
+        help = "Name the garbler certificate must carry with --tls-ca (default: host of the address)"
+    )]
+    tls_server_name: Option<String>,
+    /// Certificate chain presented to a garbler that requires one
+    #[arg(
+        long = "tls-cert",
+        requires = "tls_key",
+        help = "PEM certificate chain to present to a garbler requiring client certificates"
+    )]
+    tls_cert: Option<PathBuf>,
+    /// Private key of the certificate
+    #[arg(
+        long = "tls-key",
+        requires = "tls_cert",
+        help = "PEM private key of --tls-cert"
+    )]
+    tls_key: Option<PathBuf>,
+}
+
+/// Bandwidth options of the streaming commands
+#[derive(clap::Args, Debug)]
+struct LinkArgs {
+    /// Send rate limit in bytes per second
+    #[arg(
+        long = "max-bandwidth",
+        value_parser = parse_byte_size,
+        help = "Send at most this many bytes per second, e.g. 20M, so the transfer leaves room on a shared link"
+    )]
+    max_bandwidth: Option<u64>,
+    /// Seconds between live transfer statistics
+    #[arg(
+        long = "stats-interval",
+        default_value_t = 10,
+        help = "Print the transfer rates to stderr every this many seconds (0 disables)"
+    )]
+    stats_interval: u64,
+    /// Write link statistics to this JSON file
+    #[arg(
+        long = "link-stats-json",
+        help = "Write the final link statistics (bytes, MB/s, time throttled, TCP retransmits) to a JSON file"
+    )]
+    link_stats_json: Option<PathBuf>,
+}
+
+/// Byte stream to the other party: TCP, a WebSocket, a Unix socket or stdin/stdout, with or without TLS
+trait Transport: Read + Write {}
+
+impl<T: Read + Write> Transport for T {}
+
+/// Connection to the other party
+struct Peer {
+    stream: Box<dyn Transport>,
+    /// The TCP socket underneath, for its kernel statistics
+    tcp: Option<TcpStream>,
+}
+
+impl Peer {
+    fn tcp(
+        stream: TcpStream,
+        secure: impl FnOnce(TcpStream) -> Result<Box<dyn Transport>>,
+    ) -> Result<Self> {
+        let tcp = stream.try_clone()?;
+        Ok(Self {
+            stream: secure(stream)?,
+            tcp: Some(tcp),
+        })
+    }
+
+    fn other(stream: Box<dyn Transport>) -> Self {
+        Self { stream, tcp: None }
+    }
+}
+
+/// Channel over a connection opened with [`LinkArgs`]
+type LinkChannel = Channel<MeteredStream<Box<dyn Transport>>>;
+
+/// Monitoring of a connection opened with [`LinkArgs`]
+///
+/// Prints the send and receive rates every `--stats-interval` seconds until
+/// it is finished or dropped.
+struct Link {
+    meter: Arc<TransferMeter>,
+    tcp: Option<TcpStream>,
+    link_stats_json: Option<PathBuf>,
+    /// Dropping the sender stops the live statistics
+    _stop_reporter: Option<mpsc::Sender<()>>,
+}
+
+impl Link {
+    /// Wrap the connection in a channel with the bandwidth limit and live
+    /// statistics, and negotiate the framing version
+    fn open(peer: Peer, args: &LinkArgs) -> Result<(LinkChannel, Self)> {
+        let mut stream = MeteredStream::new(peer.stream);
+        if let Some(max_bandwidth) = args.max_bandwidth {
+            println!("Limiting the send rate to {} bytes/s", max_bandwidth);
+            stream = stream.with_max_bandwidth(max_bandwidth);
+        }
+        let meter = stream.meter();
+        let stop_reporter = (args.stats_interval > 0).then(|| {
+            let (stop, stopped) = mpsc::channel::<()>();
+            let interval = std::time::Duration::from_secs(args.stats_interval);
+            let meter = meter.clone();
+            std::thread::spawn(move || {
+                let mut last = meter.snapshot();
+                while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    let now = meter.snapshot();
+                    let secs = now.elapsed_secs - last.elapsed_secs;
+                    eprintln!(
+                        "[{:.0}s] sent {:.1} MB ({:.2} MB/s), received {:.1} MB ({:.2} MB/s)",
+                        now.elapsed_secs,
+                        now.bytes_sent as f64 / 1e6,
+                        (now.bytes_sent - last.bytes_sent) as f64 / 1e6 / secs,
+                        now.bytes_received as f64 / 1e6,
+                        (now.bytes_received - last.bytes_received) as f64 / 1e6 / secs,
+                    );
+                    last = now;
+                }
+            });
+            stop
+        });
+        let link = Self {
+            meter,
+            tcp: peer.tcp,
+            link_stats_json: args.link_stats_json.clone(),
+            _stop_reporter: stop_reporter,
+        };
+        let mut channel = Channel::new(stream);
+        channel.negotiate()?;
+        Ok((channel, link))
+    }
+
+    /// Stop the live statistics and print the final ones
+    fn finish(self) -> Result<LinkStats> {
+        let mut stats = self.meter.snapshot();
+        stats.retransmits = self.tcp.as_ref().and_then(tcp_retransmits);
+        print!(
+            "Link: sent {} bytes ({:.2} MB/s), received {} bytes ({:.2} MB/s) in {:.3}s",
+            stats.bytes_sent,
+            stats.send_mb_per_sec,
+            stats.bytes_received,
+            stats.receive_mb_per_sec,
+            stats.elapsed_secs
+        );
+        if stats.throttled_secs > 0.0 {
+            print!(", throttled {:.1}s", stats.throttled_secs);
+        }
+        match stats.retransmits {
+            Some(retransmits) => println!(", {} TCP retransmits", retransmits),
+            None => println!(),
+        }
+        if let Some(path) = &self.link_stats_json {
+            stats.save_json(path)?;
+            println!("Link statistics saved to: {}", path.display());
+        }
+        Ok(stats)
+    }
+}
+
+/// Segments the kernel retransmitted on a TCP connection so far
+#[cfg(target_os = "linux")]
+fn tcp_retransmits(stream: &TcpStream) -> Option<u64> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: tcp_info is plain integers, for which all zeros is valid
+    let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+    // SAFETY: getsockopt writes at most `len` bytes into `info`
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            (&mut info as *mut libc::tcp_info).cast(),
+            &mut len,
+        )
+    };
+    (result == 0).then_some(u64::from(info.tcpi_total_retrans))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn tcp_retransmits(_stream: &TcpStream) -> Option<u64> {
+    None
+}
+
+/// Address naming stdin/stdout as the connection to the other party
+const STDIO_ADDRESS: &str = "-";
+
+/// Prefix of addresses naming a Unix domain socket
+const UNIX_ADDRESS_PREFIX: &str = "unix:";
+
+/// Prefix of addresses naming a WebSocket endpoint
+const WEBSOCKET_ADDRESS_PREFIX: &str = "ws://";
+
+/// Where the other party is reached, parsed from `--listen` or `--connect`
+#[derive(Clone, Copy)]
+enum PeerAddress<'a> {
+    /// `host:port`
+    Tcp(&'a str),
+    /// `ws://host:port/path`: a WebSocket over TCP, e.g. through an HTTP proxy
+    WebSocket {
+        url: &'a str,
+        /// The `host:port` part of the URL
+        address: &'a str,
+    },
+    /// `unix:<path>`
+    Unix(&'a Path),
+    /// `-`: the process's stdin and stdout
+    Stdio,
+}
+
+impl<'a> PeerAddress<'a> {
+    fn parse(address: &'a str) -> Self {
+        if address == STDIO_ADDRESS {
+            PeerAddress::Stdio
+        } else if let Some(path) = address.strip_prefix(UNIX_ADDRESS_PREFIX) {
+            PeerAddress::Unix(Path::new(path))
+        } else if let Some(rest) = address.strip_prefix(WEBSOCKET_ADDRESS_PREFIX) {
+            PeerAddress::WebSocket {
+                url: address,
+                address: rest.split_once('/').map_or(rest, |(address, _)| address),
+            }
+        } else {
+            PeerAddress::Tcp(address)
+        }
+    }
+}
+
+/// Protocol stream over stdin and the original stdout
+struct StdioTransport {
+    input: std::io::Stdin,
+    output: File,
+}
+
+impl Read for StdioTransport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl Write for StdioTransport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// Stdin/stdout connection set aside by [`claim_stdio`], until a command takes it
+static STDIO_TRANSPORT: std::sync::Mutex<Option<StdioTransport>> = std::sync::Mutex::new(None);
+
+/// Reserve stdin and stdout for the protocol
+///
+/// Keeps a handle on the original stdout for the protocol and points the
+/// process's stdout at stderr, so status messages printed from then on do
+/// not mix with protocol bytes. Must run before anything is printed.
+#[cfg(unix)]
+fn claim_stdio() -> Result<()> {
+    use std::os::fd::AsFd;
+
+    std::io::stdout().flush()?;
+    let output = File::from(std::io::stdout().as_fd().try_clone_to_owned()?);
+    // SAFETY: dup2 on the process's own standard descriptors, which stay open
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    *STDIO_TRANSPORT.lock().unwrap() = Some(StdioTransport {
+        input: std::io::stdin(),
+        output,
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn claim_stdio() -> Result<()> {
+    bail!("Running the protocol over stdin/stdout is only supported on Unix")
+}
+
+/// Take the stdin/stdout connection, which serves a single session
+fn take_stdio() -> Result<Box<dyn Transport>> {
+    match STDIO_TRANSPORT.lock().unwrap().take() {
+        Some(stdio) => Ok(Box::new(stdio)),
+        None => bail!("stdin/stdout carries a single connection and it is used up"),
+    }
+}
+
+/// Wait for the evaluator on `listen`, running the TLS handshake if configured
+fn accept_peer(listen: &str, tls: &TlsServerArgs) -> Result<Peer> {
+    PeerListener::bind(listen, tls)?.accept()
+}
+
+/// Listening socket for evaluators, with the TLS server if configured
+struct PeerListener {
+    incoming: Incoming,
+    server: Option<TlsServer>,
+}
+
+/// Where a [`PeerListener`] takes connections from
+enum Incoming {
+    Tcp(TcpListener),
+    WebSocket(TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener, PathBuf),
+    Stdio,
+}
+
+impl PeerListener {
+    /// Listen on `listen`: `host:port`, `ws://host:port`, `unix:<path>` or `-` for stdin/stdout
+    fn bind(listen: &str, tls: &TlsServerArgs) -> Result<Self> {
+        // Load certificates before listening, so mistakes show up right away
+        let server = match (&tls.tls_cert, &tls.tls_key) {
+            (Some(cert), Some(key)) => {
+                let client_trust = match &tls.tls_client_ca {
+                    Some(ca) => Some(PeerTrust::load_roots_pem(ca)?),
+                    None if !tls.tls_client_pins.is_empty() => {
+                        Some(PeerTrust::pinned(&tls.tls_client_pins)?)
+                    }
+                    None => None,
+                };
+                let identity = TlsIdentity::load_pem(cert, key)?;
+                println!(
+                    "TLS certificate fingerprint: {}",
+                    hex_string(&identity.fingerprint())
+                );
+                Some(TlsServer::new(identity, client_trust)?)
+            }
+            _ => None,
+        };
+
+        let incoming = match PeerAddress::parse(listen) {
+            PeerAddress::Tcp(address) => {
+                let listener = TcpListener::bind(address)?;
+                println!("Waiting for the evaluator on {}", listener.local_addr()?);
+                Incoming::Tcp(listener)
+            }
+            PeerAddress::WebSocket { address, .. } => {
+                let listener = TcpListener::bind(address)?;
+                println!(
+                    "Waiting for the evaluator on {}{}",
+                    WEBSOCKET_ADDRESS_PREFIX,
+                    listener.local_addr()?
+                );
+                Incoming::WebSocket(listener)
+            }
+            #[cfg(unix)]
+            PeerAddress::Unix(path) => {
+                let listener = std::os::unix::net::UnixListener::bind(path)?;
+                println!("Waiting for the evaluator on {}", path.display());
+                Incoming::Unix(listener, path.to_path_buf())
+            }
+            #[cfg(not(unix))]
+            PeerAddress::Unix(_) => bail!("Unix domain sockets are only supported on Unix"),
+            PeerAddress::Stdio => {
+                println!("Talking to the evaluator over stdin/stdout");
+                Incoming::Stdio
+            }
+        };
+        Ok(Self { incoming, server })
+    }
+
+    /// Wait for the next evaluator
+    fn accept(&self) -> Result<Peer> {
+        match &self.incoming {
+            Incoming::Tcp(listener) => {
+                let (stream, peer) = listener.accept()?;
+                stream.set_nodelay(true)?;
+                println!("Evaluator connected from {}", peer);
+                Peer::tcp(stream, |stream| self.secure(stream))
+            }
+            Incoming::WebSocket(listener) => {
+                let (stream, peer) = listener.accept()?;
+                stream.set_nodelay(true)?;
+                println!("Evaluator connected from {}", peer);
+                Peer::tcp(stream, |stream| {
+                    let stream = WebSocketStream::accept(self.secure(stream)?)?;
+                    println!("WebSocket handshake completed");
+                    Ok(Box::new(stream))
+                })
+            }
+            #[cfg(unix)]
+            Incoming::Unix(listener, path) => {
+                let (stream, _) = listener.accept()?;
+                println!("Evaluator connected on {}", path.display());
+                Ok(Peer::other(self.secure(stream)?))
+            }
+            Incoming::Stdio => Ok(Peer::other(self.secure(take_stdio()?)?)),
+        }
+    }
+
+    /// Run the TLS handshake on `stream` if configured
+    fn secure<S: Read + Write + 'static>(&self, stream: S) -> Result<Box<dyn Transport>> {
+        match &self.server {
+            Some(server) => {
+                let stream = server.accept(stream)?;
+                println!("TLS handshake completed");
+                Ok(Box::new(stream))
+            }
+            None => Ok(Box::new(stream)),
+        }
+    }
+}
+
+impl Drop for PeerListener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Incoming::Unix(_, path) = &self.incoming {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Connect to the garbler at `connect`, running the TLS handshake if configured
+///
+/// `connect` is `host:port`, `ws://host:port/path`, `unix:<path>` or `-` for
+/// stdin/stdout.
+fn connect_peer(connect: &str, tls: &TlsClientArgs) -> Result<Peer> {
+    let server_trust = match &tls.tls_ca {
+        Some(ca) => Some(PeerTrust::load_roots_pem(ca)?),
+        None if !tls.tls_pins.is_empty() => Some(PeerTrust::pinned(&tls.tls_pins)?),
+        None => None,
+    };
+    let client = match server_trust {
+        Some(server_trust) => {
+            let identity = match (&tls.tls_cert, &tls.tls_key) {
+                (Some(cert), Some(key)) => Some(TlsIdentity::load_pem(cert, key)?),
+                _ => None,
+            };
+            Some(TlsClient::new(server_trust, identity)?)
+        }
+        None if tls.tls_cert.is_some() || tls.tls_server_name.is_some() => {
+            bail!("TLS needs the garbler's root (--tls-ca) or certificate fingerprint (--tls-pin)")
+        }
+        None => None,
+    };
+
+    let address = PeerAddress::parse(connect);
+    let mut tcp = None;
+    let stream: Box<dyn Transport> = match address {
+        PeerAddress::Tcp(address) | PeerAddress::WebSocket { address, .. } => {
+            println!("Connecting to the garbler at {}", address);
+            let stream = TcpStream::connect(address)?;
+            stream.set_nodelay(true)?;
+            tcp = Some(stream.try_clone()?);
+            Box::new(stream)
+        }
+        #[cfg(unix)]
+        PeerAddress::Unix(path) => {
+            println!("Connecting to the garbler on {}", path.display());
+            Box::new(std::os::unix::net::UnixStream::connect(path)?)
+        }
+        #[cfg(not(unix))]
+        PeerAddress::Unix(_) => bail!("Unix domain sockets are only supported on Unix"),
+        PeerAddress::Stdio => {
+            println!("Talking to the garbler over stdin/stdout");
+            take_stdio()?
+        }
+    };
+    match client {
+        Some(client) => {
+            let server_name = match (&tls.tls_server_name, address) {
+                (Some(name), _) => name.as_str(),
+                (None, PeerAddress::Tcp(address) | PeerAddress::WebSocket { address, .. }) => {
+                    address
+                        .rsplit_once(':')
+                        .map_or(address, |(host, _)| host)
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                }
+                (None, _) => bail!(
+                    "TLS over a Unix socket or stdin/stdout needs the garbler's name (--tls-server-name)"
+                ),
+            };
+            let stream = client.connect(server_name, stream)?;
+            println!("TLS handshake completed");
+            Ok(Peer {
+                stream: upgrade_to_websocket(address, Box::new(stream))?,
+                tcp,
+            })
+        }
+        None => Ok(Peer {
+            stream: upgrade_to_websocket(address, stream)?,
+            tcp,
+        }),
+    }
+}
+
+/// Run the WebSocket handshake on `stream` if `address` is a `ws://` URL
+fn upgrade_to_websocket(
+    address: PeerAddress<'_>,
+    stream: Box<dyn Transport>,
+) -> Result<Box<dyn Transport>> {
+    match address {
+        PeerAddress::WebSocket { url, .. } => {
+            let stream = WebSocketStream::connect(url, stream)?;
+            println!("WebSocket handshake completed");
+            Ok(Box::new(stream))
+        }
+        _ => Ok(stream),
+    }
+}
+
+/// Lowercase hex encoding of `bytes`
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The `--manifest` of a command, if one was given
+///
+/// Files a command reads are checked against their recorded digests; files
+/// not recorded yet are recorded, so the first command to see an artifact
+/// pins it for the rest of the session. Files a command writes replace
+/// their recorded digests.
+struct ManifestArg {
+    path: Option<PathBuf>,
+    manifest: SessionManifest,
+    changed: bool,
+    /// Temporary copy of a circuit piped to stdin, which is recorded as `-`
+    stdin_spool: Option<PathBuf>,
+}
+
+impl ManifestArg {
+    fn open(path: Option<PathBuf>) -> Result<Self> {
+        let manifest = match &path {
+            Some(path) => SessionManifest::load_or_new(path)?,
+            None => SessionManifest::new(),
+        };
+        Ok(Self {
+            path,
+            manifest,
+            changed: false,
+            stdin_spool: None,
+        })
+    }
+
+    /// Path to show and record for a file: `-` for the copy of stdin
+    fn named<'p>(&'p self, path: &'p Path) -> &'p Path {
+        match &self.stdin_spool {
+            Some(spool) if spool == path => Path::new(STDIN_CIRCUIT),
+            _ => path,
+        }
+    }
+
+    /// Check a file the command reads
+    fn input(&mut self, artifact: Artifact, path: &Path) -> Result<()> {
+        if self.path.is_none() {
+            return Ok(());
+        }
+        if self.manifest.verify(artifact, path)? {
+            println!(
+                "✓ {} matches the {} in the session manifest",
+                self.named(path).display(),
+                artifact
+            );
+        } else {
+            self.output(artifact, path)?;
+        }
+        Ok(())
+    }
+
+    /// Record a file the command wrote
+    fn output(&mut self, artifact: Artifact, path: &Path) -> Result<()> {
+        if self.path.is_some() {
+            let named = self.named(path).to_path_buf();
+            self.manifest.record(artifact, path)?;
+            if let Some(recorded) = self.manifest.artifacts.get_mut(&artifact) {
+                recorded.path = named;
+            }
+            self.changed = true;
+        }
+        Ok(())
+    }
+
+    /// Save the manifest if anything was recorded
+    fn save(self) -> Result<()> {
+        if let (Some(path), true) = (&self.path, self.changed) {
+            self.manifest.save(path)?;
+            println!("Session manifest saved to: {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+/// Read a 32-byte seed file
+fn load_seed(path: &Path) -> Result<[u8; 32]> {
+    let seed_data = std::fs::read(path)?;
+    let Ok(seed) = seed_data.as_slice().try_into() else {
+        bail!(
+            "Seed file must contain exactly 32 bytes, got {}",
+            seed_data.len()
+        );
+    };
+    Ok(seed)
+}
+
+/// Whether a failed transfer is worth resuming: the connection broke or a
+/// batch was corrupted in transit, as opposed to the garbler refusing
+fn is_transient(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<GcError>() {
+        Some(GcError::Disconnected(_) | GcError::Io(_) | GcError::CorruptChunk { .. }) => true,
+        Some(_) => false,
+        None => error.downcast_ref::<std::io::Error>().is_some(),
+    }
+}
+
+/// Print the decoded outputs and statistics of a serve or connect session
+fn print_session_report(report: &TwoPartyReport) {
+    println!("Session completed:");
+    println!("  Garbled tables streamed: {}", report.tables);
+    println!(
+        "  Evaluator inputs through OT: {}",
+        report.ot.evaluator_inputs
+    );
+    println!(
+        "  Bytes sent: {}, received: {}",
+        report.bytes_sent, report.bytes_received
+    );
+    println!("  Time: {:.3}s", report.wall_time_secs);
+    println!("Outputs:");
+    for (wire_id, bit) in &report.outputs {
+        println!("  Wire {}: {}", wire_id, u8::from(*bit));
+    }
+}
+
+/// Circuit path naming stdin, e.g. to pipe in a decompressed or generated circuit
+const STDIN_CIRCUIT: &str = "-";
+
+/// Open a circuit file, or stdin for `-`, skipping blank and comment lines
+/// with `--lenient` and mapping the file into memory with `--mmap-circuit`
+fn open_circuit(
+    path: &Path,
+    lenient: bool,
+    mmap: bool,
+) -> Result<BufferedLineStream<Box<dyn Read>>> {
+    let stream = if mmap && path.as_os_str() != STDIN_CIRCUIT {
+        BufferedLineStream::mmap(&File::open(path)?)?
+    } else {
+        BufferedLineStream::from_reader(open_input(path)?)
+    };
+    Ok(stream.with_lenient(lenient))
+}
+
+/// Open a circuit as [`open_circuit`] does, rewritten into the XOR/AND basis with `--lower-gates`
+///
+/// Lowering reads the circuit once more up front, to count the gates it rewrites.
+fn open_lowered_circuit(
+    path: &Path,
+    lenient: bool,
+    mmap: bool,
+    lower: bool,
+) -> Result<BufferedLineStream<Box<dyn Read>>> {
+    let stream = open_circuit(path, lenient, mmap)?;
+    if !lower {
+        return Ok(stream);
+    }
+    let report = scan_gates(&mut open_circuit(path, lenient, mmap)?)?;
+    let reader: Box<dyn Read> = Box::new(LoweringReader::new(stream, &report)?);
+    Ok(BufferedLineStream::with_buffer_size(
+        reader,
+        LOWERED_STREAM_BUFFER_SIZE,
+    ))
+}
+
+/// Open a file, or stdin for `-`
+fn open_input(path: &Path) -> Result<Box<dyn Read>> {
+    Ok(if path.as_os_str() == STDIN_CIRCUIT {
+        Box::new(std::io::stdin())
+    } else {
+        Box::new(File::open(path)?)
+    })
+}
+
+/// Worker threads of a parallel scan: the `--threads` value, or one per core for 0
+fn scan_threads(threads: usize) -> usize {
+    match threads {
+        0 => std::thread::available_parallelism().map_or(1, |cores| cores.get()),
+        threads => threads,
+    }
+}
+
+/// Copy a circuit piped to stdin into a temporary file, for commands that
+/// read the circuit more than once or digest it
+///
+/// # Returns
+/// * `Ok(NamedTempFile)` - The copy, deleted when dropped
+/// * `Err` - Reading stdin or writing the copy failed
+fn spool_stdin() -> Result<tempfile::NamedTempFile> {
+    let mut spool = tempfile::NamedTempFile::new()?;
+    std::io::copy(&mut std::io::stdin().lock(), &mut spool)?;
+    spool.flush()?;
+    Ok(spool)
+}
+
+/// Default path of a file derived from the circuit: next to the circuit
+/// file, or `stdin.<extension>` in the working directory for a piped circuit
+fn default_output(circuit: &Path, from_stdin: bool, extension: &str) -> PathBuf {
+    let mut path = if from_stdin {
+        PathBuf::from("stdin")
+    } else {
+        circuit.to_path_buf()
+    };
+    path.set_extension(extension);
+    path
+}
+
+/// Circuit formats of other toolchains that `import` converts to Bristol
+#[derive(Debug, Clone, Copy)]
+enum ImportFormat {
+    /// Bristol Fashion with `INV` and `MAND` gates, see [`import_scale_mamba`]
+    ScaleMamba,
+    /// ASCII or binary and-inverter graph, see [`import_aiger`]
+    Aiger,
+    /// Combinational BLIF netlist, see [`import_blif`]
+    Blif,
+    /// Structured JSON, see [`JsonCircuit`]
+    Json,
+}
+
+impl std::str::FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, String> {
+        match spec {
+            "scale-mamba" => Ok(ImportFormat::ScaleMamba),
+            "aiger" => Ok(ImportFormat::Aiger),
+            "blif" => Ok(ImportFormat::Blif),
+            "json" => Ok(ImportFormat::Json),
+            _ => Err(format!(
+                "Invalid circuit format '{}': expected scale-mamba, aiger, blif or json",
+                spec
+            )),
+        }
+    }
+}
+
+/// Standard components that `generate` writes, see [`gc::circuits`]
+#[derive(Debug, Clone, Copy)]
+enum Component {
+    /// See [`ripple_carry_adder`]
+    RippleAdder,
+    /// See [`carry_lookahead_adder`]
+    LookaheadAdder,
+    /// See [`multiplier`]
+    Multiplier,
+    /// See [`comparator`]
+    Comparator,
+    /// See [`equality`]
+    Equality,
+    /// See [`mux_tree`]
+    Mux,
+}
+
+impl std::str::FromStr for Component {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, String> {
+        match spec {
+            "ripple-adder" => Ok(Component::RippleAdder),
+            "lookahead-adder" => Ok(Component::LookaheadAdder),
+            "multiplier" => Ok(Component::Multiplier),
+            "comparator" => Ok(Component::Comparator),
+            "equality" => Ok(Component::Equality),
+            "mux" => Ok(Component::Mux),
+            _ => Err(format!(
+                "Invalid component '{}': expected ripple-adder, lookahead-adder, multiplier, \
+                 comparator, equality or mux",
+                spec
+            )),
+        }
+    }
+}
+
+/// Load an input assignment, which may name input buses when `--buses` is
+/// given and may be a packed number when the input wires are known
+fn load_inputs(
+    path: &Path,
+    bus_layout: Option<&BusLayout>,
+    input_wires: Option<&[WireId]>,
+) -> Result<HashMap<WireId, bool>> {
+    let buses = bus_layout.map_or(&[][..], |bus_layout| &bus_layout.inputs);
+    Ok(InputAssignment::load(path)?.resolve(input_wires, buses)?)
+}
+
+/// Parse `--garbler-inputs`, which may name input buses when `--buses` is given
+fn parse_partition(spec: &str, bus_layout: Option<&BusLayout>) -> Result<InputPartition> {
+    Ok(match bus_layout {
+        Some(bus_layout) => bus_layout.input_partition(spec)?,
+        None => InputPartition::parse(spec)?,
+    })
+}
+
+/// Print bus values as a JSON object of hex integers
+fn print_bus_values(values: &BTreeMap<String, BusValue>) -> Result<()> {
+    let values: BTreeMap<_, _> = values
+        .iter()
+        .map(|(name, value)| (name, value.to_hex()))
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&values)?);
+    Ok(())
+}
+
+/// Parse a byte count with an optional binary suffix (`K`, `M`, `G`, `T`)
+fn parse_byte_size(spec: &str) -> Result<u64, String> {
+    let spec = spec.trim();
+    let (digits, shift) = match spec.to_ascii_uppercase().trim_end_matches(['B', 'I']) {
+        s if s.ends_with('K') => (&spec[..s.len() - 1], 10),
+        s if s.ends_with('M') => (&spec[..s.len() - 1], 20),
+        s if s.ends_with('G') => (&spec[..s.len() - 1], 30),
+        s if s.ends_with('T') => (&spec[..s.len() - 1], 40),
+        s => (&spec[..s.len()], 0),
+    };
+    let value: u64 = digits
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size '{}', expected e.g. 512M or 8G", spec))?;
+    value
+        .checked_mul(1 << shift)
+        .ok_or_else(|| format!("size '{}' is too large", spec))
+}
+
+fn main() -> Result<()> {
+    let mut args = Args::parse();
+    init_tracing(args.log.as_deref())?;
+    let progress = TerminalProgress::default();
+
+    // Set stdout aside before anything is printed to it
+    let stdio_peer = match &args.command {
+        Commands::OtSend { listen, .. }
+        | Commands::Serve { listen, .. }
+        | Commands::SendTables { listen, .. } => listen == STDIO_ADDRESS,
+        Commands::OtReceive { connect, .. }
+        | Commands::Connect { connect, .. }
+        | Commands::FetchTables { connect, .. } => connect == STDIO_ADDRESS,
+        _ => false,
+    };
+    if stdio_peer {
+        claim_stdio()?;
+    }
+
+    let mut manifest = ManifestArg::open(args.manifest)?;
+
+    // Stream a circuit piped to stdin, or keep a copy when it is read more than once
+    let single_pass = manifest.path.is_none();
+    let stdin_tables = matches!(
+        &args.command,
+        Commands::Evaluate { garbled_tables_file, .. } if garbled_tables_file.as_os_str() == "-"
+    );
+    let mut stdin_circuit = false;
+    let mut _stdin_spool = None;
+    if let Some((path, reads_once)) = args.command.circuit_mut()
+        && path.as_os_str() == STDIN_CIRCUIT
+    {
+        if stdio_peer {
+            bail!("stdin cannot carry both the circuit and the connection to the other party");
+        }
+        if stdin_tables {
+            bail!("stdin cannot carry both the circuit and the garbled tables");
+        }
+        stdin_circuit = true;
+        if !(reads_once && single_pass) {
+            let spool = spool_stdin()?;
+            *path = spool.path().to_path_buf();
+            manifest.stdin_spool = Some(path.clone());
+            _stdin_spool = Some(spool);
+        }
+    }
+    let bus_layout = args
+        .bus_layout
+        .as_deref()
+        .map(BusLayout::load_json)
+        .transpose()?;
+
+    match args.command {
+        Commands::Count { file, threads } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            // Count gate types, in parallel chunks or streaming
+            let counts = match threads {
+                Some(threads) => count_gate_types_parallel(
+                    &ChunkedCircuit::open(&file, args.lenient)?,
+                    scan_threads(threads),
+                    &progress,
+                )?,
+                None => count_gate_types(
+                    &mut open_circuit(&file, args.lenient, args.mmap_circuit)?,
+                    &progress,
+                )?,
+            };
+
+            // Output as JSON
+            let json_output = serde_json::to_string_pretty(&counts)?;
+            println!("{json_output}");
+        }
+        Commands::WireAnalysis {
+            file,
+            output,
+            sparse,
+            threads,
+            fan_out,
+            top,
+        } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            // Perform wire usage analysis, in parallel chunks or streaming
+            let storage = if sparse {
+                UsageStorage::Sparse
+            } else {
+                UsageStorage::Dense
+            };
+            let wire_report = match threads {
+                Some(threads) => analyze_wire_usage_parallel(
+                    &ChunkedCircuit::open(&file, args.lenient)?,
+                    storage,
+                    scan_threads(threads),
+                    &progress,
+                )?,
+                None => analyze_wire_usage_with_storage(
+                    &mut open_circuit(&file, args.lenient, args.mmap_circuit)?,
+                    storage,
+                    &progress,
+                )?,
+            };
+
+            // Determine output file
+            let output_path =
+                output.unwrap_or_else(|| default_output(&file, stdin_circuit, "wire_analysis"));
+
+            // Save binary report
+            wire_report.save_binary(&output_path)?;
+            manifest.output(Artifact::WireAnalysis, &output_path)?;
+
+            // Print summary
+            println!("Wire analysis saved to: {}", output_path.display());
+            println!("Total wires: {}", wire_report.total_wires);
+            println!("Primary inputs: {}", wire_report.primary_inputs);
+            println!("Intermediate wires: {}", wire_report.intermediate_wires);
+            println!("Primary outputs: {}", wire_report.primary_outputs);
+            println!("Missing/unused wires: {}", wire_report.missing_wires_count);
+
+            if fan_out {
+                let report = analyze_fan_out(
+                    &mut open_circuit(&file, args.lenient, args.mmap_circuit)?,
+                    &wire_report,
+                    top,
+                    &progress,
+                )?;
+                let json_path = default_output(&file, stdin_circuit, "fan_out.json");
+                report.save_json(&json_path)?;
+                let csv_path = default_output(&file, stdin_circuit, "fan_out.csv");
+                report.export_histogram_csv(&csv_path)?;
+
+                println!("Fan-out report saved to: {}", json_path.display());
+                println!("Fan-out histogram saved to: {}", csv_path.display());
+                println!("Highest fan-out wires:");
+                for wire in &report.top_wires {
+                    let producer = match wire.producer {
+                        Some(gate_index) => format!("gate {gate_index}"),
+                        None => "primary input".to_string(),
+                    };
+                    println!(
+                        "  wire {}: fan-out {} ({})",
+                        wire.wire, wire.fan_out, producer
+                    );
+                }
+            }
+        }
+        Commands::LayerAnalysis { file, output } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            // Open file and create streaming reader
+            let mut stream = open_circuit(&file, args.lenient, args.mmap_circuit)?;
+
+            // Compute the layer of every gate
+            let layer_report = analyze_layers(&mut stream, &progress)?;
+
+            // Determine output file
+            let output_path =
+                output.unwrap_or_else(|| default_output(&file, stdin_circuit, "layers"));
+
+            // Save binary report
+            layer_report.save_binary(&output_path)?;
+
+            // Print summary
+            let widths = layer_report.layer_widths();
+            println!("Layer analysis saved to: {}", output_path.display());
+            println!("Gates: {}", layer_report.gate_layers.len());
+            println!("Layers: {}", layer_report.num_layers);
+            println!(
+                "Widest layer: {} gates",
+                widths.iter().max().copied().unwrap_or(0)
+            );
+        }
+        Commands::LayerProfile { file, output } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            let profile = profile_layers(
+                &mut open_circuit(&file, args.lenient, args.mmap_circuit)?,
+                &progress,
+            )?;
+
+            let output_path =
+                output.unwrap_or_else(|| default_output(&file, stdin_circuit, "layer_profile.csv"));
+            profile.export_csv(&output_path)?;
+
+            let num_gates: u64 = profile.gates.iter().sum();
+            let widest = |counts: &[u64]| counts.iter().max().copied().unwrap_or(0);
+            println!("Layer profile saved to: {}", output_path.display());
+            println!("Layers: {}", profile.num_layers());
+            println!("Widest layer: {} gates", widest(&profile.gates));
+            println!("Widest AND layer: {} AND gates", widest(&profile.and_gates));
+            if profile.num_layers() > 0 {
+                println!(
+                    "Average layer: {:.1} gates",
+                    num_gates as f64 / profile.num_layers() as f64
+                );
+            }
+        }
+        Commands::AndDepth { file, output } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            let report = analyze_and_depth(
+                &mut open_circuit(&file, args.lenient, args.mmap_circuit)?,
+                &progress,
+            )?;
+
+            let output_path =
+                output.unwrap_or_else(|| default_output(&file, stdin_circuit, "and_depth.json"));
+            report.save_json(&output_path)?;
+
+            println!("AND depth report saved to: {}", output_path.display());
+            println!("AND depth: {}", report.and_depth);
+            println!("AND gates: {}", report.and_gates);
+            println!("Primary outputs: {}", report.output_depths.len());
+            if let Some(bus_layout) = &bus_layout {
+                let depths = report.bus_depths(&bus_layout.outputs);
+                println!("{}", serde_json::to_string_pretty(&depths)?);
+            }
+        }
+        Commands::Garble {
+            file,
+            wire_analysis_file,
+            no_wire_analysis,
+            lower_gates,
+            seed_file,
+            output,
+            compress,
+            binary_labels,
+            stats_json,
+            garbler_inputs,
+            input_partition_file,
+            shards,
+            shard_dirs,
+            hash_threads,
+            max_memory,
+        } => {
+            if lower_gates && manifest.path.is_some() {
+                bail!(
+                    "--lower-gates cannot be combined with --manifest; lower the circuit with `lower` first"
+                );
+            }
+            manifest.input(Artifact::Circuit, &file)?;
+            if let Some(wire_analysis_file) = &wire_analysis_file {
+                manifest.input(Artifact::WireAnalysis, wire_analysis_file)?;
+            }
+            // Leave one core to the parsing thread
+            let hash_threads = hash_threads.unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map_or(0, |cores| cores.get().saturating_sub(1))
+                    .min(4)
+            });
+
+            let input_partition = match (garbler_inputs, input_partition_file) {
+                (Some(spec), _) => parse_partition(&spec, bus_layout.as_ref())?,
+                (None, Some(path)) => InputPartition::load_json(&path)?,
+                (None, None) => InputPartition::default(),
+            };
+
+            // Load wire usage analysis, or derive it with a first pass over the circuit
+            let wire_report = match wire_analysis_file {
+                Some(wire_analysis_file) => {
+                    println!(
+                        "Loading wire analysis from: {}",
+                        wire_analysis_file.display()
+                    );
+                    Some(WireUsageReport::load_binary(&wire_analysis_file)?)
+                }
+                None if no_wire_analysis => None,
+                None => {
+                    println!("Analyzing wire usage of: {}", file.display());
+                    Some(analyze_wire_usage(
+                        &mut open_lowered_circuit(
+                            &file,
+                            args.lenient,
+                            args.mmap_circuit,
+                            lower_gates,
+                        )?,
+                        &progress,
+                    )?)
+                }
+            };
+
+            // Open file and create streaming reader
+            let mut stream =
+                open_lowered_circuit(&file, args.lenient, args.mmap_circuit, lower_gates)?;
+
+            // Load 32-byte seed from file
+            let seed_array = load_seed(&seed_file)?;
+
+            // Determine output paths
+            let labels_extension = if binary_labels {
+                "labels.bin"
+            } else {
+                "labels.json"
+            };
+            let labels_path = output
+                .as_ref()
+                .map(|p| {
+                    let mut path = p.clone();
+                    path.set_extension(labels_extension);
+                    path
+                })
+                .unwrap_or_else(|| default_output(&file, stdin_circuit, labels_extension));
+
+            let mut tables_path =
+                output.unwrap_or_else(|| default_output(&file, stdin_circuit, "garbled"));
+
+            // Garble the circuit, streaming tables straight to disk
+            let garble = |sink: &mut dyn GarbledTableSink| {
+                let mut garbler = GarblerBuilder::new(seed_array)
+                    .sink(sink)
+                    .progress(&progress)
+                    .input_partition(&input_partition)
+                    .hash_threads(hash_threads);
+                if let Some(max_memory) = max_memory {
+                    garbler = garbler.max_memory(max_memory);
+                }
+                match wire_report {
+                    Some(wire_report) => garbler.garble(&mut stream, wire_report),
+                    None => garbler.garble_keep_all(&mut stream),
+                }
+            };
+            let result = if let Some(num_shards) = shards {
+                if num_shards == 0 {
+                    bail!("Number of shards must be at least 1");
+                }
+                // Size the shards from the AND gate count, one table each
+                let gate_counts = count_gate_types(
+                    &mut open_lowered_circuit(&file, args.lenient, args.mmap_circuit, lower_gates)?,
+                    &progress,
+                )?;
+                let num_tables = gate_counts.get("AND").copied().unwrap_or(0) as u64;
+                let mut manifest_path = tables_path.clone().into_os_string();
+                manifest_path.push(".json");
+                tables_path = PathBuf::from(manifest_path);
+
+                let mut sink =
+                    ShardedTableWriter::create(&tables_path, num_tables.div_ceil(num_shards))
+                        .with_shard_dirs(shard_dirs);
+                if compress {
+                    sink = sink.compressed(ZSTD_COMPRESSION_LEVEL);
+                }
+                garble(&mut sink)?
+            } else if compress {
+                let mut sink = CompressedTableWriter::create(&tables_path, ZSTD_COMPRESSION_LEVEL)?;
+                garble(&mut sink)?
+            } else {
+                let mut sink = TableWriter::create(&tables_path)?;
+                garble(&mut sink)?
+            };
+            let wire_labels = &result.wire_labels;
+            if binary_labels {
+                wire_labels.save_binary(&labels_path)?;
+            } else {
+                wire_labels.save_json(&labels_path)?;
+            }
+            manifest.output(Artifact::TablesHeader, &tables_path)?;
+            manifest.output(Artifact::Labels, &labels_path)?;
+
+            let stats = &result.stats;
+            println!("Garbling completed:");
+            println!("  Wire labels saved to: {}", labels_path.display());
+            println!("  Garbled tables saved to: {}", tables_path.display());
+            println!("  Session id: {}", wire_labels.session_id);
+            println!(
+                "  Input wires: {} ({} garbler, {} evaluator)",
+                wire_labels.input_labels.len(),
+                wire_labels.garbler_input_wires.len(),
+                wire_labels.input_labels.len() - wire_labels.garbler_input_wires.len()
+            );
+            println!("  Output wires: {}", wire_labels.output_labels.len());
+            println!(
+                "  Gates: {} ({} XOR, {} AND)",
+                stats.total_gates, stats.xor_gates, stats.and_gates
+            );
+            println!("  Peak active labels: {}", stats.peak_active_labels);
+            println!(
+                "  Time: {:.2}s ({:.0} gates/s)",
+                stats.wall_time_secs, stats.gates_per_sec
+            );
+
+            if let Some(stats_json) = stats_json {
+                stats.save_json(&stats_json)?;
+                println!("Garbling statistics saved to: {}", stats_json.display());
+            }
+        }
+        Commands::OtSimulate {
+            wire_labels_file,
+            seed_file,
+            inputs_file,
+            output,
+            garbler_inputs_file,
+            binary,
+            stream,
+        } => {
+            manifest.input(Artifact::Labels, &wire_labels_file)?;
+            // Load wire labels from garbler output
+            println!("Loading wire labels from: {}", wire_labels_file.display());
+            let wire_labels = WireLabels::load(&wire_labels_file)?;
+
+            let output_path = output.unwrap_or_else(|| {
+                let mut path = wire_labels_file.clone();
+                path.set_extension(if binary { "ot.bin" } else { "ot.json" });
+                path
+            });
+
+            let (evaluator_inputs, garbler_inputs) = if stream {
+                // Write labels as they are drawn instead of building the result
+                let Some(seed_file) = seed_file else {
+                    bail!("--stream requires --seed-file");
+                };
+                let seed_array = load_seed(&seed_file)?;
+                let stats = simulate_ot_streaming(
+                    &wire_labels,
+                    &seed_array,
+                    &output_path,
+                    binary,
+                    &progress,
+                )?;
+                (
+                    stats.evaluator_inputs as usize,
+                    stats.garbler_inputs as usize,
+                )
+            } else {
+                let mut ot_result = match (seed_file, inputs_file) {
+                    (_, Some(inputs_file)) => {
+                        // Select the given bits, in ascending wire order for hex input
+                        println!("Loading input bits from: {}", inputs_file.display());
+                        let mut input_wires: Vec<_> =
+                            wire_labels.input_labels.keys().copied().collect();
+                        input_wires.sort_unstable();
+                        let inputs =
+                            load_inputs(&inputs_file, bus_layout.as_ref(), Some(&input_wires))?;
+                        select_inputs(&wire_labels, &inputs)?
+                    }
+                    (Some(seed_file), None) => {
+                        // Simulate OT protocol with random bits from the seed
+                        let seed_array = load_seed(&seed_file)?;
+                        simulate_ot(&wire_labels, &seed_array, &progress)?
+                    }
+                    (None, None) => bail!("Either --seed-file or --inputs is required"),
+                };
+                if let Some(garbler_inputs_file) = garbler_inputs_file {
+                    println!(
+                        "Loading encoded garbler inputs from: {}",
+                        garbler_inputs_file.display()
+                    );
+                    ot_result.set_garbler_inputs(EncodedInputs::load(&garbler_inputs_file)?)?;
+                }
+
+                // Save OT results
+                if binary {
+                    ot_result.save_binary(&output_path)?;
+                } else {
+                    ot_result.save_json(&output_path)?;
+                }
+                (
+                    ot_result.selected_inputs.len(),
+                    ot_result.garbler_inputs.len(),
+                )
+            };
+
+            println!("OT simulation completed:");
+            println!("  Selected inputs: {}", evaluator_inputs);
+            if garbler_inputs > 0 {
+                println!("  Garbler inputs sent without OT: {}", garbler_inputs);
+            }
+            println!("  Results saved to: {}", output_path.display());
+        }
+        Commands::OtSend {
+            wire_labels_file,
+            listen,
+            seed_file,
+            garbler_inputs_file,
+            security,
+            extension,
+            transcript,
+            stats_json,
+            tls,
+        } => {
+            manifest.input(Artifact::Labels, &wire_labels_file)?;
+            println!("Loading wire labels from: {}", wire_labels_file.display());
+            let wire_labels = WireLabels::load(&wire_labels_file)?;
+            let seed_array = load_seed(&seed_file)?;
+            let garbler_inputs = match garbler_inputs_file {
+                Some(garbler_inputs_file) => {
+                    println!(
+                        "Loading encoded garbler inputs from: {}",
+                        garbler_inputs_file.display()
+                    );
+                    Some(EncodedInputs::load(&garbler_inputs_file)?)
+                }
+                None => None,
+            };
+
+            let mut channel = Channel::new(accept_peer(&listen, &tls)?.stream);
+            channel.negotiate()?;
+            if transcript.is_some() {
+                channel = channel.with_recording();
+            }
+            let sent = send_ot(
+                &mut channel,
+                &wire_labels,
+                garbler_inputs.as_ref(),
+                security,
+                extension,
+                &seed_array,
+                &progress,
+            );
+            // Keep the transcript of a failed run too, for debugging
+            if let Some(transcript) = &transcript {
+                OtTranscript::from_recording(channel.recorded(), OtParty::Garbler)
+                    .save(transcript)?;
+                println!("Transcript saved to: {}", transcript.display());
+            }
+            let stats = sent?;
+            if let Some(transcript) = &transcript {
+                manifest.output(Artifact::OtTranscript, transcript)?;
+            }
+
+            println!("OT completed:");
+            println!(
+                "  Evaluator inputs sent through {} OT ({}): {}",
+                security, extension, stats.evaluator_inputs
+            );
+            if stats.garbler_inputs > 0 {
+                println!("  Garbler inputs sent without OT: {}", stats.garbler_inputs);
+            }
+            println!(
+                "  Bytes sent: {}, received: {}",
+                stats.bytes_sent, stats.bytes_received
+            );
+            println!("  Time: {:.3}s", stats.wall_time_secs);
+            if let Some(stats_json) = stats_json {
+                stats.save_json(&stats_json)?;
+                println!("OT statistics saved to: {}", stats_json.display());
+            }
+        }
+        Commands::OtReceive {
+            connect,
+            inputs_file,
+            seed_file,
+            security,
+            extension,
+            transcript,
+            stats_json,
+            output,
+            binary,
+            tls,
+        } => {
+            println!("Loading input bits from: {}", inputs_file.display());
+            let inputs = load_inputs(&inputs_file, bus_layout.as_ref(), None)?;
+            let seed_array = load_seed(&seed_file)?;
+
+            let mut channel = Channel::new(connect_peer(&connect, &tls)?.stream);
+            channel.negotiate()?;
+            if transcript.is_some() {
+                channel = channel.with_recording();
+            }
+            let received = receive_ot(
+                &mut channel,
+                &inputs,
+                security,
+                extension,
+                &seed_array,
+                &progress,
+            );
+            if let Some(transcript) = &transcript {
+                OtTranscript::from_recording(channel.recorded(), OtParty::Evaluator)
+                    .save(transcript)?;
+                println!("Transcript saved to: {}", transcript.display());
+            }
+            let (ot_result, stats) = received?;
+            if let Some(transcript) = &transcript {
+                manifest.output(Artifact::OtTranscript, transcript)?;
+            }
+
+            let output_path = output.unwrap_or_else(|| {
+                let mut path = inputs_file.clone();
+                path.set_extension(if binary { "ot.bin" } else { "ot.json" });
+                path
+            });
+            if binary {
+                ot_result.save_binary(&output_path)?;
+            } else {
+                ot_result.save_json(&output_path)?;
+            }
+
+            println!("OT completed:");
+            println!("  Selected inputs: {}", ot_result.selected_inputs.len());
+            if !ot_result.garbler_inputs.is_empty() {
+                println!(
+                    "  Garbler inputs sent without OT: {}",
+                    ot_result.garbler_inputs.len()
+                );
+            }
+            println!(
+                "  Bytes sent: {}, received: {}",
+                stats.bytes_sent, stats.bytes_received
+            );
+            println!("  Time: {:.3}s", stats.wall_time_secs);
+            println!("  Results saved to: {}", output_path.display());
+            if let Some(stats_json) = stats_json {
+                stats.save_json(&stats_json)?;
+                println!("OT statistics saved to: {}", stats_json.display());
+            }
+        }
+        Commands::OtVerify {
+            wire_labels_file,
+            transcript_file,
+            garbler_seed_file,
+            evaluator_seed_file,
+            ot_result_file,
+        } => {
+            manifest.input(Artifact::Labels, &wire_labels_file)?;
+            manifest.input(Artifact::OtTranscript, &transcript_file)?;
+            println!("Loading wire labels from: {}", wire_labels_file.display());
+            let wire_labels = WireLabels::load(&wire_labels_file)?;
+            println!("Loading OT transcript from: {}", transcript_file.display());
+            let transcript = OtTranscript::load(&transcript_file)?;
+            let garbler_seed = load_seed(&garbler_seed_file)?;
+            let evaluator = match (evaluator_seed_file, ot_result_file) {
+                (Some(evaluator_seed_file), Some(ot_result_file)) => {
+                    println!("Loading OT results from: {}", ot_result_file.display());
+                    Some((
+                        load_seed(&evaluator_seed_file)?,
+                        gc::ot_simulation::OTResult::load(&ot_result_file)?,
+                    ))
+                }
+                _ => None,
+            };
+
+            let report = verify_ot_transcript(
+                &transcript,
+                &wire_labels,
+                &garbler_seed,
+                evaluator
+                    .as_ref()
+                    .map(|(seed, ot_result)| (seed, ot_result)),
+            )?;
+
+            println!("✓ OT transcript is consistent with the committed wire labels");
+            println!("  Messages: {}", transcript.messages.len());
+            println!(
+                "  OTs: {} ({} OT, {})",
+                report.ots, report.security, report.backend
+            );
+            println!(
+                "  Garbler inputs sent without OT: {}",
+                report.garbler_inputs
+            );
+            match report.evaluator_inputs {
+                Some(count) => println!("  Evaluator labels replayed: {}", count),
+                None => println!("  Evaluator side not replayed (no --evaluator-seed)"),
+            }
+        }
+        Commands::Serve {
+            input,
+            listen,
+            seed_file,
+            garbler_inputs,
+            input_partition_file,
+            inputs_file,
+            security,
+            extension,
+            stats_json,
+            tls,
+            link,
+        } => {
+            manifest.input(Artifact::Circuit, &input)?;
+            let input_partition = match (garbler_inputs, input_partition_file) {
+                (Some(spec), _) => parse_partition(&spec, bus_layout.as_ref())?,
+                (None, Some(path)) => InputPartition::load_json(&path)?,
+                (None, None) => InputPartition::default(),
+            };
+            let inputs = match inputs_file {
+                Some(inputs_file) => {
+                    println!("Loading input bits from: {}", inputs_file.display());
+                    load_inputs(&inputs_file, bus_layout.as_ref(), None)?
+                }
+                None => HashMap::new(),
+            };
+            let seed_array = load_seed(&seed_file)?;
+
+            let (mut channel, link) = Link::open(accept_peer(&listen, &tls)?, &link)?;
+            let report = garble_and_serve(
+                &mut channel,
+                &input,
+                &input_partition,
+                &inputs,
+                &seed_array,
+                &TwoPartyOptions {
+                    security,
+                    backend: extension,
+                    lenient: args.lenient,
+                },
+                &progress,
+            )?;
+            print_session_report(&report);
+            link.finish()?;
+            if let Some(stats_json) = stats_json {
+                report.save_json(&stats_json)?;
+                println!("Session statistics saved to: {}", stats_json.display());
+            }
+        }
+        Commands::Connect {
+            input,
+            connect,
+            inputs_file,
+            seed_file,
+            security,
+            extension,
+            output,
+            stats_json,
+            tls,
+            link,
+        } => {
+            manifest.input(Artifact::Circuit, &input)?;
+            let inputs = match inputs_file {
+                Some(inputs_file) => {
+                    println!("Loading input bits from: {}", inputs_file.display());
+                    load_inputs(&inputs_file, bus_layout.as_ref(), None)?
+                }
+                None => HashMap::new(),
+            };
+            let seed_array = load_seed(&seed_file)?;
+
+            let (mut channel, link) = Link::open(connect_peer(&connect, &tls)?, &link)?;
+            let (evaluation_result, report) = connect_and_evaluate(
+                &mut channel,
+                &input,
+                &inputs,
+                &seed_array,
+                &TwoPartyOptions {
+                    security,
+                    backend: extension,
+                    lenient: args.lenient,
+                },
+                &progress,
+            )?;
+            print_session_report(&report);
+            link.finish()?;
+            if let Some(output) = output {
+                evaluation_result.save_json(&output)?;
+                manifest.output(Artifact::Outputs, &output)?;
+                println!("Evaluation result saved to: {}", output.display());
+            }
+            if let Some(stats_json) = stats_json {
+                report.save_json(&stats_json)?;
+                println!("Session statistics saved to: {}", stats_json.display());
+            }
+        }
+        Commands::RpcServe {
+            input,
+            listen,
+            seed_file,
+            garbler_inputs,
+            input_partition_file,
+            inputs_file,
+            security,
+            extension,
+        } => {
+            manifest.input(Artifact::Circuit, &input)?;
+            let input_partition = match (garbler_inputs, input_partition_file) {
+                (Some(spec), _) => parse_partition(&spec, bus_layout.as_ref())?,
+                (None, Some(path)) => InputPartition::load_json(&path)?,
+                (None, None) => InputPartition::default(),
+            };
+            let inputs = match inputs_file {
+                Some(inputs_file) => {
+                    println!("Loading input bits from: {}", inputs_file.display());
+                    load_inputs(&inputs_file, bus_layout.as_ref(), None)?
+                }
+                None => HashMap::new(),
+            };
+            let seed_array = load_seed(&seed_file)?;
+
+            let service = GarblerService::new(
+                &input,
+                input_partition,
+                &seed_array,
+                TwoPartyOptions {
+                    security,
+                    backend: extension,
+                    lenient: args.lenient,
+                },
+            )?
+            .with_inputs(&inputs)?;
+            let runtime = tokio::runtime::Runtime::new()?;
+            let outputs = runtime.block_on(async {
+                let listener = tokio::net::TcpListener::bind(&listen).await?;
+                println!(
+                    "Serving the garbler over gRPC on {}",
+                    listener.local_addr()?
+                );
+                Ok::<_, anyhow::Error>(service.serve(listener).await?)
+            })?;
+            println!("Session completed, outputs:");
+            for (wire_id, bit) in &outputs {
+                println!("  Wire {}: {}", wire_id, u8::from(*bit));
+            }
+        }
+        Commands::RpcConnect {
+            input,
+            connect,
+            inputs_file,
+            seed_file,
+            security,
+            extension,
+            output,
+        } => {
+            manifest.input(Artifact::Circuit, &input)?;
+            let inputs = match inputs_file {
+                Some(inputs_file) => {
+                    println!("Loading input bits from: {}", inputs_file.display());
+                    load_inputs(&inputs_file, bus_layout.as_ref(), None)?
+                }
+                None => HashMap::new(),
+            };
+            let seed_array = load_seed(&seed_file)?;
+
+            println!("Connecting to the garbler at {}", connect);
+            let runtime = tokio::runtime::Runtime::new()?;
+            let (evaluation_result, outputs) = runtime.block_on(evaluate_with_garbler(
+                connect,
+                &input,
+                &inputs,
+                &seed_array,
+                &TwoPartyOptions {
+                    security,
+                    backend: extension,
+                    lenient: args.lenient,
+                },
+            ))?;
+            println!("Session completed, outputs:");
+            for (wire_id, bit) in &outputs {
+                println!("  Wire {}: {}", wire_id, u8::from(*bit));
+            }
+            if let Some(output) = output {
+                evaluation_result.save_json(&output)?;
+                manifest.output(Artifact::Outputs, &output)?;
+                println!("Evaluation result saved to: {}", output.display());
+            }
+        }
+        Commands::SendTables {
+            input,
+            listen,
+            stats_json,
+            tls,
+            link,
+        } => {
+            manifest.input(Artifact::TablesHeader, &input)?;
+            // Serve evaluators until one has every table; a broken
+            // connection is answered by the evaluator resuming
+            let listener = PeerListener::bind(&listen, &tls)?;
+            let report = loop {
+                let peer = match listener.accept() {
+                    Ok(peer) => peer,
+                    Err(e) => {
+                        println!("Connection failed: {}", e);
+                        continue;
+                    }
+                };
+                let (mut channel, link_stats) = match Link::open(peer, &link) {
+                    Ok(opened) => opened,
+                    Err(e) => {
+                        println!("Connection failed: {}", e);
+                        continue;
+                    }
+                };
+                let sent = send_tables(&mut channel, &input, &progress);
+                link_stats.finish()?;
+                match sent {
+                    Ok(report) => break report,
+                    Err(e @ (GcError::Disconnected(_) | GcError::Io(_))) => {
+                        println!("Transfer interrupted: {}", e);
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            };
+            println!(
+                "Sent tables {}..{} of {} ({} bytes in {:.3}s)",
+                report.first_table,
+                report.first_table + report.tables_transferred,
+                report.total_tables,
+                report.bytes_sent,
+                report.wall_time_secs
+            );
+            if let Some(stats_json) = stats_json {
+                report.save_json(&stats_json)?;
+                println!("Transfer statistics saved to: {}", stats_json.display());
+            }
+        }
+        Commands::FetchTables {
+            output,
+            connect,
+            retries,
+            retry_delay,
+            stats_json,
+            tls,
+            link,
+        } => {
+            let mut attempt = 0;
+            let report = loop {
+                if let Some(tables) = resume_point(&output)? {
+                    println!("Resuming the download at table {}", tables);
+                }
+                let result = connect_peer(&connect, &tls).and_then(|peer| {
+                    let (mut channel, link_stats) = Link::open(peer, &link)?;
+                    let received = receive_tables(&mut channel, &output, &progress);
+                    link_stats.finish()?;
+                    Ok(received?)
+                });
+                match result {
+                    Ok(report) => break report,
+                    Err(e) if attempt < retries && is_transient(&e) => {
+                        attempt += 1;
+                        println!(
+                            "Transfer interrupted ({}), reconnecting in {}s (attempt {} of {})",
+                            e, retry_delay, attempt, retries
+                        );
+                        std::thread::sleep(std::time::Duration::from_secs(retry_delay));
+                    }
+                    Err(e) => return Err(e),
+                }
+            };
+            println!(
+                "Received tables {}..{} of {} ({} bytes in {:.3}s)",
+                report.first_table,
+                report.first_table + report.tables_transferred,
+                report.total_tables,
+                report.bytes_received,
+                report.wall_time_secs
+            );
+            if attempt > 0 {
+                println!("Reconnected {} times", attempt);
+            }
+            println!("Garbled tables saved to: {}", output.display());
+            manifest.input(Artifact::TablesHeader, &output)?;
+            if let Some(stats_json) = stats_json {
+                report.save_json(&stats_json)?;
+                println!("Transfer statistics saved to: {}", stats_json.display());
+            }
+        }
+        Commands::OtChoose {
+            inputs_file,
+            seed_file,
+            output,
+        } => {
+            println!("Loading input bits from: {}", inputs_file.display());
+            let inputs = load_inputs(&inputs_file, bus_layout.as_ref(), None)?;
+            let seed_array = load_seed(&seed_file)?;
+            let choices = OtChoices::new(inputs, &seed_array);
+
+            let output_path = output.unwrap_or_else(|| {
+                let mut path = inputs_file.clone();
+                path.set_extension("choices.json");
+                path
+            });
+            choices.save_json(&output_path)?;
+
+            println!("OT choices written:");
+            println!("  Choices: {}", choices.choices.len());
+            println!("  Results saved to: {}", output_path.display());
+        }
+        Commands::OtRespond {
+            wire_labels_file,
+            choices_file,
+            garbler_inputs_file,
+            output,
+        } => {
+            manifest.input(Artifact::Labels, &wire_labels_file)?;
+            println!("Loading wire labels from: {}", wire_labels_file.display());
+            let wire_labels = WireLabels::load(&wire_labels_file)?;
+            println!("Loading OT choices from: {}", choices_file.display());
+            let choices = OtChoices::load_json(&choices_file)?;
+            let garbler_inputs = match garbler_inputs_file {
+                Some(garbler_inputs_file) => {
+                    println!(
+                        "Loading encoded garbler inputs from: {}",
+                        garbler_inputs_file.display()
+                    );
+                    Some(EncodedInputs::load(&garbler_inputs_file)?)
+                }
+                None => None,
+            };
+
+            let message =
+                OtSenderMessage::respond(&wire_labels, &choices, garbler_inputs.as_ref())?;
+
+            let output_path = output.unwrap_or_else(|| {
+                let mut path = wire_labels_file.clone();
+                path.set_extension("ot-message.json");
+                path
+            });
+            message.save_json(&output_path)?;
+
+            println!("OT answer written:");
+            println!("  Masked labels: {}", message.masked_labels.len());
+            if !message.garbler_inputs.is_empty() {
+                println!(
+                    "  Garbler inputs sent without OT: {}",
+                    message.garbler_inputs.len()
+                );
+            }
+            println!("  Results saved to: {}", output_path.display());
+        }
+        Commands::OtFinish {
+            choices_file,
+            message_file,
+            output,
+            binary,
+        } => {
+            println!("Loading OT choices from: {}", choices_file.display());
+            let choices = OtChoices::load_json(&choices_file)?;
+            println!("Loading OT sender message from: {}", message_file.display());
+            let message = OtSenderMessage::load_json(&message_file)?;
+
+            let ot_result = choices.receive(&message)?;
+
+            let output_path = output.unwrap_or_else(|| {
+                let mut path = choices_file.clone();
+                path.set_extension(if binary { "ot.bin" } else { "ot.json" });
+                path
+            });
+            if binary {
+                ot_result.save_binary(&output_path)?;
+            } else {
+                ot_result.save_json(&output_path)?;
+            }
+
+            println!("OT simulation completed:");
+            println!("  Selected inputs: {}", ot_result.selected_inputs.len());
+            if !ot_result.garbler_inputs.is_empty() {
+                println!(
+                    "  Garbler inputs sent without OT: {}",
+                    ot_result.garbler_inputs.len()
+                );
+            }
+            println!("  Results saved to: {}", output_path.display());
+        }
+        Commands::EncodeInputs {
+            wire_labels_file,
+            inputs_file,
+            output,
+            binary,
+        } => {
+            manifest.input(Artifact::Labels, &wire_labels_file)?;
+            println!("Loading wire labels from: {}", wire_labels_file.display());
+            let wire_labels = WireLabels::load(&wire_labels_file)?;
+            let mut input_wires: Vec<_> = wire_labels.input_labels.keys().copied().collect();
+            input_wires.sort_unstable();
+            let inputs = load_inputs(&inputs_file, bus_layout.as_ref(), Some(&input_wires))?;
+
+            let encoded = wire_labels.encode_inputs(&inputs)?;
+
+            let output_path = output.unwrap_or_else(|| {
+                let mut path = wire_labels_file.clone();
+                path.set_extension(if binary { "inputs.bin" } else { "inputs.json" });
+                path
+            });
+            if binary {
+                encoded.save_binary(&output_path)?;
+            } else {
+                encoded.save_json(&output_path)?;
+            }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+            println!("Input encoding completed:");
+            println!("  Encoded garbler inputs: {}", encoded.inputs.len());
+            println!("  Results saved to: {}", output_path.display());
+        }
+        Commands::Evaluate {
+            file,
+            wire_analysis_file,
+            lower_gates,
+            ot_result_file,
+            garbled_tables_file,
+            mmap,
+            prefetch,
+            layers_file,
+            check_file,
+            stats_json,
+            binary,
+            max_memory,
+            output,
+        } => {
+            if lower_gates && manifest.path.is_some() {
+                bail!(
+                    "--lower-gates cannot be combined with --manifest; lower the circuit with `lower` first"
+                );
+            }
+            manifest.input(Artifact::Circuit, &file)?;
+            if let Some(wire_analysis_file) = &wire_analysis_file {
+                manifest.input(Artifact::WireAnalysis, wire_analysis_file)?;
+            }
+            if garbled_tables_file.as_os_str() != "-" {
+                manifest.input(Artifact::TablesHeader, &garbled_tables_file)?;
+            }
+            // Load wire usage analysis, or derive it with a first pass over the circuit
+            let wire_report = match wire_analysis_file {
+                Some(wire_analysis_file) => {
+                    println!(
+                        "Loading wire analysis from: {}",
+                        wire_analysis_file.display()
+                    );
+                    WireUsageReport::load_binary(&wire_analysis_file)?
+                }
+                None => {
+                    println!("Analyzing wire usage of: {}", file.display());
+                    analyze_wire_usage(
+                        &mut open_lowered_circuit(
+                            &file,
+                            args.lenient,
+                            args.mmap_circuit,
+                            lower_gates,
+                        )?,
+                        &progress,
+                    )?
+                }
+            };
 
-    match args.command {
-        Commands::Count { file } => {
-            // Open file and create streaming reader
-            let file_handle = File::open(&file)?;
-            let mut stream = BufferedLineStream::new(file_handle);
+            // Load OT simulation results
+            println!("Loading OT results from: {}", ot_result_file.display());
+            let ot_result = gc::ot_simulation::OTResult::load(&ot_result_file)?;
 
-            // Count gate types
-            let counts = count_gate_types(&mut stream)?;
+            // Open circuit file and create streaming reader
+            let mut stream =
+                open_lowered_circuit(&file, args.lenient, args.mmap_circuit, lower_gates)?;
 
-            // Output as JSON
-            let json_output = serde_json::to_string_pretty(&counts)?;
-            println!("{json_output}");
-        }
-        Commands::WireAnalysis { file, output } => {
-            // Open file and create streaming reader
-            let file_handle = File::open(&file)?;
-            let mut stream = BufferedLineStream::new(file_handle);
+            // Evaluate the circuit
+            let options = EvaluationOptions {
+                table_access: if mmap {
+                    TableAccess::Mmap
+                } else if prefetch {
+                    TableAccess::Prefetch
+                } else {
+                    TableAccess::Buffered
+                },
+                max_memory,
+                ..Default::default()
+            };
+            let evaluation_result = match layers_file {
+                Some(layers_file) => {
+                    println!("Loading layer analysis from: {}", layers_file.display());
+                    let layer_report = LayerReport::load_binary(&layers_file)?;
+                    evaluate_circuit_layered(
+                        &mut stream,
+                        &wire_report,
+                        &layer_report,
+                        &ot_result,
+                        &garbled_tables_file,
+                        &options,
+                        &progress,
+                    )?
+                }
+                None if garbled_tables_file.as_os_str() == "-" => evaluate_circuit_from_reader(
+                    &mut stream,
+                    wire_report,
+                    &ot_result,
+                    std::io::stdin(),
+                    &options,
+                    &progress,
+                )?,
+                None => evaluate_circuit_with_options(
+                    &mut stream,
+                    wire_report,
+                    &ot_result,
+                    &garbled_tables_file,
+                    &options,
+                    &progress,
+                )?,
+            };
 
-            // Perform wire usage analysis
-            let wire_report = analyze_wire_usage(&mut stream)?;
+            // Check output labels against the garbler's commitments and decode them
+            let evaluation_result = match check_file {
+                Some(check_file) => {
+                    println!("Loading decoding info from: {}", check_file.display());
+                    let decoding_info = DecodingInfo::load_json(&check_file)?;
+                    check_outputs(&evaluation_result, &decoding_info)?;
+                    println!(
+                        "✓ All {} output labels match the garbler's commitments",
+                        evaluation_result.output_results.len()
+                    );
+                    decode_outputs(&evaluation_result, &decoding_info)?
+                }
+                None => evaluation_result,
+            };
 
             // Determine output file
             let output_path = output.unwrap_or_else(|| {
-                let mut path = file.clone();
-                path.set_extension("wire_analysis");
-                path
+                default_output(
+                    &file,
+                    stdin_circuit,
+                    if binary { "eval.bin" } else { "eval.json" },
+                )
             });
 
-            // Save binary report
-            wire_report.save_binary(&output_path)?;
+            // Save evaluation statistics
+            if let Some(stats_json) = stats_json
+                && let Some(stats) = &evaluation_result.stats
+            {
+                stats.save_json(&stats_json)?;
+                println!("Evaluation statistics saved to: {}", stats_json.display());
+            }
 
-            // Print summary
-            println!("Wire analysis saved to: {}", output_path.display());
-            println!("Total wires: {}", wire_report.total_wires);
-            println!("Primary inputs: {}", wire_report.primary_inputs);
-            println!("Intermediate wires: {}", wire_report.intermediate_wires);
-            println!("Primary outputs: {}", wire_report.primary_outputs);
-            println!("Missing/unused wires: {}", wire_report.missing_wires_count);
+            // Save evaluation results
+            if binary {
+                evaluation_result.save_binary(&output_path)?;
+            } else {
+                evaluation_result.save_json(&output_path)?;
+            }
+            manifest.output(Artifact::Outputs, &output_path)?;
+
+            // Print summary removed
+            println!("Evaluation results saved to: {}", output_path.display());
         }
-        Commands::Garble {
+        Commands::PlainEvaluate {
             file,
-            wire_analysis_file,
-            seed_file,
+            ot_result_file,
+            wire_labels_file,
+            inputs_file,
+            compare_file,
             output,
         } => {
-            // Load wire usage analysis
-            println!(
-                "Loading wire analysis from: {}",
-                wire_analysis_file.display()
-            );
-            let wire_report = WireUsageReport::load_binary(&wire_analysis_file)?;
+            manifest.input(Artifact::Circuit, &file)?;
+            let circuit =
+                Circuit::from_stream(&mut open_circuit(&file, args.lenient, args.mmap_circuit)?)?;
+            let wire_report = analyze_circuit(&circuit)?;
+            let inputs = match (ot_result_file, inputs_file) {
+                (Some(ot_result_file), _) => {
+                    println!("Loading OT results from: {}", ot_result_file.display());
+                    let wire_labels = wire_labels_file.map(WireLabels::load).transpose()?;
+                    input_bits_from_ot(
+                        &gc::ot_simulation::OTResult::load(&ot_result_file)?,
+                        wire_labels.as_ref(),
+                    )?
+                }
+                (None, Some(inputs_file)) => {
+                    println!("Loading input bits from: {}", inputs_file.display());
+                    load_inputs(
+                        &inputs_file,
+                        bus_layout.as_ref(),
+                        Some(&wire_report.primary_input_wires),
+                    )?
+                }
+                (None, None) => bail!("Either --ot-result or --inputs is required"),
+            };
 
-            // Open file and create streaming reader
-            let file_handle = File::open(&file)?;
-            let mut stream = BufferedLineStream::new(file_handle);
+            let plain_result = PlainResult {
+                output_bits: evaluate_plain(&circuit, &wire_report, &inputs)?
+                    .into_iter()
+                    .collect(),
+            };
 
-            // Load 32-byte seed from file
-            let seed_data = std::fs::read(&seed_file)?;
-            if seed_data.len() != 32 {
-                bail!(
-                    "Seed file must contain exactly 32 bytes, got {}",
-                    seed_data.len()
+            // Compare against a garbled evaluation
+            if let Some(compare_file) = compare_file {
+                manifest.input(Artifact::Outputs, &compare_file)?;
+                println!(
+                    "Loading evaluation results from: {}",
+                    compare_file.display()
+                );
+                let evaluation_result = EvaluationResult::load(&compare_file)?;
+                let mismatched = plain_result.mismatched_outputs(&evaluation_result);
+                if !mismatched.is_empty() {
+                    bail!(
+                        "{} of {} output bits differ from the plain evaluation: {:?}",
+                        mismatched.len(),
+                        plain_result.output_bits.len(),
+                        mismatched
+                    );
+                }
+                println!(
+                    "✓ All {} output bits match the plain evaluation",
+                    plain_result.output_bits.len()
                 );
             }
-            let mut seed_array = [0u8; 32];
-            seed_array.copy_from_slice(&seed_data);
-
-            // Garble the circuit
-            let garbling_result = garble_circuit(&mut stream, &wire_report, &seed_array)?;
-
-            // Determine output paths
-            let labels_path = output
-                .as_ref()
-                .map(|p| {
-                    let mut path = p.clone();
-                    path.set_extension("labels.json");
-                    path
-                })
-                .unwrap_or_else(|| {
-                    let mut path = file.clone();
-                    path.set_extension("labels.json");
-                    path
-                });
 
-            let tables_path = output.unwrap_or_else(|| {
-                let mut path = file.clone();
-                path.set_extension("garbled");
-                path
-            });
+            // Determine output file
+            let output_path =
+                output.unwrap_or_else(|| default_output(&file, stdin_circuit, "plain.json"));
 
-            // Save results
-            garbling_result.save(&labels_path, &tables_path)?;
+            plain_result.save_json(&output_path)?;
+            println!("Plain results saved to: {}", output_path.display());
 
-            println!("Garbling completed:");
-            println!("  Wire labels saved to: {}", labels_path.display());
-            println!("  Garbled tables saved to: {}", tables_path.display());
-            println!(
-                "  Input wires: {}",
-                garbling_result.wire_labels.input_labels.len()
-            );
-            println!(
-                "  Output wires: {}",
-                garbling_result.wire_labels.output_labels.len()
-            );
-            println!("  AND gates: {}", garbling_result.garbled_tables.len());
+            if let Some(bus_layout) = &bus_layout {
+                print_bus_values(&plain_result.bus_values(&bus_layout.outputs)?)?;
+            }
         }
-        Commands::OtSimulate {
+        Commands::DecodingInfo {
             wire_labels_file,
-            seed_file,
             output,
         } => {
+            manifest.input(Artifact::Labels, &wire_labels_file)?;
             // Load wire labels from garbler output
             println!("Loading wire labels from: {}", wire_labels_file.display());
-            let wire_labels = WireLabels::load_json(&wire_labels_file)?;
-
-            // Load 32-byte seed from file
-            let seed_data = std::fs::read(&seed_file)?;
-            if seed_data.len() != 32 {
-                bail!(
-                    "Seed file must contain exactly 32 bytes, got {}",
-                    seed_data.len()
-                );
-            }
-            let mut seed_array = [0u8; 32];
-            seed_array.copy_from_slice(&seed_data);
+            let wire_labels = WireLabels::load(&wire_labels_file)?;
 
-            // Simulate OT protocol
-            let ot_result = simulate_ot(&wire_labels, &seed_array)?;
+            let decoding_info = DecodingInfo::from_wire_labels(&wire_labels);
 
             // Determine output file
             let output_path = output.unwrap_or_else(|| {
                 let mut path = wire_labels_file.clone();
-                path.set_extension("ot.json");
+                path.set_extension("decoding.json");
                 path
             });
 
-            // Save OT results
-            ot_result.save_json(&output_path)?;
+            decoding_info.save_json(&output_path)?;
+            println!("Decoding information saved to: {}", output_path.display());
+        }
+        Commands::Decode {
+            eval_result_file,
+            decoding_info_file,
+            output,
+            output_buses,
+        } => {
+            manifest.input(Artifact::Outputs, &eval_result_file)?;
+            println!(
+                "Loading evaluation results from: {}",
+                eval_result_file.display()
+            );
+            let evaluation_result = EvaluationResult::load(&eval_result_file)?;
 
-            println!("OT simulation completed:");
-            println!("  Selected inputs: {}", ot_result.selected_inputs.len());
-            println!("  Results saved to: {}", output_path.display());
+            println!(
+                "Loading decoding information from: {}",
+                decoding_info_file.display()
+            );
+            let decoding_info = DecodingInfo::load_json(&decoding_info_file)?;
+
+            let decoded = decode_outputs(&evaluation_result, &decoding_info)?;
+
+            // Determine output file
+            let output_path = output.unwrap_or_else(|| {
+                let mut path = eval_result_file.clone();
+                path.set_extension("decoded.json");
+                path
+            });
+
+            decoded.save_json(&output_path)?;
+            println!("Decoded results saved to: {}", output_path.display());
+
+            // Print the layout's output buses and the requested ones as hex integers
+            let buses: Vec<Bus> = bus_layout
+                .into_iter()
+                .flat_map(|bus_layout| bus_layout.outputs)
+                .chain(output_buses)
+                .collect();
+            if !buses.is_empty() {
+                print_bus_values(&decode_buses(&decoded, &buses)?)?;
+            }
         }
-        Commands::Evaluate {
+        Commands::Verify {
             file,
             wire_analysis_file,
-            ot_result_file,
+            wire_labels_file,
             garbled_tables_file,
-            output,
         } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            manifest.input(Artifact::WireAnalysis, &wire_analysis_file)?;
+            manifest.input(Artifact::Labels, &wire_labels_file)?;
+            manifest.input(Artifact::TablesHeader, &garbled_tables_file)?;
             // Load wire usage analysis
             println!(
                 "Loading wire analysis from: {}",
@@ -329,29 +3768,87 @@ fn main() -> Result<()> {
             );
             let wire_report = WireUsageReport::load_binary(&wire_analysis_file)?;
 
-            // Load OT simulation results
-            println!("Loading OT results from: {}", ot_result_file.display());
-            let ot_result = gc::ot_simulation::OTResult::load_json(&ot_result_file)?;
+            // Load wire labels from garbler output
+            println!("Loading wire labels from: {}", wire_labels_file.display());
+            let wire_labels = WireLabels::load(&wire_labels_file)?;
 
             // Open circuit file and create streaming reader
-            let file_handle = File::open(&file)?;
-            let mut stream = BufferedLineStream::new(file_handle);
+            let mut stream = open_circuit(&file, args.lenient, args.mmap_circuit)?;
 
-            // Evaluate the circuit
-            let evaluation_result =
-                evaluate_circuit(&mut stream, &wire_report, &ot_result, &garbled_tables_file)?;
+            let report = verify_garbled_tables(
+                &mut stream,
+                &wire_report,
+                &wire_labels,
+                &garbled_tables_file,
+                &progress,
+            )?;
 
-            // Determine output file
-            let output_path = output.unwrap_or_else(|| {
-                let mut path = file.clone();
-                path.set_extension("eval.json");
-                path
-            });
+            println!("Garbling verification:");
+            println!("  AND tables checked: {}", report.and_gates_checked);
+            println!("  Failed tables: {}", report.failed_gates.len());
+            println!(
+                "  Output label mismatches: {}",
+                report.output_mismatches.len()
+            );
+            println!("  Unused tables: {}", report.unused_tables);
 
-            // Save evaluation results
-            evaluation_result.save_json(&output_path)?;
+            if !report.is_valid() {
+                bail!("Garbled tables failed verification");
+            }
+        }
+        Commands::GarbleStacked {
+            file,
+            seed_file,
+            output,
+            garbler_inputs,
+        } => {
+            let circuit = StackedCircuit::load(&file)?;
+            let seed_array = load_seed(&seed_file)?;
+            let partition = garbler_inputs
+                .map(|spec| InputPartition::parse(&spec))
+                .transpose()?;
+            let garbling = garble_stacked(&circuit, &seed_array, partition.as_ref())?;
 
-            // Print summary removed
+            let material_path = output.unwrap_or_else(|| default_output(&file, false, "material"));
+            let mut labels_path = material_path.clone();
+            labels_path.set_extension("labels.json");
+            garbling.wire_labels.save_json(&labels_path)?;
+            garbling.material.save(&material_path)?;
+
+            let wire_labels = &garbling.wire_labels;
+            let cost = circuit.cost();
+            println!("Stacked garbling completed:");
+            println!("  Wire labels saved to: {}", labels_path.display());
+            println!("  Stacked material saved to: {}", material_path.display());
+            println!("  Session id: {}", wire_labels.session_id);
+            println!(
+                "  Input wires: {} ({} garbler, {} evaluator)",
+                wire_labels.input_labels.len(),
+                wire_labels.garbler_input_wires.len(),
+                wire_labels.input_labels.len() - wire_labels.garbler_input_wires.len()
+            );
+            println!("  Output wires: {}", wire_labels.output_labels.len());
+            println!("  Conditionals: {}", garbling.material.conditionals.len());
+            println!("  AND tables outside conditionals: {}", cost.and_tables);
+            println!(
+                "  Branch tables: {} stacked ({} side by side)",
+                cost.stacked_tables, cost.unstacked_tables
+            );
+            println!("  Branch interface tables: {}", cost.interface_tables);
+        }
+        Commands::EvaluateStacked {
+            file,
+            ot_result_file,
+            material_file,
+            output,
+        } => {
+            let circuit = StackedCircuit::load(&file)?;
+            let ot_result = gc::ot_simulation::OTResult::load(&ot_result_file)?;
+            let material = StackedMaterial::load(&material_file)?;
+            let evaluation_result = evaluate_stacked(&circuit, &material, &ot_result)?;
+
+            let output_path = output.unwrap_or_else(|| default_output(&file, false, "eval.json"));
+            evaluation_result.save_json(&output_path)?;
             println!("Evaluation results saved to: {}", output_path.display());
         }
         Commands::MemorySimulation {
@@ -359,6 +3856,8 @@ fn main() -> Result<()> {
             wire_analysis_file,
             output,
         } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            manifest.input(Artifact::WireAnalysis, &wire_analysis_file)?;
             // Load wire usage analysis
             println!(
                 "Loading wire analysis from: {}",
@@ -367,21 +3866,17 @@ fn main() -> Result<()> {
             let wire_report = WireUsageReport::load_binary(&wire_analysis_file)?;
 
             // Open file and create streaming reader
-            let file_handle = File::open(&file)?;
-            let mut stream = BufferedLineStream::new(file_handle);
+            let mut stream = open_circuit(&file, args.lenient, args.mmap_circuit)?;
 
             // Simulate memory usage
-            let simulation_result = simulate_memory_usage(&mut stream, &wire_report)?;
+            let simulation_result = simulate_memory_usage(&mut stream, &wire_report, &progress)?;
 
             // Print summary
             simulation_result.print_summary();
 
             // Determine output file
-            let output_path = output.unwrap_or_else(|| {
-                let mut path = file.clone();
-                path.set_extension("memory.csv");
-                path
-            });
+            let output_path =
+                output.unwrap_or_else(|| default_output(&file, stdin_circuit, "memory.csv"));
 
             // Export CSV results
             simulation_result.export_csv(&output_path)?;
@@ -396,6 +3891,8 @@ fn main() -> Result<()> {
             wire_analysis_file,
             output,
         } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            manifest.input(Artifact::WireAnalysis, &wire_analysis_file)?;
             // Load wire usage analysis
             println!(
                 "Loading wire analysis from: {}",
@@ -404,18 +3901,14 @@ fn main() -> Result<()> {
             let wire_report = WireUsageReport::load_binary(&wire_analysis_file)?;
 
             // Open file and create streaming reader
-            let file_handle = File::open(&file)?;
-            let mut stream = BufferedLineStream::new(file_handle);
+            let mut stream = open_circuit(&file, args.lenient, args.mmap_circuit)?;
 
             // Analyze single-use gates
-            let analysis_result = analyze_single_use_gates(&mut stream, &wire_report)?;
+            let analysis_result = analyze_single_use_gates(&mut stream, &wire_report, &progress)?;
 
             // Determine output file
-            let output_path = output.unwrap_or_else(|| {
-                let mut path = file.clone();
-                path.set_extension("single_use.json");
-                path
-            });
+            let output_path =
+                output.unwrap_or_else(|| default_output(&file, stdin_circuit, "single_use.json"));
 
             // Save analysis results as JSON
             let json_output = serde_json::to_string_pretty(&analysis_result)?;
@@ -436,7 +3929,542 @@ fn main() -> Result<()> {
             );
             println!("  Results saved to: {}", output_path.display());
         }
+        Commands::Analyze { file, output } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            let analysis = analyze_fused(
+                &mut open_circuit(&file, args.lenient, args.mmap_circuit)?,
+                &progress,
+            )?;
+
+            // Every output is named like the one of its separate command
+            let output_path = |extension: &str| match &output {
+                Some(prefix) => default_output(prefix, false, extension),
+                None => default_output(&file, stdin_circuit, extension),
+            };
+            let counts_path = output_path("counts.json");
+            std::fs::write(
+                &counts_path,
+                serde_json::to_string_pretty(&analysis.gate_counts)?,
+            )?;
+            let wire_analysis_path = output_path("wire_analysis");
+            analysis.wire_report.save_binary(&wire_analysis_path)?;
+            manifest.output(Artifact::WireAnalysis, &wire_analysis_path)?;
+            let single_use_path = output_path("single_use.json");
+            std::fs::write(
+                &single_use_path,
+                serde_json::to_string_pretty(&analysis.single_use)?,
+            )?;
+            let memory_path = output_path("memory.csv");
+            analysis.memory.export_csv(&memory_path)?;
+
+            let wire_report = &analysis.wire_report;
+            println!("Gates: {}", analysis.memory.total_gates_processed);
+            println!("Total wires: {}", wire_report.total_wires);
+            println!("Primary inputs: {}", wire_report.primary_inputs);
+            println!("Primary outputs: {}", wire_report.primary_outputs);
+            println!(
+                "Single-use wires: {} ({} AND, {} XOR)",
+                analysis.single_use.total_single_use_wires,
+                analysis.single_use.single_use_and_gates,
+                analysis.single_use.single_use_xor_gates
+            );
+            println!("Maximum live wires: {}", analysis.memory.max_live_wires);
+            println!("Gate counts saved to: {}", counts_path.display());
+            println!("Wire analysis saved to: {}", wire_analysis_path.display());
+            println!(
+                "Single-use analysis saved to: {}",
+                single_use_path.display()
+            );
+            println!("Memory simulation saved to: {}", memory_path.display());
+        }
+        Commands::WireLifetime {
+            file,
+            output,
+            peak_wires,
+        } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            let lifetimes = analyze_wire_lifetimes(
+                &mut open_circuit(&file, args.lenient, args.mmap_circuit)?,
+                &progress,
+            )?;
+
+            let output_path = |extension: &str| match &output {
+                Some(prefix) => default_output(prefix, false, extension),
+                None => default_output(&file, stdin_circuit, extension),
+            };
+            let lifetimes_path = output_path("lifetimes");
+            lifetimes.save_binary(&lifetimes_path)?;
+            let summary_path = output_path("lifetimes.csv");
+            lifetimes.export_summary_csv(&summary_path)?;
+
+            let (point, live_wires) = lifetimes.peak();
+            println!("Wire lifetimes saved to: {}", lifetimes_path.display());
+            println!("Lifetime summary saved to: {}", summary_path.display());
+            println!("Peak: {live_wires} live wires after {point} gates");
+            println!("Longest held wires at the peak:");
+            for wire_id in lifetimes.live_wires_at(point).into_iter().take(peak_wires) {
+                let gate = |gate_index: u64, none: &str| match gate_index {
+                    NO_GATE => none.to_string(),
+                    gate_index => format!("gate {gate_index}"),
+                };
+                println!(
+                    "  wire {}: written by {}, last read by {}",
+                    wire_id,
+                    gate(lifetimes.produced[wire_id as usize], "primary input"),
+                    gate(lifetimes.last_use[wire_id as usize], "no gate")
+                );
+            }
+        }
+        Commands::Import {
+            input,
+            format,
+            output,
+        } => {
+            let circuit = match format {
+                ImportFormat::ScaleMamba => {
+                    import_scale_mamba(&mut open_circuit(&input, args.lenient, args.mmap_circuit)?)?
+                }
+                ImportFormat::Aiger => import_aiger(open_input(&input)?)?,
+                ImportFormat::Blif => {
+                    import_blif(&mut open_circuit(&input, args.lenient, args.mmap_circuit)?)?
+                }
+                ImportFormat::Json => {
+                    let json: JsonCircuit =
+                        serde_json::from_reader(std::io::BufReader::new(open_input(&input)?))?;
+                    let circuit = json.to_circuit()?;
+                    // Buses that Bristol Fashion cannot declare go to a layout for --buses
+                    if circuit.io.is_none() && !(json.inputs.is_empty() && json.outputs.is_empty())
+                    {
+                        let layout_path = output.with_extension("buses.json");
+                        json.bus_layout().save_json(&layout_path)?;
+                        println!("Bus layout saved to: {}", layout_path.display());
+                    }
+                    circuit
+                }
+            };
+
+            let mut writer = std::io::BufWriter::new(File::create(&output)?);
+            circuit.write_bristol(&mut writer)?;
+            manifest.output(Artifact::Circuit, &output)?;
+
+            println!("Imported circuit:");
+            println!("  Gates: {}", circuit.gates.len());
+            println!("  Wires: {}", circuit.num_wires);
+            println!("  Saved to: {}", output.display());
+        }
+        Commands::Convert {
+            file,
+            output,
+            index,
+        } => {
+            // The magic bytes tell a binary circuit from Bristol text
+            let mut input = open_input(&file)?;
+            let mut magic = Vec::with_capacity(BINARY_CIRCUIT_MAGIC.len());
+            (&mut input)
+                .take(BINARY_CIRCUIT_MAGIC.len() as u64)
+                .read_to_end(&mut magic)?;
+            let to_bristol = magic == BINARY_CIRCUIT_MAGIC;
+            let input: Box<dyn Read> = Box::new(std::io::Cursor::new(magic).chain(input));
+
+            let output_path = output.unwrap_or_else(|| {
+                default_output(
+                    &file,
+                    stdin_circuit,
+                    if to_bristol { "bristol" } else { "bin" },
+                )
+            });
+            if index && to_bristol {
+                bail!("--index only applies when converting to the binary format");
+            }
+            let writer = std::io::BufWriter::new(File::create(&output_path)?);
+            let header = if to_bristol {
+                convert_binary_to_bristol(input, writer, &progress)?
+            } else {
+                let mut stream = BufferedLineStream::from_reader(input).with_lenient(args.lenient);
+                let (header, gate_index) =
+                    convert_bristol_to_binary(&mut stream, writer, &progress)?;
+                if index {
+                    let index_path = default_output(&output_path, false, "idx");
+                    gate_index.save_binary(&index_path)?;
+                    println!("Gate index saved to: {}", index_path.display());
+                }
+                header
+            };
+
+            println!("Converted circuit:");
+            println!("  Gates: {}", header.num_gates);
+            println!("  Wires: {}", header.num_wires);
+            let output_size = std::fs::metadata(&output_path)?.len();
+            match std::fs::metadata(&file) {
+                Ok(metadata) if !stdin_circuit => println!(
+                    "  Size: {} -> {} bytes ({:.1}%)",
+                    metadata.len(),
+                    output_size,
+                    100.0 * output_size as f64 / metadata.len().max(1) as f64
+                ),
+                _ => println!("  Size: {} bytes", output_size),
+            }
+            println!("  Saved to: {}", output_path.display());
+        }
+        Commands::Inspect {
+            file,
+            gate,
+            count,
+            index,
+        } => {
+            let index_path = index
+                .or_else(|| Some(default_output(&file, false, "idx")).filter(|path| path.exists()));
+            let reader = std::io::BufReader::new(File::open(&file)?);
+            let mut binary = match &index_path {
+                Some(index_path) => BinaryCircuitReader::seek_to_gate(
+                    reader,
+                    &GateIndex::load_binary(index_path)?,
+                    gate,
+                )?,
+                None => BinaryCircuitReader::new(reader)?,
+            };
+            let header = binary.header();
+            println!("Binary circuit: {}", file.display());
+            println!("  Gates: {}", header.num_gates);
+            println!("  Wires: {}", header.num_wires);
+            if let Some(io) = &header.io {
+                println!("  Input widths: {:?}", io.input_widths);
+                println!("  Output widths: {:?}", io.output_widths);
+            }
+            if gate > header.num_gates {
+                bail!(
+                    "Gate {} is beyond the {} gates of the circuit",
+                    gate,
+                    header.num_gates
+                );
+            }
+
+            // Without an index, decode everything up to the first gate
+            while binary.position() < gate {
+                binary.next().transpose()?;
+            }
+            println!();
+            for (number, gate) in (gate..).zip(binary.by_ref().take(count as usize)) {
+                println!("{:>10}: {}", number, gate?);
+            }
+        }
+        Commands::Hash { file, tables } => {
+            let digest = hash_circuit(open_input(&file)?)?;
+            println!("{}  {}", hex_string(&digest), file.display());
+            if let Some(tables) = tables {
+                let reader = GarbledTableReader::open(&tables, TableAccess::Buffered)?;
+                let Some(header) = reader.header() else {
+                    bail!("{} has no header recording its circuit", tables.display());
+                };
+                header.check_circuit(&digest)?;
+                println!("Garbled tables {} match the circuit", tables.display());
+            }
+        }
+        Commands::Export { file, output } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            let circuit =
+                Circuit::from_stream(&mut open_circuit(&file, args.lenient, args.mmap_circuit)?)?;
+            let json = JsonCircuit::from_circuit(&circuit, bus_layout.as_ref());
+
+            let output_path =
+                output.unwrap_or_else(|| default_output(&file, stdin_circuit, "circuit.json"));
+            json.save_json(&output_path)?;
+            println!("JSON circuit saved to: {}", output_path.display());
+        }
+        Commands::Compact {
+            file,
+            output,
+            remap,
+        } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            let circuit =
+                Circuit::from_stream(&mut open_circuit(&file, args.lenient, args.mmap_circuit)?)?;
+            let (compacted, wire_remap) = compact_wires(&circuit)?;
+
+            let output_path =
+                output.unwrap_or_else(|| default_output(&file, stdin_circuit, "compact.bristol"));
+            let mut writer = std::io::BufWriter::new(File::create(&output_path)?);
+            compacted.write_bristol(&mut writer)?;
+            writer.flush()?;
+            manifest.output(Artifact::Circuit, &output_path)?;
+            let remap_path =
+                remap.unwrap_or_else(|| default_output(&file, stdin_circuit, "remap.json"));
+            wire_remap.save_json(&remap_path)?;
+
+            println!("Compacted circuit:");
+            println!("  Wires: {} -> {}", circuit.num_wires, compacted.num_wires);
+            println!("  Gates: {}", compacted.gates.len());
+            println!("  Saved to: {}", output_path.display());
+            println!("  Wire remap saved to: {}", remap_path.display());
+        }
+        Commands::Compose {
+            first,
+            second,
+            wiring,
+            output,
+            remap,
+        } => {
+            let first_circuit =
+                Circuit::from_stream(&mut open_circuit(&first, args.lenient, args.mmap_circuit)?)?;
+            let second_circuit =
+                Circuit::from_stream(&mut open_circuit(&second, args.lenient, args.mmap_circuit)?)?;
+            let (composed, composition_remap) = compose_circuits(
+                &first_circuit,
+                &second_circuit,
+                &WiringSpec::load_json(&wiring)?,
+            )?;
+
+            let mut writer = std::io::BufWriter::new(File::create(&output)?);
+            composed.write_bristol(&mut writer)?;
+            writer.flush()?;
+            manifest.output(Artifact::Circuit, &output)?;
+            let remap_path = remap.unwrap_or_else(|| default_output(&output, false, "remap.json"));
+            composition_remap.save_json(&remap_path)?;
+
+            println!("Composed circuit:");
+            println!(
+                "  Gates: {} + {} = {}",
+                first_circuit.gates.len(),
+                second_circuit.gates.len(),
+                composed.gates.len()
+            );
+            println!("  Wires: {}", composed.num_wires);
+            println!("  Saved to: {}", output.display());
+            println!("  Wire remap saved to: {}", remap_path.display());
+        }
+        Commands::Prune {
+            file,
+            wire_analysis_file,
+            output,
+            report,
+        } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            let circuit =
+                Circuit::from_stream(&mut open_circuit(&file, args.lenient, args.mmap_circuit)?)?;
+            let wire_report = match &wire_analysis_file {
+                Some(wire_analysis_file) => {
+                    manifest.input(Artifact::WireAnalysis, wire_analysis_file)?;
+                    WireUsageReport::load_binary(wire_analysis_file)?
+                }
+                None => analyze_circuit(&circuit)?,
+            };
+            let (pruned, dead_gates) = eliminate_dead_gates(&circuit, &wire_report)?;
+
+            let output_path =
+                output.unwrap_or_else(|| default_output(&file, stdin_circuit, "pruned.bristol"));
+            let mut writer = std::io::BufWriter::new(File::create(&output_path)?);
+            pruned.write_bristol(&mut writer)?;
+            writer.flush()?;
+            manifest.output(Artifact::Circuit, &output_path)?;
+            let report_path =
+                report.unwrap_or_else(|| default_output(&file, stdin_circuit, "dead_gates.json"));
+            dead_gates.save_json(&report_path)?;
+
+            println!("Dead gate elimination:");
+            println!(
+                "  Gates: {} -> {}",
+                dead_gates.total_gates,
+                pruned.gates.len()
+            );
+            println!("  Dead AND gates: {}", dead_gates.dead_and_gates);
+            println!("  Dead XOR gates: {}", dead_gates.dead_xor_gates);
+            println!("  Dead other gates: {}", dead_gates.dead_other_gates);
+            println!("  Saved to: {}", output_path.display());
+            println!("  Report saved to: {}", report_path.display());
+        }
+        Commands::Lower { file, output } => {
+            let report = scan_gates(&mut open_circuit(&file, args.lenient, args.mmap_circuit)?)?;
+            let mut reader = LoweringReader::new(
+                open_circuit(&file, args.lenient, args.mmap_circuit)?,
+                &report,
+            )?;
+
+            let output_path =
+                output.unwrap_or_else(|| default_output(&file, stdin_circuit, "lowered.bristol"));
+            let mut writer = std::io::BufWriter::new(File::create(&output_path)?);
+            std::io::copy(&mut reader, &mut writer)?;
+            writer.flush()?;
+            manifest.output(Artifact::Circuit, &output_path)?;
+
+            println!("Lowered circuit:");
+            println!(
+                "  Rewritten gates: {} NAND, {} OR, {} NOR, {} XNOR, {} INV",
+                report.nand_gates,
+                report.or_gates,
+                report.nor_gates,
+                report.xnor_gates,
+                report.inv_gates
+            );
+            println!(
+                "  Gates: {} -> {}",
+                report.header.num_gates,
+                report.header.num_gates + report.added_wires()
+            );
+            println!(
+                "  Wires: {} -> {}",
+                report.header.num_wires,
+                report.header.num_wires + report.added_wires()
+            );
+            println!("  Saved to: {}", output_path.display());
+        }
+        Commands::Generate {
+            component,
+            width,
+            output,
+        } => {
+            let circuit = match component {
+                Component::RippleAdder => ripple_carry_adder(width)?,
+                Component::LookaheadAdder => carry_lookahead_adder(width)?,
+                Component::Multiplier => multiplier(width)?,
+                Component::Comparator => comparator(width)?,
+                Component::Equality => equality(width)?,
+                Component::Mux => mux_tree(width)?,
+            };
+
+            let mut writer = std::io::BufWriter::new(File::create(&output)?);
+            circuit.write_bristol(&mut writer)?;
+            manifest.output(Artifact::Circuit, &output)?;
+
+            let and_gates = circuit
+                .gates
+                .iter()
+                .filter(|gate| gate.gate_type == GateType::And)
+                .count();
+            println!("Generated circuit:");
+            println!("  Gates: {} ({} AND)", circuit.gates.len(), and_gates);
+            println!("  Wires: {}", circuit.num_wires);
+            println!("  Saved to: {}", output.display());
+        }
+        Commands::Random {
+            gates,
+            inputs,
+            outputs,
+            and_ratio,
+            depth,
+            fan_out,
+            seed,
+            binary,
+            output,
+        } => {
+            let circuit = RandomCircuit::new(RandomCircuitParams {
+                num_gates: gates,
+                num_inputs: inputs,
+                num_outputs: outputs,
+                and_ratio,
+                depth,
+                fan_out,
+                seed,
+            })?;
+            let num_wires = circuit.num_wires();
+
+            let writer = std::io::BufWriter::new(File::create(&output)?);
+            if binary {
+                let header = BinaryCircuitHeader {
+                    num_gates: gates,
+                    num_wires,
+                    io: Some(circuit.io()),
+                };
+                let mut binary_writer = BinaryCircuitWriter::new(writer, header)?;
+                for gate in circuit {
+                    binary_writer.write_gate(&gate)?;
+                }
+                binary_writer.finish()?.flush()?;
+            } else {
+                let mut bristol_writer =
+                    BristolWriter::new(writer, gates, num_wires, Some(&circuit.io()))?;
+                bristol_writer.write_gates(circuit)?;
+                bristol_writer.finish()?;
+            }
+            manifest.output(Artifact::Circuit, &output)?;
+
+            println!("Random circuit:");
+            println!("  Gates: {}", gates);
+            println!("  Wires: {}", num_wires);
+            println!("  Saved to: {}", output.display());
+        }
+        Commands::CheckOrder {
+            file,
+            reorder,
+            output,
+        } => {
+            manifest.input(Artifact::Circuit, &file)?;
+            let mut stream = open_circuit(&file, args.lenient, args.mmap_circuit)?;
+            let report = check_topological_order(&mut stream, &progress)?;
+
+            println!("Gate order check:");
+            println!("  Gates: {}", report.num_gates);
+            println!(
+                "  Wires read before they are written: {}",
+                report.num_violations
+            );
+            for violation in report.violations.iter().take(10) {
+                println!(
+                    "    Gate {} reads wire {} before gate {} writes it",
+                    violation.gate, violation.wire, violation.producer
+                );
+            }
+            if report.num_violations > 10 {
+                println!("    ... and {} more", report.num_violations - 10);
+            }
+
+            if reorder {
+                let circuit = Circuit::from_stream(&mut open_circuit(
+                    &file,
+                    args.lenient,
+                    args.mmap_circuit,
+                )?)?;
+                let sorted = topological_sort(&circuit)?;
+                let output_path = output
+                    .unwrap_or_else(|| default_output(&file, stdin_circuit, "sorted.bristol"));
+                let mut writer = std::io::BufWriter::new(File::create(&output_path)?);
+                sorted.write_bristol(&mut writer)?;
+                writer.flush()?;
+                manifest.output(Artifact::Circuit, &output_path)?;
+                println!("  Sorted circuit saved to: {}", output_path.display());
+            } else if !report.is_topological() {
+                bail!("Gates are not in topological order; rerun with --reorder to sort them");
+            } else {
+                println!("✓ Gates are in topological order");
+            }
+        }
     }
 
-    Ok(())
+    manifest.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        // Checks what clap otherwise only checks when a command is run, such
+        // as clashing short flags or missing conflicting arguments
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn test_decode_bus_with_global_bus_layout() {
+        // The global --buses and decode's -b are read as different types, so
+        // they need different ids; clap only notices the clash on access
+        let args = Args::try_parse_from([
+            "gc-cli",
+            "--buses",
+            "layout.json",
+            "decode",
+            "-e",
+            "eval.json",
+            "-d",
+            "dec.json",
+            "-b",
+            "sum=440..503",
+        ])
+        .unwrap();
+        assert_eq!(args.bus_layout, Some(PathBuf::from("layout.json")));
+        let Commands::Decode { output_buses, .. } = args.command else {
+            panic!("expected the decode command");
+        };
+        assert_eq!(output_buses.len(), 1);
+    }
 }
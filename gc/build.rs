@@ -0,0 +1,64 @@
+//! Generates the gRPC client and server of the `rpc` feature
+//!
+//! The messages are written by hand in `src/rpc.rs` after
+//! `proto/garbler.proto`, so building needs no `protoc`; only the service
+//! plumbing is generated here.
+
+fn main() {
+    #[cfg(feature = "rpc")]
+    generate_rpc_service();
+}
+
+#[cfg(feature = "rpc")]
+fn generate_rpc_service() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    println!("cargo:rerun-if-changed=build.rs");
+    let method = |name: &str, route: &str, input: &str, output: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(format!("crate::rpc::{}", input))
+            .output_type(format!("crate::rpc::{}", output))
+            .codec_path("tonic_prost::ProstCodec")
+    };
+    let service = Service::builder()
+        .name("Garbler")
+        .package("gc.rpc")
+        .method(
+            method(
+                "submit_inputs",
+                "SubmitInputs",
+                "SubmitInputsRequest",
+                "SessionInfo",
+            )
+            .build(),
+        )
+        .method(
+            method("run_ot", "RunOt", "OtMessage", "OtMessage")
+                .client_streaming()
+                .server_streaming()
+                .build(),
+        )
+        .method(
+            method(
+                "stream_tables",
+                "StreamTables",
+                "StreamTablesRequest",
+                "TableChunk",
+            )
+            .server_streaming()
+            .build(),
+        )
+        .method(
+            method(
+                "get_result",
+                "GetResult",
+                "GetResultRequest",
+                "GetResultResponse",
+            )
+            .build(),
+        )
+        .build();
+    Builder::new().compile(&[service]);
+}
@@ -0,0 +1,369 @@
+use crate::error::{GcError, Result};
+use std::io::Read;
+
+use crate::emitter::GateEmitter;
+use crate::gate::{Circuit, WireId};
+
+/// Kind of file named in import errors
+const FORMAT: &str = "AIGER file";
+
+/// What an AIGER variable stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Node {
+    /// Not defined by the file; only the constant variable 0 may be read as such
+    Undefined,
+    /// Primary input, with its wire
+    Input(WireId),
+    /// AND of two literals
+    And(u64, u64),
+}
+
+/// Import a combinational AIGER and-inverter graph, in ASCII (`aag`) or binary (`aig`) form
+///
+/// AND nodes become AND gates and inverted literals become XORs with a
+/// constant-one wire, which cost nothing to garble. AIGER variables may be
+/// numbered with gaps and, in ASCII files, defined in any order; the circuit
+/// numbers its wires contiguously in topological order instead: the inputs
+/// take the first wires in declaration order, then the gates follow, and
+/// every output gets a wire of its own at the end, as in Bristol Fashion.
+/// The circuit declares one input and one output bus spanning all inputs and
+/// outputs. Symbol tables and comments are ignored.
+///
+/// # Arguments
+/// * `reader` - AIGER file
+///
+/// # Returns
+/// * `Ok(Circuit)` - The circuit with its input/output declarations
+/// * `Err(GcError)` - Malformed file, or a sequential circuit with latches or properties
+pub fn import_aiger<R: Read>(mut reader: R) -> Result<Circuit> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let mut cursor = Cursor {
+        bytes: &bytes,
+        position: 0,
+        line_number: 0,
+    };
+
+    let header = cursor.line()?;
+    let mut fields = header.split_whitespace();
+    let binary = match fields.next() {
+        Some("aig") => true,
+        Some("aag") => false,
+        _ => {
+            return Err(GcError::invalid_format(
+                FORMAT,
+                format!("expected an 'aig' or 'aag' header, got '{}'", header),
+            ));
+        }
+    };
+    let counts = fields
+        .map(|field| {
+            field.parse::<u64>().map_err(|_| {
+                GcError::invalid_format(FORMAT, format!("invalid header '{}'", header))
+            })
+        })
+        .collect::<Result<Vec<u64>>>()?;
+    let &[max_var, num_inputs, num_latches, num_outputs, num_ands, ..] = counts.as_slice() else {
+        return Err(GcError::invalid_format(
+            FORMAT,
+            format!("expected 'M I L O A' in the header, got '{}'", header),
+        ));
+    };
+    if num_latches != 0 {
+        return Err(GcError::invalid_format(
+            FORMAT,
+            format!(
+                "{} latches: only combinational circuits can be garbled",
+                num_latches
+            ),
+        ));
+    }
+    if counts[5..].iter().any(|&count| count != 0) {
+        return Err(GcError::invalid_format(
+            FORMAT,
+            "bad state, constraint, justice and fairness properties are not supported",
+        ));
+    }
+    if binary && max_var != num_inputs + num_ands {
+        return Err(GcError::invalid_format(
+            FORMAT,
+            format!("binary header must have M = I + L + A, got '{}'", header),
+        ));
+    }
+    let num_vars = usize::try_from(max_var)
+        .ok()
+        .and_then(|max_var| max_var.checked_add(1))
+        .ok_or_else(|| {
+            GcError::invalid_format(FORMAT, format!("too many variables: {}", max_var))
+        })?;
+
+    let mut nodes = vec![Node::Undefined; num_vars];
+    let mut and_vars = Vec::with_capacity(num_ands as usize);
+    let mut define = |lhs: u64, node: Node, line_number: u64| -> Result<()> {
+        let var = check_literal(lhs, max_var, line_number)?;
+        if lhs & 1 == 1 || var == 0 {
+            return Err(GcError::invalid_format(
+                FORMAT,
+                format!(
+                    "line {}: {} is not a positive variable literal",
+                    line_number, lhs
+                ),
+            ));
+        }
+        if nodes[var] != Node::Undefined {
+            return Err(GcError::invalid_format(
+                FORMAT,
+                format!("line {}: variable {} is defined twice", line_number, var),
+            ));
+        }
+        nodes[var] = node;
+        Ok(())
+    };
+
+    for input in 0..num_inputs {
+        if binary {
+            define(2 * (input + 1), Node::Input(input), 0)?;
+        } else {
+            let [literal] = cursor.literals()?;
+            define(literal, Node::Input(input), cursor.line_number)?;
+        }
+    }
+    let mut outputs = Vec::with_capacity(num_outputs as usize);
+    for _ in 0..num_outputs {
+        let [literal] = cursor.literals()?;
+        check_literal(literal, max_var, cursor.line_number)?;
+        outputs.push(literal);
+    }
+    for and in 0..num_ands {
+        let (lhs, rhs0, rhs1) = if binary {
+            let lhs = 2 * (num_inputs + and + 1);
+            let (delta0, delta1) = (cursor.delta()?, cursor.delta()?);
+            let rhs0 = lhs.checked_sub(delta0).filter(|_| delta0 > 0);
+            let rhs1 = rhs0.and_then(|rhs0| rhs0.checked_sub(delta1));
+            let (Some(rhs0), Some(rhs1)) = (rhs0, rhs1) else {
+                return Err(GcError::invalid_format(
+                    FORMAT,
+                    format!("invalid delta encoding of AND gate {}", lhs),
+                ));
+            };
+            (lhs, rhs0, rhs1)
+        } else {
+            let [lhs, rhs0, rhs1] = cursor.literals()?;
+            (lhs, rhs0, rhs1)
+        };
+        check_literal(rhs0, max_var, cursor.line_number)?;
+        check_literal(rhs1, max_var, cursor.line_number)?;
+        define(lhs, Node::And(rhs0, rhs1), cursor.line_number)?;
+        and_vars.push((lhs >> 1) as usize);
+    }
+
+    lower(&nodes, &and_vars, num_inputs, &outputs)
+}
+
+/// Number the wires of an and-inverter graph and emit its gates in topological order
+fn lower(nodes: &[Node], and_vars: &[usize], num_inputs: u64, outputs: &[u64]) -> Result<Circuit> {
+    let mut emitter = GateEmitter::new(num_inputs);
+    let mut wires: Vec<Option<WireId>> = nodes
+        .iter()
+        .map(|node| match node {
+            Node::Input(wire_id) => Some(*wire_id),
+            _ => None,
+        })
+        .collect();
+    // Wire holding a literal whose variable already has one or is the constant 0
+    let literal_wire = |emitter: &mut GateEmitter, wires: &[Option<WireId>], literal: u64| {
+        let inverted = literal & 1 == 1;
+        match wires[(literal >> 1) as usize] {
+            None => emitter.constant(inverted),
+            Some(wire_id) if inverted => emitter.not(wire_id),
+            Some(wire_id) => wire_id,
+        }
+    };
+
+    // Depth-first, so each AND gate follows the gates of its operands
+    let mut visiting = vec![false; nodes.len()];
+    let mut stack = Vec::new();
+    for &root in and_vars {
+        stack.push((root, false));
+        while let Some((var, operands_done)) = stack.pop() {
+            if wires[var].is_some() {
+                continue;
+            }
+            let Node::And(rhs0, rhs1) = nodes[var] else {
+                unreachable!("only AND variables are pushed");
+            };
+            if operands_done {
+                let input_wire_1 = literal_wire(&mut emitter, &wires, rhs0);
+                let input_wire_2 = literal_wire(&mut emitter, &wires, rhs1);
+                wires[var] = Some(emitter.and(input_wire_1, input_wire_2));
+                continue;
+            }
+            visiting[var] = true;
+            stack.push((var, true));
+            for literal in [rhs1, rhs0] {
+                let operand = (literal >> 1) as usize;
+                match nodes[operand] {
+                    Node::Undefined if operand != 0 => {
+                        return Err(GcError::invalid_format(
+                            FORMAT,
+                            format!("AND gate {} reads undefined literal {}", 2 * var, literal),
+                        ));
+                    }
+                    Node::And(..) if wires[operand].is_none() => {
+                        if visiting[operand] {
+                            return Err(GcError::invalid_format(
+                                FORMAT,
+                                format!("AND gate {} depends on itself", 2 * operand),
+                            ));
+                        }
+                        stack.push((operand, false));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let mut output_wires = Vec::with_capacity(outputs.len());
+    for &literal in outputs {
+        let var = (literal >> 1) as usize;
+        if var != 0 && nodes[var] == Node::Undefined {
+            return Err(GcError::invalid_format(
+                FORMAT,
+                format!("output reads undefined literal {}", literal),
+            ));
+        }
+        output_wires.push(literal_wire(&mut emitter, &wires, literal));
+    }
+    Ok(emitter.into_circuit(&output_wires))
+}
+
+/// Variable of `literal`, checked against the header's maximum
+fn check_literal(literal: u64, max_var: u64, line_number: u64) -> Result<usize> {
+    let var = literal >> 1;
+    if var > max_var {
+        return Err(GcError::invalid_format(
+            FORMAT,
+            format!(
+                "line {}: literal {} exceeds the maximum variable {}",
+                line_number, literal, max_var
+            ),
+        ));
+    }
+    Ok(var as usize)
+}
+
+/// Position in the bytes of an AIGER file
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    /// Line of the last text line read
+    line_number: u64,
+}
+
+impl<'a> Cursor<'a> {
+    /// Next text line, without its line ending
+    fn line(&mut self) -> Result<&'a str> {
+        let rest = &self.bytes[self.position..];
+        if rest.is_empty() {
+            return Err(GcError::invalid_format(
+                FORMAT,
+                format!("unexpected end of file after line {}", self.line_number),
+            ));
+        }
+        let length = rest.iter().position(|&byte| byte == b'\n');
+        self.position += length.map_or(rest.len(), |length| length + 1);
+        self.line_number += 1;
+        let line = &rest[..length.unwrap_or(rest.len())];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        std::str::from_utf8(line).map_err(|_| {
+            GcError::invalid_format(FORMAT, format!("line {} is not text", self.line_number))
+        })
+    }
+
+    /// Next text line, holding exactly `N` literals
+    fn literals<const N: usize>(&mut self) -> Result<[u64; N]> {
+        let line = self.line()?;
+        let literals: Vec<u64> = line
+            .split_whitespace()
+            .map(|token| token.parse().ok())
+            .collect::<Option<_>>()
+            .unwrap_or_default();
+        literals.try_into().map_err(|_| {
+            GcError::invalid_format(
+                FORMAT,
+                format!(
+                    "line {}: expected {} literals, got '{}'",
+                    self.line_number, N, line
+                ),
+            )
+        })
+    }
+
+    /// Next variable-length delta of the binary AND section: 7 bits per
+    /// byte, least significant first, high bit set on all but the last byte
+    fn delta(&mut self) -> Result<u64> {
+        let mut delta: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let Some(&byte) = self.bytes.get(self.position) else {
+                return Err(GcError::invalid_format(FORMAT, "truncated AND section"));
+            };
+            self.position += 1;
+            delta |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(delta);
+            }
+        }
+        Err(GcError::invalid_format(
+            FORMAT,
+            "AND gate delta exceeds 64 bits",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plain::evaluate_plain;
+    use crate::wire_analyzer::analyze_gates;
+    use std::collections::HashMap;
+
+    /// Outputs a XOR b and a AND b, with the ANDs defined out of order
+    const HALF_ADDER: &str = "aag 5 2 0 2 3\n2\n4\n10\n6\n10 7 9\n6 2 4\n8 3 5\nc\nhalf adder\n";
+
+    #[test]
+    fn test_import_aiger() -> Result<()> {
+        // The same graph in binary form: deltas lhs - rhs0 and rhs0 - rhs1
+        let mut binary = b"aig 5 2 0 2 3\n10\n6\n".to_vec();
+        binary.extend([2, 2, 3, 2, 1, 2]);
+        binary.extend(b"i0 a\ni1 b\n");
+
+        for circuit in [
+            import_aiger(HALF_ADDER.as_bytes())?,
+            import_aiger(binary.as_slice())?,
+        ] {
+            let io = circuit.io.as_ref().unwrap();
+            assert_eq!(io.input_wires(), 0..2);
+            let output_wires = io.output_wires(circuit.num_wires);
+            assert_eq!(output_wires.end, circuit.num_wires);
+
+            let wire_report = analyze_gates(&circuit.gates, circuit.num_wires)?;
+            for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+                let inputs = HashMap::from([(0, a), (1, b)]);
+                let outputs = evaluate_plain(&circuit, &wire_report, &inputs)?;
+                assert_eq!(outputs[&output_wires.start], a ^ b);
+                assert_eq!(outputs[&(output_wires.start + 1)], a & b);
+            }
+        }
+
+        let error_msg = import_aiger("aag 1 0 1 1 0\n2 3\n2\n".as_bytes())
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("only combinational circuits"));
+        let error_msg = import_aiger("aag 3 1 0 1 2\n2\n4\n4 2 6\n6 4 2\n".as_bytes())
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("depends on itself"));
+        Ok(())
+    }
+}
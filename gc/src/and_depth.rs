@@ -0,0 +1,210 @@
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+
+use crate::bus::Bus;
+use crate::constants::PROGRESS_UPDATE_INTERVAL;
+use crate::gate::WireId;
+use crate::parser::{CircuitHeader, WireRef, read_header, visit_gate_wires};
+use crate::progress::ProgressObserver;
+use crate::stream::BufferedLineStream;
+
+/// AND depth (multiplicative depth) of a circuit and of each of its outputs
+///
+/// The AND depth of a wire is the largest number of non-linear gates on any
+/// path from a primary input to it. XOR, INV and copy gates are free;
+/// AND, NAND, OR and NOR gates each add one. It bounds the number of rounds
+/// of protocols that need interaction per AND gate, and compares circuit
+/// versions independently of how their linear layers are written.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AndDepthReport {
+    /// Largest AND depth of any primary output
+    pub and_depth: u32,
+    /// Number of non-linear gates in the circuit
+    pub and_gates: u64,
+    /// AND depth of every primary output: wire_id -> depth
+    pub output_depths: BTreeMap<WireId, u32>,
+}
+
+impl AndDepthReport {
+    /// Largest AND depth among the outputs of each bus
+    ///
+    /// # Arguments
+    /// * `buses` - Output buses, as in a [`BusLayout`](crate::bus::BusLayout)
+    ///
+    /// # Returns
+    /// * `BTreeMap<String, u32>` - Depth of each bus by name, 0 for a bus holding no primary output
+    pub fn bus_depths(&self, buses: &[Bus]) -> BTreeMap<String, u32> {
+        buses
+            .iter()
+            .map(|bus| {
+                let depth = self
+                    .output_depths
+                    .range(bus.first_wire..=bus.last_wire)
+                    .map(|(_, &depth)| depth)
+                    .max()
+                    .unwrap_or(0);
+                (bus.name.clone(), depth)
+            })
+            .collect()
+    }
+
+    /// Save the report as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Whether a Bristol gate type costs an AND gate: AND, NAND, OR and NOR
+pub(crate) fn is_non_linear(gate_type: &str) -> bool {
+    matches!(gate_type, "AND" | "NAND" | "OR" | "NOR")
+}
+
+/// Compute the AND depth of a Bristol circuit and of each of its outputs
+///
+/// The primary outputs are the wires a Bristol Fashion header declares, or
+/// else the wires that gates write but never read.
+///
+/// # Arguments
+/// * `stream` - The line stream to process
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(AndDepthReport)` - AND depth of the circuit and of every primary output
+/// * `Err(GcError)` - Parse error, IO error, or a wire outside `0..num_wires`
+#[tracing::instrument(name = "and_depth", skip_all)]
+pub fn analyze_and_depth<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    progress: &dyn ProgressObserver,
+) -> Result<AndDepthReport> {
+    let CircuitHeader {
+        num_gates,
+        num_wires,
+        io,
+        lines,
+    } = read_header(stream)?;
+
+    // AND depth of every wire, 0 for primary inputs
+    let mut wire_depths = vec![0u32; num_wires as usize];
+    // Whether some gate reads or writes each wire, to find the outputs of
+    // circuits without declarations
+    let mut read = vec![false; num_wires as usize];
+    let mut written = vec![false; num_wires as usize];
+    let mut and_gates = 0u64;
+    let mut gates_processed = 0u64;
+    let mut line_number = lines;
+
+    progress.start(Some(num_gates), "Computing AND depth");
+
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        let line = line_result?;
+
+        // Outputs are one deeper than the deepest input behind a non-linear gate
+        let non_linear = line
+            .split_whitespace()
+            .next_back()
+            .is_some_and(is_non_linear);
+        let mut depth = 0u32;
+        visit_gate_wires(line, line_number, |wire| {
+            wire.check_range(num_wires, line_number)?;
+            match wire {
+                WireRef::Input(wire_id) => {
+                    depth = depth.max(wire_depths[wire_id as usize]);
+                    read[wire_id as usize] = true;
+                }
+                WireRef::Output(wire_id) => {
+                    wire_depths[wire_id as usize] = depth + u32::from(non_linear);
+                    written[wire_id as usize] = true;
+                }
+            }
+            Ok(())
+        })?;
+        and_gates += u64::from(non_linear);
+
+        gates_processed += 1;
+        if gates_processed.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            progress.update(gates_processed);
+        }
+    }
+
+    let output_depths: BTreeMap<WireId, u32> = match &io {
+        Some(io) => io
+            .output_wires(num_wires)
+            .map(|wire_id| (wire_id, wire_depths[wire_id as usize]))
+            .collect(),
+        None => (0..num_wires)
+            .filter(|&wire_id| written[wire_id as usize] && !read[wire_id as usize])
+            .map(|wire_id| (wire_id, wire_depths[wire_id as usize]))
+            .collect(),
+    };
+    let and_depth = output_depths.values().copied().max().unwrap_or(0);
+
+    progress.update(gates_processed);
+    progress.finish(&format!(
+        "✓ Analyzed {gates_processed} gates, AND depth {and_depth}"
+    ));
+
+    Ok(AndDepthReport {
+        and_depth,
+        and_gates,
+        output_depths,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuits::{carry_lookahead_adder, ripple_carry_adder};
+    use crate::gate::Circuit;
+    use crate::progress::NoProgress;
+
+    fn and_depth(circuit: &Circuit) -> Result<AndDepthReport> {
+        let mut text = Vec::new();
+        circuit.write_bristol(&mut text)?;
+        analyze_and_depth(
+            &mut BufferedLineStream::from_reader(text.as_slice()),
+            &NoProgress,
+        )
+    }
+
+    #[test]
+    fn test_and_depth() -> Result<()> {
+        // Wire 5 = (0 AND 1) OR 2 at depth 2, wire 6 = 3 XOR 4 at depth 0,
+        // wire 7 = NOT 5 stays at depth 2
+        let text = "4 8\n2 1 0 1 4 AND\n2 1 4 2 5 OR\n2 1 3 3 6 XOR\n1 1 5 7 INV\n";
+        let report = analyze_and_depth(
+            &mut BufferedLineStream::from_reader(text.as_bytes()),
+            &NoProgress,
+        )?;
+        assert_eq!(report.and_depth, 2);
+        assert_eq!(report.and_gates, 2);
+        assert_eq!(report.output_depths, BTreeMap::from([(6, 0), (7, 2)]));
+
+        // The ripple-carry adder is as deep as it is wide, the Kogge-Stone
+        // one as deep as the log of its width
+        let ripple = and_depth(&ripple_carry_adder(16)?)?;
+        let lookahead = and_depth(&carry_lookahead_adder(16)?)?;
+        assert_eq!(ripple.and_depth, 15);
+        assert_eq!(lookahead.and_depth, 4);
+        assert_eq!(ripple.output_depths.len(), 16);
+        let sum = Bus {
+            name: "sum".to_string(),
+            first_wire: *ripple.output_depths.keys().next().unwrap(),
+            last_wire: *ripple.output_depths.keys().next_back().unwrap(),
+            bit_order: Default::default(),
+        };
+        assert_eq!(
+            ripple.bus_depths(std::slice::from_ref(&sum))["sum"],
+            ripple.and_depth
+        );
+        // Bit i of the sum waits for the carry out of bit i - 1
+        let depths: Vec<u32> = ripple.output_depths.values().copied().collect();
+        assert_eq!(depths, (0..16).collect::<Vec<u32>>());
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+use crate::error::{GcError, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use crate::channel::{FrameHeader, FrameType};
+use crate::chunk::ChunkWriter;
+use crate::constants::{FRAME_HEADER_SIZE, FRAMING_VERSION};
+use crate::evaluator::TableCursor;
+use crate::garbler::{GarbledTable, TablesHeader};
+use crate::sink::GarbledTableSink;
+use crate::two_party::{
+    TABLES_END, TableMessageSource, TableMessages, tables_batch_message, tables_end_message,
+};
+
+/// Garbled table sink handing batches to an async network task
+///
+/// Garbling runs on a blocking thread (e.g. `tokio::task::spawn_blocking`)
+/// and each batch becomes one message on a bounded queue, which
+/// [`write_table_stream`] drains onto a socket. When the socket stalls
+/// because the evaluator falls behind, the queue fills up and
+/// [`write_tables`](GarbledTableSink::write_tables) blocks the garbler, so
+/// at most `in_flight` batches are held in memory however large the circuit.
+///
+/// Messages are framed like a [`Channel`](crate::channel::Channel) message,
+/// so the stream can be read by [`read_table_stream`] or by a blocking
+/// evaluator reading the same framing.
+#[derive(Debug)]
+pub struct AsyncTableSink {
+    messages: mpsc::Sender<Vec<u8>>,
+    chunks: ChunkWriter,
+}
+
+impl AsyncTableSink {
+    /// Create a sink and the queue [`write_table_stream`] reads from
+    ///
+    /// # Arguments
+    /// * `in_flight` - Maximum number of batches queued before the garbler blocks; at least 1
+    ///
+    /// # Returns
+    /// * `(AsyncTableSink, mpsc::Receiver<Vec<u8>>)` - The sink for the garbler and the queue for the network task
+    pub fn bounded(in_flight: usize) -> (Self, mpsc::Receiver<Vec<u8>>) {
+        let (sender, receiver) = mpsc::channel(in_flight.max(1));
+        let sink = Self {
+            messages: sender,
+            chunks: ChunkWriter::new(),
+        };
+        (sink, receiver)
+    }
+
+    /// Queue one message, waiting while the queue is full
+    fn queue(&self, message: Vec<u8>) -> Result<()> {
+        self.messages.blocking_send(message).map_err(|_| {
+            GcError::Disconnected("The table stream stopped before garbling finished".to_string())
+        })
+    }
+}
+
+impl GarbledTableSink for AsyncTableSink {
+    fn write_tables(&mut self, tables: &[GarbledTable]) -> Result<()> {
+        let message = tables_batch_message(&mut self.chunks, tables);
+        self.queue(message)
+    }
+
+    fn finish(&mut self, header: &TablesHeader) -> Result<()> {
+        self.queue(tables_end_message(header))
+    }
+}
+
+/// Write the messages of an [`AsyncTableSink`] to a socket
+///
+/// Returns once the last message, carrying the tables header, is written and
+/// flushed. Awaiting the socket is what applies backpressure: while the
+/// evaluator does not read, nothing is taken off the queue.
+///
+/// # Arguments
+/// * `messages` - Queue returned by [`AsyncTableSink::bounded`]
+/// * `writer` - Connection to the evaluator
+///
+/// # Returns
+/// * `Ok(u64)` - Bytes written, frame headers included
+/// * `Err(GcError)` - Write error, or the garbler stopped before the tables header
+pub async fn write_table_stream<W: AsyncWrite + Unpin>(
+    messages: &mut mpsc::Receiver<Vec<u8>>,
+    writer: &mut W,
+) -> Result<u64> {
+    let mut bytes_written = 0;
+    loop {
+        let Some(message) = messages.recv().await else {
+            return Err(GcError::Disconnected(
+                "Garbling stopped before the tables header".to_string(),
+            ));
+        };
+        writer
+            .write_all(&FrameHeader::message(message.len()).to_bytes())
+            .await?;
+        writer.write_all(&message).await?;
+        writer.flush().await?;
+        bytes_written += (FRAME_HEADER_SIZE + message.len()) as u64;
+        if message.first() == Some(&TABLES_END) {
+            return Ok(bytes_written);
+        }
+    }
+}
+
+/// Read a table stream from a socket onto the queue of an [`AsyncTableSource`]
+///
+/// Returns once the last message, carrying the tables header, is queued.
+/// While the queue is full the socket is not read, so a slow evaluator
+/// stalls the garbler through the transport's flow control instead of
+/// buffering tables without bound.
+///
+/// # Arguments
+/// * `reader` - Connection to the garbler
+/// * `messages` - Queue returned by [`AsyncTableSource::bounded`]
+///
+/// # Returns
+/// * `Ok(u64)` - Bytes read, frame headers included
+/// * `Err(GcError)` - The garbler disconnected, sent a malformed frame or one larger than [`MAX_MESSAGE_SIZE`](crate::constants::MAX_MESSAGE_SIZE), or the evaluator stopped
+pub async fn read_table_stream<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    messages: &mpsc::Sender<Vec<u8>>,
+) -> Result<u64> {
+    let mut bytes_read = 0;
+    loop {
+        let mut header = [0u8; FRAME_HEADER_SIZE];
+        reader.read_exact(&mut header).await.map_err(|_| {
+            GcError::Disconnected("Garbler closed the table stream early".to_string())
+        })?;
+        let header = FrameHeader::from_bytes(&header)?;
+        if header.frame_type != FrameType::Message || header.version != FRAMING_VERSION {
+            return Err(GcError::Protocol(format!(
+                "Expected a table message of framing version {}, got a {:?} frame of version {}",
+                FRAMING_VERSION, header.frame_type, header.version
+            )));
+        }
+        let length = header.length;
+
+        let mut message = Vec::new();
+        (&mut *reader)
+            .take(length)
+            .read_to_end(&mut message)
+            .await?;
+        if (message.len() as u64) < length {
+            return Err(GcError::Disconnected(format!(
+                "Connection closed after {} of {} message bytes",
+                message.len(),
+                length
+            )));
+        }
+        bytes_read += FRAME_HEADER_SIZE as u64 + length;
+        let last = message.first() == Some(&TABLES_END);
+        messages.send(message).await.map_err(|_| {
+            GcError::Disconnected("Evaluation stopped before the table stream ended".to_string())
+        })?;
+        if last {
+            return Ok(bytes_read);
+        }
+    }
+}
+
+/// Garbled tables fed by [`read_table_stream`], for an evaluator on a blocking thread
+///
+/// Implements [`TableCursor`], so it plugs into an
+/// [`EvaluatorSession`](crate::evaluator::EvaluatorSession). Each table
+/// request takes a batch off the bounded queue when the previous one is used
+/// up, waiting for the network task if needed.
+#[derive(Debug)]
+pub struct AsyncTableSource {
+    tables: TableMessageSource<QueuedMessages>,
+}
+
+/// Receiving end of the queue between [`read_table_stream`] and the evaluator
+#[derive(Debug)]
+struct QueuedMessages(mpsc::Receiver<Vec<u8>>);
+
+impl TableMessages for QueuedMessages {
+    fn next_message(&mut self) -> Result<Vec<u8>> {
+        self.0.blocking_recv().ok_or_else(|| {
+            GcError::Disconnected("The table stream stopped before the tables header".to_string())
+        })
+    }
+}
+
+impl AsyncTableSource {
+    /// Create a source and the queue [`read_table_stream`] writes to
+    ///
+    /// # Arguments
+    /// * `in_flight` - Maximum number of batches queued before the socket is left unread; at least 1
+    ///
+    /// # Returns
+    /// * `(mpsc::Sender<Vec<u8>>, AsyncTableSource)` - The queue for the network task and the source for the evaluator
+    pub fn bounded(in_flight: usize) -> (mpsc::Sender<Vec<u8>>, Self) {
+        let (sender, receiver) = mpsc::channel(in_flight.max(1));
+        let source = Self {
+            tables: TableMessageSource::new(QueuedMessages(receiver)),
+        };
+        (sender, source)
+    }
+
+    /// Take the tables header once every gate has been evaluated
+    ///
+    /// # Returns
+    /// * `Ok(TablesHeader)` - The header sent after the last table
+    /// * `Err(GcError)` - Tables are left over, the count differs from the header's, or the stream stopped
+    pub fn finish(self) -> Result<TablesHeader> {
+        self.tables.finish()
+    }
+}
+
+impl TableCursor for AsyncTableSource {
+    fn next_table(&mut self) -> Result<GarbledTable> {
+        self.tables.next_table()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::{EvaluationOptions, EvaluatorSession};
+    use crate::garbler::GarblerBuilder;
+    use crate::gate::hash_circuit;
+    use crate::ot_simulation::simulate_ot;
+    use crate::parser::{parse_gate_line, read_header};
+    use crate::progress::NoProgress;
+    use crate::stream::BufferedLineStream;
+    use crate::wire_analyzer::analyze_wire_usage;
+    use std::io::Write;
+
+    /// Chain of alternating AND and XOR gates over inputs 0 and 1, long
+    /// enough to span several garbling batches
+    fn chain_circuit(gates: u64) -> String {
+        let mut circuit = format!("{} {}\n", gates, gates + 2);
+        for gate in 0..gates {
+            let kind = if gate % 2 == 0 { "AND" } else { "XOR" };
+            circuit.push_str(&format!(
+                "2 1 {} {} {} {}\n",
+                gate + 1,
+                gate % 2,
+                gate + 2,
+                kind
+            ));
+        }
+        circuit
+    }
+
+    #[test]
+    fn test_stream_with_bounded_buffers() -> Result<()> {
+        let gates = 20_000;
+        let circuit = chain_circuit(gates);
+        let mut circuit_file = tempfile::NamedTempFile::new()?;
+        circuit_file.write_all(circuit.as_bytes())?;
+        circuit_file.flush()?;
+        let circuit_path = circuit_file.path().to_path_buf();
+        let open = move || -> Result<BufferedLineStream> {
+            Ok(BufferedLineStream::new(std::fs::File::open(&circuit_path)?))
+        };
+
+        let wire_report = analyze_wire_usage(&mut open()?, &NoProgress)?;
+        let input_labels = GarblerBuilder::new([7; 32]).input_wire_labels(&wire_report)?;
+        let ot_result = simulate_ot(&input_labels, &[8; 32], &NoProgress)?;
+        let inputs = [0, 1].map(|wire| ot_result.selected_inputs[&wire].bit_value);
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        let (tables_header, garbling, result) = runtime.block_on(async {
+            // A tiny pipe and one queued batch per side keep the garbler
+            // waiting on the evaluator for most of the run
+            let (mut garbler_end, mut evaluator_end) = tokio::io::duplex(4096);
+            let (mut sink, mut outgoing) = AsyncTableSink::bounded(1);
+            let (incoming, mut source) = AsyncTableSource::bounded(1);
+
+            let garbler_report = wire_report.clone();
+            let garbler_open = open.clone();
+            let garbler = tokio::task::spawn_blocking(move || {
+                GarblerBuilder::new([7; 32])
+                    .sink(&mut sink)
+                    .garble(&mut garbler_open()?, &garbler_report)
+            });
+            let evaluator = tokio::task::spawn_blocking(move || -> Result<_> {
+                let mut stream = open()?;
+                read_header(&mut stream)?;
+                let mut session = EvaluatorSession::new(
+                    &wire_report,
+                    &ot_result,
+                    || source.next_table(),
+                    &EvaluationOptions::default(),
+                )?;
+                let mut line_number = 0;
+                while let Some(line) = stream.next_line() {
+                    line_number += 1;
+                    session.feed_gate(parse_gate_line(line?, line_number)?)?;
+                }
+                let result = session.finish()?;
+                Ok((source.finish()?, result))
+            });
+            let writer =
+                tokio::spawn(
+                    async move { write_table_stream(&mut outgoing, &mut garbler_end).await },
+                );
+
+            let read = read_table_stream(&mut evaluator_end, &incoming).await?;
+            assert_eq!(writer.await.unwrap()?, read);
+            let garbling = garbler.await.unwrap()?;
+            let (tables_header, result) = evaluator.await.unwrap()?;
+            Ok::<_, GcError>((tables_header, garbling, result))
+        })?;
+
+        tables_header.check_circuit(&hash_circuit(circuit_file.reopen()?)?)?;
+        assert_eq!(tables_header.num_tables, gates / 2);
+
+        // The chain alternately ANDs with input 0 and XORs with input 1
+        let mut expected = inputs[1];
+        for gate in 0..gates {
+            expected = if gate % 2 == 0 {
+                expected & inputs[0]
+            } else {
+                expected ^ inputs[1]
+            };
+        }
+        let output_labels = garbling.wire_labels.get_wire_labels(gates + 1).unwrap();
+        assert_eq!(
+            result.output_results[&(gates + 1)].label,
+            output_labels[expected as usize]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,321 @@
+use crate::error::{GcError, Result};
+use std::collections::BTreeMap;
+use std::sync::mpsc::{Receiver, SyncSender, channel, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::garbler::{GarbledTable, WireLabel, garble_and_gate};
+use crate::sink::GarbledTableSink;
+
+/// Everything a backend needs to garble a single AND gate
+#[derive(Debug, Clone, Copy)]
+pub struct AndGateJob {
+    /// Both labels of each input wire: [input1_labels, input2_labels]
+    pub input_labels: [[WireLabel; 2]; 2],
+    /// Both labels of the output wire: [output_0, output_1]
+    pub output_labels: [WireLabel; 2],
+}
+
+/// Backend performing the per-AND-gate hashing and encryption
+///
+/// The garbler streams the circuit and hands AND gates to the backend in
+/// batches, so implementations are free to offload the work to other threads
+/// or to an accelerator (e.g. a GPU) as long as they return one table per job,
+/// in the same order as the jobs. A batch coming back with any other number
+/// of tables fails the garbling with [`GcError::TableCountMismatch`].
+pub trait GarblingBackend {
+    /// Garble a batch of AND gates, returning the tables in job order
+    fn garble_and_gates(&mut self, jobs: &[AndGateJob]) -> Result<Vec<GarbledTable>>;
+}
+
+/// Default backend garbling each gate sequentially on the calling thread
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CpuBackend;
+
+impl GarblingBackend for CpuBackend {
+    fn garble_and_gates(&mut self, jobs: &[AndGateJob]) -> Result<Vec<GarbledTable>> {
+        Ok(jobs
+            .iter()
+            .map(|job| garble_and_gate(&job.input_labels, &job.output_labels))
+            .collect())
+    }
+}
+
+/// Garble a batch of AND gates with `backend`, checking it returned one table per job
+///
+/// # Returns
+/// * `Ok(Vec<GarbledTable>)` - The tables in job order
+/// * `Err(GcError)` - The backend failed, or returned more or fewer tables than jobs
+pub(crate) fn garble_batch(
+    backend: &mut dyn GarblingBackend,
+    jobs: &[AndGateJob],
+) -> Result<Vec<GarbledTable>> {
+    let tables = backend.garble_and_gates(jobs)?;
+    if tables.len() != jobs.len() {
+        return Err(GcError::TableCountMismatch {
+            expected: jobs.len() as u64,
+            found: tables.len() as u64,
+            message: format!(
+                "Garbling backend returned {} tables for a batch of {} AND gates",
+                tables.len(),
+                jobs.len()
+            ),
+        });
+    }
+    Ok(tables)
+}
+
+/// A batch of AND gates and its position in the circuit
+type NumberedJobs = (u64, Vec<AndGateJob>);
+
+/// The tables of a batch and its position in the circuit
+type NumberedTables = (u64, Vec<GarbledTable>);
+
+/// Worker threads hashing batches of AND gates behind the garbler's back
+///
+/// The parsing thread submits batches through a bounded channel, so parsing,
+/// hashing and writing overlap while at most a few batches are in flight.
+/// Finished batches may complete out of order; they are held back and
+/// written to the sink strictly in submission order.
+#[derive(Debug)]
+pub(crate) struct HashingPool {
+    job_sender: Option<SyncSender<NumberedJobs>>,
+    result_receiver: Receiver<Result<NumberedTables>>,
+    workers: Vec<JoinHandle<()>>,
+    /// Sequence number of the next submitted batch
+    next_submitted: u64,
+    /// Sequence number of the next batch to write
+    next_written: u64,
+    /// Finished batches waiting for their predecessors
+    finished: BTreeMap<u64, Vec<GarbledTable>>,
+}
+
+impl HashingPool {
+    /// Start `threads` hashing workers
+    pub(crate) fn new(threads: usize) -> Self {
+        let threads = threads.max(1);
+        let (job_sender, job_receiver) = sync_channel::<NumberedJobs>(2 * threads);
+        let (result_sender, result_receiver) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..threads)
+            .map(|_| {
+                let job_receiver = Arc::clone(&job_receiver);
+                let result_sender = result_sender.clone();
+                std::thread::spawn(move || {
+                    loop {
+                        // The lock is only held while waiting for the next batch
+                        let batch = job_receiver.lock().unwrap().recv();
+                        let Ok((sequence, jobs)) = batch else {
+                            break;
+                        };
+                        let tables = garble_batch(&mut CpuBackend, &jobs);
+                        let result = tables.map(|tables| (sequence, tables));
+                        if result_sender.send(result).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            job_sender: Some(job_sender),
+            result_receiver,
+            workers,
+            next_submitted: 0,
+            next_written: 0,
+            finished: BTreeMap::new(),
+        }
+    }
+
+    /// Queue a batch for hashing and write any batches that are done
+    ///
+    /// Blocks while the job queue is full.
+    pub(crate) fn submit(
+        &mut self,
+        jobs: Vec<AndGateJob>,
+        sink: &mut dyn GarbledTableSink,
+    ) -> Result<()> {
+        let sequence = self.next_submitted;
+        self.next_submitted += 1;
+        self.job_sender
+            .as_ref()
+            .expect("pool is running")
+            .send((sequence, jobs))
+            .map_err(|_| GcError::Disconnected("Hashing workers stopped unexpectedly".into()))?;
+
+        while let Ok(result) = self.result_receiver.try_recv() {
+            self.collect(result, sink)?;
+        }
+        Ok(())
+    }
+
+    /// Wait for every submitted batch and write the rest of the tables
+    pub(crate) fn finish(mut self, sink: &mut dyn GarbledTableSink) -> Result<()> {
+        // Closing the job queue lets the workers exit once it is drained
+        self.job_sender = None;
+        while self.next_written < self.next_submitted {
+            let result = self.result_receiver.recv().map_err(|_| {
+                GcError::Disconnected("Hashing workers stopped unexpectedly".into())
+            })?;
+            self.collect(result, sink)?;
+        }
+        for worker in self.workers.drain(..) {
+            worker
+                .join()
+                .map_err(|_| GcError::Disconnected("Hashing worker panicked".into()))?;
+        }
+        Ok(())
+    }
+
+    /// Store a finished batch and write every batch that is now in order
+    fn collect(
+        &mut self,
+        result: Result<NumberedTables>,
+        sink: &mut dyn GarbledTableSink,
+    ) -> Result<()> {
+        let (sequence, tables) = result?;
+        self.finished.insert(sequence, tables);
+        while let Some(tables) = self.finished.remove(&self.next_written) {
+            sink.write_tables(&tables)?;
+            self.next_written += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::GARBLING_BATCH_SIZE;
+    use crate::garbler::{GarblerBuilder, garble_circuit, garble_circuit_with_backend};
+    use crate::gate::Gate;
+    use crate::progress::NoProgress;
+    use crate::stream::BufferedLineStream;
+    use crate::test_util::create_test_file;
+    use crate::wire_analyzer::{analyze_gates, analyze_wire_usage};
+    use std::fs::File;
+
+    /// Backend recording batch sizes while delegating to the CPU backend
+    #[derive(Debug, Default)]
+    struct RecordingBackend {
+        batch_sizes: Vec<usize>,
+    }
+
+    impl GarblingBackend for RecordingBackend {
+        fn garble_and_gates(&mut self, jobs: &[AndGateJob]) -> Result<Vec<GarbledTable>> {
+            self.batch_sizes.push(jobs.len());
+            CpuBackend.garble_and_gates(jobs)
+        }
+    }
+
+    /// Backend dropping the last table of every batch
+    #[derive(Debug, Default)]
+    struct ShortBackend;
+
+    impl GarblingBackend for ShortBackend {
+        fn garble_and_gates(&mut self, jobs: &[AndGateJob]) -> Result<Vec<GarbledTable>> {
+            let mut tables = CpuBackend.garble_and_gates(jobs)?;
+            tables.pop();
+            Ok(tables)
+        }
+    }
+
+    #[test]
+    fn test_custom_backend_matches_default() -> Result<()> {
+        let circuit_data = "3 7\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n2 1 4 5 6 AND\n";
+        let temp_file = create_test_file(circuit_data)?;
+        let seed = [0x42; 32];
+
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+        let wire_report = analyze_wire_usage(&mut stream, &NoProgress)?;
+
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+        let default_result = garble_circuit(&mut stream, &wire_report, &seed)?;
+
+        let mut backend = RecordingBackend::default();
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+        let custom_result = garble_circuit_with_backend(
+            &mut stream,
+            &wire_report,
+            &seed,
+            &mut backend,
+            &NoProgress,
+        )?;
+
+        // Both AND gates fit in a single batch flushed at the end
+        assert_eq!(backend.batch_sizes, vec![2]);
+        assert_eq!(custom_result.garbled_tables.len(), 2);
+        for (a, b) in default_result
+            .garbled_tables
+            .iter()
+            .zip(custom_result.garbled_tables.iter())
+        {
+            assert_eq!(a.ciphertexts, b.ciphertexts);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_backend_is_rejected() -> Result<()> {
+        let gates = vec![Gate::and(0, 1, 2), Gate::and(2, 1, 3)];
+        let wire_report = analyze_gates(&gates, 4)?;
+
+        let error = GarblerBuilder::new([0x42; 32])
+            .backend(&mut ShortBackend)
+            .garble_gates(&gates, &wire_report)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            GcError::TableCountMismatch {
+                expected: 2,
+                found: 1,
+                ..
+            }
+        ));
+        assert!(
+            error
+                .to_string()
+                .contains("Garbling backend returned 1 tables for a batch of 2 AND gates")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_threads_match_inline_hashing() -> Result<()> {
+        // A chain of AND gates spanning several batches
+        let num_gates = 3 * GARBLING_BATCH_SIZE as u64 + 17;
+        let gates: Vec<Gate> = (0..num_gates).map(|i| Gate::and(i, i + 1, i + 2)).collect();
+        let wire_report = analyze_gates(&gates, num_gates + 2)?;
+        let seed = [0x17; 32];
+
+        let inline = GarblerBuilder::new(seed).garble_gates(&gates, &wire_report)?;
+        let threaded = GarblerBuilder::new(seed)
+            .hash_threads(3)
+            .garble_gates(&gates, &wire_report)?;
+
+        assert_eq!(threaded.garbled_tables.len(), num_gates as usize);
+        assert_eq!(threaded.circuit_digest, inline.circuit_digest);
+        for (a, b) in inline
+            .garbled_tables
+            .iter()
+            .zip(threaded.garbled_tables.iter())
+        {
+            assert_eq!(a.ciphertexts, b.ciphertexts);
+        }
+
+        let mut backend = RecordingBackend::default();
+        let error_msg = GarblerBuilder::new(seed)
+            .backend(&mut backend)
+            .hash_threads(2)
+            .garble_gates(&gates, &wire_report)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("cannot be combined with a custom garbling backend"));
+
+        Ok(())
+    }
+}
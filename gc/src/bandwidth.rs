@@ -0,0 +1,213 @@
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Largest write passed to the stream at once when throttled, so the rate
+/// stays smooth instead of alternating between bursts and long pauses
+const MAX_THROTTLED_WRITE: usize = 64 * 1024;
+
+/// Bytes moved over a link so far, shared between the stream and observers
+///
+/// A [`MeteredStream`] updates the counters as bytes go through it, and any
+/// thread holding the meter can take a [`LinkStats`] snapshot at any time,
+/// e.g. to print live transfer rates during a long session.
+#[derive(Debug)]
+pub struct TransferMeter {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    throttled_nanos: AtomicU64,
+    start: Instant,
+}
+
+impl TransferMeter {
+    /// Start measuring now
+    pub fn new() -> Self {
+        Self {
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            throttled_nanos: AtomicU64::new(0),
+            start: Instant::now(),
+        }
+    }
+
+    /// Counters and average rates since the meter was created
+    pub fn snapshot(&self) -> LinkStats {
+        let elapsed_secs = self.start.elapsed().as_secs_f64();
+        let bytes_sent = self.bytes_sent.load(Ordering::Relaxed);
+        let bytes_received = self.bytes_received.load(Ordering::Relaxed);
+        let rate = |bytes: u64| {
+            if elapsed_secs > 0.0 {
+                bytes as f64 / 1e6 / elapsed_secs
+            } else {
+                0.0
+            }
+        };
+        LinkStats {
+            bytes_sent,
+            bytes_received,
+            elapsed_secs,
+            send_mb_per_sec: rate(bytes_sent),
+            receive_mb_per_sec: rate(bytes_received),
+            throttled_secs: Duration::from_nanos(self.throttled_nanos.load(Ordering::Relaxed))
+                .as_secs_f64(),
+            retransmits: None,
+        }
+    }
+}
+
+impl Default for TransferMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Traffic over a link, as measured by a [`TransferMeter`]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct LinkStats {
+    /// Bytes written to the link
+    pub bytes_sent: u64,
+    /// Bytes read from the link
+    pub bytes_received: u64,
+    /// Time since the meter was created, in seconds
+    pub elapsed_secs: f64,
+    /// Average send rate in megabytes (10^6 bytes) per second
+    pub send_mb_per_sec: f64,
+    /// Average receive rate in megabytes (10^6 bytes) per second
+    pub receive_mb_per_sec: f64,
+    /// Time writes spent waiting for the bandwidth limit, in seconds
+    pub throttled_secs: f64,
+    /// Segments the transport retransmitted, where it reports them (TCP on Linux)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retransmits: Option<u64>,
+}
+
+impl LinkStats {
+    /// Save the statistics as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Byte stream that counts the traffic through it and optionally caps the send rate
+///
+/// Wrap the connection before handing it to a
+/// [`Channel`](crate::channel::Channel): writes wait as needed to stay under
+/// the limit, so a long transfer does not saturate a shared link, and the
+/// [`TransferMeter`] sees every byte in both directions. Reads are never
+/// delayed; the peer's own limit paces them.
+#[derive(Debug)]
+pub struct MeteredStream<S> {
+    inner: S,
+    meter: Arc<TransferMeter>,
+    /// Send rate limit in bytes per second
+    max_bytes_per_sec: Option<u64>,
+    /// Earliest time the next byte may be sent under the limit
+    next_send: Instant,
+}
+
+impl<S> MeteredStream<S> {
+    /// Wrap `inner`, counting its traffic on a new meter
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            meter: Arc::new(TransferMeter::new()),
+            max_bytes_per_sec: None,
+            next_send: Instant::now(),
+        }
+    }
+
+    /// Send at most `bytes_per_sec` bytes per second on average
+    pub fn with_max_bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        self.max_bytes_per_sec = Some(bytes_per_sec.max(1));
+        self
+    }
+
+    /// Meter counting the traffic, to share with a monitoring thread
+    pub fn meter(&self) -> Arc<TransferMeter> {
+        self.meter.clone()
+    }
+
+    /// Unwrap the underlying stream
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Wait until `bytes` more bytes fit under the limit
+    fn throttle(&mut self, bytes: usize, bytes_per_sec: u64) {
+        let now = Instant::now();
+        // Time not spent sending is not saved up for a later burst
+        self.next_send =
+            self.next_send.max(now) + Duration::from_secs_f64(bytes as f64 / bytes_per_sec as f64);
+        let wait = self.next_send - now;
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+            self.meter
+                .throttled_nanos
+                .fetch_add(wait.as_nanos() as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<S: Read> Read for MeteredStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.meter
+            .bytes_received
+            .fetch_add(count as u64, Ordering::Relaxed);
+        Ok(count)
+    }
+}
+
+impl<S: Write> Write for MeteredStream<S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let buf = match self.max_bytes_per_sec {
+            Some(bytes_per_sec) => {
+                // About a tenth of a second of traffic per write
+                let max_write = (bytes_per_sec as usize / 10).clamp(1024, MAX_THROTTLED_WRITE);
+                let buf = &buf[..buf.len().min(max_write)];
+                self.throttle(buf.len(), bytes_per_sec);
+                buf
+            }
+            None => buf,
+        };
+        let count = self.inner.write(buf)?;
+        self.meter
+            .bytes_sent
+            .fetch_add(count as u64, Ordering::Relaxed);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bandwidth_limit_paces_writes() -> Result<()> {
+        let mut stream = MeteredStream::new(Vec::new()).with_max_bandwidth(1_000_000);
+        let meter = stream.meter();
+        let start = Instant::now();
+        stream.write_all(&[7; 250_000])?;
+        let elapsed = start.elapsed().as_secs_f64();
+
+        // 250 KB at 1 MB/s
+        assert!(elapsed >= 0.24, "took only {}s", elapsed);
+        let stats = meter.snapshot();
+        assert_eq!(stats.bytes_sent, 250_000);
+        assert_eq!(stats.bytes_received, 0);
+        assert!(stats.throttled_secs > 0.2);
+        assert!(stats.send_mb_per_sec <= 1.05);
+        assert_eq!(stream.into_inner().len(), 250_000);
+        Ok(())
+    }
+}
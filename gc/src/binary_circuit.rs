@@ -0,0 +1,959 @@
+use crate::error::{GcError, Result};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use zstd::stream::raw::CParameter;
+
+use crate::constants::{
+    BINARY_CIRCUIT_BATCH_SIZE, BINARY_CIRCUIT_CHECKSUM_SIZE, BINARY_CIRCUIT_FRAME_BATCHES,
+    BINARY_CIRCUIT_HEADER_SIZE, BINARY_CIRCUIT_MAGIC, BINARY_CIRCUIT_VERSION, GATE_INDEX_MAGIC,
+    GATE_INDEX_VERSION, PROGRESS_UPDATE_INTERVAL, ZSTD_COMPRESSION_LEVEL,
+};
+use crate::gate::{Circuit, Gate, GateType, WireId};
+use crate::parser::{BristolWriter, IoDeclaration, parse_gate_line, read_header};
+use crate::progress::ProgressObserver;
+use crate::stream::BufferedLineStream;
+
+/// Kind of file named in errors
+const FORMAT: &str = "binary circuit file";
+
+/// Header of a binary circuit file, stored uncompressed in front of the gates
+///
+/// Holds everything a Bristol header does, so a binary circuit stands in for
+/// its Bristol file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryCircuitHeader {
+    /// Number of gates in the file
+    pub num_gates: u64,
+    /// Number of wires of the circuit; every wire ID is below it
+    pub num_wires: u64,
+    /// Input and output declarations of a Bristol Fashion circuit, if present
+    pub io: Option<IoDeclaration>,
+}
+
+impl BinaryCircuitHeader {
+    /// Encode the header with magic bytes and format version
+    ///
+    /// Layout: [`BINARY_CIRCUIT_MAGIC`], the format version as a
+    /// little-endian `u32`, the gate and wire counts as little-endian `u64`s,
+    /// then a byte that is 1 if declarations follow and 0 otherwise. The
+    /// declarations are the number of inputs as a little-endian `u32` and
+    /// each input width as a `u64`, then the same for the outputs.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(BINARY_CIRCUIT_HEADER_SIZE + 1);
+        bytes.extend_from_slice(&BINARY_CIRCUIT_MAGIC);
+        bytes.extend_from_slice(&BINARY_CIRCUIT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.num_gates.to_le_bytes());
+        bytes.extend_from_slice(&self.num_wires.to_le_bytes());
+        bytes.push(self.io.is_some() as u8);
+        if let Some(io) = &self.io {
+            for widths in [&io.input_widths, &io.output_widths] {
+                bytes.extend_from_slice(&(widths.len() as u32).to_le_bytes());
+                for width in widths {
+                    bytes.extend_from_slice(&width.to_le_bytes());
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Read a header, validating magic bytes, version and declarations
+    ///
+    /// # Returns
+    /// * `Ok(BinaryCircuitHeader)` - The header; `reader` is at the first frame
+    /// * `Err(GcError)` - Not a binary circuit file, an unsupported version, or declarations that exceed the wires
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut bytes = [0u8; BINARY_CIRCUIT_HEADER_SIZE];
+        reader.read_exact(&mut bytes).map_err(truncated)?;
+        if bytes[0..4] != BINARY_CIRCUIT_MAGIC {
+            return Err(GcError::invalid_format(FORMAT, "bad magic bytes"));
+        }
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        if version != BINARY_CIRCUIT_VERSION {
+            return Err(GcError::FormatVersionMismatch {
+                format: "binary circuit",
+                found: version,
+                expected: BINARY_CIRCUIT_VERSION,
+            });
+        }
+        let num_gates = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let num_wires = u64::from_le_bytes(bytes[16..24].try_into().unwrap());
+
+        let mut has_io = [0u8; 1];
+        reader.read_exact(&mut has_io).map_err(truncated)?;
+        let io = match has_io[0] {
+            0 => None,
+            1 => {
+                let mut read_widths = || -> Result<Vec<u64>> {
+                    let mut count = [0u8; 4];
+                    reader.read_exact(&mut count).map_err(truncated)?;
+                    (0..u32::from_le_bytes(count))
+                        .map(|_| {
+                            let mut width = [0u8; 8];
+                            reader.read_exact(&mut width).map_err(truncated)?;
+                            Ok(u64::from_le_bytes(width))
+                        })
+                        .collect()
+                };
+                let io = IoDeclaration {
+                    input_widths: read_widths()?,
+                    output_widths: read_widths()?,
+                };
+                let declared = io
+                    .num_input_wires()
+                    .checked_add(io.num_output_wires())
+                    .filter(|&declared| declared <= num_wires);
+                if declared.is_none() {
+                    return Err(GcError::invalid_format(
+                        FORMAT,
+                        format!("declared inputs and outputs exceed the {} wires", num_wires),
+                    ));
+                }
+                Some(io)
+            }
+            flag => {
+                return Err(GcError::invalid_format(
+                    FORMAT,
+                    format!("bad declaration flag {}", flag),
+                ));
+            }
+        };
+        Ok(Self {
+            num_gates,
+            num_wires,
+            io,
+        })
+    }
+}
+
+/// Writes gates in the compact binary circuit format
+///
+/// Layout: a [`BinaryCircuitHeader`], then batches of up to
+/// [`BINARY_CIRCUIT_BATCH_SIZE`] gates. A batch is its gate count as one
+/// byte, one type byte per gate (XOR 0, AND 1, copy 2, constant 0 and 1 as
+/// 3 and 4), then per gate its output wire and its input wires as varints:
+/// the output as the zigzag-encoded difference from the previous output in
+/// the batch (from 0 for the first), which is +1 in most circuits, and each
+/// input as the zigzag-encoded output minus input, small for the mostly
+/// local wiring of real circuits. The explicit counts make every gate
+/// unambiguous, including those on wire 0, and every batch decodes on its
+/// own.
+///
+/// The batches are compressed as consecutive zstd frames of
+/// [`BINARY_CIRCUIT_FRAME_BATCHES`] batches each. Frames decompress
+/// independently, so given their offsets from a [`GateIndex`] a reader can
+/// start at any frame, and carry zstd's content checksum. The file ends with
+/// the BLAKE3 checksum of everything before it.
+pub struct BinaryCircuitWriter<W: Write> {
+    writer: W,
+    compressor: zstd::bulk::Compressor<'static>,
+    /// Checksum of the bytes written so far, for the trailer
+    hasher: blake3::Hasher,
+    header: BinaryCircuitHeader,
+    gates_written: u64,
+    /// Gates per frame, a multiple of the batch size
+    frame_gates: u64,
+    /// Gates of the batch being filled
+    batch: Vec<Gate>,
+    /// Encoded batches of the frame being filled
+    frame: Vec<u8>,
+    /// Bytes written to `writer`, including the header
+    bytes_written: u64,
+    /// File offset of each frame written so far
+    frame_offsets: Vec<u64>,
+}
+
+impl<W: Write> std::fmt::Debug for BinaryCircuitWriter<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BinaryCircuitWriter")
+            .field("header", &self.header)
+            .field("gates_written", &self.gates_written)
+            .field("frame_gates", &self.frame_gates)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write> BinaryCircuitWriter<W> {
+    /// Write `header` and start compressing gates into `writer`
+    pub fn new(mut writer: W, header: BinaryCircuitHeader) -> Result<Self> {
+        let header_bytes = header.to_bytes();
+        writer.write_all(&header_bytes)?;
+        let mut compressor = zstd::bulk::Compressor::new(ZSTD_COMPRESSION_LEVEL)?;
+        compressor.set_parameter(CParameter::ChecksumFlag(true))?;
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&header_bytes);
+        Ok(Self {
+            writer,
+            compressor,
+            hasher,
+            header,
+            gates_written: 0,
+            frame_gates: (BINARY_CIRCUIT_FRAME_BATCHES * BINARY_CIRCUIT_BATCH_SIZE) as u64,
+            batch: Vec::with_capacity(BINARY_CIRCUIT_BATCH_SIZE),
+            frame: Vec::new(),
+            bytes_written: header_bytes.len() as u64,
+            frame_offsets: Vec::new(),
+        })
+    }
+
+    /// Compress every `frame_batches` batches into their own frame instead
+    /// of every [`BINARY_CIRCUIT_FRAME_BATCHES`]
+    ///
+    /// Smaller frames make seeking cheaper at some cost in compression.
+    /// Must be set before the first gate is written.
+    pub fn with_frame_batches(mut self, frame_batches: usize) -> Self {
+        assert!(frame_batches > 0, "frames need at least one batch");
+        assert_eq!(self.gates_written, 0, "frame size set after writing gates");
+        self.frame_gates = (frame_batches * BINARY_CIRCUIT_BATCH_SIZE) as u64;
+        self
+    }
+
+    /// Append a gate
+    ///
+    /// # Returns
+    /// * `Ok(())` - The gate is buffered or written
+    /// * `Err(GcError)` - A wire exceeds `num_wires`, or the header's gate count is exceeded
+    pub fn write_gate(&mut self, gate: &Gate) -> Result<()> {
+        if let Some(wire_id) = gate
+            .input_wires()
+            .chain([gate.output_wire])
+            .find(|&wire_id| wire_id >= self.header.num_wires)
+        {
+            return Err(GcError::InvalidCircuit(format!(
+                "Wire {} exceeds num_wires ({})",
+                wire_id, self.header.num_wires
+            )));
+        }
+        if self.gates_written == self.header.num_gates {
+            return Err(GcError::InvalidCircuit(format!(
+                "More gates than the {} declared in the header",
+                self.header.num_gates
+            )));
+        }
+        self.batch.push(*gate);
+        self.gates_written += 1;
+        if self.batch.len() == BINARY_CIRCUIT_BATCH_SIZE {
+            self.encode_batch();
+            if self.gates_written.is_multiple_of(self.frame_gates) {
+                self.write_frame()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the last frame and the checksum
+    ///
+    /// # Returns
+    /// * `Ok(W)` - The underlying writer, flushed
+    /// * `Err(GcError)` - Fewer gates were written than the header declares
+    pub fn finish(self) -> Result<W> {
+        Ok(self.finish_with_index()?.0)
+    }
+
+    /// Write the last frame and the checksum, and return the offsets of all frames
+    ///
+    /// # Returns
+    /// * `Ok((W, GateIndex))` - The underlying writer, flushed, and the index of the file
+    /// * `Err(GcError)` - Fewer gates were written than the header declares
+    pub fn finish_with_index(mut self) -> Result<(W, GateIndex)> {
+        self.encode_batch();
+        self.write_frame()?;
+        if self.gates_written != self.header.num_gates {
+            return Err(GcError::InvalidCircuit(format!(
+                "The header declares {} gates, but {} were written",
+                self.header.num_gates, self.gates_written
+            )));
+        }
+        let checksum = self.hasher.finalize();
+        self.writer.write_all(checksum.as_bytes())?;
+        self.bytes_written += BINARY_CIRCUIT_CHECKSUM_SIZE as u64;
+        self.writer.flush()?;
+        let index = GateIndex {
+            num_gates: self.header.num_gates,
+            circuit_size: self.bytes_written,
+            frame_gates: self.frame_gates,
+            frame_offsets: self.frame_offsets,
+        };
+        Ok((self.writer, index))
+    }
+
+    fn encode_batch(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+        self.frame.push(self.batch.len() as u8);
+        self.frame
+            .extend(self.batch.iter().map(|gate| type_code(gate.gate_type)));
+        let mut previous_output: WireId = 0;
+        for gate in &self.batch {
+            write_varint(
+                &mut self.frame,
+                zigzag(gate.output_wire.wrapping_sub(previous_output)),
+            );
+            for input_wire in gate.input_wires() {
+                write_varint(
+                    &mut self.frame,
+                    zigzag(gate.output_wire.wrapping_sub(input_wire)),
+                );
+            }
+            previous_output = gate.output_wire;
+        }
+        self.batch.clear();
+    }
+
+    fn write_frame(&mut self) -> Result<()> {
+        if self.frame.is_empty() {
+            return Ok(());
+        }
+        let compressed = self.compressor.compress(&self.frame)?;
+        self.writer.write_all(&compressed)?;
+        self.hasher.update(&compressed);
+        self.frame_offsets.push(self.bytes_written);
+        self.bytes_written += compressed.len() as u64;
+        self.frame.clear();
+        Ok(())
+    }
+}
+
+/// Offsets of the compressed frames of a binary circuit file, for seeking to
+/// a gate without decompressing everything before it
+///
+/// Written next to the circuit by [`BinaryCircuitWriter::finish_with_index`]
+/// and used by [`BinaryCircuitReader::seek_to_gate`]. Frame `i` starts with
+/// gate `i * frame_gates`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GateIndex {
+    /// Number of gates of the indexed circuit
+    pub num_gates: u64,
+    /// Size of the indexed circuit file in bytes, to detect a stale index
+    pub circuit_size: u64,
+    /// Gates per frame
+    pub frame_gates: u64,
+    /// File offset of each frame
+    pub frame_offsets: Vec<u64>,
+}
+
+impl GateIndex {
+    /// Save the index
+    ///
+    /// Layout: [`GATE_INDEX_MAGIC`], the format version as a little-endian
+    /// `u32`, then the gate count, circuit size, gates per frame, frame
+    /// count and each frame offset as little-endian `u64`s.
+    pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&GATE_INDEX_MAGIC)?;
+        writer.write_all(&GATE_INDEX_VERSION.to_le_bytes())?;
+        for value in [
+            self.num_gates,
+            self.circuit_size,
+            self.frame_gates,
+            self.frame_offsets.len() as u64,
+        ]
+        .iter()
+        .chain(&self.frame_offsets)
+        {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load an index saved by [`save_binary`](Self::save_binary)
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Self> {
+        const INDEX_FORMAT: &str = "gate index file";
+        let truncated = |error: io::Error| {
+            if error.kind() == io::ErrorKind::UnexpectedEof {
+                GcError::invalid_format(INDEX_FORMAT, "truncated file")
+            } else {
+                error.into()
+            }
+        };
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).map_err(truncated)?;
+        if header[..4] != GATE_INDEX_MAGIC {
+            return Err(GcError::invalid_format(INDEX_FORMAT, "bad magic bytes"));
+        }
+        let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if version != GATE_INDEX_VERSION {
+            return Err(GcError::FormatVersionMismatch {
+                format: "gate index",
+                found: version,
+                expected: GATE_INDEX_VERSION,
+            });
+        }
+
+        let mut read_u64 = || -> Result<u64> {
+            let mut bytes = [0u8; 8];
+            reader.read_exact(&mut bytes).map_err(truncated)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let num_gates = read_u64()?;
+        let circuit_size = read_u64()?;
+        let frame_gates = read_u64()?;
+        let num_frames = read_u64()?;
+        if frame_gates == 0
+            || !frame_gates.is_multiple_of(BINARY_CIRCUIT_BATCH_SIZE as u64)
+            || num_frames != num_gates.div_ceil(frame_gates)
+        {
+            return Err(GcError::invalid_format(
+                INDEX_FORMAT,
+                format!(
+                    "{} frames of {} gates cannot hold {} gates",
+                    num_frames, frame_gates, num_gates
+                ),
+            ));
+        }
+        let frame_offsets = (0..num_frames).map(|_| read_u64()).collect::<Result<_>>()?;
+        Ok(Self {
+            num_gates,
+            circuit_size,
+            frame_gates,
+            frame_offsets,
+        })
+    }
+}
+
+/// Reads the gates of a binary circuit file in order
+///
+/// Yields exactly the number of gates the header declares; a file that ends
+/// early or holds wire IDs beyond `num_wires` yields an error instead. After
+/// the last gate, the checksum at the end of the file is verified and a
+/// mismatch is yielded as a final error.
+pub struct BinaryCircuitReader<R: Read> {
+    decoder: zstd::Decoder<'static, BufReader<ChecksumReader<R>>>,
+    header: BinaryCircuitHeader,
+    /// Gates decoded so far, including the current batch
+    gates_decoded: u64,
+    batch: Vec<Gate>,
+    /// Next gate of `batch` to yield
+    position: usize,
+}
+
+impl<R: Read> std::fmt::Debug for BinaryCircuitReader<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BinaryCircuitReader")
+            .field("header", &self.header)
+            .field("gates_decoded", &self.gates_decoded)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R: Read> BinaryCircuitReader<R> {
+    /// Read the header and start decompressing the gates
+    ///
+    /// # Returns
+    /// * `Ok(BinaryCircuitReader)` - Reader positioned at the first gate
+    /// * `Err(GcError)` - Not a binary circuit file, or an unsupported version
+    pub fn new(reader: R) -> Result<Self> {
+        let mut reader = ChecksumReader::new(reader);
+        let header = BinaryCircuitHeader::read_from(&mut reader)?;
+        Self::with_decoder(reader, header, 0)
+    }
+
+    /// Decompress gates from the current position of `reader`, which is at
+    /// the start of a frame holding gate `first_gate`
+    fn with_decoder(
+        reader: ChecksumReader<R>,
+        header: BinaryCircuitHeader,
+        first_gate: u64,
+    ) -> Result<Self> {
+        Ok(Self {
+            header,
+            decoder: zstd::Decoder::new(reader)?,
+            gates_decoded: first_gate,
+            batch: Vec::with_capacity(BINARY_CIRCUIT_BATCH_SIZE),
+            position: 0,
+        })
+    }
+
+    /// Header of the file
+    pub fn header(&self) -> &BinaryCircuitHeader {
+        &self.header
+    }
+
+    /// Number of the next gate to yield
+    pub fn position(&self) -> u64 {
+        self.gates_decoded - (self.batch.len() - self.position) as u64
+    }
+
+    fn read_batch(&mut self) -> Result<()> {
+        let mut count = [0u8; 1];
+        self.decoder.read_exact(&mut count).map_err(corrupt)?;
+        let count = count[0] as usize;
+        if count == 0 || self.gates_decoded + count as u64 > self.header.num_gates {
+            return Err(GcError::invalid_format(
+                FORMAT,
+                format!(
+                    "batch of {} gates after {} of {}",
+                    count, self.gates_decoded, self.header.num_gates
+                ),
+            ));
+        }
+        let mut codes = [0u8; BINARY_CIRCUIT_BATCH_SIZE];
+        self.decoder
+            .read_exact(&mut codes[..count])
+            .map_err(corrupt)?;
+
+        self.batch.clear();
+        self.position = 0;
+        let mut previous_output: WireId = 0;
+        for &code in &codes[..count] {
+            let gate_type = gate_type(code).ok_or_else(|| {
+                GcError::invalid_format(FORMAT, format!("unknown gate type {}", code))
+            })?;
+            let output_wire =
+                previous_output.wrapping_add(unzigzag(read_varint(&mut self.decoder)?));
+            let mut input_wires = [0; 2];
+            for input_wire in input_wires.iter_mut().take(gate_type.num_inputs()) {
+                *input_wire = output_wire.wrapping_sub(unzigzag(read_varint(&mut self.decoder)?));
+            }
+            if gate_type == GateType::Buf {
+                // Copies read their input twice, as built by `Gate::buf`
+                input_wires[1] = input_wires[0];
+            }
+            let gate = Gate {
+                input_wire_1: input_wires[0],
+                input_wire_2: input_wires[1],
+                output_wire,
+                gate_type,
+            };
+            if let Some(wire_id) = gate
+                .input_wires()
+                .chain([output_wire])
+                .find(|&wire_id| wire_id >= self.header.num_wires)
+            {
+                return Err(GcError::invalid_format(
+                    FORMAT,
+                    format!(
+                        "wire {} of gate {} exceeds num_wires ({})",
+                        wire_id,
+                        self.gates_decoded + self.batch.len() as u64,
+                        self.header.num_wires
+                    ),
+                ));
+            }
+            self.batch.push(gate);
+            previous_output = output_wire;
+        }
+        self.gates_decoded += count as u64;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> BinaryCircuitReader<R> {
+    /// Read the header and position the reader at gate `gate`, decompressing
+    /// only the frame that holds it
+    ///
+    /// The checksum at the end of the file covers everything before it, so
+    /// it is not verified; the zstd checksums of the frames still are.
+    ///
+    /// # Arguments
+    /// * `reader` - Binary circuit file
+    /// * `index` - Index of the file
+    /// * `gate` - Gate to yield first; the gate count positions it after the last gate
+    ///
+    /// # Returns
+    /// * `Ok(BinaryCircuitReader)` - Reader yielding gate `gate` onwards
+    /// * `Err(GcError)` - The index belongs to another file, or `gate` is out of range
+    pub fn seek_to_gate(mut reader: R, index: &GateIndex, gate: u64) -> Result<Self> {
+        let circuit_size = reader.seek(SeekFrom::End(0))?;
+        reader.rewind()?;
+        let header = BinaryCircuitHeader::read_from(&mut reader)?;
+        if index.num_gates != header.num_gates || index.circuit_size != circuit_size {
+            return Err(GcError::InvalidArgument(format!(
+                "Gate index is for a circuit of {} gates and {} bytes, not {} gates and {} bytes",
+                index.num_gates, index.circuit_size, header.num_gates, circuit_size
+            )));
+        }
+        if gate > header.num_gates {
+            return Err(GcError::InvalidArgument(format!(
+                "Gate {} is beyond the {} gates of the circuit",
+                gate, header.num_gates
+            )));
+        }
+
+        let frame = gate / index.frame_gates;
+        let mut binary = match index.frame_offsets.get(frame as usize) {
+            Some(&offset) => {
+                reader.seek(SeekFrom::Start(offset))?;
+                let first_gate = frame * index.frame_gates;
+                Self::with_decoder(ChecksumReader::unverified(reader), header, first_gate)?
+            }
+            // Past the last frame: nothing left to decode
+            None => {
+                let num_gates = header.num_gates;
+                Self::with_decoder(ChecksumReader::unverified(reader), header, num_gates)?
+            }
+        };
+        while binary.position() < gate {
+            if let Some(Err(e)) = binary.next() {
+                return Err(e);
+            }
+        }
+        Ok(binary)
+    }
+}
+
+impl<R: Read> Iterator for BinaryCircuitReader<R> {
+    type Item = Result<Gate>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position == self.batch.len() {
+            if self.gates_decoded == self.header.num_gates {
+                // Checks the trailer once, then ends
+                return self.decoder.get_mut().get_mut().verify().err().map(Err);
+            }
+            if let Err(e) = self.read_batch() {
+                // Nothing after a corrupt batch can be trusted
+                self.gates_decoded = self.header.num_gates;
+                self.batch.clear();
+                self.position = 0;
+                self.decoder.get_mut().get_mut().hasher = None;
+                return Some(Err(e));
+            }
+        }
+        self.position += 1;
+        Some(Ok(self.batch[self.position - 1]))
+    }
+}
+
+/// Checksums the bytes of a binary circuit file as they are read, holding
+/// back the last [`BINARY_CIRCUIT_CHECKSUM_SIZE`] bytes seen, which end up
+/// being the trailer
+struct ChecksumReader<R> {
+    inner: R,
+    /// `None` once verified, or when the file is not read from the start
+    hasher: Option<blake3::Hasher>,
+    /// Last bytes read, not hashed yet
+    tail: Vec<u8>,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Some(blake3::Hasher::new()),
+            tail: Vec::with_capacity(BINARY_CIRCUIT_CHECKSUM_SIZE),
+        }
+    }
+
+    fn unverified(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: None,
+            tail: Vec::new(),
+        }
+    }
+
+    /// Read to the end of the file and compare its trailer with the checksum
+    /// of everything before it
+    fn verify(&mut self) -> Result<()> {
+        if self.hasher.is_none() {
+            return Ok(());
+        }
+        io::copy(self, &mut io::sink())?;
+        let hasher = self.hasher.take().expect("checked above");
+        if self.tail.len() < BINARY_CIRCUIT_CHECKSUM_SIZE {
+            return Err(GcError::invalid_format(FORMAT, "truncated file"));
+        }
+        let checksum: [u8; BINARY_CIRCUIT_CHECKSUM_SIZE] = self.tail[..].try_into().unwrap();
+        // blake3::Hash compares in constant time
+        if hasher.finalize() != checksum {
+            return Err(GcError::ChecksumMismatch {
+                artifact: "binary circuit file",
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            self.tail.extend_from_slice(&buf[..len]);
+            let hashed = self.tail.len().saturating_sub(BINARY_CIRCUIT_CHECKSUM_SIZE);
+            hasher.update(&self.tail[..hashed]);
+            self.tail.drain(..hashed);
+        }
+        Ok(len)
+    }
+}
+
+/// Convert a Bristol circuit into the binary circuit format
+///
+/// Bristol Fashion input and output declarations are kept in the header.
+///
+/// # Arguments
+/// * `stream` - Bristol circuit
+/// * `writer` - Destination of the binary circuit
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok((BinaryCircuitHeader, GateIndex))` - Header of the converted circuit, and its index
+/// * `Err(GcError)` - Malformed circuit, or a gate count that differs from its header
+pub fn convert_bristol_to_binary<R: Read, W: Write>(
+    stream: &mut BufferedLineStream<R>,
+    writer: W,
+    progress: &dyn ProgressObserver,
+) -> Result<(BinaryCircuitHeader, GateIndex)> {
+    let circuit_header = read_header(stream)?;
+    let header = BinaryCircuitHeader {
+        num_gates: circuit_header.num_gates,
+        num_wires: circuit_header.num_wires,
+        io: circuit_header.io,
+    };
+    let mut binary = BinaryCircuitWriter::new(writer, header.clone())?;
+
+    progress.start(Some(header.num_gates), "Converting to binary");
+    let mut line_number = circuit_header.lines; // Already processed header
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        let gate = parse_gate_line(line_result?, line_number)?;
+        binary.write_gate(&gate)?;
+        if binary
+            .gates_written
+            .is_multiple_of(PROGRESS_UPDATE_INTERVAL)
+        {
+            progress.update(binary.gates_written);
+        }
+    }
+    let (_, index) = binary.finish_with_index()?;
+    progress.finish(&format!("✓ Converted {} gates", header.num_gates));
+    Ok((header, index))
+}
+
+/// Convert a binary circuit back into Bristol text
+///
+/// # Arguments
+/// * `reader` - Binary circuit
+/// * `writer` - Destination of the Bristol circuit
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(BinaryCircuitHeader)` - Header of the circuit
+/// * `Err(GcError)` - Not a binary circuit file, or a corrupt one
+pub fn convert_binary_to_bristol<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    progress: &dyn ProgressObserver,
+) -> Result<BinaryCircuitHeader> {
+    let mut binary = BinaryCircuitReader::new(reader)?;
+    let header = binary.header().clone();
+    let mut bristol = BristolWriter::new(
+        writer,
+        header.num_gates,
+        header.num_wires,
+        header.io.as_ref(),
+    )?;
+
+    progress.start(Some(header.num_gates), "Converting to Bristol");
+    for gate in &mut binary {
+        bristol.write_gate(&gate?)?;
+        if bristol
+            .gates_written()
+            .is_multiple_of(PROGRESS_UPDATE_INTERVAL)
+        {
+            progress.update(bristol.gates_written());
+        }
+    }
+    bristol.finish()?;
+    progress.finish(&format!("✓ Converted {} gates", header.num_gates));
+    Ok(header)
+}
+
+/// Read a whole binary circuit into memory
+///
+/// # Returns
+/// * `Ok(Circuit)` - The circuit, with the declarations of its header
+/// * `Err(GcError)` - Not a binary circuit file, or a corrupt one
+pub fn read_binary_circuit<R: Read>(reader: R) -> Result<Circuit> {
+    let binary = BinaryCircuitReader::new(reader)?;
+    let header = binary.header().clone();
+    let gates = binary.collect::<Result<Vec<_>>>()?;
+    Ok(Circuit {
+        num_wires: header.num_wires,
+        gates,
+        io: header.io,
+    })
+}
+
+fn type_code(gate_type: GateType) -> u8 {
+    match gate_type {
+        GateType::Xor => 0,
+        GateType::And => 1,
+        GateType::Buf => 2,
+        GateType::Const(false) => 3,
+        GateType::Const(true) => 4,
+    }
+}
+
+fn gate_type(code: u8) -> Option<GateType> {
+    Some(match code {
+        0 => GateType::Xor,
+        1 => GateType::And,
+        2 => GateType::Buf,
+        3 => GateType::Const(false),
+        4 => GateType::Const(true),
+        _ => return None,
+    })
+}
+
+/// Map a wrapping difference of wire IDs to a small unsigned value
+fn zigzag(delta: u64) -> u64 {
+    let delta = delta as i64;
+    ((delta << 1) ^ (delta >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> u64 {
+    ((value >> 1) as i64 ^ -((value & 1) as i64)) as u64
+}
+
+/// Append `value` 7 bits at a time, least significant first
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte).map_err(corrupt)?;
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(GcError::invalid_format(FORMAT, "varint exceeds 64 bits"))
+}
+
+/// Report a file that ends early as malformed rather than as an I/O failure
+fn truncated(error: io::Error) -> GcError {
+    if error.kind() == io::ErrorKind::UnexpectedEof {
+        GcError::invalid_format(FORMAT, "truncated file")
+    } else {
+        error.into()
+    }
+}
+
+/// Report a frame that fails to decompress, e.g. on a zstd checksum
+/// mismatch, as malformed rather than as an I/O failure
+fn corrupt(error: io::Error) -> GcError {
+    if error.kind() == io::ErrorKind::Other {
+        GcError::invalid_format(FORMAT, format!("corrupt frame: {}", error))
+    } else {
+        truncated(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+
+    #[test]
+    fn test_binary_round_trip() -> Result<()> {
+        // Several batches, gates reading wire 0, constants and copies
+        let mut bristol = String::from("600 603\n2 2 1\n1 1\n\n");
+        for gate in 0..600u64 {
+            let output = gate + 3;
+            bristol.push_str(&match gate % 4 {
+                0 => format!("2 1 0 {} {} AND\n", output - 1, output),
+                1 => format!("2 1 {} 0 {} XOR\n", output - 2, output),
+                2 => format!("1 1 {} {} EQW\n", output - 1, output),
+                _ => format!("1 1 {} {} EQ\n", gate % 8 / 4, output),
+            });
+        }
+
+        let mut binary = Vec::new();
+        let (header, _) = convert_bristol_to_binary(
+            &mut BufferedLineStream::from_reader(bristol.as_bytes()),
+            &mut binary,
+            &NoProgress,
+        )?;
+        assert_eq!(header.num_gates, 600);
+        assert!(binary.len() < bristol.len() / 4);
+
+        let mut converted = Vec::new();
+        convert_binary_to_bristol(binary.as_slice(), &mut converted, &NoProgress)?;
+        assert_eq!(String::from_utf8(converted).unwrap(), bristol);
+        assert_eq!(
+            read_binary_circuit(binary.as_slice())?,
+            Circuit::parse(&bristol)?
+        );
+
+        let first_error = |bytes: &[u8]| -> Result<GcError> {
+            Ok(BinaryCircuitReader::new(bytes)?
+                .find_map(|gate| gate.err())
+                .unwrap())
+        };
+        let error_msg = first_error(&binary[..binary.len() - 40])?.to_string();
+        assert!(error_msg.contains("truncated"), "{}", error_msg);
+
+        // Every gate decodes, but the trailer does not match
+        let mut corrupt = binary.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        let error = first_error(&corrupt)?;
+        assert!(
+            matches!(error, GcError::ChecksumMismatch { .. }),
+            "{}",
+            error
+        );
+
+        // The frame's own checksum catches corrupt gates before the trailer
+        let mut corrupt = binary.clone();
+        corrupt[binary.len() - BINARY_CIRCUIT_CHECKSUM_SIZE - 20] ^= 1;
+        assert!(!matches!(
+            first_error(&corrupt)?,
+            GcError::ChecksumMismatch { .. }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_seek_to_gate() -> Result<()> {
+        let header = BinaryCircuitHeader {
+            num_gates: 1000,
+            num_wires: 1002,
+            io: None,
+        };
+        let gates: Vec<Gate> = (0..1000)
+            .map(|gate| Gate::xor(gate, gate + 1, gate + 2))
+            .collect();
+        let mut binary =
+            BinaryCircuitWriter::new(Vec::new(), header.clone())?.with_frame_batches(2);
+        for gate in &gates {
+            binary.write_gate(gate)?;
+        }
+        let (binary, index) = binary.finish_with_index()?;
+        assert_eq!(index.frame_offsets.len(), 2);
+        assert_eq!(index.frame_offsets[0], header.to_bytes().len() as u64);
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("circuit.idx");
+        index.save_binary(&path)?;
+        let index = GateIndex::load_binary(&path)?;
+
+        for start in [0, 509, 510, 777, 1000] {
+            let reader =
+                BinaryCircuitReader::seek_to_gate(io::Cursor::new(&binary), &index, start)?;
+            assert_eq!(reader.position(), start);
+            let rest = reader.collect::<Result<Vec<_>>>()?;
+            assert_eq!(rest, gates[start as usize..]);
+        }
+
+        let error = BinaryCircuitReader::seek_to_gate(io::Cursor::new(&binary[..100]), &index, 0);
+        assert!(
+            error
+                .unwrap_err()
+                .to_string()
+                .contains("not 1000 gates and 100 bytes")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,452 @@
+use crate::error::{GcError, Result};
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::emitter::GateEmitter;
+use crate::gate::{Circuit, WireId};
+use crate::stream::BufferedLineStream;
+
+/// Kind of file named in import errors
+const FORMAT: &str = "BLIF file";
+
+/// Nodes with up to this many inputs are checked for being XORs of their
+/// inputs, by evaluating their whole truth table
+const MAX_AFFINE_INPUTS: usize = 10;
+
+/// A `.names` node: a single-output function given by a sum of products
+#[derive(Debug)]
+struct Node {
+    /// Signals the function reads
+    inputs: Vec<String>,
+    /// Cubes of the cover, one `0`, `1` or `-` per input
+    cubes: Vec<Vec<u8>>,
+    /// The cover lists the rows where the output is 1, rather than 0
+    on_set: bool,
+    /// Line of the `.names` command, for errors
+    line: u64,
+}
+
+/// What a signal name refers to
+#[derive(Debug, Clone, Copy)]
+enum Signal {
+    /// Primary input, with its wire
+    Input(WireId),
+    /// Output of the node with this index
+    Node(usize),
+}
+
+/// Import the first model of a combinational BLIF netlist
+///
+/// Nodes computing an XOR of some of their inputs, possibly inverted, such
+/// as copies, inverters, constants and the XOR tables of synthesis tools,
+/// become free XOR gates. Other nodes are lowered from their sum-of-products
+/// cover: each cube becomes an AND of its literals and the cubes are ORed
+/// together, where inverted literals are XORs with a constant-one wire and
+/// `a OR b` costs a single AND as `a XOR b XOR (a AND b)`. Pairwise disjoint
+/// cubes are summed with XORs only, and covers of the OFF-set are inverted. `.conn` connections
+/// are copies. Nodes may appear in any order; the inputs take the first
+/// wires in `.inputs` order and the outputs get the last ones, under one
+/// input and one output bus as in [`import_aiger`](crate::aiger::import_aiger).
+/// Latches, subcircuits and library gates are not supported.
+///
+/// # Arguments
+/// * `stream` - BLIF netlist, e.g. from Yosys `write_blif`
+///
+/// # Returns
+/// * `Ok(Circuit)` - The circuit with its input/output declarations
+/// * `Err(GcError)` - Malformed netlist, undefined or cyclic signals, or an unsupported command
+pub fn import_blif<R: Read>(stream: &mut BufferedLineStream<R>) -> Result<Circuit> {
+    let mut input_names = Vec::new();
+    let mut output_names = Vec::new();
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut signals: HashMap<String, Signal> = HashMap::new();
+    let mut lines = LogicalLines {
+        stream,
+        line_number: 0,
+        pushed_back: None,
+    };
+    while let Some((line_number, line)) = lines.next()? {
+        let mut tokens = line.split_whitespace();
+        let Some(command) = tokens.next() else {
+            continue;
+        };
+        match command {
+            ".model" | ".attr" | ".param" | ".cname" => {}
+            ".end" => break,
+            ".inputs" => {
+                for name in tokens {
+                    let wire_id = input_names.len() as WireId;
+                    define(&mut signals, name, Signal::Input(wire_id), line_number)?;
+                    input_names.push(name.to_string());
+                }
+            }
+            ".outputs" => output_names.extend(tokens.map(str::to_string)),
+            ".names" | ".conn" => {
+                let mut names: Vec<String> = tokens.map(str::to_string).collect();
+                let Some(output) = names.pop() else {
+                    return Err(invalid(line_number, format!("{} without signals", command)));
+                };
+                let node = if command == ".conn" {
+                    if names.len() != 1 {
+                        return Err(invalid(line_number, ".conn connects exactly two signals"));
+                    }
+                    Node {
+                        inputs: names,
+                        cubes: vec![b"1".to_vec()],
+                        on_set: true,
+                        line: line_number,
+                    }
+                } else {
+                    read_cover(&mut lines, names, line_number)?
+                };
+                define(
+                    &mut signals,
+                    &output,
+                    Signal::Node(nodes.len()),
+                    line_number,
+                )?;
+                nodes.push(node);
+            }
+            ".latch" | ".mlatch" | ".subckt" | ".gate" | ".search" => {
+                return Err(invalid(
+                    line_number,
+                    format!(
+                        "{} is not supported: only flat combinational netlists can be garbled",
+                        command
+                    ),
+                ));
+            }
+            _ => {
+                return Err(invalid(
+                    line_number,
+                    format!("unexpected '{}'", line.trim()),
+                ));
+            }
+        }
+    }
+
+    lower(&nodes, &signals, input_names.len() as u64, &output_names)
+}
+
+/// Read the cover rows following a `.names` command
+fn read_cover<R: Read>(
+    lines: &mut LogicalLines<'_, R>,
+    inputs: Vec<String>,
+    line: u64,
+) -> Result<Node> {
+    let mut cubes = Vec::new();
+    let mut output_values = Vec::new();
+    while let Some((line_number, row)) = lines.next()? {
+        if row.trim_start().starts_with('.') {
+            lines.push_back(line_number, row);
+            break;
+        }
+        let tokens: Vec<&str> = row.split_whitespace().collect();
+        let (cube, value) = match (inputs.len(), tokens.as_slice()) {
+            (0, [value]) => ("", *value),
+            (_, [cube, value]) => (*cube, *value),
+            _ => return Err(invalid(line_number, format!("invalid cover row '{}'", row))),
+        };
+        if cube.len() != inputs.len() || !cube.bytes().all(|c| matches!(c, b'0' | b'1' | b'-')) {
+            return Err(invalid(
+                line_number,
+                format!("expected {} of 0, 1 or - in '{}'", inputs.len(), row),
+            ));
+        }
+        output_values.push(match value {
+            "1" => true,
+            "0" => false,
+            _ => {
+                return Err(invalid(
+                    line_number,
+                    format!("invalid output value in '{}'", row),
+                ));
+            }
+        });
+        cubes.push(cube.as_bytes().to_vec());
+    }
+    if output_values.windows(2).any(|pair| pair[0] != pair[1]) {
+        return Err(invalid(line, "cover mixes rows for output 0 and 1"));
+    }
+    Ok(Node {
+        inputs,
+        cubes,
+        on_set: output_values.first().copied().unwrap_or(true),
+        line,
+    })
+}
+
+/// Emit the gates of every node in topological order
+fn lower(
+    nodes: &[Node],
+    signals: &HashMap<String, Signal>,
+    num_inputs: u64,
+    output_names: &[String],
+) -> Result<Circuit> {
+    let mut emitter = GateEmitter::new(num_inputs);
+    let mut wires: Vec<Option<WireId>> = vec![None; nodes.len()];
+    let lookup = |name: &str, line: u64| {
+        signals
+            .get(name)
+            .copied()
+            .ok_or_else(|| invalid(line, format!("signal '{}' is never defined", name)))
+    };
+
+    // Depth-first, so each node follows the nodes it reads
+    let mut visiting = vec![false; nodes.len()];
+    let mut stack = Vec::new();
+    for root in 0..nodes.len() {
+        stack.push((root, false));
+        while let Some((index, operands_done)) = stack.pop() {
+            if wires[index].is_some() {
+                continue;
+            }
+            let node = &nodes[index];
+            if operands_done {
+                let input_wires = node
+                    .inputs
+                    .iter()
+                    .map(|name| match lookup(name, node.line)? {
+                        Signal::Input(wire_id) => Ok(wire_id),
+                        Signal::Node(operand) => Ok(wires[operand].expect("operands come first")),
+                    })
+                    .collect::<Result<Vec<WireId>>>()?;
+                wires[index] = Some(lower_cover(&mut emitter, node, &input_wires));
+                continue;
+            }
+            visiting[index] = true;
+            stack.push((index, true));
+            for name in node.inputs.iter().rev() {
+                if let Signal::Node(operand) = lookup(name, node.line)?
+                    && wires[operand].is_none()
+                {
+                    if visiting[operand] {
+                        return Err(invalid(
+                            nodes[operand].line,
+                            format!("signal '{}' depends on itself", name),
+                        ));
+                    }
+                    stack.push((operand, false));
+                }
+            }
+        }
+    }
+
+    let output_wires = output_names
+        .iter()
+        .map(|name| match lookup(name, 0)? {
+            Signal::Input(wire_id) => Ok(wire_id),
+            Signal::Node(index) => Ok(wires[index].expect("every node is lowered")),
+        })
+        .collect::<Result<Vec<WireId>>>()?;
+    Ok(emitter.into_circuit(&output_wires))
+}
+
+/// Gates computing a node's cover over `input_wires`
+fn lower_cover(emitter: &mut GateEmitter, node: &Node, input_wires: &[WireId]) -> WireId {
+    if let Some((inverted, terms)) = affine_form(node) {
+        let sum = terms
+            .into_iter()
+            .map(|input| input_wires[input])
+            .reduce(|a, b| emitter.xor(a, b));
+        return match sum {
+            None => emitter.constant(inverted),
+            Some(sum) if inverted => emitter.not(sum),
+            Some(sum) => sum,
+        };
+    }
+
+    let products: Vec<WireId> = node
+        .cubes
+        .iter()
+        .map(|cube| {
+            let literals: Vec<WireId> = cube
+                .iter()
+                .zip(input_wires)
+                .filter_map(|(&value, &wire_id)| match value {
+                    b'1' => Some(wire_id),
+                    b'0' => Some(emitter.not(wire_id)),
+                    _ => None,
+                })
+                .collect();
+            literals
+                .into_iter()
+                .reduce(|a, b| emitter.and(a, b))
+                .unwrap_or_else(|| emitter.constant(true))
+        })
+        .collect();
+
+    // Disjoint cubes are never true together, so their OR is their XOR
+    let disjoint = node.cubes.iter().enumerate().all(|(i, cube)| {
+        node.cubes[i + 1..].iter().all(|other| {
+            cube.iter()
+                .zip(other)
+                .any(|pair| matches!(pair, (b'0', b'1') | (b'1', b'0')))
+        })
+    });
+    let sum = products
+        .into_iter()
+        .reduce(|a, b| {
+            if disjoint {
+                emitter.xor(a, b)
+            } else {
+                emitter.or(a, b)
+            }
+        })
+        .unwrap_or_else(|| emitter.constant(false));
+    if node.on_set { sum } else { emitter.not(sum) }
+}
+
+/// The node's function as `inverted XOR` the inputs listed in `terms`, if it is one
+fn affine_form(node: &Node) -> Option<(bool, Vec<usize>)> {
+    let num_inputs = node.inputs.len();
+    if num_inputs > MAX_AFFINE_INPUTS {
+        return None;
+    }
+    let evaluate = |bits: usize| {
+        let covered = node.cubes.iter().any(|cube| {
+            cube.iter().enumerate().all(|(input, &value)| match value {
+                b'1' => bits >> input & 1 == 1,
+                b'0' => bits >> input & 1 == 0,
+                _ => true,
+            })
+        });
+        covered == node.on_set
+    };
+    let inverted = evaluate(0);
+    let terms: Vec<usize> = (0..num_inputs)
+        .filter(|&input| evaluate(1 << input) != inverted)
+        .collect();
+    let mask: usize = terms.iter().map(|&input| 1 << input).sum();
+    (0..1usize << num_inputs)
+        .all(|bits| evaluate(bits) == (inverted ^ ((bits & mask).count_ones() % 2 == 1)))
+        .then_some((inverted, terms))
+}
+
+/// Name `name` a signal, which no other command may define
+fn define(
+    signals: &mut HashMap<String, Signal>,
+    name: &str,
+    signal: Signal,
+    line: u64,
+) -> Result<()> {
+    if signals.insert(name.to_string(), signal).is_some() {
+        return Err(invalid(line, format!("signal '{}' is defined twice", name)));
+    }
+    Ok(())
+}
+
+/// Invalid BLIF at `line`
+fn invalid(line: u64, message: impl std::fmt::Display) -> GcError {
+    GcError::invalid_format(FORMAT, format!("line {}: {}", line, message))
+}
+
+/// Lines of a BLIF file without comments, with `\` continuations joined
+struct LogicalLines<'s, R> {
+    stream: &'s mut BufferedLineStream<R>,
+    line_number: u64,
+    /// Line to return again from the next call to [`next`](Self::next)
+    pushed_back: Option<(u64, String)>,
+}
+
+impl<R: Read> LogicalLines<'_, R> {
+    /// Next non-blank logical line and the line number it starts on
+    fn next(&mut self) -> Result<Option<(u64, String)>> {
+        if let Some(line) = self.pushed_back.take() {
+            return Ok(Some(line));
+        }
+        let mut logical = String::new();
+        let mut start = 0;
+        while let Some(line) = self.stream.next_line() {
+            let line = line?;
+            self.line_number += 1;
+            let line = line.split('#').next().unwrap_or_default();
+            if logical.is_empty() {
+                start = self.line_number;
+            }
+            match line.trim_end().strip_suffix('\\') {
+                Some(continued) => {
+                    logical.push_str(continued);
+                    logical.push(' ');
+                }
+                None => {
+                    logical.push_str(line);
+                    if !logical.trim().is_empty() {
+                        return Ok(Some((start, logical)));
+                    }
+                    logical.clear();
+                }
+            }
+        }
+        Ok((!logical.trim().is_empty()).then_some((start, logical)))
+    }
+
+    /// Return `line`, starting on `line_number`, again from the next call to [`next`](Self::next)
+    fn push_back(&mut self, line_number: u64, line: String) {
+        self.pushed_back = Some((line_number, line));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gate::GateType;
+    use crate::plain::evaluate_plain;
+    use crate::wire_analyzer::analyze_gates;
+    use std::collections::HashMap;
+
+    /// Full adder with a NAND output, nodes out of order
+    const FULL_ADDER: &str = "\
+# full adder
+.model full_adder
+.inputs a b \\
+  cin
+.outputs s cout nand
+.names t cin s
+10 1
+01 1
+.names a b t  # half sum
+01 1
+10 1
+.names a b cin cout
+11- 1
+1-1 1
+-11 1
+.names a b nand
+11 0
+.end
+";
+
+    #[test]
+    fn test_import_blif() -> Result<()> {
+        let circuit = import_blif(&mut BufferedLineStream::from_reader(FULL_ADDER.as_bytes()))?;
+        // Three products and two ORs for cout, one AND for the NAND; the XORs are free
+        let and_gates = circuit
+            .gates
+            .iter()
+            .filter(|gate| gate.gate_type == GateType::And)
+            .count();
+        assert_eq!(and_gates, 6);
+
+        let io = circuit.io.as_ref().unwrap();
+        assert_eq!(io.input_wires(), 0..3);
+        let outputs = io.output_wires(circuit.num_wires);
+        let wire_report = analyze_gates(&circuit.gates, circuit.num_wires)?;
+        for bits in 0..8 {
+            let (a, b, cin) = (bits & 1 == 1, bits & 2 == 2, bits & 4 == 4);
+            let inputs = HashMap::from([(0, a), (1, b), (2, cin)]);
+            let values = evaluate_plain(&circuit, &wire_report, &inputs)?;
+            assert_eq!(values[&outputs.start], a ^ b ^ cin);
+            assert_eq!(values[&(outputs.start + 1)], (a & b) | (cin & (a ^ b)));
+            assert_eq!(values[&(outputs.start + 2)], !(a & b));
+        }
+
+        let error_msg = import_blif(&mut BufferedLineStream::from_reader(
+            ".model m\n.inputs d clk\n.outputs q\n.latch d q re clk 0\n.end\n".as_bytes(),
+        ))
+        .unwrap_err()
+        .to_string();
+        assert!(error_msg.contains("line 4: .latch is not supported"));
+        Ok(())
+    }
+}
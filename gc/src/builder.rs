@@ -0,0 +1,263 @@
+use crate::error::{GcError, Result};
+use std::collections::HashSet;
+
+use crate::bus::{BitOrder, Bus, BusLayout};
+use crate::gate::{Circuit, Gate, WireId};
+use crate::parser::IoDeclaration;
+
+/// Builds a circuit in memory from named input buses and gate calls
+///
+/// Every call returns the wire it creates, so circuits are written as
+/// expressions over wires instead of hand-counted wire IDs:
+///
+/// ```
+/// use gc::builder::CircuitBuilder;
+///
+/// let mut builder = CircuitBuilder::new();
+/// let a = builder.input("a", 2);
+/// let b = builder.input("b", 2);
+/// let low = builder.xor(a[0], b[0]);
+/// let carry = builder.and(a[0], b[0]);
+/// let high = builder.xor(a[1], b[1]);
+/// let high = builder.xor(high, carry);
+/// builder.output("sum", &[low, high]);
+/// let (circuit, layout) = builder.build()?;
+/// # Ok::<(), gc::error::GcError>(())
+/// ```
+///
+/// Inputs and gates may be added in any order. [`build`](Self::build)
+/// numbers the input buses first, in the order they were declared, then
+/// the gates, then copies of the output buses, so the result is a Bristol
+/// Fashion circuit whose [`BusLayout`] names each declared value. Gates can
+/// only read wires that exist when they are added, so they always come out
+/// in topological order.
+#[derive(Debug, Default)]
+pub struct CircuitBuilder {
+    /// Gates on the builder's own wire IDs
+    gates: Vec<Gate>,
+    /// Number of wires created so far, inputs and gate outputs alike
+    num_wires: u64,
+    /// Input buses in declaration order, with their wires
+    inputs: Vec<(String, Vec<WireId>)>,
+    /// Output buses in declaration order, with the wires they copy
+    outputs: Vec<(String, Vec<WireId>)>,
+    /// Constant wires: `constants[value]`, once needed
+    constants: [Option<WireId>; 2],
+}
+
+impl CircuitBuilder {
+    /// Start an empty circuit
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn new_wire(&mut self) -> WireId {
+        self.num_wires += 1;
+        self.num_wires - 1
+    }
+
+    fn push(&mut self, gate: impl FnOnce(WireId) -> Gate) -> WireId {
+        let output_wire = self.new_wire();
+        self.gates.push(gate(output_wire));
+        output_wire
+    }
+
+    /// Declare an input bus of `width` wires, least significant bit first
+    pub fn input(&mut self, name: &str, width: u64) -> Vec<WireId> {
+        let wires: Vec<WireId> = (0..width).map(|_| self.new_wire()).collect();
+        self.inputs.push((name.to_string(), wires.clone()));
+        wires
+    }
+
+    /// Declare an output bus holding `wires`, least significant bit first
+    pub fn output(&mut self, name: &str, wires: &[WireId]) {
+        self.outputs.push((name.to_string(), wires.to_vec()));
+    }
+
+    /// Wire holding the constant `value`, shared by every caller
+    pub fn constant(&mut self, value: bool) -> WireId {
+        if let Some(wire_id) = self.constants[value as usize] {
+            return wire_id;
+        }
+        let wire_id = self.push(|output_wire| Gate::constant(value, output_wire));
+        self.constants[value as usize] = Some(wire_id);
+        wire_id
+    }
+
+    /// Wire holding `a XOR b`
+    pub fn xor(&mut self, a: WireId, b: WireId) -> WireId {
+        self.push(|output_wire| Gate::xor(a, b, output_wire))
+    }
+
+    /// Wire holding `a AND b`
+    pub fn and(&mut self, a: WireId, b: WireId) -> WireId {
+        self.push(|output_wire| Gate::and(a, b, output_wire))
+    }
+
+    /// Wire holding `NOT a`, as a free XOR with the constant one
+    pub fn not(&mut self, a: WireId) -> WireId {
+        let one = self.constant(true);
+        self.xor(a, one)
+    }
+
+    /// Wire holding `a OR b`, as `a XOR b XOR (a AND b)` for a single AND gate
+    pub fn or(&mut self, a: WireId, b: WireId) -> WireId {
+        let both = self.and(a, b);
+        let either = self.xor(a, b);
+        self.xor(either, both)
+    }
+
+    /// Wire holding `if_true` if `select` is set and `if_false` otherwise,
+    /// for a single AND gate
+    pub fn mux(&mut self, select: WireId, if_false: WireId, if_true: WireId) -> WireId {
+        let differs = self.xor(if_false, if_true);
+        let flip = self.and(select, differs);
+        self.xor(if_false, flip)
+    }
+
+    /// Number the wires and assemble the circuit and its bus layout
+    ///
+    /// # Returns
+    /// * `Ok((Circuit, BusLayout))` - Bristol Fashion circuit and the names of its values
+    /// * `Err(GcError)` - A bus is empty or named twice, or a bus or gate holds a wire of another
+    ///   builder
+    pub fn build(self) -> Result<(Circuit, BusLayout)> {
+        let mut names = HashSet::new();
+        for (name, wires) in self.inputs.iter().chain(&self.outputs) {
+            if wires.is_empty() {
+                return Err(GcError::InvalidArgument(format!(
+                    "Bus '{}' has no wires",
+                    name
+                )));
+            }
+            if wires.iter().any(|&wire_id| wire_id >= self.num_wires) {
+                return Err(GcError::InvalidArgument(format!(
+                    "Bus '{}' holds a wire that this builder did not create",
+                    name
+                )));
+            }
+            if !names.insert(name) {
+                return Err(GcError::InvalidArgument(format!(
+                    "Bus '{}' is declared more than once",
+                    name
+                )));
+            }
+        }
+        for (index, gate) in self.gates.iter().enumerate() {
+            if gate
+                .input_wires()
+                .any(|wire_id| wire_id >= gate.output_wire)
+            {
+                return Err(GcError::InvalidCircuit(format!(
+                    "Gate {} reads a wire that this builder did not create",
+                    index
+                )));
+            }
+        }
+
+        let mut new_ids = vec![0; self.num_wires as usize];
+        let mut next_id: WireId = 0;
+        let mut layout = BusLayout::default();
+        let bus = |name: &str, first_wire: WireId, width: usize| Bus {
+            name: name.to_string(),
+            first_wire,
+            last_wire: first_wire + width as WireId - 1,
+            bit_order: BitOrder::Le,
+        };
+        for (name, wires) in &self.inputs {
+            layout.inputs.push(bus(name, next_id, wires.len()));
+            for &wire_id in wires {
+                new_ids[wire_id as usize] = next_id;
+                next_id += 1;
+            }
+        }
+        for gate in &self.gates {
+            new_ids[gate.output_wire as usize] = next_id;
+            next_id += 1;
+        }
+        let mut gates: Vec<Gate> = self
+            .gates
+            .iter()
+            .map(|gate| gate.map_wires(|wire_id| new_ids[wire_id as usize]))
+            .collect();
+        for (name, wires) in &self.outputs {
+            layout.outputs.push(bus(name, next_id, wires.len()));
+            for &wire_id in wires {
+                gates.push(Gate::buf(new_ids[wire_id as usize], next_id));
+                next_id += 1;
+            }
+        }
+
+        let widths = |buses: &[Bus]| buses.iter().map(Bus::width).collect();
+        let io = IoDeclaration::new(widths(&layout.inputs), widths(&layout.outputs), next_id)?;
+        Ok((
+            Circuit {
+                num_wires: next_id,
+                gates,
+                io: Some(io),
+            },
+            layout,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulate::run_end_to_end;
+
+    #[test]
+    fn test_circuit_builder() -> Result<()> {
+        // max = a < b ? b : a, with the gates added before input b exists
+        let mut builder = CircuitBuilder::new();
+        let a = builder.input("a", 3);
+        let not_a: Vec<WireId> = a.iter().map(|&wire_id| builder.not(wire_id)).collect();
+        let b = builder.input("b", 3);
+        let mut less = builder.and(not_a[0], b[0]);
+        for bit in 1..3 {
+            let differs = builder.xor(a[bit], b[bit]);
+            less = builder.mux(differs, less, b[bit]);
+        }
+        let max: Vec<WireId> = (0..3)
+            .map(|bit| builder.mux(less, a[bit], b[bit]))
+            .collect();
+        builder.output("max", &max);
+        builder.output("less", &[less]);
+        let (circuit, layout) = builder.build()?;
+
+        assert_eq!(
+            circuit.io,
+            Some(IoDeclaration::new(
+                vec![3, 3],
+                vec![3, 1],
+                circuit.num_wires
+            )?)
+        );
+        assert_eq!(
+            (layout.inputs[1].first_wire, layout.inputs[1].last_wire),
+            (3, 5)
+        );
+        for a in 0..8u64 {
+            for b in 0..8u64 {
+                let assignment = serde_json::json!({ "a": a, "b": b });
+                let inputs = layout.resolve_inputs(assignment.as_object().unwrap())?;
+                let result = run_end_to_end(&circuit, &inputs, &[3; 32])?;
+                assert!(result.mismatched_outputs().is_empty());
+                let outputs = result.plain.bus_values(&layout.outputs)?;
+                assert_eq!(outputs["max"].to_u128(), Some(a.max(b) as u128));
+                assert_eq!(outputs["less"].to_u128(), Some((a < b) as u128));
+            }
+        }
+
+        let mut builder = CircuitBuilder::new();
+        let a = builder.input("a", 1);
+        builder.output("a", &a);
+        let error_msg = builder.build().unwrap_err().to_string();
+        assert!(
+            error_msg.contains("Bus 'a' is declared more than once"),
+            "{}",
+            error_msg
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,387 @@
+use crate::error::{GcError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::gate::WireId;
+use crate::input_assignment::{InputAssignment, resolve_keyed};
+use crate::partition::{InputPartition, WireRange};
+
+/// Bit order of a multi-bit bus
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BitOrder {
+    /// The first wire of the bus is the least significant bit
+    #[default]
+    Le,
+    /// The first wire of the bus is the most significant bit
+    Be,
+}
+
+/// A named group of contiguous wires forming one integer value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bus {
+    /// Name of the value (e.g. "sum")
+    pub name: String,
+    /// First wire of the bus
+    pub first_wire: WireId,
+    /// Last wire of the bus (inclusive)
+    pub last_wire: WireId,
+    /// How wires map to bit significance
+    #[serde(default)]
+    pub bit_order: BitOrder,
+}
+
+impl Bus {
+    /// Number of wires, i.e. bits, in the bus
+    pub fn width(&self) -> u64 {
+        self.last_wire - self.first_wire + 1
+    }
+
+    /// The wires of the bus as a half-open range
+    pub fn wire_range(&self) -> WireRange {
+        WireRange {
+            start: self.first_wire,
+            end: self.last_wire + 1,
+        }
+    }
+
+    /// Wire IDs ordered from least to most significant bit
+    pub fn wires_lsb_first(&self) -> Vec<WireId> {
+        let wires = self.first_wire..=self.last_wire;
+        match self.bit_order {
+            BitOrder::Le => wires.collect(),
+            BitOrder::Be => wires.rev().collect(),
+        }
+    }
+}
+
+impl FromStr for Bus {
+    type Err = GcError;
+
+    /// Parse `name=first..last` (little-endian) or `name=first..last:be`
+    fn from_str(spec: &str) -> Result<Self> {
+        let invalid_bus = || {
+            GcError::InvalidArgument(format!("Invalid bus '{}': expected name=first..last", spec))
+        };
+        let (name, range) = spec.split_once('=').ok_or_else(invalid_bus)?;
+
+        let (range, bit_order) = match range.split_once(':') {
+            Some((range, "le")) => (range, BitOrder::Le),
+            Some((range, "be")) => (range, BitOrder::Be),
+            Some((_, order)) => {
+                return Err(GcError::InvalidArgument(format!(
+                    "Invalid bit order '{}' in bus '{}'",
+                    order, spec
+                )));
+            }
+            None => (range, BitOrder::Le),
+        };
+
+        let (first, last) = range.split_once("..").ok_or_else(invalid_bus)?;
+        let first_wire: WireId = first.parse().map_err(|_| {
+            GcError::InvalidArgument(format!("Invalid first wire in bus '{}'", spec))
+        })?;
+        let last_wire: WireId = last.parse().map_err(|_| {
+            GcError::InvalidArgument(format!("Invalid last wire in bus '{}'", spec))
+        })?;
+
+        if name.is_empty() || last_wire < first_wire {
+            return Err(invalid_bus());
+        }
+
+        Ok(Bus {
+            name: name.to_string(),
+            first_wire,
+            last_wire,
+            bit_order,
+        })
+    }
+}
+
+/// Decoded value of an output bus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusValue {
+    /// Bit values ordered from least to most significant
+    pub bits: Vec<bool>,
+}
+
+impl BusValue {
+    /// Value of a `bus` given as a JSON integer or a string
+    ///
+    /// Strings are hex with a `0x` prefix or binary with a `0b` prefix (any
+    /// width, `_` allowed as a separator), or decimal (up to 128 bits).
+    ///
+    /// # Returns
+    /// * `Ok(BusValue)` - One bit per wire of the bus
+    /// * `Err(GcError)` - Not a non-negative integer, or too wide for the bus
+    pub fn from_json(value: &serde_json::Value, bus: &Bus) -> Result<Self> {
+        let invalid = || {
+            GcError::InvalidArgument(format!(
+                "Invalid value {} for bus '{}': expected an integer, or a hex or binary string",
+                value, bus.name
+            ))
+        };
+        let width = bus.width() as usize;
+        let bits: Vec<bool> = match value {
+            serde_json::Value::Number(number) => {
+                let value = number.as_u64().ok_or_else(invalid)?;
+                (0..64).map(|i| (value >> i) & 1 == 1).collect()
+            }
+            serde_json::Value::String(text) => {
+                let hex = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"));
+                let binary = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B"));
+                match (hex, binary) {
+                    (Some(digits), _) => parse_radix_bits(digits, 4).ok_or_else(invalid)?,
+                    (_, Some(digits)) => parse_radix_bits(digits, 1).ok_or_else(invalid)?,
+                    _ => {
+                        let value: u128 = text.parse().map_err(|_| invalid())?;
+                        (0..128).map(|i| (value >> i) & 1 == 1).collect()
+                    }
+                }
+            }
+            _ => return Err(invalid()),
+        };
+
+        if bits[width.min(bits.len())..].contains(&true) {
+            return Err(GcError::InvalidArgument(format!(
+                "Value {} does not fit in the {}-bit bus '{}'",
+                value, width, bus.name
+            )));
+        }
+        let mut bits = bits;
+        bits.resize(width, false);
+        Ok(Self { bits })
+    }
+
+    /// Value as an integer, if the bus is at most 128 bits wide
+    pub fn to_u128(&self) -> Option<u128> {
+        if self.bits.len() > 128 {
+            return None;
+        }
+        Some(
+            self.bits
+                .iter()
+                .enumerate()
+                .fold(0u128, |acc, (i, &bit)| acc | ((bit as u128) << i)),
+        )
+    }
+
+    /// Value as a big-endian hex string (`0x`-prefixed), for buses of any width
+    pub fn to_hex(&self) -> String {
+        let num_nibbles = self.bits.len().div_ceil(4).max(1);
+        let mut hex = String::with_capacity(num_nibbles + 2);
+        hex.push_str("0x");
+        for nibble_index in (0..num_nibbles).rev() {
+            let nibble = (0..4).fold(0u8, |acc, bit| {
+                let bit_index = nibble_index * 4 + bit;
+                let bit_value = self.bits.get(bit_index).copied().unwrap_or(false);
+                acc | ((bit_value as u8) << bit)
+            });
+            let _ = write!(hex, "{nibble:x}");
+        }
+        hex
+    }
+}
+
+/// Named input and output buses of a circuit, kept in a JSON sidecar
+///
+/// Lets input assignments, the garbler's share of the inputs and decoded
+/// outputs speak of values instead of wire IDs. For a 64-bit adder:
+///
+/// ```json
+/// {
+///   "inputs": [
+///     { "name": "a", "first_wire": 0, "last_wire": 63 },
+///     { "name": "b", "first_wire": 64, "last_wire": 127 }
+///   ],
+///   "outputs": [{ "name": "sum", "first_wire": 440, "last_wire": 503, "bit_order": "le" }]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BusLayout {
+    /// Input buses
+    #[serde(default)]
+    pub inputs: Vec<Bus>,
+    /// Output buses
+    #[serde(default)]
+    pub outputs: Vec<Bus>,
+}
+
+impl BusLayout {
+    /// Load a layout from JSON
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let layout = serde_json::from_str(&data)?;
+        Ok(layout)
+    }
+
+    /// Save the layout as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// The input bus called `name`
+    pub fn input(&self, name: &str) -> Option<&Bus> {
+        self.inputs.iter().find(|bus| bus.name == name)
+    }
+
+    /// Resolve an input assignment that may name input buses
+    ///
+    /// Keys that are wire IDs take a boolean or 0 or 1; keys naming an input
+    /// bus take its value as in [`BusValue::from_json`], e.g.
+    /// `{"a": 5, "b": "0xff", "130": true}`. See [`InputAssignment`] for
+    /// assignments that also check the wires against a circuit.
+    ///
+    /// # Returns
+    /// * `Ok(HashMap<WireId, bool>)` - Bit value per input wire: wire_id -> bit
+    /// * `Err(GcError)` - Unknown bus, invalid value, or a wire assigned twice
+    pub fn resolve_inputs(
+        &self,
+        assignment: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<HashMap<WireId, bool>> {
+        resolve_keyed(assignment, None, &self.inputs)
+    }
+
+    /// Load an input assignment that may name input buses
+    ///
+    /// See [`resolve_inputs`](Self::resolve_inputs) for the format.
+    pub fn load_inputs<P: AsRef<Path>>(&self, path: P) -> Result<HashMap<WireId, bool>> {
+        InputAssignment::load(path)?.resolve(None, &self.inputs)
+    }
+
+    /// Parse a garbler input partition that may name input buses
+    ///
+    /// Like [`InputPartition::parse`], with input bus names allowed next to
+    /// wire ranges, e.g. `a` or `a,128..130`.
+    pub fn input_partition(&self, spec: &str) -> Result<InputPartition> {
+        let garbler_inputs = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .map(|token| match self.input(token) {
+                Some(bus) => Ok(bus.wire_range()),
+                None => token.parse(),
+            })
+            .collect::<Result<Vec<WireRange>>>()?;
+        Ok(InputPartition { garbler_inputs })
+    }
+}
+
+/// Bits of a number written in hex (4 bits per digit) or binary (1 bit per
+/// digit), least significant first; `_` separates digits
+///
+/// Returns `None` for an empty number or a digit outside the radix.
+pub(crate) fn parse_radix_bits(digits: &str, bits_per_digit: u32) -> Option<Vec<bool>> {
+    let mut bits = Vec::with_capacity(digits.len() * bits_per_digit as usize);
+    for digit in digits.chars().rev().filter(|&c| c != '_') {
+        let value = digit.to_digit(1 << bits_per_digit)?;
+        bits.extend((0..bits_per_digit).map(|i| (value >> i) & 1 == 1));
+    }
+    (!bits.is_empty()).then_some(bits)
+}
+
+/// Assemble bits into the values of named buses
+///
+/// # Arguments
+/// * `buses` - Bus descriptions to assemble
+/// * `bit_of` - Bit value of a wire of a bus
+///
+/// # Returns
+/// * `Ok(BTreeMap<String, BusValue>)` - Bus name to value
+/// * `Err(GcError)` - The first error from `bit_of`
+pub fn assemble_buses(
+    buses: &[Bus],
+    mut bit_of: impl FnMut(&Bus, WireId) -> Result<bool>,
+) -> Result<BTreeMap<String, BusValue>> {
+    let mut values = BTreeMap::new();
+    for bus in buses {
+        let bits = bus
+            .wires_lsb_first()
+            .into_iter()
+            .map(|wire_id| bit_of(bus, wire_id))
+            .collect::<Result<Vec<bool>>>()?;
+        values.insert(bus.name.clone(), BusValue { bits });
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bus_from_str() -> Result<()> {
+        let bus: Bus = "sum=440..503".parse()?;
+        assert_eq!(bus.name, "sum");
+        assert_eq!((bus.first_wire, bus.last_wire), (440, 503));
+        assert_eq!(bus.bit_order, BitOrder::Le);
+
+        let bus: Bus = "x=0..7:be".parse()?;
+        assert_eq!(bus.bit_order, BitOrder::Be);
+
+        assert!("sum".parse::<Bus>().is_err());
+        assert!("sum=5..4".parse::<Bus>().is_err());
+        assert!("sum=0..4:xx".parse::<Bus>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bus_value_wide_hex() {
+        let mut bits = vec![false; 130];
+        bits[129] = true;
+        bits[0] = true;
+        let value = BusValue { bits };
+
+        assert_eq!(value.to_u128(), None);
+        assert_eq!(value.to_hex(), format!("0x2{}1", "0".repeat(31)));
+    }
+
+    #[test]
+    fn test_bus_layout_inputs() -> Result<()> {
+        let layout: BusLayout = serde_json::from_str(
+            r#"{"inputs": [
+                {"name": "a", "first_wire": 0, "last_wire": 3},
+                {"name": "b", "first_wire": 4, "last_wire": 7, "bit_order": "be"}
+            ]}"#,
+        )?;
+        assert!(layout.outputs.is_empty());
+
+        let assignment = serde_json::json!({"a": 5, "b": "0x1", "8": true});
+        let inputs = layout.resolve_inputs(assignment.as_object().unwrap())?;
+        let bits: Vec<bool> = (0..9).map(|wire_id| inputs[&wire_id]).collect();
+        assert_eq!(
+            bits,
+            [true, false, true, false, false, false, false, true, true]
+        );
+
+        let resolve = |assignment: serde_json::Value| {
+            layout
+                .resolve_inputs(assignment.as_object().unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(
+            resolve(serde_json::json!({"a": 16})).contains("does not fit in the 4-bit bus 'a'")
+        );
+        assert!(resolve(serde_json::json!({"c": 1})).contains("No input bus named 'c'"));
+        assert!(
+            resolve(serde_json::json!({"a": 1, "0": true})).contains("assigned more than once")
+        );
+
+        assert_eq!(
+            layout.input_partition("b, 10")?.garbler_inputs,
+            vec![
+                WireRange { start: 4, end: 8 },
+                WireRange { start: 10, end: 11 }
+            ]
+        );
+        assert!(layout.input_partition("c").is_err());
+        Ok(())
+    }
+}
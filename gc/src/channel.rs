@@ -0,0 +1,378 @@
+use crate::error::{GcError, Result};
+use std::io::{ErrorKind, Read, Write};
+
+use crate::constants::{
+    FRAME_HEADER_SIZE, FRAME_MAGIC, FRAMING_VERSION, MAX_MESSAGE_SIZE, MIN_FRAMING_VERSION,
+};
+
+/// Kind of a frame on a [`Channel`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    /// Range of framing versions a party speaks, see [`Channel::negotiate`]
+    Negotiate,
+    /// A protocol message
+    Message,
+}
+
+impl FrameType {
+    /// Identifier in the frame header
+    pub fn id(self) -> u8 {
+        match self {
+            FrameType::Negotiate => 0,
+            FrameType::Message => 1,
+        }
+    }
+
+    /// Frame type for an identifier, if known
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(FrameType::Negotiate),
+            1 => Some(FrameType::Message),
+            _ => None,
+        }
+    }
+}
+
+/// Header in front of every frame sent over a [`Channel`]
+///
+/// Layout ([`FRAME_HEADER_SIZE`] bytes): magic `GC`, framing version (u8),
+/// frame type (u8) and payload length (little-endian u64). The magic and
+/// version let a peer speaking another protocol, or another version of
+/// this one, be told apart from a corrupt stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// Framing version the frame was sent with
+    pub version: u8,
+    /// Kind of frame
+    pub frame_type: FrameType,
+    /// Payload bytes following the header
+    pub length: u64,
+}
+
+impl FrameHeader {
+    /// Header of a protocol message of `length` bytes at the current framing version
+    pub fn message(length: usize) -> Self {
+        Self {
+            version: FRAMING_VERSION,
+            frame_type: FrameType::Message,
+            length: length as u64,
+        }
+    }
+
+    /// Encode the header in its wire form
+    pub fn to_bytes(&self) -> [u8; FRAME_HEADER_SIZE] {
+        let mut bytes = [0u8; FRAME_HEADER_SIZE];
+        bytes[0..2].copy_from_slice(&FRAME_MAGIC);
+        bytes[2] = self.version;
+        bytes[3] = self.frame_type.id();
+        bytes[4..12].copy_from_slice(&self.length.to_le_bytes());
+        bytes
+    }
+
+    /// Decode a header, validating the magic, frame type and length
+    ///
+    /// # Returns
+    /// * `Ok(FrameHeader)` - The header; its version is left for the caller to check
+    /// * `Err(GcError::Protocol)` - Bad magic, unknown frame type, or a payload larger than [`MAX_MESSAGE_SIZE`]
+    pub fn from_bytes(bytes: &[u8; FRAME_HEADER_SIZE]) -> Result<Self> {
+        if bytes[0..2] != FRAME_MAGIC {
+            return Err(GcError::Protocol(
+                "Bad frame magic: the other party does not speak this protocol, or an older version without framing".to_string(),
+            ));
+        }
+        let frame_type = FrameType::from_id(bytes[3]).ok_or_else(|| {
+            GcError::Protocol(format!(
+                "Unknown frame type {} in framing version {}",
+                bytes[3], bytes[2]
+            ))
+        })?;
+        let length = u64::from_le_bytes(bytes[4..12].try_into().unwrap());
+        if length > MAX_MESSAGE_SIZE {
+            return Err(GcError::Protocol(format!(
+                "Message of {} bytes exceeds the limit of {} bytes",
+                length, MAX_MESSAGE_SIZE
+            )));
+        }
+        Ok(Self {
+            version: bytes[2],
+            frame_type,
+            length,
+        })
+    }
+}
+
+/// Message channel between the garbler and the evaluator over a byte stream
+///
+/// Each message is sent as a frame: a [`FrameHeader`] followed by the
+/// message bytes, so protocol steps that exchange byte messages can run over
+/// any reliable stream, e.g. a `TcpStream`. Parties agree on the framing
+/// version with [`negotiate`](Self::negotiate) when they connect. Counts
+/// the bytes moved in each direction, frame headers included, and
+/// optionally records every message for a transcript.
+#[derive(Debug)]
+pub struct Channel<S> {
+    stream: S,
+    /// Framing version of every frame after negotiation
+    version: u8,
+    bytes_sent: u64,
+    bytes_received: u64,
+    recording: Option<Vec<RecordedMessage>>,
+}
+
+/// A message recorded by a [`Channel`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedMessage {
+    /// Whether the recording party sent the message, rather than received it
+    pub sent: bool,
+    /// The message bytes, without the frame header
+    pub bytes: Vec<u8>,
+}
+
+impl<S: Read + Write> Channel<S> {
+    /// Wrap a connected stream
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            version: FRAMING_VERSION,
+            bytes_sent: 0,
+            bytes_received: 0,
+            recording: None,
+        }
+    }
+
+    /// Record every message sent or received from now on
+    pub fn with_recording(mut self) -> Self {
+        self.recording = Some(Vec::new());
+        self
+    }
+
+    /// Messages recorded so far, in order; empty unless recording
+    pub fn recorded(&self) -> &[RecordedMessage] {
+        self.recording.as_deref().unwrap_or_default()
+    }
+
+    /// Agree on a framing version with the other party
+    ///
+    /// Both parties call this right after connecting. Each announces the
+    /// range of framing versions it speaks and both settle on the newest one
+    /// in common, so a peer built from an older release keeps working as
+    /// long as the ranges overlap, and otherwise fails here with both ranges
+    /// named instead of midway through a protocol.
+    ///
+    /// # Returns
+    /// * `Ok(u8)` - The framing version used from now on
+    /// * `Err(GcError)` - No version in common, the other party does not negotiate, or it disconnected
+    pub fn negotiate(&mut self) -> Result<u8> {
+        self.write_frame(
+            FrameType::Negotiate,
+            &[MIN_FRAMING_VERSION, FRAMING_VERSION],
+        )?;
+        let (header, payload) = self.read_frame()?;
+        let &[peer_min, peer_max] = payload.as_slice() else {
+            return Err(GcError::Protocol(format!(
+                "Expected the other party's framing versions, got a {:?} frame of {} bytes",
+                header.frame_type,
+                payload.len()
+            )));
+        };
+        if header.frame_type != FrameType::Negotiate {
+            return Err(GcError::Protocol(
+                "The other party sent a message before negotiating the framing version".to_string(),
+            ));
+        }
+        let version = FRAMING_VERSION.min(peer_max);
+        if version < MIN_FRAMING_VERSION.max(peer_min) {
+            return Err(GcError::Protocol(format!(
+                "No framing version in common: this party speaks {}..={}, the other party {}..={}",
+                MIN_FRAMING_VERSION, FRAMING_VERSION, peer_min, peer_max
+            )));
+        }
+        self.version = version;
+        tracing::debug!(version, "Negotiated the framing version");
+        Ok(version)
+    }
+
+    /// Send one message and flush the stream
+    pub fn send(&mut self, message: &[u8]) -> Result<()> {
+        self.write_frame(FrameType::Message, message)?;
+        if let Some(recording) = &mut self.recording {
+            recording.push(RecordedMessage {
+                sent: true,
+                bytes: message.to_vec(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Receive the next message
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - The message bytes
+    /// * `Err(GcError)` - The other party disconnected, sent a malformed or unexpected frame, or announced a message larger than [`MAX_MESSAGE_SIZE`]
+    pub fn receive(&mut self) -> Result<Vec<u8>> {
+        let (header, message) = self.read_frame()?;
+        if header.frame_type != FrameType::Message {
+            return Err(GcError::Protocol(format!(
+                "Unexpected {:?} frame in the middle of a protocol",
+                header.frame_type
+            )));
+        }
+        if header.version != self.version {
+            return Err(GcError::Protocol(format!(
+                "Message framed with version {}, expected version {}",
+                header.version, self.version
+            )));
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.push(RecordedMessage {
+                sent: false,
+                bytes: message.clone(),
+            });
+        }
+        Ok(message)
+    }
+
+    fn write_frame(&mut self, frame_type: FrameType, payload: &[u8]) -> Result<()> {
+        let header = FrameHeader {
+            version: self.version,
+            frame_type,
+            length: payload.len() as u64,
+        };
+        self.stream.write_all(&header.to_bytes())?;
+        self.stream.write_all(payload)?;
+        self.stream.flush()?;
+        self.bytes_sent += (FRAME_HEADER_SIZE + payload.len()) as u64;
+        Ok(())
+    }
+
+    fn read_frame(&mut self) -> Result<(FrameHeader, Vec<u8>)> {
+        let mut header = [0u8; FRAME_HEADER_SIZE];
+        self.stream.read_exact(&mut header).map_err(disconnected)?;
+        let header = FrameHeader::from_bytes(&header)?;
+
+        // Grow the buffer as data arrives rather than trusting the length up front
+        let mut payload = Vec::new();
+        (&mut self.stream)
+            .take(header.length)
+            .read_to_end(&mut payload)?;
+        if (payload.len() as u64) < header.length {
+            return Err(GcError::Disconnected(format!(
+                "Connection closed after {} of {} message bytes",
+                payload.len(),
+                header.length
+            )));
+        }
+        self.bytes_received += FRAME_HEADER_SIZE as u64 + header.length;
+        Ok((header, payload))
+    }
+
+    /// Bytes sent so far
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Bytes received so far
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// Unwrap the underlying stream
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+/// Report an early end of stream as a disconnect rather than a bare I/O error
+fn disconnected(error: std::io::Error) -> GcError {
+    if error.kind() == ErrorKind::UnexpectedEof {
+        GcError::Disconnected("Connection closed while waiting for a message".to_string())
+    } else {
+        GcError::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn test_channel_round_trip() -> Result<()> {
+        let mut channel = Channel::new(Cursor::new(Vec::new()));
+        channel.send(b"hello")?;
+        channel.send(&[])?;
+        let frames = (2 * FRAME_HEADER_SIZE + 5) as u64;
+        assert_eq!(channel.bytes_sent(), frames);
+
+        let mut channel = Channel::new(Cursor::new(channel.into_inner().into_inner()));
+        assert_eq!(channel.receive()?, b"hello");
+        assert!(channel.receive()?.is_empty());
+        assert_eq!(channel.bytes_received(), frames);
+        assert!(matches!(channel.receive(), Err(GcError::Disconnected(_))));
+
+        // Truncated message, oversized length and a bare length prefix
+        let mut bytes = FrameHeader::message(10).to_bytes().to_vec();
+        bytes.extend_from_slice(b"short");
+        let mut channel = Channel::new(Cursor::new(bytes));
+        assert!(matches!(channel.receive(), Err(GcError::Disconnected(_))));
+        let mut header = FrameHeader::message(0);
+        header.length = u64::MAX;
+        let mut channel = Channel::new(Cursor::new(header.to_bytes().to_vec()));
+        assert!(matches!(channel.receive(), Err(GcError::Protocol(_))));
+        let mut bytes = 5u64.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&[0; 9]);
+        let mut channel = Channel::new(Cursor::new(bytes));
+        let error_msg = channel.receive().unwrap_err().to_string();
+        assert!(error_msg.contains("Bad frame magic"));
+
+        // Recording keeps both directions in order
+        let mut channel = Channel::new(Cursor::new(Vec::new())).with_recording();
+        channel.send(b"ping")?;
+        assert_eq!(
+            channel.recorded(),
+            [RecordedMessage {
+                sent: true,
+                bytes: b"ping".to_vec()
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_negotiation() -> Result<()> {
+        // Two parties of this version agree on it
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let peer = std::thread::spawn(move || -> Result<Vec<u8>> {
+            let mut channel = Channel::new(listener.accept()?.0);
+            channel.negotiate()?;
+            channel.receive()
+        });
+        let mut channel = Channel::new(TcpStream::connect(address)?);
+        assert_eq!(channel.negotiate()?, FRAMING_VERSION);
+        channel.send(b"hello")?;
+        assert_eq!(peer.join().unwrap()?, b"hello");
+
+        // A peer that only speaks newer versions is refused up front
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let peer = std::thread::spawn(move || -> Result<()> {
+            let mut stream = listener.accept()?.0;
+            stream.read_exact(&mut [0; FRAME_HEADER_SIZE + 2])?;
+            let header = FrameHeader {
+                version: FRAMING_VERSION + 5,
+                frame_type: FrameType::Negotiate,
+                length: 2,
+            };
+            stream.write_all(&header.to_bytes())?;
+            stream.write_all(&[FRAMING_VERSION + 5, FRAMING_VERSION + 9])?;
+            Ok(())
+        });
+        let mut channel = Channel::new(TcpStream::connect(address)?);
+        let error_msg = channel.negotiate().unwrap_err().to_string();
+        assert!(error_msg.contains("No framing version in common"));
+        peer.join().unwrap()?;
+        Ok(())
+    }
+}
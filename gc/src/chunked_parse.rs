@@ -0,0 +1,274 @@
+use crate::error::{GcError, Result};
+use memmap2::Mmap;
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::Duration;
+
+use crate::constants::PROGRESS_UPDATE_INTERVAL;
+use crate::parser::{CircuitHeader, read_header};
+use crate::progress::ProgressObserver;
+use crate::stream::BufferedLineStream;
+
+/// Chunks per worker thread, so threads that finish early pick up more work
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// How often the calling thread reports the progress of the workers
+const PROGRESS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Circuit file mapped into memory, to scan its gate lines on several threads
+///
+/// Read-only analyses such as
+/// [`count_gate_types_parallel`](crate::counter::count_gate_types_parallel)
+/// and [`analyze_wire_usage_parallel`](crate::wire_analyzer::analyze_wire_usage_parallel)
+/// split the gate lines into byte ranges aligned to line boundaries, let
+/// each thread gather statistics of the ranges it is handed, and merge them.
+/// Parse errors still name the line in the whole file, as a sequential pass
+/// would, and the file is mapped once for any number of analyses.
+#[derive(Debug)]
+pub struct ChunkedCircuit {
+    /// Read-only mapping of the whole file
+    mmap: Arc<Mmap>,
+    /// Parsed header of the circuit
+    header: CircuitHeader,
+    /// Byte offset of the first gate line
+    gates_start: usize,
+    /// Skip blank and comment lines
+    lenient: bool,
+}
+
+impl ChunkedCircuit {
+    /// Map a circuit file and read its header
+    ///
+    /// # Arguments
+    /// * `path` - Bristol circuit file, which must not be modified while it is mapped
+    /// * `lenient` - Skip blank and comment lines, see [`BufferedLineStream::with_lenient`]
+    ///
+    /// # Returns
+    /// * `Ok(ChunkedCircuit)` - Circuit ready to scan
+    /// * `Err(GcError)` - The file could not be mapped, or its header is invalid
+    pub fn open<P: AsRef<Path>>(path: P, lenient: bool) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the mapping is read-only and the circuit file is not
+        // expected to be modified while it is being read.
+        let mmap = Arc::new(unsafe { Mmap::map(&file)? });
+        let mut stream = BufferedLineStream::<File>::from_mapped(mmap.clone(), 0..mmap.len())
+            .with_lenient(lenient);
+        let header = read_header(&mut stream)?;
+        let gates_start = stream.mapped_offset().unwrap_or(mmap.len());
+        Ok(Self {
+            mmap,
+            header,
+            gates_start,
+            lenient,
+        })
+    }
+
+    /// Header of the circuit
+    pub fn header(&self) -> &CircuitHeader {
+        &self.header
+    }
+
+    /// Scan every gate line on `threads` threads
+    ///
+    /// Each worker thread starts from `init()` and calls `visit` on the
+    /// lines of the chunks it is handed, so a state sees an unpredictable
+    /// subset of the lines; merge the returned states for the result of the
+    /// whole circuit. `visit` also gets a line number to report in
+    /// [`GcError::Parse`] errors, which are renumbered to the line in the
+    /// file. Progress is reported in gate lines, from the calling thread.
+    ///
+    /// # Arguments
+    /// * `threads` - Worker threads, at least one
+    /// * `progress` - Receives progress updates; the caller starts and finishes the task
+    /// * `init` - Initial state of each worker
+    /// * `visit` - Called with a worker's state, a gate line and its line number
+    ///
+    /// # Returns
+    /// * `Ok((Vec<T>, u64))` - Final state of every worker and the number of gate lines
+    /// * `Err(GcError)` - The first error in file order
+    pub fn scan<T, I, F>(
+        &self,
+        threads: usize,
+        progress: &dyn ProgressObserver,
+        init: I,
+        visit: F,
+    ) -> Result<(Vec<T>, u64)>
+    where
+        T: Send,
+        I: Fn() -> T + Sync,
+        F: Fn(&mut T, &str, u64) -> Result<()> + Sync,
+    {
+        let threads = threads.max(1);
+        let chunks = self.split(threads * CHUNKS_PER_THREAD);
+        let next_chunk = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let lines_scanned = AtomicU64::new(0);
+        // Lines in each chunk, or the chunk's first error numbered from its start
+        let outcomes: Vec<Mutex<Option<Result<u64>>>> =
+            chunks.iter().map(|_| Mutex::new(None)).collect();
+
+        let states = std::thread::scope(|scope| {
+            // Every worker holds a sender, so the channel closes once all are done
+            let (running, all_done) = mpsc::channel::<()>();
+            let workers: Vec<_> = (0..threads.min(chunks.len()))
+                .map(|_| {
+                    let running = running.clone();
+                    let (init, visit) = (&init, &visit);
+                    let (chunks, outcomes) = (&chunks, &outcomes);
+                    let (next_chunk, failed, lines_scanned) =
+                        (&next_chunk, &failed, &lines_scanned);
+                    scope.spawn(move || {
+                        let _running = running;
+                        let mut state = init();
+                        // Chunks are handed out in order and finished even
+                        // after an error, so every chunk before a failed one
+                        // has its line count
+                        while !failed.load(Ordering::Relaxed) {
+                            let index = next_chunk.fetch_add(1, Ordering::Relaxed);
+                            let Some(range) = chunks.get(index) else {
+                                break;
+                            };
+                            let outcome =
+                                self.scan_chunk(range.clone(), &mut state, visit, lines_scanned);
+                            if outcome.is_err() {
+                                failed.store(true, Ordering::Relaxed);
+                            }
+                            *outcomes[index].lock().unwrap() = Some(outcome);
+                        }
+                        state
+                    })
+                })
+                .collect();
+            drop(running);
+
+            while let Err(mpsc::RecvTimeoutError::Timeout) =
+                all_done.recv_timeout(PROGRESS_POLL_INTERVAL)
+            {
+                progress.update(lines_scanned.load(Ordering::Relaxed));
+            }
+            workers
+                .into_iter()
+                .map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect::<Vec<T>>()
+        });
+
+        let mut lines_before = self.header.lines;
+        for outcome in outcomes {
+            match outcome.into_inner().unwrap() {
+                Some(Ok(lines)) => lines_before += lines,
+                Some(Err(GcError::Parse { line, message })) => {
+                    return Err(GcError::parse(lines_before + line, message));
+                }
+                Some(Err(e)) => return Err(e),
+                None => unreachable!("chunks before a failed one are always scanned"),
+            }
+        }
+        Ok((states, lines_before - self.header.lines))
+    }
+
+    /// Visit the lines of one chunk, numbering them from 1
+    fn scan_chunk<T, F>(
+        &self,
+        range: Range<usize>,
+        state: &mut T,
+        visit: &F,
+        lines_scanned: &AtomicU64,
+    ) -> Result<u64>
+    where
+        F: Fn(&mut T, &str, u64) -> Result<()>,
+    {
+        let mut stream = BufferedLineStream::<File>::from_mapped(self.mmap.clone(), range)
+            .with_lenient(self.lenient);
+        let mut line_number: u64 = 0;
+        while let Some(line_result) = stream.next_line() {
+            line_number += 1;
+            visit(state, line_result?, line_number)?;
+            if line_number.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+                lines_scanned.fetch_add(PROGRESS_UPDATE_INTERVAL, Ordering::Relaxed);
+            }
+        }
+        lines_scanned.fetch_add(line_number % PROGRESS_UPDATE_INTERVAL, Ordering::Relaxed);
+        Ok(line_number)
+    }
+
+    /// Split the gate lines into at most `count` ranges of about equal size,
+    /// each ending right after a newline or at the end of the file
+    fn split(&self, count: usize) -> Vec<Range<usize>> {
+        let bytes = &self.mmap[..];
+        let gates_length = bytes.len() - self.gates_start;
+        let mut ranges = Vec::with_capacity(count);
+        let mut start = self.gates_start;
+        for part in 1..count {
+            let target = (self.gates_start + gates_length * part / count).max(start);
+            let Some(newline) = bytes[target..].iter().position(|&byte| byte == b'\n') else {
+                break;
+            };
+            let end = target + newline + 1;
+            ranges.push(start..end);
+            start = end;
+        }
+        if start < bytes.len() {
+            ranges.push(start..bytes.len());
+        }
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+    use std::io::Write;
+
+    #[test]
+    fn test_scan_covers_every_line_once() -> Result<()> {
+        let mut circuit = String::from("100 300\n");
+        for gate in 0..100 {
+            circuit.push_str(&format!("2 1 {} {} {} XOR\n", gate, gate + 100, gate + 200));
+        }
+        let mut circuit_file = tempfile::NamedTempFile::new()?;
+        circuit_file.write_all(circuit.as_bytes())?;
+        circuit_file.flush()?;
+        let chunked = ChunkedCircuit::open(circuit_file.path(), false)?;
+
+        for threads in [1, 3, 8] {
+            let (states, lines) = chunked.scan(
+                threads,
+                &NoProgress,
+                Vec::new,
+                |seen: &mut Vec<String>, line, _| {
+                    seen.push(line.to_string());
+                    Ok(())
+                },
+            )?;
+            assert_eq!(lines, 100);
+            let mut seen: Vec<String> = states.into_iter().flatten().collect();
+            seen.sort_by_key(|line| line.split(' ').nth(2).unwrap().parse::<u64>().unwrap());
+            assert_eq!(seen, circuit.lines().skip(1).collect::<Vec<_>>());
+        }
+
+        // Errors name the line in the file, not in the chunk
+        let error = chunked
+            .scan(
+                4,
+                &NoProgress,
+                || (),
+                |_, line, line_number| {
+                    if line.contains(" 57 ") {
+                        return Err(GcError::parse(line_number, "rejected"));
+                    }
+                    Ok(())
+                },
+            )
+            .unwrap_err();
+        assert!(matches!(error, GcError::Parse { line: 59, .. }));
+        Ok(())
+    }
+}
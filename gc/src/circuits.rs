@@ -0,0 +1,305 @@
+use crate::error::{GcError, Result};
+
+use crate::emitter::GateEmitter;
+use crate::gate::{Circuit, WireId};
+
+/// Largest number of select bits [`mux_tree`] accepts
+pub const MAX_SELECT_BITS: u64 = 24;
+
+/// Adder of two `width`-bit numbers, rippling the carry from bit to bit
+///
+/// The sum is taken modulo `2^width`, like the `adder64` circuit of the MPC
+/// circuit repositories. Each full adder costs a single AND gate, for
+/// `width - 1` AND gates on an AND depth of `width - 1`.
+///
+/// # Arguments
+/// * `width` - Number of bits of each operand and of the sum
+///
+/// # Returns
+/// * `Ok(Circuit)` - Bristol Fashion circuit with inputs `a` and `b` and output `a + b`,
+///   all little-endian
+/// * `Err(GcError)` - `width` is 0
+pub fn ripple_carry_adder(width: u64) -> Result<Circuit> {
+    check_width(width)?;
+    let mut emitter = GateEmitter::new(2 * width);
+    let (a, b) = operands(width);
+    let sum = add(&mut emitter, &a, &b);
+    Ok(emitter.into_declared_circuit(vec![width, width], &[&sum]))
+}
+
+/// Adder of two `width`-bit numbers computing all carries with a parallel prefix
+///
+/// Computes the same sum as [`ripple_carry_adder`] with a Kogge-Stone carry
+/// lookahead network, trading more AND gates for an AND depth logarithmic
+/// in `width`.
+///
+/// # Arguments
+/// * `width` - Number of bits of each operand and of the sum
+///
+/// # Returns
+/// * `Ok(Circuit)` - Bristol Fashion circuit with inputs `a` and `b` and output `a + b`,
+///   all little-endian
+/// * `Err(GcError)` - `width` is 0
+pub fn carry_lookahead_adder(width: u64) -> Result<Circuit> {
+    check_width(width)?;
+    let mut emitter = GateEmitter::new(2 * width);
+    let (a, b) = operands(width);
+    let propagate: Vec<WireId> = a.iter().zip(&b).map(|(&x, &y)| emitter.xor(x, y)).collect();
+    // Generate and propagate signals of the bit ranges ending at each bit,
+    // doubling in length each round. A range that propagates a carry cannot
+    // generate one, so the OR merging them is a free XOR.
+    let mut generate: Vec<WireId> = a.iter().zip(&b).map(|(&x, &y)| emitter.and(x, y)).collect();
+    let mut group_propagate = propagate.clone();
+    let mut distance = 1;
+    while distance < width as usize {
+        let mut next_generate = generate.clone();
+        let mut next_propagate = group_propagate.clone();
+        for bit in distance..width as usize {
+            let carried = emitter.and(group_propagate[bit], generate[bit - distance]);
+            next_generate[bit] = emitter.xor(generate[bit], carried);
+            if bit >= 2 * distance {
+                next_propagate[bit] =
+                    emitter.and(group_propagate[bit], group_propagate[bit - distance]);
+            }
+        }
+        generate = next_generate;
+        group_propagate = next_propagate;
+        distance *= 2;
+    }
+
+    let mut sum = vec![propagate[0]];
+    for bit in 1..width as usize {
+        sum.push(emitter.xor(propagate[bit], generate[bit - 1]));
+    }
+    Ok(emitter.into_declared_circuit(vec![width, width], &[&sum]))
+}
+
+/// Multiplier of two `width`-bit numbers, adding shifted partial products
+///
+/// The product is taken modulo `2^width`, like the `mult64` circuit of the
+/// MPC circuit repositories, so partial product bits beyond it are never
+/// computed.
+///
+/// # Arguments
+/// * `width` - Number of bits of each operand and of the product
+///
+/// # Returns
+/// * `Ok(Circuit)` - Bristol Fashion circuit with inputs `a` and `b` and output `a * b`,
+///   all little-endian
+/// * `Err(GcError)` - `width` is 0
+pub fn multiplier(width: u64) -> Result<Circuit> {
+    check_width(width)?;
+    let mut emitter = GateEmitter::new(2 * width);
+    let (a, b) = operands(width);
+    let mut product: Vec<WireId> = a.iter().map(|&x| emitter.and(x, b[0])).collect();
+    for shift in 1..width as usize {
+        let row: Vec<WireId> = a[..a.len() - shift]
+            .iter()
+            .map(|&x| emitter.and(x, b[shift]))
+            .collect();
+        let partial = add(&mut emitter, &product[shift..], &row);
+        product.truncate(shift);
+        product.extend(partial);
+    }
+    Ok(emitter.into_declared_circuit(vec![width, width], &[&product]))
+}
+
+/// Unsigned comparison of two `width`-bit numbers
+///
+/// Scans the bits from the least significant up, letting each bit where the
+/// operands differ decide, for one AND gate per bit.
+///
+/// # Arguments
+/// * `width` - Number of bits of each operand
+///
+/// # Returns
+/// * `Ok(Circuit)` - Bristol Fashion circuit with little-endian inputs `a` and `b` and the
+///   single output bit `a < b`
+/// * `Err(GcError)` - `width` is 0
+pub fn comparator(width: u64) -> Result<Circuit> {
+    check_width(width)?;
+    let mut emitter = GateEmitter::new(2 * width);
+    let (a, b) = operands(width);
+    let differs = emitter.xor(a[0], b[0]);
+    let mut less = emitter.and(differs, b[0]);
+    for bit in 1..width as usize {
+        // less = a[bit] != b[bit] ? b[bit] : less
+        let differs = emitter.xor(a[bit], b[bit]);
+        let flips = emitter.xor(b[bit], less);
+        let flip = emitter.and(differs, flips);
+        less = emitter.xor(less, flip);
+    }
+    Ok(emitter.into_declared_circuit(vec![width, width], &[&[less]]))
+}
+
+/// Equality check of two `width`-bit values
+///
+/// Inverts the XOR of each bit pair and combines them in a balanced tree of
+/// `width - 1` AND gates.
+///
+/// # Arguments
+/// * `width` - Number of bits of each operand
+///
+/// # Returns
+/// * `Ok(Circuit)` - Bristol Fashion circuit with inputs `a` and `b` and the single output
+///   bit `a == b`
+/// * `Err(GcError)` - `width` is 0
+pub fn equality(width: u64) -> Result<Circuit> {
+    check_width(width)?;
+    let mut emitter = GateEmitter::new(2 * width);
+    let (a, b) = operands(width);
+    let mut equal: Vec<WireId> = a
+        .iter()
+        .zip(&b)
+        .map(|(&x, &y)| {
+            let differs = emitter.xor(x, y);
+            emitter.not(differs)
+        })
+        .collect();
+    while equal.len() > 1 {
+        equal = equal
+            .chunks(2)
+            .map(|pair| match *pair {
+                [x, y] => emitter.and(x, y),
+                _ => pair[0],
+            })
+            .collect();
+    }
+    Ok(emitter.into_declared_circuit(vec![width, width], &[&equal]))
+}
+
+/// Multiplexer choosing one of `2^select_bits` data bits
+///
+/// A binary tree of 2-to-1 multiplexers, each a single AND gate, halves the
+/// candidates once per select bit, least significant first.
+///
+/// # Arguments
+/// * `select_bits` - Number of select bits, at most [`MAX_SELECT_BITS`]
+///
+/// # Returns
+/// * `Ok(Circuit)` - Bristol Fashion circuit with inputs `data` of `2^select_bits` bits and a
+///   little-endian `select`, and the single output bit `data[select]`
+/// * `Err(GcError)` - `select_bits` is 0 or above [`MAX_SELECT_BITS`]
+pub fn mux_tree(select_bits: u64) -> Result<Circuit> {
+    if select_bits == 0 || select_bits > MAX_SELECT_BITS {
+        return Err(GcError::InvalidArgument(format!(
+            "Number of select bits must be between 1 and {}, got {}",
+            MAX_SELECT_BITS, select_bits
+        )));
+    }
+    let data_bits = 1u64 << select_bits;
+    let mut emitter = GateEmitter::new(data_bits + select_bits);
+    let mut candidates: Vec<WireId> = (0..data_bits).collect();
+    for select in data_bits..data_bits + select_bits {
+        candidates = candidates
+            .chunks(2)
+            .map(|pair| {
+                // pair[0] ^ (select & (pair[0] ^ pair[1]))
+                let differs = emitter.xor(pair[0], pair[1]);
+                let flip = emitter.and(select, differs);
+                emitter.xor(pair[0], flip)
+            })
+            .collect();
+    }
+    Ok(emitter.into_declared_circuit(vec![data_bits, select_bits], &[&candidates]))
+}
+
+fn check_width(width: u64) -> Result<()> {
+    if width == 0 {
+        return Err(GcError::InvalidArgument(
+            "Operand width must be at least 1 bit".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Wires of the operands `a` and `b`, the first `2 * width` inputs
+fn operands(width: u64) -> (Vec<WireId>, Vec<WireId>) {
+    ((0..width).collect(), (width..2 * width).collect())
+}
+
+/// Ripple-carry sum of two equally wide little-endian values, dropping the carry out
+fn add(emitter: &mut GateEmitter, a: &[WireId], b: &[WireId]) -> Vec<WireId> {
+    let mut sum = vec![emitter.xor(a[0], b[0])];
+    let mut carry = None;
+    for bit in 1..a.len() {
+        // The carry into bit 1 is the AND of bit 0; later carries take
+        // carry ^ ((a ^ carry) & (b ^ carry)), a single AND per bit
+        let carry_in = match carry {
+            None => emitter.and(a[bit - 1], b[bit - 1]),
+            Some(carry) => {
+                let a_differs = emitter.xor(a[bit - 1], carry);
+                let b_differs = emitter.xor(b[bit - 1], carry);
+                let flip = emitter.and(a_differs, b_differs);
+                emitter.xor(carry, flip)
+            }
+        };
+        carry = Some(carry_in);
+        let half = emitter.xor(a[bit], b[bit]);
+        sum.push(emitter.xor(half, carry_in));
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plain::evaluate_plain;
+    use crate::wire_analyzer::analyze_circuit;
+    use std::collections::HashMap;
+
+    /// Evaluate `circuit` on little-endian `values` of the given widths
+    fn evaluate(circuit: &Circuit, values: &[(u64, u64)]) -> Result<u64> {
+        let mut inputs = HashMap::new();
+        let mut wire_id = 0;
+        for &(value, width) in values {
+            for bit in 0..width {
+                inputs.insert(wire_id, value >> bit & 1 == 1);
+                wire_id += 1;
+            }
+        }
+        let outputs = evaluate_plain(circuit, &analyze_circuit(circuit)?, &inputs)?;
+        let first_output = circuit.num_wires - outputs.len() as u64;
+        Ok(outputs
+            .iter()
+            .map(|(&wire_id, &bit)| (bit as u64) << (wire_id - first_output))
+            .sum())
+    }
+
+    #[test]
+    fn test_generated_circuits() -> Result<()> {
+        let width = 5;
+        let mask = (1 << width) - 1;
+        let ripple = ripple_carry_adder(width)?;
+        let lookahead = carry_lookahead_adder(width)?;
+        let product = multiplier(width)?;
+        let less = comparator(width)?;
+        let equal = equality(width)?;
+        for a in 0..1 << width {
+            for b in 0..1 << width {
+                let operands = [(a, width), (b, width)];
+                assert_eq!(evaluate(&ripple, &operands)?, (a + b) & mask);
+                assert_eq!(evaluate(&lookahead, &operands)?, (a + b) & mask);
+                assert_eq!(evaluate(&product, &operands)?, (a * b) & mask);
+                assert_eq!(evaluate(&less, &operands)?, (a < b) as u64);
+                assert_eq!(evaluate(&equal, &operands)?, (a == b) as u64);
+            }
+        }
+
+        let mux = mux_tree(3)?;
+        let data = 0b1011_0010;
+        for select in 0..8 {
+            assert_eq!(
+                evaluate(&mux, &[(data, 8), (select, 3)])?,
+                data >> select & 1
+            );
+        }
+
+        // Generated circuits round-trip through Bristol text
+        let mut text = Vec::new();
+        ripple.write_bristol(&mut text)?;
+        assert_eq!(Circuit::parse(std::str::from_utf8(&text).unwrap())?, ripple);
+        assert!(ripple_carry_adder(0).is_err());
+        Ok(())
+    }
+}
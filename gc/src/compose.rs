@@ -0,0 +1,330 @@
+use crate::error::{GcError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use crate::gate::{Circuit, WireId};
+use crate::parser::IoDeclaration;
+use crate::renumber::{PENDING, UNMAPPED, WireRemap, renumber_gate};
+use crate::wire_analyzer::{WireUsageReport, analyze_circuit};
+
+/// Outputs of the first circuit feeding inputs of the second
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Connection {
+    /// First output wire of the first circuit
+    pub output: WireId,
+    /// First input wire of the second circuit
+    pub input: WireId,
+    /// Number of consecutive wires connected
+    #[serde(default = "default_width")]
+    pub width: u64,
+}
+
+fn default_width() -> u64 {
+    1
+}
+
+/// How two circuits are stitched together
+///
+/// Loaded from JSON such as
+///
+/// ```json
+/// { "connections": [{ "output": 120, "input": 0, "width": 8 }, { "output": 130, "input": 64 }] }
+/// ```
+///
+/// which feeds outputs 120 to 127 of the first circuit into inputs 0 to 7 of
+/// the second, and output 130 into input 64.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WiringSpec {
+    /// Connected wire ranges
+    pub connections: Vec<Connection>,
+}
+
+impl WiringSpec {
+    /// Load a wiring spec from JSON
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let spec = serde_json::from_str(&data)?;
+        Ok(spec)
+    }
+}
+
+/// Where the inputs and outputs of both circuits ended up in their composition
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompositionRemap {
+    /// Inputs and unconnected outputs of the first circuit
+    pub first: WireRemap,
+    /// Unconnected inputs and outputs of the second circuit
+    pub second: WireRemap,
+}
+
+impl CompositionRemap {
+    /// Save the remap tables as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Stitch two circuits into one, feeding outputs of `first` into inputs of `second`
+///
+/// The composition evaluates the gates of `first`, then those of `second`,
+/// on a gap-free range of wires. Its inputs are those of `first` followed by
+/// the unconnected inputs of `second`; its outputs are the unconnected
+/// outputs of `first` followed by those of `second`. Connected outputs become
+/// intermediate wires. The composition is always declared in Bristol Fashion:
+/// each declared input or output of either circuit keeps its unconnected
+/// wires as one value, and an undeclared circuit contributes all its inputs,
+/// or outputs, as a single value.
+///
+/// # Arguments
+/// * `first` - The circuit computed first
+/// * `second` - The circuit reading outputs of `first`
+/// * `wiring` - Which outputs of `first` feed which inputs of `second`
+///
+/// # Returns
+/// * `Ok((Circuit, CompositionRemap))` - The composition and the new IDs of the inputs and outputs of both circuits
+/// * `Err(GcError)` - The wiring connects a wire that is not an output of `first` or an input of
+///   `second`, connects an input twice, or either circuit is malformed
+pub fn compose_circuits(
+    first: &Circuit,
+    second: &Circuit,
+    wiring: &WiringSpec,
+) -> Result<(Circuit, CompositionRemap)> {
+    let first_report = analyze_circuit(first)?;
+    let second_report = analyze_circuit(second)?;
+    let connected = connected_inputs(&first_report, &second_report, wiring)?;
+    let connected_outputs: HashSet<WireId> = connected.values().copied().collect();
+
+    let mut first_ids = vec![UNMAPPED; first.num_wires as usize];
+    let mut second_ids = vec![UNMAPPED; second.num_wires as usize];
+    let mut next_id: WireId = 0;
+    let mut input_widths = Vec::new();
+    let mut output_widths = Vec::new();
+
+    number_values(
+        &mut next_id,
+        &mut first_ids,
+        declared_values(first, &first_report, true),
+        &|_| false,
+        &mut input_widths,
+    );
+    number_values(
+        &mut next_id,
+        &mut second_ids,
+        declared_values(second, &second_report, true),
+        &|wire_id| connected.contains_key(&wire_id),
+        &mut input_widths,
+    );
+
+    // Outputs are numbered last; connected ones are numbered in gate order
+    for &wire_id in &first_report.primary_output_wires {
+        if !connected_outputs.contains(&wire_id) {
+            first_ids[wire_id as usize] = PENDING;
+        }
+    }
+    for &wire_id in &second_report.primary_output_wires {
+        second_ids[wire_id as usize] = PENDING;
+    }
+    for (circuit, new_ids) in [(first, &mut first_ids), (second, &mut second_ids)] {
+        for gate in &circuit.gates {
+            let new_id = &mut new_ids[gate.output_wire as usize];
+            if *new_id == UNMAPPED {
+                *new_id = next_id;
+                next_id += 1;
+            }
+        }
+    }
+    number_values(
+        &mut next_id,
+        &mut first_ids,
+        declared_values(first, &first_report, false),
+        &|wire_id| connected_outputs.contains(&wire_id),
+        &mut output_widths,
+    );
+    number_values(
+        &mut next_id,
+        &mut second_ids,
+        declared_values(second, &second_report, false),
+        &|_| false,
+        &mut output_widths,
+    );
+    for (&input, &output) in &connected {
+        second_ids[input as usize] = first_ids[output as usize];
+    }
+
+    let mut gates = Vec::with_capacity(first.gates.len() + second.gates.len());
+    for (index, gate) in first.gates.iter().enumerate() {
+        gates.push(renumber_gate(index, gate, &first_ids)?);
+    }
+    for (index, gate) in second.gates.iter().enumerate() {
+        gates.push(renumber_gate(first.gates.len() + index, gate, &second_ids)?);
+    }
+
+    let remap_of = |new_ids: &[WireId], wires: &[WireId], excluded: &dyn Fn(WireId) -> bool| {
+        wires
+            .iter()
+            .filter(|&&wire_id| !excluded(wire_id))
+            .map(|&wire_id| (wire_id, new_ids[wire_id as usize]))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let remap = CompositionRemap {
+        first: WireRemap {
+            original_wires: first.num_wires,
+            inputs: remap_of(&first_ids, &first_report.primary_input_wires, &|_| false),
+            outputs: remap_of(&first_ids, &first_report.primary_output_wires, &|wire_id| {
+                connected_outputs.contains(&wire_id)
+            }),
+        },
+        second: WireRemap {
+            original_wires: second.num_wires,
+            inputs: remap_of(
+                &second_ids,
+                &second_report.primary_input_wires,
+                &|wire_id| connected.contains_key(&wire_id),
+            ),
+            outputs: remap_of(&second_ids, &second_report.primary_output_wires, &|_| false),
+        },
+    };
+    tracing::info!(
+        gates = gates.len(),
+        wires = next_id,
+        connections = connected.len(),
+        "composed circuits"
+    );
+    Ok((
+        Circuit {
+            num_wires: next_id,
+            gates,
+            io: Some(IoDeclaration::new(input_widths, output_widths, next_id)?),
+        },
+        remap,
+    ))
+}
+
+/// Number the wires of each value from `next_id` on, skipping `excluded`,
+/// and declare the width of what is left of the value
+fn number_values(
+    next_id: &mut WireId,
+    new_ids: &mut [WireId],
+    values: Vec<&[WireId]>,
+    excluded: &dyn Fn(WireId) -> bool,
+    widths: &mut Vec<u64>,
+) {
+    for value in values {
+        let mut width = 0;
+        for &wire_id in value.iter().filter(|&&wire_id| !excluded(wire_id)) {
+            new_ids[wire_id as usize] = *next_id;
+            *next_id += 1;
+            width += 1;
+        }
+        if width > 0 {
+            widths.push(width);
+        }
+    }
+}
+
+/// The output of `first` feeding each connected input of `second`
+fn connected_inputs(
+    first_report: &WireUsageReport,
+    second_report: &WireUsageReport,
+    wiring: &WiringSpec,
+) -> Result<HashMap<WireId, WireId>> {
+    let outputs: HashSet<WireId> = first_report.primary_output_wires.iter().copied().collect();
+    let inputs: HashSet<WireId> = second_report.primary_input_wires.iter().copied().collect();
+    let mut connected = HashMap::new();
+    for connection in &wiring.connections {
+        for offset in 0..connection.width {
+            let (output, input) = (connection.output + offset, connection.input + offset);
+            if !outputs.contains(&output) {
+                return Err(GcError::InvalidArgument(format!(
+                    "Wire {} of the first circuit is not one of its outputs",
+                    output
+                )));
+            }
+            if !inputs.contains(&input) {
+                return Err(GcError::InvalidArgument(format!(
+                    "Wire {} of the second circuit is not one of its inputs",
+                    input
+                )));
+            }
+            if connected.insert(input, output).is_some() {
+                return Err(GcError::InvalidArgument(format!(
+                    "Input wire {} of the second circuit is connected twice",
+                    input
+                )));
+            }
+        }
+    }
+    Ok(connected)
+}
+
+/// The input or output values of a circuit: one per Bristol Fashion
+/// declaration, or all its inputs or outputs if undeclared
+fn declared_values<'a>(
+    circuit: &Circuit,
+    report: &'a WireUsageReport,
+    inputs: bool,
+) -> Vec<&'a [WireId]> {
+    let (wires, widths) = match (inputs, &circuit.io) {
+        (true, Some(io)) => (&report.primary_input_wires, &io.input_widths),
+        (false, Some(io)) => (&report.primary_output_wires, &io.output_widths),
+        (true, None) => return vec![&report.primary_input_wires],
+        (false, None) => return vec![&report.primary_output_wires],
+    };
+    let mut rest = wires.as_slice();
+    widths
+        .iter()
+        .map(|&width| {
+            let (value, tail) = rest.split_at(width as usize);
+            rest = tail;
+            value
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plain::evaluate_plain;
+
+    #[test]
+    fn test_compose_circuits() -> Result<()> {
+        // out = ((a AND b) XOR c) AND d
+        let first = Circuit::parse("2 5\n3 1 1 1\n1 1\n\n2 1 0 1 3 AND\n2 1 3 2 4 XOR\n")?;
+        let second = Circuit::parse("1 3\n2 1 0 1 2 AND\n")?;
+        let wiring: WiringSpec =
+            serde_json::from_str(r#"{"connections": [{"output": 4, "input": 0}]}"#)?;
+        let (composed, remap) = compose_circuits(&first, &second, &wiring)?;
+        assert_eq!(composed.num_wires, 7);
+        assert_eq!(
+            composed.io,
+            Some(IoDeclaration::new(vec![1, 1, 1, 1], vec![1], 7)?)
+        );
+        assert!(remap.first.outputs.is_empty());
+        assert_eq!(remap.second.inputs, BTreeMap::from([(1, 3)]));
+        assert_eq!(remap.second.outputs, BTreeMap::from([(2, 6)]));
+
+        let report = analyze_circuit(&composed)?;
+        for bits in 0..16u8 {
+            let inputs: HashMap<WireId, bool> = (0..4).map(|i| (i, bits >> i & 1 == 1)).collect();
+            let expected = (inputs[&0] && inputs[&1]) ^ inputs[&2] && inputs[&3];
+            let outputs = evaluate_plain(&composed, &report, &inputs)?;
+            assert_eq!(outputs[&6], expected);
+        }
+
+        let wiring = WiringSpec {
+            connections: vec![Connection {
+                output: 3,
+                input: 0,
+                width: 1,
+            }],
+        };
+        let error_msg = compose_circuits(&first, &second, &wiring)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Wire 3 of the first circuit is not one of its outputs"));
+        Ok(())
+    }
+}
@@ -2,5 +2,165 @@
 /// Buffer size for binary file operations (256MB)
 pub const BUFFER_SIZE: usize = 256 * 1024 * 1024;
 
+/// Buffer size of the line stream over a circuit lowered as it is read
+/// (1MB); the circuit underneath is read through its own buffer
+pub const LOWERED_STREAM_BUFFER_SIZE: usize = 1024 * 1024;
+
 /// Progress update interval - update progress bar/spinner every N operations
-pub const PROGRESS_UPDATE_INTERVAL: u32 = 100000;
+pub const PROGRESS_UPDATE_INTERVAL: u64 = 100000;
+
+/// Number of AND gates handed to a garbling backend in a single batch
+pub const GARBLING_BATCH_SIZE: usize = 4096;
+
+/// Size of a single garbled AND table in bytes (4 ciphertexts × 16 bytes)
+pub const GARBLED_TABLE_SIZE: usize = 64;
+
+/// Read-ahead buffer size when streaming garbled tables (64MB = 1M tables)
+pub const TABLE_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+
+/// Number of garbled tables read by the prefetch thread per batch
+pub const PREFETCH_BATCH_SIZE: usize = 4096;
+
+/// Number of batches the prefetch thread may read ahead of the evaluator
+/// (64 × 4096 tables × 64 bytes = 16MB)
+pub const PREFETCH_QUEUE_DEPTH: usize = 64;
+
+/// Default zstd compression level for compressed garbled tables
+pub const ZSTD_COMPRESSION_LEVEL: i32 = 3;
+
+/// Number of tables in each independently compressed zstd frame (4MB
+/// uncompressed)
+///
+/// Each frame is decodable as soon as it is written, so an evaluator reading
+/// a compressed stream never waits for more than this many tables, and a
+/// [`SeekTable`](crate::seek_table::SeekTable) can jump to any frame.
+pub const ZSTD_FLUSH_INTERVAL: usize = 65536;
+
+/// Magic bytes at the start of every zstd frame
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// Magic number of the skippable zstd frame holding a seek table
+pub const ZSTD_SEEK_TABLE_MAGIC: u32 = 0x184D_2A5E;
+
+/// Magic number at the very end of a seek table in the zstd seekable format
+pub const ZSTD_SEEKABLE_MAGIC: u32 = 0x8F92_EAB1;
+
+/// Size of a seek table entry in bytes
+/// (compressed size 4 + decompressed size 4)
+pub const SEEK_TABLE_ENTRY_SIZE: usize = 8;
+
+/// Size of the seek table footer in bytes
+/// (frame count 4 + descriptor 1 + seekable magic 4)
+pub const SEEK_TABLE_FOOTER_SIZE: usize = 9;
+
+/// Magic bytes at the start of a binary evaluation result file
+pub const EVALUATION_RESULT_MAGIC: [u8; 4] = *b"GCEV";
+
+/// Current version of the binary evaluation result format
+pub const EVALUATION_RESULT_VERSION: u32 = 2;
+
+/// Magic bytes at the start of a binary wire labels file
+pub const WIRE_LABELS_MAGIC: [u8; 4] = *b"GCWL";
+
+/// Current version of the binary wire labels format
+pub const WIRE_LABELS_VERSION: u32 = 3;
+
+/// Magic bytes at the start of a binary encoded inputs file
+pub const ENCODED_INPUTS_MAGIC: [u8; 4] = *b"GCEI";
+
+/// Current version of the binary encoded inputs format
+pub const ENCODED_INPUTS_VERSION: u32 = 3;
+
+/// Magic bytes at the start of a binary OT result file
+pub const OT_RESULT_MAGIC: [u8; 4] = *b"GCOT";
+
+/// Current version of the binary OT result format
+pub const OT_RESULT_VERSION: u32 = 2;
+
+/// Magic bytes at the start of an OT transcript file
+pub const OT_TRANSCRIPT_MAGIC: [u8; 4] = *b"GCTR";
+
+/// Current version of the OT transcript format
+pub const OT_TRANSCRIPT_VERSION: u32 = 2;
+
+/// Magic bytes at the start of a garbled tables file with a header
+pub const GARBLED_TABLES_MAGIC: [u8; 4] = *b"GCGT";
+
+/// Current version of the garbled tables file header
+pub const GARBLED_TABLES_VERSION: u32 = 3;
+
+/// Size of the garbled tables file header in bytes
+/// (magic 4 + version 4 + scheme 4 + table count 8 + circuit digest 32 +
+/// session id 16 + tables checksum 32)
+pub const GARBLED_TABLES_HEADER_SIZE: usize = 100;
+
+/// Magic bytes at the start of a binary circuit file
+pub const BINARY_CIRCUIT_MAGIC: [u8; 4] = *b"GCBC";
+
+/// Current version of the binary circuit format
+pub const BINARY_CIRCUIT_VERSION: u32 = 3;
+
+/// Size of the fixed part of the binary circuit file header in bytes
+/// (magic 4 + version 4 + gate count 8 + wire count 8), which the input and
+/// output declarations follow
+pub const BINARY_CIRCUIT_HEADER_SIZE: usize = 24;
+
+/// Most gates in one batch of a binary circuit file, so the count fits in a byte
+pub const BINARY_CIRCUIT_BATCH_SIZE: usize = 255;
+
+/// Batches per independently compressed zstd frame of a binary circuit file,
+/// the granularity at which a [`GateIndex`](crate::binary_circuit::GateIndex)
+/// can seek
+pub const BINARY_CIRCUIT_FRAME_BATCHES: usize = 256;
+
+/// Size of the BLAKE3 checksum that ends a binary circuit file
+pub const BINARY_CIRCUIT_CHECKSUM_SIZE: usize = 32;
+
+/// Magic bytes at the start of a binary circuit gate index file
+pub const GATE_INDEX_MAGIC: [u8; 4] = *b"GCGI";
+
+/// Current version of the gate index format
+pub const GATE_INDEX_VERSION: u32 = 1;
+
+/// Magic bytes at the start of a binary wire analysis file
+pub const WIRE_ANALYSIS_MAGIC: [u8; 4] = *b"GCWA";
+
+/// Current version of the binary wire analysis format
+pub const WIRE_ANALYSIS_VERSION: u32 = 1;
+
+/// Current version of the shard manifest format
+pub const SHARD_MANIFEST_VERSION: u32 = 2;
+
+/// Estimated memory per wire label held in a label map, including the wire
+/// id, last-use time and hash map overhead (bytes)
+///
+/// Converts a memory cap into the number of labels kept in memory before
+/// spilling to disk.
+pub const RESIDENT_LABEL_BYTES: u64 = 48;
+
+/// A memory-capped label map spills `1/SPILL_EVICTION_FRACTION` of its
+/// labels at a time once it is full
+pub const SPILL_EVICTION_FRACTION: usize = 8;
+
+/// Magic bytes at the start of every frame of a networked protocol
+pub const FRAME_MAGIC: [u8; 2] = *b"GC";
+
+/// Newest protocol framing version this library speaks
+pub const FRAMING_VERSION: u8 = 1;
+
+/// Oldest protocol framing version this library still speaks
+pub const MIN_FRAMING_VERSION: u8 = 1;
+
+/// Size of a frame header in bytes
+/// (magic 2 + framing version 1 + frame type 1 + payload length 8)
+pub const FRAME_HEADER_SIZE: usize = 12;
+
+/// Largest message accepted from the other party in a networked protocol
+/// (64GB); anything larger is treated as a corrupt frame header
+pub const MAX_MESSAGE_SIZE: u64 = 64 * 1024 * 1024 * 1024;
+
+/// Magic bytes identifying a stacked garbling material file
+pub const STACKED_MATERIAL_MAGIC: [u8; 4] = *b"GCSM";
+
+/// Version of the stacked garbling material file format
+pub const STACKED_MATERIAL_VERSION: u32 = 1;
@@ -1,9 +1,12 @@
-use anyhow::{Result, bail};
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::error::{GcError, Result};
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::io::Read;
 
+use crate::chunked_parse::ChunkedCircuit;
 use crate::constants::PROGRESS_UPDATE_INTERVAL;
+use crate::parser::read_header;
+use crate::progress::ProgressObserver;
 use crate::stream::BufferedLineStream;
 
 /// Count gate types in a Bristol circuit file
@@ -13,10 +16,11 @@ use crate::stream::BufferedLineStream;
 ///
 /// # Arguments
 /// * `stream` - The line stream to process
+/// * `progress` - Receives progress updates
 ///
 /// # Returns
 /// * `Ok(HashMap<String, usize>)` - Gate type to count mapping
-/// * `Err(anyhow::Error)` - IO error or malformed line error
+/// * `Err(GcError)` - IO error or malformed line error
 ///
 ///
 /// # Panics
@@ -25,95 +29,105 @@ use crate::stream::BufferedLineStream;
 /// # Note
 /// This function doesn't enforce any allowed set of gate types,
 /// and will count any string which is at the end of a gate line as a gate type.
-pub fn count_gate_types(stream: &mut BufferedLineStream) -> Result<HashMap<String, usize>> {
-    // Parse and validate header line (but ignore values)
-    let header_line = stream
-        .next_line()
-        .ok_or_else(|| anyhow::anyhow!("Missing header line"))??;
-
-    let header_tokens: Vec<&str> = header_line.split_whitespace().collect();
-    if header_tokens.len() != 2 {
-        bail!(
-            "Invalid header: expected '<num_gates> <num_wires>', got: '{}'",
-            header_line
-        );
-    }
-
-    // Parse to validate format but ignore values (we'll count actual gates)
-    let _num_gates: u32 = header_tokens[0]
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid num_gates: '{}'", header_tokens[0]))?;
-    let _num_wires: u32 = header_tokens[1]
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid num_wires: '{}'", header_tokens[1]))?;
+#[tracing::instrument(name = "count_gates", skip_all)]
+pub fn count_gate_types<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    progress: &dyn ProgressObserver,
+) -> Result<HashMap<String, usize>> {
+    // Parse and validate the header (only its length is needed)
+    let header = read_header(stream)?;
 
     let mut counts = HashMap::new();
-    let mut line_number: u32 = 1; // Already processed header
+    let mut line_number: u64 = header.lines; // Already processed header
 
-    // Create a spinner progress bar
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} [{elapsed_precise}] {msg}")
-            .unwrap(),
-    );
-    pb.set_message("Counting gate types...");
+    progress.start(None, "Counting gate types...");
 
     while let Some(line_result) = stream.next_line() {
         line_number += 1;
-        let line = line_result?;
-
-        // Extract gate type (last token) using iterator - more efficient
-        // Bristol format: "2 1 0 1 2 XOR" -> gate type is "XOR"
-        // This also handles empty lines by returning None
-        let gate_type = line.split_whitespace().last().ok_or_else(|| {
-            anyhow::anyhow!(
-                "Empty line or no gate type found at line {}: '{}'",
-                line_number,
-                line
-            )
-        })?;
-
-        // Increment count for this gate type
-
-        // ORIGINAL APPROACH - Always allocates String (comment/uncomment to compare)
-        // *counts.entry(gate_type.to_string()).or_insert(0) += 1;
-
-        // COW OPTIMIZED APPROACH - Reduces allocations for common gates
-        let cow_gate: Cow<'_, str> = match gate_type {
-            // Common gates - use borrowed &str (zero allocation)
-            "XOR" | "AND" | "OR" | "NOT" | "NAND" | "NOR" | "BUF" | "INV" => {
-                Cow::Borrowed(gate_type)
-            }
-            // Rare/custom gates - allocate only when needed
-            _ => Cow::Owned(gate_type.to_string()),
-        };
-        *counts.entry(cow_gate.into_owned()).or_insert(0) += 1;
+        count_gate_line(&mut counts, line_result?, line_number)?;
 
-        // Update spinner periodically (avoid string allocation)
+        // Report progress periodically
         if line_number.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
-            pb.tick();
+            progress.update(line_number);
         }
     }
 
-    // Finish progress bar
-    pb.finish_with_message(format!("✓ Processed {line_number} gates"));
+    let gates_processed = line_number - header.lines;
+    progress.finish(&format!("✓ Processed {gates_processed} gates"));
+
+    Ok(counts)
+}
+
+/// Count gate types on several threads
+///
+/// Same counts as [`count_gate_types`], gathered per thread over chunks of
+/// the mapped circuit file and added up.
+///
+/// # Arguments
+/// * `circuit` - Mapped circuit file
+/// * `threads` - Worker threads
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(HashMap<String, usize>)` - Gate type to count mapping
+/// * `Err(GcError)` - IO error or malformed line error
+#[tracing::instrument(name = "count_gates", skip_all)]
+pub fn count_gate_types_parallel(
+    circuit: &ChunkedCircuit,
+    threads: usize,
+    progress: &dyn ProgressObserver,
+) -> Result<HashMap<String, usize>> {
+    progress.start(Some(circuit.header().num_gates), "Counting gate types...");
+    let (partial_counts, gates_processed) =
+        circuit.scan(threads, progress, HashMap::new, count_gate_line)?;
+
+    let mut counts = HashMap::new();
+    for (gate_type, count) in partial_counts.into_iter().flatten() {
+        *counts.entry(gate_type).or_insert(0) += count;
+    }
+    progress.finish(&format!("✓ Processed {gates_processed} gates"));
 
     Ok(counts)
 }
 
+/// Count the gate type of one gate line
+fn count_gate_line(
+    counts: &mut HashMap<String, usize>,
+    line: &str,
+    line_number: u64,
+) -> Result<()> {
+    // Extract gate type (last token) using iterator - more efficient
+    // Bristol format: "2 1 0 1 2 XOR" -> gate type is "XOR"
+    // This also handles empty lines by returning None
+    let gate_type = line.split_whitespace().last().ok_or_else(|| {
+        GcError::parse(
+            line_number,
+            format!("Empty line or no gate type found: '{}'", line),
+        )
+    })?;
+
+    // Increment count for this gate type
+
+    // ORIGINAL APPROACH - Always allocates String (comment/uncomment to compare)
+    // *counts.entry(gate_type.to_string()).or_insert(0) += 1;
+
+    // COW OPTIMIZED APPROACH - Reduces allocations for common gates
+    let cow_gate: Cow<'_, str> = match gate_type {
+        // Common gates - use borrowed &str (zero allocation)
+        "XOR" | "AND" | "OR" | "NOT" | "NAND" | "NOR" | "BUF" | "INV" => Cow::Borrowed(gate_type),
+        // Rare/custom gates - allocate only when needed
+        _ => Cow::Owned(gate_type.to_string()),
+    };
+    *counts.entry(cow_gate.into_owned()).or_insert(0) += 1;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::progress::NoProgress;
+    use crate::test_util::create_test_file;
     use std::fs::File;
-    use std::io::Write;
-
-    fn create_test_file(content: &str) -> Result<tempfile::NamedTempFile> {
-        let mut temp_file = tempfile::NamedTempFile::new()?;
-        temp_file.write_all(content.as_bytes())?;
-        temp_file.flush()?;
-        Ok(temp_file)
-    }
 
     #[test]
     fn test_count_gate_types_basic() -> Result<()> {
@@ -122,7 +136,7 @@ mod tests {
         let file = File::open(temp_file.path())?;
         let mut stream = BufferedLineStream::new(file);
 
-        let counts = count_gate_types(&mut stream)?;
+        let counts = count_gate_types(&mut stream, &NoProgress)?;
 
         assert_eq!(counts.get("XOR"), Some(&2));
         assert_eq!(counts.get("AND"), Some(&1));
@@ -138,7 +152,7 @@ mod tests {
         let file = File::open(temp_file.path())?;
         let mut stream = BufferedLineStream::new(file);
 
-        let counts = count_gate_types(&mut stream)?;
+        let counts = count_gate_types(&mut stream, &NoProgress)?;
 
         assert_eq!(counts.get("NAND"), Some(&1));
         assert_eq!(counts.len(), 1);
@@ -153,7 +167,7 @@ mod tests {
         let file = File::open(temp_file.path())?;
         let mut stream = BufferedLineStream::new(file);
 
-        let counts = count_gate_types(&mut stream)?;
+        let counts = count_gate_types(&mut stream, &NoProgress)?;
 
         assert_eq!(counts.get("OR"), Some(&4));
         assert_eq!(counts.len(), 1);
@@ -161,6 +175,50 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_count_gate_types_from_memory() -> Result<()> {
+        let circuit_data = "3 9\n2 1 0 1 2 XOR\n2 1 3 4 5 AND\n2 1 6 7 8 XOR\n";
+        let mut stream = BufferedLineStream::from_reader(circuit_data.as_bytes());
+
+        let counts = count_gate_types(&mut stream, &NoProgress)?;
+
+        assert_eq!(counts.get("XOR"), Some(&2));
+        assert_eq!(counts.get("AND"), Some(&1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_gate_types_parallel() -> Result<()> {
+        let mut circuit_data = String::from("# generated\n300 900\n");
+        for gate in 0..300 {
+            let gate_type = ["XOR", "AND", "INV"][gate % 3];
+            circuit_data.push_str(&format!(
+                "2 1 {} {} {} {}\n",
+                gate,
+                gate + 300,
+                gate + 600,
+                gate_type
+            ));
+            if gate % 50 == 0 {
+                circuit_data.push('\n');
+            }
+        }
+        let temp_file = create_test_file(&circuit_data)?;
+        let chunked = ChunkedCircuit::open(temp_file.path(), true)?;
+
+        let counts = count_gate_types_parallel(&chunked, 3, &NoProgress)?;
+        assert_eq!(counts.get("XOR"), Some(&100));
+        assert_eq!(counts.get("AND"), Some(&100));
+        assert_eq!(counts.get("INV"), Some(&100));
+
+        // Without lenient parsing the leading comment is not a valid header
+        let strict = ChunkedCircuit::open(temp_file.path(), false);
+        assert!(matches!(strict, Err(GcError::Parse { line: 1, .. })));
+
+        Ok(())
+    }
+
     #[test]
     fn test_count_gate_types_empty_file() -> Result<()> {
         let circuit_data = "0 0\n";
@@ -168,7 +226,7 @@ mod tests {
         let file = File::open(temp_file.path())?;
         let mut stream = BufferedLineStream::new(file);
 
-        let counts = count_gate_types(&mut stream)?;
+        let counts = count_gate_types(&mut stream, &NoProgress)?;
 
         assert_eq!(counts.len(), 0);
 
@@ -182,11 +240,13 @@ mod tests {
         let file = File::open(temp_file.path()).unwrap();
         let mut stream = BufferedLineStream::new(file);
 
-        let result = count_gate_types(&mut stream);
+        let result = count_gate_types(&mut stream, &NoProgress);
 
         assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("Empty line or no gate type found at line 3"));
+        assert!(matches!(
+            result.unwrap_err(),
+            GcError::Parse { line: 3, .. }
+        ));
     }
 
     #[test]
@@ -196,11 +256,13 @@ mod tests {
         let file = File::open(temp_file.path()).unwrap();
         let mut stream = BufferedLineStream::new(file);
 
-        let result = count_gate_types(&mut stream);
+        let result = count_gate_types(&mut stream, &NoProgress);
 
         assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("Empty line or no gate type found at line 3"));
+        assert!(matches!(
+            result.unwrap_err(),
+            GcError::Parse { line: 3, .. }
+        ));
     }
 
     #[test]
@@ -219,7 +281,7 @@ mod tests {
         let file = File::open(temp_file.path())?;
         let mut stream = BufferedLineStream::new(file);
 
-        let counts = count_gate_types(&mut stream)?;
+        let counts = count_gate_types(&mut stream, &NoProgress)?;
 
         assert_eq!(counts.get("XOR"), Some(&2));
         assert_eq!(counts.get("AND"), Some(&1));
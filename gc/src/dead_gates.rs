@@ -0,0 +1,146 @@
+use crate::error::{GcError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::gate::{Circuit, GateType};
+use crate::wire_analyzer::WireUsageReport;
+
+/// Gates removed by [`eliminate_dead_gates`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadGateReport {
+    /// Number of gates before elimination
+    pub total_gates: u64,
+    /// Number of AND gates removed
+    pub dead_and_gates: u64,
+    /// Number of XOR gates removed
+    pub dead_xor_gates: u64,
+    /// Number of copy and constant gates removed
+    pub dead_other_gates: u64,
+    /// Indices of the removed gates in the original circuit, ascending
+    pub dead_gates: Vec<u64>,
+}
+
+impl DeadGateReport {
+    /// Total number of gates removed
+    pub fn num_dead_gates(&self) -> u64 {
+        self.dead_gates.len() as u64
+    }
+
+    /// Save the report as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Remove the gates whose outputs never reach a primary output
+///
+/// Walks the gates backwards from the primary outputs of `wire_report`,
+/// keeping a gate only if a later kept gate, or the final value of an
+/// output, reads the value it writes. Undeclared circuits take every wire
+/// no gate reads as an output, so their dead logic is what feeds only
+/// overwritten wires; Bristol Fashion circuits lose every gate the declared
+/// outputs do not depend on. Wires and declarations stay as they are, so
+/// [`compact_wires`](crate::renumber::compact_wires) can close the gaps the
+/// removed gates leave.
+///
+/// # Arguments
+/// * `circuit` - The circuit to clean up
+/// * `wire_report` - Wire usage analysis of the circuit naming its primary outputs
+///
+/// # Returns
+/// * `Ok((Circuit, DeadGateReport))` - The circuit without dead gates and what was removed
+/// * `Err(GcError)` - The wire analysis is of a circuit with a different number of wires
+#[tracing::instrument(name = "dead_gate_elimination", skip_all)]
+pub fn eliminate_dead_gates(
+    circuit: &Circuit,
+    wire_report: &WireUsageReport,
+) -> Result<(Circuit, DeadGateReport)> {
+    if wire_report.total_wires != circuit.num_wires {
+        return Err(GcError::InvalidArgument(format!(
+            "Wire analysis covers {} wires, but the circuit has {}",
+            wire_report.total_wires, circuit.num_wires
+        )));
+    }
+
+    let mut live = vec![false; circuit.num_wires as usize];
+    for &wire_id in &wire_report.primary_output_wires {
+        live[wire_id as usize] = true;
+    }
+    let mut keep = vec![false; circuit.gates.len()];
+    for (index, gate) in circuit.gates.iter().enumerate().rev() {
+        if live[gate.output_wire as usize] {
+            keep[index] = true;
+            // Earlier writes of the wire are overwritten before anyone reads them
+            live[gate.output_wire as usize] = false;
+            for wire_id in gate.input_wires() {
+                live[wire_id as usize] = true;
+            }
+        }
+    }
+
+    let mut report = DeadGateReport {
+        total_gates: circuit.gates.len() as u64,
+        ..Default::default()
+    };
+    let mut gates = Vec::with_capacity(keep.iter().filter(|&&kept| kept).count());
+    for (index, (gate, kept)) in circuit.gates.iter().zip(keep).enumerate() {
+        if kept {
+            gates.push(*gate);
+            continue;
+        }
+        match gate.gate_type {
+            GateType::And => report.dead_and_gates += 1,
+            GateType::Xor => report.dead_xor_gates += 1,
+            GateType::Buf | GateType::Const(_) => report.dead_other_gates += 1,
+        }
+        report.dead_gates.push(index as u64);
+    }
+    tracing::info!(
+        gates = report.total_gates,
+        dead = report.num_dead_gates(),
+        "eliminated dead gates"
+    );
+    Ok((
+        Circuit {
+            num_wires: circuit.num_wires,
+            gates,
+            io: circuit.io.clone(),
+        },
+        report,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire_analyzer::analyze_circuit;
+
+    #[test]
+    fn test_eliminate_dead_gates() -> Result<()> {
+        // Only the last gate feeds the declared output; gate 1 feeds gate 2
+        // but is dead with it, and wire 4 is overwritten before it is read
+        let circuit = Circuit::parse(
+            "5 8\n3 1 1 1\n1 1\n\n2 1 0 1 3 AND\n2 1 0 2 4 XOR\n2 1 4 1 5 AND\n1 1 1 4 EQ\n2 1 3 4 7 XOR\n",
+        )?;
+        let report = analyze_circuit(&circuit)?;
+        let (cleaned, dead) = eliminate_dead_gates(&circuit, &report)?;
+        assert_eq!(dead.dead_gates, vec![1, 2]);
+        assert_eq!(
+            (
+                dead.dead_and_gates,
+                dead.dead_xor_gates,
+                dead.dead_other_gates
+            ),
+            (1, 1, 0)
+        );
+        assert_eq!(cleaned.gates.len(), 3);
+        assert_eq!(cleaned.io, circuit.io);
+
+        // Eliminating again finds nothing left
+        let (_, dead) = eliminate_dead_gates(&cleaned, &analyze_circuit(&cleaned)?)?;
+        assert_eq!(dead.num_dead_gates(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,305 @@
+use crate::error::{GcError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::bus::{Bus, BusValue, assemble_buses};
+use crate::evaluator::{EvaluationResult, OutputResult};
+use crate::garbler::{WireLabel, WireLabels, garbling_hash};
+use crate::gate::WireId;
+use crate::serde_helpers::serialize_sorted_map;
+
+/// Output decoding information published by the garbler
+///
+/// Holds a hash of both labels of every primary output wire, so the evaluator
+/// can map its output labels to bits without learning label_0 or delta.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DecodingInfo {
+    /// Output label hashes: wire_id -> [H(label_0), H(label_1)]
+    #[serde(serialize_with = "serialize_sorted_map")]
+    pub output_hashes: HashMap<WireId, [[u8; 16]; 2]>,
+}
+
+impl DecodingInfo {
+    /// Derive decoding information from the garbler's wire labels
+    pub fn from_wire_labels(wire_labels: &WireLabels) -> Self {
+        let output_hashes = wire_labels
+            .output_labels
+            .iter()
+            .map(|(&wire_id, &label_0)| {
+                let label_1 = label_0.xor(&wire_labels.delta);
+                (wire_id, [hash_label(&label_0), hash_label(&label_1)])
+            })
+            .collect();
+
+        DecodingInfo { output_hashes }
+    }
+
+    /// Save decoding information as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Load decoding information from JSON
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let info = serde_json::from_str(&data)?;
+        Ok(info)
+    }
+
+    /// Decode a single output label to its bit value
+    pub fn decode_label(&self, wire_id: WireId, label: &WireLabel) -> Result<bool> {
+        let hashes = self.output_hashes.get(&wire_id).ok_or_else(|| {
+            GcError::missing_wire(
+                wire_id,
+                format!("No decoding information for wire {}", wire_id),
+            )
+        })?;
+
+        let label_hash = hash_label(label);
+        if label_hash == hashes[0] {
+            Ok(false)
+        } else if label_hash == hashes[1] {
+            Ok(true)
+        } else {
+            Err(GcError::InvalidLabel { wire: wire_id })
+        }
+    }
+}
+
+/// Hash a single label for decoding (same PRF as garbling)
+fn hash_label(label: &WireLabel) -> [u8; 16] {
+    garbling_hash(std::slice::from_ref(label))
+}
+
+/// Decode a label-only evaluation result into bits
+///
+/// Every output label must match one of the two hashed labels for its wire;
+/// a label matching neither indicates corrupted tables or a garbler bug.
+///
+/// # Arguments
+/// * `result` - Evaluation result (bit values may be absent)
+/// * `decoding_info` - Decoding information from the garbler
+///
+/// # Returns
+/// * `Ok(EvaluationResult)` - The same labels with `bit_value` filled in
+/// * `Err(GcError)` - Missing decoding information or an invalid label
+pub fn decode_outputs(
+    result: &EvaluationResult,
+    decoding_info: &DecodingInfo,
+) -> Result<EvaluationResult> {
+    let mut output_results = HashMap::with_capacity(result.output_results.len());
+    for (&wire_id, output) in &result.output_results {
+        let bit_value = decoding_info.decode_label(wire_id, &output.label)?;
+        output_results.insert(
+            wire_id,
+            OutputResult {
+                label: output.label,
+                bit_value: Some(bit_value),
+            },
+        );
+    }
+
+    Ok(EvaluationResult {
+        output_results,
+        stats: result.stats.clone(),
+    })
+}
+
+/// Check every output label against the garbler's output label commitments
+///
+/// Fails if any output label matches neither committed label of its wire, if
+/// a wire has no commitment, or if a reported bit value disagrees with the
+/// label. All offending wires are listed in the error.
+///
+/// # Arguments
+/// * `result` - Evaluation result to check
+/// * `decoding_info` - Hashes of both output labels per wire from the garbler
+///
+/// # Returns
+/// * `Ok(())` - Every output label matches one of its commitments
+/// * `Err(GcError)` - One or more output labels failed the check
+pub fn check_outputs(result: &EvaluationResult, decoding_info: &DecodingInfo) -> Result<()> {
+    let mut failed_wires: Vec<WireId> = result
+        .output_results
+        .iter()
+        .filter(
+            |&(&wire_id, output)| match decoding_info.decode_label(wire_id, &output.label) {
+                Ok(bit_value) => output.bit_value.is_some_and(|bit| bit != bit_value),
+                Err(_) => true,
+            },
+        )
+        .map(|(&wire_id, _)| wire_id)
+        .collect();
+
+    if !failed_wires.is_empty() {
+        failed_wires.sort_unstable();
+        return Err(GcError::OutputCheckFailed {
+            failed_wires,
+            total: result.output_results.len(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Assemble output bits into named integer values
+///
+/// # Arguments
+/// * `result` - Evaluation result with bit values (decode label-only results first)
+/// * `buses` - Bus descriptions to assemble
+///
+/// # Returns
+/// * `Ok(BTreeMap<String, BusValue>)` - Bus name to decoded value
+/// * `Err(GcError)` - A bus wire is missing or has no bit value
+pub fn decode_buses(
+    result: &EvaluationResult,
+    buses: &[Bus],
+) -> Result<BTreeMap<String, BusValue>> {
+    assemble_buses(buses, |bus, wire_id| {
+        let output = result.output_results.get(&wire_id).ok_or_else(|| {
+            GcError::missing_wire(
+                wire_id,
+                format!("Output wire {} of bus '{}' not found", wire_id, bus.name),
+            )
+        })?;
+        output.bit_value.ok_or_else(|| {
+            GcError::missing_wire(
+                wire_id,
+                format!(
+                    "Output wire {} of bus '{}' has no bit value",
+                    wire_id, bus.name
+                ),
+            )
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionId;
+
+    fn create_wire_labels() -> WireLabels {
+        let mut output_labels = HashMap::new();
+        output_labels.insert(5u64, WireLabel::new([0x11; 16]));
+        WireLabels {
+            input_labels: HashMap::new(),
+            output_labels,
+            delta: WireLabel::new([0xF0; 16]),
+            garbler_input_wires: Vec::new(),
+            session_id: SessionId::default(),
+        }
+    }
+
+    fn label_only_result(label: WireLabel) -> EvaluationResult {
+        let mut output_results = HashMap::new();
+        output_results.insert(
+            5u64,
+            OutputResult {
+                label,
+                bit_value: None,
+            },
+        );
+        EvaluationResult {
+            output_results,
+            stats: None,
+        }
+    }
+
+    #[test]
+    fn test_decode_outputs_both_bits() -> Result<()> {
+        let wire_labels = create_wire_labels();
+        let decoding_info = DecodingInfo::from_wire_labels(&wire_labels);
+        let [label_0, label_1] = wire_labels.get_wire_labels(5).unwrap();
+
+        let decoded = decode_outputs(&label_only_result(label_0), &decoding_info)?;
+        assert_eq!(decoded.output_results[&5].bit_value, Some(false));
+
+        let decoded = decode_outputs(&label_only_result(label_1), &decoding_info)?;
+        assert_eq!(decoded.output_results[&5].bit_value, Some(true));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_outputs_invalid_label() {
+        let decoding_info = DecodingInfo::from_wire_labels(&create_wire_labels());
+        let result = label_only_result(WireLabel::new([0x00; 16]));
+
+        let error_msg = decode_outputs(&result, &decoding_info)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("matches neither output label"));
+    }
+
+    #[test]
+    fn test_check_outputs() -> Result<()> {
+        let wire_labels = create_wire_labels();
+        let decoding_info = DecodingInfo::from_wire_labels(&wire_labels);
+        let [_, label_1] = wire_labels.get_wire_labels(5).unwrap();
+
+        let mut result = label_only_result(label_1);
+        check_outputs(&result, &decoding_info)?;
+
+        // Bit value contradicting the label
+        result.output_results.get_mut(&5).unwrap().bit_value = Some(false);
+        let error_msg = check_outputs(&result, &decoding_info)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("failed for 1 of 1 output wires: [5]"));
+
+        // Corrupted label
+        let result = label_only_result(WireLabel::new([0x00; 16]));
+        assert!(check_outputs(&result, &decoding_info).is_err());
+
+        Ok(())
+    }
+
+    fn bits_result(first_wire: WireId, bits: &[bool]) -> EvaluationResult {
+        let output_results = bits
+            .iter()
+            .enumerate()
+            .map(|(i, &bit)| {
+                (
+                    first_wire + i as u64,
+                    OutputResult {
+                        label: WireLabel::new([0x00; 16]),
+                        bit_value: Some(bit),
+                    },
+                )
+            })
+            .collect();
+        EvaluationResult {
+            output_results,
+            stats: None,
+        }
+    }
+
+    #[test]
+    fn test_decode_buses_bit_orders() -> Result<()> {
+        // Wires 10..13 hold bits 1,0,1,1 (in wire order)
+        let result = bits_result(10, &[true, false, true, true]);
+        let buses = ["le=10..13".parse::<Bus>()?, "be=10..13:be".parse::<Bus>()?];
+
+        let values = decode_buses(&result, &buses)?;
+        assert_eq!(values["le"].to_u128(), Some(0b1101));
+        assert_eq!(values["be"].to_u128(), Some(0b1011));
+        assert_eq!(values["le"].to_hex(), "0xd");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_buses_missing_wire() -> Result<()> {
+        let result = bits_result(0, &[true, false]);
+        let buses = ["x=0..2".parse::<Bus>()?];
+
+        let error_msg = decode_buses(&result, &buses).unwrap_err().to_string();
+        assert!(error_msg.contains("Output wire 2 of bus 'x' not found"));
+
+        Ok(())
+    }
+}
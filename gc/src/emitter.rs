@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use crate::gate::{Circuit, Gate, WireId};
+use crate::parser::IoDeclaration;
+
+/// Gates of a circuit under construction, on contiguously numbered wires
+///
+/// Inputs take the first wires and every gate writes a new wire, so gates
+/// come out in topological order. Inverters are XORs with a constant-one
+/// wire, free to garble; the wire is created on first use, and each wire is
+/// inverted at most once.
+#[derive(Debug)]
+pub(crate) struct GateEmitter {
+    gates: Vec<Gate>,
+    num_wires: u64,
+    num_inputs: u64,
+    /// Constant wires: `constants[value]`, once needed
+    constants: [Option<WireId>; 2],
+    /// Inverted copy of each wire, once needed
+    inverted: HashMap<WireId, WireId>,
+}
+
+impl GateEmitter {
+    /// Start a circuit whose inputs are wires `0..num_inputs`
+    pub(crate) fn new(num_inputs: u64) -> Self {
+        Self {
+            gates: Vec::new(),
+            num_wires: num_inputs,
+            num_inputs,
+            constants: [None; 2],
+            inverted: HashMap::new(),
+        }
+    }
+
+    fn new_wire(&mut self) -> WireId {
+        self.num_wires += 1;
+        self.num_wires - 1
+    }
+
+    /// Wire holding the constant `value`
+    pub(crate) fn constant(&mut self, value: bool) -> WireId {
+        if let Some(wire_id) = self.constants[value as usize] {
+            return wire_id;
+        }
+        let wire_id = self.new_wire();
+        self.gates.push(Gate::constant(value, wire_id));
+        self.constants[value as usize] = Some(wire_id);
+        wire_id
+    }
+
+    /// Wire holding `NOT wire_id`
+    pub(crate) fn not(&mut self, wire_id: WireId) -> WireId {
+        if let Some(&inverted) = self.inverted.get(&wire_id) {
+            return inverted;
+        }
+        let one = self.constant(true);
+        let inverted = self.xor(wire_id, one);
+        self.inverted.insert(wire_id, inverted);
+        inverted
+    }
+
+    /// Wire holding `a XOR b`
+    pub(crate) fn xor(&mut self, a: WireId, b: WireId) -> WireId {
+        let output_wire = self.new_wire();
+        self.gates.push(Gate::xor(a, b, output_wire));
+        output_wire
+    }
+
+    /// Wire holding `a AND b`
+    pub(crate) fn and(&mut self, a: WireId, b: WireId) -> WireId {
+        let output_wire = self.new_wire();
+        self.gates.push(Gate::and(a, b, output_wire));
+        output_wire
+    }
+
+    /// Wire holding `a OR b`, as `a XOR b XOR (a AND b)` for a single AND gate
+    pub(crate) fn or(&mut self, a: WireId, b: WireId) -> WireId {
+        let both = self.and(a, b);
+        let either = self.xor(a, b);
+        self.xor(either, both)
+    }
+
+    /// Copy `outputs` to the last wires and declare one input and one output bus
+    pub(crate) fn into_circuit(self, outputs: &[WireId]) -> Circuit {
+        let input_widths = vec![self.num_inputs];
+        self.into_declared_circuit(input_widths, &[outputs])
+    }
+
+    /// Copy each output value to the last wires, in order, and declare the
+    /// inputs as values of `input_widths` wires
+    pub(crate) fn into_declared_circuit(
+        mut self,
+        input_widths: Vec<u64>,
+        outputs: &[&[WireId]],
+    ) -> Circuit {
+        debug_assert_eq!(input_widths.iter().sum::<u64>(), self.num_inputs);
+        let mut output_widths = Vec::with_capacity(outputs.len());
+        for value in outputs {
+            for &source in *value {
+                let output_wire = self.new_wire();
+                self.gates.push(Gate::buf(source, output_wire));
+            }
+            output_widths.push(value.len() as u64);
+        }
+        let declared = |widths: Vec<u64>| widths.into_iter().filter(|&width| width > 0).collect();
+        Circuit {
+            num_wires: self.num_wires,
+            gates: self.gates,
+            io: Some(IoDeclaration {
+                input_widths: declared(input_widths),
+                output_widths: declared(output_widths),
+            }),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use crate::gate::WireId;
+use crate::session::SessionId;
+
+/// Result type of every fallible function in the library
+pub type Result<T, E = GcError> = std::result::Result<T, E>;
+
+/// Errors returned by the library
+///
+/// Variants carry the values callers typically branch on (the line of a
+/// parse error, the wire that was missing, the format versions involved),
+/// so a malformed circuit can be told apart from e.g. a missing file
+/// without matching on the message.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GcError {
+    /// A line of a Bristol circuit could not be parsed
+    #[error("{message} at line {line}")]
+    Parse {
+        /// 1-based line number in the circuit file
+        line: u64,
+        /// What was wrong with the line
+        message: String,
+    },
+    /// The circuit parsed but is not well-formed, e.g. a wire ID exceeds
+    /// `num_wires` or a wire is read before it is produced
+    #[error("Invalid circuit: {0}")]
+    InvalidCircuit(String),
+    /// Reading or writing a file or stream failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// A JSON file could not be read or written
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    /// A binary file could not be encoded
+    #[error("Binary encoding error: {0}")]
+    Encode(#[from] bincode::error::EncodeError),
+    /// A binary file could not be decoded
+    #[error("Binary decoding error: {0}")]
+    Decode(#[from] bincode::error::DecodeError),
+    /// A file is not in the expected format, e.g. bad magic bytes or truncated
+    #[error("Invalid {format}: {message}")]
+    InvalidFormat {
+        /// Kind of file, e.g. "wire labels file"
+        format: &'static str,
+        /// What was wrong with it
+        message: String,
+    },
+    /// A file was written by an incompatible version of the library
+    #[error("Unsupported {format} format version {found}: expected {expected}")]
+    FormatVersionMismatch {
+        /// Kind of file, e.g. "wire labels"
+        format: &'static str,
+        /// Version stored in the file
+        found: u32,
+        /// Version this library reads
+        expected: u32,
+    },
+    /// A wire has no label, value or input bit where one is required
+    #[error("{message}")]
+    MissingWire {
+        /// The wire that was missing
+        wire: WireId,
+        /// Where it was missing
+        message: String,
+    },
+    /// The number of garbled tables differs from what the circuit or a
+    /// header requires
+    #[error("{message}")]
+    TableCountMismatch {
+        /// Number of tables required
+        expected: u64,
+        /// Number of tables available
+        found: u64,
+        /// Which tables were counted
+        message: String,
+    },
+    /// Garbled tables were produced for a different circuit
+    #[error("Garbled tables were produced for a different circuit: circuit digest mismatch")]
+    CircuitMismatch,
+    /// Artifacts of different garbling runs were combined
+    #[error(
+        "Session id mismatch: {artifact} belongs to session {found}, expected session {expected}"
+    )]
+    SessionMismatch {
+        /// Artifact whose session differs, e.g. "OT result"
+        artifact: String,
+        /// Session id of the artifact
+        found: SessionId,
+        /// Session id it should have had
+        expected: SessionId,
+    },
+    /// A file differs from the one recorded in the session manifest
+    #[error("{path} does not match the {artifact} recorded in the session manifest ({recorded})")]
+    ManifestMismatch {
+        /// Kind of artifact, e.g. "circuit"
+        artifact: &'static str,
+        /// The file that was checked
+        path: String,
+        /// The file the manifest recorded
+        recorded: String,
+    },
+    /// An output label matches neither of the garbler's labels for its wire
+    #[error("Label for output wire {wire} matches neither output label")]
+    InvalidLabel {
+        /// The output wire
+        wire: WireId,
+    },
+    /// Output labels are not among the garbler's committed labels
+    #[error(
+        "Output label check failed for {} of {total} output wires: {failed_wires:?}",
+        failed_wires.len()
+    )]
+    OutputCheckFailed {
+        /// Wires whose labels failed the check
+        failed_wires: Vec<WireId>,
+        /// Number of output wires checked
+        total: usize,
+    },
+    /// A configured limit was exceeded
+    #[error("{0}")]
+    LimitExceeded(String),
+    /// An argument or option is invalid or options conflict
+    #[error("{0}")]
+    InvalidArgument(String),
+    /// The other party sent a malformed or unexpected protocol message
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+    /// A chunk of a stream failed its checksum or arrived out of order
+    #[error("Chunk {sequence} is corrupt: {message}")]
+    CorruptChunk {
+        /// Sequence number the chunk should have had
+        sequence: u64,
+        /// What was wrong with it
+        message: String,
+    },
+    /// A file or stream does not match the checksum it was written with
+    #[error("{artifact} is corrupt: checksum mismatch")]
+    ChecksumMismatch {
+        /// What was checked, e.g. "garbled tables file"
+        artifact: &'static str,
+    },
+    /// A worker thread or the other end of a channel went away
+    #[error("{0}")]
+    Disconnected(String),
+    /// No usable GPU was found, or garbling on it failed
+    #[cfg(feature = "gpu")]
+    #[error("GPU error: {0}")]
+    Gpu(String),
+    /// TLS configuration is invalid or rejected by rustls
+    #[cfg(feature = "tls")]
+    #[error("TLS error: {0}")]
+    Tls(#[from] rustls::Error),
+    /// Error from a user-provided extension, e.g. a custom backend or sink
+    #[error(transparent)]
+    Other(Box<dyn std::error::Error + Send + Sync + 'static>),
+}
+
+impl GcError {
+    /// Parse error at `line`
+    pub fn parse(line: u64, message: impl Into<String>) -> Self {
+        GcError::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// Wire `wire` is missing, described by `message`
+    pub fn missing_wire(wire: WireId, message: impl Into<String>) -> Self {
+        GcError::MissingWire {
+            wire,
+            message: message.into(),
+        }
+    }
+
+    /// Bad magic bytes or otherwise unrecognizable `format` file
+    pub fn invalid_format(format: &'static str, message: impl Into<String>) -> Self {
+        GcError::InvalidFormat {
+            format,
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_messages() {
+        assert_eq!(
+            GcError::parse(3, "Unsupported gate type: NAND").to_string(),
+            "Unsupported gate type: NAND at line 3"
+        );
+        assert_eq!(
+            GcError::FormatVersionMismatch {
+                format: "wire labels",
+                found: 1,
+                expected: 3
+            }
+            .to_string(),
+            "Unsupported wire labels format version 1: expected 3"
+        );
+        assert_eq!(
+            GcError::OutputCheckFailed {
+                failed_wires: vec![4, 7],
+                total: 10
+            }
+            .to_string(),
+            "Output label check failed for 2 of 10 output wires: [4, 7]"
+        );
+
+        let io_error = GcError::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(matches!(io_error, GcError::Io(_)));
+    }
+}
@@ -1,29 +1,102 @@
-use anyhow::{Result, bail};
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::error::{GcError, Result};
 use serde::{Deserialize, Serialize};
+use std::borrow::{Borrow, Cow};
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::Path;
-
-use crate::constants::PROGRESS_UPDATE_INTERVAL;
-use crate::garbler::{GarbledTable, WireLabel, garbling_hash};
+use std::sync::mpsc::{Receiver, sync_channel};
+use std::time::Instant;
+
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use crate::constants::{
+    BUFFER_SIZE, EVALUATION_RESULT_MAGIC, EVALUATION_RESULT_VERSION, GARBLED_TABLE_SIZE,
+    GARBLED_TABLES_HEADER_SIZE, GARBLED_TABLES_MAGIC, PREFETCH_BATCH_SIZE, PREFETCH_QUEUE_DEPTH,
+    PROGRESS_UPDATE_INTERVAL, TABLE_BUFFER_SIZE, ZSTD_MAGIC,
+};
+use crate::garbler::{
+    GarbledTable, GarblingScheme, TablesHeader, WireLabel, constant_label, garbling_hash,
+};
+use crate::gate::{CircuitDigest, Gate, GateType, WireId};
+use crate::label_store::LabelStore;
+use crate::layering::LayerReport;
 use crate::ot_simulation::OTResult;
+use crate::parser::{CircuitHeader, parse_gate_line, read_header};
+use crate::progress::{NoProgress, ProgressObserver, trace_throughput};
+use crate::seek_table::SeekTable;
+use crate::serde_helpers::serialize_sorted_map;
+use crate::session::SessionId;
+use crate::shard::{ShardedTables, is_manifest_path};
 use crate::stream::BufferedLineStream;
-use crate::wire_analyzer::WireUsageReport;
+use crate::wire_analyzer::{UsageCounts, WireUsageReport, analyze_wire_usage, take_usage_counts};
 
-/// Evaluation result containing output wire labels and their bit values
-#[derive(Debug, Serialize, Deserialize)]
+/// Evaluation result containing output wire labels, and their bit values once decoded
+#[derive(Debug, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
 pub struct EvaluationResult {
-    /// Final output labels with their bit values: wire_id -> (label, bit_value)
-    pub output_results: HashMap<u32, OutputResult>,
+    /// Final output labels with their decoded bit values: wire_id -> (label, bit_value)
+    #[serde(serialize_with = "serialize_sorted_map")]
+    pub output_results: HashMap<WireId, OutputResult>,
+    /// Statistics of the evaluation run that produced this result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<EvaluationStats>,
+}
+
+/// Performance statistics of a single evaluation run
+#[derive(Debug, Default, Clone, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
+pub struct EvaluationStats {
+    /// Number of gates evaluated
+    pub total_gates: u64,
+    /// Number of AND gates evaluated (one garbled table each)
+    pub and_gates: u64,
+    /// Wall-clock time of the evaluation in seconds
+    pub wall_time_secs: f64,
+    /// Evaluation throughput in gates per second
+    pub gates_per_sec: f64,
+    /// Largest number of wire labels held in memory at once
+    pub peak_active_labels: u64,
+    /// Bytes of (decompressed) garbled tables consumed
+    pub table_bytes_read: u64,
 }
 
-/// An evaluated output wire with its label and bit value
-#[derive(Debug, Serialize, Deserialize)]
+impl EvaluationStats {
+    fn new(total_gates: u64, and_gates: u64, peak_active_labels: u64, start: Instant) -> Self {
+        let wall_time_secs = start.elapsed().as_secs_f64();
+        EvaluationStats {
+            total_gates,
+            and_gates,
+            wall_time_secs,
+            gates_per_sec: if wall_time_secs > 0.0 {
+                total_gates as f64 / wall_time_secs
+            } else {
+                0.0
+            },
+            peak_active_labels,
+            table_bytes_read: and_gates * GARBLED_TABLE_SIZE as u64,
+        }
+    }
+
+    /// Save statistics as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// An evaluated output wire with its label, and its bit value once decoded
+#[derive(Debug, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
 pub struct OutputResult {
     /// The output wire label
     pub label: WireLabel,
     /// The bit value this label represents
-    pub bit_value: bool,
+    ///
+    /// `None` as evaluated, since the evaluator only learns labels; see
+    /// [`crate::decoder`] for turning labels into bits with the garbler's
+    /// decoding information.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bit_value: Option<bool>,
 }
 
 impl EvaluationResult {
@@ -41,7 +114,61 @@ impl EvaluationResult {
         Ok(result)
     }
 
+    /// Save evaluation result in the compact binary format
+    ///
+    /// Layout: [`EVALUATION_RESULT_MAGIC`], the format version as a
+    /// little-endian `u32`, then the bincode-encoded result.
+    pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::with_capacity(BUFFER_SIZE, file);
+        writer.write_all(&EVALUATION_RESULT_MAGIC)?;
+        writer.write_all(&EVALUATION_RESULT_VERSION.to_le_bytes())?;
+        bincode::encode_into_std_write(self, &mut writer, bincode::config::standard())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load evaluation result from the binary format
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        let mut reader = BufReader::with_capacity(BUFFER_SIZE, file);
+
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        if header[..4] != EVALUATION_RESULT_MAGIC {
+            return Err(GcError::invalid_format(
+                "binary evaluation result file",
+                "bad magic bytes",
+            ));
+        }
+        let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if version != EVALUATION_RESULT_VERSION {
+            return Err(GcError::FormatVersionMismatch {
+                format: "evaluation result",
+                found: version,
+                expected: EVALUATION_RESULT_VERSION,
+            });
+        }
+
+        let result = bincode::decode_from_std_read(&mut reader, bincode::config::standard())?;
+        Ok(result)
+    }
+
+    /// Load evaluation result from either format, detected by the magic bytes
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        let bytes_read = File::open(path.as_ref())?.read(&mut magic)?;
+        if bytes_read == magic.len() && magic == EVALUATION_RESULT_MAGIC {
+            Self::load_binary(path)
+        } else {
+            Self::load_json(path)
+        }
+    }
+
     /// Print evaluation results to console
+    ///
+    /// Only available with the `cli` feature.
+    #[cfg(feature = "cli")]
     pub fn print_summary(&self) {
         println!("Circuit Evaluation Results:");
         println!("  Output wires: {}", self.output_results.len());
@@ -54,44 +181,33 @@ impl EvaluationResult {
             println!(
                 "  Wire {}: {} (label: {:02x}...)",
                 wire_id,
-                if output.bit_value { 1 } else { 0 },
+                match output.bit_value {
+                    Some(true) => "1",
+                    Some(false) => "0",
+                    None => "?",
+                },
                 output.label.as_bytes()[0]
             );
         }
     }
 }
 
-/// Wire label with its corresponding bit value (used during evaluation)
-#[derive(Debug, Clone)]
-struct LabelWithBit {
-    label: WireLabel,
-    bit_value: bool,
-}
-
-// Hash function is now imported from garbler.rs to ensure consistency
-
-/// Evaluate an AND gate using knowledge of input bit values
+/// Evaluate an AND gate from the point-and-permute bits of its input labels
 ///
-/// Since we know the bit values of input labels, we can directly compute which
-/// row of the garbled table to decrypt without trial decryption.
+/// The permute bits say which row of the garbled table to decrypt, so there
+/// is no trial decryption and the evaluator never learns the bits its labels
+/// stand for.
 fn evaluate_and_gate(
-    input1: &LabelWithBit,
-    input2: &LabelWithBit,
+    input1: &WireLabel,
+    input2: &WireLabel,
     garbled_table: &GarbledTable,
-) -> Result<LabelWithBit> {
-    // Compute truth table row index from input bit values
-    // Row encoding: (input1_bit, input2_bit) -> row_index
+) -> Result<WireLabel> {
+    // Row encoding: (input1_permute_bit, input2_permute_bit) -> row_index
     // (0,0) -> 0, (0,1) -> 1, (1,0) -> 2, (1,1) -> 3
-    let row_index = (input1.bit_value as usize) * 2 + (input2.bit_value as usize);
-
-    // Compute output bit value using AND truth table
-    let output_bit = input1.bit_value && input2.bit_value;
-
-    // Extract the input labels for hashing
-    let input_labels = [input1.label, input2.label];
+    let row_index = (input1.permute_bit() as usize) * 2 + (input2.permute_bit() as usize);
 
     // Compute decryption key
-    let key = garbling_hash(&input_labels);
+    let key = garbling_hash(&[*input1, *input2]);
 
     // Decrypt the appropriate ciphertext
     let ciphertext = &garbled_table.ciphertexts[row_index];
@@ -100,67 +216,728 @@ fn evaluate_and_gate(
         plaintext[i] = ciphertext[i] ^ key[i];
     }
 
-    let output_label = WireLabel::new(plaintext);
+    Ok(WireLabel::new(plaintext))
+}
 
-    Ok(LabelWithBit {
-        label: output_label,
-        bit_value: output_bit,
-    })
+/// Evaluate any gate, looking up its input labels with `input`
+///
+/// `next_table` is only called for AND gates, and may hand out an owned or
+/// a borrowed table.
+pub(crate) fn evaluate_gate<T: Borrow<GarbledTable>>(
+    gate: &Gate,
+    mut input: impl FnMut(WireId) -> Result<WireLabel>,
+    next_table: impl FnOnce() -> Result<T>,
+) -> Result<WireLabel> {
+    let output = match gate.gate_type {
+        // Free XOR: output_label = input1_label XOR input2_label
+        GateType::Xor => input(gate.input_wire_1)?.xor(&input(gate.input_wire_2)?),
+        // Evaluate AND gate using garbled table
+        GateType::And => evaluate_and_gate(
+            &input(gate.input_wire_1)?,
+            &input(gate.input_wire_2)?,
+            next_table()?.borrow(),
+        )?,
+        // Copy: the output carries the input's label
+        GateType::Buf => input(gate.input_wire_1)?,
+        // Constant: the label of the public value is derived, not sent
+        GateType::Const(_) => constant_label(gate.output_wire),
+    };
+    Ok(output)
+}
+
+/// How the evaluator accesses the garbled tables file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TableAccess {
+    /// Read tables through a bounded read-ahead buffer
+    #[default]
+    Buffered,
+    /// Memory-map the file and index into it directly
+    ///
+    /// Avoids both the read syscalls and the buffer allocation. The mapping is
+    /// advised as sequential so the kernel reads ahead and drops consumed pages,
+    /// keeping resident memory flat for huge table files.
+    Mmap,
+    /// Read tables on a background thread through a bounded queue
+    ///
+    /// The next batches of tables are read and parsed while the evaluator
+    /// works through runs of XOR gates, hiding I/O latency on slow disks and
+    /// network filesystems. Works for both plain and compressed files.
+    Prefetch,
+}
+
+/// Options controlling circuit evaluation
+#[derive(Debug, Default, Clone)]
+pub struct EvaluationOptions {
+    /// How garbled tables are read from disk
+    pub table_access: TableAccess,
+    /// Keep at most this many bytes of wire labels in memory
+    ///
+    /// Beyond the budget the least recently used labels are spilled to an
+    /// anonymous temp file (in `TMPDIR`) and read back on demand. `None`
+    /// keeps every live label in memory. Not supported by
+    /// [`evaluate_circuit_layered`].
+    pub max_memory: Option<u64>,
+    /// Scheme the garbled tables must have been garbled with
+    ///
+    /// Tables whose header names another scheme are rejected before any
+    /// gate is evaluated. Headerless tables are assumed to match.
+    pub scheme: GarblingScheme,
 }
 
-/// Load garbled tables from binary file
+/// Adapts a channel of byte chunks into a [`Read`] stream
 ///
-/// Each garbled table is 64 bytes (4 ciphertexts × 16 bytes each)
-fn load_garbled_tables<P: AsRef<Path>>(path: P) -> Result<Vec<GarbledTable>> {
-    // Create progress bar for loading
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} [{elapsed_precise}] {msg}")
-            .unwrap(),
-    );
-    pb.set_message("Loading garbled tables...");
+/// Lets a receiving thread push garbled tables to the evaluator as they
+/// arrive (see [`evaluate_circuit_from_reader`]). Chunks need not be aligned
+/// to table boundaries. The stream ends once every sender is dropped.
+#[derive(Debug)]
+pub struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
 
-    let data = std::fs::read(path)?;
+impl ChannelReader {
+    /// Wrap the receiving end of a channel
+    pub fn new(receiver: Receiver<Vec<u8>>) -> Self {
+        Self {
+            receiver,
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+}
 
-    if data.len() % 64 != 0 {
-        bail!(
-            "Invalid garbled tables file: size {} is not multiple of 64",
-            data.len()
-        );
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                // All senders dropped: end of stream
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.chunk.len() - self.position);
+        buf[..len].copy_from_slice(&self.chunk[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// Type-erased byte stream carrying garbled tables
+type TableBytes = Box<dyn Read + Send>;
+
+/// Backing storage of a [`GarbledTableReader`]
+enum TableSource {
+    /// Buffered reader bounding the read-ahead to `TABLE_BUFFER_SIZE` bytes
+    Buffered(BufReader<File>),
+    /// Read-only memory mapping of the whole file, with tables starting at
+    /// `offset` (past the header)
+    Mapped { mmap: Mmap, offset: usize },
+    /// Streaming zstd decoder over a compressed tables file or stream
+    Compressed(zstd::Decoder<'static, BufReader<TableBytes>>),
+    /// Compressed tables file ending with a [`SeekTable`]
+    Seekable(Box<SeekableTables>),
+    /// Arbitrary byte stream, e.g. a socket or a [`ChannelReader`]
+    Stream(BufReader<TableBytes>),
+    /// Tables read ahead by a background thread
+    Prefetched(TablePrefetcher),
+    /// Shard files listed in a [`ShardManifest`](crate::shard::ShardManifest)
+    Sharded(Box<ShardedTables>),
+}
+
+impl std::fmt::Debug for TableSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableSource::Buffered(_) => f.write_str("Buffered"),
+            TableSource::Mapped { mmap, .. } => write!(f, "Mapped({} bytes)", mmap.len()),
+            TableSource::Compressed(_) => f.write_str("Compressed"),
+            TableSource::Seekable(_) => f.write_str("Seekable"),
+            TableSource::Stream(_) => f.write_str("Stream"),
+            TableSource::Prefetched(_) => f.write_str("Prefetched"),
+            TableSource::Sharded(_) => f.write_str("Sharded"),
+        }
+    }
+}
+
+/// Sequential reader over a garbled tables file
+///
+/// Tables are consumed in the order the garbler produced them, so they are read
+/// on demand instead of being loaded into memory up front.
+/// Each garbled table is 64 bytes (4 ciphertexts × 16 bytes each).
+#[derive(Debug)]
+pub struct GarbledTableReader {
+    /// Where the table bytes come from
+    source: TableSource,
+    /// Header of the file, absent for legacy headerless tables
+    header: Option<TablesHeader>,
+    /// Total number of tables in the file (unknown for headerless compressed
+    /// files and streams)
+    num_tables: Option<usize>,
+    /// Number of tables consumed so far
+    tables_read: usize,
+    /// Checksum of the tables read so far, while the header's checksum can
+    /// still be verified
+    hasher: Option<blake3::Hasher>,
+}
+
+impl GarbledTableReader {
+    /// Open a garbled tables file, validating its header and size
+    ///
+    /// A [`TablesHeader`] at the start of the file is checked for the format
+    /// version and scheme, and its table count against the file size; its
+    /// checksum is verified when the last table is read. Files
+    /// without a header are still accepted. Tables starting with the zstd
+    /// magic bytes are decoded on the fly; without a header their count is
+    /// only known once the stream is exhausted.
+    ///
+    /// A path ending in `.json` is read as a
+    /// [`ShardManifest`](crate::shard::ShardManifest), and the shards it lists
+    /// are read one after another with the given access mode.
+    pub fn open<P: AsRef<Path>>(path: P, access: TableAccess) -> Result<Self> {
+        if is_manifest_path(path.as_ref()) {
+            let (shards, header) = ShardedTables::open(path.as_ref(), access)?;
+            return Ok(Self {
+                source: TableSource::Sharded(Box::new(shards)),
+                header: Some(header),
+                num_tables: Some(header.num_tables as usize),
+                tables_read: 0,
+                hasher: checksum_hasher(Some(&header)),
+            });
+        }
+
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len() as usize;
+        let header = read_tables_header(&mut file)?;
+        let offset = if header.is_some() {
+            GARBLED_TABLES_HEADER_SIZE
+        } else {
+            0
+        };
+        let num_tables = header.map(|header| header.num_tables as usize);
+
+        if is_zstd_compressed(&mut file, offset as u64)? {
+            if access == TableAccess::Mmap {
+                return Err(GcError::InvalidArgument(
+                    "Memory-mapped access is not supported for compressed garbled tables".into(),
+                ));
+            }
+            let seek_table = SeekTable::read_from(&mut file, offset as u64)?;
+            file.seek(SeekFrom::Start(offset as u64))?;
+            let num_tables = match (&seek_table, num_tables) {
+                (Some(seek_table), Some(num_tables))
+                    if seek_table.num_tables() != num_tables as u64 =>
+                {
+                    return Err(GcError::TableCountMismatch {
+                        expected: num_tables as u64,
+                        found: seek_table.num_tables(),
+                        message: format!(
+                            "Garbled tables file is corrupt: header promises {} tables, its frames hold {}",
+                            num_tables,
+                            seek_table.num_tables()
+                        ),
+                    });
+                }
+                (Some(seek_table), _) => Some(seek_table.num_tables() as usize),
+                (None, num_tables) => num_tables,
+            };
+            let source = match (access, seek_table) {
+                (TableAccess::Prefetch, _) => {
+                    let file: TableBytes = Box::new(file);
+                    TableSource::Prefetched(TablePrefetcher::spawn(zstd::Decoder::with_buffer(
+                        BufReader::with_capacity(TABLE_BUFFER_SIZE, file),
+                    )?))
+                }
+                (_, Some(seek_table)) => TableSource::Seekable(Box::new(SeekableTables {
+                    decoder: zstd::Decoder::with_buffer(BufReader::with_capacity(
+                        TABLE_BUFFER_SIZE,
+                        file,
+                    ))?,
+                    seek_table,
+                    data_offset: offset as u64,
+                })),
+                (_, None) => {
+                    let file: TableBytes = Box::new(file);
+                    TableSource::Compressed(zstd::Decoder::with_buffer(BufReader::with_capacity(
+                        TABLE_BUFFER_SIZE,
+                        file,
+                    ))?)
+                }
+            };
+            return Ok(Self {
+                source,
+                header,
+                num_tables,
+                tables_read: 0,
+                hasher: checksum_hasher(header.as_ref()),
+            });
+        }
+
+        let tables_size = size - offset;
+        if let Some(num_tables) = num_tables {
+            if tables_size != num_tables * GARBLED_TABLE_SIZE {
+                return Err(GcError::TableCountMismatch {
+                    expected: num_tables as u64,
+                    found: (tables_size / GARBLED_TABLE_SIZE) as u64,
+                    message: format!(
+                        "Garbled tables file is truncated or corrupt: header promises {} tables ({} bytes), found {} bytes",
+                        num_tables,
+                        num_tables * GARBLED_TABLE_SIZE,
+                        tables_size
+                    ),
+                });
+            }
+        } else if !tables_size.is_multiple_of(GARBLED_TABLE_SIZE) {
+            return Err(GcError::invalid_format(
+                "garbled tables file",
+                format!("size {} is not multiple of {}", size, GARBLED_TABLE_SIZE),
+            ));
+        }
+
+        let source = match access {
+            TableAccess::Buffered => {
+                TableSource::Buffered(BufReader::with_capacity(TABLE_BUFFER_SIZE, file))
+            }
+            TableAccess::Mmap => {
+                // SAFETY: the mapping is read-only and the tables file is not
+                // expected to be modified while it is being evaluated.
+                let mmap = unsafe { Mmap::map(&file)? };
+                #[cfg(unix)]
+                mmap.advise(memmap2::Advice::Sequential)?;
+                TableSource::Mapped { mmap, offset }
+            }
+            TableAccess::Prefetch => TableSource::Prefetched(TablePrefetcher::spawn(
+                BufReader::with_capacity(TABLE_BUFFER_SIZE, file),
+            )),
+        };
+
+        Ok(Self {
+            source,
+            header,
+            num_tables: Some(tables_size / GARBLED_TABLE_SIZE),
+            tables_read: 0,
+            hasher: checksum_hasher(header.as_ref()),
+        })
     }
 
-    let num_tables = data.len() / 64;
-    let mut tables = Vec::with_capacity(num_tables);
+    /// Read tables from an arbitrary byte stream
+    ///
+    /// A [`TablesHeader`] at the start of the stream is validated and
+    /// consumed; without one, the number of tables is unknown until the
+    /// stream ends. Tables starting with the zstd magic bytes are decoded on
+    /// the fly, as for files. Blocks until the first bytes arrive.
+    pub fn from_reader<R: Read + Send + 'static>(reader: R) -> Result<Self> {
+        let (magic, mut reader) = peek_magic(Box::new(reader))?;
+
+        let mut header = None;
+        let mut magic = magic;
+        if magic == Some(GARBLED_TABLES_MAGIC) {
+            let mut bytes = [0u8; GARBLED_TABLES_HEADER_SIZE];
+            reader.read_exact(&mut bytes).map_err(|e| {
+                GcError::invalid_format(
+                    "garbled tables stream",
+                    format!("stream ended inside the header: {}", e),
+                )
+            })?;
+            header = Some(TablesHeader::from_bytes(&bytes)?);
+            (magic, reader) = peek_magic(reader)?;
+        }
 
-    pb.set_message(format!("Parsing {num_tables} garbled tables..."));
+        let source = if magic == Some(ZSTD_MAGIC) {
+            TableSource::Compressed(zstd::Decoder::with_buffer(BufReader::new(reader))?)
+        } else {
+            TableSource::Stream(BufReader::new(reader))
+        };
 
-    for i in 0..num_tables {
-        let start = i * 64;
-        let table_data = &data[start..start + 64];
+        Ok(Self {
+            source,
+            header,
+            num_tables: header.map(|header| header.num_tables as usize),
+            tables_read: 0,
+            hasher: checksum_hasher(header.as_ref()),
+        })
+    }
 
-        let mut ciphertexts = [[0u8; 16]; 4];
-        for (j, ciphertext) in ciphertexts.iter_mut().enumerate() {
-            let ct_start = j * 16;
-            ciphertext.copy_from_slice(&table_data[ct_start..ct_start + 16]);
+    /// Header of the tables, if the file or stream has one
+    pub fn header(&self) -> Option<&TablesHeader> {
+        self.header.as_ref()
+    }
+
+    /// Fail if the header says the tables were garbled with a different scheme
+    ///
+    /// Headerless tables carry no scheme and always pass.
+    pub fn check_scheme(&self, scheme: GarblingScheme) -> Result<()> {
+        match &self.header {
+            Some(header) => header.check_scheme(scheme),
+            None => Ok(()),
         }
+    }
 
-        tables.push(GarbledTable { ciphertexts });
+    /// Fail if the header says the tables belong to a different circuit
+    ///
+    /// Headerless tables carry no digest and always pass.
+    pub fn check_circuit(&self, circuit_digest: &[u8; 32]) -> Result<()> {
+        match &self.header {
+            Some(header) => header.check_circuit(circuit_digest),
+            None => Ok(()),
+        }
+    }
 
-        // Update progress every PROGRESS_UPDATE_INTERVAL tables
-        if (i as u32).is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
-            pb.tick();
+    /// Fail if the header says the tables come from a different garbling session
+    ///
+    /// `artifact` names what `session_id` came from, e.g. "OT result".
+    /// Headerless tables carry no session id and always pass.
+    pub fn check_session(&self, session_id: &SessionId, artifact: &str) -> Result<()> {
+        match &self.header {
+            Some(header) => header.check_session(session_id, artifact),
+            None => Ok(()),
         }
     }
 
-    pb.finish_with_message(format!("✓ Loaded {num_tables} garbled tables"));
-    Ok(tables)
+    /// Read the next garbled table, failing if the file is exhausted
+    pub fn next_table(&mut self) -> Result<GarbledTable> {
+        if self
+            .num_tables
+            .is_some_and(|num_tables| self.tables_read >= num_tables)
+        {
+            return Err(self.not_enough_tables());
+        }
+
+        let mut table_data = [0u8; GARBLED_TABLE_SIZE];
+        let result = match &mut self.source {
+            TableSource::Buffered(reader) => reader.read_exact(&mut table_data),
+            TableSource::Mapped { mmap, offset } => {
+                let start = *offset + self.tables_read * GARBLED_TABLE_SIZE;
+                table_data.copy_from_slice(&mmap[start..start + GARBLED_TABLE_SIZE]);
+                Ok(())
+            }
+            TableSource::Compressed(decoder) => decoder.read_exact(&mut table_data),
+            TableSource::Seekable(tables) => tables.decoder.read_exact(&mut table_data),
+            TableSource::Stream(reader) => reader.read_exact(&mut table_data),
+            TableSource::Prefetched(prefetcher) => match prefetcher.next_table() {
+                Ok(Some(table)) => {
+                    table_data = table.as_binary();
+                    Ok(())
+                }
+                Ok(None) => Err(std::io::ErrorKind::UnexpectedEof.into()),
+                Err(e) => Err(e),
+            },
+            TableSource::Sharded(shards) => match shards.next_table()? {
+                Some(table) => {
+                    table_data = table.as_binary();
+                    Ok(())
+                }
+                None => Err(std::io::ErrorKind::UnexpectedEof.into()),
+            },
+        };
+        if let Err(e) = result {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                // The header promised more tables than the stream holds
+                if let Some(header) = &self.header {
+                    return Err(GcError::TableCountMismatch {
+                        expected: header.num_tables,
+                        found: self.tables_read as u64,
+                        message: format!(
+                            "Garbled tables file is truncated: found {} of {} tables",
+                            self.tables_read, header.num_tables
+                        ),
+                    });
+                }
+                // Streams of unknown length end with a short read
+                return Err(self.not_enough_tables());
+            }
+            return Err(e.into());
+        }
+        self.tables_read += 1;
+        self.verify_checksum(&table_data)?;
+
+        Ok(GarbledTable::from_binary(&table_data))
+    }
+
+    /// Add a table to the running checksum, and compare it with the header's
+    /// once the last table was read
+    fn verify_checksum(&mut self, table_data: &[u8; GARBLED_TABLE_SIZE]) -> Result<()> {
+        let Some(hasher) = &mut self.hasher else {
+            return Ok(());
+        };
+        hasher.update(table_data);
+        if Some(self.tables_read) != self.num_tables {
+            return Ok(());
+        }
+        let expected = self.header.and_then(|header| header.tables_checksum);
+        // blake3::Hash compares in constant time
+        if expected.is_some_and(|expected| hasher.finalize() != expected) {
+            return Err(GcError::ChecksumMismatch {
+                artifact: "garbled tables",
+            });
+        }
+        Ok(())
+    }
+
+    /// Skip the next `count` tables, e.g. to resume a transfer
+    ///
+    /// Plain files seek past them and compressed files with a [`SeekTable`]
+    /// restart decoding at the frame holding the next table, both of which
+    /// leave the checksum unverified; other compressed files and streams
+    /// decode and drop them.
+    ///
+    /// # Returns
+    /// * `Ok(())` - The next table read is the one after the skipped ones
+    /// * `Err(GcError)` - Fewer than `count` tables are left, or a read error
+    pub fn skip_tables(&mut self, count: usize) -> Result<()> {
+        if count == 0 {
+            return Ok(());
+        }
+        if self
+            .remaining_tables()
+            .is_some_and(|remaining| remaining < count)
+        {
+            return Err(not_enough_tables(
+                self.tables_read + count,
+                self.num_tables.unwrap_or(self.tables_read),
+            ));
+        }
+        match &mut self.source {
+            TableSource::Buffered(reader) => {
+                reader.seek_relative((count * GARBLED_TABLE_SIZE) as i64)?;
+                self.tables_read += count;
+                self.hasher = None;
+            }
+            TableSource::Mapped { .. } => {
+                self.tables_read += count;
+                self.hasher = None;
+            }
+            TableSource::Seekable(tables) => {
+                let target = self.tables_read + count;
+                if let Some(first_table) = tables.seek_to_table(target as u64, self.tables_read)? {
+                    self.tables_read = first_table as usize;
+                    self.hasher = None;
+                }
+                for _ in self.tables_read..target {
+                    self.next_table()?;
+                }
+            }
+            _ => {
+                for _ in 0..count {
+                    self.next_table()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of tables left unread, if the total is known up front
+    pub fn remaining_tables(&self) -> Option<usize> {
+        self.num_tables
+            .map(|num_tables| num_tables.saturating_sub(self.tables_read))
+    }
+
+    /// Error reported when the circuit needs more tables than the file holds
+    fn not_enough_tables(&self) -> GcError {
+        not_enough_tables(
+            self.tables_read + 1,
+            self.num_tables.unwrap_or(self.tables_read),
+        )
+    }
+}
+
+/// Compressed tables file whose decoder can restart at any frame listed in
+/// its [`SeekTable`]
+struct SeekableTables {
+    decoder: zstd::Decoder<'static, BufReader<File>>,
+    seek_table: SeekTable,
+    /// Offset of the first frame in the file, past the header
+    data_offset: u64,
+}
+
+impl SeekableTables {
+    /// Restart decoding at the frame holding `table`, unless it is the frame
+    /// the decoder is in anyway
+    ///
+    /// # Arguments
+    /// * `table` - Table to read next
+    /// * `tables_read` - Tables decoded so far
+    ///
+    /// # Returns
+    /// * `Ok(Some(first_table))` - The decoder moved on to the frame starting at `first_table`, or past the last table
+    /// * `Ok(None)` - The decoder already is in the frame holding `table`
+    /// * `Err(GcError)` - The file could not be reopened at the frame
+    fn seek_to_table(&mut self, table: u64, tables_read: usize) -> Result<Option<u64>> {
+        let Some(frame) = self.seek_table.frame_for_table(table) else {
+            // Nothing is left to decode
+            return Ok(Some(table));
+        };
+        if frame.first_table <= tables_read as u64 {
+            return Ok(None);
+        }
+        let mut file = self.decoder.get_ref().get_ref().try_clone()?;
+        file.seek(SeekFrom::Start(self.data_offset + frame.offset))?;
+        self.decoder =
+            zstd::Decoder::with_buffer(BufReader::with_capacity(TABLE_BUFFER_SIZE, file))?;
+        Ok(Some(frame.first_table))
+    }
+}
+
+/// Background thread reading garbled tables ahead of the evaluator
+///
+/// Tables are sent in batches of [`PREFETCH_BATCH_SIZE`] over a channel
+/// bounded to [`PREFETCH_QUEUE_DEPTH`] batches, so read-ahead memory stays
+/// fixed. The thread stops at the end of the input, on the first I/O error
+/// (which is handed to the evaluator), or once the prefetcher is dropped.
+struct TablePrefetcher {
+    /// Batches of parsed tables, or the error that stopped the thread
+    receiver: Receiver<std::io::Result<Vec<GarbledTable>>>,
+    /// Tables of the current batch not consumed yet
+    batch: std::vec::IntoIter<GarbledTable>,
+}
+
+impl TablePrefetcher {
+    /// Start reading tables from `reader` on a new thread
+    fn spawn<R: Read + Send + 'static>(mut reader: R) -> Self {
+        let (sender, receiver) = sync_channel(PREFETCH_QUEUE_DEPTH);
+
+        std::thread::spawn(move || {
+            let mut table_data = [0u8; GARBLED_TABLE_SIZE];
+            loop {
+                let mut batch = Vec::with_capacity(PREFETCH_BATCH_SIZE);
+                let mut finished = false;
+                while batch.len() < PREFETCH_BATCH_SIZE {
+                    match reader.read_exact(&mut table_data) {
+                        Ok(()) => batch.push(GarbledTable::from_binary(&table_data)),
+                        // A short read marks the end of the input
+                        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                            finished = true;
+                            break;
+                        }
+                        Err(e) => {
+                            let _ = sender.send(Err(e));
+                            return;
+                        }
+                    }
+                }
+
+                // Sending fails once the evaluator is gone; stop reading
+                if !batch.is_empty() && sender.send(Ok(batch)).is_err() {
+                    return;
+                }
+                if finished {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            receiver,
+            batch: Vec::new().into_iter(),
+        }
+    }
+
+    /// Next table, or `None` once the input is exhausted
+    fn next_table(&mut self) -> std::io::Result<Option<GarbledTable>> {
+        loop {
+            if let Some(table) = self.batch.next() {
+                return Ok(Some(table));
+            }
+            match self.receiver.recv() {
+                Ok(batch) => self.batch = batch?.into_iter(),
+                // The thread hung up after sending everything
+                Err(_) => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Check for the zstd magic bytes at `offset`, returning to it afterwards
+fn is_zstd_compressed(file: &mut File, offset: u64) -> Result<bool> {
+    let mut magic = [0u8; 4];
+    let is_compressed = match file.read_exact(&mut magic) {
+        Ok(()) => magic == ZSTD_MAGIC,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => false,
+        Err(e) => return Err(e.into()),
+    };
+    file.seek(SeekFrom::Start(offset))?;
+    Ok(is_compressed)
+}
+
+/// Read the [`TablesHeader`] if the file starts with one
+///
+/// Leaves the file positioned at the first table: after the header, or at
+/// the start for legacy headerless files.
+fn read_tables_header(file: &mut File) -> Result<Option<TablesHeader>> {
+    let mut bytes = [0u8; GARBLED_TABLES_HEADER_SIZE];
+    let magic_len = read_up_to(file, &mut bytes[..4])?;
+    if magic_len < 4 || bytes[..4] != GARBLED_TABLES_MAGIC {
+        file.seek(SeekFrom::Start(0))?;
+        return Ok(None);
+    }
+
+    file.read_exact(&mut bytes[4..]).map_err(|e| {
+        GcError::invalid_format(
+            "garbled tables file",
+            format!("truncated inside the header: {}", e),
+        )
+    })?;
+    Ok(Some(TablesHeader::from_bytes(&bytes)?))
+}
+
+/// Hasher for verifying the tables against the header's checksum, if it has one
+fn checksum_hasher(header: Option<&TablesHeader>) -> Option<blake3::Hasher> {
+    header
+        .and_then(|header| header.tables_checksum)
+        .map(|_| blake3::Hasher::new())
+}
+
+/// Error reported when an AND gate finds no garbled table left
+fn not_enough_tables(needed: usize, available: usize) -> GcError {
+    GcError::TableCountMismatch {
+        expected: needed as u64,
+        found: available as u64,
+        message: format!(
+            "Not enough garbled tables: need at least {}, have {}",
+            needed, available
+        ),
+    }
+}
+
+/// Read the first four bytes of a stream without consuming them
+///
+/// Returns `None` for streams shorter than four bytes. The returned reader
+/// yields the peeked bytes again before the rest of the stream.
+fn peek_magic(mut reader: TableBytes) -> std::io::Result<(Option<[u8; 4]>, TableBytes)> {
+    let mut magic = [0u8; 4];
+    let magic_len = read_up_to(&mut reader, &mut magic)?;
+    let peeked = std::io::Cursor::new(magic[..magic_len].to_vec());
+    let magic = (magic_len == magic.len()).then_some(magic);
+    Ok((magic, Box::new(peeked.chain(reader))))
+}
+
+/// Fill `buf` as far as the reader allows, returning the number of bytes read
+///
+/// Unlike [`Read::read_exact`], a short input is not an error.
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
 }
 
 /// Evaluate a garbled circuit using selected input labels from OT simulation
 ///
 /// This function evaluates a Bristol circuit using the same memory-efficient
 /// streaming approach as the garbler, maintaining only live wire labels in memory.
+/// Passing the report by value lets the evaluator reuse its usage counts
+/// instead of copying them.
+/// Runs without progress reporting; use [`evaluate_circuit_with_options`] to
+/// receive progress callbacks.
 ///
 /// Expected format:
 /// First line: `<num_gates> <num_wires>`
@@ -168,322 +945,1424 @@ fn load_garbled_tables<P: AsRef<Path>>(path: P) -> Result<Vec<GarbledTable>> {
 ///
 /// # Arguments
 /// * `stream` - The line stream to process Bristol circuit gates
-/// * `wire_report` - Wire usage analysis for memory optimization
+/// * `wire_report` - Wire usage analysis for memory optimization, borrowed or owned
 /// * `ot_result` - Selected input labels from OT simulation
 /// * `garbled_tables_path` - Path to binary file containing garbled truth tables
 ///
 /// # Returns
 /// * `Ok(EvaluationResult)` - Output wire labels with their bit values
-/// * `Err(anyhow::Error)` - Parse error, file error, or evaluation error
-pub fn evaluate_circuit(
-    stream: &mut BufferedLineStream,
-    wire_report: &WireUsageReport,
+/// * `Err(GcError)` - Parse error, file error, or evaluation error
+pub fn evaluate_circuit<'r, R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    wire_report: impl Into<Cow<'r, WireUsageReport>>,
     ot_result: &OTResult,
     garbled_tables_path: &Path,
 ) -> Result<EvaluationResult> {
-    // Parse and validate header line (but ignore values)
-    let header_line = stream
-        .next_line()
-        .ok_or_else(|| anyhow::anyhow!("Missing header line"))??;
-
-    let header_tokens: Vec<&str> = header_line.split_whitespace().collect();
-    if header_tokens.len() != 2 {
-        bail!(
-            "Invalid header: expected '<num_gates> <num_wires>', got: '{}'",
-            header_line
-        );
+    evaluate_circuit_with_options(
+        stream,
+        wire_report,
+        ot_result,
+        garbled_tables_path,
+        &EvaluationOptions::default(),
+        &NoProgress,
+    )
+}
+
+/// Evaluate a garbled circuit file without a precomputed wire analysis
+///
+/// Makes a quick first pass over the circuit to derive the wire usage counts
+/// (see [`analyze_wire_usage`]) and then evaluates it as
+/// [`evaluate_circuit_with_options`] does. Convenient for one-off
+/// evaluations; when evaluating the same circuit repeatedly, save the wire
+/// analysis once and call [`evaluate_circuit_with_options`] instead.
+///
+/// # Arguments
+/// * `circuit_path` - Path to the Bristol circuit file
+/// * `ot_result` - Selected input labels from OT simulation
+/// * `garbled_tables_path` - Path to binary file containing garbled truth tables
+/// * `options` - Evaluation options
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(EvaluationResult)` - Output wire labels with their bit values
+/// * `Err(GcError)` - Parse error, file error, or evaluation error
+pub fn evaluate_circuit_file(
+    circuit_path: &Path,
+    ot_result: &OTResult,
+    garbled_tables_path: &Path,
+    options: &EvaluationOptions,
+    progress: &dyn ProgressObserver,
+) -> Result<EvaluationResult> {
+    let wire_report = analyze_wire_usage(
+        &mut BufferedLineStream::new(File::open(circuit_path)?),
+        progress,
+    )?;
+
+    evaluate_circuit_with_options(
+        &mut BufferedLineStream::new(File::open(circuit_path)?),
+        &wire_report,
+        ot_result,
+        garbled_tables_path,
+        options,
+        progress,
+    )
+}
+
+/// Evaluate a garbled circuit with explicit [`EvaluationOptions`]
+///
+/// Behaves like [`evaluate_circuit`] but lets the caller choose e.g. how the
+/// garbled tables file is accessed.
+///
+/// # Arguments
+/// * `stream` - The line stream to process Bristol circuit gates
+/// * `wire_report` - Wire usage analysis for memory optimization, borrowed or owned
+/// * `ot_result` - Selected input labels from OT simulation
+/// * `garbled_tables_path` - Path to binary file containing garbled truth tables
+/// * `options` - Evaluation options
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(EvaluationResult)` - Output wire labels with their bit values
+/// * `Err(GcError)` - Parse error, file error, or evaluation error
+pub fn evaluate_circuit_with_options<'r, R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    wire_report: impl Into<Cow<'r, WireUsageReport>>,
+    ot_result: &OTResult,
+    garbled_tables_path: &Path,
+    options: &EvaluationOptions,
+    progress: &dyn ProgressObserver,
+) -> Result<EvaluationResult> {
+    let garbled_tables = GarbledTableReader::open(garbled_tables_path, options.table_access)?;
+    evaluate_with_table_reader(
+        stream,
+        wire_report,
+        ot_result,
+        garbled_tables,
+        options,
+        progress,
+    )
+}
+
+/// Evaluate a garbled circuit reading garbled tables from any byte stream
+///
+/// Tables are consumed strictly in order as gates are evaluated, so the
+/// source can be a socket or a [`ChannelReader`] still being filled by the
+/// garbler, overlapping transfer and evaluation. Compressed streams are
+/// detected and decoded on the fly; the garbler's periodic encoder flushes
+/// keep decoding close behind the transfer.
+/// [`EvaluationOptions::table_access`] is ignored.
+///
+/// # Arguments
+/// * `stream` - The line stream to process Bristol circuit gates
+/// * `wire_report` - Wire usage analysis for memory optimization, borrowed or owned
+/// * `ot_result` - Selected input labels from OT simulation
+/// * `garbled_tables` - Byte stream of garbled tables in garbling order
+/// * `options` - Evaluation options
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(EvaluationResult)` - Output wire labels with their bit values
+/// * `Err(GcError)` - Parse error, read error, or evaluation error
+pub fn evaluate_circuit_from_reader<'r, R: Read + Send + 'static, C: Read>(
+    stream: &mut BufferedLineStream<C>,
+    wire_report: impl Into<Cow<'r, WireUsageReport>>,
+    ot_result: &OTResult,
+    garbled_tables: R,
+    options: &EvaluationOptions,
+    progress: &dyn ProgressObserver,
+) -> Result<EvaluationResult> {
+    evaluate_with_table_reader(
+        stream,
+        wire_report,
+        ot_result,
+        GarbledTableReader::from_reader(garbled_tables)?,
+        options,
+        progress,
+    )
+}
+
+/// Streaming evaluation shared by the file and reader entry points
+fn evaluate_with_table_reader<'r, R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    wire_report: impl Into<Cow<'r, WireUsageReport>>,
+    ot_result: &OTResult,
+    mut garbled_tables: GarbledTableReader,
+    options: &EvaluationOptions,
+    progress: &dyn ProgressObserver,
+) -> Result<EvaluationResult> {
+    garbled_tables.check_scheme(options.scheme)?;
+    garbled_tables.check_session(&ot_result.session_id, "OT result")?;
+
+    // Parse and validate header line (values are not needed)
+    read_header(stream)?;
+
+    // Parse each gate as we read it (streaming approach), hashing it for the
+    // circuit check against the tables header
+    let mut line_number = 0;
+    let mut circuit_digest = CircuitDigest::new();
+    let gates = std::iter::from_fn(|| {
+        let line_result = stream.next_line()?;
+        line_number += 1;
+        let gate = line_result
+            .map_err(GcError::from)
+            .and_then(|line| parse_gate_line(line, line_number));
+        if let Ok(gate) = &gate {
+            circuit_digest.update(gate);
+        }
+        Some(gate)
+    });
+
+    let result = evaluate_gates(
+        gates,
+        wire_report,
+        ot_result,
+        || garbled_tables.next_table(),
+        options,
+        progress,
+    )?;
+    garbled_tables.check_circuit(&circuit_digest.finalize())?;
+    Ok(result)
+}
+
+/// Evaluate a garbled circuit held entirely in memory
+///
+/// Same algorithm as [`evaluate_circuit_with_options`], but gates and garbled
+/// tables come from slices instead of files, so the evaluator can be embedded
+/// e.g. in a server without writing temporary files.
+///
+/// # Arguments
+/// * `gates` - Circuit gates in topological order
+/// * `wire_report` - Wire usage analysis for memory optimization, borrowed or owned
+/// * `ot_result` - Selected input labels from OT simulation
+/// * `garbled_tables` - Garbled tables of the AND gates, in gate order
+/// * `options` - Evaluation options ([`EvaluationOptions::table_access`] is ignored)
+///
+/// # Returns
+/// * `Ok(EvaluationResult)` - Output wire labels with their bit values
+/// * `Err(GcError)` - Missing wires or garbled tables
+pub fn evaluate_circuit_in_memory<'r>(
+    gates: &[Gate],
+    wire_report: impl Into<Cow<'r, WireUsageReport>>,
+    ot_result: &OTResult,
+    garbled_tables: &[GarbledTable],
+    options: &EvaluationOptions,
+) -> Result<EvaluationResult> {
+    let mut tables = garbled_tables.iter();
+    let mut tables_read = 0;
+    evaluate_gates(
+        gates.iter().copied().map(Ok),
+        wire_report,
+        ot_result,
+        || {
+            tables_read += 1;
+            tables
+                .next()
+                .cloned()
+                .ok_or_else(|| not_enough_tables(tables_read, garbled_tables.len()))
+        },
+        options,
+        &NoProgress,
+    )
+}
+
+/// Supplies garbled tables to an [`EvaluatorSession`]
+///
+/// Called once per AND gate, in the order the garbler produced the tables.
+/// Implemented for [`GarbledTableReader`] and for closures, so tables can
+/// come from a file, a slice, or a queue filled by a network task.
+pub trait TableCursor {
+    /// Next garbled table, failing if none are left
+    fn next_table(&mut self) -> Result<GarbledTable>;
+}
+
+impl TableCursor for GarbledTableReader {
+    fn next_table(&mut self) -> Result<GarbledTable> {
+        GarbledTableReader::next_table(self)
     }
+}
 
-    // Parse header values for validation
-    let _num_gates: u32 = header_tokens[0]
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid num_gates: '{}'", header_tokens[0]))?;
-    let _num_wires: u32 = header_tokens[1]
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid num_wires: '{}'", header_tokens[1]))?;
+impl<F: FnMut() -> Result<GarbledTable>> TableCursor for F {
+    fn next_table(&mut self) -> Result<GarbledTable> {
+        self()
+    }
+}
 
-    // Load all garbled tables into memory
-    let garbled_tables = load_garbled_tables(garbled_tables_path)?;
+/// Incremental evaluation state: live wire labels plus a table cursor
+///
+/// Gates are fed one at a time with [`feed_gate`](Self::feed_gate), so the
+/// caller decides when and in which order work happens, e.g. from a custom
+/// scheduler or an async network loop. Gates must still arrive in an order
+/// where every input wire was produced before it is read, and AND gates must
+/// arrive in garbling order so they line up with the tables. Labels are
+/// dropped as soon as the wire usage counts say they have no readers left.
+#[derive(Debug)]
+pub struct EvaluatorSession<'a, T> {
+    /// Wire usage analysis providing the primary outputs (its usage counts
+    /// are copied or moved into `remaining_usage`)
+    wire_report: Cow<'a, WireUsageReport>,
+    /// Source of garbled tables for AND gates
+    tables: T,
+    /// Remaining reads of every wire
+    remaining_usage: UsageCounts,
+    /// Labels of wires that still have readers (or are primary outputs)
+    active_wire_labels: LabelStore<WireLabel>,
+    /// Number of gates fed so far
+    gates_evaluated: u64,
+    /// Number of AND gates fed so far
+    and_gates: u64,
+    /// Largest number of labels held at once
+    peak_active_labels: usize,
+    /// When the session was created, for statistics
+    start: Instant,
+}
 
-    // Initialize usage counts for runtime tracking (clone from wire analysis)
-    let mut remaining_usage = wire_report.wire_usage_counts.clone();
+impl<'a> EvaluatorSession<'a, GarbledTableReader> {
+    /// Start a session reading garbled tables from a file
+    pub fn open<P: AsRef<Path>>(
+        wire_report: impl Into<Cow<'a, WireUsageReport>>,
+        ot_result: &OTResult,
+        garbled_tables_path: P,
+        options: &EvaluationOptions,
+    ) -> Result<Self> {
+        let garbled_tables = GarbledTableReader::open(garbled_tables_path, options.table_access)?;
+        Self::new(wire_report, ot_result, garbled_tables, options)
+    }
+}
 
-    // Initialize active wire labels HashMap with input labels from OT
-    let mut active_wire_labels: HashMap<u32, LabelWithBit> = HashMap::new();
+impl<'a, T: TableCursor> EvaluatorSession<'a, T> {
+    /// Start a session from the OT-selected input labels
+    pub fn new(
+        wire_report: impl Into<Cow<'a, WireUsageReport>>,
+        ot_result: &OTResult,
+        tables: T,
+        options: &EvaluationOptions,
+    ) -> Result<Self> {
+        let mut wire_report = wire_report.into();
+        let remaining_usage = take_usage_counts(&mut wire_report);
+        let mut active_wire_labels = LabelStore::new(options.max_memory)?;
+        for (wire_id, label) in ot_result.inputs() {
+            active_wire_labels.insert(wire_id, label)?;
+        }
 
-    // Initialize with selected input labels
-    for (&wire_id, selected_input) in &ot_result.selected_inputs {
-        active_wire_labels.insert(
-            wire_id,
-            LabelWithBit {
-                label: selected_input.label,
-                bit_value: selected_input.bit_value,
-            },
-        );
+        Ok(Self {
+            wire_report,
+            tables,
+            remaining_usage,
+            peak_active_labels: active_wire_labels.len(),
+            active_wire_labels,
+            gates_evaluated: 0,
+            and_gates: 0,
+            start: Instant::now(),
+        })
     }
 
-    // Process gates using streaming approach
-    let mut and_gate_counter = 0usize;
-    let mut line_number = 0;
+    /// Evaluate one gate, returning the label of its output wire
+    pub fn feed_gate(&mut self, gate: Gate) -> Result<OutputResult> {
+        let active_wire_labels = &mut self.active_wire_labels;
+        let tables = &mut self.tables;
+        let output = evaluate_gate(
+            &gate,
+            |wire_id| {
+                active_wire_labels.get(wire_id)?.ok_or_else(|| {
+                    GcError::missing_wire(wire_id, format!("Input wire {} not found", wire_id))
+                })
+            },
+            || tables.next_table(),
+        )?;
+        if gate.gate_type == GateType::And {
+            self.and_gates += 1;
+        }
+        self.gates_evaluated += 1;
 
-    // Create progress bar for gate processing
-    let estimated_gates = wire_report.total_wires - wire_report.primary_inputs;
-    let pb = ProgressBar::new(estimated_gates as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
-            .unwrap()
-            .progress_chars("##-"),
-    );
-    pb.set_message("Evaluating circuit...");
+        // Add output wire to active set
+        self.active_wire_labels.insert(gate.output_wire, output)?;
 
-    // Process each gate as we read it (streaming approach)
-    while let Some(line_result) = stream.next_line() {
-        line_number += 1;
-        let line = line_result?;
+        // Process input wires: decrement usage and remove if no longer needed
+        for input_wire in gate.input_wires() {
+            // Remove wire label from active set if no longer needed
+            if self.remaining_usage.release(input_wire) {
+                self.active_wire_labels.remove(input_wire);
+            }
+        }
+
+        self.peak_active_labels = self.peak_active_labels.max(self.active_wire_labels.len());
 
-        if line.trim().is_empty() {
-            bail!("Empty line at line number {}", line_number);
+        Ok(self.output_result(&output))
+    }
+
+    /// Current label of a wire, if it is still live
+    ///
+    /// Fails only if a spilled label can't be read back.
+    pub fn wire(&self, wire_id: WireId) -> Result<Option<OutputResult>> {
+        Ok(self
+            .active_wire_labels
+            .peek(wire_id)?
+            .map(|label| self.output_result(&label)))
+    }
+
+    /// Number of gates fed so far
+    pub fn gates_evaluated(&self) -> u64 {
+        self.gates_evaluated
+    }
+
+    /// Primary outputs computed so far, with statistics up to this point
+    ///
+    /// Outputs whose gates were not fed yet are simply absent.
+    pub fn snapshot(&self) -> Result<EvaluationResult> {
+        let mut output_results = HashMap::new();
+        for &wire_id in &self.wire_report.primary_output_wires {
+            if let Some(output) = self.wire(wire_id)? {
+                output_results.insert(wire_id, output);
+            }
         }
 
-        // Parse gate line directly using iterator (PERFORMANCE CRITICAL - matches garbler)
-        let mut tokens = line.split_whitespace();
+        Ok(EvaluationResult {
+            output_results,
+            stats: Some(self.stats()),
+        })
+    }
 
-        // Parse num_inputs and num_outputs
-        let num_inputs: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing num_inputs at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid num_inputs at line {}: '{}'", line_number, line)
+    /// Finish the session, failing if any primary output was never computed
+    pub fn finish(self) -> Result<EvaluationResult> {
+        // Collect output wire results from remaining active wires
+        let mut output_results = HashMap::new();
+        for &output_wire_id in &self.wire_report.primary_output_wires {
+            let output = self.wire(output_wire_id)?.ok_or_else(|| {
+                GcError::missing_wire(
+                    output_wire_id,
+                    format!("Output wire {} not found in active labels", output_wire_id),
+                )
             })?;
+            output_results.insert(output_wire_id, output);
+        }
 
-        let num_outputs: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing num_outputs at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid num_outputs at line {}: '{}'", line_number, line)
-            })?;
+        Ok(EvaluationResult {
+            output_results,
+            stats: Some(self.stats()),
+        })
+    }
 
-        // Validate standard gate format (2 inputs, 1 output)
-        if num_inputs != 2 || num_outputs != 1 {
-            bail!(
-                "Gate must have 2 inputs and 1 output at line {}: got {} inputs, {} outputs",
-                line_number,
-                num_inputs,
-                num_outputs
-            );
+    /// Statistics of the gates fed so far
+    fn stats(&self) -> EvaluationStats {
+        EvaluationStats::new(
+            self.gates_evaluated,
+            self.and_gates,
+            self.peak_active_labels as u64,
+            self.start,
+        )
+    }
+
+    /// Public view of a label; its bit is only known once decoded
+    fn output_result(&self, label: &WireLabel) -> OutputResult {
+        OutputResult {
+            label: *label,
+            bit_value: None,
         }
+    }
+}
 
-        // Parse input wires directly (no Vec allocation)
-        let input_wire_1: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing input wire 1 at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid input wire 1 at line {}: '{}'", line_number, line)
-            })?;
+/// Core evaluation loop over a sequence of gates
+///
+/// Drives an [`EvaluatorSession`] over all gates with progress reporting.
+/// `next_table` is called once per AND gate, in gate order.
+#[tracing::instrument(name = "evaluate", skip_all)]
+fn evaluate_gates<'r>(
+    gates: impl Iterator<Item = Result<Gate>>,
+    wire_report: impl Into<Cow<'r, WireUsageReport>>,
+    ot_result: &OTResult,
+    next_table: impl FnMut() -> Result<GarbledTable>,
+    options: &EvaluationOptions,
+    progress: &dyn ProgressObserver,
+) -> Result<EvaluationResult> {
+    let wire_report = wire_report.into();
 
-        let input_wire_2: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing input wire 2 at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid input wire 2 at line {}: '{}'", line_number, line)
-            })?;
+    // Create progress bar for gate processing
+    let estimated_gates = wire_report.total_wires - wire_report.primary_inputs;
+    let mut session = EvaluatorSession::new(wire_report, ot_result, next_table, options)?;
+    progress.start(Some(estimated_gates), "Evaluating circuit...");
 
-        // Parse output wire directly (no Vec allocation)
-        let output_wire: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing output wire at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid output wire at line {}: '{}'", line_number, line)
-            })?;
+    for (gate_index, gate) in gates.enumerate() {
+        session.feed_gate(gate?)?;
 
-        // Parse gate type (NO .to_string() allocation)
-        let gate_type = tokens.next().ok_or_else(|| {
-            anyhow::anyhow!("Missing gate type at line {}: '{}'", line_number, line)
-        })?;
+        // Update progress bar every PROGRESS_UPDATE_INTERVAL gates
+        if (gate_index as u64).is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            progress.update(gate_index as u64);
+            trace_throughput("evaluate", gate_index as u64, session.start);
+        }
+    }
 
-        // Validate no extra tokens
-        if tokens.next().is_some() {
-            bail!("Too many tokens at line {}: '{}'", line_number, line);
-        }
-
-        let gate_index: u32 = line_number - 1;
-
-        // Direct &str match (NO String allocation or conversion)
-        match gate_type {
-            "XOR" => {
-                // Free XOR: output_bit = input1_bit XOR input2_bit
-                // output_label = input1_label XOR input2_label
-                let input1 = active_wire_labels
-                    .get(&input_wire_1)
-                    .ok_or_else(|| anyhow::anyhow!("Input wire {} not found", input_wire_1))?;
-                let input2 = active_wire_labels
-                    .get(&input_wire_2)
-                    .ok_or_else(|| anyhow::anyhow!("Input wire {} not found", input_wire_2))?;
-
-                // XOR the labels and bit values
-                let output_label = input1.label.xor(&input2.label);
-                let output_bit = input1.bit_value ^ input2.bit_value;
-
-                // Add output wire to active set
-                active_wire_labels.insert(
-                    output_wire,
-                    LabelWithBit {
-                        label: output_label,
-                        bit_value: output_bit,
-                    },
-                );
-
-                // Process input wires: decrement usage and remove if no longer needed
-                let input_wires = [input_wire_1, input_wire_2];
-                for &input_wire in &input_wires {
-                    if remaining_usage[input_wire as usize] > 0 {
-                        // Wires with count 255 are never decremented (permanent wires)
-                        if remaining_usage[input_wire as usize] < 255 {
-                            remaining_usage[input_wire as usize] -= 1;
-                        }
+    // Finish progress bar
+    progress.finish(&format!(
+        "✓ Evaluated {} gates, {} AND gates",
+        session.gates_evaluated, session.and_gates
+    ));
+    tracing::info!(
+        gates = session.gates_evaluated,
+        and_gates = session.and_gates,
+        wall_time_secs = session.start.elapsed().as_secs_f64(),
+        "evaluation finished"
+    );
+    session.active_wire_labels.trace_spill_stats("evaluate");
 
-                        // Remove wire label from active set if no longer needed
-                        if remaining_usage[input_wire as usize] == 0 {
-                            active_wire_labels.remove(&input_wire);
-                        }
-                    }
-                }
-            }
-            "AND" => {
-                // Evaluate AND gate using garbled table
-                let input1 = active_wire_labels
-                    .get(&input_wire_1)
-                    .ok_or_else(|| anyhow::anyhow!("Input wire {} not found", input_wire_1))?;
-                let input2 = active_wire_labels
-                    .get(&input_wire_2)
-                    .ok_or_else(|| anyhow::anyhow!("Input wire {} not found", input_wire_2))?;
-
-                let output = evaluate_and_gate(
-                    input1,
-                    input2,
-                    garbled_tables.get(and_gate_counter).ok_or_else(|| {
-                        anyhow::anyhow!(
-                            "Not enough garbled tables: need at least {}, have {}",
-                            and_gate_counter + 1,
-                            garbled_tables.len()
-                        )
-                    })?,
-                )?;
+    session.finish()
+}
 
-                // Add output wire to active set
-                active_wire_labels.insert(output_wire, output);
-                and_gate_counter += 1;
-
-                // Process input wires: decrement usage and remove if no longer needed
-                let input_wires = [input_wire_1, input_wire_2];
-                for &input_wire in &input_wires {
-                    if remaining_usage[input_wire as usize] > 0 {
-                        // Wires with count 255 are never decremented (permanent wires)
-                        if remaining_usage[input_wire as usize] < 255 {
-                            remaining_usage[input_wire as usize] -= 1;
-                        }
+/// A parsed gate held in memory by the layered evaluator
+#[derive(Debug, Clone, Copy)]
+struct LayeredGate {
+    gate: Gate,
+    /// Index into the garbled tables for AND gates, `None` for other gates
+    and_index: Option<usize>,
+}
 
-                        // Remove wire label from active set if no longer needed
-                        if remaining_usage[input_wire as usize] == 0 {
-                            active_wire_labels.remove(&input_wire);
-                        }
-                    }
-                }
-            }
-            _ => {
-                bail!(
-                    "Unsupported gate type: {} at line {}",
-                    gate_type,
-                    line_number
-                );
+/// Evaluate a garbled circuit layer by layer, in parallel within each layer
+///
+/// Gates in the same dependency layer (see [`crate::layering`]) don't depend
+/// on each other, so each layer is evaluated concurrently on all cores.
+/// Unlike [`evaluate_circuit_with_options`] this keeps every gate, every
+/// garbled table and every wire label in memory for the whole evaluation.
+///
+/// # Arguments
+/// * `stream` - The line stream to process Bristol circuit gates
+/// * `wire_report` - Wire usage analysis providing the primary output wires
+/// * `layer_report` - Layer of every gate, computed from the same circuit
+/// * `ot_result` - Selected input labels from OT simulation
+/// * `garbled_tables_path` - Path to binary file containing garbled truth tables
+/// * `options` - Evaluation options
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(EvaluationResult)` - Output wire labels with their bit values
+/// * `Err(GcError)` - Parse error, file error, or evaluation error
+#[tracing::instrument(name = "evaluate_layered", skip_all)]
+pub fn evaluate_circuit_layered<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    wire_report: &WireUsageReport,
+    layer_report: &LayerReport,
+    ot_result: &OTResult,
+    garbled_tables_path: &Path,
+    options: &EvaluationOptions,
+    progress: &dyn ProgressObserver,
+) -> Result<EvaluationResult> {
+    if options.max_memory.is_some() {
+        return Err(GcError::InvalidArgument(
+            "The layered evaluator keeps every label in memory and does not support a memory cap"
+                .to_string(),
+        ));
+    }
+    let start = Instant::now();
+
+    let CircuitHeader {
+        num_gates,
+        num_wires,
+        lines,
+        ..
+    } = read_header(stream)?;
+
+    // Load every gate and its garbled table into memory
+    let mut garbled_tables = GarbledTableReader::open(garbled_tables_path, options.table_access)?;
+    garbled_tables.check_scheme(options.scheme)?;
+    garbled_tables.check_session(&ot_result.session_id, "OT result")?;
+    let mut tables = Vec::new();
+    let mut gates = Vec::with_capacity(num_gates as usize);
+    let mut circuit_digest = CircuitDigest::new();
+    let mut line_number = lines; // Already processed header
+
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        let gate = parse_gate_line(line_result?, line_number)?;
+        for wire_id in gate.input_wires().chain([gate.output_wire]) {
+            if wire_id >= num_wires {
+                return Err(GcError::parse(
+                    line_number,
+                    format!("Wire ID {} exceeds num_wires {}", wire_id, num_wires),
+                ));
             }
         }
 
-        // Update progress bar every PROGRESS_UPDATE_INTERVAL gates
-        if gate_index.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
-            pb.set_position(gate_index as u64);
-            // Avoid string allocation - use static message
-            pb.set_message("Evaluating circuit...");
+        let and_index = if gate.gate_type == GateType::And {
+            tables.push(garbled_tables.next_table()?);
+            Some(tables.len() - 1)
+        } else {
+            None
+        };
+
+        circuit_digest.update(&gate);
+        gates.push(LayeredGate { gate, and_index });
+    }
+    garbled_tables.check_circuit(&circuit_digest.finalize())?;
+
+    if layer_report.gate_layers.len() != gates.len() {
+        return Err(GcError::InvalidArgument(format!(
+            "Layer report covers {} gates but circuit has {}",
+            layer_report.gate_layers.len(),
+            gates.len()
+        )));
+    }
+
+    // Bucket gate indices by layer, preserving file order within a layer
+    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); layer_report.num_layers as usize];
+    for (gate_index, &layer) in layer_report.gate_layers.iter().enumerate() {
+        layers
+            .get_mut(layer as usize)
+            .ok_or_else(|| {
+                GcError::InvalidArgument(format!(
+                    "Gate {} has layer {} but report has only {} layers",
+                    gate_index, layer, layer_report.num_layers
+                ))
+            })?
+            .push(gate_index);
+    }
+
+    // Dense label storage indexed by wire ID
+    let mut wire_labels: Vec<Option<WireLabel>> = vec![None; num_wires as usize];
+    for (wire_id, label) in ot_result.inputs() {
+        let slot = wire_labels.get_mut(wire_id as usize).ok_or_else(|| {
+            GcError::InvalidCircuit(format!("Input wire {} exceeds num_wires", wire_id))
+        })?;
+        *slot = Some(label);
+    }
+
+    tracing::debug!(
+        gates = gates.len(),
+        and_tables = tables.len(),
+        layers = layers.len(),
+        "circuit loaded"
+    );
+    progress.start(Some(layers.len() as u64), "Evaluating circuit...");
+
+    for (layer_index, layer) in layers.iter().enumerate() {
+        // Every input of this layer was produced by an earlier one, so the
+        // label storage can be shared read-only while the layer is evaluated
+        let outputs: Vec<(WireId, WireLabel)> = layer
+            .par_iter()
+            .map(|&gate_index| {
+                let LayeredGate { gate, and_index } = &gates[gate_index];
+                let output = evaluate_gate(
+                    gate,
+                    |wire_id| {
+                        wire_labels[wire_id as usize].ok_or_else(|| {
+                            GcError::missing_wire(
+                                wire_id,
+                                format!("Input wire {} not found", wire_id),
+                            )
+                        })
+                    },
+                    || {
+                        and_index
+                            .map(|and_index| &tables[and_index])
+                            .ok_or_else(|| {
+                                GcError::InvalidCircuit(format!(
+                                    "AND gate {} has no table",
+                                    gate_index
+                                ))
+                            })
+                    },
+                )?;
+                Ok((gate.output_wire, output))
+            })
+            .collect::<Result<_>>()?;
+
+        for (output_wire, output) in outputs {
+            wire_labels[output_wire as usize] = Some(output);
         }
+        progress.update(layer_index as u64 + 1);
     }
 
-    // Finish progress bar
-    pb.finish_with_message(format!(
-        "✓ Evaluated {line_number} gates, {and_gate_counter} AND gates"
+    progress.finish(&format!(
+        "✓ Evaluated {} gates in {} layers, {} AND gates",
+        gates.len(),
+        layers.len(),
+        tables.len()
     ));
+    tracing::info!(
+        gates = gates.len(),
+        layers = layers.len(),
+        wall_time_secs = start.elapsed().as_secs_f64(),
+        "evaluation finished"
+    );
 
-    // Collect output wire results from remaining active wires
     let mut output_results = HashMap::new();
     for &output_wire_id in &wire_report.primary_output_wires {
-        let label_with_bit = active_wire_labels.get(&output_wire_id).ok_or_else(|| {
-            anyhow::anyhow!("Output wire {} not found in active labels", output_wire_id)
-        })?;
+        let label = wire_labels
+            .get(output_wire_id as usize)
+            .copied()
+            .flatten()
+            .ok_or_else(|| {
+                GcError::missing_wire(
+                    output_wire_id,
+                    format!("Output wire {} not found in active labels", output_wire_id),
+                )
+            })?;
 
         output_results.insert(
             output_wire_id,
             OutputResult {
-                label: label_with_bit.label,
-                bit_value: label_with_bit.bit_value,
+                label,
+                bit_value: None,
             },
         );
     }
 
-    Ok(EvaluationResult { output_results })
+    // Every label stays in memory until the end
+    let peak_active_labels = wire_labels.iter().filter(|label| label.is_some()).count();
+    let stats = EvaluationStats::new(
+        gates.len() as u64,
+        tables.len() as u64,
+        peak_active_labels as u64,
+        start,
+    );
+
+    Ok(EvaluationResult {
+        output_results,
+        stats: Some(stats),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::constants::RESIDENT_LABEL_BYTES;
+    use crate::garbler::{GarblerBuilder, GarblingResult, garble_and_gate, garble_circuit};
+    use crate::gate::Circuit;
+    use crate::layering::analyze_layers;
+    use crate::ot_simulation::simulate_ot;
+    use crate::progress::NoProgress;
+    use crate::test_util::create_test_file;
+    use crate::wire_analyzer::{UsageStorage, analyze_wire_usage_with_storage};
     use std::collections::HashMap;
 
+    fn open_stream(temp_file: &tempfile::NamedTempFile) -> Result<BufferedLineStream> {
+        Ok(BufferedLineStream::new(File::open(temp_file.path())?))
+    }
+
+    /// Garbler's label for `bit` on a primary output wire
+    fn output_label(garbling: &GarblingResult, wire_id: WireId, bit: bool) -> WireLabel {
+        garbling.wire_labels.get_wire_labels(wire_id).unwrap()[bit as usize]
+    }
+
+    #[test]
+    fn test_evaluate_circuit_streams_tables() -> Result<()> {
+        let circuit_data = "3 7\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n2 1 4 5 6 AND\n";
+        let circuit_file = create_test_file(circuit_data.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save(labels_file.path(), tables_file.path())?;
+
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+        let bit = |wire: WireId| ot_result.selected_inputs[&wire].bit_value;
+
+        let result = evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            tables_file.path(),
+        )?;
+
+        let expected = ((bit(0) && bit(1)) ^ bit(3)) && bit(5);
+        let output = &result.output_results[&6];
+        assert_eq!(output.label, output_label(&garbling, 6, expected));
+        // The evaluator only learns labels
+        assert_eq!(output.bit_value, None);
+
+        let stats = result.stats.as_ref().unwrap();
+        assert_eq!(stats.total_gates, 3);
+        assert_eq!(stats.and_gates, 2);
+        assert_eq!(stats.table_bytes_read, 128);
+        assert!(stats.peak_active_labels >= 2);
+
+        // Handing over the report reuses its usage counts and gives the same result
+        let owned = evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            wire_report,
+            &ot_result,
+            tables_file.path(),
+        )?;
+        assert_eq!(owned.output_results[&6].label, output.label);
+
+        Ok(())
+    }
+
     #[test]
-    fn test_evaluate_and_gate() {
-        // Create test input labels with bit values
-        let input1 = LabelWithBit {
-            label: WireLabel::new([0x01; 16]),
-            bit_value: true,
+    fn test_evaluate_circuit_mmap_matches_buffered() -> Result<()> {
+        let circuit_data = "2 5\n2 1 0 1 2 AND\n2 1 2 3 4 AND\n";
+        let circuit_file = create_test_file(circuit_data.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save(labels_file.path(), tables_file.path())?;
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+
+        let buffered = evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            tables_file.path(),
+        )?;
+        let options = EvaluationOptions {
+            table_access: TableAccess::Mmap,
+            ..Default::default()
         };
-        let input2 = LabelWithBit {
-            label: WireLabel::new([0x02; 16]),
-            bit_value: false,
+        let mapped = evaluate_circuit_with_options(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            tables_file.path(),
+            &options,
+            &NoProgress,
+        )?;
+
+        assert_eq!(
+            buffered.output_results[&4].label,
+            mapped.output_results[&4].label
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_circuit_compressed_tables() -> Result<()> {
+        let circuit_data = "3 7\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n2 1 4 5 6 AND\n";
+        let circuit_file = create_test_file(circuit_data.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save_compressed(labels_file.path(), tables_file.path(), 3)?;
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+
+        let result = evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            tables_file.path(),
+        )?;
+
+        let bit = |wire: WireId| ot_result.selected_inputs[&wire].bit_value;
+        let expected = ((bit(0) && bit(1)) ^ bit(3)) && bit(5);
+        assert_eq!(
+            result.output_results[&6].label,
+            output_label(&garbling, 6, expected)
+        );
+
+        // Dropping the last table must surface as a missing table, not a decode error
+        let truncated_file = tempfile::NamedTempFile::new()?;
+        let truncated = GarblingResult {
+            wire_labels: garbling.wire_labels,
+            garbled_tables: vec![garbling.garbled_tables[0].clone()],
+            circuit_digest: garbling.circuit_digest,
+            scheme: garbling.scheme,
+            stats: Default::default(),
         };
+        truncated.save_compressed(labels_file.path(), truncated_file.path(), 3)?;
+        let error_msg = evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            truncated_file.path(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error_msg.contains("Not enough garbled tables: need at least 2, have 1"));
 
-        // Create a dummy garbled table (normally this would be created by garbler)
-        let garbled_table = GarbledTable {
-            ciphertexts: [
-                [0u8; 16], // (0,0) -> 0
-                [0u8; 16], // (0,1) -> 0
-                [0u8; 16], // (1,0) -> 0
-                [0u8; 16], // (1,1) -> 1
-            ],
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_circuit_prefetch_matches_buffered() -> Result<()> {
+        let circuit_data = "3 7\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n2 1 4 5 6 AND\n";
+        let circuit_file = create_test_file(circuit_data.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        let compressed_file = tempfile::NamedTempFile::new()?;
+        garbling.save(labels_file.path(), tables_file.path())?;
+        garbling.save_compressed(labels_file.path(), compressed_file.path(), 3)?;
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+
+        let buffered = evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            tables_file.path(),
+        )?;
+        let options = EvaluationOptions {
+            table_access: TableAccess::Prefetch,
+            ..Default::default()
         };
+        for path in [tables_file.path(), compressed_file.path()] {
+            let prefetched = evaluate_circuit_with_options(
+                &mut open_stream(&circuit_file)?,
+                &wire_report,
+                &ot_result,
+                path,
+                &options,
+                &NoProgress,
+            )?;
+            assert_eq!(
+                buffered.output_results[&6].label,
+                prefetched.output_results[&6].label
+            );
+        }
 
-        let result = evaluate_and_gate(&input1, &input2, &garbled_table);
+        // Running out of prefetched tables reports the usual error
+        let truncated_file = tempfile::NamedTempFile::new()?;
+        std::fs::write(
+            truncated_file.path(),
+            garbling.garbled_tables[0].as_binary(),
+        )?;
+        let error_msg = evaluate_circuit_with_options(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            truncated_file.path(),
+            &options,
+            &NoProgress,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error_msg.contains("Not enough garbled tables: need at least 2, have 1"));
 
-        // Should succeed and return correct output bit (true AND false = false)
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(!output.bit_value);
+        Ok(())
     }
 
     #[test]
-    fn test_evaluation_result_serialization() -> anyhow::Result<()> {
+    fn test_evaluate_circuit_layered_matches_sequential() -> Result<()> {
+        let circuit_data =
+            "5 10\n2 1 0 1 4 AND\n2 1 2 3 5 XOR\n2 1 4 5 6 AND\n2 1 0 3 7 AND\n2 1 6 7 8 XOR\n";
+        let circuit_file = create_test_file(circuit_data.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+        let layer_report = analyze_layers(&mut open_stream(&circuit_file)?, &NoProgress)?;
+        assert_eq!(layer_report.gate_layers, vec![0, 0, 1, 0, 2]);
+
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save(labels_file.path(), tables_file.path())?;
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+
+        let sequential = evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            tables_file.path(),
+        )?;
+        let layered = evaluate_circuit_layered(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &layer_report,
+            &ot_result,
+            tables_file.path(),
+            &EvaluationOptions::default(),
+            &NoProgress,
+        )?;
+
+        let bit = |wire: WireId| ot_result.selected_inputs[&wire].bit_value;
+        let expected = ((bit(0) && bit(1)) && (bit(2) ^ bit(3))) ^ (bit(0) && bit(3));
+        assert_eq!(
+            layered.output_results[&8].label,
+            output_label(&garbling, 8, expected)
+        );
+        assert_eq!(
+            layered.output_results[&8].label,
+            sequential.output_results[&8].label
+        );
+
+        let stats = layered.stats.as_ref().unwrap();
+        assert_eq!(stats.total_gates, 5);
+        assert_eq!(stats.and_gates, 3);
+        assert_eq!(stats.peak_active_labels, 9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_circuit_file_without_wire_analysis() -> Result<()> {
+        let circuit_data = "3 7\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n2 1 4 5 6 AND\n";
+        let circuit_file = create_test_file(circuit_data.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save(labels_file.path(), tables_file.path())?;
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+
+        let result = evaluate_circuit_file(
+            circuit_file.path(),
+            &ot_result,
+            tables_file.path(),
+            &EvaluationOptions::default(),
+            &NoProgress,
+        )?;
+
+        let bit = |wire: WireId| ot_result.selected_inputs[&wire].bit_value;
+        let expected = ((bit(0) && bit(1)) ^ bit(3)) && bit(5);
+        assert_eq!(result.output_results.len(), 1);
+        assert_eq!(
+            result.output_results[&6].label,
+            output_label(&garbling, 6, expected)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_circuit_from_channel() -> Result<()> {
+        let circuit_data = "3 7\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n2 1 4 5 6 AND\n";
+        let circuit_file = create_test_file(circuit_data.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+
+        // Send the tables from another thread in chunks that split tables
+        let table_bytes: Vec<u8> = garbling
+            .garbled_tables
+            .iter()
+            .flat_map(|table| table.as_binary())
+            .collect();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let sender_thread = std::thread::spawn(move || {
+            for chunk in table_bytes.chunks(40) {
+                sender.send(chunk.to_vec()).unwrap();
+            }
+        });
+
+        let result = evaluate_circuit_from_reader(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            ChannelReader::new(receiver),
+            &EvaluationOptions::default(),
+            &NoProgress,
+        )?;
+        sender_thread.join().unwrap();
+
+        let bit = |wire: WireId| ot_result.selected_inputs[&wire].bit_value;
+        let expected = ((bit(0) && bit(1)) ^ bit(3)) && bit(5);
+        assert_eq!(
+            result.output_results[&6].label,
+            output_label(&garbling, 6, expected)
+        );
+
+        // A stream that ends early reports the missing tables
+        let (sender, receiver) = std::sync::mpsc::channel();
+        sender
+            .send(garbling.garbled_tables[0].as_binary().to_vec())
+            .unwrap();
+        drop(sender);
+        let error_msg = evaluate_circuit_from_reader(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            ChannelReader::new(receiver),
+            &EvaluationOptions::default(),
+            &NoProgress,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error_msg.contains("Not enough garbled tables: need at least 2, have 1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluator_session_incremental() -> Result<()> {
+        let circuit_data = "3 7\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n2 1 4 5 6 AND\n";
+        let circuit_file = create_test_file(circuit_data.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save(labels_file.path(), tables_file.path())?;
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+        let bit = |wire: WireId| ot_result.selected_inputs[&wire].bit_value;
+
+        let options = EvaluationOptions::default();
+        let mut session =
+            EvaluatorSession::open(&wire_report, &ot_result, tables_file.path(), &options)?;
+
+        let and_output = session.feed_gate(Gate::and(0, 1, 2))?;
+        assert_eq!(and_output.bit_value, None);
+        session.feed_gate(Gate::xor(2, 3, 4))?;
+        // Wire 2 had a single reader, so its label is gone
+        assert!(session.wire(2)?.is_none());
+        assert_eq!(
+            session.wire(4)?.unwrap().label,
+            and_output.label.xor(&ot_result.selected_inputs[&3].label)
+        );
+        assert!(session.snapshot()?.output_results.is_empty());
+
+        session.feed_gate(Gate::and(4, 5, 6))?;
+        assert_eq!(session.gates_evaluated(), 3);
+        let result = session.finish()?;
+
+        let from_file = evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            tables_file.path(),
+        )?;
+        let expected = ((bit(0) && bit(1)) ^ bit(3)) && bit(5);
+        assert_eq!(
+            result.output_results[&6].label,
+            output_label(&garbling, 6, expected)
+        );
+        assert_eq!(
+            result.output_results[&6].label,
+            from_file.output_results[&6].label
+        );
+
+        // Output never computed
+        let session =
+            EvaluatorSession::open(&wire_report, &ot_result, tables_file.path(), &options)?;
+        let error_msg = session.finish().unwrap_err().to_string();
+        assert!(error_msg.contains("Output wire 6 not found"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_circuit_in_memory_matches_file() -> Result<()> {
+        let circuit_data = "3 7\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n2 1 4 5 6 AND\n";
+        let circuit_file = create_test_file(circuit_data.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save(labels_file.path(), tables_file.path())?;
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+
+        let gates = [Gate::and(0, 1, 2), Gate::xor(2, 3, 4), Gate::and(4, 5, 6)];
+        let in_memory = evaluate_circuit_in_memory(
+            &gates,
+            &wire_report,
+            &ot_result,
+            &garbling.garbled_tables,
+            &EvaluationOptions::default(),
+        )?;
+        let from_file = evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            tables_file.path(),
+        )?;
+        assert_eq!(
+            in_memory.output_results[&6].label,
+            from_file.output_results[&6].label
+        );
+
+        let error_msg = evaluate_circuit_in_memory(
+            &gates,
+            &wire_report,
+            &ot_result,
+            &garbling.garbled_tables[..1],
+            &EvaluationOptions::default(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error_msg.contains("Not enough garbled tables: need at least 2, have 1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_cap_spills_without_changing_results() -> Result<()> {
+        // 256 inputs, each read by two AND gates, whose outputs are folded
+        // with XORs, so far more labels are live than the cap allows
+        let mut gate_lines = Vec::new();
+        for i in 0..255 {
+            gate_lines.push(format!("2 1 {} {} {} AND", i, i + 1, 256 + i));
+        }
+        let mut acc = 256;
+        for j in 1..255 {
+            gate_lines.push(format!("2 1 {} {} {} XOR", acc, 256 + j, 510 + j));
+            acc = 510 + j;
+        }
+        let circuit_data = format!("{} 765\n{}\n", gate_lines.len(), gate_lines.join("\n"));
+        let circuit_file = create_test_file(circuit_data.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+        let max_memory = 16 * RESIDENT_LABEL_BYTES;
+
+        let unbounded = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let capped = GarblerBuilder::new([1; 32])
+            .max_memory(max_memory)
+            .garble(&mut open_stream(&circuit_file)?, &wire_report)?;
+        assert_eq!(
+            unbounded.wire_labels.output_labels,
+            capped.wire_labels.output_labels
+        );
+        assert_eq!(unbounded.garbled_tables.len(), capped.garbled_tables.len());
+        for (a, b) in unbounded.garbled_tables.iter().zip(&capped.garbled_tables) {
+            assert_eq!(a.as_binary(), b.as_binary());
+        }
+
+        let ot_result = simulate_ot(&unbounded.wire_labels, &[2; 32], &NoProgress)?;
+        let circuit = Circuit::parse(&circuit_data)?;
+        let expected = evaluate_circuit_in_memory(
+            &circuit.gates,
+            &wire_report,
+            &ot_result,
+            &unbounded.garbled_tables,
+            &EvaluationOptions::default(),
+        )?;
+        let options = EvaluationOptions {
+            max_memory: Some(max_memory),
+            ..Default::default()
+        };
+        let spilled = evaluate_circuit_in_memory(
+            &circuit.gates,
+            &wire_report,
+            &ot_result,
+            &unbounded.garbled_tables,
+            &options,
+        )?;
+        assert_eq!(
+            expected.output_results[&acc].label,
+            spilled.output_results[&acc].label
+        );
+        assert_eq!(
+            expected.stats.unwrap().peak_active_labels,
+            spilled.stats.unwrap().peak_active_labels
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sparse_usage_counts_end_to_end() -> Result<()> {
+        // A trillion wire IDs, of which only seven are used
+        let circuit_data = "3 1000000000000\n2 1 7 999999999999 500 AND\n\
+                            2 1 500 42 80000000000 XOR\n2 1 80000000000 3 123456789012 AND\n";
+        let circuit_file = create_test_file(circuit_data.as_bytes())?;
+        let wire_report = analyze_wire_usage_with_storage(
+            &mut open_stream(&circuit_file)?,
+            UsageStorage::Sparse,
+            &NoProgress,
+        )?;
+        assert_eq!(
+            wire_report.primary_input_wires,
+            vec![3, 7, 42, 999999999999]
+        );
+        assert_eq!(wire_report.primary_output_wires, vec![123456789012]);
+        assert_eq!(wire_report.missing_wires_count, 1000000000000 - 7);
+
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+        let bit = |wire: WireId| ot_result.selected_inputs[&wire].bit_value;
+
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save(tempfile::NamedTempFile::new()?.path(), tables_file.path())?;
+        let result = evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            wire_report,
+            &ot_result,
+            tables_file.path(),
+        )?;
+
+        let expected = ((bit(7) && bit(999999999999)) ^ bit(42)) && bit(3);
+        assert_eq!(
+            result.output_results[&123456789012].label,
+            output_label(&garbling, 123456789012, expected)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_circuit_not_enough_tables() -> Result<()> {
+        let circuit_data = "2 5\n2 1 0 1 2 AND\n2 1 2 3 4 AND\n";
+        let circuit_file = create_test_file(circuit_data.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+
+        // Only ship the first of the two tables
+        let tables_file = create_test_file(garbling.garbled_tables[0].as_binary())?;
+
+        let result = evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            tables_file.path(),
+        );
+
+        let error_msg = result.unwrap_err().to_string();
+        assert!(error_msg.contains("Not enough garbled tables: need at least 2, have 1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_circuit_rejects_tables_of_other_circuit() -> Result<()> {
+        // Same shape and AND count, but different wiring
+        let circuit_file = create_test_file(b"2 5\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n")?;
+        let other_file = create_test_file(b"2 5\n2 1 0 1 2 AND\n2 1 3 2 4 XOR\n")?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save(labels_file.path(), tables_file.path())?;
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+
+        evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &ot_result,
+            tables_file.path(),
+        )?;
+
+        let error_msg = evaluate_circuit(
+            &mut open_stream(&other_file)?,
+            &wire_report,
+            &ot_result,
+            tables_file.path(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error_msg.contains("produced for a different circuit"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_circuit_rejects_ot_result_of_other_session() -> Result<()> {
+        let circuit_file = create_test_file(b"2 5\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n")?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+
+        // Two runs over the same circuit with different seeds
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[1; 32])?;
+        let other = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[3; 32])?;
+        assert_ne!(
+            garbling.wire_labels.session_id,
+            other.wire_labels.session_id
+        );
+
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save(labels_file.path(), tables_file.path())?;
+        let other_ot_result = simulate_ot(&other.wire_labels, &[2; 32], &NoProgress)?;
+
+        let error_msg = evaluate_circuit(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &other_ot_result,
+            tables_file.path(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error_msg.contains("Session id mismatch: OT result belongs to session"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_garbled_table_reader_rejects_partial_table() -> Result<()> {
+        let tables_file = create_test_file([0u8; 65])?;
+        let error_msg = GarbledTableReader::open(tables_file.path(), TableAccess::Buffered)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("size 65 is not multiple of 64"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_and_gate() -> Result<()> {
+        // Delta has its permute bit set, so each wire's labels land on different rows
+        let delta = WireLabel::new([0x81; 16]);
+        let input_labels = [WireLabel::new([0x01; 16]), WireLabel::new([0x02; 16])]
+            .map(|label_0| [label_0, label_0.xor(&delta)]);
+        let output_labels = [WireLabel::new([0x30; 16]), WireLabel::new([0xB1; 16])];
+        let garbled_table = garble_and_gate(&input_labels, &output_labels);
+
+        // Only the labels are needed to find the row
+        for (bit1, bit2) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let output = evaluate_and_gate(
+                &input_labels[0][bit1],
+                &input_labels[1][bit2],
+                &garbled_table,
+            )?;
+            assert_eq!(output, output_labels[bit1 & bit2]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluation_result_binary_roundtrip() -> Result<()> {
+        let output_results = (0..1000u64)
+            .map(|wire_id| {
+                (
+                    wire_id,
+                    OutputResult {
+                        label: WireLabel::new([wire_id as u8; 16]),
+                        bit_value: (wire_id % 3 != 0).then_some(wire_id % 2 == 0),
+                    },
+                )
+            })
+            .collect();
+        let eval_result = EvaluationResult {
+            output_results,
+            stats: None,
+        };
+
+        let binary_file = tempfile::NamedTempFile::new()?;
+        let json_file = tempfile::NamedTempFile::new()?;
+        eval_result.save_binary(binary_file.path())?;
+        eval_result.save_json(json_file.path())?;
+        assert!(
+            std::fs::metadata(binary_file.path())?.len()
+                < std::fs::metadata(json_file.path())?.len() / 4
+        );
+
+        // Both formats are picked up by `load`
+        for path in [binary_file.path(), json_file.path()] {
+            let loaded = EvaluationResult::load(path)?;
+            assert_eq!(loaded.output_results.len(), 1000);
+            assert_eq!(loaded.output_results[&7].label, WireLabel::new([7; 16]));
+            assert_eq!(loaded.output_results[&7].bit_value, Some(false));
+            assert_eq!(loaded.output_results[&9].bit_value, None);
+        }
+
+        // Unknown versions are rejected
+        let mut data = std::fs::read(binary_file.path())?;
+        data[4] = 99;
+        let bad_file = create_test_file(&data)?;
+        let error_msg = EvaluationResult::load_binary(bad_file.path())
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Unsupported evaluation result format version 99"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluation_result_serialization() -> Result<()> {
         let mut output_results = HashMap::new();
         output_results.insert(
-            0u32,
+            0u64,
             OutputResult {
                 label: WireLabel::new([0x42; 16]),
-                bit_value: true,
+                bit_value: Some(true),
             },
         );
 
-        let eval_result = EvaluationResult { output_results };
+        let eval_result = EvaluationResult {
+            output_results,
+            stats: None,
+        };
 
         // Test JSON serialization round-trip using temporary file
         let temp_file = tempfile::NamedTempFile::new()?;
@@ -491,9 +2370,9 @@ mod tests {
         let loaded_result = EvaluationResult::load_json(temp_file.path())?;
 
         assert_eq!(loaded_result.output_results.len(), 1);
-        let output = loaded_result.output_results.get(&0u32).unwrap();
+        let output = loaded_result.output_results.get(&0u64).unwrap();
         assert_eq!(output.label, WireLabel::new([0x42; 16]));
-        assert!(output.bit_value);
+        assert_eq!(output.bit_value, Some(true));
 
         Ok(())
     }
@@ -0,0 +1,280 @@
+use crate::error::Result;
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::constants::PROGRESS_UPDATE_INTERVAL;
+use crate::gate::WireId;
+use crate::memory_simulation::{MemorySimulationReport, MemorySnapshot};
+use crate::parser::{CircuitHeader, WireRef, read_header, visit_gate_wires};
+use crate::progress::ProgressObserver;
+use crate::single_use_analyzer::SingleUseGateAnalysis;
+use crate::stream::BufferedLineStream;
+use crate::wire_analyzer::{UsageStorage, UsageTally, WireUsageReport};
+
+// Kind of gate writing a wire, as kept per wire by `analyze_fused`
+const NOT_PRODUCED: u8 = 0;
+const AND_PRODUCER: u8 = 1;
+const XOR_PRODUCER: u8 = 2;
+const OTHER_PRODUCER: u8 = 3;
+
+/// Results of [`analyze_fused`]
+#[derive(Debug)]
+pub struct FusedAnalysis {
+    /// Number of gates of each type, as [`count_gate_types`](crate::counter::count_gate_types)
+    /// counts them
+    pub gate_counts: HashMap<String, usize>,
+    /// Wire usage, as [`analyze_wire_usage`](crate::wire_analyzer::analyze_wire_usage) reports it
+    pub wire_report: WireUsageReport,
+    /// Gate types of single-use wires, as
+    /// [`analyze_single_use_gates`](crate::single_use_analyzer::analyze_single_use_gates)
+    /// reports them
+    pub single_use: SingleUseGateAnalysis,
+    /// Live wires over time, as
+    /// [`simulate_memory_usage`](crate::memory_simulation::simulate_memory_usage) reports them
+    pub memory: MemorySimulationReport,
+}
+
+/// Count gates, analyze wire usage and single-use wires, and simulate memory
+/// usage in a single pass over a Bristol circuit
+///
+/// The single-use analysis and memory simulation need the final usage count
+/// of every wire, which the separate passes take from a finished wire
+/// analysis. Here the pass instead keeps, next to the usage counts, the kind
+/// of gate writing each wire, the last gate reading it and the number of
+/// wires each gate writes, and both results are worked out from those once
+/// the gates are read. That costs about 10 bytes per wire and 4 per gate on
+/// top of the wire analysis, in exchange for reading the file only once.
+///
+/// # Arguments
+/// * `stream` - The line stream to process
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(FusedAnalysis)` - The four analyses, the same as from the separate passes
+/// * `Err(GcError)` - Parse error, IO error, or a wire outside `0..num_wires`
+#[tracing::instrument(name = "fused_analysis", skip_all)]
+pub fn analyze_fused<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    progress: &dyn ProgressObserver,
+) -> Result<FusedAnalysis> {
+    let CircuitHeader {
+        num_gates,
+        num_wires,
+        io,
+        lines,
+    } = read_header(stream)?;
+
+    let mut tally = UsageTally::new(UsageStorage::Dense, num_wires);
+    let mut gate_counts: HashMap<String, usize> = HashMap::new();
+    let mut producer_kinds = vec![NOT_PRODUCED; num_wires as usize];
+    // Index of the last gate reading each wire plus one, 0 if none does
+    let mut last_readers = vec![0u64; num_wires as usize];
+    // Change in the number of live wires at each gate: the wires it writes
+    // now, less the wires it is the last reader of once those are known
+    let mut live_deltas: Vec<i32> = Vec::with_capacity(num_gates as usize);
+    let mut output_wires: Vec<WireId> = Vec::new();
+
+    progress.start(Some(num_gates), "Analyzing circuit");
+    let mut line_number = lines;
+    let mut gates_processed: u64 = 0;
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        output_wires.clear();
+        let gate_type = visit_gate_wires(line_result?, line_number, |wire| {
+            wire.check_range(num_wires, line_number)?;
+            tally.record(wire);
+            match wire {
+                WireRef::Input(wire_id) => last_readers[wire_id as usize] = gates_processed + 1,
+                WireRef::Output(wire_id) => output_wires.push(wire_id),
+            }
+            Ok(())
+        })?;
+
+        let producer_kind = match gate_type {
+            "AND" => AND_PRODUCER,
+            "XOR" => XOR_PRODUCER,
+            _ => OTHER_PRODUCER,
+        };
+        for &wire_id in &output_wires {
+            producer_kinds[wire_id as usize] = producer_kind;
+        }
+        live_deltas.push(output_wires.len() as i32);
+        match gate_counts.get_mut(gate_type) {
+            Some(count) => *count += 1,
+            None => {
+                gate_counts.insert(gate_type.to_string(), 1);
+            }
+        }
+
+        gates_processed += 1;
+        if gates_processed.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            progress.update(gates_processed);
+        }
+    }
+
+    progress.update(gates_processed);
+    progress.finish(&format!(
+        "✓ Analyzed {gates_processed} gates, found {num_wires} wires"
+    ));
+    if gates_processed != num_gates {
+        tracing::warn!(
+            expected = num_gates,
+            processed = gates_processed,
+            "gate count differs from circuit header"
+        );
+    }
+
+    let wire_report = tally.classify(num_wires, io.as_ref())?;
+    let usage_counts = &wire_report.wire_usage_counts;
+    let mut single_use = SingleUseGateAnalysis {
+        single_use_and_gates: 0,
+        single_use_xor_gates: 0,
+        total_single_use_wires: 0,
+    };
+    // A wire's label is dropped at its last reader, unless it is permanent
+    let release = |wire_id: WireId, live_deltas: &mut [i32]| {
+        if (1..255).contains(&usage_counts.get(wire_id)) {
+            live_deltas[last_readers[wire_id as usize] as usize - 1] -= 1;
+        }
+    };
+    for wire_id in 0..num_wires {
+        let producer_kind = producer_kinds[wire_id as usize];
+        if producer_kind == NOT_PRODUCED {
+            continue;
+        }
+        if usage_counts.get(wire_id) == 1 {
+            single_use.total_single_use_wires += 1;
+            match producer_kind {
+                AND_PRODUCER => single_use.single_use_and_gates += 1,
+                XOR_PRODUCER => single_use.single_use_xor_gates += 1,
+                _ => {}
+            }
+        }
+        release(wire_id, &mut live_deltas);
+    }
+    for &wire_id in &wire_report.primary_input_wires {
+        if producer_kinds[wire_id as usize] == NOT_PRODUCED {
+            release(wire_id, &mut live_deltas);
+        }
+    }
+
+    let mut live_wires = wire_report.primary_input_wires.len() as i64;
+    let mut max_live_wires = live_wires;
+    let mut snapshots = Vec::new();
+    for (gate_index, &delta) in live_deltas.iter().enumerate() {
+        live_wires += i64::from(delta);
+        max_live_wires = max_live_wires.max(live_wires);
+        if (gate_index as u64).is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            snapshots.push(MemorySnapshot {
+                gate_number: gate_index + 1,
+                live_wire_count: live_wires as usize,
+            });
+        }
+    }
+    let memory = MemorySimulationReport {
+        max_live_wires: max_live_wires as usize,
+        final_live_wires: live_wires as usize,
+        total_gates_processed: gates_processed as usize,
+        snapshots,
+    };
+
+    Ok(FusedAnalysis {
+        gate_counts,
+        wire_report,
+        single_use,
+        memory,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::count_gate_types;
+    use crate::gate::Gate;
+    use crate::memory_simulation::simulate_memory_usage;
+    use crate::parser::IoDeclaration;
+    use crate::progress::NoProgress;
+    use crate::random_circuit::{RandomCircuit, RandomCircuitParams};
+    use crate::single_use_analyzer::analyze_single_use_gates;
+    use crate::wire_analyzer::analyze_wire_usage;
+
+    #[test]
+    fn test_fused_analysis() -> Result<()> {
+        // Enough gates for several memory snapshots, with outputs read by
+        // later gates and a copy gate beside AND and XOR
+        let params = RandomCircuitParams {
+            num_gates: 200_000,
+            num_inputs: 64,
+            num_outputs: 1000,
+            depth: 500,
+            fan_out: "local:2000".parse()?,
+            ..RandomCircuitParams::default()
+        };
+        let mut circuit = RandomCircuit::new(params)?.into_circuit();
+        circuit.gates.push(Gate::buf(3, circuit.num_wires));
+        circuit.num_wires += 1;
+        circuit.io = Some(IoDeclaration::new(vec![64], vec![1001], circuit.num_wires)?);
+        for declared in [true, false] {
+            if !declared {
+                // Outputs are then the wires no gate reads
+                circuit.io = None;
+            }
+            let mut text = Vec::new();
+            circuit.write_bristol(&mut text)?;
+            let stream = || BufferedLineStream::from_reader(text.as_slice());
+
+            let fused = analyze_fused(&mut stream(), &NoProgress)?;
+            assert_eq!(
+                fused.gate_counts,
+                count_gate_types(&mut stream(), &NoProgress)?
+            );
+            assert_eq!(fused.gate_counts["EQW"], 1);
+
+            let wire_report = analyze_wire_usage(&mut stream(), &NoProgress)?;
+            assert_eq!(
+                fused.wire_report.wire_usage_counts,
+                wire_report.wire_usage_counts
+            );
+            assert_eq!(
+                fused.wire_report.primary_input_wires,
+                wire_report.primary_input_wires
+            );
+            assert_eq!(
+                fused.wire_report.primary_output_wires,
+                wire_report.primary_output_wires
+            );
+
+            let single_use = analyze_single_use_gates(&mut stream(), &wire_report, &NoProgress)?;
+            assert_eq!(
+                (
+                    fused.single_use.single_use_and_gates,
+                    fused.single_use.single_use_xor_gates,
+                    fused.single_use.total_single_use_wires
+                ),
+                (
+                    single_use.single_use_and_gates,
+                    single_use.single_use_xor_gates,
+                    single_use.total_single_use_wires
+                )
+            );
+
+            let memory = simulate_memory_usage(&mut stream(), &wire_report, &NoProgress)?;
+            assert_eq!(fused.memory.max_live_wires, memory.max_live_wires);
+            assert_eq!(fused.memory.final_live_wires, memory.final_live_wires);
+            assert_eq!(
+                fused.memory.total_gates_processed,
+                memory.total_gates_processed
+            );
+            let snapshots = |report: &MemorySimulationReport| -> Vec<(usize, usize)> {
+                report
+                    .snapshots
+                    .iter()
+                    .map(|snapshot| (snapshot.gate_number, snapshot.live_wire_count))
+                    .collect()
+            };
+            assert_eq!(snapshots(&fused.memory), snapshots(&memory));
+            assert_eq!(fused.memory.snapshots.len(), 3);
+        }
+        Ok(())
+    }
+}
@@ -1,17 +1,40 @@
-use anyhow::{Result, bail};
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::error::{GcError, Result};
 use rand::{RngCore, SeedableRng};
 use rand_chacha::ChaCha12Rng;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
-
-use crate::constants::PROGRESS_UPDATE_INTERVAL;
+use std::time::Instant;
+
+use crate::backend::{AndGateJob, CpuBackend, GarblingBackend, HashingPool, garble_batch};
+use crate::constants::{
+    BUFFER_SIZE, GARBLED_TABLE_SIZE, GARBLED_TABLES_HEADER_SIZE, GARBLED_TABLES_MAGIC,
+    GARBLED_TABLES_VERSION, GARBLING_BATCH_SIZE, PROGRESS_UPDATE_INTERVAL, WIRE_LABELS_MAGIC,
+    WIRE_LABELS_VERSION,
+};
+use crate::gate::{CircuitDigest, Gate, GateType, WireId};
+use crate::label_store::{LabelStore, SpillValue};
+use crate::ot_simulation::{EncodedInputs, select_label};
+use crate::parser::{CircuitHeader, parse_gate_line, read_header};
+use crate::partition::InputPartition;
+use crate::progress::{NoProgress, ProgressObserver, trace_throughput};
+use crate::serde_helpers::serialize_sorted_map;
+use crate::session::SessionId;
+use crate::sink::{
+    ChecksumSink, CompressedTableWriter, GarbledTableSink, TableWriter, tables_checksum,
+};
 use crate::stream::BufferedLineStream;
-use crate::wire_analyzer::WireUsageReport;
+use crate::wire_analyzer::{WireUsageReport, analyze_wire_usage, take_usage_counts};
 
 /// 128-bit wire label for garbled circuits
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, bincode::Encode, bincode::Decode,
+)]
 pub struct WireLabel([u8; 16]);
 
 impl WireLabel {
@@ -27,6 +50,24 @@ impl WireLabel {
         WireLabel(bytes)
     }
 
+    /// Generate a random delta whose point-and-permute bit is set
+    ///
+    /// The two labels of a wire then always have opposite
+    /// [`permute_bit`](Self::permute_bit)s.
+    pub fn random_delta(rng: &mut ChaCha12Rng) -> Self {
+        let mut delta = Self::random(rng);
+        delta.0[0] |= 1;
+        delta
+    }
+
+    /// Point-and-permute bit of the label: the least significant bit of its first byte
+    ///
+    /// It picks the row of a garbled table to decrypt and says nothing about
+    /// the bit the label stands for.
+    pub fn permute_bit(&self) -> bool {
+        self.0[0] & 1 == 1
+    }
+
     /// XOR this wire label with another wire label
     pub fn xor(&self, other: &WireLabel) -> WireLabel {
         let mut result = [0u8; 16];
@@ -42,15 +83,38 @@ impl WireLabel {
     }
 }
 
+impl SpillValue for WireLabel {
+    const SIZE: usize = 16;
+
+    fn write_to(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.0);
+    }
+
+    fn read_from(buf: &[u8]) -> Self {
+        WireLabel(buf.try_into().unwrap())
+    }
+}
+
 /// Wire labels for input and output wires (only label_0, label_1 = label_0 XOR delta)
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
 pub struct WireLabels {
     /// Input wire labels: wire_id -> label_0
-    pub input_labels: std::collections::HashMap<u32, WireLabel>,
+    #[serde(serialize_with = "serialize_sorted_map")]
+    pub input_labels: std::collections::HashMap<WireId, WireLabel>,
     /// Output wire labels: wire_id -> label_0
-    pub output_labels: std::collections::HashMap<u32, WireLabel>,
+    #[serde(serialize_with = "serialize_sorted_map")]
+    pub output_labels: std::collections::HashMap<WireId, WireLabel>,
     /// Global delta for computing label_1 = label_0 XOR delta
     pub delta: WireLabel,
+    /// Input wires owned by the garbler, in ascending order
+    ///
+    /// Their labels are sent to the evaluator directly; all other input
+    /// wires go through OT. Empty unless garbled with an
+    /// [`InputPartition`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub garbler_input_wires: Vec<WireId>,
+    /// Garbling run these labels belong to
+    pub session_id: SessionId,
 }
 
 impl WireLabels {
@@ -68,8 +132,119 @@ impl WireLabels {
         Ok(labels)
     }
 
+    /// Save wire labels in the compact binary format
+    ///
+    /// Layout: [`WIRE_LABELS_MAGIC`], the format version as a little-endian
+    /// `u32`, then the bincode-encoded labels. Much smaller and faster to
+    /// load than JSON for circuits with millions of input wires.
+    pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::with_capacity(BUFFER_SIZE, file);
+        writer.write_all(&WIRE_LABELS_MAGIC)?;
+        writer.write_all(&WIRE_LABELS_VERSION.to_le_bytes())?;
+        bincode::encode_into_std_write(self, &mut writer, bincode::config::standard())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load wire labels from the binary format
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        let mut reader = BufReader::with_capacity(BUFFER_SIZE, file);
+
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        if header[..4] != WIRE_LABELS_MAGIC {
+            return Err(GcError::invalid_format(
+                "binary wire labels file",
+                "bad magic bytes",
+            ));
+        }
+        let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if version != WIRE_LABELS_VERSION {
+            return Err(GcError::FormatVersionMismatch {
+                format: "wire labels",
+                found: version,
+                expected: WIRE_LABELS_VERSION,
+            });
+        }
+
+        let labels = bincode::decode_from_std_read(&mut reader, bincode::config::standard())?;
+        Ok(labels)
+    }
+
+    /// Load wire labels from either format, detected by the magic bytes
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        let bytes_read = File::open(path.as_ref())?.read(&mut magic)?;
+        if bytes_read == magic.len() && magic == WIRE_LABELS_MAGIC {
+            Self::load_binary(path)
+        } else {
+            Self::load_json(path)
+        }
+    }
+
+    /// Encode the garbler's own input bits as labels for the evaluator
+    ///
+    /// With an [`InputPartition`], `inputs` must give a bit for exactly the
+    /// wires in [`garbler_input_wires`](Self::garbler_input_wires). Without
+    /// one, any primary inputs may be encoded.
+    ///
+    /// # Arguments
+    /// * `inputs` - The garbler's plaintext input bits: wire_id -> bit
+    ///
+    /// # Returns
+    /// * `Ok(EncodedInputs)` - The label matching each bit
+    /// * `Err(GcError)` - A wire is not a garbler input, or a garbler input has no bit
+    pub fn encode_inputs(&self, inputs: &HashMap<WireId, bool>) -> Result<EncodedInputs> {
+        for &wire_id in &self.garbler_input_wires {
+            if !inputs.contains_key(&wire_id) {
+                return Err(GcError::missing_wire(
+                    wire_id,
+                    format!("No input bit given for garbler input wire {}", wire_id),
+                ));
+            }
+        }
+
+        let mut encoded = HashMap::with_capacity(inputs.len());
+        for (&wire_id, &bit_value) in inputs {
+            let &label_0 = self.input_labels.get(&wire_id).ok_or_else(|| {
+                GcError::missing_wire(wire_id, format!("Wire {} is not a primary input", wire_id))
+            })?;
+            if !self.garbler_input_wires.is_empty() && !self.is_garbler_input(wire_id) {
+                return Err(GcError::InvalidArgument(format!(
+                    "Wire {} is an evaluator input, not a garbler input",
+                    wire_id
+                )));
+            }
+            encoded.insert(wire_id, select_label(self, label_0, bit_value).label);
+        }
+
+        Ok(EncodedInputs {
+            inputs: encoded,
+            session_id: self.session_id,
+        })
+    }
+
+    /// Whether `wire` is an input owned by the garbler
+    pub fn is_garbler_input(&self, wire: WireId) -> bool {
+        self.garbler_input_wires.binary_search(&wire).is_ok()
+    }
+
+    /// Input wires owned by the evaluator, in ascending order
+    pub fn evaluator_input_wires(&self) -> Vec<WireId> {
+        let mut wires: Vec<WireId> = self
+            .input_labels
+            .keys()
+            .copied()
+            .filter(|&wire| !self.is_garbler_input(wire))
+            .collect();
+        wires.sort_unstable();
+        wires
+    }
+
     /// Get both labels for a wire
-    pub fn get_wire_labels(&self, wire_id: u32) -> Option<[WireLabel; 2]> {
+    pub fn get_wire_labels(&self, wire_id: WireId) -> Option<[WireLabel; 2]> {
         if let Some(&label_0) = self.input_labels.get(&wire_id) {
             Some([label_0, label_0.xor(&self.delta)])
         } else if let Some(&label_0) = self.output_labels.get(&wire_id) {
@@ -78,10 +253,33 @@ impl WireLabels {
             None
         }
     }
+
+    /// Bit that a label of an input wire stands for
+    ///
+    /// Only the garbler, who holds both labels of the wire, can tell.
+    ///
+    /// # Returns
+    /// * `Ok(bool)` - The bit `label` encodes
+    /// * `Err(GcError)` - The wire is not an input, or `label` is neither of its labels
+    pub fn input_bit(&self, wire_id: WireId, label: &WireLabel) -> Result<bool> {
+        let label_0 = self.input_labels.get(&wire_id).ok_or_else(|| {
+            GcError::missing_wire(wire_id, format!("Wire {} is not a primary input", wire_id))
+        })?;
+        if label == label_0 {
+            Ok(false)
+        } else if *label == label_0.xor(&self.delta) {
+            Ok(true)
+        } else {
+            Err(GcError::InvalidArgument(format!(
+                "Label for input wire {} matches neither of its labels",
+                wire_id
+            )))
+        }
+    }
 }
 
-/// Garbled truth table for an AND gate (4 ciphertexts, classic Yao)
-#[derive(Debug, Clone)]
+/// Garbled truth table for an AND gate (4 ciphertexts, ordered by point-and-permute bits)
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
 pub struct GarbledTable {
     /// 4 ciphertexts, each 16 bytes (128 bits)
     pub ciphertexts: [[u8; 16]; 4],
@@ -96,6 +294,183 @@ impl GarbledTable {
         }
         result
     }
+
+    /// Load garbled table from its 64-byte binary form
+    pub fn from_binary(data: &[u8; 64]) -> Self {
+        let mut ciphertexts = [[0u8; 16]; 4];
+        for (i, ciphertext) in ciphertexts.iter_mut().enumerate() {
+            ciphertext.copy_from_slice(&data[i * 16..(i + 1) * 16]);
+        }
+        GarbledTable { ciphertexts }
+    }
+}
+
+/// Garbling scheme that produced a set of garbled tables
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GarblingScheme {
+    /// 4-row Yao tables with free XOR and point-and-permute
+    #[default]
+    PointAndPermute,
+}
+
+impl GarblingScheme {
+    /// Identifier stored in the garbled tables file header
+    pub fn id(self) -> u32 {
+        match self {
+            GarblingScheme::PointAndPermute => 2,
+        }
+    }
+
+    /// Look up a scheme by its header identifier
+    pub fn from_id(id: u32) -> Option<Self> {
+        match id {
+            2 => Some(GarblingScheme::PointAndPermute),
+            _ => None,
+        }
+    }
+
+    /// Width of the wire labels the scheme garbles with
+    pub fn label_width(self) -> LabelWidth {
+        match self {
+            GarblingScheme::PointAndPermute => LabelWidth::Bits128,
+        }
+    }
+}
+
+/// Width of the wire labels, which sets the security parameter
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LabelWidth {
+    /// 16-byte labels, the width of [`WireLabel`]
+    #[default]
+    Bits128,
+}
+
+impl LabelWidth {
+    /// Look up a label width by its size in bits
+    ///
+    /// # Arguments
+    /// * `bits` - Label width in bits
+    ///
+    /// # Returns
+    /// * `Ok(LabelWidth)` - The width, if labels of that size are implemented
+    /// * `Err(GcError)` - No scheme garbles with labels of that size
+    pub fn from_bits(bits: u32) -> Result<Self> {
+        match bits {
+            128 => Ok(LabelWidth::Bits128),
+            _ => Err(GcError::InvalidArgument(format!(
+                "Unsupported label width of {} bits; only 128-bit labels are implemented",
+                bits
+            ))),
+        }
+    }
+
+    /// Size of a label in bits
+    pub fn bits(self) -> u32 {
+        match self {
+            LabelWidth::Bits128 => 128,
+        }
+    }
+}
+
+/// Header at the start of a garbled tables file
+///
+/// Lets the evaluator reject tables produced by a different scheme or for a
+/// different circuit or garbling run, and detect truncated or corrupt files.
+/// Layout (100 bytes, integers little-endian): magic `GCGT`, format version
+/// (u32), scheme id (u32), table count (u64), the [`CircuitDigest`] of the
+/// garbled gates, the [`SessionId`] of the run and the BLAKE3 checksum of the
+/// tables, all zeros if not recorded. The tables follow, plain or as a zstd
+/// stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TablesHeader {
+    /// Scheme the tables were garbled with
+    pub scheme: GarblingScheme,
+    /// Number of tables following the header
+    pub num_tables: u64,
+    /// Digest of the circuit's gates
+    pub circuit_digest: [u8; 32],
+    /// Garbling run that produced the tables
+    pub session_id: SessionId,
+    /// BLAKE3 checksum of the tables in their 64-byte binary form, which the
+    /// evaluator verifies once it has read the last table
+    pub tables_checksum: Option<[u8; 32]>,
+}
+
+impl TablesHeader {
+    /// Encode the header in its on-disk form
+    pub fn to_bytes(&self) -> [u8; GARBLED_TABLES_HEADER_SIZE] {
+        let mut bytes = [0u8; GARBLED_TABLES_HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&GARBLED_TABLES_MAGIC);
+        bytes[4..8].copy_from_slice(&GARBLED_TABLES_VERSION.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.scheme.id().to_le_bytes());
+        bytes[12..20].copy_from_slice(&self.num_tables.to_le_bytes());
+        bytes[20..52].copy_from_slice(&self.circuit_digest);
+        bytes[52..68].copy_from_slice(self.session_id.as_bytes());
+        if let Some(checksum) = &self.tables_checksum {
+            bytes[68..100].copy_from_slice(checksum);
+        }
+        bytes
+    }
+
+    /// Decode a header, validating magic bytes, version and scheme
+    pub fn from_bytes(bytes: &[u8; GARBLED_TABLES_HEADER_SIZE]) -> Result<Self> {
+        if bytes[0..4] != GARBLED_TABLES_MAGIC {
+            return Err(GcError::invalid_format(
+                "garbled tables file",
+                "bad magic bytes",
+            ));
+        }
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        if version != GARBLED_TABLES_VERSION {
+            return Err(GcError::FormatVersionMismatch {
+                format: "garbled tables",
+                found: version,
+                expected: GARBLED_TABLES_VERSION,
+            });
+        }
+        let scheme_id = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        let scheme = GarblingScheme::from_id(scheme_id).ok_or_else(|| {
+            GcError::invalid_format(
+                "garbled tables file",
+                format!("unsupported garbling scheme id {}", scheme_id),
+            )
+        })?;
+
+        Ok(Self {
+            scheme,
+            num_tables: u64::from_le_bytes(bytes[12..20].try_into().unwrap()),
+            circuit_digest: bytes[20..52].try_into().unwrap(),
+            session_id: SessionId::new(bytes[52..68].try_into().unwrap()),
+            tables_checksum: Some(bytes[68..100].try_into().unwrap())
+                .filter(|checksum| *checksum != [0; 32]),
+        })
+    }
+
+    /// Fail unless the tables were garbled with `scheme`
+    pub fn check_scheme(&self, scheme: GarblingScheme) -> Result<()> {
+        if self.scheme != scheme {
+            return Err(GcError::invalid_format(
+                "garbled tables file",
+                format!("garbled with {:?}, expected {:?}", self.scheme, scheme),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fail unless the tables were garbled for a circuit with this digest
+    pub fn check_circuit(&self, circuit_digest: &[u8; 32]) -> Result<()> {
+        if &self.circuit_digest != circuit_digest {
+            return Err(GcError::CircuitMismatch);
+        }
+        Ok(())
+    }
+
+    /// Fail unless the tables were produced in the given garbling session
+    ///
+    /// `artifact` names what the session id came from, e.g. "OT result".
+    pub fn check_session(&self, session_id: &SessionId, artifact: &str) -> Result<()> {
+        session_id.check(&self.session_id, artifact)
+    }
 }
 
 /// Result of garbling a Bristol circuit
@@ -105,6 +480,42 @@ pub struct GarblingResult {
     pub wire_labels: WireLabels,
     /// Garbled truth tables for AND gates
     pub garbled_tables: Vec<GarbledTable>,
+    /// Digest of the garbled circuit's gates (see [`CircuitDigest`])
+    pub circuit_digest: [u8; 32],
+    /// Scheme the tables were garbled with
+    pub scheme: GarblingScheme,
+    /// Statistics of the garbling run that produced this result
+    pub stats: GarblingStats,
+}
+
+/// Performance statistics of a single garbling run
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct GarblingStats {
+    /// Number of gates garbled
+    pub total_gates: u64,
+    /// Number of XOR gates (free, no table)
+    pub xor_gates: u64,
+    /// Number of AND gates
+    pub and_gates: u64,
+    /// Number of garbled tables handed to the sink
+    pub tables_written: u64,
+    /// Wall-clock time of the garbling in seconds
+    pub wall_time_secs: f64,
+    /// Garbling throughput in gates per second
+    pub gates_per_sec: f64,
+    /// Largest number of wire labels held in memory at once
+    pub peak_active_labels: u64,
+    /// Bytes of (uncompressed) garbled tables produced
+    pub table_bytes_written: u64,
+}
+
+impl GarblingStats {
+    /// Save statistics as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
 }
 
 impl GarblingResult {
@@ -114,13 +525,39 @@ impl GarblingResult {
         self.wire_labels.save_json(labels_path)?;
 
         // Save garbled tables as binary
-        let mut tables_data = Vec::new();
-        for table in &self.garbled_tables {
-            tables_data.extend_from_slice(&table.as_binary());
-        }
-        std::fs::write(tables_path, tables_data)?;
+        let mut writer = TableWriter::create(tables_path)?;
+        writer.write_tables(&self.garbled_tables)?;
+        writer.finish(&self.tables_header())
+    }
 
-        Ok(())
+    /// Save the complete garbling result with zstd-compressed garbled tables
+    ///
+    /// The evaluator detects the compressed form automatically and decodes the
+    /// tables on the fly.
+    pub fn save_compressed<P: AsRef<Path>>(
+        &self,
+        labels_path: P,
+        tables_path: P,
+        level: i32,
+    ) -> Result<()> {
+        // Save wire labels as JSON
+        self.wire_labels.save_json(labels_path)?;
+
+        // Stream garbled tables through the zstd encoder
+        let mut writer = CompressedTableWriter::create(tables_path, level)?;
+        writer.write_tables(&self.garbled_tables)?;
+        writer.finish(&self.tables_header())
+    }
+
+    /// Header describing the garbled tables of this result
+    pub fn tables_header(&self) -> TablesHeader {
+        TablesHeader {
+            scheme: self.scheme,
+            num_tables: self.garbled_tables.len() as u64,
+            circuit_digest: self.circuit_digest,
+            session_id: self.wire_labels.session_id,
+            tables_checksum: Some(tables_checksum(&self.garbled_tables)),
+        }
     }
 }
 
@@ -140,30 +577,42 @@ pub fn garbling_hash(input_labels: &[WireLabel]) -> [u8; 16] {
     result
 }
 
-/// Garble an AND gate using classic Yao (4 ciphertexts)
-fn garble_and_gate(
+/// Label the evaluator holds for the output of a constant (`EQ`) gate
+///
+/// Constants are public, so instead of sending a label the garbler derives
+/// the label of the constant's value from the wire ID, and the evaluator does
+/// the same. The label of the opposite value stays secret as it is offset by
+/// delta.
+pub fn constant_label(output_wire: WireId) -> WireLabel {
+    let mut wire_bytes = [0u8; 16];
+    wire_bytes[..8].copy_from_slice(&output_wire.to_le_bytes());
+    WireLabel::new(garbling_hash(&[WireLabel::new(wire_bytes)]))
+}
+
+/// Garble an AND gate into 4 ciphertexts ordered by point-and-permute bits
+///
+/// Row `2 * a + b` encrypts the output label under the input labels whose
+/// [`permute_bit`](WireLabel::permute_bit)s are `a` and `b`, so the evaluator
+/// finds its row without learning which bits its labels stand for.
+pub(crate) fn garble_and_gate(
     input_labels: &[[WireLabel; 2]; 2], // [input1_labels, input2_labels]
     output_labels: &[WireLabel; 2],     // [output_0, output_1]
 ) -> GarbledTable {
     let mut ciphertexts = [[0u8; 16]; 4];
 
-    // Truth table for AND: (0,0)->0, (0,1)->0, (1,0)->0, (1,1)->1
-    let truth_table = [
-        (0, 0, 0), // input1=0, input2=0 -> output=0
-        (0, 1, 0), // input1=0, input2=1 -> output=0
-        (1, 0, 0), // input1=1, input2=0 -> output=0
-        (1, 1, 1), // input1=1, input2=1 -> output=1
-    ];
+    for in1_bit in 0..2 {
+        for in2_bit in 0..2 {
+            let input_combo = [input_labels[0][in1_bit], input_labels[1][in2_bit]];
+            let row =
+                2 * input_combo[0].permute_bit() as usize + input_combo[1].permute_bit() as usize;
 
-    for (i, (in1_bit, in2_bit, out_bit)) in truth_table.iter().enumerate() {
-        let input_combo = [input_labels[0][*in1_bit], input_labels[1][*in2_bit]];
+            let key = garbling_hash(&input_combo);
+            let plaintext = output_labels[in1_bit & in2_bit].as_bytes();
 
-        let key = garbling_hash(&input_combo);
-        let plaintext = output_labels[*out_bit].as_bytes();
-
-        // XOR encryption: ciphertext = plaintext XOR key
-        for j in 0..16 {
-            ciphertexts[i][j] = plaintext[j] ^ key[j];
+            // XOR encryption: ciphertext = plaintext XOR key
+            for j in 0..16 {
+                ciphertexts[row][j] = plaintext[j] ^ key[j];
+            }
         }
     }
 
@@ -173,319 +622,779 @@ fn garble_and_gate(
 /// Garble a Bristol circuit using Yao's protocol with free XOR optimization
 /// Uses wire analysis data for memory-efficient label management
 ///
+/// Passing the report by value lets the garbler reuse its usage counts
+/// instead of copying them, which saves one byte per wire.
+///
+/// Runs without progress reporting; use [`GarblerBuilder`] to configure the
+/// backend, table sink or progress reporting.
+///
 /// Expected format:
 /// First line: `<num_gates> <num_wires>`
 /// Followed by gate lines in Bristol format
 ///
 /// # Arguments
 /// * `stream` - The line stream to process Bristol circuit
-/// * `wire_report` - Wire usage analysis for memory optimization
+/// * `wire_report` - Wire usage analysis for memory optimization, borrowed or owned
 /// * `seed_data` - 32 bytes of random seed for CSPRNG
 ///
 /// # Returns
 /// * `Ok(GarblingResult)` - Complete garbling with wire labels and garbled tables
-/// * `Err(anyhow::Error)` - Parse error or garbling error
-pub fn garble_circuit(
-    stream: &mut BufferedLineStream,
-    wire_report: &WireUsageReport,
+/// * `Err(GcError)` - Parse error or garbling error
+pub fn garble_circuit<'r, R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    wire_report: impl Into<Cow<'r, WireUsageReport>>,
     seed_data: &[u8; 32],
 ) -> Result<GarblingResult> {
-    // Parse and validate header line (but ignore values)
-    let header_line = stream
-        .next_line()
-        .ok_or_else(|| anyhow::anyhow!("Missing header line"))??;
-
-    let header_tokens: Vec<&str> = header_line.split_whitespace().collect();
-    if header_tokens.len() != 2 {
-        bail!(
-            "Invalid header: expected '<num_gates> <num_wires>', got: '{}'",
-            header_line
-        );
+    GarblerBuilder::new(*seed_data).garble(stream, wire_report)
+}
+
+/// Garble a Bristol circuit, offloading AND gate encryption to the given backend
+///
+/// Shorthand for [`GarblerBuilder`] with a backend and progress observer.
+///
+/// # Arguments
+/// * `stream` - The line stream to process Bristol circuit
+/// * `wire_report` - Wire usage analysis for memory optimization, borrowed or owned
+/// * `seed_data` - 32 bytes of random seed for CSPRNG
+/// * `backend` - Backend performing the per-AND-gate hashing and encryption
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(GarblingResult)` - Complete garbling with wire labels and garbled tables
+/// * `Err(GcError)` - Parse error, garbling error or backend error
+pub fn garble_circuit_with_backend<'r, B: GarblingBackend, R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    wire_report: impl Into<Cow<'r, WireUsageReport>>,
+    seed_data: &[u8; 32],
+    backend: &mut B,
+    progress: &dyn ProgressObserver,
+) -> Result<GarblingResult> {
+    GarblerBuilder::new(*seed_data)
+        .backend(backend)
+        .progress(progress)
+        .garble(stream, wire_report)
+}
+
+/// Garble a Bristol circuit, streaming garbled tables into a sink
+///
+/// Shorthand for [`GarblerBuilder`] with a backend, sink and progress
+/// observer. Only the input and output wire labels are returned.
+///
+/// # Arguments
+/// * `stream` - The line stream to process Bristol circuit
+/// * `wire_report` - Wire usage analysis for memory optimization, borrowed or owned
+/// * `seed_data` - 32 bytes of random seed for CSPRNG
+/// * `backend` - Backend performing the per-AND-gate hashing and encryption
+/// * `sink` - Receives garbled tables in circuit order
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(WireLabels)` - Input and output wire labels with delta
+/// * `Err(GcError)` - Parse error, garbling error, backend or sink error
+pub fn garble_circuit_to_sink<'r, B: GarblingBackend, R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    wire_report: impl Into<Cow<'r, WireUsageReport>>,
+    seed_data: &[u8; 32],
+    backend: &mut B,
+    sink: &mut dyn GarbledTableSink,
+    progress: &dyn ProgressObserver,
+) -> Result<WireLabels> {
+    let result = GarblerBuilder::new(*seed_data)
+        .backend(backend)
+        .sink(sink)
+        .progress(progress)
+        .garble(stream, wire_report)?;
+    Ok(result.wire_labels)
+}
+
+/// Garble a circuit held entirely in memory
+///
+/// Same algorithm as [`garble_circuit`], but the gates come from a slice
+/// instead of a Bristol file.
+///
+/// # Arguments
+/// * `gates` - Circuit gates in topological order
+/// * `wire_report` - Wire usage analysis for memory optimization, borrowed or owned
+/// * `seed_data` - 32 bytes of random seed for CSPRNG
+///
+/// # Returns
+/// * `Ok(GarblingResult)` - Complete garbling with wire labels and garbled tables
+/// * `Err(GcError)` - Missing wires or garbling error
+pub fn garble_circuit_in_memory<'r>(
+    gates: &[Gate],
+    wire_report: impl Into<Cow<'r, WireUsageReport>>,
+    seed_data: &[u8; 32],
+) -> Result<GarblingResult> {
+    GarblerBuilder::new(*seed_data).garble_gates(gates, wire_report)
+}
+
+/// Configuration of a garbling run
+///
+/// Starts from a seed with the defaults of [`garble_circuit`]: the
+/// [`CpuBackend`], tables collected in memory, no progress reporting and no
+/// memory limit. Override what you need, then call one of the `garble*`
+/// methods, which consume the builder:
+///
+/// ```no_run
+/// # use gc::error::Result;
+/// # use gc::garbler::GarblerBuilder;
+/// # use gc::sink::{GarbledTableSink, TableWriter};
+/// # use gc::stream::BufferedLineStream;
+/// # use gc::wire_analyzer::WireUsageReport;
+/// # fn run(stream: &mut BufferedLineStream, wire_report: &WireUsageReport) -> Result<()> {
+/// let mut sink = TableWriter::create("circuit.garbled")?;
+/// let result = GarblerBuilder::new([0x42; 32])
+///     .sink(&mut sink)
+///     .max_active_labels(1 << 24)
+///     .garble(stream, wire_report)?;
+/// result.wire_labels.save_json("circuit.labels.json")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct GarblerBuilder<'a> {
+    /// Seed of the CSPRNG generating delta and all labels
+    seed: [u8; 32],
+    /// Backend garbling AND gates, [`CpuBackend`] if unset
+    backend: Option<&'a mut dyn GarblingBackend>,
+    /// Destination of garbled tables, collected in memory if unset
+    sink: Option<&'a mut dyn GarbledTableSink>,
+    /// Receives progress updates
+    progress: &'a dyn ProgressObserver,
+    /// Fail once more than this many wire labels are live at once
+    max_active_labels: Option<usize>,
+    /// Spill wire labels to disk beyond this many bytes
+    max_memory: Option<u64>,
+    /// Which primary inputs belong to the garbler
+    input_partition: Option<&'a InputPartition>,
+    /// Session id recorded in the artifacts
+    session_id: SessionId,
+    /// Worker threads hashing AND gates, 0 to hash on the calling thread
+    hash_threads: usize,
+    /// Scheme recorded in the tables header
+    scheme: GarblingScheme,
+    /// Width of the wire labels, which the scheme must support
+    label_width: LabelWidth,
+}
+
+impl std::fmt::Debug for GarblerBuilder<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GarblerBuilder")
+            .field("custom_backend", &self.backend.is_some())
+            .field("custom_sink", &self.sink.is_some())
+            .field("max_active_labels", &self.max_active_labels)
+            .field("max_memory", &self.max_memory)
+            .field("input_partition", &self.input_partition)
+            .field("session_id", &self.session_id)
+            .field("hash_threads", &self.hash_threads)
+            .field("scheme", &self.scheme)
+            .field("label_width", &self.label_width)
+            .finish_non_exhaustive()
     }
+}
 
-    // Parse header values - use num_gates for progress bar
-    let num_gates: u32 = header_tokens[0]
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid num_gates: '{}'", header_tokens[0]))?;
-    let _num_wires: u32 = header_tokens[1]
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid num_wires: '{}'", header_tokens[1]))?;
+impl<'a> GarblerBuilder<'a> {
+    /// Start configuring a garbling run with the given 32-byte seed
+    pub fn new(seed: [u8; 32]) -> Self {
+        Self {
+            seed,
+            backend: None,
+            sink: None,
+            progress: &NoProgress,
+            max_active_labels: None,
+            max_memory: None,
+            input_partition: None,
+            session_id: SessionId::from_seed(&seed),
+            hash_threads: 0,
+            scheme: GarblingScheme::default(),
+            label_width: LabelWidth::default(),
+        }
+    }
 
-    // Initialize CSPRNG with provided seed
-    let mut rng = ChaCha12Rng::from_seed(*seed_data);
+    /// Backend performing the per-AND-gate hashing and encryption
+    pub fn backend(mut self, backend: &'a mut dyn GarblingBackend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
 
-    // Generate global delta for free XOR
-    let delta = WireLabel::random(&mut rng);
+    /// Stream garbled tables into `sink` instead of collecting them
+    ///
+    /// The [`GarblingResult`] returned by the `garble*` methods then has no
+    /// garbled tables.
+    pub fn sink(mut self, sink: &'a mut dyn GarbledTableSink) -> Self {
+        self.sink = Some(sink);
+        self
+    }
 
-    // Initialize usage counts for runtime tracking (clone from wire analysis)
-    let mut remaining_usage = wire_report.wire_usage_counts.clone();
+    /// Receive progress updates
+    pub fn progress(mut self, progress: &'a dyn ProgressObserver) -> Self {
+        self.progress = progress;
+        self
+    }
 
-    // Initialize active wire labels HashMap (only stores labels for live wires)
-    let mut active_wire_labels: std::collections::HashMap<u32, WireLabel> =
-        std::collections::HashMap::new();
+    /// Fail instead of holding more than `limit` wire labels at once
+    ///
+    /// Each label takes 16 bytes plus map overhead, so this bounds the
+    /// garbler's memory on circuits with unexpectedly many live wires.
+    pub fn max_active_labels(mut self, limit: usize) -> Self {
+        self.max_active_labels = Some(limit);
+        self
+    }
 
-    // Initialize primary input wires with random labels and collect them for final result
-    let mut input_labels = std::collections::HashMap::new();
-    for &input_wire_id in &wire_report.primary_input_wires {
-        let label_0 = WireLabel::random(&mut rng);
-        active_wire_labels.insert(input_wire_id, label_0);
-        input_labels.insert(input_wire_id, label_0); // Save for final result
+    /// Keep at most `bytes` worth of wire labels in memory
+    ///
+    /// Once the live labels outgrow the budget, the least recently used
+    /// ones are moved to an anonymous temp file (in `TMPDIR`) and read back
+    /// when a gate needs them. The output is the same as without a cap,
+    /// only slower. Unlike [`max_active_labels`](Self::max_active_labels)
+    /// this never fails for lack of memory.
+    pub fn max_memory(mut self, bytes: u64) -> Self {
+        self.max_memory = Some(bytes);
+        self
     }
 
-    // Process gates and generate garbled tables using streaming approach
-    let mut garbled_tables = Vec::new();
-    let mut _gate_counter = 0u32;
-    let mut line_number = 0;
+    /// Record which primary inputs belong to the garbler
+    ///
+    /// The garbler's inputs are listed in
+    /// [`WireLabels::garbler_input_wires`], so later stages send only the
+    /// evaluator's inputs through OT. Without a partition every input is
+    /// treated as the evaluator's.
+    pub fn input_partition(mut self, partition: &'a InputPartition) -> Self {
+        self.input_partition = Some(partition);
+        self
+    }
 
-    // Create progress bar for gate processing (use actual count from header)
-    let pb = ProgressBar::new(num_gates as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
-            .unwrap()
-            .progress_chars("##-"),
-    );
-    pb.set_message("Garbling circuit...");
-
-    // Process each gate as we read it (streaming approach - no memory accumulation)
-    while let Some(line_result) = stream.next_line() {
-        line_number += 1;
-        let line = line_result?;
+    /// Session id stored in the tables header and wire labels
+    ///
+    /// Defaults to [`SessionId::from_seed`] of the garbling seed.
+    pub fn session_id(mut self, session_id: SessionId) -> Self {
+        self.session_id = session_id;
+        self
+    }
 
-        if line.trim().is_empty() {
-            bail!("Empty line at line number {}", line_number);
-        }
+    /// Hash AND gates on `threads` worker threads
+    ///
+    /// Batches of AND gates are handed to the workers through a bounded
+    /// queue while the calling thread keeps parsing, and the tables are
+    /// written in circuit order, so the output is identical to hashing on
+    /// the calling thread. `0` (the default) disables the workers. Cannot
+    /// be combined with a custom [`backend`](Self::backend).
+    pub fn hash_threads(mut self, threads: usize) -> Self {
+        self.hash_threads = threads;
+        self
+    }
 
-        // Parse gate line directly using iterator (more efficient)
-        let mut tokens = line.split_whitespace();
+    /// Garble with `scheme`, recorded in the tables header
+    ///
+    /// Defaults to [`GarblingScheme::PointAndPermute`]. The evaluator can
+    /// insist on a scheme through [`EvaluationOptions::scheme`].
+    ///
+    /// [`EvaluationOptions::scheme`]: crate::evaluator::EvaluationOptions::scheme
+    pub fn scheme(mut self, scheme: GarblingScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
 
-        // Parse num_inputs and num_outputs
-        let num_inputs: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing num_inputs at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid num_inputs at line {}: '{}'", line_number, line)
-            })?;
+    /// Garble with labels of `width`
+    ///
+    /// Garbling fails unless the [`scheme`](Self::scheme) uses labels of
+    /// that width. Parse a width given in bits with
+    /// [`LabelWidth::from_bits`], which rejects widths no scheme implements.
+    pub fn label_width(mut self, width: LabelWidth) -> Self {
+        self.label_width = width;
+        self
+    }
 
-        let num_outputs: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing num_outputs at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid num_outputs at line {}: '{}'", line_number, line)
-            })?;
+    /// Garble a Bristol circuit using a wire usage analysis
+    ///
+    /// The circuit is streamed on the calling thread while AND gates are
+    /// queued and handed to the backend in batches of
+    /// [`GARBLING_BATCH_SIZE`]. Output labels of AND gates do not depend on
+    /// the garbled table, so queuing does not stall the streaming pass.
+    /// Tables are produced in circuit order.
+    pub fn garble<'r, R: Read>(
+        self,
+        stream: &mut BufferedLineStream<R>,
+        wire_report: impl Into<Cow<'r, WireUsageReport>>,
+    ) -> Result<GarblingResult> {
+        let (header, gates) = parse_circuit_stream(stream)?;
+        self.run(
+            gates,
+            header.num_gates,
+            WireTracking::Analyzed(wire_report.into()),
+        )
+    }
 
-        // Collect input wire IDs directly (no intermediate allocation)
-        let input_wires: Result<Vec<u32>> = (0..num_inputs)
-            .map(|i| {
-                let wire_id: u32 = tokens
-                    .next()
-                    .ok_or_else(|| {
-                        anyhow::anyhow!("Missing input wire {} at line {}", i, line_number)
-                    })?
-                    .parse()
-                    .map_err(|_| {
-                        anyhow::anyhow!("Invalid input wire ID at line {}: '{}'", line_number, line)
-                    })?;
-                Ok(wire_id)
-            })
-            .collect();
-        let input_wires = input_wires?;
-
-        // Collect output wire IDs directly (no intermediate allocation)
-        let output_wires: Result<Vec<u32>> = (0..num_outputs)
-            .map(|i| {
-                let wire_id: u32 = tokens
-                    .next()
-                    .ok_or_else(|| {
-                        anyhow::anyhow!("Missing output wire {} at line {}", i, line_number)
-                    })?
-                    .parse()
-                    .map_err(|_| {
-                        anyhow::anyhow!(
-                            "Invalid output wire ID at line {}: '{}'",
-                            line_number,
-                            line
-                        )
-                    })?;
-                Ok(wire_id)
-            })
-            .collect();
-        let output_wires = output_wires?;
+    /// Garble a Bristol circuit file without a precomputed wire analysis
+    ///
+    /// Makes a quick first pass over the circuit to derive the wire usage
+    /// counts (see [`analyze_wire_usage`]) and then garbles it as
+    /// [`garble`](Self::garble) does. The result is identical to garbling
+    /// with a saved wire analysis.
+    pub fn garble_file(self, circuit_path: &Path) -> Result<GarblingResult> {
+        let wire_report = analyze_wire_usage(
+            &mut BufferedLineStream::new(File::open(circuit_path)?),
+            self.progress,
+        )?;
+
+        self.garble(
+            &mut BufferedLineStream::new(File::open(circuit_path)?),
+            wire_report,
+        )
+    }
 
-        // Parse gate type (last token)
-        let gate_type = tokens.next().ok_or_else(|| {
-            anyhow::anyhow!("Missing gate type at line {}: '{}'", line_number, line)
-        })?;
+    /// Garble a Bristol circuit in a single pass, keeping every wire label
+    ///
+    /// Needs no wire analysis: wires read before any gate produces them
+    /// become primary inputs, and wires never read become primary outputs.
+    /// Since labels are never dropped, memory grows with the number of wires,
+    /// and input labels are drawn in a different order than with an
+    /// analysis, so the same seed gives different labels than
+    /// [`garble`](Self::garble).
+    pub fn garble_keep_all<R: Read>(
+        self,
+        stream: &mut BufferedLineStream<R>,
+    ) -> Result<GarblingResult> {
+        let (header, gates) = parse_circuit_stream(stream)?;
+        self.run(
+            gates,
+            header.num_gates,
+            WireTracking::KeepAll {
+                num_wires: header.num_wires,
+            },
+        )
+    }
 
-        // Validate no extra tokens
-        if tokens.next().is_some() {
-            bail!("Too many tokens at line {}: '{}'", line_number, line);
+    /// Input wire labels and delta that [`garble`](Self::garble) will use, without garbling
+    ///
+    /// Labels depend only on the seed and the primary inputs of the wire
+    /// analysis, so a garbler can run OT for the evaluator's inputs before
+    /// streaming the tables. The returned labels have no output labels.
+    ///
+    /// # Arguments
+    /// * `wire_report` - The wire usage analysis the circuit will be garbled with
+    ///
+    /// # Returns
+    /// * `Ok(WireLabels)` - Input labels, delta, garbler input wires and session id
+    /// * `Err(GcError)` - A range of the input partition covers no primary input
+    pub fn input_wire_labels(&self, wire_report: &WireUsageReport) -> Result<WireLabels> {
+        let mut rng = ChaCha12Rng::from_seed(self.seed);
+        let delta = WireLabel::random_delta(&mut rng);
+        let input_labels: HashMap<WireId, WireLabel> =
+            draw_input_labels(&mut rng, &wire_report.primary_input_wires).collect();
+        let garbler_input_wires = match self.input_partition {
+            Some(partition) => partition.garbler_input_wires(&wire_report.primary_input_wires)?,
+            None => Vec::new(),
+        };
+
+        Ok(WireLabels {
+            input_labels,
+            output_labels: HashMap::new(),
+            delta,
+            garbler_input_wires,
+            session_id: self.session_id,
+        })
+    }
+
+    /// Garble gates held in memory using a wire usage analysis
+    pub fn garble_gates<'r>(
+        self,
+        gates: &[Gate],
+        wire_report: impl Into<Cow<'r, WireUsageReport>>,
+    ) -> Result<GarblingResult> {
+        self.run(
+            gates.iter().copied().map(Ok),
+            gates.len() as u64,
+            WireTracking::Analyzed(wire_report.into()),
+        )
+    }
+
+    /// Core garbling loop over a sequence of gates
+    ///
+    /// With a wire analysis, keeps only live wire labels in memory, dropping
+    /// each label once the wire usage counts say it has no readers left.
+    /// Without one, wires read before being produced become primary inputs
+    /// and wires produced but never read become primary outputs. `num_gates`
+    /// is only used for progress reporting.
+    #[tracing::instrument(name = "garble", skip_all, fields(num_gates))]
+    fn run(
+        self,
+        gates: impl Iterator<Item = Result<Gate>>,
+        num_gates: u64,
+        mut wire_tracking: WireTracking<'_>,
+    ) -> Result<GarblingResult> {
+        if self.label_width != self.scheme.label_width() {
+            return Err(GcError::InvalidArgument(format!(
+                "{:?} garbles with {}-bit labels, not {}-bit ones",
+                self.scheme,
+                self.scheme.label_width().bits(),
+                self.label_width.bits()
+            )));
         }
+        let mut hashing_pool = match (self.hash_threads, &self.backend) {
+            (0, _) => None,
+            (_, Some(_)) => {
+                return Err(GcError::InvalidArgument(
+                    "Hashing threads cannot be combined with a custom garbling backend".into(),
+                ));
+            }
+            (threads, None) => Some(HashingPool::new(threads)),
+        };
+        let mut cpu_backend = CpuBackend;
+        let backend: &mut dyn GarblingBackend = match self.backend {
+            Some(backend) => backend,
+            None => &mut cpu_backend,
+        };
+        let mut collected_tables = Vec::new();
+        let mut checksum_sink = ChecksumSink::new(match self.sink {
+            Some(sink) => sink,
+            None => &mut collected_tables,
+        });
+        let sink: &mut dyn GarbledTableSink = &mut checksum_sink;
+        let progress = self.progress;
+        let start = Instant::now();
+
+        // Initialize CSPRNG with provided seed
+        let mut rng = ChaCha12Rng::from_seed(self.seed);
+
+        // Generate global delta for free XOR
+        let delta = WireLabel::random_delta(&mut rng);
+
+        // Initialize active wire labels (only stores labels for live wires)
+        let mut active_wire_labels: LabelStore<WireLabel> = LabelStore::new(self.max_memory)?;
+        let mut input_labels = std::collections::HashMap::new();
 
-        let gate_index: u32 = line_number - 1;
-        match gate_type {
-            "XOR" => {
-                // Free XOR: output = input1 XOR input2
-                if input_wires.len() != 2 || output_wires.len() != 1 {
-                    bail!(
-                        "XOR gate must have 2 inputs and 1 output at line {}",
-                        line_number
-                    );
+        // Usage counts for runtime tracking (taken from wire analysis), or a record
+        // of which wires were ever read when discovering inputs and outputs
+        let mut remaining_usage = None;
+        let mut wire_was_read = Vec::new();
+        match &mut wire_tracking {
+            WireTracking::Analyzed(wire_report) => {
+                remaining_usage = Some(take_usage_counts(wire_report));
+
+                // Initialize primary input wires with random labels and collect them for final result
+                for (input_wire_id, label_0) in
+                    draw_input_labels(&mut rng, &wire_report.primary_input_wires)
+                {
+                    active_wire_labels.insert(input_wire_id, label_0)?;
+                    input_labels.insert(input_wire_id, label_0); // Save for final result
                 }
 
-                let input1_label_0 = active_wire_labels.get(&input_wires[0]).ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "Input wire {} not found at line {}",
-                        input_wires[0],
-                        line_number
-                    )
-                })?;
-                let input2_label_0 = active_wire_labels.get(&input_wires[1]).ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "Input wire {} not found at line {}",
-                        input_wires[1],
-                        line_number
-                    )
-                })?;
-
-                // For XOR: output_0 = input1_0 XOR input2_0
-                let output_label_0 = input1_label_0.xor(input2_label_0);
-
-                // Add output wire label to active set
-                active_wire_labels.insert(output_wires[0], output_label_0);
-
-                // Process input wires: decrement usage and remove if no longer needed
-                for &input_wire in &input_wires {
-                    if remaining_usage[input_wire as usize] > 0 {
-                        // Wires with count 255 are never decremented (permanent wires)
-                        if remaining_usage[input_wire as usize] < 255 {
-                            remaining_usage[input_wire as usize] -= 1;
-                        }
-
-                        // Remove wire label from active set if no longer needed
-                        if remaining_usage[input_wire as usize] == 0 {
-                            active_wire_labels.remove(&input_wire);
-                        }
-                    }
+                if let Some(limit) = self.max_active_labels
+                    && active_wire_labels.len() > limit
+                {
+                    return Err(GcError::LimitExceeded(format!(
+                        "Active wire labels exceed the limit of {} with {} primary inputs",
+                        limit,
+                        active_wire_labels.len()
+                    )));
                 }
             }
-            "AND" => {
-                // Garbled AND gate with 4 ciphertexts
-                if input_wires.len() != 2 || output_wires.len() != 1 {
-                    bail!(
-                        "AND gate must have 2 inputs and 1 output at line {}",
-                        line_number
-                    );
+            WireTracking::KeepAll { num_wires } => wire_was_read = vec![false; *num_wires as usize],
+        }
+
+        // Process gates and generate garbled tables using streaming approach
+        let mut num_tables = 0usize;
+        let mut pending_and_gates: Vec<AndGateJob> = Vec::with_capacity(GARBLING_BATCH_SIZE);
+        let mut gate_counter = 0u64;
+        let mut and_gates = 0u64;
+        let mut xor_gates = 0u64;
+        let mut peak_active_labels = active_wire_labels.len();
+        let mut circuit_digest = CircuitDigest::new();
+
+        // Create progress bar for gate processing (use actual count from header)
+        progress.start(Some(num_gates), "Garbling circuit...");
+
+        for gate in gates {
+            let gate = gate?;
+            circuit_digest.update(&gate);
+            let gate_index = gate_counter;
+            gate_counter += 1;
+
+            if let &WireTracking::KeepAll { num_wires } = &wire_tracking {
+                for wire_id in gate.input_wires().chain([gate.output_wire]) {
+                    if wire_id >= num_wires {
+                        return Err(GcError::InvalidCircuit(format!(
+                            "Wire ID {} exceeds num_wires {} at gate {}",
+                            wire_id, num_wires, gate_index
+                        )));
+                    }
+                }
+                if input_labels.contains_key(&gate.output_wire) {
+                    return Err(GcError::InvalidCircuit(format!(
+                        "Wire {} is produced at gate {} after being used as an input",
+                        gate.output_wire, gate_index
+                    )));
                 }
 
-                let input1_label_0 = active_wire_labels.get(&input_wires[0]).ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "Input wire {} not found at line {}",
-                        input_wires[0],
-                        line_number
-                    )
-                })?;
-                let input2_label_0 = active_wire_labels.get(&input_wires[1]).ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "Input wire {} not found at line {}",
-                        input_wires[1],
-                        line_number
+                // Wires read before any gate produced them are primary inputs
+                for input_wire in gate.input_wires() {
+                    wire_was_read[input_wire as usize] = true;
+                    if let Entry::Vacant(entry) = input_labels.entry(input_wire)
+                        && active_wire_labels.peek(input_wire)?.is_none()
+                    {
+                        let label_0 = WireLabel::random(&mut rng);
+                        active_wire_labels.insert(input_wire, label_0)?;
+                        entry.insert(label_0);
+                    }
+                }
+            }
+
+            let mut label_0 = |wire_id: WireId| -> Result<WireLabel> {
+                active_wire_labels.get(wire_id)?.ok_or_else(|| {
+                    GcError::missing_wire(
+                        wire_id,
+                        format!(
+                            "Input wire {} not found at gate {}; no earlier gate writes it, \
+                             so the gates may be out of topological order",
+                            wire_id, gate_index
+                        ),
                     )
-                })?;
-
-                // Compute both labels for inputs
-                let input1_labels = [*input1_label_0, input1_label_0.xor(&delta)];
-                let input2_labels = [*input2_label_0, input2_label_0.xor(&delta)];
-
-                // Generate output labels
-                let output_label_0 = WireLabel::random(&mut rng);
-                let output_label_1 = output_label_0.xor(&delta);
-                let output_labels = [output_label_0, output_label_1];
-
-                // Create garbled table
-                let input_label_pairs = [input1_labels, input2_labels];
-                let garbled_table = garble_and_gate(&input_label_pairs, &output_labels);
-                garbled_tables.push(garbled_table);
-
-                // Add output wire label to active set
-                active_wire_labels.insert(output_wires[0], output_label_0);
-                _gate_counter += 1;
-
-                // Process input wires: decrement usage and remove if no longer needed
-                for &input_wire in &input_wires {
-                    if remaining_usage[input_wire as usize] > 0 {
-                        // Wires with count 255 are never decremented (permanent wires)
-                        if remaining_usage[input_wire as usize] < 255 {
-                            remaining_usage[input_wire as usize] -= 1;
-                        }
-
-                        // Remove wire label from active set if no longer needed
-                        if remaining_usage[input_wire as usize] == 0 {
-                            active_wire_labels.remove(&input_wire);
-                        }
+                })
+            };
+
+            let output_label_0 = match gate.gate_type {
+                // Free XOR: output_0 = input1_0 XOR input2_0
+                GateType::Xor => {
+                    xor_gates += 1;
+                    label_0(gate.input_wire_1)?.xor(&label_0(gate.input_wire_2)?)
+                }
+                // Garbled AND gate with 4 ciphertexts
+                GateType::And => {
+                    and_gates += 1;
+                    let input1_label_0 = label_0(gate.input_wire_1)?;
+                    let input2_label_0 = label_0(gate.input_wire_2)?;
+
+                    // Compute both labels for inputs
+                    let input1_labels = [input1_label_0, input1_label_0.xor(&delta)];
+                    let input2_labels = [input2_label_0, input2_label_0.xor(&delta)];
+
+                    // Generate output labels
+                    let output_label_0 = WireLabel::random(&mut rng);
+                    let output_label_1 = output_label_0.xor(&delta);
+
+                    // Queue the gate for the backend, flushing full batches
+                    pending_and_gates.push(AndGateJob {
+                        input_labels: [input1_labels, input2_labels],
+                        output_labels: [output_label_0, output_label_1],
+                    });
+                    if pending_and_gates.len() == GARBLING_BATCH_SIZE {
+                        num_tables += pending_and_gates.len();
+                        let jobs = std::mem::replace(
+                            &mut pending_and_gates,
+                            Vec::with_capacity(GARBLING_BATCH_SIZE),
+                        );
+                        flush_and_gates(jobs, hashing_pool.as_mut(), backend, sink)?;
                     }
+
+                    output_label_0
                 }
+                // Copy: the output aliases the input's labels
+                GateType::Buf => label_0(gate.input_wire_1)?,
+                // Constant: the evaluator derives the label of `value` itself
+                GateType::Const(value) => {
+                    let public_label = constant_label(gate.output_wire);
+                    if value {
+                        public_label.xor(&delta)
+                    } else {
+                        public_label
+                    }
+                }
+            };
+
+            // Add output wire label to active set
+            active_wire_labels.insert(gate.output_wire, output_label_0)?;
+            peak_active_labels = peak_active_labels.max(active_wire_labels.len());
+
+            // Process input wires: decrement usage and remove if no longer needed
+            // Without usage counts every label is kept
+            if let Some(remaining_usage) = &mut remaining_usage {
+                for input_wire in gate.input_wires() {
+                    // Remove wire label from active set if no longer needed
+                    if remaining_usage.release(input_wire) {
+                        active_wire_labels.remove(input_wire);
+                    }
+                }
+            }
+
+            if let Some(limit) = self.max_active_labels
+                && active_wire_labels.len() > limit
+            {
+                return Err(GcError::LimitExceeded(format!(
+                    "Active wire labels exceed the limit of {} at gate {}",
+                    limit, gate_index
+                )));
             }
-            _ => {
-                bail!(
-                    "Unsupported gate type: {} at line {}",
-                    gate_type,
-                    line_number
-                );
+
+            // Update progress bar periodically for better performance
+            if gate_index.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+                progress.update(gate_index);
+                trace_throughput("garble", gate_index, start);
             }
         }
 
-        // Update progress bar periodically for better performance
-        if gate_index.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
-            pb.set_position(gate_index as u64);
-            // Avoid string allocation - use static message
-            pb.set_message("Garbling circuit...");
+        // Flush any remaining queued AND gates
+        if !pending_and_gates.is_empty() {
+            num_tables += pending_and_gates.len();
+            flush_and_gates(pending_and_gates, hashing_pool.as_mut(), backend, sink)?;
         }
-    }
+        if let Some(hashing_pool) = hashing_pool {
+            hashing_pool.finish(sink)?;
+        }
+        let circuit_digest = circuit_digest.finalize();
+        // Stamped by the checksum sink
+        sink.finish(&TablesHeader {
+            scheme: self.scheme,
+            num_tables: num_tables as u64,
+            circuit_digest,
+            session_id: self.session_id,
+            tables_checksum: None,
+        })?;
 
-    // Finish progress bar
-    pb.finish_with_message(format!(
-        "✓ Garbled {} gates, {} AND tables generated",
-        line_number,
-        garbled_tables.len()
-    ));
+        let wall_time_secs = start.elapsed().as_secs_f64();
+        active_wire_labels.trace_spill_stats("garble");
+        tracing::info!(
+            gates = gate_counter,
+            and_tables = num_tables,
+            wall_time_secs,
+            "garbling finished"
+        );
 
-    // Collect output wire labels from remaining active wires (should be primary outputs)
-    let mut output_labels = std::collections::HashMap::new();
-    for &output_wire_id in &wire_report.primary_output_wires {
-        let label_0 = active_wire_labels.get(&output_wire_id).ok_or_else(|| {
-            anyhow::anyhow!("Output wire {} not found in active labels", output_wire_id)
-        })?;
-        output_labels.insert(output_wire_id, *label_0);
+        // Finish progress bar
+        progress.finish(&format!(
+            "✓ Garbled {} gates, {} AND tables generated",
+            gate_counter, num_tables
+        ));
+
+        // Collect output wire labels from remaining active wires (should be primary outputs)
+        let mut output_labels = std::collections::HashMap::new();
+        match &wire_tracking {
+            WireTracking::Analyzed(wire_report) => {
+                for &output_wire_id in &wire_report.primary_output_wires {
+                    let label_0 = active_wire_labels.peek(output_wire_id)?.ok_or_else(|| {
+                        GcError::missing_wire(
+                            output_wire_id,
+                            format!("Output wire {} not found in active labels", output_wire_id),
+                        )
+                    })?;
+                    output_labels.insert(output_wire_id, label_0);
+                }
+            }
+            WireTracking::KeepAll { .. } => {
+                // Wires produced by a gate but never read are primary outputs
+                for (wire_id, label_0) in active_wire_labels.entries()? {
+                    if !wire_was_read[wire_id as usize] {
+                        output_labels.insert(wire_id, label_0);
+                    }
+                }
+            }
+        }
+
+        let mut primary_inputs: Vec<WireId> = input_labels.keys().copied().collect();
+        primary_inputs.sort_unstable();
+        let garbler_input_wires = match self.input_partition {
+            Some(partition) => partition.garbler_input_wires(&primary_inputs)?,
+            None => Vec::new(),
+        };
+
+        Ok(GarblingResult {
+            wire_labels: WireLabels {
+                input_labels,
+                output_labels,
+                delta,
+                garbler_input_wires,
+                session_id: self.session_id,
+            },
+            garbled_tables: collected_tables,
+            circuit_digest,
+            scheme: self.scheme,
+            stats: GarblingStats {
+                total_gates: gate_counter,
+                xor_gates,
+                and_gates,
+                tables_written: num_tables as u64,
+                wall_time_secs,
+                gates_per_sec: if wall_time_secs > 0.0 {
+                    gate_counter as f64 / wall_time_secs
+                } else {
+                    0.0
+                },
+                peak_active_labels: peak_active_labels as u64,
+                table_bytes_written: (num_tables * GARBLED_TABLE_SIZE) as u64,
+            },
+        })
+    }
+}
+
+/// Garble a batch of AND gates and hand the tables to `sink`
+///
+/// With a hashing pool the batch is queued and its tables are written once
+/// all earlier batches are done; otherwise `backend` garbles it right away.
+fn flush_and_gates(
+    jobs: Vec<AndGateJob>,
+    hashing_pool: Option<&mut HashingPool>,
+    backend: &mut dyn GarblingBackend,
+    sink: &mut dyn GarbledTableSink,
+) -> Result<()> {
+    match hashing_pool {
+        Some(hashing_pool) => hashing_pool.submit(jobs, sink),
+        None => sink.write_tables(&garble_batch(backend, &jobs)?),
     }
+}
+
+/// Parse the header of a Bristol circuit and return its gates as a lazy iterator
+///
+/// Gates are parsed as they are read (streaming approach - no memory
+/// accumulation), so parse errors surface from the iterator.
+fn parse_circuit_stream<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+) -> Result<(CircuitHeader, impl Iterator<Item = Result<Gate>> + '_)> {
+    let header = read_header(stream)?;
+
+    let mut line_number = 0;
+    let gates = std::iter::from_fn(move || {
+        let line_result = stream.next_line()?;
+        line_number += 1;
+        Some(
+            line_result
+                .map_err(GcError::from)
+                .and_then(|line| parse_gate_line(line, line_number)),
+        )
+    });
+
+    Ok((header, gates))
+}
 
-    let wire_labels = WireLabels {
-        input_labels,
-        output_labels,
-        delta,
-    };
+/// Draw `label_0` of every primary input, in the order the garbler does
+///
+/// Called right after delta is drawn, so the labels only depend on the seed.
+fn draw_input_labels<'a>(
+    rng: &'a mut ChaCha12Rng,
+    primary_input_wires: &'a [WireId],
+) -> impl Iterator<Item = (WireId, WireLabel)> + 'a {
+    primary_input_wires
+        .iter()
+        .map(|&input_wire_id| (input_wire_id, WireLabel::random(rng)))
+}
 
-    Ok(GarblingResult {
-        wire_labels,
-        garbled_tables,
-    })
+/// How the garbling core learns which wires are primary inputs and outputs
+#[derive(Debug, Clone)]
+enum WireTracking<'a> {
+    /// From a wire usage analysis; labels are dropped after their last use
+    Analyzed(Cow<'a, WireUsageReport>),
+    /// Discovered during the pass; every label is kept until the end
+    KeepAll {
+        /// Number of wires from the circuit header
+        num_wires: u64,
+    },
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::wire_analyzer::analyze_wire_usage;
+    use crate::progress::NoProgress;
+    use crate::test_util::create_test_file;
+    use crate::wire_analyzer::{UsageCounts, analyze_wire_usage};
     use std::fs::File;
-    use std::io::Write;
-
-    fn create_test_file(content: &str) -> Result<tempfile::NamedTempFile> {
-        let mut temp_file = tempfile::NamedTempFile::new()?;
-        temp_file.write_all(content.as_bytes())?;
-        temp_file.flush()?;
-        Ok(temp_file)
-    }
 
     fn create_test_seed() -> [u8; 32] {
         [0x42; 32] // Fixed seed for reproducible tests
@@ -496,7 +1405,7 @@ mod tests {
         let temp_file = create_test_file(circuit_data)?;
         let file = File::open(temp_file.path())?;
         let mut stream = BufferedLineStream::new(file);
-        analyze_wire_usage(&mut stream)
+        analyze_wire_usage(&mut stream, &NoProgress)
     }
 
     #[test]
@@ -551,15 +1460,17 @@ mod tests {
         let label_0 = WireLabel::new([0x01; 16]);
         let delta = WireLabel::new([0xFF; 16]);
 
-        input_labels.insert(42u32, label_0);
+        input_labels.insert(42u64, label_0);
 
         let wire_labels = WireLabels {
             input_labels,
             output_labels: std::collections::HashMap::new(),
             delta,
+            garbler_input_wires: Vec::new(),
+            session_id: SessionId::default(),
         };
 
-        let both_labels = wire_labels.get_wire_labels(42u32).unwrap();
+        let both_labels = wire_labels.get_wire_labels(42u64).unwrap();
         assert_eq!(both_labels[0], label_0);
         assert_eq!(both_labels[1], label_0.xor(&delta));
     }
@@ -570,9 +1481,11 @@ mod tests {
             input_labels: std::collections::HashMap::new(),
             output_labels: std::collections::HashMap::new(),
             delta: WireLabel::new([0x00; 16]),
+            garbler_input_wires: Vec::new(),
+            session_id: SessionId::default(),
         };
 
-        assert!(wire_labels.get_wire_labels(123u32).is_none());
+        assert!(wire_labels.get_wire_labels(123u64).is_none());
     }
 
     #[test]
@@ -616,9 +1529,13 @@ mod tests {
         // Should produce 4 ciphertexts
         assert_eq!(table.ciphertexts.len(), 4);
 
-        // Each ciphertext should be 16 bytes
-        for ciphertext in &table.ciphertexts {
-            assert_eq!(ciphertext.len(), 16);
+        // The row picked by the labels' permute bits decrypts to the AND of their bits
+        for (in1_bit, in2_bit) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let labels = [input1_labels[in1_bit], input2_labels[in2_bit]];
+            let row = 2 * labels[0].permute_bit() as usize + labels[1].permute_bit() as usize;
+            let key = garbling_hash(&labels);
+            let plaintext: [u8; 16] = std::array::from_fn(|i| table.ciphertexts[row][i] ^ key[i]);
+            assert_eq!(WireLabel::new(plaintext), output_labels[in1_bit & in2_bit]);
         }
     }
 
@@ -729,7 +1646,7 @@ mod tests {
             intermediate_wires: 0,
             primary_outputs: 1,
             missing_wires_count: 0,
-            wire_usage_counts: vec![1, 1, 0],
+            wire_usage_counts: UsageCounts::Dense(vec![1, 1, 0]),
             primary_input_wires: vec![0, 1],
             primary_output_wires: vec![2],
         };
@@ -755,7 +1672,7 @@ mod tests {
             intermediate_wires: 0,
             primary_outputs: 0,
             missing_wires_count: 0,
-            wire_usage_counts: vec![],
+            wire_usage_counts: UsageCounts::Dense(vec![]),
             primary_input_wires: vec![],
             primary_output_wires: vec![],
         };
@@ -764,8 +1681,9 @@ mod tests {
         let result = garble_circuit(&mut stream, &wire_report, &seed);
 
         assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("Missing header line"));
+        let error = result.unwrap_err();
+        assert!(matches!(error, GcError::Parse { line: 1, .. }));
+        assert!(error.to_string().contains("Missing header"));
     }
 
     #[test]
@@ -798,7 +1716,7 @@ mod tests {
             intermediate_wires: 0,
             primary_outputs: 1,
             missing_wires_count: 0,
-            wire_usage_counts: vec![1, 1, 0],
+            wire_usage_counts: UsageCounts::Dense(vec![1, 1, 0]),
             primary_input_wires: vec![0, 1],
             primary_output_wires: vec![2],
         };
@@ -807,8 +1725,9 @@ mod tests {
         let result = garble_circuit(&mut stream, &wire_report, &seed);
 
         assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("Invalid num_inputs at line "));
+        let error = result.unwrap_err();
+        assert!(matches!(error, GcError::Parse { .. }));
+        assert!(error.to_string().starts_with("Invalid num_inputs"));
     }
 
     #[test]
@@ -824,7 +1743,7 @@ mod tests {
             intermediate_wires: 0,
             primary_outputs: 1,
             missing_wires_count: 0,
-            wire_usage_counts: vec![1, 1, 0],
+            wire_usage_counts: UsageCounts::Dense(vec![1, 1, 0]),
             primary_input_wires: vec![0, 1],
             primary_output_wires: vec![2],
         };
@@ -833,8 +1752,9 @@ mod tests {
         let result = garble_circuit(&mut stream, &wire_report, &seed);
 
         assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("Empty line at line number "));
+        let error = result.unwrap_err();
+        assert!(matches!(error, GcError::Parse { .. }));
+        assert!(error.to_string().starts_with("Empty line at line "));
     }
 
     #[test]
@@ -852,6 +1772,8 @@ mod tests {
             input_labels,
             output_labels,
             delta,
+            garbler_input_wires: Vec::new(),
+            session_id: SessionId::default(),
         };
 
         // Test save and load
@@ -874,6 +1796,20 @@ mod tests {
             assert_eq!(loaded_labels.input_labels.get(&wire_id), Some(&label));
         }
 
+        // Binary format, and detection of both formats
+        let binary_file = tempfile::NamedTempFile::new()?;
+        wire_labels.save_binary(binary_file.path())?;
+        for loaded_labels in [
+            WireLabels::load_binary(binary_file.path())?,
+            WireLabels::load(binary_file.path())?,
+            WireLabels::load(temp_file.path())?,
+        ] {
+            assert_eq!(loaded_labels.input_labels, wire_labels.input_labels);
+            assert_eq!(loaded_labels.output_labels, wire_labels.output_labels);
+            assert_eq!(loaded_labels.delta, wire_labels.delta);
+        }
+        assert!(WireLabels::load_binary(temp_file.path()).is_err());
+
         Ok(())
     }
 
@@ -903,8 +1839,23 @@ mod tests {
         let tables_size = std::fs::metadata(tables_file.path())?.len();
 
         assert!(labels_size > 0);
-        // Should have 1 AND gate = 64 bytes
-        assert_eq!(tables_size, 64);
+        // Should have the header plus 1 AND gate = 100 + 64 bytes
+        assert_eq!(tables_size, 100 + 64);
+
+        // The header identifies the circuit
+        let tables = std::fs::read(tables_file.path())?;
+        let header = TablesHeader::from_bytes(tables[..100].try_into().unwrap())?;
+        let circuit = crate::gate::Circuit::parse(circuit_data)?;
+        assert_eq!(header.scheme, GarblingScheme::PointAndPermute);
+        assert_eq!(header.num_tables, 1);
+        assert_eq!(
+            header.circuit_digest,
+            CircuitDigest::of_gates(&circuit.gates)
+        );
+        assert_eq!(
+            header.tables_checksum,
+            Some(*blake3::hash(&tables[100..]).as_bytes())
+        );
 
         Ok(())
     }
@@ -969,4 +1920,157 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_garbler_builder() -> Result<()> {
+        let circuit_data = "3 7\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n2 1 4 5 6 AND\n";
+        let wire_report = create_wire_report(circuit_data)?;
+        let seed = create_test_seed();
+        let temp_file = create_test_file(circuit_data)?;
+
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+        let expected = garble_circuit(&mut stream, &wire_report, &seed)?;
+
+        // Streaming into a sink yields the same tables and leaves the result empty
+        let mut tables = Vec::new();
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+        let result = GarblerBuilder::new(seed)
+            .backend(&mut CpuBackend)
+            .sink(&mut tables)
+            .progress(&NoProgress)
+            .max_active_labels(8)
+            .garble(&mut stream, &wire_report)?;
+        assert_eq!(result.wire_labels.delta, expected.wire_labels.delta);
+        assert!(result.garbled_tables.is_empty());
+        assert_eq!(tables.len(), expected.garbled_tables.len());
+        for (table, expected_table) in tables.iter().zip(&expected.garbled_tables) {
+            assert_eq!(table.ciphertexts, expected_table.ciphertexts);
+        }
+
+        // Statistics count the gates and the peak of four inputs plus one output
+        let stats = &result.stats;
+        assert_eq!(stats.total_gates, 3);
+        assert_eq!(stats.xor_gates, 1);
+        assert_eq!(stats.and_gates, 2);
+        assert_eq!(stats.tables_written, 2);
+        assert_eq!(stats.table_bytes_written, 128);
+        assert_eq!(stats.peak_active_labels, 5);
+
+        // A partition marks the garbler's inputs in the labels
+        let partition = InputPartition::parse("0..2")?;
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+        let result = GarblerBuilder::new(seed)
+            .input_partition(&partition)
+            .garble(&mut stream, &wire_report)?;
+        assert_eq!(result.wire_labels.garbler_input_wires, vec![0, 1]);
+        assert_eq!(result.wire_labels.evaluator_input_wires(), vec![3, 5]);
+
+        // Four input labels are live before the first gate
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+        let error_msg = GarblerBuilder::new(seed)
+            .max_active_labels(3)
+            .garble(&mut stream, &wire_report)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Active wire labels exceed the limit of 3"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_label_width() -> Result<()> {
+        assert_eq!(LabelWidth::from_bits(128)?, LabelWidth::Bits128);
+        assert_eq!(LabelWidth::Bits128.bits(), 128);
+        assert_eq!(
+            GarblingScheme::PointAndPermute.label_width(),
+            LabelWidth::Bits128
+        );
+        for bits in [0, 64, 80, 256] {
+            let error = LabelWidth::from_bits(bits).unwrap_err();
+            assert!(matches!(error, GcError::InvalidArgument(_)));
+            assert!(
+                error
+                    .to_string()
+                    .contains(&format!("Unsupported label width of {} bits", bits))
+            );
+        }
+
+        // The configured scheme ends up in the result and its tables header
+        let circuit_data = "1 3\n2 1 0 1 2 AND\n";
+        let wire_report = create_wire_report(circuit_data)?;
+        let temp_file = create_test_file(circuit_data)?;
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+        let result = GarblerBuilder::new(create_test_seed())
+            .scheme(GarblingScheme::PointAndPermute)
+            .label_width(LabelWidth::from_bits(128)?)
+            .garble(&mut stream, &wire_report)?;
+        assert_eq!(result.scheme, GarblingScheme::PointAndPermute);
+        let header = result.tables_header();
+        assert_eq!(header.scheme, GarblingScheme::PointAndPermute);
+        header.check_scheme(GarblingScheme::PointAndPermute)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_garble_without_wire_analysis() -> Result<()> {
+        use crate::evaluator::{EvaluationOptions, evaluate_circuit_in_memory};
+        use crate::gate::Circuit;
+        use crate::ot_simulation::select_inputs;
+
+        let circuit_data = "3 7\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n2 1 4 5 6 AND\n";
+        let wire_report = create_wire_report(circuit_data)?;
+        let seed = create_test_seed();
+        let temp_file = create_test_file(circuit_data)?;
+
+        // Analysis on the fly matches a saved analysis exactly
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+        let analyzed = garble_circuit(&mut stream, &wire_report, &seed)?;
+        let from_file = GarblerBuilder::new(seed).garble_file(temp_file.path())?;
+        assert_eq!(from_file.wire_labels.delta, analyzed.wire_labels.delta);
+        assert_eq!(
+            from_file.garbled_tables.len(),
+            analyzed.garbled_tables.len()
+        );
+
+        // Keeping all labels discovers the same inputs and outputs
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+        let GarblingResult {
+            wire_labels: labels,
+            garbled_tables: tables,
+            ..
+        } = GarblerBuilder::new(seed).garble_keep_all(&mut stream)?;
+        let mut input_wires: Vec<WireId> = labels.input_labels.keys().copied().collect();
+        input_wires.sort_unstable();
+        assert_eq!(input_wires, wire_report.primary_input_wires);
+        let output_wires: Vec<WireId> = labels.output_labels.keys().copied().collect();
+        assert_eq!(output_wires, wire_report.primary_output_wires);
+
+        // ...and its tables evaluate correctly
+        let inputs = std::collections::HashMap::from([(0, true), (1, true), (3, false), (5, true)]);
+        let ot_result = select_inputs(&labels, &inputs)?;
+        let circuit = Circuit::parse(circuit_data)?;
+        let result = evaluate_circuit_in_memory(
+            &circuit.gates,
+            &wire_report,
+            &ot_result,
+            &tables,
+            &EvaluationOptions::default(),
+        )?;
+        assert_eq!(
+            result.output_results[&6].label,
+            labels.output_labels[&6].xor(&labels.delta)
+        );
+
+        // Producing a wire after it was read as an input is rejected
+        let bad_file = create_test_file("2 4\n2 1 0 1 2 XOR\n2 1 2 3 0 AND\n")?;
+        let mut stream = BufferedLineStream::new(File::open(bad_file.path())?);
+        let error_msg = GarblerBuilder::new(seed)
+            .garble_keep_all(&mut stream)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Wire 0 is produced at gate 1 after being used as an input"));
+
+        Ok(())
+    }
 }
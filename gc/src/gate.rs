@@ -0,0 +1,416 @@
+use crate::error::{GcError, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::{Read, Write};
+
+use crate::binary_circuit::BinaryCircuitReader;
+use crate::constants::BINARY_CIRCUIT_MAGIC;
+use crate::parser::{
+    BristolWriter, IoDeclaration, parse_declaration_line, parse_gate_line, parse_header_line,
+    read_header,
+};
+use crate::stream::BufferedLineStream;
+
+/// Identifier of a wire in a circuit
+///
+/// 64 bits wide so circuits can have more than 2^32 wires. Binary formats
+/// that store wire IDs encode them as varints, so small IDs stay compact.
+pub type WireId = u64;
+
+/// Gate types supported by the garbler and evaluator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GateType {
+    /// Free XOR gate (no garbled table)
+    Xor,
+    /// AND gate (one garbled table)
+    And,
+    /// Single-input copy `output = input_1` (Bristol `EQW` and `BUF`)
+    ///
+    /// Garbled as label aliasing: the output wire reuses the input's labels.
+    Buf,
+    /// Constant assignment `output = value` (Bristol `EQ`)
+    ///
+    /// The value is public, so the evaluator derives the matching label
+    /// itself; see [`constant_label`](crate::garbler::constant_label).
+    Const(bool),
+}
+
+impl GateType {
+    /// Number of wires the gate reads
+    pub fn num_inputs(self) -> usize {
+        match self {
+            GateType::Xor | GateType::And => 2,
+            GateType::Buf => 1,
+            GateType::Const(_) => 0,
+        }
+    }
+}
+
+/// A two-input, one-output gate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Gate {
+    /// First input wire
+    pub input_wire_1: WireId,
+    /// Second input wire
+    pub input_wire_2: WireId,
+    /// Output wire
+    pub output_wire: WireId,
+    /// Gate type
+    pub gate_type: GateType,
+}
+
+impl Gate {
+    /// XOR gate `output = input_1 ^ input_2`
+    pub fn xor(input_wire_1: WireId, input_wire_2: WireId, output_wire: WireId) -> Self {
+        Gate {
+            input_wire_1,
+            input_wire_2,
+            output_wire,
+            gate_type: GateType::Xor,
+        }
+    }
+
+    /// AND gate `output = input_1 & input_2`
+    pub fn and(input_wire_1: WireId, input_wire_2: WireId, output_wire: WireId) -> Self {
+        Gate {
+            input_wire_1,
+            input_wire_2,
+            output_wire,
+            gate_type: GateType::And,
+        }
+    }
+
+    /// Copy gate `output = input`
+    pub fn buf(input_wire: WireId, output_wire: WireId) -> Self {
+        Gate {
+            input_wire_1: input_wire,
+            input_wire_2: input_wire,
+            output_wire,
+            gate_type: GateType::Buf,
+        }
+    }
+
+    /// Constant gate `output = value`
+    pub fn constant(value: bool, output_wire: WireId) -> Self {
+        Gate {
+            input_wire_1: 0,
+            input_wire_2: 0,
+            output_wire,
+            gate_type: GateType::Const(value),
+        }
+    }
+
+    /// Wires the gate reads: both inputs, only the first for copies, and
+    /// none for constants
+    pub fn input_wires(&self) -> impl Iterator<Item = WireId> {
+        [self.input_wire_1, self.input_wire_2]
+            .into_iter()
+            .take(self.gate_type.num_inputs())
+    }
+
+    /// The same gate on the wires `renumber` gives for the wires it reads and writes
+    pub fn map_wires(self, mut renumber: impl FnMut(WireId) -> WireId) -> Self {
+        match self.gate_type {
+            GateType::Xor => Gate::xor(
+                renumber(self.input_wire_1),
+                renumber(self.input_wire_2),
+                renumber(self.output_wire),
+            ),
+            GateType::And => Gate::and(
+                renumber(self.input_wire_1),
+                renumber(self.input_wire_2),
+                renumber(self.output_wire),
+            ),
+            GateType::Buf => Gate::buf(renumber(self.input_wire_1), renumber(self.output_wire)),
+            GateType::Const(value) => Gate::constant(value, renumber(self.output_wire)),
+        }
+    }
+}
+
+/// The gate as a Bristol gate line, as accepted by [`parse_gate_line`]
+impl fmt::Display for Gate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.gate_type {
+            GateType::Xor | GateType::And => write!(
+                f,
+                "2 1 {} {} {} {}",
+                self.input_wire_1,
+                self.input_wire_2,
+                self.output_wire,
+                if self.gate_type == GateType::Xor {
+                    "XOR"
+                } else {
+                    "AND"
+                }
+            ),
+            GateType::Buf => write!(f, "1 1 {} {} EQW", self.input_wire_1, self.output_wire),
+            GateType::Const(value) => write!(f, "1 1 {} {} EQ", u8::from(value), self.output_wire),
+        }
+    }
+}
+
+/// Running SHA-256 digest of a gate sequence, the canonical fingerprint of a circuit
+///
+/// Identifies the circuit a set of garbled tables was produced for. Only the
+/// parsed gates are hashed, so formatting differences such as extra
+/// whitespace do not change the digest, and a circuit hashes the same in
+/// Bristol and binary form (see [`hash_circuit`]).
+#[derive(Debug, Clone, Default)]
+pub struct CircuitDigest {
+    hasher: Sha256,
+}
+
+impl CircuitDigest {
+    /// Start a digest over an empty gate sequence
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next gate in circuit order
+    pub fn update(&mut self, gate: &Gate) {
+        let gate_type: u8 = match gate.gate_type {
+            GateType::Xor => 0,
+            GateType::And => 1,
+            GateType::Buf => 2,
+            GateType::Const(false) => 3,
+            GateType::Const(true) => 4,
+        };
+        self.hasher.update([gate_type]);
+        self.hasher.update(gate.input_wire_1.to_le_bytes());
+        self.hasher.update(gate.input_wire_2.to_le_bytes());
+        self.hasher.update(gate.output_wire.to_le_bytes());
+    }
+
+    /// Digest of all gates added so far
+    pub fn finalize(self) -> [u8; 32] {
+        self.hasher.finalize().into()
+    }
+
+    /// Digest of a whole gate slice
+    pub fn of_gates(gates: &[Gate]) -> [u8; 32] {
+        let mut digest = Self::new();
+        for gate in gates {
+            digest.update(gate);
+        }
+        digest.finalize()
+    }
+}
+
+/// Canonical fingerprint of a Bristol or binary circuit
+///
+/// The [`CircuitDigest`] of its gates, as recorded in the header of garbled
+/// tables for the circuit. Binary circuits are told apart by their magic
+/// bytes; blank and comment lines of Bristol text are skipped.
+///
+/// # Arguments
+/// * `reader` - The circuit, in either form
+///
+/// # Returns
+/// * `Ok([u8; 32])` - The fingerprint
+/// * `Err(GcError)` - Malformed circuit or read error
+pub fn hash_circuit<R: Read>(mut reader: R) -> Result<[u8; 32]> {
+    let mut magic = Vec::with_capacity(BINARY_CIRCUIT_MAGIC.len());
+    (&mut reader)
+        .take(BINARY_CIRCUIT_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    let is_binary = magic == BINARY_CIRCUIT_MAGIC;
+    let reader = std::io::Cursor::new(magic).chain(reader);
+
+    let mut digest = CircuitDigest::new();
+    if is_binary {
+        for gate in BinaryCircuitReader::new(std::io::BufReader::new(reader))? {
+            digest.update(&gate?);
+        }
+    } else {
+        let mut stream = BufferedLineStream::from_reader(reader).with_lenient(true);
+        let header = read_header(&mut stream)?;
+        let mut line_number = header.lines;
+        while let Some(line_result) = stream.next_line() {
+            line_number += 1;
+            digest.update(&parse_gate_line(line_result?, line_number)?);
+        }
+    }
+    Ok(digest.finalize())
+}
+
+/// A whole circuit held in memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Circuit {
+    /// Total number of wires, from the header
+    pub num_wires: u64,
+    /// Gates in topological order
+    pub gates: Vec<Gate>,
+    /// Input and output declarations of a Bristol Fashion header, if present
+    pub io: Option<IoDeclaration>,
+}
+
+impl Circuit {
+    /// Read a whole Bristol circuit from a line stream
+    #[tracing::instrument(name = "parse", skip_all)]
+    pub fn from_stream<R: Read>(stream: &mut BufferedLineStream<R>) -> Result<Self> {
+        let header = read_header(stream)?;
+
+        let mut gates = Vec::with_capacity(header.num_gates as usize);
+        let mut line_number = header.lines; // Already processed header
+        while let Some(line_result) = stream.next_line() {
+            line_number += 1;
+            gates.push(parse_gate_line(line_result?, line_number)?);
+        }
+
+        Ok(Circuit {
+            num_wires: header.num_wires,
+            gates,
+            io: header.io,
+        })
+    }
+
+    /// Write the circuit as Bristol text
+    ///
+    /// The header holds the number of gates and wires, followed by the
+    /// Bristol Fashion declarations if the circuit has them; the written
+    /// text parses back into the same circuit.
+    ///
+    /// # Arguments
+    /// * `writer` - Destination of the text
+    ///
+    /// # Returns
+    /// * `Ok(())` - The whole circuit was written
+    /// * `Err(GcError)` - A gate wire exceeds `num_wires`, or writing failed
+    pub fn write_bristol<W: Write>(&self, writer: W) -> Result<()> {
+        let mut bristol = BristolWriter::new(
+            writer,
+            self.gates.len() as u64,
+            self.num_wires,
+            self.io.as_ref(),
+        )?;
+        bristol.write_gates(self.gates.iter().copied())?;
+        bristol.finish()?;
+        Ok(())
+    }
+
+    /// Parse a whole Bristol circuit from a string
+    #[tracing::instrument(name = "parse", skip_all)]
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines().peekable();
+        let header_line = lines
+            .next()
+            .ok_or_else(|| GcError::parse(1, "Missing header"))?;
+        let mut header = parse_header_line(header_line)?;
+
+        // Bristol Fashion input and output declarations, as in `read_header`
+        let input_widths = match lines.peek() {
+            Some(line) => parse_declaration_line(line, 2, "input")?,
+            None => None,
+        };
+        if let Some(input_widths) = input_widths {
+            lines.next();
+            let output_widths = match lines.next() {
+                Some(line) => parse_declaration_line(line, 3, "output")?,
+                None => None,
+            }
+            .ok_or_else(|| GcError::parse(3, "Missing output declaration"))?;
+            header.lines = 3;
+            while lines.next_if(|line| line.is_empty()).is_some() {
+                header.lines += 1;
+            }
+            header.io = Some(IoDeclaration::new(
+                input_widths,
+                output_widths,
+                header.num_wires,
+            )?);
+        }
+
+        let mut gates = Vec::with_capacity(header.num_gates as usize);
+        for (index, line) in lines.enumerate() {
+            gates.push(parse_gate_line(line, header.lines + index as u64 + 1)?);
+        }
+
+        Ok(Circuit {
+            num_wires: header.num_wires,
+            gates,
+            io: header.io,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_parse() -> Result<()> {
+        let circuit = Circuit::parse("2 5\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n")?;
+        assert_eq!(circuit.num_wires, 5);
+        assert_eq!(circuit.gates, vec![Gate::and(0, 1, 2), Gate::xor(2, 3, 4)]);
+
+        let error_msg = Circuit::parse("1 3\n2 1 0 1 2 NAND\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("at line 2"));
+        assert!(Circuit::parse("").is_err());
+
+        // Bristol Fashion declarations
+        let circuit = Circuit::parse("1 5\n2 2 2\n1 1\n\n2 1 0 2 4 AND\n")?;
+        assert_eq!(circuit.io.unwrap().output_widths, vec![1]);
+        assert_eq!(circuit.gates, vec![Gate::and(0, 2, 4)]);
+        let error_msg = Circuit::parse("1 5\n2 2 2\n1 1\n\n2 1 0 2 4 NAND\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("at line 5"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_bristol_round_trip() -> Result<()> {
+        for text in [
+            "4 6\n2 1 0 1 2 AND\n1 1 2 3 EQW\n1 1 1 4 EQ\n2 1 3 4 5 XOR\n",
+            "1 5\n2 2 2\n1 1\n\n2 1 0 2 4 AND\n",
+        ] {
+            let circuit = Circuit::parse(text)?;
+            let mut written = Vec::new();
+            circuit.write_bristol(&mut written)?;
+            assert_eq!(String::from_utf8(written).unwrap(), text);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_circuit() -> Result<()> {
+        let text = "3 6\n2 1 0 1 2 AND\n1 1 2 3 EQW\n2 1 3 1 5 XOR\n";
+        let expected = CircuitDigest::of_gates(&Circuit::parse(text)?.gates);
+        assert_eq!(hash_circuit(text.as_bytes())?, expected);
+
+        let reformatted = "3  6\n\n# gates\n2 1 0 1 2 AND\n1 1  2 3 EQW\n\t2 1 3 1 5 XOR  \n";
+        assert_eq!(hash_circuit(reformatted.as_bytes())?, expected);
+
+        let mut binary = Vec::new();
+        crate::binary_circuit::convert_bristol_to_binary(
+            &mut BufferedLineStream::from_reader(text.as_bytes()),
+            &mut binary,
+            &crate::progress::NoProgress,
+        )?;
+        assert_eq!(hash_circuit(binary.as_slice())?, expected);
+
+        let changed = text.replace("XOR", "AND");
+        assert_ne!(hash_circuit(changed.as_bytes())?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_circuit_from_lenient_stream() -> Result<()> {
+        let text = "# adder\n2 5\n\n// gates\n2 1 0 1 2 AND\n  # carry\n2 1 2 3 4 XOR\n\n";
+        let mut file = tempfile::NamedTempFile::new()?;
+        std::io::Write::write_all(&mut file, text.as_bytes())?;
+
+        let strict = Circuit::from_stream(&mut BufferedLineStream::new(file.reopen()?));
+        assert!(strict.is_err());
+
+        let mut stream = BufferedLineStream::new(file.reopen()?).with_lenient(true);
+        let circuit = Circuit::from_stream(&mut stream)?;
+        assert_eq!(circuit.gates, vec![Gate::and(0, 1, 2), Gate::xor(2, 3, 4)]);
+
+        Ok(())
+    }
+}
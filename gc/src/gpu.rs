@@ -0,0 +1,315 @@
+use std::sync::mpsc::channel;
+
+use wgpu::util::DeviceExt;
+
+use crate::backend::{AndGateJob, GarblingBackend};
+use crate::error::{GcError, Result};
+use crate::garbler::GarbledTable;
+
+/// Compute shader garbling one AND gate per invocation
+const SHADER: &str = include_str!("gpu.wgsl");
+
+/// Invocations per workgroup, as declared by the shader
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Bytes of one job in the shader's input: six labels
+const JOB_BYTES: usize = 6 * 16;
+
+/// Bytes of one garbled table in the shader's output: four rows
+const TABLE_BYTES: usize = 4 * 16;
+
+/// Backend garbling AND gates on a GPU through wgpu
+///
+/// Each batch is uploaded, garbled by a compute shader running one
+/// invocation per gate, and read back. The shader computes the same SHA-256
+/// based hash as [`CpuBackend`](crate::backend::CpuBackend), so the tables
+/// are byte for byte the same.
+#[derive(Debug)]
+pub struct GpuBackend {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    /// Most jobs one dispatch takes, within the device's buffer and workgroup limits
+    max_jobs: usize,
+}
+
+impl GpuBackend {
+    /// Open the GPU wgpu picks, preferring a discrete one
+    ///
+    /// `WGPU_BACKEND` (e.g. `vulkan` or `gl`) and `WGPU_ADAPTER_NAME` narrow
+    /// the choice.
+    ///
+    /// # Returns
+    /// * `Ok(GpuBackend)` - A backend ready to garble
+    /// * `Err(GcError)` - No adapter was found, or it cannot run compute shaders
+    pub fn new() -> Result<Self> {
+        let instance =
+            wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle_from_env());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .map_err(|e| GcError::Gpu(format!("No GPU adapter found: {}", e)))?;
+        Self::with_adapter(&adapter)
+    }
+
+    /// Open a device on `adapter`
+    ///
+    /// # Arguments
+    /// * `adapter` - Adapter to garble on, e.g. one of `Instance::enumerate_adapters`
+    ///
+    /// # Returns
+    /// * `Ok(GpuBackend)` - A backend ready to garble
+    /// * `Err(GcError)` - The adapter cannot run compute shaders, or opening the device failed
+    pub fn with_adapter(adapter: &wgpu::Adapter) -> Result<Self> {
+        let info = adapter.get_info();
+        if !adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            return Err(GcError::Gpu(format!(
+                "{} ({:?}) cannot run compute shaders",
+                info.name, info.backend
+            )));
+        }
+
+        let limits = adapter.limits();
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("gc garbling"),
+            required_limits: limits.clone(),
+            ..Default::default()
+        }))
+        .map_err(|e| GcError::Gpu(format!("Cannot open {}: {}", info.name, e)))?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("gc garbling"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("gc garbling"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let max_binding = limits
+            .max_storage_buffer_binding_size
+            .min(limits.max_buffer_size);
+        let max_jobs = (max_binding / JOB_BYTES as u64)
+            .min(limits.max_compute_workgroups_per_dimension as u64 * WORKGROUP_SIZE as u64)
+            as usize;
+
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+            max_jobs,
+        })
+    }
+
+    /// Garble jobs that fit in a single dispatch
+    fn garble_dispatch(&self, jobs: &[AndGateJob]) -> Result<Vec<GarbledTable>> {
+        let mut input = Vec::with_capacity(jobs.len() * JOB_BYTES);
+        for job in jobs {
+            let [first, second] = job.input_labels;
+            for label in first.iter().chain(&second).chain(&job.output_labels) {
+                push_words(&mut input, label.as_bytes());
+            }
+        }
+
+        let output_size = (jobs.len() * TABLE_BYTES) as u64;
+        let job_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("gc garbling jobs"),
+                contents: &input,
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let table_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gc garbled tables"),
+            size: output_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let read_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gc garbled tables readback"),
+            size: output_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gc garbling"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: job_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: table_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups((jobs.len() as u32).div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&table_buffer, 0, &read_buffer, 0, output_size);
+        self.queue.submit([encoder.finish()]);
+
+        let (sender, receiver) = channel();
+        read_buffer.map_async(wgpu::MapMode::Read, .., move |result| {
+            let _ = sender.send(result);
+        });
+        self.device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|e| GcError::Gpu(format!("Garbling on the GPU failed: {}", e)))?;
+        receiver
+            .recv()
+            .map_err(|_| GcError::Gpu("GPU dropped the garbled tables".into()))?
+            .map_err(|e| GcError::Gpu(format!("Cannot read the garbled tables: {}", e)))?;
+
+        let tables = {
+            let output = read_buffer.get_mapped_range(..);
+            output
+                .chunks_exact(TABLE_BYTES)
+                .map(|table| {
+                    let mut ciphertexts = [[0u8; 16]; 4];
+                    for (row, bytes) in ciphertexts.iter_mut().zip(table.chunks_exact(16)) {
+                        pull_words(row, bytes);
+                    }
+                    GarbledTable { ciphertexts }
+                })
+                .collect()
+        };
+        read_buffer.unmap();
+        Ok(tables)
+    }
+}
+
+impl GarblingBackend for GpuBackend {
+    fn garble_and_gates(&mut self, jobs: &[AndGateJob]) -> Result<Vec<GarbledTable>> {
+        let mut tables = Vec::with_capacity(jobs.len());
+        for chunk in jobs.chunks(self.max_jobs) {
+            tables.extend(self.garble_dispatch(chunk)?);
+        }
+        Ok(tables)
+    }
+}
+
+/// Append a label as the big-endian words the shader hashes, in the GPU's little-endian layout
+fn push_words(buffer: &mut Vec<u8>, label: &[u8; 16]) {
+    for word in label.chunks_exact(4) {
+        buffer.extend(word.iter().rev());
+    }
+}
+
+/// Turn the shader's words of one row back into label bytes
+fn pull_words(row: &mut [u8; 16], words: &[u8]) {
+    for (bytes, word) in row.chunks_exact_mut(4).zip(words.chunks_exact(4)) {
+        for (byte, &value) in bytes.iter_mut().zip(word.iter().rev()) {
+            *byte = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::CpuBackend;
+    use crate::garbler::GarblerBuilder;
+    use crate::garbler::WireLabel;
+    use crate::gate::Gate;
+    use crate::wire_analyzer::analyze_gates;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha12Rng;
+
+    /// The GPU backend, or `None` on machines without a usable adapter
+    fn gpu_backend() -> Option<GpuBackend> {
+        match GpuBackend::new() {
+            Ok(backend) => Some(backend),
+            Err(e) => {
+                eprintln!("Skipping GPU test: {}", e);
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_label_words_round_trip() {
+        let label = WireLabel::new(std::array::from_fn(|i| i as u8));
+        let mut words = Vec::new();
+        push_words(&mut words, label.as_bytes());
+        assert_eq!(&words[..4], &[3, 2, 1, 0]);
+
+        let mut row = [0u8; 16];
+        pull_words(&mut row, &words);
+        assert_eq!(&row, label.as_bytes());
+    }
+
+    #[test]
+    fn test_gpu_backend_matches_cpu_backend() -> Result<()> {
+        let Some(mut backend) = gpu_backend() else {
+            return Ok(());
+        };
+        let mut rng = ChaCha12Rng::from_seed([0x42; 32]);
+        let delta = WireLabel::random_delta(&mut rng);
+        // Not a multiple of the workgroup size, so the last workgroup is partial
+        let jobs: Vec<AndGateJob> = (0..1000)
+            .map(|_| {
+                let [a, b, c] = std::array::from_fn(|_| WireLabel::random(&mut rng));
+                AndGateJob {
+                    input_labels: [[a, a.xor(&delta)], [b, b.xor(&delta)]],
+                    output_labels: [c, c.xor(&delta)],
+                }
+            })
+            .collect();
+
+        let gpu = backend.garble_and_gates(&jobs)?;
+        let cpu = CpuBackend.garble_and_gates(&jobs)?;
+        assert_eq!(gpu.len(), cpu.len());
+        for (gpu, cpu) in gpu.iter().zip(&cpu) {
+            assert_eq!(gpu.ciphertexts, cpu.ciphertexts);
+        }
+        assert!(backend.garble_and_gates(&[])?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gpu_garbling_matches_default() -> Result<()> {
+        let Some(mut backend) = gpu_backend() else {
+            return Ok(());
+        };
+        let gates = vec![
+            Gate::and(0, 1, 4),
+            Gate::xor(2, 3, 5),
+            Gate::and(4, 5, 6),
+            Gate::and(6, 0, 7),
+        ];
+        let wire_report = analyze_gates(&gates, 8)?;
+        let seed = [0x17; 32];
+
+        let default = GarblerBuilder::new(seed).garble_gates(&gates, &wire_report)?;
+        let gpu = GarblerBuilder::new(seed)
+            .backend(&mut backend)
+            .garble_gates(&gates, &wire_report)?;
+
+        assert_eq!(gpu.garbled_tables.len(), 3);
+        for (a, b) in default.garbled_tables.iter().zip(&gpu.garbled_tables) {
+            assert_eq!(a.ciphertexts, b.ciphertexts);
+        }
+
+        Ok(())
+    }
+}
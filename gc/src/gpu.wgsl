@@ -0,0 +1,107 @@
+// Garbling of AND gates, one invocation per gate
+//
+// Each job holds six wire labels of four u32 words: both labels of the first
+// input, both labels of the second input and both labels of the output. The
+// words are big-endian, the byte order SHA-256 reads, so a label hashes as
+// its 16 bytes. Each table gets four rows of four words, the same layout as
+// `garble_and_gate` on the CPU.
+
+@group(0) @binding(0) var<storage, read> jobs: array<u32>;
+@group(0) @binding(1) var<storage, read_write> tables: array<u32>;
+
+const JOB_WORDS: u32 = 24u;
+const TABLE_WORDS: u32 = 16u;
+
+var<private> K: array<u32, 64> = array<u32, 64>(
+    0x428a2f98u, 0x71374491u, 0xb5c0fbcfu, 0xe9b5dba5u, 0x3956c25bu, 0x59f111f1u, 0x923f82a4u, 0xab1c5ed5u,
+    0xd807aa98u, 0x12835b01u, 0x243185beu, 0x550c7dc3u, 0x72be5d74u, 0x80deb1feu, 0x9bdc06a7u, 0xc19bf174u,
+    0xe49b69c1u, 0xefbe4786u, 0x0fc19dc6u, 0x240ca1ccu, 0x2de92c6fu, 0x4a7484aau, 0x5cb0a9dcu, 0x76f988dau,
+    0x983e5152u, 0xa831c66du, 0xb00327c8u, 0xbf597fc7u, 0xc6e00bf3u, 0xd5a79147u, 0x06ca6351u, 0x14292967u,
+    0x27b70a85u, 0x2e1b2138u, 0x4d2c6dfcu, 0x53380d13u, 0x650a7354u, 0x766a0abbu, 0x81c2c92eu, 0x92722c85u,
+    0xa2bfe8a1u, 0xa81a664bu, 0xc24b8b70u, 0xc76c51a3u, 0xd192e819u, 0xd6990624u, 0xf40e3585u, 0x106aa070u,
+    0x19a4c116u, 0x1e376c08u, 0x2748774cu, 0x34b0bcb5u, 0x391c0cb3u, 0x4ed8aa4au, 0x5b9cca4fu, 0x682e6ff3u,
+    0x748f82eeu, 0x78a5636fu, 0x84c87814u, 0x8cc70208u, 0x90befffau, 0xa4506cebu, 0xbef9a3f7u, 0xc67178f2u,
+);
+
+fn rotr(x: u32, n: u32) -> u32 {
+    return (x >> n) | (x << (32u - n));
+}
+
+// First 16 bytes of SHA-256 over two labels: `garbling_hash` on the CPU
+//
+// The 32-byte message fits one block with its padding and length.
+fn garbling_hash(first: vec4<u32>, second: vec4<u32>) -> vec4<u32> {
+    var w: array<u32, 64>;
+    for (var i = 0u; i < 4u; i++) {
+        w[i] = first[i];
+        w[4u + i] = second[i];
+    }
+    w[8] = 0x80000000u;
+    for (var i = 9u; i < 15u; i++) {
+        w[i] = 0u;
+    }
+    w[15] = 256u;
+    for (var i = 16u; i < 64u; i++) {
+        let s0 = rotr(w[i - 15u], 7u) ^ rotr(w[i - 15u], 18u) ^ (w[i - 15u] >> 3u);
+        let s1 = rotr(w[i - 2u], 17u) ^ rotr(w[i - 2u], 19u) ^ (w[i - 2u] >> 10u);
+        w[i] = w[i - 16u] + s0 + w[i - 7u] + s1;
+    }
+
+    var a = 0x6a09e667u;
+    var b = 0xbb67ae85u;
+    var c = 0x3c6ef372u;
+    var d = 0xa54ff53au;
+    var e = 0x510e527fu;
+    var f = 0x9b05688cu;
+    var g = 0x1f83d9abu;
+    var h = 0x5be0cd19u;
+    for (var i = 0u; i < 64u; i++) {
+        let s1 = rotr(e, 6u) ^ rotr(e, 11u) ^ rotr(e, 25u);
+        let ch = (e & f) ^ (~e & g);
+        let t1 = h + s1 + ch + K[i] + w[i];
+        let s0 = rotr(a, 2u) ^ rotr(a, 13u) ^ rotr(a, 22u);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0 + maj;
+        h = g;
+        g = f;
+        f = e;
+        e = d + t1;
+        d = c;
+        c = b;
+        b = a;
+        a = t1 + t2;
+    }
+    return vec4<u32>(0x6a09e667u + a, 0xbb67ae85u + b, 0x3c6ef372u + c, 0xa54ff53au + d);
+}
+
+fn label(offset: u32) -> vec4<u32> {
+    return vec4<u32>(jobs[offset], jobs[offset + 1u], jobs[offset + 2u], jobs[offset + 3u]);
+}
+
+// Permute bit of a label: the last bit of its first byte
+fn permute_bit(label: vec4<u32>) -> u32 {
+    return (label.x >> 24u) & 1u;
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let gate = id.x;
+    if (gate >= arrayLength(&jobs) / JOB_WORDS) {
+        return;
+    }
+    let job = gate * JOB_WORDS;
+    for (var in1_bit = 0u; in1_bit < 2u; in1_bit++) {
+        for (var in2_bit = 0u; in2_bit < 2u; in2_bit++) {
+            let first = label(job + 4u * in1_bit);
+            let second = label(job + 8u + 4u * in2_bit);
+            let plaintext = label(job + 16u + 4u * (in1_bit & in2_bit));
+            let ciphertext = plaintext ^ garbling_hash(first, second);
+
+            let row = 2u * permute_bit(first) + permute_bit(second);
+            let offset = gate * TABLE_WORDS + 4u * row;
+            for (var i = 0u; i < 4u; i++) {
+                tables[offset + i] = ciphertext[i];
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use crate::error::{GcError, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::bus::{Bus, BusValue, parse_radix_bits};
+use crate::gate::WireId;
+
+/// Input bits given per wire, per named bus, or as one number over all inputs
+///
+/// Parsed from either of two forms. A JSON object assigns wires and buses
+/// by key:
+///
+/// ```json
+/// { "a": 5, "b": "0xff", "c": "0b1010", "130": true, "131": 0 }
+/// ```
+///
+/// Keys that are wire IDs take `true`, `false`, `1` or `0`. Other keys name
+/// an input bus and take its value as in [`BusValue::from_json`]: an
+/// integer, a `0x`-prefixed hex or `0b`-prefixed binary string of any width,
+/// or a decimal string. Anything else is a single number, hex with or
+/// without a `0x` prefix or binary with a `0b` prefix (`_` allowed as a
+/// separator), whose bit `i`, counting from the least significant bit, is
+/// the value of the `i`-th primary input wire in ascending order; inputs
+/// beyond its highest set bit are 0.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputAssignment {
+    /// One number over all primary inputs, least significant bit first
+    Packed(Vec<bool>),
+    /// Values keyed by wire ID or input bus name
+    Keyed(serde_json::Map<String, serde_json::Value>),
+}
+
+impl InputAssignment {
+    /// Parse an assignment from its text
+    ///
+    /// # Returns
+    /// * `Ok(InputAssignment)` - The assignment, not yet checked against a circuit
+    /// * `Err(GcError)` - Neither a JSON object nor a hex or binary number
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        if text.starts_with('{') {
+            return Ok(InputAssignment::Keyed(serde_json::from_str(text)?));
+        }
+        let bits = match text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+            Some(digits) => parse_radix_bits(digits, 1),
+            None => {
+                let digits = text
+                    .strip_prefix("0x")
+                    .or_else(|| text.strip_prefix("0X"))
+                    .unwrap_or(text);
+                parse_radix_bits(digits, 4)
+            }
+        };
+        bits.map(InputAssignment::Packed).ok_or_else(|| {
+            GcError::InvalidArgument(format!(
+                "Invalid input assignment '{}': expected a JSON object, or a hex or 0b-prefixed binary number",
+                text
+            ))
+        })
+    }
+
+    /// Load an assignment from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Resolve the assignment into one bit per input wire
+    ///
+    /// # Arguments
+    /// * `input_wires` - Primary input wires of the circuit in ascending order, if known. A
+    ///   packed number needs them; keyed wires must be among them.
+    /// * `buses` - Input buses that keys may name
+    ///
+    /// # Returns
+    /// * `Ok(HashMap<WireId, bool>)` - Bit value per input wire: wire_id -> bit
+    /// * `Err(GcError)` - Unknown bus, invalid value, a wire that is not an input or is assigned
+    ///   twice, or a number with more bits than there are input wires
+    pub fn resolve(
+        &self,
+        input_wires: Option<&[WireId]>,
+        buses: &[Bus],
+    ) -> Result<HashMap<WireId, bool>> {
+        match self {
+            InputAssignment::Packed(bits) => {
+                let input_wires = input_wires.ok_or_else(|| {
+                    GcError::InvalidArgument(
+                        "A packed input number needs the circuit's input wires; name wires or buses instead"
+                            .to_string(),
+                    )
+                })?;
+                resolve_packed(bits, input_wires)
+            }
+            InputAssignment::Keyed(assignment) => resolve_keyed(assignment, input_wires, buses),
+        }
+    }
+}
+
+/// Spread a packed number over `input_wires`, least significant bit first
+pub(crate) fn resolve_packed(
+    bits: &[bool],
+    input_wires: &[WireId],
+) -> Result<HashMap<WireId, bool>> {
+    if let Some(bit_index) = bits.iter().rposition(|&bit| bit)
+        && bit_index >= input_wires.len()
+    {
+        return Err(GcError::InvalidArgument(format!(
+            "Input assignment sets bit {} but the circuit has only {} input wires",
+            bit_index,
+            input_wires.len()
+        )));
+    }
+    let bit = |index: usize| bits.get(index).copied().unwrap_or(false);
+    Ok(input_wires
+        .iter()
+        .enumerate()
+        .map(|(index, &wire_id)| (wire_id, bit(index)))
+        .collect())
+}
+
+/// Resolve keys naming wires or `buses` into one bit per wire
+pub(crate) fn resolve_keyed(
+    assignment: &serde_json::Map<String, serde_json::Value>,
+    input_wires: Option<&[WireId]>,
+    buses: &[Bus],
+) -> Result<HashMap<WireId, bool>> {
+    let mut inputs = HashMap::new();
+    let mut assign = |wire_id: WireId, bit: bool| {
+        if input_wires.is_some_and(|input_wires| input_wires.binary_search(&wire_id).is_err()) {
+            return Err(GcError::InvalidArgument(format!(
+                "Wire {} is not a primary input",
+                wire_id
+            )));
+        }
+        if inputs.insert(wire_id, bit).is_some() {
+            return Err(GcError::InvalidArgument(format!(
+                "Input wire {} is assigned more than once",
+                wire_id
+            )));
+        }
+        Ok(())
+    };
+
+    for (key, value) in assignment {
+        if let Ok(wire_id) = key.parse::<WireId>() {
+            let bit = match value {
+                serde_json::Value::Bool(bit) => Some(*bit),
+                serde_json::Value::Number(number) => match number.as_u64() {
+                    Some(0) => Some(false),
+                    Some(1) => Some(true),
+                    _ => None,
+                },
+                _ => None,
+            }
+            .ok_or_else(|| {
+                GcError::InvalidArgument(format!(
+                    "Invalid value {} for input wire {}: expected true, false, 1 or 0",
+                    value, wire_id
+                ))
+            })?;
+            assign(wire_id, bit)?;
+            continue;
+        }
+        let bus = buses
+            .iter()
+            .find(|bus| bus.name == *key)
+            .ok_or_else(|| GcError::InvalidArgument(format!("No input bus named '{}'", key)))?;
+        let bus_value = BusValue::from_json(value, bus)?;
+        for (wire_id, bit) in bus.wires_lsb_first().into_iter().zip(bus_value.bits) {
+            assign(wire_id, bit)?;
+        }
+    }
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_assignment() -> Result<()> {
+        let buses = vec!["a=0..3".parse::<Bus>()?, "b=4..7".parse::<Bus>()?];
+        let input_wires: Vec<WireId> = (0..10).collect();
+        let bits = |inputs: HashMap<WireId, bool>| -> Vec<bool> {
+            input_wires.iter().map(|wire_id| inputs[wire_id]).collect()
+        };
+
+        // Buses in hex and binary, and wires as booleans or 0 and 1
+        let keyed = InputAssignment::parse(r#"{"a": "0x5", "b": "0b1001", "8": true, "9": 0}"#)?;
+        let expected = [
+            true, false, true, false, true, false, false, true, true, false,
+        ];
+        assert_eq!(bits(keyed.resolve(Some(&input_wires), &buses)?), expected);
+
+        // The same bits packed into one number, in hex or binary
+        let packed = InputAssignment::parse("0x195\n")?;
+        assert_eq!(bits(packed.resolve(Some(&input_wires), &buses)?), expected);
+        let binary = InputAssignment::parse("0b1_1001_0101")?;
+        assert_eq!(bits(binary.resolve(Some(&input_wires), &buses)?), expected);
+
+        let error = |assignment: &str, input_wires: Option<&[WireId]>| {
+            InputAssignment::parse(assignment)
+                .and_then(|assignment| assignment.resolve(input_wires, &buses))
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(
+            error("0x400", Some(&input_wires)).contains("sets bit 10 but the circuit has only 10")
+        );
+        assert!(error("0x1", None).contains("needs the circuit's input wires"));
+        assert!(
+            error(r#"{"10": true}"#, Some(&input_wires)).contains("Wire 10 is not a primary input")
+        );
+        assert!(error(r#"{"0": 2}"#, None).contains("expected true, false, 1 or 0"));
+        assert!(error("0xg", None).contains("Invalid input assignment '0xg'"));
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+use crate::error::{GcError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::bus::{Bus, BusLayout};
+use crate::gate::{Circuit, Gate, GateType, WireId};
+use crate::parser::IoDeclaration;
+
+/// A gate of a [`JsonCircuit`], tagged with its Bristol gate name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum JsonGate {
+    /// `output = inputs[0] XOR inputs[1]`
+    #[serde(rename = "XOR")]
+    Xor {
+        /// Input wires
+        inputs: [WireId; 2],
+        /// Output wire
+        output: WireId,
+    },
+    /// `output = inputs[0] AND inputs[1]`
+    #[serde(rename = "AND")]
+    And {
+        /// Input wires
+        inputs: [WireId; 2],
+        /// Output wire
+        output: WireId,
+    },
+    /// `output = input`
+    #[serde(rename = "EQW", alias = "BUF")]
+    Eqw {
+        /// Input wire
+        input: WireId,
+        /// Output wire
+        output: WireId,
+    },
+    /// `output = value`
+    #[serde(rename = "EQ")]
+    Eq {
+        /// Constant value
+        value: bool,
+        /// Output wire
+        output: WireId,
+    },
+}
+
+impl From<&Gate> for JsonGate {
+    fn from(gate: &Gate) -> Self {
+        let (input_1, input_2, output) = (gate.input_wire_1, gate.input_wire_2, gate.output_wire);
+        match gate.gate_type {
+            GateType::Xor => JsonGate::Xor {
+                inputs: [input_1, input_2],
+                output,
+            },
+            GateType::And => JsonGate::And {
+                inputs: [input_1, input_2],
+                output,
+            },
+            GateType::Buf => JsonGate::Eqw {
+                input: input_1,
+                output,
+            },
+            GateType::Const(value) => JsonGate::Eq { value, output },
+        }
+    }
+}
+
+impl From<&JsonGate> for Gate {
+    fn from(gate: &JsonGate) -> Self {
+        match *gate {
+            JsonGate::Xor { inputs, output } => Gate::xor(inputs[0], inputs[1], output),
+            JsonGate::And { inputs, output } => Gate::and(inputs[0], inputs[1], output),
+            JsonGate::Eqw { input, output } => Gate::buf(input, output),
+            JsonGate::Eq { value, output } => Gate::constant(value, output),
+        }
+    }
+}
+
+/// A circuit as structured JSON, for tools that would rather not read or
+/// write Bristol text
+///
+/// Holds the Bristol header, the input and output buses as in a
+/// [`BusLayout`], and the gates in topological order:
+///
+/// ```json
+/// {
+///   "num_gates": 2,
+///   "num_wires": 5,
+///   "inputs": [{ "name": "a", "first_wire": 0, "last_wire": 1 }, { "name": "b", "first_wire": 2, "last_wire": 2 }],
+///   "outputs": [{ "name": "out", "first_wire": 4, "last_wire": 4 }],
+///   "gates": [
+///     { "type": "AND", "inputs": [0, 1], "output": 3 },
+///     { "type": "XOR", "inputs": [3, 2], "output": 4 }
+///   ]
+/// }
+/// ```
+///
+/// Copies are `{"type": "EQW", "input": 3, "output": 4}` and constants
+/// `{"type": "EQ", "value": true, "output": 4}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonCircuit {
+    /// Number of gates, which must match `gates`
+    pub num_gates: u64,
+    /// Number of wires
+    pub num_wires: u64,
+    /// Input buses
+    #[serde(default)]
+    pub inputs: Vec<Bus>,
+    /// Output buses
+    #[serde(default)]
+    pub outputs: Vec<Bus>,
+    /// Gates in topological order
+    pub gates: Vec<JsonGate>,
+}
+
+impl JsonCircuit {
+    /// Describe a circuit, naming its buses after `buses` if given
+    ///
+    /// Without `buses`, the Bristol Fashion declarations of the circuit, if
+    /// any, become buses named `input_0`, `input_1`, ... and `output_0`, ...
+    pub fn from_circuit(circuit: &Circuit, buses: Option<&BusLayout>) -> Self {
+        let (inputs, outputs) = match (buses, &circuit.io) {
+            (Some(buses), _) => (buses.inputs.clone(), buses.outputs.clone()),
+            (None, Some(io)) => (
+                declared_buses("input", 0, &io.input_widths),
+                declared_buses(
+                    "output",
+                    io.output_wires(circuit.num_wires).start,
+                    &io.output_widths,
+                ),
+            ),
+            (None, None) => (Vec::new(), Vec::new()),
+        };
+        Self {
+            num_gates: circuit.gates.len() as u64,
+            num_wires: circuit.num_wires,
+            inputs,
+            outputs,
+            gates: circuit.gates.iter().map(JsonGate::from).collect(),
+        }
+    }
+
+    /// The circuit, checked for consistency
+    ///
+    /// When the input buses cover the first wires and the output buses the
+    /// last ones, each in wire order, the circuit gets the matching Bristol
+    /// Fashion declarations; otherwise the buses are only available from
+    /// [`bus_layout`](Self::bus_layout).
+    ///
+    /// # Returns
+    /// * `Ok(Circuit)` - The circuit
+    /// * `Err(GcError)` - `num_gates` does not match the gates, or a wire exceeds `num_wires`
+    pub fn to_circuit(&self) -> Result<Circuit> {
+        if self.num_gates != self.gates.len() as u64 {
+            return Err(GcError::InvalidCircuit(format!(
+                "num_gates is {} but {} gates are listed",
+                self.num_gates,
+                self.gates.len()
+            )));
+        }
+        let gates: Vec<Gate> = self.gates.iter().map(Gate::from).collect();
+        for (index, gate) in gates.iter().enumerate() {
+            if let Some(wire_id) = gate
+                .input_wires()
+                .chain([gate.output_wire])
+                .find(|&wire_id| wire_id >= self.num_wires)
+            {
+                return Err(GcError::InvalidCircuit(format!(
+                    "Gate {} uses wire {}, but the circuit has {} wires",
+                    index, wire_id, self.num_wires
+                )));
+            }
+        }
+        Ok(Circuit {
+            num_wires: self.num_wires,
+            gates,
+            io: self.io_declaration(),
+        })
+    }
+
+    /// Input and output buses, e.g. to pass as `--buses`
+    pub fn bus_layout(&self) -> BusLayout {
+        BusLayout {
+            inputs: self.inputs.clone(),
+            outputs: self.outputs.clone(),
+        }
+    }
+
+    /// Bristol Fashion declarations matching the buses, if they follow its layout
+    fn io_declaration(&self) -> Option<IoDeclaration> {
+        let mut buses = self.inputs.iter().chain(&self.outputs);
+        if buses.clone().next().is_none() || buses.any(|bus| bus.last_wire < bus.first_wire) {
+            return None;
+        }
+        let input_widths = contiguous_widths(&self.inputs, 0)?;
+        let num_output_wires: u64 = self.outputs.iter().map(Bus::width).sum();
+        let output_widths =
+            contiguous_widths(&self.outputs, self.num_wires.checked_sub(num_output_wires)?)?;
+        IoDeclaration::new(input_widths, output_widths, self.num_wires).ok()
+    }
+
+    /// Load a circuit from JSON
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let circuit = serde_json::from_str(&data)?;
+        Ok(circuit)
+    }
+
+    /// Save the circuit as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Buses named `<prefix>_<index>` of the given widths, starting at
+/// `first_wire`; empty declarations have no bus
+fn declared_buses(prefix: &str, first_wire: WireId, widths: &[u64]) -> Vec<Bus> {
+    let mut next_wire = first_wire;
+    widths
+        .iter()
+        .enumerate()
+        .filter(|(_, width)| **width > 0)
+        .map(|(index, &width)| {
+            let bus = Bus {
+                name: format!("{}_{}", prefix, index),
+                first_wire: next_wire,
+                last_wire: next_wire + width - 1,
+                bit_order: Default::default(),
+            };
+            next_wire += width;
+            bus
+        })
+        .collect()
+}
+
+/// Widths of `buses` if they are back to back from `first_wire`
+fn contiguous_widths(buses: &[Bus], first_wire: WireId) -> Option<Vec<u64>> {
+    let mut next_wire = first_wire;
+    buses
+        .iter()
+        .map(|bus| {
+            (bus.first_wire == next_wire).then(|| {
+                next_wire = bus.last_wire + 1;
+                bus.width()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() -> Result<()> {
+        let circuit = Circuit::parse(
+            "4 7\n2 2 1\n1 2\n\n2 1 0 1 3 AND\n1 1 1 4 EQ\n2 1 3 2 5 XOR\n1 1 5 6 EQW\n",
+        )?;
+        let json = JsonCircuit::from_circuit(&circuit, None);
+        assert_eq!(json.inputs[1].name, "input_1");
+        assert_eq!(json.outputs[0].first_wire, 5);
+
+        let text = serde_json::to_string(&json)?;
+        assert!(text.contains(r#"{"type":"EQ","value":true,"output":4}"#));
+        let parsed: JsonCircuit = serde_json::from_str(&text)?;
+        assert_eq!(parsed.to_circuit()?, circuit);
+
+        // Buses out of the Bristol Fashion layout leave the circuit undeclared
+        let mut reordered = parsed.clone();
+        reordered.inputs.swap(0, 1);
+        assert_eq!(reordered.to_circuit()?.io, None);
+        assert_eq!(reordered.bus_layout().inputs[0].name, "input_1");
+
+        reordered.num_wires = 6;
+        let error_msg = reordered.to_circuit().unwrap_err().to_string();
+        assert!(error_msg.contains("Gate 3 uses wire 6"));
+        Ok(())
+    }
+}
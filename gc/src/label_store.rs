@@ -0,0 +1,371 @@
+use crate::error::{GcError, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::constants::{RESIDENT_LABEL_BYTES, SPILL_EVICTION_FRACTION};
+use crate::gate::WireId;
+
+/// Largest record a [`SpillValue`] may encode to
+const MAX_SPILL_VALUE_SIZE: usize = 32;
+
+/// A wire label value with a fixed-size binary encoding for the spill file
+pub(crate) trait SpillValue: Copy {
+    /// Encoded size in bytes, at most [`MAX_SPILL_VALUE_SIZE`]
+    const SIZE: usize;
+
+    /// Encode into `buf`, which is exactly [`SIZE`](Self::SIZE) bytes long
+    fn write_to(&self, buf: &mut [u8]);
+
+    /// Decode from `buf`, which is exactly [`SIZE`](Self::SIZE) bytes long
+    fn read_from(buf: &[u8]) -> Self;
+}
+
+/// Labels of the wires that are currently live
+///
+/// Without a memory cap this is a plain map. With a cap, at most
+/// `max_memory / RESIDENT_LABEL_BYTES` labels stay in memory; when the map
+/// grows past that, the least recently used labels are written to an
+/// anonymous temp file and read back the next time they are needed.
+#[derive(Debug)]
+pub(crate) enum LabelStore<V> {
+    /// Every live label in memory
+    Unbounded(HashMap<WireId, V>),
+    /// Recently used labels in memory, the rest in a spill file
+    Capped(CappedLabels<V>),
+}
+
+/// A label held in memory under a cap, with the time it was last used
+#[derive(Debug, Clone, Copy)]
+struct Resident<V> {
+    value: V,
+    last_used: u64,
+}
+
+/// Memory-capped label map backed by a spill file
+///
+/// The spill file is indexed by wire id: the label of wire `w` lives at
+/// offset `w * V::SIZE`. It is sparse, so only the pages of spilled wires
+/// take up disk space. Which wires are spilled is tracked with one bit per
+/// wire id, a small fraction of the memory the labels themselves would take.
+#[derive(Debug)]
+pub(crate) struct CappedLabels<V> {
+    /// Labels kept in memory
+    resident: HashMap<WireId, Resident<V>>,
+    /// Largest number of labels kept in memory
+    max_resident: usize,
+    /// Logical clock, advanced on every access
+    clock: u64,
+    /// Anonymous temp file holding the spilled labels
+    file: File,
+    /// Bit `w` is set while the label of wire `w` is in the spill file
+    spilled_bits: Vec<u64>,
+    /// Number of labels currently in the spill file
+    spilled: usize,
+    /// Total number of labels written to the spill file
+    spill_writes: u64,
+    /// Total number of labels read back from the spill file
+    reloads: u64,
+}
+
+impl<V: SpillValue> LabelStore<V> {
+    /// Create an empty store, spilling to disk beyond `max_memory` bytes
+    ///
+    /// # Arguments
+    /// * `max_memory` - Memory budget for resident labels in bytes, `None` for no cap
+    ///
+    /// # Returns
+    /// * `Ok(LabelStore)` - An empty store
+    /// * `Err(GcError)` - The budget is too small or the spill file can't be created
+    pub(crate) fn new(max_memory: Option<u64>) -> Result<Self> {
+        let Some(max_memory) = max_memory else {
+            return Ok(LabelStore::Unbounded(HashMap::new()));
+        };
+
+        let max_resident = (max_memory / RESIDENT_LABEL_BYTES) as usize;
+        if max_resident < SPILL_EVICTION_FRACTION {
+            return Err(GcError::InvalidArgument(format!(
+                "Memory cap of {} bytes is too small, need at least {} bytes",
+                max_memory,
+                SPILL_EVICTION_FRACTION as u64 * RESIDENT_LABEL_BYTES
+            )));
+        }
+
+        Ok(LabelStore::Capped(CappedLabels {
+            resident: HashMap::new(),
+            max_resident,
+            clock: 0,
+            file: tempfile::tempfile()?,
+            spilled_bits: Vec::new(),
+            spilled: 0,
+            spill_writes: 0,
+            reloads: 0,
+        }))
+    }
+
+    /// Number of live labels, in memory or spilled
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            LabelStore::Unbounded(labels) => labels.len(),
+            LabelStore::Capped(capped) => capped.resident.len() + capped.spilled,
+        }
+    }
+
+    /// Add or replace the label of `wire_id`
+    pub(crate) fn insert(&mut self, wire_id: WireId, value: V) -> Result<()> {
+        match self {
+            LabelStore::Unbounded(labels) => {
+                labels.insert(wire_id, value);
+                Ok(())
+            }
+            LabelStore::Capped(capped) => {
+                // A stale spilled copy would otherwise be counted twice
+                capped.forget_spilled(wire_id);
+                capped.insert_resident(wire_id, value)
+            }
+        }
+    }
+
+    /// Label of `wire_id`, reloading it into memory if it was spilled
+    pub(crate) fn get(&mut self, wire_id: WireId) -> Result<Option<V>> {
+        match self {
+            LabelStore::Unbounded(labels) => Ok(labels.get(&wire_id).copied()),
+            LabelStore::Capped(capped) => {
+                capped.clock += 1;
+                if let Some(resident) = capped.resident.get_mut(&wire_id) {
+                    resident.last_used = capped.clock;
+                    return Ok(Some(resident.value));
+                }
+
+                let Some(value) = capped.take_spilled(wire_id)? else {
+                    return Ok(None);
+                };
+                capped.reloads += 1;
+                capped.insert_resident(wire_id, value)?;
+                Ok(Some(value))
+            }
+        }
+    }
+
+    /// Label of `wire_id` without moving it into memory
+    pub(crate) fn peek(&self, wire_id: WireId) -> Result<Option<V>> {
+        match self {
+            LabelStore::Unbounded(labels) => Ok(labels.get(&wire_id).copied()),
+            LabelStore::Capped(capped) => match capped.resident.get(&wire_id) {
+                Some(resident) => Ok(Some(resident.value)),
+                None => capped.read_spilled(wire_id),
+            },
+        }
+    }
+
+    /// Drop the label of `wire_id`, wherever it is
+    pub(crate) fn remove(&mut self, wire_id: WireId) {
+        match self {
+            LabelStore::Unbounded(labels) => {
+                labels.remove(&wire_id);
+            }
+            LabelStore::Capped(capped) => {
+                if capped.resident.remove(&wire_id).is_none() {
+                    capped.forget_spilled(wire_id);
+                }
+            }
+        }
+    }
+
+    /// All live labels, in no particular order
+    ///
+    /// Reads every spilled label back, so this is meant for the end of a run.
+    pub(crate) fn entries(&self) -> Result<Vec<(WireId, V)>> {
+        match self {
+            LabelStore::Unbounded(labels) => Ok(labels.iter().map(|(&w, &v)| (w, v)).collect()),
+            LabelStore::Capped(capped) => {
+                let mut entries: Vec<(WireId, V)> = capped
+                    .resident
+                    .iter()
+                    .map(|(&wire_id, resident)| (wire_id, resident.value))
+                    .collect();
+
+                for (word_index, &word) in capped.spilled_bits.iter().enumerate() {
+                    let mut bits = word;
+                    while bits != 0 {
+                        let wire_id = word_index as u64 * 64 + bits.trailing_zeros() as u64;
+                        bits &= bits - 1;
+                        entries.push((wire_id, capped.read_record(wire_id)?));
+                    }
+                }
+                Ok(entries)
+            }
+        }
+    }
+
+    /// Log how much was spilled, if anything
+    pub(crate) fn trace_spill_stats(&self, phase: &'static str) {
+        if let LabelStore::Capped(capped) = self {
+            tracing::info!(
+                phase,
+                max_resident = capped.max_resident,
+                spill_writes = capped.spill_writes,
+                reloads = capped.reloads,
+                "label spilling"
+            );
+        }
+    }
+}
+
+impl<V: SpillValue> CappedLabels<V> {
+    /// Whether the label of `wire_id` is in the spill file
+    fn is_spilled(&self, wire_id: WireId) -> bool {
+        self.spilled_bits
+            .get((wire_id / 64) as usize)
+            .is_some_and(|word| word & (1 << (wire_id % 64)) != 0)
+    }
+
+    /// Mark the spilled label of `wire_id`, if any, as gone
+    ///
+    /// The record stays in the file and is overwritten on the next spill.
+    fn forget_spilled(&mut self, wire_id: WireId) {
+        if self.is_spilled(wire_id) {
+            self.spilled_bits[(wire_id / 64) as usize] &= !(1 << (wire_id % 64));
+            self.spilled -= 1;
+        }
+    }
+
+    /// Put a label in memory, spilling the least recently used ones if full
+    fn insert_resident(&mut self, wire_id: WireId, value: V) -> Result<()> {
+        self.clock += 1;
+        self.resident.insert(
+            wire_id,
+            Resident {
+                value,
+                last_used: self.clock,
+            },
+        );
+        if self.resident.len() > self.max_resident {
+            self.evict()?;
+        }
+        Ok(())
+    }
+
+    /// Spill a fraction of the resident labels, least recently used first
+    ///
+    /// Evicting in batches keeps the cost of finding victims amortized, and
+    /// writing them in wire order keeps the file accesses close together.
+    fn evict(&mut self) -> Result<()> {
+        let evict_count = self.max_resident / SPILL_EVICTION_FRACTION;
+        let mut by_age: Vec<(u64, WireId)> = self
+            .resident
+            .iter()
+            .map(|(&wire_id, resident)| (resident.last_used, wire_id))
+            .collect();
+        by_age.select_nth_unstable(evict_count - 1);
+        let mut victims: Vec<WireId> = by_age[..evict_count]
+            .iter()
+            .map(|&(_, wire_id)| wire_id)
+            .collect();
+        victims.sort_unstable();
+
+        let mut record = [0u8; MAX_SPILL_VALUE_SIZE];
+        for wire_id in victims {
+            let resident = self.resident.remove(&wire_id).unwrap();
+            resident.value.write_to(&mut record[..V::SIZE]);
+            self.file.seek(SeekFrom::Start(wire_id * V::SIZE as u64))?;
+            self.file.write_all(&record[..V::SIZE])?;
+
+            let word = (wire_id / 64) as usize;
+            if word >= self.spilled_bits.len() {
+                self.spilled_bits.resize(word + 1, 0);
+            }
+            self.spilled_bits[word] |= 1 << (wire_id % 64);
+        }
+        self.spilled += evict_count;
+        self.spill_writes += evict_count as u64;
+        Ok(())
+    }
+
+    /// Read the spilled label of `wire_id`, if there is one
+    fn read_spilled(&self, wire_id: WireId) -> Result<Option<V>> {
+        if !self.is_spilled(wire_id) {
+            return Ok(None);
+        }
+        Ok(Some(self.read_record(wire_id)?))
+    }
+
+    /// Read the spilled label of `wire_id` and drop it from the spill file
+    fn take_spilled(&mut self, wire_id: WireId) -> Result<Option<V>> {
+        let value = self.read_spilled(wire_id)?;
+        if value.is_some() {
+            self.forget_spilled(wire_id);
+        }
+        Ok(value)
+    }
+
+    /// Read the record of `wire_id` from the spill file
+    fn read_record(&self, wire_id: WireId) -> Result<V> {
+        let mut file = &self.file;
+        let mut record = [0u8; MAX_SPILL_VALUE_SIZE];
+        file.seek(SeekFrom::Start(wire_id * V::SIZE as u64))?;
+        file.read_exact(&mut record[..V::SIZE])?;
+        Ok(V::read_from(&record[..V::SIZE]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl SpillValue for u64 {
+        const SIZE: usize = 8;
+
+        fn write_to(&self, buf: &mut [u8]) {
+            buf.copy_from_slice(&self.to_le_bytes());
+        }
+
+        fn read_from(buf: &[u8]) -> Self {
+            u64::from_le_bytes(buf.try_into().unwrap())
+        }
+    }
+
+    #[test]
+    fn test_capped_store_spills_and_reloads() -> Result<()> {
+        let max_resident = 4 * SPILL_EVICTION_FRACTION;
+        let mut store = LabelStore::<u64>::new(Some(max_resident as u64 * RESIDENT_LABEL_BYTES))?;
+        for wire_id in 0..100 {
+            store.insert(wire_id, wire_id * 7)?;
+        }
+        assert_eq!(store.len(), 100);
+        let LabelStore::Capped(capped) = &store else {
+            panic!("expected a capped store");
+        };
+        assert!(capped.resident.len() <= max_resident);
+        assert!(capped.spilled > 0);
+
+        // Every label survives, whether resident or spilled
+        assert_eq!(store.peek(3)?, Some(21));
+        for wire_id in 0..100 {
+            assert_eq!(store.get(wire_id)?, Some(wire_id * 7));
+        }
+        assert_eq!(store.get(100)?, None);
+        assert_eq!(store.peek(1000)?, None);
+
+        for wire_id in (0..100).step_by(2) {
+            store.remove(wire_id);
+        }
+        assert_eq!(store.len(), 50);
+        assert_eq!(store.get(2)?, None);
+
+        let mut entries = store.entries()?;
+        entries.sort_unstable();
+        let expected: Vec<(u64, u64)> = (1..100).step_by(2).map(|w| (w, w * 7)).collect();
+        assert_eq!(entries, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_capped_store_rejects_tiny_cap() {
+        let error_msg = LabelStore::<u64>::new(Some(RESIDENT_LABEL_BYTES))
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("is too small"));
+    }
+}
@@ -0,0 +1,292 @@
+use crate::error::Result;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::and_depth::is_non_linear;
+use crate::constants::{BUFFER_SIZE, PROGRESS_UPDATE_INTERVAL};
+use crate::parser::{CircuitHeader, WireRef, read_header, visit_gate_wires};
+use crate::progress::ProgressObserver;
+use crate::stream::BufferedLineStream;
+
+/// Dependency layering of a circuit.
+///
+/// A gate's layer is one more than the deepest layer producing any of its
+/// inputs, with gates fed only by primary inputs in layer 0. All gates in a
+/// layer are independent of each other and can be evaluated concurrently.
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct LayerReport {
+    /// Number of layers in the circuit
+    pub num_layers: u32,
+    /// Layer of every gate: index = gate index (0-based, in file order)
+    pub gate_layers: Vec<u32>,
+}
+
+impl LayerReport {
+    /// Save the layering to a binary file for reuse by the layered evaluator
+    pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let encoded = bincode::encode_to_vec(self, bincode::config::standard())?;
+        std::fs::write(path, encoded)?;
+        Ok(())
+    }
+
+    /// Load a layering from a binary file
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        let mut reader = BufReader::with_capacity(BUFFER_SIZE, file);
+
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        let (report, _) = bincode::decode_from_slice(&buffer, bincode::config::standard())?;
+        Ok(report)
+    }
+
+    /// Number of gates in each layer: index = layer
+    pub fn layer_widths(&self) -> Vec<usize> {
+        let mut widths = vec![0usize; self.num_layers as usize];
+        for &layer in &self.gate_layers {
+            widths[layer as usize] += 1;
+        }
+        widths
+    }
+}
+
+/// Compute the dependency layer of every gate in a Bristol circuit
+///
+/// Expected format:
+/// First line: `<num_gates> <num_wires>`
+/// Followed by gate lines in Bristol format
+///
+/// # Arguments
+/// * `stream` - The line stream to process
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(LayerReport)` - Layer of every gate
+/// * `Err(GcError)` - Parse error or IO error
+#[tracing::instrument(name = "layering", skip_all)]
+pub fn analyze_layers<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    progress: &dyn ProgressObserver,
+) -> Result<LayerReport> {
+    let CircuitHeader {
+        num_gates,
+        num_wires,
+        lines,
+        ..
+    } = read_header(stream)?;
+
+    // Depth of every wire: 0 for primary inputs, layer + 1 for gate outputs
+    let mut wire_depths = vec![0u32; num_wires as usize];
+    let mut gate_layers = Vec::with_capacity(num_gates as usize);
+    let mut num_layers = 0u32;
+    let mut line_number = lines; // Already processed header
+
+    progress.start(Some(num_gates), "Computing layers");
+
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        let (layer, _) = gate_layer(line_result?, line_number, &mut wire_depths)?;
+
+        gate_layers.push(layer);
+        num_layers = num_layers.max(layer + 1);
+
+        if (gate_layers.len() as u64).is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            progress.update(gate_layers.len() as u64);
+        }
+    }
+
+    progress.update(gate_layers.len() as u64);
+    progress.finish(&format!(
+        "✓ Layered {} gates into {num_layers} layers",
+        gate_layers.len()
+    ));
+
+    Ok(LayerReport {
+        num_layers,
+        gate_layers,
+    })
+}
+
+/// Number of gates and of AND gates in each dependency layer of a circuit
+///
+/// Shows how much parallelism a layered evaluator can exploit: wide layers
+/// keep every thread busy, narrow ones leave them idle. AND gates are counted
+/// apart because they dominate the cost of garbling and evaluation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayerProfile {
+    /// Number of gates in each layer: index = layer
+    pub gates: Vec<u64>,
+    /// Number of non-linear gates (AND, NAND, OR, NOR) in each layer: index = layer
+    pub and_gates: Vec<u64>,
+}
+
+impl LayerProfile {
+    /// Number of layers in the circuit
+    pub fn num_layers(&self) -> usize {
+        self.gates.len()
+    }
+
+    /// Export the profile to a CSV file
+    ///
+    /// # Arguments
+    /// * `path` - Path to write CSV file
+    ///
+    /// # CSV Format
+    /// ```csv
+    /// layer,gates,and_gates
+    /// 0,1024,512
+    /// 1,980,300
+    /// ```
+    pub fn export_csv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "layer,gates,and_gates")?;
+        for (layer, (gates, and_gates)) in self.gates.iter().zip(&self.and_gates).enumerate() {
+            writeln!(file, "{layer},{gates},{and_gates}")?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Count the gates and AND gates in every dependency layer of a Bristol circuit
+///
+/// Layers gates like [`analyze_layers`], but only keeps the count of each
+/// layer instead of the layer of every gate.
+///
+/// # Arguments
+/// * `stream` - The line stream to process
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(LayerProfile)` - Gates and AND gates of every layer
+/// * `Err(GcError)` - Parse error or IO error
+#[tracing::instrument(name = "layer_profile", skip_all)]
+pub fn profile_layers<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    progress: &dyn ProgressObserver,
+) -> Result<LayerProfile> {
+    let CircuitHeader {
+        num_gates,
+        num_wires,
+        lines,
+        ..
+    } = read_header(stream)?;
+
+    let mut wire_depths = vec![0u32; num_wires as usize];
+    let mut profile = LayerProfile::default();
+    let mut gates_processed = 0u64;
+    let mut line_number = lines;
+
+    progress.start(Some(num_gates), "Profiling layers");
+
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        let (layer, gate_type) = gate_layer(line_result?, line_number, &mut wire_depths)?;
+
+        let layer = layer as usize;
+        if layer >= profile.gates.len() {
+            profile.gates.resize(layer + 1, 0);
+            profile.and_gates.resize(layer + 1, 0);
+        }
+        profile.gates[layer] += 1;
+        profile.and_gates[layer] += u64::from(is_non_linear(gate_type));
+
+        gates_processed += 1;
+        if gates_processed.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            progress.update(gates_processed);
+        }
+    }
+
+    progress.update(gates_processed);
+    progress.finish(&format!(
+        "✓ Profiled {gates_processed} gates in {} layers",
+        profile.num_layers()
+    ));
+
+    Ok(profile)
+}
+
+/// Layer of one gate line and its gate type, recording the depth of its outputs
+///
+/// A gate's layer is the deepest of its input wires, whose depths are 0 for
+/// primary inputs and one more than the layer of the gate writing them.
+fn gate_layer<'a>(
+    line: &'a str,
+    line_number: u64,
+    wire_depths: &mut [u32],
+) -> Result<(u32, &'a str)> {
+    // Inputs are visited before outputs
+    let mut layer = 0u32;
+    let gate_type = visit_gate_wires(line, line_number, |wire| {
+        wire.check_range(wire_depths.len() as u64, line_number)?;
+        match wire {
+            WireRef::Input(wire_id) => layer = layer.max(wire_depths[wire_id as usize]),
+            WireRef::Output(wire_id) => wire_depths[wire_id as usize] = layer + 1,
+        }
+        Ok(())
+    })?;
+    Ok((layer, gate_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+    use crate::test_util::create_test_file;
+
+    #[test]
+    fn test_analyze_layers_basic() -> Result<()> {
+        // Gates 0 and 1 only use primary inputs, gate 2 depends on both,
+        // gate 3 depends on gate 2 and a primary input
+        let circuit_data = "4 9\n2 1 0 1 4 XOR\n2 1 2 3 5 AND\n2 1 4 5 6 AND\n2 1 6 0 7 XOR\n";
+        let temp_file = create_test_file(circuit_data)?;
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+
+        let report = analyze_layers(&mut stream, &NoProgress)?;
+
+        assert_eq!(report.gate_layers, vec![0, 0, 1, 2]);
+        assert_eq!(report.num_layers, 3);
+        assert_eq!(report.layer_widths(), vec![2, 1, 1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_layers() -> Result<()> {
+        // Same circuit as above: an XOR and an AND in layer 0, then an AND, then an XOR
+        let circuit_data = "4 9\n2 1 0 1 4 XOR\n2 1 2 3 5 AND\n2 1 4 5 6 AND\n2 1 6 0 7 XOR\n";
+        let mut stream = BufferedLineStream::from_reader(circuit_data.as_bytes());
+
+        let profile = profile_layers(&mut stream, &NoProgress)?;
+
+        assert_eq!(profile.gates, vec![2, 1, 1]);
+        assert_eq!(profile.and_gates, vec![1, 1, 0]);
+
+        let csv_file = tempfile::NamedTempFile::new()?;
+        profile.export_csv(csv_file.path())?;
+        assert_eq!(
+            std::fs::read_to_string(csv_file.path())?,
+            "layer,gates,and_gates\n0,2,1\n1,1,1\n2,1,0\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_layer_report_binary_roundtrip() -> Result<()> {
+        let report = LayerReport {
+            num_layers: 2,
+            gate_layers: vec![0, 1, 0],
+        };
+
+        let binary_file = tempfile::NamedTempFile::new()?;
+        report.save_binary(binary_file.path())?;
+        let loaded = LayerReport::load_binary(binary_file.path())?;
+
+        assert_eq!(loaded.num_layers, 2);
+        assert_eq!(loaded.gate_layers, vec![0, 1, 0]);
+
+        Ok(())
+    }
+}
@@ -1,20 +1,136 @@
 //! High-performance Rust implementation of Yao's garbled circuits protocol with free XOR optimization.
 
+/// Import of AIGER and-inverter graphs
+pub mod aiger;
+/// AND depth (multiplicative depth) of a circuit and of each of its outputs
+pub mod and_depth;
+/// Async streaming of garbled tables over bounded queues with backpressure
+#[cfg(feature = "async")]
+pub mod async_tables;
+/// Pluggable backends for AND gate garbling
+pub mod backend;
+/// Bandwidth limiting and traffic metering for networked sessions
+pub mod bandwidth;
+/// Compact zstd-compressed binary circuit format
+pub mod binary_circuit;
+/// Import of BLIF netlists
+pub mod blif;
+/// In-memory circuit construction with named input and output buses
+pub mod builder;
+/// Named input and output buses of a circuit
+pub mod bus;
+/// Versioned message framing for networked protocols
+pub mod channel;
+/// Numbered chunks with BLAKE3 checksums for long transfers
+pub mod chunk;
+/// Circuit files scanned on several threads in chunks of whole lines
+pub mod chunked_parse;
+/// Generators of standard components: adders, multipliers, comparators, equality checks and multiplexers
+pub mod circuits;
+/// Composition of two circuits, feeding outputs of one into inputs of the other
+pub mod compose;
 /// Shared constants used across the library
 pub mod constants;
 /// Circuit gate counting utilities
 pub mod counter;
+/// Dead gate elimination, dropping gates no primary output depends on
+pub mod dead_gates;
+/// Decoding of output labels into bit values
+pub mod decoder;
+/// Gate emission with free inverters for the circuit importers
+mod emitter;
+/// Error type returned by the library
+pub mod error;
 /// Garbled circuit evaluation functionality
 pub mod evaluator;
+/// Gate counts, wire usage, single-use and memory analyses in one pass over the circuit
+pub mod fused_analysis;
 /// Circuit garbling using Yao's protocol with free XOR
 pub mod garbler;
+/// Gate, wire and in-memory circuit representation
+pub mod gate;
+/// AND gate garbling on a GPU through wgpu
+#[cfg(feature = "gpu")]
+pub mod gpu;
+/// Input assignment files giving bits per wire, per named bus or as one packed number
+pub mod input_assignment;
+/// Circuits as structured JSON with named buses
+pub mod json_circuit;
+/// Live wire label storage with an optional memory cap and disk spilling
+mod label_store;
+/// Dependency layering of gates for parallel evaluation
+pub mod layering;
+/// Rewriting of NAND, OR, NOR, XNOR and INV gates into the XOR/AND basis
+pub mod lowering;
 /// Simulate the memory usage to store active wires
 pub mod memory_simulation;
+/// Base oblivious transfer (OT) between garbler and evaluator
+pub mod ot;
+/// OT extension turning a few base OTs into one OT per input wire
+pub mod ot_extension;
+/// OT between a garbler and an evaluator connected over a byte stream
+pub mod ot_remote;
 /// Oblivious transfer (OT) protocol simulation
 pub mod ot_simulation;
+/// Recorded OT transcripts and their post-hoc verification
+pub mod ot_transcript;
+/// Bristol header and gate line parsing shared by every pass
+pub mod parser;
+/// Assignment of primary inputs to the garbler and the evaluator
+pub mod partition;
+/// Cleartext circuit evaluation, the ground truth for garbled runs
+pub mod plain;
+/// Progress reporting hooks for long-running passes
+pub mod progress;
+/// Random circuits of tunable size, gate mix, fan-out and depth for benchmarks and fuzzing
+pub mod random_circuit;
+/// Random OT precomputed before the labels exist, and its derandomization
+pub mod random_ot;
+/// Wire renumbering into a gap-free range
+pub mod renumber;
+/// gRPC garbler service and the evaluator client driving it
+#[cfg(feature = "rpc")]
+pub mod rpc;
+/// Import of SCALE-MAMBA circuit descriptions
+pub mod scale_mamba;
+/// Seek tables of compressed garbled tables, mapping AND gates to zstd frames
+pub mod seek_table;
+/// Serde helpers for deterministic JSON output
+mod serde_helpers;
+/// Identifiers and manifests tying the artifacts of one garbling run together
+pub mod session;
+/// Garbled tables split into shard files with a manifest
+pub mod shard;
+/// One-call in-memory run of the whole protocol
+pub mod simulate;
 /// Count number of single-use gates
 pub mod single_use_analyzer;
+/// Destinations for garbled tables streamed out of the garbler
+pub mod sink;
+/// SoftSpokenOT building blocks: punctured GGM trees and small-field VOLE
+mod soft_spoken;
+/// Stacked garbling of conditionals whose branches are mutually exclusive
+pub mod stacked;
 /// High-performance streaming file reader
 pub mod stream;
+/// Resumable transfer of garbled tables files from the garbler to the evaluator
+pub mod table_transfer;
+/// Fixtures shared by the unit tests
+#[cfg(test)]
+pub(crate) mod test_util;
+/// TLS transport with certificate pinning for networked sessions
+#[cfg(feature = "tls")]
+pub mod tls;
+/// Topological order checks and reordering of circuit gates
+pub mod topological;
+/// Whole two-party sessions between a garbler and an evaluator over a byte stream
+pub mod two_party;
+/// Offline self-test of garbled tables against the garbler's labels
+pub mod verifier;
+/// WebSocket transport for peers that can only be reached over WebSockets
+#[cfg(feature = "websocket")]
+pub mod websocket;
 /// Wire usage analysis for memory optimization
 pub mod wire_analyzer;
+/// Gate writing and gate last reading every wire, and the labels held at each point
+pub mod wire_lifetime;
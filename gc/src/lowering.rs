@@ -0,0 +1,358 @@
+use crate::error::{GcError, Result};
+use std::io::{self, Read};
+
+use crate::constants::LOWERED_STREAM_BUFFER_SIZE;
+use crate::gate::{Gate, WireId};
+use crate::parser::{BristolWriter, CircuitHeader, parse_gate_line, read_header};
+use crate::stream::BufferedLineStream;
+
+/// A gate outside the XOR/AND basis the garbler supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompoundGate {
+    /// `out = NOT (a AND b)`
+    Nand,
+    /// `out = a OR b`
+    Or,
+    /// `out = NOT (a OR b)`
+    Nor,
+    /// `out = NOT (a XOR b)`
+    Xnor,
+    /// `out = NOT a`
+    Inv,
+}
+
+/// Parse a gate line of a gate outside the basis, or `None` for any other gate
+///
+/// Accepts `2 1 <in1> <in2> <out> NAND|OR|NOR|XNOR` and `1 1 <in> <out> INV|NOT`.
+fn parse_compound_line(
+    line: &str,
+    line_number: u64,
+) -> Result<Option<(CompoundGate, [WireId; 2], WireId)>> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let (kind, num_inputs) = match tokens.last() {
+        Some(&"NAND") => (CompoundGate::Nand, 2),
+        Some(&"OR") => (CompoundGate::Or, 2),
+        Some(&"NOR") => (CompoundGate::Nor, 2),
+        Some(&"XNOR") => (CompoundGate::Xnor, 2),
+        Some(&"INV" | &"NOT") => (CompoundGate::Inv, 1),
+        _ => return Ok(None),
+    };
+    let counts_match =
+        tokens.len() == num_inputs + 4 && tokens[0] == num_inputs.to_string() && tokens[1] == "1";
+    let wires = tokens[2..tokens.len() - 1]
+        .iter()
+        .map(|token| token.parse::<WireId>())
+        .collect::<std::result::Result<Vec<_>, _>>();
+    match wires {
+        Ok(wires) if counts_match => {
+            let inputs = [wires[0], wires[num_inputs - 1]];
+            Ok(Some((kind, inputs, wires[num_inputs])))
+        }
+        _ => Err(GcError::parse(
+            line_number,
+            format!(
+                "Expected '{} 1 <input>... <output> {}', got: '{}'",
+                num_inputs,
+                tokens[tokens.len() - 1],
+                line
+            ),
+        )),
+    }
+}
+
+/// Gates outside the XOR/AND basis found by [`scan_gates`]
+///
+/// Fixes the header of the lowered circuit before its first gate is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoweringReport {
+    /// Header of the circuit as read
+    pub header: CircuitHeader,
+    /// Number of `NAND` gates
+    pub nand_gates: u64,
+    /// Number of `OR` gates
+    pub or_gates: u64,
+    /// Number of `NOR` gates
+    pub nor_gates: u64,
+    /// Number of `XNOR` gates
+    pub xnor_gates: u64,
+    /// Number of `INV` and `NOT` gates
+    pub inv_gates: u64,
+}
+
+impl LoweringReport {
+    /// Number of gates to rewrite
+    pub fn num_lowered(&self) -> u64 {
+        self.nand_gates + self.or_gates + self.nor_gates + self.xnor_gates + self.inv_gates
+    }
+
+    /// Whether the lowered circuit needs a constant-one wire to invert with
+    fn uses_one(&self) -> bool {
+        self.nand_gates + self.nor_gates + self.xnor_gates + self.inv_gates > 0
+    }
+
+    /// Number of wires the lowering adds, each written by one added gate
+    ///
+    /// `NAND` and `XNOR` need one intermediate wire, `OR` and `NOR` two, and
+    /// inverting ones the constant-one wire.
+    pub fn added_wires(&self) -> u64 {
+        self.nand_gates
+            + self.xnor_gates
+            + 2 * (self.or_gates + self.nor_gates)
+            + u64::from(self.uses_one())
+    }
+}
+
+/// Count the gates of a circuit outside the XOR/AND basis
+///
+/// First of the two passes of lowering, see [`LoweringReader`]; gates in the
+/// basis are not parsed.
+///
+/// # Arguments
+/// * `stream` - Bristol circuit that may contain `NAND`, `OR`, `NOR`, `XNOR` and `INV` gates
+///
+/// # Returns
+/// * `Ok(LoweringReport)` - The header and the gates to rewrite
+/// * `Err(GcError)` - Malformed header or gate line of a gate outside the basis
+pub fn scan_gates<R: Read>(stream: &mut BufferedLineStream<R>) -> Result<LoweringReport> {
+    let header = read_header(stream)?;
+    let mut report = LoweringReport {
+        header: header.clone(),
+        nand_gates: 0,
+        or_gates: 0,
+        nor_gates: 0,
+        xnor_gates: 0,
+        inv_gates: 0,
+    };
+    let mut line_number = header.lines;
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        if let Some((kind, _, _)) = parse_compound_line(line_result?, line_number)? {
+            *match kind {
+                CompoundGate::Nand => &mut report.nand_gates,
+                CompoundGate::Or => &mut report.or_gates,
+                CompoundGate::Nor => &mut report.nor_gates,
+                CompoundGate::Xnor => &mut report.xnor_gates,
+                CompoundGate::Inv => &mut report.inv_gates,
+            } += 1;
+        }
+    }
+    Ok(report)
+}
+
+/// A Bristol circuit rewritten into the XOR/AND basis as it is read
+///
+/// Second pass of lowering: reads the circuit [`scan_gates`] counted and
+/// yields it as Bristol text with only `XOR`, `AND`, `EQW` and `EQ` gates,
+/// so it can feed any pass, including the garbler, without a converted copy
+/// on disk. Inverters are XORs with a constant-one wire, free to garble:
+///
+/// * `NAND`: `t = a AND b`, `out = t XOR 1`
+/// * `XNOR`: `t = a XOR b`, `out = t XOR 1`
+/// * `OR`: `t1 = a XOR b`, `t2 = a AND b`, `out = t1 XOR t2`
+/// * `NOR`: `t1 = a XOR 1`, `t2 = b XOR 1`, `out = t1 AND t2`
+/// * `INV`: `out = a XOR 1`
+///
+/// The added wires go right before the outputs, which move up to stay the
+/// last wires of the circuit as the Bristol Fashion declarations require;
+/// the constant-one wire comes first and is written by the first gate.
+#[derive(Debug)]
+pub struct LoweringReader<R: Read> {
+    stream: BufferedLineStream<R>,
+    /// First added wire; wires from here on move up by `shift`
+    first_added: WireId,
+    shift: u64,
+    one_wire: Option<WireId>,
+    next_added: WireId,
+    line_number: u64,
+    /// Lowered text not yet read
+    output: BristolWriter<Vec<u8>>,
+    position: usize,
+    /// Gates of the current line
+    gates: Vec<Gate>,
+}
+
+impl<R: Read> LoweringReader<R> {
+    /// Start lowering a circuit, writing the lowered header
+    ///
+    /// # Arguments
+    /// * `stream` - The circuit `report` was scanned from, read again from the start
+    /// * `report` - Result of [`scan_gates`] over the circuit
+    ///
+    /// # Returns
+    /// * `Ok(LoweringReader)` - Reader of the lowered circuit
+    /// * `Err(GcError)` - The circuit has a different header than the one scanned
+    pub fn new(mut stream: BufferedLineStream<R>, report: &LoweringReport) -> Result<Self> {
+        let header = read_header(&mut stream)?;
+        if header != report.header {
+            return Err(GcError::InvalidArgument(
+                "circuit changed between the lowering passes".into(),
+            ));
+        }
+        let first_added = match &header.io {
+            Some(io) => io.output_wires(header.num_wires).start,
+            None => header.num_wires,
+        };
+        let shift = report.added_wires();
+        let one_wire = report.uses_one().then_some(first_added);
+
+        let output = BristolWriter::new(
+            Vec::new(),
+            header.num_gates + shift,
+            header.num_wires + shift,
+            header.io.as_ref(),
+        )?;
+        let mut gates = Vec::with_capacity(3);
+        if let Some(one_wire) = one_wire {
+            gates.push(Gate::constant(true, one_wire));
+        }
+        let mut reader = Self {
+            stream,
+            first_added,
+            shift,
+            one_wire,
+            next_added: first_added + u64::from(one_wire.is_some()),
+            line_number: header.lines,
+            output,
+            position: 0,
+            gates,
+        };
+        reader.write_gates()?;
+        Ok(reader)
+    }
+
+    /// Read the lowered circuit as a line stream
+    pub fn into_stream(self) -> BufferedLineStream<Self> {
+        BufferedLineStream::with_buffer_size(self, LOWERED_STREAM_BUFFER_SIZE)
+    }
+
+    fn added_wire(&mut self) -> WireId {
+        self.next_added += 1;
+        self.next_added - 1
+    }
+
+    /// Lower the next gate line into `gates`, returning false at the end
+    fn lower_next_line(&mut self) -> Result<bool> {
+        let Some(line_result) = self.stream.next_line() else {
+            return Ok(false);
+        };
+        self.line_number += 1;
+        let line = line_result?;
+        let (first_added, shift) = (self.first_added, self.shift);
+        let renumber = |wire_id: WireId| {
+            if wire_id >= first_added {
+                wire_id + shift
+            } else {
+                wire_id
+            }
+        };
+        let Some((kind, [a, b], output)) = parse_compound_line(line, self.line_number)? else {
+            let gate = parse_gate_line(line, self.line_number)?;
+            self.gates.push(gate.map_wires(renumber));
+            return Ok(true);
+        };
+
+        let (a, b, output) = (renumber(a), renumber(b), renumber(output));
+        // Set whenever the circuit has a gate needing it
+        let one = self.one_wire.unwrap_or_default();
+        match kind {
+            CompoundGate::Nand => {
+                let both = self.added_wire();
+                self.gates.push(Gate::and(a, b, both));
+                self.gates.push(Gate::xor(both, one, output));
+            }
+            CompoundGate::Xnor => {
+                let either = self.added_wire();
+                self.gates.push(Gate::xor(a, b, either));
+                self.gates.push(Gate::xor(either, one, output));
+            }
+            CompoundGate::Or => {
+                let (either, both) = (self.added_wire(), self.added_wire());
+                self.gates.push(Gate::xor(a, b, either));
+                self.gates.push(Gate::and(a, b, both));
+                self.gates.push(Gate::xor(either, both, output));
+            }
+            CompoundGate::Nor => {
+                let (not_a, not_b) = (self.added_wire(), self.added_wire());
+                self.gates.push(Gate::xor(a, one, not_a));
+                self.gates.push(Gate::xor(b, one, not_b));
+                self.gates.push(Gate::and(not_a, not_b, output));
+            }
+            CompoundGate::Inv => self.gates.push(Gate::xor(a, one, output)),
+        }
+        Ok(true)
+    }
+
+    /// Append the pending gates to the output as Bristol lines
+    fn write_gates(&mut self) -> Result<()> {
+        self.output.write_gates(self.gates.drain(..))
+    }
+}
+
+impl<R: Read> Read for LoweringReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.output.get_mut().len() {
+            self.output.get_mut().clear();
+            self.position = 0;
+            let more = self
+                .lower_next_line()
+                .and_then(|more| self.write_gates().map(|()| more))
+                .map_err(io::Error::other)?;
+            if !more {
+                return Ok(0);
+            }
+        }
+        let buffer = self.output.get_mut();
+        let count = buf.len().min(buffer.len() - self.position);
+        buf[..count].copy_from_slice(&buffer[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gate::Circuit;
+    use crate::plain::evaluate_plain;
+    use crate::wire_analyzer::analyze_circuit;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_lower_gates() -> Result<()> {
+        // One output per compound gate over inputs a = 0 and b = 1
+        let text = "5 7\n2 1 1\n5 1 1 1 1 1\n\n2 1 0 1 2 NAND\n2 1 0 1 3 OR\n2 1 0 1 4 NOR\n\
+                    2 1 0 1 5 XNOR\n1 1 0 6 INV\n";
+        let stream = || BufferedLineStream::from_reader(Cursor::new(text));
+        let report = scan_gates(&mut stream())?;
+        assert_eq!(report.num_lowered(), 5);
+        assert_eq!(report.added_wires(), 7);
+
+        let mut lowered = String::new();
+        LoweringReader::new(stream(), &report)?.read_to_string(&mut lowered)?;
+        let circuit = Circuit::parse(&lowered)?;
+        assert_eq!(circuit.num_wires, 14);
+        assert_eq!(circuit.gates.len(), 12);
+        assert_eq!(circuit.gates[0], Gate::constant(true, 2));
+        let wire_report = analyze_circuit(&circuit)?;
+        assert_eq!(
+            wire_report.primary_output_wires,
+            (9..14).collect::<Vec<_>>()
+        );
+
+        for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+            let outputs = evaluate_plain(&circuit, &wire_report, &HashMap::from([(0, a), (1, b)]))?;
+            let expected = [!(a && b), a || b, !(a || b), a == b, !a];
+            let actual: Vec<bool> = (9..14).map(|wire_id| outputs[&wire_id]).collect();
+            assert_eq!(actual, expected);
+        }
+
+        let error_msg = scan_gates(&mut BufferedLineStream::from_reader(Cursor::new(
+            "1 3\n2 1 0 2 NAND\n",
+        )))
+        .unwrap_err()
+        .to_string();
+        assert!(error_msg.contains("Expected '2 1 <input>... <output> NAND'"));
+        Ok(())
+    }
+}
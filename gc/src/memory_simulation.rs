@@ -1,11 +1,13 @@
-use anyhow::{Result, bail};
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::error::Result;
 use std::collections::HashSet;
 use std::fs::File;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::Path;
 
 use crate::constants::PROGRESS_UPDATE_INTERVAL;
+use crate::gate::WireId;
+use crate::parser::{WireRef, read_header, visit_gate_wires};
+use crate::progress::ProgressObserver;
 use crate::stream::BufferedLineStream;
 use crate::wire_analyzer::WireUsageReport;
 
@@ -44,10 +46,11 @@ pub struct MemorySnapshot {
 /// # Arguments
 /// * `stream` - The line stream to process Bristol circuit
 /// * `wire_report` - Wire usage analysis from wire_analyzer module
+/// * `progress` - Receives progress updates
 ///
 /// # Returns
 /// * `Ok(MemorySimulationReport)` - Memory simulation results with snapshots
-/// * `Err(anyhow::Error)` - Parse error or IO error
+/// * `Err(GcError)` - Parse error or IO error
 ///
 /// # Algorithm
 /// 1. Parse and validate Bristol format header
@@ -58,133 +61,50 @@ pub struct MemorySnapshot {
 ///    - Add output wires to active set
 /// 4. Take snapshots every PROGRESS_UPDATE_INTERVAL gates processed
 /// 5. Track maximum number of live wires throughout simulation
-pub fn simulate_memory_usage(
-    stream: &mut BufferedLineStream,
+#[tracing::instrument(name = "memory_simulation", skip_all)]
+pub fn simulate_memory_usage<R: Read>(
+    stream: &mut BufferedLineStream<R>,
     wire_report: &WireUsageReport,
+    progress: &dyn ProgressObserver,
 ) -> Result<MemorySimulationReport> {
-    // Parse and validate header line
-    let header_line = stream
-        .next_line()
-        .ok_or_else(|| anyhow::anyhow!("Missing header line"))??;
+    let num_gates = read_header(stream)?.num_gates;
 
-    let header_tokens: Vec<&str> = header_line.split_whitespace().collect();
-    if header_tokens.len() != 2 {
-        bail!(
-            "Invalid header: expected '<num_gates> <num_wires>', got: '{}'",
-            header_line
-        );
-    }
-
-    // Parse header values - use num_gates for progress bar
-    let num_gates: u32 = header_tokens[0]
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid num_gates: '{}'", header_tokens[0]))?;
-    let _num_wires: u32 = header_tokens[1]
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid num_wires: '{}'", header_tokens[1]))?;
-
-    // Initialize active wire set with primary inputs (convert to u32)
-    let mut active_wires: HashSet<u32> = wire_report.primary_input_wires.iter().cloned().collect();
+    // Initialize active wire set with primary inputs
+    let mut active_wires: HashSet<WireId> =
+        wire_report.primary_input_wires.iter().cloned().collect();
 
     // Initialize wire usage counts (mutable copy)
     let mut remaining_usage = wire_report.wire_usage_counts.clone();
 
     // Tracking variables
     let mut max_live_wires = active_wires.len();
-    let mut gate_number = 0u32;
+    let mut gate_number = 0u64;
     let mut snapshots = Vec::new();
-    let mut line_number = 0u32;
+    let mut line_number = 0u64;
 
     // Create progress bar for gate processing (use actual count from header)
-    let pb = ProgressBar::new(num_gates as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
-            .unwrap()
-            .progress_chars("##-"),
-    );
-    pb.set_message("Simulating memory usage...");
+    progress.start(Some(num_gates), "Simulating memory usage...");
 
     // Process each gate as we read it (streaming approach - matches garbler.rs)
     while let Some(line_result) = stream.next_line() {
         line_number += 1;
         let line = line_result?;
 
-        if line.trim().is_empty() {
-            bail!("Empty line at line number {}", line_number);
-        }
-
-        // Parse gate line directly using iterator (more efficient - matches garbler.rs)
-        let mut tokens = line.split_whitespace();
-
-        // Parse num_inputs and num_outputs
-        let num_inputs: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing num_inputs at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid num_inputs at line {}: '{}'", line_number, line)
-            })?;
-
-        let num_outputs: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing num_outputs at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid num_outputs at line {}: '{}'", line_number, line)
-            })?;
-
-        // Process input wires directly (no intermediate allocation)
-        for i in 0..num_inputs {
-            let input_wire: u32 = tokens
-                .next()
-                .ok_or_else(|| anyhow::anyhow!("Missing input wire {} at line {}", i, line_number))?
-                .parse()
-                .map_err(|_| {
-                    anyhow::anyhow!("Invalid input wire ID at line {}: '{}'", line_number, line)
-                })?;
-
-            // Add bounds checking for array access
-            if (input_wire as usize) < remaining_usage.len()
-                && remaining_usage[input_wire as usize] > 0
-            {
-                // Wires with count 255 are never decremented (permanent wires)
-                if remaining_usage[input_wire as usize] < 255 {
-                    remaining_usage[input_wire as usize] -= 1;
-                }
-
+        visit_gate_wires(line, line_number, |wire| {
+            match wire {
                 // Remove wire from active set if no longer needed
-                if remaining_usage[input_wire as usize] == 0 {
-                    active_wires.remove(&input_wire);
+                WireRef::Input(input_wire) => {
+                    if remaining_usage.release(input_wire) {
+                        active_wires.remove(&input_wire);
+                    }
+                }
+                WireRef::Output(output_wire) => {
+                    active_wires.insert(output_wire);
                 }
             }
-        }
-
-        // Process output wires directly (no intermediate allocation)
-        for i in 0..num_outputs {
-            let output_wire: u32 = tokens
-                .next()
-                .ok_or_else(|| {
-                    anyhow::anyhow!("Missing output wire {} at line {}", i, line_number)
-                })?
-                .parse()
-                .map_err(|_| {
-                    anyhow::anyhow!("Invalid output wire ID at line {}: '{}'", line_number, line)
-                })?;
-
-            active_wires.insert(output_wire);
-        }
-
-        // Parse gate type (last token)
-        let _gate_type = tokens.next().ok_or_else(|| {
-            anyhow::anyhow!("Missing gate type at line {}: '{}'", line_number, line)
+            Ok(())
         })?;
 
-        // Validate no extra tokens
-        if tokens.next().is_some() {
-            bail!("Too many tokens at line {}: '{}'", line_number, line);
-        }
-
         gate_number += 1;
 
         // Update maximum live wires
@@ -192,7 +112,7 @@ pub fn simulate_memory_usage(
             max_live_wires = active_wires.len();
         }
 
-        let gate_index: u32 = line_number - 1;
+        let gate_index: u64 = line_number - 1;
 
         if gate_index.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
             snapshots.push(MemorySnapshot {
@@ -202,13 +122,12 @@ pub fn simulate_memory_usage(
         }
 
         if gate_index.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
-            pb.set_position(gate_index as u64);
-            pb.set_message("Simulating memory usage...");
+            progress.update(gate_index);
         }
     }
 
     // Finish progress bar
-    pb.finish_with_message(format!(
+    progress.finish(&format!(
         "✓ Simulated {line_number} gates, max {max_live_wires} live wires"
     ));
 
@@ -233,15 +152,6 @@ impl MemorySimulationReport {
     /// 20000,1180
     /// ```
     pub fn export_csv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        // Create a spinner progress bar for CSV export
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} [{elapsed_precise}] {msg}")
-                .unwrap(),
-        );
-        pb.set_message("Writing CSV file...");
-
         let mut file = File::create(path)?;
 
         // Write CSV header
@@ -256,11 +166,13 @@ impl MemorySimulationReport {
             )?;
         }
 
-        pb.finish_with_message("✓ CSV file saved");
         Ok(())
     }
 
     /// Print summary statistics to console
+    ///
+    /// Only available with the `cli` feature.
+    #[cfg(feature = "cli")]
     pub fn print_summary(&self) {
         println!("Memory Simulation Summary:");
         println!("  Total gates processed: {}", self.total_gates_processed);
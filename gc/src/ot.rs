@@ -0,0 +1,570 @@
+use crate::error::{GcError, Result};
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_TABLE;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::garbler::{WireLabel, WireLabels};
+use crate::gate::WireId;
+use crate::ot_simulation::{OTResult, SelectedInput, encode_garbler_inputs};
+use crate::session::SessionId;
+
+/// Domain separation tag for the keys that encrypt transferred labels
+const BASE_OT_KEY_DOMAIN: &[u8] = b"gc-base-ot-key-v1";
+
+/// Size of a compressed Ristretto point in bytes
+const POINT_SIZE: usize = 32;
+
+/// Size of the setup message: the OT count followed by the sender's point
+const SETUP_MESSAGE_SIZE: usize = 8 + POINT_SIZE;
+
+/// Size of one encrypted label pair in the transfer message
+const CIPHERTEXT_SIZE: usize = 32;
+
+/// Sender side of a batch of 1-out-of-2 base OTs
+///
+/// Implements the Chou-Orlandi "simplest OT" over the Ristretto group, which
+/// is secure against a semi-honest receiver. The sender holds a pair of
+/// labels per OT; the receiver learns exactly one label of each pair and the
+/// sender learns nothing about which. The protocol takes three messages, all
+/// plain byte vectors so they can be carried over any transport:
+///
+/// 1. [`BaseOtSender::setup`] produces the setup message for the receiver
+/// 2. [`BaseOtReceiver::choose`] answers it with the receiver's choices
+/// 3. [`BaseOtSender::transfer`] answers those with the encrypted pairs,
+///    which [`BaseOtReceiver::finish`] decrypts
+///
+/// Calling a step out of order, or feeding it a malformed message, returns
+/// [`GcError::Protocol`].
+#[derive(Debug)]
+pub struct BaseOtSender {
+    rng: ChaCha12Rng,
+    state: SenderState,
+}
+
+#[derive(Debug)]
+enum SenderState {
+    /// Holds the label pairs until the setup message is sent
+    Ready { pairs: Vec<(WireLabel, WireLabel)> },
+    /// Setup sent, waiting for the receiver's choices
+    AwaitingChoices {
+        secret: Scalar,
+        point: RistrettoPoint,
+        pairs: Vec<(WireLabel, WireLabel)>,
+    },
+    /// Encrypted pairs sent
+    Finished,
+}
+
+impl BaseOtSender {
+    /// Create a sender transferring one label of each pair
+    ///
+    /// # Arguments
+    /// * `pairs` - The two labels offered in each OT, indexed by choice bit
+    /// * `seed_data` - 32 bytes of random seed for the sender's secret
+    pub fn new(pairs: Vec<(WireLabel, WireLabel)>, seed_data: &[u8; 32]) -> Self {
+        Self {
+            rng: ChaCha12Rng::from_seed(*seed_data),
+            state: SenderState::Ready { pairs },
+        }
+    }
+
+    /// Create a sender offering both labels of every evaluator input wire
+    ///
+    /// Pairs are ordered by wire ID, as returned by
+    /// [`WireLabels::evaluator_input_wires`]; the receiver must use the same
+    /// order, e.g. through [`BaseOtReceiver::for_input_bits`].
+    pub fn for_wire_labels(wire_labels: &WireLabels, seed_data: &[u8; 32]) -> Self {
+        Self::new(evaluator_label_pairs(wire_labels), seed_data)
+    }
+
+    /// First step: produce the setup message for the receiver
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - The OT count as a little-endian `u64` followed by the sender's public point
+    /// * `Err(GcError)` - The setup message was already produced
+    pub fn setup(&mut self) -> Result<Vec<u8>> {
+        let SenderState::Ready { pairs } =
+            std::mem::replace(&mut self.state, SenderState::Finished)
+        else {
+            return Err(unexpected_step("sender", "setup"));
+        };
+
+        let secret = random_scalar(&mut self.rng);
+        let point = &secret * RISTRETTO_BASEPOINT_TABLE;
+
+        let mut message = Vec::with_capacity(SETUP_MESSAGE_SIZE);
+        message.extend_from_slice(&(pairs.len() as u64).to_le_bytes());
+        message.extend_from_slice(point.compress().as_bytes());
+
+        self.state = SenderState::AwaitingChoices {
+            secret,
+            point,
+            pairs,
+        };
+        Ok(message)
+    }
+
+    /// Third step: encrypt both labels of each pair under the receiver's choices
+    ///
+    /// # Arguments
+    /// * `choices` - The message produced by [`BaseOtReceiver::choose`]
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - Both labels of every pair, each encrypted under its own key
+    /// * `Err(GcError)` - The message is malformed or the setup message was not produced yet
+    pub fn transfer(&mut self, choices: &[u8]) -> Result<Vec<u8>> {
+        let SenderState::AwaitingChoices {
+            secret,
+            point,
+            pairs,
+        } = std::mem::replace(&mut self.state, SenderState::Finished)
+        else {
+            return Err(unexpected_step("sender", "transfer"));
+        };
+
+        if choices.len() != pairs.len() * POINT_SIZE {
+            return Err(GcError::Protocol(format!(
+                "Choice message has {} bytes, expected {} for {} OTs",
+                choices.len(),
+                pairs.len() * POINT_SIZE,
+                pairs.len()
+            )));
+        }
+
+        let sender_point = point.compress();
+        let mut message = Vec::with_capacity(pairs.len() * CIPHERTEXT_SIZE);
+        for (index, (chunk, (label_0, label_1))) in
+            choices.chunks_exact(POINT_SIZE).zip(&pairs).enumerate()
+        {
+            let compressed = CompressedRistretto(chunk.try_into().unwrap());
+            let receiver_point = decompress(&compressed, index)?;
+
+            let key_0 = derive_key(
+                index,
+                &sender_point,
+                &compressed,
+                &(secret * receiver_point),
+            );
+            let key_1 = derive_key(
+                index,
+                &sender_point,
+                &compressed,
+                &(secret * (receiver_point - point)),
+            );
+            message.extend_from_slice(label_0.xor(&key_0).as_bytes());
+            message.extend_from_slice(label_1.xor(&key_1).as_bytes());
+        }
+
+        tracing::debug!(ots = pairs.len(), "Base OT sender finished");
+        Ok(message)
+    }
+}
+
+/// Receiver side of a batch of 1-out-of-2 base OTs
+///
+/// Counterpart of [`BaseOtSender`]; see there for the message flow.
+#[derive(Debug)]
+pub struct BaseOtReceiver {
+    rng: ChaCha12Rng,
+    state: ReceiverState,
+}
+
+#[derive(Debug)]
+enum ReceiverState {
+    /// Holds the choice bits until the setup message arrives
+    Ready { choices: Vec<bool> },
+    /// Choices sent, waiting for the encrypted pairs
+    AwaitingTransfer {
+        choices: Vec<bool>,
+        keys: Vec<WireLabel>,
+    },
+    /// Labels decrypted
+    Finished,
+}
+
+impl BaseOtReceiver {
+    /// Create a receiver selecting one label per OT
+    ///
+    /// # Arguments
+    /// * `choices` - The choice bit of each OT: `false` selects the first label of the pair
+    /// * `seed_data` - 32 bytes of random seed for the receiver's secrets
+    pub fn new(choices: Vec<bool>, seed_data: &[u8; 32]) -> Self {
+        Self {
+            rng: ChaCha12Rng::from_seed(*seed_data),
+            state: ReceiverState::Ready { choices },
+        }
+    }
+
+    /// Create a receiver choosing the evaluator's input bits
+    ///
+    /// # Arguments
+    /// * `wire_ids` - The evaluator's input wires in ascending order, matching [`BaseOtSender::for_wire_labels`]
+    /// * `input_bits` - Bit value for every wire in `wire_ids`: wire_id -> bit
+    /// * `seed_data` - 32 bytes of random seed for the receiver's secrets
+    ///
+    /// # Returns
+    /// * `Ok(BaseOtReceiver)` - Receiver with one OT per wire
+    /// * `Err(GcError)` - A wire has no bit in `input_bits`
+    pub fn for_input_bits(
+        wire_ids: &[WireId],
+        input_bits: &HashMap<WireId, bool>,
+        seed_data: &[u8; 32],
+    ) -> Result<Self> {
+        Ok(Self::new(input_choices(wire_ids, input_bits)?, seed_data))
+    }
+
+    /// Second step: answer the sender's setup message with blinded choices
+    ///
+    /// # Arguments
+    /// * `setup` - The message produced by [`BaseOtSender::setup`]
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - One public point per OT, which hides the choice bit from the sender
+    /// * `Err(GcError)` - The message is malformed, announces a different number of OTs, or arrived twice
+    pub fn choose(&mut self, setup: &[u8]) -> Result<Vec<u8>> {
+        let ReceiverState::Ready { choices } =
+            std::mem::replace(&mut self.state, ReceiverState::Finished)
+        else {
+            return Err(unexpected_step("receiver", "choose"));
+        };
+
+        if setup.len() != SETUP_MESSAGE_SIZE {
+            return Err(GcError::Protocol(format!(
+                "Setup message has {} bytes, expected {}",
+                setup.len(),
+                SETUP_MESSAGE_SIZE
+            )));
+        }
+        let count = u64::from_le_bytes(setup[..8].try_into().unwrap());
+        if count != choices.len() as u64 {
+            return Err(GcError::Protocol(format!(
+                "Sender offers {} OTs but the receiver has {} choices",
+                count,
+                choices.len()
+            )));
+        }
+        let sender_point = CompressedRistretto(setup[8..].try_into().unwrap());
+        let point = decompress(&sender_point, 0)?;
+
+        let mut message = Vec::with_capacity(choices.len() * POINT_SIZE);
+        let mut keys = Vec::with_capacity(choices.len());
+        for (index, &choice) in choices.iter().enumerate() {
+            let secret = random_scalar(&mut self.rng);
+            // B = b*G for choice 0 and A + b*G for choice 1
+            let receiver_point =
+                &secret * RISTRETTO_BASEPOINT_TABLE + Scalar::from(choice as u8) * point;
+            let compressed = receiver_point.compress();
+            keys.push(derive_key(
+                index,
+                &sender_point,
+                &compressed,
+                &(secret * point),
+            ));
+            message.extend_from_slice(compressed.as_bytes());
+        }
+
+        self.state = ReceiverState::AwaitingTransfer { choices, keys };
+        Ok(message)
+    }
+
+    /// Last step: decrypt the chosen label of every pair
+    ///
+    /// # Arguments
+    /// * `transfer` - The message produced by [`BaseOtSender::transfer`]
+    ///
+    /// # Returns
+    /// * `Ok(Vec<WireLabel>)` - The chosen label of each OT, in order
+    /// * `Err(GcError)` - The message is malformed or the choices were not sent yet
+    pub fn finish(&mut self, transfer: &[u8]) -> Result<Vec<WireLabel>> {
+        let ReceiverState::AwaitingTransfer { choices, keys } =
+            std::mem::replace(&mut self.state, ReceiverState::Finished)
+        else {
+            return Err(unexpected_step("receiver", "finish"));
+        };
+
+        if transfer.len() != choices.len() * CIPHERTEXT_SIZE {
+            return Err(GcError::Protocol(format!(
+                "Transfer message has {} bytes, expected {} for {} OTs",
+                transfer.len(),
+                choices.len() * CIPHERTEXT_SIZE,
+                choices.len()
+            )));
+        }
+
+        let labels = transfer
+            .chunks_exact(CIPHERTEXT_SIZE)
+            .zip(choices.iter().zip(&keys))
+            .map(|(ciphertexts, (&choice, key))| {
+                let offset = if choice { 16 } else { 0 };
+                let ciphertext: [u8; 16] = ciphertexts[offset..offset + 16].try_into().unwrap();
+                WireLabel::new(ciphertext).xor(key)
+            })
+            .collect();
+        Ok(labels)
+    }
+}
+
+/// Assemble the evaluator's [`OTResult`] from the labels it received
+///
+/// # Arguments
+/// * `wire_ids` - The evaluator's input wires, in the order the OTs ran
+/// * `input_bits` - The bit chosen for every wire in `wire_ids`
+/// * `labels` - The labels returned by [`BaseOtReceiver::finish`]
+/// * `session_id` - Garbling run the labels belong to
+///
+/// # Returns
+/// * `Ok(OTResult)` - The received labels with their bit values; the garbler's inputs are added later with [`OTResult::set_garbler_inputs`]
+/// * `Err(GcError)` - The number of labels differs from the number of wires, or a wire has no bit
+pub fn received_ot_result(
+    wire_ids: &[WireId],
+    input_bits: &HashMap<WireId, bool>,
+    labels: Vec<WireLabel>,
+    session_id: SessionId,
+) -> Result<OTResult> {
+    if labels.len() != wire_ids.len() {
+        return Err(GcError::Protocol(format!(
+            "Received {} labels for {} input wires",
+            labels.len(),
+            wire_ids.len()
+        )));
+    }
+
+    let mut selected_inputs = HashMap::with_capacity(wire_ids.len());
+    for (&wire_id, label) in wire_ids.iter().zip(labels) {
+        let bit_value = input_bit(input_bits, wire_id)?;
+        selected_inputs.insert(wire_id, SelectedInput { label, bit_value });
+    }
+
+    Ok(OTResult {
+        selected_inputs,
+        garbler_inputs: HashMap::new(),
+        session_id,
+    })
+}
+
+/// Run both sides of the base OT for the evaluator's inputs in one process
+///
+/// Produces the same [`OTResult`] as [`select_inputs`](crate::ot_simulation::select_inputs)
+/// would for the evaluator's wires, but the labels travel through the real
+/// protocol messages. The garbler's inputs are selected directly, as the
+/// garbler would send their labels without OT. Mostly useful for testing; a real
+/// two-party run drives [`BaseOtSender`] and [`BaseOtReceiver`] on either
+/// side of a connection.
+///
+/// # Arguments
+/// * `wire_labels` - Wire labels from garbler output containing input/output labels and delta
+/// * `input_bits` - Bit value for every evaluator input wire, and optionally every garbler input wire: wire_id -> bit
+/// * `seed_data` - 32 bytes of random seed, split into the sender's and the receiver's
+///
+/// # Returns
+/// * `Ok(OTResult)` - Selected input labels with their corresponding bit values
+/// * `Err(GcError)` - An evaluator input wire has no bit in `input_bits`, or only some garbler input wires have one
+#[tracing::instrument(name = "ot", skip_all)]
+pub fn run_base_ot(
+    wire_labels: &WireLabels,
+    input_bits: &HashMap<WireId, bool>,
+    seed_data: &[u8; 32],
+) -> Result<OTResult> {
+    let mut rng = ChaCha12Rng::from_seed(*seed_data);
+    let mut sender_seed = [0u8; 32];
+    let mut receiver_seed = [0u8; 32];
+    rng.fill_bytes(&mut sender_seed);
+    rng.fill_bytes(&mut receiver_seed);
+
+    let wire_ids = wire_labels.evaluator_input_wires();
+    let mut sender = BaseOtSender::for_wire_labels(wire_labels, &sender_seed);
+    let mut receiver = BaseOtReceiver::for_input_bits(&wire_ids, input_bits, &receiver_seed)?;
+
+    let setup = sender.setup()?;
+    let choices = receiver.choose(&setup)?;
+    let transfer = sender.transfer(&choices)?;
+    let labels = receiver.finish(&transfer)?;
+
+    let mut ot_result = received_ot_result(&wire_ids, input_bits, labels, wire_labels.session_id)?;
+    add_garbler_inputs(&mut ot_result, wire_labels, input_bits)?;
+
+    tracing::info!(
+        evaluator_inputs = ot_result.selected_inputs.len(),
+        garbler_inputs = ot_result.garbler_inputs.len(),
+        "Base OT finished"
+    );
+    Ok(ot_result)
+}
+
+/// Both labels of every evaluator input wire, ordered by wire ID
+pub(crate) fn evaluator_label_pairs(wire_labels: &WireLabels) -> Vec<(WireLabel, WireLabel)> {
+    wire_labels
+        .evaluator_input_wires()
+        .into_iter()
+        .map(|wire_id| {
+            let label_0 = wire_labels.input_labels[&wire_id];
+            (label_0, label_0.xor(&wire_labels.delta))
+        })
+        .collect()
+}
+
+/// Choice bit of every wire in `wire_ids`, in order
+pub(crate) fn input_choices(
+    wire_ids: &[WireId],
+    input_bits: &HashMap<WireId, bool>,
+) -> Result<Vec<bool>> {
+    wire_ids
+        .iter()
+        .map(|&wire_id| input_bit(input_bits, wire_id))
+        .collect()
+}
+
+/// Encode the garbler's input labels directly, as the garbler would send them
+pub(crate) fn add_garbler_inputs(
+    ot_result: &mut OTResult,
+    wire_labels: &WireLabels,
+    input_bits: &HashMap<WireId, bool>,
+) -> Result<()> {
+    ot_result.garbler_inputs = encode_garbler_inputs(wire_labels, input_bits)?;
+    Ok(())
+}
+
+/// Bit given for `wire_id`
+fn input_bit(input_bits: &HashMap<WireId, bool>, wire_id: WireId) -> Result<bool> {
+    input_bits.get(&wire_id).copied().ok_or_else(|| {
+        GcError::missing_wire(
+            wire_id,
+            format!("No input bit given for input wire {}", wire_id),
+        )
+    })
+}
+
+/// Uniformly random scalar from 64 random bytes
+fn random_scalar(rng: &mut ChaCha12Rng) -> Scalar {
+    let mut bytes = [0u8; 64];
+    rng.fill_bytes(&mut bytes);
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+/// Decompress a point received from the other party
+fn decompress(compressed: &CompressedRistretto, index: usize) -> Result<RistrettoPoint> {
+    compressed
+        .decompress()
+        .ok_or_else(|| GcError::Protocol(format!("Invalid group element for OT {}", index)))
+}
+
+/// Key for one label of OT `index`, bound to both parties' points
+fn derive_key(
+    index: usize,
+    sender_point: &CompressedRistretto,
+    receiver_point: &CompressedRistretto,
+    shared_point: &RistrettoPoint,
+) -> WireLabel {
+    let mut hasher = Sha256::new();
+    hasher.update(BASE_OT_KEY_DOMAIN);
+    hasher.update((index as u64).to_le_bytes());
+    hasher.update(sender_point.as_bytes());
+    hasher.update(receiver_point.as_bytes());
+    hasher.update(shared_point.compress().as_bytes());
+    let digest = hasher.finalize();
+    WireLabel::new(digest[..16].try_into().unwrap())
+}
+
+/// Error for a protocol step called in the wrong state
+fn unexpected_step(party: &str, step: &str) -> GcError {
+    GcError::Protocol(format!(
+        "Base OT {} cannot {} in its current state",
+        party, step
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ot_simulation::select_inputs;
+
+    fn test_pairs(count: u8) -> Vec<(WireLabel, WireLabel)> {
+        (0..count)
+            .map(|i| (WireLabel::new([i; 16]), WireLabel::new([i | 0x80; 16])))
+            .collect()
+    }
+
+    #[test]
+    fn test_base_ot_transfers_chosen_labels() -> Result<()> {
+        let pairs = test_pairs(8);
+        let choices = vec![false, true, true, false, true, false, false, true];
+        let mut sender = BaseOtSender::new(pairs.clone(), &[1; 32]);
+        let mut receiver = BaseOtReceiver::new(choices.clone(), &[2; 32]);
+
+        let setup = sender.setup()?;
+        let choice_message = receiver.choose(&setup)?;
+        let transfer = sender.transfer(&choice_message)?;
+        let labels = receiver.finish(&transfer)?;
+
+        for ((label, &choice), (label_0, label_1)) in labels.iter().zip(&choices).zip(&pairs) {
+            assert_eq!(label, if choice { label_1 } else { label_0 });
+        }
+        // The label not chosen stays hidden
+        for (label, (label_0, label_1)) in labels.iter().zip(&pairs) {
+            assert!(label != label_0 || label != label_1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_base_ot_rejects_bad_messages() -> Result<()> {
+        let mut sender = BaseOtSender::new(test_pairs(2), &[1; 32]);
+        assert!(matches!(sender.transfer(&[]), Err(GcError::Protocol(_))));
+
+        let mut sender = BaseOtSender::new(test_pairs(2), &[1; 32]);
+        let setup = sender.setup()?;
+        assert!(sender.setup().is_err());
+
+        // Count mismatch
+        let mut receiver = BaseOtReceiver::new(vec![true; 3], &[2; 32]);
+        let error_msg = receiver.choose(&setup).unwrap_err().to_string();
+        assert!(error_msg.contains("Sender offers 2 OTs but the receiver has 3 choices"));
+
+        // Points that don't decode
+        let mut receiver = BaseOtReceiver::new(vec![true; 2], &[2; 32]);
+        let mut bad_setup = setup.clone();
+        bad_setup[8..].fill(0xFF);
+        assert!(matches!(
+            receiver.choose(&bad_setup),
+            Err(GcError::Protocol(_))
+        ));
+
+        let mut sender = BaseOtSender::new(test_pairs(2), &[1; 32]);
+        sender.setup()?;
+        assert!(sender.transfer(&[0xFF; 64]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_base_ot_matches_select_inputs() -> Result<()> {
+        let label_0 = WireLabel::new([0x01; 16]);
+        let delta = WireLabel::new([0xFF; 16]);
+        let wire_labels = WireLabels {
+            input_labels: HashMap::from([(0, label_0), (1, label_0), (2, label_0)]),
+            output_labels: HashMap::new(),
+            delta,
+            garbler_input_wires: vec![1],
+            session_id: SessionId::default(),
+        };
+        let bits = HashMap::from([(0, true), (1, true), (2, false)]);
+
+        let ot_result = run_base_ot(&wire_labels, &bits, &[7; 32])?;
+        let expected = select_inputs(&wire_labels, &bits)?;
+        for (wire_id, label) in expected.inputs() {
+            assert_eq!(ot_result.input_label(wire_id), Some(label));
+        }
+        for (wire_id, input) in &expected.selected_inputs {
+            assert_eq!(
+                ot_result.selected_inputs[wire_id].bit_value,
+                input.bit_value
+            );
+        }
+        assert_eq!(ot_result.selected_inputs.len(), 2);
+        assert_eq!(ot_result.garbler_inputs.len(), 1);
+        Ok(())
+    }
+}
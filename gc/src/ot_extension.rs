@@ -0,0 +1,1383 @@
+use crate::error::{GcError, Result};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::garbler::{WireLabel, WireLabels};
+use crate::gate::WireId;
+use crate::ot::{
+    BaseOtReceiver, BaseOtSender, add_garbler_inputs, evaluator_label_pairs, input_choices,
+    received_ot_result,
+};
+use crate::ot_simulation::OTResult;
+use crate::random_ot::{RandomOtReceiver, RandomOtSender};
+use crate::soft_spoken::{expand_tree, punctured_tree, receiver_vole, sender_vole};
+
+/// Domain separation tag for the pseudorandom expansion of base OT seeds
+const PRG_DOMAIN: &[u8] = b"gc-ot-extension-prg-v1";
+
+/// Domain separation tag for the keys that encrypt transferred labels
+const KEY_DOMAIN: &[u8] = b"gc-ot-extension-key-v1";
+
+/// Domain separation tag for the KOS challenge derived from the extension message
+const CHALLENGE_DOMAIN: &[u8] = b"gc-ot-extension-kos-challenge-v1";
+
+/// Number of base OTs, the computational security parameter
+const BASE_OTS: usize = 128;
+
+/// Extra OTs run for the KOS check: the computational plus the statistical
+/// security parameter (128 + 64). Their random choice bits mask the real
+/// choices in the check values sent to the garbler.
+const KOS_PADDING: usize = 192;
+
+/// Size of the extension message header: the security level, the backend and the OT count
+const EXTENSION_HEADER_SIZE: usize = 1 + 1 + 8;
+
+/// Size of a base OT transfer message for [`BASE_OTS`] seeds
+const BASE_TRANSFER_SIZE: usize = BASE_OTS * 32;
+
+/// Size of the KOS check values: the masked choices and the combined rows
+const CHECK_SIZE: usize = 32;
+
+/// Transfer message kind: both labels of each pair, encrypted
+const TRANSFER_CHOSEN: u8 = 0;
+
+/// Transfer message kind: one correction word per OT, for labels that differ by delta
+const TRANSFER_CORRELATED: u8 = 1;
+
+/// Security level of the OT extension
+///
+/// Both parties must use the same level; a mismatch is reported as a
+/// [`GcError::Protocol`] error by the sender.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OtSecurity {
+    /// Plain IKNP extension, secure as long as both parties follow the protocol
+    #[default]
+    SemiHonest,
+    /// IKNP with the KOS consistency check, so a receiver that deviates from
+    /// the protocol to learn both labels of a wire is caught by the sender
+    Malicious,
+}
+
+impl OtSecurity {
+    /// Identifier of the security level in protocol messages
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            OtSecurity::SemiHonest => 0,
+            OtSecurity::Malicious => 1,
+        }
+    }
+
+    /// Security level for an identifier from a protocol message
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(OtSecurity::SemiHonest),
+            1 => Some(OtSecurity::Malicious),
+            _ => None,
+        }
+    }
+
+    /// Number of OTs extended for `count` real ones, rounded up to whole bytes
+    fn extended_count(self, count: usize) -> usize {
+        let padding = match self {
+            OtSecurity::SemiHonest => 0,
+            OtSecurity::Malicious => KOS_PADDING,
+        };
+        (count + padding).div_ceil(8) * 8
+    }
+}
+
+impl FromStr for OtSecurity {
+    type Err = GcError;
+
+    /// Parse `semi-honest` or `malicious`
+    fn from_str(spec: &str) -> Result<Self> {
+        match spec {
+            "semi-honest" => Ok(OtSecurity::SemiHonest),
+            "malicious" => Ok(OtSecurity::Malicious),
+            _ => Err(GcError::InvalidArgument(format!(
+                "Invalid OT security level '{}': expected semi-honest or malicious",
+                spec
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for OtSecurity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtSecurity::SemiHonest => write!(f, "semi-honest"),
+            OtSecurity::Malicious => write!(f, "malicious"),
+        }
+    }
+}
+
+/// How the base OTs are stretched into the extension matrix
+///
+/// Both parties must use the same backend; a mismatch is reported as a
+/// [`GcError::Protocol`] error by the sender. Either backend works with both
+/// security levels and with correlated transfers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OtExtensionBackend {
+    /// Ishai-Kilian-Nissim-Petrank: the receiver sends one matrix column per base OT
+    #[default]
+    Iknp,
+    /// SoftSpokenOT: the base OTs are grouped into blocks of `k` and turned
+    /// into a small-field VOLE over GF(2^k) via punctured GGM trees, so the
+    /// receiver sends one column per block. This cuts the extension traffic
+    /// by a factor of `k` at the cost of `2^k / k` times as much hashing.
+    /// `k` must be 1, 2, 4 or 8; `k = 1` sends as much as IKNP.
+    SoftSpoken {
+        /// Bits per field element, the communication/computation tradeoff
+        k: u8,
+    },
+}
+
+impl OtExtensionBackend {
+    /// Identifier of the backend in protocol messages: 0 for IKNP, else `k`
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            OtExtensionBackend::Iknp => 0,
+            OtExtensionBackend::SoftSpoken { k } => k,
+        }
+    }
+
+    /// Backend for an identifier from a protocol message
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(OtExtensionBackend::Iknp),
+            1 | 2 | 4 | 8 => Some(OtExtensionBackend::SoftSpoken { k: id }),
+            _ => None,
+        }
+    }
+
+    /// Reject a SoftSpoken block size that doesn't divide the base OTs
+    fn validate(self) -> Result<()> {
+        match Self::from_id(self.id()) {
+            Some(_) => Ok(()),
+            None => Err(GcError::InvalidArgument(format!(
+                "Invalid SoftSpokenOT block size {}: expected 1, 2, 4 or 8",
+                self.id()
+            ))),
+        }
+    }
+
+    /// Number of columns the receiver sends
+    fn matrix_columns(self) -> usize {
+        match self {
+            OtExtensionBackend::Iknp => BASE_OTS,
+            OtExtensionBackend::SoftSpoken { k } => BASE_OTS / k as usize,
+        }
+    }
+}
+
+impl FromStr for OtExtensionBackend {
+    type Err = GcError;
+
+    /// Parse `iknp` or `softspoken-K`, e.g. `softspoken-4`
+    fn from_str(spec: &str) -> Result<Self> {
+        let backend = if spec == "iknp" {
+            Some(OtExtensionBackend::Iknp)
+        } else {
+            spec.strip_prefix("softspoken-")
+                .and_then(|k| k.parse().ok())
+                .filter(|&k| k != 0)
+                .and_then(OtExtensionBackend::from_id)
+        };
+        backend.ok_or_else(|| {
+            GcError::InvalidArgument(format!(
+                "Invalid OT extension backend '{}': expected iknp or softspoken-K with K in 1, 2, 4, 8",
+                spec
+            ))
+        })
+    }
+}
+
+impl fmt::Display for OtExtensionBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtExtensionBackend::Iknp => write!(f, "iknp"),
+            OtExtensionBackend::SoftSpoken { k } => write!(f, "softspoken-{}", k),
+        }
+    }
+}
+
+/// Sender side of the OT extension, run by the garbler
+///
+/// Extends 128 base OTs (with reversed roles) into any number of
+/// 1-out-of-2 OTs using only hashing, following Ishai-Kilian-Nissim-Petrank.
+/// With [`OtSecurity::Malicious`] the receiver additionally proves that it
+/// used the same choice bits in every base OT column (Keller-Orsini-Scholl),
+/// with the challenge derived from a hash of its extension message. The
+/// protocol takes four byte messages:
+///
+/// 1. [`OtExtensionReceiver::setup`] starts the base OTs
+/// 2. [`OtExtensionSender::choose`] answers with the base OT choices
+/// 3. [`OtExtensionReceiver::extend`] finishes the base OTs and sends the extension matrix
+/// 4. [`OtExtensionSender::transfer`] checks it and sends the encrypted pairs,
+///    which [`OtExtensionReceiver::finish`] decrypts
+///
+/// A sender created with [`OtExtensionSender::new_correlated`] transfers
+/// labels that differ by a fixed delta, as free-XOR wire labels do. It uses
+/// the delta itself as the secret IKNP correlation, so every matrix row
+/// already hides `label_0` or `label_0 xor delta` and the transfer needs a
+/// single 16-byte correction word per OT instead of two ciphertexts.
+///
+/// A sender created with [`OtExtensionSender::new_random`] stops after the
+/// third message: both parties derive random messages from their matrix rows,
+/// which [`crate::random_ot`] later adjusts to the real labels.
+///
+/// The extension runs on IKNP unless another [`OtExtensionBackend`] is
+/// chosen with [`OtExtensionSender::with_backend`].
+#[derive(Debug)]
+pub struct OtExtensionSender {
+    rng: ChaCha12Rng,
+    security: OtSecurity,
+    backend: OtExtensionBackend,
+    state: SenderState,
+}
+
+/// Labels offered by the sender
+#[derive(Debug)]
+enum SenderLabels {
+    /// Two independent labels per OT
+    Chosen(Vec<(WireLabel, WireLabel)>),
+    /// `label_0` per OT, with `label_1 = label_0 xor delta`
+    Correlated {
+        label_0s: Vec<WireLabel>,
+        delta: WireLabel,
+    },
+    /// No labels: the protocol picks both messages of this many OTs
+    Random(usize),
+}
+
+impl SenderLabels {
+    /// Number of OTs
+    fn len(&self) -> usize {
+        match self {
+            SenderLabels::Chosen(pairs) => pairs.len(),
+            SenderLabels::Correlated { label_0s, .. } => label_0s.len(),
+            SenderLabels::Random(count) => *count,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SenderState {
+    /// Holds the labels until the base OT setup arrives
+    Ready { labels: SenderLabels },
+    /// Base OT choices sent, waiting for the extension message
+    AwaitingExtension {
+        labels: SenderLabels,
+        correlation: u128,
+        base_receiver: Box<BaseOtReceiver>,
+    },
+    /// Encrypted pairs sent
+    Finished,
+}
+
+impl OtExtensionSender {
+    /// Create a sender transferring one label of each pair
+    ///
+    /// # Arguments
+    /// * `pairs` - The two labels offered in each OT, indexed by choice bit
+    /// * `security` - Security level, which must match the receiver's
+    /// * `seed_data` - 32 bytes of random seed for the sender's secrets
+    pub fn new(
+        pairs: Vec<(WireLabel, WireLabel)>,
+        security: OtSecurity,
+        seed_data: &[u8; 32],
+    ) -> Self {
+        Self {
+            rng: ChaCha12Rng::from_seed(*seed_data),
+            security,
+            backend: OtExtensionBackend::Iknp,
+            state: SenderState::Ready {
+                labels: SenderLabels::Chosen(pairs),
+            },
+        }
+    }
+
+    /// Create a sender transferring `label_0` or `label_0 xor delta` in each OT
+    ///
+    /// # Arguments
+    /// * `label_0s` - The label selected by choice bit `false` in each OT
+    /// * `delta` - The offset of every label selected by choice bit `true`
+    /// * `security` - Security level, which must match the receiver's
+    /// * `seed_data` - 32 bytes of random seed for the sender's secrets
+    pub fn new_correlated(
+        label_0s: Vec<WireLabel>,
+        delta: WireLabel,
+        security: OtSecurity,
+        seed_data: &[u8; 32],
+    ) -> Self {
+        Self {
+            rng: ChaCha12Rng::from_seed(*seed_data),
+            security,
+            backend: OtExtensionBackend::Iknp,
+            state: SenderState::Ready {
+                labels: SenderLabels::Correlated { label_0s, delta },
+            },
+        }
+    }
+
+    /// Create a sender for `count` random OTs, whose messages the protocol picks
+    ///
+    /// Needs no labels, so it can run before the circuit is garbled; finish it
+    /// with [`OtExtensionSender::finish_random`] instead of
+    /// [`OtExtensionSender::transfer`].
+    ///
+    /// # Arguments
+    /// * `count` - Number of OTs
+    /// * `security` - Security level, which must match the receiver's
+    /// * `seed_data` - 32 bytes of random seed for the sender's secrets
+    pub fn new_random(count: usize, security: OtSecurity, seed_data: &[u8; 32]) -> Self {
+        Self {
+            rng: ChaCha12Rng::from_seed(*seed_data),
+            security,
+            backend: OtExtensionBackend::Iknp,
+            state: SenderState::Ready {
+                labels: SenderLabels::Random(count),
+            },
+        }
+    }
+
+    /// Create a sender offering both labels of every evaluator input wire
+    ///
+    /// Wires are ordered by wire ID, as returned by
+    /// [`WireLabels::evaluator_input_wires`]. Since free-XOR labels differ by
+    /// the global delta, the transfer is correlated.
+    pub fn for_wire_labels(
+        wire_labels: &WireLabels,
+        security: OtSecurity,
+        seed_data: &[u8; 32],
+    ) -> Self {
+        let label_0s = evaluator_label_pairs(wire_labels)
+            .into_iter()
+            .map(|(label_0, _)| label_0)
+            .collect();
+        Self::new_correlated(label_0s, wire_labels.delta, security, seed_data)
+    }
+
+    /// Use `backend` to extend the base OTs, which must match the receiver's
+    pub fn with_backend(mut self, backend: OtExtensionBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Second step: pick the secret correlation and answer the base OT setup
+    ///
+    /// # Arguments
+    /// * `base_setup` - The message produced by [`OtExtensionReceiver::setup`]
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - The base OT choice message
+    /// * `Err(GcError)` - The message is malformed or arrived twice
+    pub fn choose(&mut self, base_setup: &[u8]) -> Result<Vec<u8>> {
+        let SenderState::Ready { labels } =
+            std::mem::replace(&mut self.state, SenderState::Finished)
+        else {
+            return Err(unexpected_step("sender", "choose"));
+        };
+
+        self.backend.validate()?;
+        let correlation = match &labels {
+            SenderLabels::Chosen(_) | SenderLabels::Random(_) => random_block(&mut self.rng),
+            SenderLabels::Correlated { delta, .. } => u128::from_le_bytes(*delta.as_bytes()),
+        };
+        let choices = match self.backend {
+            OtExtensionBackend::Iknp => {
+                (0..BASE_OTS).map(|j| (correlation >> j) & 1 == 1).collect()
+            }
+            OtExtensionBackend::SoftSpoken { k } => {
+                // Per block, learn every tree level's sum off the path to delta_b
+                let k = k as usize;
+                (0..BASE_OTS)
+                    .map(|j| {
+                        let delta = block_delta(correlation, j / k, k);
+                        (delta >> (k - 1 - j % k)) & 1 == 0
+                    })
+                    .collect()
+            }
+        };
+        let mut base_seed = [0u8; 32];
+        self.rng.fill_bytes(&mut base_seed);
+        let mut base_receiver = BaseOtReceiver::new(choices, &base_seed);
+        let message = base_receiver.choose(base_setup)?;
+
+        self.state = SenderState::AwaitingExtension {
+            labels,
+            correlation,
+            base_receiver: Box::new(base_receiver),
+        };
+        Ok(message)
+    }
+
+    /// Fourth step: derive the extended OTs and encrypt the labels
+    ///
+    /// # Arguments
+    /// * `extension` - The message produced by [`OtExtensionReceiver::extend`]
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - The transfer kind followed by both labels of every pair, each encrypted under its own key, or by one correction word per correlated OT
+    /// * `Err(GcError)` - The message is malformed, uses another security level or OT count, or fails the KOS check
+    pub fn transfer(&mut self, extension: &[u8]) -> Result<Vec<u8>> {
+        let (labels, correlation, rows) = self.extension_rows(extension, "transfer")?;
+
+        let message = match &labels {
+            SenderLabels::Chosen(pairs) => {
+                let mut message = Vec::with_capacity(1 + pairs.len() * 32);
+                message.push(TRANSFER_CHOSEN);
+                for (index, ((label_0, label_1), &row)) in pairs.iter().zip(&rows).enumerate() {
+                    message.extend_from_slice(label_0.xor(&derive_key(index, row)).as_bytes());
+                    message.extend_from_slice(
+                        label_1
+                            .xor(&derive_key(index, row ^ correlation))
+                            .as_bytes(),
+                    );
+                }
+                message
+            }
+            SenderLabels::Correlated { label_0s, .. } => {
+                // q_i = t_i xor r_i * delta, so label_0 xor q_i xor t_i is the chosen label
+                let mut message = Vec::with_capacity(1 + label_0s.len() * 16);
+                message.push(TRANSFER_CORRELATED);
+                for (label_0, &row) in label_0s.iter().zip(&rows) {
+                    let correction = u128::from_le_bytes(*label_0.as_bytes()) ^ row;
+                    message.extend_from_slice(&correction.to_le_bytes());
+                }
+                message
+            }
+            SenderLabels::Random(_) => {
+                return Err(GcError::Protocol(
+                    "A random OT sender has no labels to transfer; use finish_random".to_string(),
+                ));
+            }
+        };
+
+        tracing::debug!(
+            ots = labels.len(),
+            correlated = matches!(labels, SenderLabels::Correlated { .. }),
+            security = ?self.security,
+            backend = %self.backend,
+            "OT extension sender finished"
+        );
+        Ok(message)
+    }
+
+    /// Last step of a random OT: derive both random messages of every OT
+    ///
+    /// Takes the place of [`OtExtensionSender::transfer`] for a sender created
+    /// with [`OtExtensionSender::new_random`]; nothing is sent back.
+    ///
+    /// # Arguments
+    /// * `extension` - The message produced by [`OtExtensionReceiver::extend`]
+    ///
+    /// # Returns
+    /// * `Ok(RandomOtSender)` - Both messages of every OT, to be derandomized once the labels are known
+    /// * `Err(GcError)` - The message is malformed, uses another security level or OT count, or fails the KOS check
+    pub fn finish_random(&mut self, extension: &[u8]) -> Result<RandomOtSender> {
+        let (labels, correlation, rows) = self.extension_rows(extension, "finish_random")?;
+        let SenderLabels::Random(count) = labels else {
+            return Err(GcError::Protocol(
+                "Only a sender created with new_random finishes a random OT".to_string(),
+            ));
+        };
+
+        let pairs = rows
+            .iter()
+            .take(count)
+            .enumerate()
+            .map(|(index, &row)| (derive_key(index, row), derive_key(index, row ^ correlation)))
+            .collect();
+        tracing::debug!(
+            ots = count,
+            security = ?self.security,
+            backend = %self.backend,
+            "Random OT sender finished"
+        );
+        Ok(RandomOtSender::new(pairs))
+    }
+
+    /// Check the extension message and derive the sender's matrix rows
+    ///
+    /// # Returns
+    /// The sender's labels, the secret correlation and one row per extended OT
+    fn extension_rows(
+        &mut self,
+        extension: &[u8],
+        step: &str,
+    ) -> Result<(SenderLabels, u128, Vec<u128>)> {
+        let SenderState::AwaitingExtension {
+            labels,
+            correlation,
+            mut base_receiver,
+        } = std::mem::replace(&mut self.state, SenderState::Finished)
+        else {
+            return Err(unexpected_step("sender", step));
+        };
+
+        let extended = self.security.extended_count(labels.len());
+        let column_size = extended / 8;
+        let check_size = match self.security {
+            OtSecurity::SemiHonest => 0,
+            OtSecurity::Malicious => CHECK_SIZE,
+        };
+        let matrix_columns = self.backend.matrix_columns();
+        let expected_size =
+            EXTENSION_HEADER_SIZE + BASE_TRANSFER_SIZE + matrix_columns * column_size + check_size;
+        if extension.len() < EXTENSION_HEADER_SIZE {
+            return Err(GcError::Protocol(format!(
+                "Extension message has {} bytes, expected {}",
+                extension.len(),
+                expected_size
+            )));
+        }
+        if extension[0] != self.security.id() {
+            return Err(GcError::Protocol(format!(
+                "Receiver uses OT security level {}, sender uses {:?}",
+                extension[0], self.security
+            )));
+        }
+        if extension[1] != self.backend.id() {
+            return Err(GcError::Protocol(format!(
+                "Receiver uses OT extension backend {}, sender uses {}",
+                OtExtensionBackend::from_id(extension[1]).map_or_else(
+                    || format!("id {}", extension[1]),
+                    |backend| backend.to_string()
+                ),
+                self.backend
+            )));
+        }
+        let count = u64::from_le_bytes(extension[2..EXTENSION_HEADER_SIZE].try_into().unwrap());
+        if count != labels.len() as u64 {
+            return Err(GcError::Protocol(format!(
+                "Receiver asks for {} OTs but the sender has {} pairs",
+                count,
+                labels.len()
+            )));
+        }
+        if extension.len() != expected_size {
+            return Err(GcError::Protocol(format!(
+                "Extension message has {} bytes, expected {}",
+                extension.len(),
+                expected_size
+            )));
+        }
+
+        let (base_transfer, rest) = extension[EXTENSION_HEADER_SIZE..].split_at(BASE_TRANSFER_SIZE);
+        let (matrix, check) = rest.split_at(matrix_columns * column_size);
+        let seeds = base_receiver.finish(base_transfer)?;
+
+        let columns: Vec<Vec<u8>> = match self.backend {
+            // q_j = G(k_j^{s_j}) xor s_j * u_j = t_j xor s_j * r
+            OtExtensionBackend::Iknp => seeds
+                .iter()
+                .zip(matrix.chunks_exact(column_size))
+                .enumerate()
+                .map(|(j, (seed, u))| {
+                    let mut column = expand(seed, column_size);
+                    if (correlation >> j) & 1 == 1 {
+                        xor_into(&mut column, u);
+                    }
+                    column
+                })
+                .collect(),
+            // q_{b,j} = w_{b,j} xor delta_{b,j} * c_b = t_{b,j} xor delta_{b,j} * r
+            OtExtensionBackend::SoftSpoken { k } => {
+                let k = k as usize;
+                let mut columns = Vec::with_capacity(BASE_OTS);
+                for (block, (level_keys, correction)) in seeds
+                    .chunks_exact(k)
+                    .zip(matrix.chunks_exact(column_size))
+                    .enumerate()
+                {
+                    let delta = block_delta(correlation, block, k);
+                    let leaves = punctured_tree(delta, k, level_keys);
+                    for (j, mut column) in sender_vole(&leaves, delta, column_size)
+                        .into_iter()
+                        .enumerate()
+                    {
+                        if (delta >> j) & 1 == 1 {
+                            xor_into(&mut column, correction);
+                        }
+                        columns.push(column);
+                    }
+                }
+                columns
+            }
+        };
+        let rows = transpose(&columns, extended);
+
+        if self.security == OtSecurity::Malicious {
+            let masked_choices = u128::from_le_bytes(check[..16].try_into().unwrap());
+            let combined_rows = u128::from_le_bytes(check[16..].try_into().unwrap());
+            let mut challenges = challenge_rng(matrix, extended);
+            let combined = rows.iter().fold(0u128, |acc, &row| {
+                acc ^ gf128_mul(row, random_block(&mut challenges))
+            });
+            if combined != combined_rows ^ gf128_mul(masked_choices, correlation) {
+                return Err(GcError::Protocol(
+                    "KOS consistency check failed: the receiver used inconsistent choice bits"
+                        .to_string(),
+                ));
+            }
+        }
+
+        Ok((labels, correlation, rows))
+    }
+}
+
+/// Receiver side of the OT extension, run by the evaluator
+///
+/// Counterpart of [`OtExtensionSender`]; see there for the message flow.
+#[derive(Debug)]
+pub struct OtExtensionReceiver {
+    rng: ChaCha12Rng,
+    security: OtSecurity,
+    backend: OtExtensionBackend,
+    state: ReceiverState,
+}
+
+#[derive(Debug)]
+enum ReceiverState {
+    /// Holds the choice bits until the base OTs start
+    Ready { choices: Vec<bool> },
+    /// Base OT setup sent, waiting for the sender's base OT choices
+    AwaitingBaseChoices {
+        choices: Vec<bool>,
+        seeds: Vec<(WireLabel, WireLabel)>,
+        /// Leaves of each block's GGM tree, for SoftSpokenOT only
+        leaves: Vec<Vec<WireLabel>>,
+        base_sender: Box<BaseOtSender>,
+    },
+    /// Extension message sent, waiting for the encrypted pairs
+    AwaitingTransfer { choices: Vec<bool>, rows: Vec<u128> },
+    /// Labels decrypted
+    Finished,
+}
+
+impl OtExtensionReceiver {
+    /// Create a receiver selecting one label per OT
+    ///
+    /// # Arguments
+    /// * `choices` - The choice bit of each OT: `false` selects the first label of the pair
+    /// * `security` - Security level, which must match the sender's
+    /// * `seed_data` - 32 bytes of random seed for the receiver's secrets
+    pub fn new(choices: Vec<bool>, security: OtSecurity, seed_data: &[u8; 32]) -> Self {
+        Self {
+            rng: ChaCha12Rng::from_seed(*seed_data),
+            security,
+            backend: OtExtensionBackend::Iknp,
+            state: ReceiverState::Ready { choices },
+        }
+    }
+
+    /// Create a receiver for `count` random OTs with random choice bits
+    ///
+    /// Counterpart of [`OtExtensionSender::new_random`]; finish it with
+    /// [`OtExtensionReceiver::finish_random`] right after
+    /// [`OtExtensionReceiver::extend`].
+    ///
+    /// # Arguments
+    /// * `count` - Number of OTs
+    /// * `security` - Security level, which must match the sender's
+    /// * `seed_data` - 32 bytes of random seed for the choices and the receiver's secrets
+    pub fn new_random(count: usize, security: OtSecurity, seed_data: &[u8; 32]) -> Self {
+        let mut rng = ChaCha12Rng::from_seed(*seed_data);
+        let choices = (0..count).map(|_| rng.next_u32() & 1 == 1).collect();
+        Self {
+            rng,
+            security,
+            backend: OtExtensionBackend::Iknp,
+            state: ReceiverState::Ready { choices },
+        }
+    }
+
+    /// Use `backend` to extend the base OTs, which must match the sender's
+    pub fn with_backend(mut self, backend: OtExtensionBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Create a receiver choosing the evaluator's input bits
+    ///
+    /// # Arguments
+    /// * `wire_ids` - The evaluator's input wires in ascending order, matching [`OtExtensionSender::for_wire_labels`]
+    /// * `input_bits` - Bit value for every wire in `wire_ids`: wire_id -> bit
+    /// * `security` - Security level, which must match the sender's
+    /// * `seed_data` - 32 bytes of random seed for the receiver's secrets
+    ///
+    /// # Returns
+    /// * `Ok(OtExtensionReceiver)` - Receiver with one OT per wire
+    /// * `Err(GcError)` - A wire has no bit in `input_bits`
+    pub fn for_input_bits(
+        wire_ids: &[WireId],
+        input_bits: &HashMap<WireId, bool>,
+        security: OtSecurity,
+        seed_data: &[u8; 32],
+    ) -> Result<Self> {
+        Ok(Self::new(
+            input_choices(wire_ids, input_bits)?,
+            security,
+            seed_data,
+        ))
+    }
+
+    /// First step: start the base OTs, acting as their sender
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - The base OT setup message
+    /// * `Err(GcError)` - The setup message was already produced
+    pub fn setup(&mut self) -> Result<Vec<u8>> {
+        let ReceiverState::Ready { choices } =
+            std::mem::replace(&mut self.state, ReceiverState::Finished)
+        else {
+            return Err(unexpected_step("receiver", "setup"));
+        };
+        self.backend.validate()?;
+
+        let mut leaves = Vec::new();
+        let seeds: Vec<(WireLabel, WireLabel)> = match self.backend {
+            OtExtensionBackend::Iknp => (0..BASE_OTS)
+                .map(|_| {
+                    (
+                        WireLabel::random(&mut self.rng),
+                        WireLabel::random(&mut self.rng),
+                    )
+                })
+                .collect(),
+            // One GGM tree per block, offering the sums of each level's children
+            OtExtensionBackend::SoftSpoken { k } => {
+                let mut seeds = Vec::with_capacity(BASE_OTS);
+                for _ in 0..BASE_OTS / k as usize {
+                    let (tree_leaves, level_sums) =
+                        expand_tree(&WireLabel::random(&mut self.rng), k as usize);
+                    leaves.push(tree_leaves);
+                    seeds.extend(level_sums);
+                }
+                seeds
+            }
+        };
+        let mut base_seed = [0u8; 32];
+        self.rng.fill_bytes(&mut base_seed);
+        let mut base_sender = BaseOtSender::new(seeds.clone(), &base_seed);
+        let message = base_sender.setup()?;
+
+        self.state = ReceiverState::AwaitingBaseChoices {
+            choices,
+            seeds,
+            leaves,
+            base_sender: Box::new(base_sender),
+        };
+        Ok(message)
+    }
+
+    /// Third step: finish the base OTs and send the extension matrix
+    ///
+    /// # Arguments
+    /// * `base_choices` - The message produced by [`OtExtensionSender::choose`]
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - Security level, backend, OT count, base OT transfer, the matrix columns and, with [`OtSecurity::Malicious`], the KOS check values
+    /// * `Err(GcError)` - The message is malformed or the base OTs were not started yet
+    pub fn extend(&mut self, base_choices: &[u8]) -> Result<Vec<u8>> {
+        let ReceiverState::AwaitingBaseChoices {
+            choices,
+            seeds,
+            leaves,
+            mut base_sender,
+        } = std::mem::replace(&mut self.state, ReceiverState::Finished)
+        else {
+            return Err(unexpected_step("receiver", "extend"));
+        };
+        let base_transfer = base_sender.transfer(base_choices)?;
+
+        // Real choices followed by random padding, packed into bits
+        let extended = self.security.extended_count(choices.len());
+        let column_size = extended / 8;
+        let mut packed_choices = vec![0u8; column_size];
+        self.rng.fill_bytes(&mut packed_choices);
+        for (i, &choice) in choices.iter().enumerate() {
+            let mask = 1 << (i % 8);
+            if choice {
+                packed_choices[i / 8] |= mask;
+            } else {
+                packed_choices[i / 8] &= !mask;
+            }
+        }
+
+        let mut message = Vec::with_capacity(
+            EXTENSION_HEADER_SIZE
+                + BASE_TRANSFER_SIZE
+                + self.backend.matrix_columns() * column_size
+                + CHECK_SIZE,
+        );
+        message.push(self.security.id());
+        message.push(self.backend.id());
+        message.extend_from_slice(&(choices.len() as u64).to_le_bytes());
+        message.extend_from_slice(&base_transfer);
+
+        let mut columns = Vec::with_capacity(BASE_OTS);
+        match self.backend {
+            // t_j = G(k_j^0), u_j = t_j xor G(k_j^1) xor r
+            OtExtensionBackend::Iknp => {
+                for (seed_0, seed_1) in &seeds {
+                    let column = expand(seed_0, column_size);
+                    let mut u = expand(seed_1, column_size);
+                    xor_into(&mut u, &column);
+                    xor_into(&mut u, &packed_choices);
+                    message.extend_from_slice(&u);
+                    columns.push(column);
+                }
+            }
+            // t_{b,j} = v_{b,j}, c_b = u_b xor r
+            OtExtensionBackend::SoftSpoken { .. } => {
+                for tree_leaves in &leaves {
+                    let (mut u, v) = receiver_vole(tree_leaves, column_size);
+                    xor_into(&mut u, &packed_choices);
+                    message.extend_from_slice(&u);
+                    columns.extend(v);
+                }
+            }
+        }
+        let rows = transpose(&columns, extended);
+
+        if self.security == OtSecurity::Malicious {
+            let matrix = &message[EXTENSION_HEADER_SIZE + BASE_TRANSFER_SIZE..];
+            let mut challenges = challenge_rng(matrix, extended);
+            let mut masked_choices = 0u128;
+            let mut combined_rows = 0u128;
+            for (i, &row) in rows.iter().enumerate() {
+                let challenge = random_block(&mut challenges);
+                if (packed_choices[i / 8] >> (i % 8)) & 1 == 1 {
+                    masked_choices ^= challenge;
+                }
+                combined_rows ^= gf128_mul(row, challenge);
+            }
+            message.extend_from_slice(&masked_choices.to_le_bytes());
+            message.extend_from_slice(&combined_rows.to_le_bytes());
+        }
+
+        let mut rows = rows;
+        rows.truncate(choices.len());
+        self.state = ReceiverState::AwaitingTransfer { choices, rows };
+        Ok(message)
+    }
+
+    /// Last step: decrypt the chosen label of every OT
+    ///
+    /// Handles both chosen and correlated transfers, as announced by the
+    /// first byte of the message.
+    ///
+    /// # Arguments
+    /// * `transfer` - The message produced by [`OtExtensionSender::transfer`]
+    ///
+    /// # Returns
+    /// * `Ok(Vec<WireLabel>)` - The chosen label of each OT, in order
+    /// * `Err(GcError)` - The message is malformed or the extension message was not sent yet
+    pub fn finish(&mut self, transfer: &[u8]) -> Result<Vec<WireLabel>> {
+        let ReceiverState::AwaitingTransfer { choices, rows } =
+            std::mem::replace(&mut self.state, ReceiverState::Finished)
+        else {
+            return Err(unexpected_step("receiver", "finish"));
+        };
+
+        let Some((&kind, body)) = transfer.split_first() else {
+            return Err(GcError::Protocol("Transfer message is empty".to_string()));
+        };
+        let word_size = match kind {
+            TRANSFER_CHOSEN => 32,
+            TRANSFER_CORRELATED => 16,
+            _ => {
+                return Err(GcError::Protocol(format!(
+                    "Unknown OT transfer kind {}",
+                    kind
+                )));
+            }
+        };
+        if body.len() != choices.len() * word_size {
+            return Err(GcError::Protocol(format!(
+                "Transfer message has {} bytes, expected {} for {} OTs",
+                transfer.len(),
+                1 + choices.len() * word_size,
+                choices.len()
+            )));
+        }
+
+        let labels = body
+            .chunks_exact(word_size)
+            .zip(choices.iter().zip(&rows))
+            .enumerate()
+            .map(|(index, (words, (&choice, &row)))| {
+                if kind == TRANSFER_CORRELATED {
+                    let correction = u128::from_le_bytes(words.try_into().unwrap());
+                    return WireLabel::new((correction ^ row).to_le_bytes());
+                }
+                let offset = if choice { 16 } else { 0 };
+                let ciphertext: [u8; 16] = words[offset..offset + 16].try_into().unwrap();
+                WireLabel::new(ciphertext).xor(&derive_key(index, row))
+            })
+            .collect();
+        Ok(labels)
+    }
+
+    /// Last step of a random OT: the random choices and their messages
+    ///
+    /// Takes the place of [`OtExtensionReceiver::finish`]: the receiver's
+    /// message of every OT is already fixed by its matrix row, so no transfer
+    /// message is needed.
+    ///
+    /// # Returns
+    /// * `Ok(RandomOtReceiver)` - The choice bit and chosen message of every OT
+    /// * `Err(GcError)` - The extension message was not sent yet
+    pub fn finish_random(&mut self) -> Result<RandomOtReceiver> {
+        let ReceiverState::AwaitingTransfer { choices, rows } =
+            std::mem::replace(&mut self.state, ReceiverState::Finished)
+        else {
+            return Err(unexpected_step("receiver", "finish_random"));
+        };
+
+        let labels = rows
+            .iter()
+            .enumerate()
+            .map(|(index, &row)| derive_key(index, row))
+            .collect();
+        Ok(RandomOtReceiver::new(choices, labels))
+    }
+}
+
+/// Run both sides of the OT extension for the evaluator's inputs in one process
+///
+/// Extension counterpart of [`run_base_ot`](crate::ot::run_base_ot): the
+/// evaluator's labels travel through the real protocol messages and the
+/// garbler's are selected directly.
+///
+/// # Arguments
+/// * `wire_labels` - Wire labels from garbler output containing input/output labels and delta
+/// * `input_bits` - Bit value for every evaluator input wire, and optionally every garbler input wire: wire_id -> bit
+/// * `security` - Security level of the extension
+/// * `seed_data` - 32 bytes of random seed, split into the sender's and the receiver's
+///
+/// # Returns
+/// * `Ok(OTResult)` - Selected input labels with their corresponding bit values
+/// * `Err(GcError)` - An evaluator input wire has no bit in `input_bits`, or only some garbler input wires have one
+#[tracing::instrument(name = "ot", skip_all)]
+pub fn run_ot_extension(
+    wire_labels: &WireLabels,
+    input_bits: &HashMap<WireId, bool>,
+    security: OtSecurity,
+    seed_data: &[u8; 32],
+) -> Result<OTResult> {
+    let mut rng = ChaCha12Rng::from_seed(*seed_data);
+    let mut sender_seed = [0u8; 32];
+    let mut receiver_seed = [0u8; 32];
+    rng.fill_bytes(&mut sender_seed);
+    rng.fill_bytes(&mut receiver_seed);
+
+    let wire_ids = wire_labels.evaluator_input_wires();
+    let mut sender = OtExtensionSender::for_wire_labels(wire_labels, security, &sender_seed);
+    let mut receiver =
+        OtExtensionReceiver::for_input_bits(&wire_ids, input_bits, security, &receiver_seed)?;
+
+    let base_setup = receiver.setup()?;
+    let base_choices = sender.choose(&base_setup)?;
+    let extension = receiver.extend(&base_choices)?;
+    let transfer = sender.transfer(&extension)?;
+    let labels = receiver.finish(&transfer)?;
+
+    let mut ot_result = received_ot_result(&wire_ids, input_bits, labels, wire_labels.session_id)?;
+    add_garbler_inputs(&mut ot_result, wire_labels, input_bits)?;
+
+    tracing::info!(
+        evaluator_inputs = ot_result.selected_inputs.len(),
+        garbler_inputs = ot_result.garbler_inputs.len(),
+        ?security,
+        "OT extension finished"
+    );
+    Ok(ot_result)
+}
+
+/// Pseudorandom expansion of a base OT seed into one matrix column
+pub(crate) fn expand(seed: &WireLabel, size: usize) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(PRG_DOMAIN);
+    hasher.update(seed.as_bytes());
+    let mut rng = ChaCha12Rng::from_seed(hasher.finalize().into());
+    let mut column = vec![0u8; size];
+    rng.fill_bytes(&mut column);
+    column
+}
+
+/// Generator of the KOS challenges, bound to the whole extension matrix
+fn challenge_rng(matrix: &[u8], extended: usize) -> ChaCha12Rng {
+    let mut hasher = Sha256::new();
+    hasher.update(CHALLENGE_DOMAIN);
+    hasher.update((extended as u64).to_le_bytes());
+    hasher.update(matrix);
+    ChaCha12Rng::from_seed(hasher.finalize().into())
+}
+
+/// Key for the label of OT `index` selected by matrix row `row`
+fn derive_key(index: usize, row: u128) -> WireLabel {
+    let mut hasher = Sha256::new();
+    hasher.update(KEY_DOMAIN);
+    hasher.update((index as u64).to_le_bytes());
+    hasher.update(row.to_le_bytes());
+    let digest = hasher.finalize();
+    WireLabel::new(digest[..16].try_into().unwrap())
+}
+
+/// SoftSpokenOT field element of `block`: its `k` bits of the correlation
+fn block_delta(correlation: u128, block: usize, k: usize) -> usize {
+    ((correlation >> (block * k)) & ((1 << k) - 1)) as usize
+}
+
+/// Random 128-bit block
+fn random_block(rng: &mut ChaCha12Rng) -> u128 {
+    let mut bytes = [0u8; 16];
+    rng.fill_bytes(&mut bytes);
+    u128::from_le_bytes(bytes)
+}
+
+/// XOR `other` into `target`, which must have the same length
+pub(crate) fn xor_into(target: &mut [u8], other: &[u8]) {
+    for (byte, other_byte) in target.iter_mut().zip(other) {
+        *byte ^= other_byte;
+    }
+}
+
+/// Turn [`BASE_OTS`] bit columns of `rows` bits into one 128-bit block per row
+///
+/// Bit `i` of a column is bit `i % 8` of byte `i / 8`; bit `j` of a row is
+/// the row's bit in column `j`.
+fn transpose(columns: &[Vec<u8>], rows: usize) -> Vec<u128> {
+    let mut transposed = vec![0u128; rows];
+    for (j, column) in columns.iter().enumerate() {
+        for (i, row) in transposed.iter_mut().enumerate() {
+            let bit = (column[i / 8] >> (i % 8)) & 1;
+            *row |= (bit as u128) << j;
+        }
+    }
+    transposed
+}
+
+/// Multiplication in GF(2^128) modulo x^128 + x^7 + x^2 + x + 1
+fn gf128_mul(a: u128, b: u128) -> u128 {
+    let mut low = 0u128;
+    let mut high = 0u128;
+    for i in 0..128 {
+        if (b >> i) & 1 == 1 {
+            low ^= a << i;
+            if i > 0 {
+                high ^= a >> (128 - i);
+            }
+        }
+    }
+    // x^128 = x^7 + x^2 + x + 1; the bits shifted out of `high` fold back once more
+    let overflow = (high >> 127) ^ (high >> 126) ^ (high >> 121);
+    let folded = high ^ (high << 1) ^ (high << 2) ^ (high << 7);
+    low ^ folded ^ overflow ^ (overflow << 1) ^ (overflow << 2) ^ (overflow << 7)
+}
+
+/// Error for a protocol step called in the wrong state
+fn unexpected_step(party: &str, step: &str) -> GcError {
+    GcError::Protocol(format!(
+        "OT extension {} cannot {} in its current state",
+        party, step
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ot_simulation::select_inputs;
+    use crate::session::SessionId;
+
+    fn test_pairs(count: usize) -> Vec<(WireLabel, WireLabel)> {
+        (0..count)
+            .map(|i| {
+                let mut bytes = [0u8; 16];
+                bytes[..8].copy_from_slice(&(i as u64).to_le_bytes());
+                let label_0 = WireLabel::new(bytes);
+                bytes[15] = 0xFF;
+                (label_0, WireLabel::new(bytes))
+            })
+            .collect()
+    }
+
+    /// Run the first three messages, returning the extension message
+    fn extension_message(
+        sender: &mut OtExtensionSender,
+        receiver: &mut OtExtensionReceiver,
+    ) -> Result<Vec<u8>> {
+        let base_setup = receiver.setup()?;
+        let base_choices = sender.choose(&base_setup)?;
+        receiver.extend(&base_choices)
+    }
+
+    #[test]
+    fn test_parse_ot_security() -> Result<()> {
+        for security in [OtSecurity::SemiHonest, OtSecurity::Malicious] {
+            assert_eq!(security.to_string().parse::<OtSecurity>()?, security);
+            assert_eq!(OtSecurity::from_id(security.id()), Some(security));
+        }
+        assert!("honest".parse::<OtSecurity>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_ot_extension_backend() -> Result<()> {
+        for spec in [
+            "iknp",
+            "softspoken-1",
+            "softspoken-2",
+            "softspoken-4",
+            "softspoken-8",
+        ] {
+            let backend: OtExtensionBackend = spec.parse()?;
+            assert_eq!(backend.to_string(), spec);
+            assert_eq!(OtExtensionBackend::from_id(backend.id()), Some(backend));
+        }
+        for spec in [
+            "softspoken-0",
+            "softspoken-3",
+            "softspoken-16",
+            "softspoken",
+        ] {
+            assert!(spec.parse::<OtExtensionBackend>().is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_soft_spoken_transfers_chosen_labels() -> Result<()> {
+        let pairs = test_pairs(77);
+        let choices: Vec<bool> = (0..77).map(|i| i % 3 == 1).collect();
+        let delta = WireLabel::new([0x5C; 16]);
+        let label_0s: Vec<WireLabel> = pairs.iter().map(|&(label_0, _)| label_0).collect();
+
+        for security in [OtSecurity::SemiHonest, OtSecurity::Malicious] {
+            let column_size = security.extended_count(77) / 8;
+            for k in [1, 2, 4, 8] {
+                let backend = OtExtensionBackend::SoftSpoken { k };
+                let mut sender =
+                    OtExtensionSender::new(pairs.clone(), security, &[1; 32]).with_backend(backend);
+                let mut receiver = OtExtensionReceiver::new(choices.clone(), security, &[2; 32])
+                    .with_backend(backend);
+                let extension = extension_message(&mut sender, &mut receiver)?;
+                assert_eq!(
+                    extension.len(),
+                    EXTENSION_HEADER_SIZE
+                        + BASE_TRANSFER_SIZE
+                        + BASE_OTS / k as usize * column_size
+                        + if security == OtSecurity::Malicious {
+                            CHECK_SIZE
+                        } else {
+                            0
+                        }
+                );
+                let labels = receiver.finish(&sender.transfer(&extension)?)?;
+                for ((label, &choice), (label_0, label_1)) in
+                    labels.iter().zip(&choices).zip(&pairs)
+                {
+                    assert_eq!(label, if choice { label_1 } else { label_0 });
+                }
+
+                let mut sender =
+                    OtExtensionSender::new_correlated(label_0s.clone(), delta, security, &[3; 32])
+                        .with_backend(backend);
+                let mut receiver = OtExtensionReceiver::new(choices.clone(), security, &[4; 32])
+                    .with_backend(backend);
+                let extension = extension_message(&mut sender, &mut receiver)?;
+                let labels = receiver.finish(&sender.transfer(&extension)?)?;
+                for ((label, &choice), label_0) in labels.iter().zip(&choices).zip(&label_0s) {
+                    assert_eq!(
+                        *label,
+                        if choice {
+                            label_0.xor(&delta)
+                        } else {
+                            *label_0
+                        }
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_soft_spoken_kos_check_and_backend_mismatch() -> Result<()> {
+        let backend = OtExtensionBackend::SoftSpoken { k: 4 };
+        let mut sender = OtExtensionSender::new(test_pairs(64), OtSecurity::Malicious, &[1; 32])
+            .with_backend(backend);
+        let mut receiver =
+            OtExtensionReceiver::new(vec![true; 64], OtSecurity::Malicious, &[2; 32])
+                .with_backend(backend);
+        let mut extension = extension_message(&mut sender, &mut receiver)?;
+        // Flip the choice bit of OT 0 in the first block only
+        extension[EXTENSION_HEADER_SIZE + BASE_TRANSFER_SIZE] ^= 1;
+        let error_msg = sender.transfer(&extension).unwrap_err().to_string();
+        assert!(error_msg.contains("KOS consistency check failed"));
+
+        let mut sender = OtExtensionSender::new(test_pairs(8), OtSecurity::SemiHonest, &[1; 32]);
+        let mut receiver =
+            OtExtensionReceiver::new(vec![false; 8], OtSecurity::SemiHonest, &[2; 32])
+                .with_backend(backend);
+        let extension = extension_message(&mut sender, &mut receiver)?;
+        let error_msg = sender.transfer(&extension).unwrap_err().to_string();
+        assert!(
+            error_msg.contains("Receiver uses OT extension backend softspoken-4, sender uses iknp")
+        );
+
+        let mut receiver =
+            OtExtensionReceiver::new(vec![false; 8], OtSecurity::SemiHonest, &[2; 32])
+                .with_backend(OtExtensionBackend::SoftSpoken { k: 3 });
+        assert!(matches!(receiver.setup(), Err(GcError::InvalidArgument(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_gf128_mul() {
+        let a = 0x0123_4567_89AB_CDEF_FEDC_BA98_7654_3210u128;
+        let b = 0x1111_2222_3333_4444_5555_6666_7777_8888u128;
+        assert_eq!(gf128_mul(a, 1), a);
+        assert_eq!(gf128_mul(a, b), gf128_mul(b, a));
+        assert_eq!(gf128_mul(1 << 127, 2), 0x87);
+        assert_eq!(gf128_mul(a, b ^ 3), gf128_mul(a, b) ^ gf128_mul(a, 3));
+    }
+
+    #[test]
+    fn test_ot_extension_transfers_chosen_labels() -> Result<()> {
+        let pairs = test_pairs(301);
+        let choices: Vec<bool> = (0..301).map(|i| i % 3 == 0).collect();
+
+        for security in [OtSecurity::SemiHonest, OtSecurity::Malicious] {
+            let mut sender = OtExtensionSender::new(pairs.clone(), security, &[1; 32]);
+            let mut receiver = OtExtensionReceiver::new(choices.clone(), security, &[2; 32]);
+            let extension = extension_message(&mut sender, &mut receiver)?;
+            let transfer = sender.transfer(&extension)?;
+            let labels = receiver.finish(&transfer)?;
+
+            assert_eq!(labels.len(), 301);
+            for ((label, &choice), (label_0, label_1)) in labels.iter().zip(&choices).zip(&pairs) {
+                assert_eq!(label, if choice { label_1 } else { label_0 });
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_correlated_ot_extension_sends_one_word_per_ot() -> Result<()> {
+        let delta = WireLabel::new([0xA7; 16]);
+        let label_0s: Vec<WireLabel> = test_pairs(301)
+            .into_iter()
+            .map(|(label_0, _)| label_0)
+            .collect();
+        let choices: Vec<bool> = (0..301).map(|i| i % 5 < 2).collect();
+
+        for security in [OtSecurity::SemiHonest, OtSecurity::Malicious] {
+            let mut sender =
+                OtExtensionSender::new_correlated(label_0s.clone(), delta, security, &[1; 32]);
+            let mut receiver = OtExtensionReceiver::new(choices.clone(), security, &[2; 32]);
+            let extension = extension_message(&mut sender, &mut receiver)?;
+            let transfer = sender.transfer(&extension)?;
+            assert_eq!(transfer.len(), 1 + 301 * 16);
+            let labels = receiver.finish(&transfer)?;
+
+            for ((label, &choice), label_0) in labels.iter().zip(&choices).zip(&label_0s) {
+                let expected = if choice {
+                    label_0.xor(&delta)
+                } else {
+                    *label_0
+                };
+                assert_eq!(*label, expected);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_kos_check_catches_inconsistent_receiver() -> Result<()> {
+        let choices = vec![true; 64];
+        let matrix_start = EXTENSION_HEADER_SIZE + BASE_TRANSFER_SIZE;
+        let column_size = OtSecurity::Malicious.extended_count(64) / 8;
+
+        // Flip the choice bit of OT 0 in half of the columns only
+        let tamper = |message: &mut Vec<u8>, column_size: usize| {
+            for j in 0..BASE_OTS / 2 {
+                message[matrix_start + j * column_size] ^= 1;
+            }
+        };
+
+        let mut sender = OtExtensionSender::new(test_pairs(64), OtSecurity::Malicious, &[1; 32]);
+        let mut receiver =
+            OtExtensionReceiver::new(choices.clone(), OtSecurity::Malicious, &[2; 32]);
+        let mut extension = extension_message(&mut sender, &mut receiver)?;
+        tamper(&mut extension, column_size);
+        let error_msg = sender.transfer(&extension).unwrap_err().to_string();
+        assert!(error_msg.contains("KOS consistency check failed"));
+
+        // Without the check the sender can't tell
+        let column_size = OtSecurity::SemiHonest.extended_count(64) / 8;
+        let mut sender = OtExtensionSender::new(test_pairs(64), OtSecurity::SemiHonest, &[1; 32]);
+        let mut receiver = OtExtensionReceiver::new(choices, OtSecurity::SemiHonest, &[2; 32]);
+        let mut extension = extension_message(&mut sender, &mut receiver)?;
+        tamper(&mut extension, column_size);
+        assert!(sender.transfer(&extension).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ot_extension_rejects_mismatched_parties() -> Result<()> {
+        let mut sender = OtExtensionSender::new(test_pairs(8), OtSecurity::Malicious, &[1; 32]);
+        let mut receiver =
+            OtExtensionReceiver::new(vec![false; 8], OtSecurity::SemiHonest, &[2; 32]);
+        let extension = extension_message(&mut sender, &mut receiver)?;
+        let error_msg = sender.transfer(&extension).unwrap_err().to_string();
+        assert!(error_msg.contains("Receiver uses OT security level 0"));
+
+        let mut sender = OtExtensionSender::new(test_pairs(8), OtSecurity::SemiHonest, &[1; 32]);
+        let mut receiver =
+            OtExtensionReceiver::new(vec![false; 9], OtSecurity::SemiHonest, &[2; 32]);
+        let extension = extension_message(&mut sender, &mut receiver)?;
+        assert!(matches!(
+            sender.transfer(&extension),
+            Err(GcError::Protocol(_))
+        ));
+        assert!(sender.transfer(&extension).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_ot_extension_matches_select_inputs() -> Result<()> {
+        let label_0 = WireLabel::new([0x01; 16]);
+        let delta = WireLabel::new([0xFF; 16]);
+        let wire_labels = WireLabels {
+            input_labels: (0..20).map(|wire_id| (wire_id, label_0)).collect(),
+            output_labels: HashMap::new(),
+            delta,
+            garbler_input_wires: vec![3, 4],
+            session_id: SessionId::default(),
+        };
+        let bits: HashMap<WireId, bool> =
+            (0..20).map(|wire_id| (wire_id, wire_id % 2 == 1)).collect();
+
+        let ot_result = run_ot_extension(&wire_labels, &bits, OtSecurity::Malicious, &[7; 32])?;
+        let expected = select_inputs(&wire_labels, &bits)?;
+        for (wire_id, label) in expected.inputs() {
+            assert_eq!(ot_result.input_label(wire_id), Some(label));
+        }
+        for (wire_id, input) in &expected.selected_inputs {
+            assert_eq!(
+                ot_result.selected_inputs[wire_id].bit_value,
+                input.bit_value
+            );
+        }
+        assert_eq!(ot_result.selected_inputs.len(), 18);
+        Ok(())
+    }
+}
@@ -0,0 +1,386 @@
+use crate::error::{GcError, Result};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::time::Instant;
+
+use crate::channel::Channel;
+use crate::garbler::{WireLabel, WireLabels};
+use crate::gate::WireId;
+use crate::ot::received_ot_result;
+use crate::ot_extension::{OtExtensionBackend, OtExtensionReceiver, OtExtensionSender, OtSecurity};
+use crate::ot_simulation::{EncodedInputs, OTResult, OtStats};
+use crate::progress::ProgressObserver;
+use crate::session::SessionId;
+
+/// Size of the hello message before the wire IDs: session id, security level, backend and OT count
+const HELLO_HEADER_SIZE: usize = 16 + 1 + 1 + 8;
+
+/// Messages exchanged in one run: the hello, the four OT extension messages
+/// and the garbler's inputs. Progress is reported once per message.
+const OT_MESSAGES: u64 = 6;
+
+/// Serve the evaluator's input labels over OT, as the garbler
+///
+/// Runs the OT extension for every evaluator input wire on `channel`:
+///
+/// 1. The garbler announces the session id, the security level, the
+///    extension backend and the evaluator's input wires in ascending order
+/// 2. Both sides exchange the four [`OtExtensionSender`] messages
+/// 3. The garbler sends the labels of its own inputs, if any, without the
+///    bits they stand for
+///
+/// # Arguments
+/// * `channel` - Connection to the evaluator
+/// * `wire_labels` - Wire labels from garbler output containing input/output labels and delta
+/// * `garbler_inputs` - Encoded labels of the garbler's own inputs, from [`WireLabels::encode_inputs`]
+/// * `security` - Security level of the OT extension, which must match the evaluator's
+/// * `backend` - OT extension backend, which must match the evaluator's
+/// * `seed_data` - 32 bytes of random seed for the garbler's OT secrets
+/// * `progress` - Receives one update per protocol message
+///
+/// # Returns
+/// * `Ok(OtStats)` - Number of OTs run and garbler inputs sent, bytes exchanged and time taken
+/// * `Err(GcError)` - The connection failed, the evaluator deviated from the protocol, or the garbler inputs belong to another session
+#[tracing::instrument(name = "ot", skip_all)]
+pub fn send_ot<S: Read + Write>(
+    channel: &mut Channel<S>,
+    wire_labels: &WireLabels,
+    garbler_inputs: Option<&EncodedInputs>,
+    security: OtSecurity,
+    backend: OtExtensionBackend,
+    seed_data: &[u8; 32],
+    progress: &dyn ProgressObserver,
+) -> Result<OtStats> {
+    let start = Instant::now();
+    let (bytes_sent, bytes_received) = (channel.bytes_sent(), channel.bytes_received());
+    if let Some(encoded) = garbler_inputs {
+        encoded
+            .session_id
+            .check(&wire_labels.session_id, "Garbler's encoded inputs")?;
+    }
+
+    let wire_ids = wire_labels.evaluator_input_wires();
+    progress.start(
+        Some(OT_MESSAGES),
+        &format!("Sending {} input wires over OT", wire_ids.len()),
+    );
+    let mut hello = Vec::with_capacity(HELLO_HEADER_SIZE + wire_ids.len() * 8);
+    hello.extend_from_slice(wire_labels.session_id.as_bytes());
+    hello.push(security.id());
+    hello.push(backend.id());
+    hello.extend_from_slice(&(wire_ids.len() as u64).to_le_bytes());
+    for wire_id in &wire_ids {
+        hello.extend_from_slice(&wire_id.to_le_bytes());
+    }
+    channel.send(&hello)?;
+    progress.update(1);
+
+    let mut sender =
+        OtExtensionSender::for_wire_labels(wire_labels, security, seed_data).with_backend(backend);
+    let base_setup = channel.receive()?;
+    progress.update(2);
+    channel.send(&sender.choose(&base_setup)?)?;
+    progress.update(3);
+    let extension = channel.receive()?;
+    progress.update(4);
+    channel.send(&sender.transfer(&extension)?)?;
+    progress.update(5);
+
+    let empty = EncodedInputs {
+        inputs: HashMap::new(),
+        session_id: wire_labels.session_id,
+    };
+    channel.send(&encode_garbler_inputs_message(
+        garbler_inputs.unwrap_or(&empty),
+    )?)?;
+    progress.update(OT_MESSAGES);
+
+    let stats = OtStats {
+        evaluator_inputs: wire_ids.len() as u64,
+        garbler_inputs: garbler_inputs.map_or(0, |encoded| encoded.inputs.len() as u64),
+        bytes_sent: channel.bytes_sent() - bytes_sent,
+        bytes_received: channel.bytes_received() - bytes_received,
+        wall_time_secs: start.elapsed().as_secs_f64(),
+    };
+    progress.finish(&format!(
+        "✓ Sent {} input wires over OT",
+        stats.evaluator_inputs
+    ));
+    tracing::info!(
+        ots = stats.evaluator_inputs,
+        %security,
+        %backend,
+        bytes_sent = stats.bytes_sent,
+        bytes_received = stats.bytes_received,
+        "OT sender finished"
+    );
+    Ok(stats)
+}
+
+/// Obtain the labels for the evaluator's input bits over OT, as the evaluator
+///
+/// Counterpart of [`send_ot`]. Bits for wires the garbler does not announce
+/// are ignored, so `input_bits` may cover every primary input.
+///
+/// # Arguments
+/// * `channel` - Connection to the garbler
+/// * `input_bits` - Bit value for every evaluator input wire: wire_id -> bit
+/// * `security` - Security level of the OT extension, which must match the garbler's
+/// * `backend` - OT extension backend, which must match the garbler's
+/// * `seed_data` - 32 bytes of random seed for the evaluator's OT secrets
+/// * `progress` - Receives one update per protocol message
+///
+/// # Returns
+/// * `Ok((OTResult, OtStats))` - The evaluator's selected labels and the garbler's encoded inputs, with the run's statistics
+/// * `Err(GcError)` - The connection failed, the garbler uses another security level or backend, or an announced wire has no bit
+#[tracing::instrument(name = "ot", skip_all)]
+pub fn receive_ot<S: Read + Write>(
+    channel: &mut Channel<S>,
+    input_bits: &HashMap<WireId, bool>,
+    security: OtSecurity,
+    backend: OtExtensionBackend,
+    seed_data: &[u8; 32],
+    progress: &dyn ProgressObserver,
+) -> Result<(OTResult, OtStats)> {
+    let start = Instant::now();
+    let (bytes_sent, bytes_received) = (channel.bytes_sent(), channel.bytes_received());
+    let hello = channel.receive()?;
+    if hello.len() < HELLO_HEADER_SIZE {
+        return Err(GcError::Protocol(format!(
+            "Hello message has {} bytes, expected at least {}",
+            hello.len(),
+            HELLO_HEADER_SIZE
+        )));
+    }
+    let session_id = SessionId::new(hello[..16].try_into().unwrap());
+    let garbler_security = OtSecurity::from_id(hello[16])
+        .ok_or_else(|| GcError::Protocol(format!("Unknown OT security level {}", hello[16])))?;
+    if garbler_security != security {
+        return Err(GcError::Protocol(format!(
+            "Garbler runs {} OT, evaluator expects {}",
+            garbler_security, security
+        )));
+    }
+    let garbler_backend = OtExtensionBackend::from_id(hello[17])
+        .ok_or_else(|| GcError::Protocol(format!("Unknown OT extension backend {}", hello[17])))?;
+    if garbler_backend != backend {
+        return Err(GcError::Protocol(format!(
+            "Garbler extends OT with {}, evaluator expects {}",
+            garbler_backend, backend
+        )));
+    }
+    let count = u64::from_le_bytes(hello[18..HELLO_HEADER_SIZE].try_into().unwrap());
+    let wire_bytes = &hello[HELLO_HEADER_SIZE..];
+    if wire_bytes.len() as u64 != count.saturating_mul(8) {
+        return Err(GcError::Protocol(format!(
+            "Hello message announces {} wires but carries {} bytes of wire IDs",
+            count,
+            wire_bytes.len()
+        )));
+    }
+    let wire_ids: Vec<WireId> = wire_bytes
+        .chunks_exact(8)
+        .map(|chunk| WireId::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    progress.start(
+        Some(OT_MESSAGES),
+        &format!("Receiving {} input wires over OT", wire_ids.len()),
+    );
+    progress.update(1);
+
+    let mut receiver =
+        OtExtensionReceiver::for_input_bits(&wire_ids, input_bits, security, seed_data)?
+            .with_backend(backend);
+    channel.send(&receiver.setup()?)?;
+    progress.update(2);
+    let base_choices = channel.receive()?;
+    progress.update(3);
+    channel.send(&receiver.extend(&base_choices)?)?;
+    progress.update(4);
+    let labels = receiver.finish(&channel.receive()?)?;
+    progress.update(5);
+    let mut ot_result = received_ot_result(&wire_ids, input_bits, labels, session_id)?;
+
+    ot_result.set_garbler_inputs(decode_garbler_inputs_message(&channel.receive()?)?)?;
+    progress.update(OT_MESSAGES);
+
+    let stats = OtStats {
+        evaluator_inputs: wire_ids.len() as u64,
+        garbler_inputs: ot_result.garbler_inputs.len() as u64,
+        bytes_sent: channel.bytes_sent() - bytes_sent,
+        bytes_received: channel.bytes_received() - bytes_received,
+        wall_time_secs: start.elapsed().as_secs_f64(),
+    };
+    progress.finish(&format!(
+        "✓ Received {} input wires over OT",
+        stats.evaluator_inputs
+    ));
+    tracing::info!(
+        ots = stats.evaluator_inputs,
+        garbler_inputs = stats.garbler_inputs,
+        %security,
+        %backend,
+        bytes_sent = stats.bytes_sent,
+        bytes_received = stats.bytes_received,
+        "OT receiver finished"
+    );
+    Ok((ot_result, stats))
+}
+
+/// The garbler's encoded inputs as its last message, sorted by wire so the
+/// same inputs always give the same bytes
+///
+/// Only the session id and each wire's label are encoded; the garbler's bits
+/// never leave it.
+pub(crate) fn encode_garbler_inputs_message(encoded: &EncodedInputs) -> Result<Vec<u8>> {
+    let mut inputs: Vec<(&WireId, &WireLabel)> = encoded.inputs.iter().collect();
+    inputs.sort_unstable_by_key(|&(&wire_id, _)| wire_id);
+    Ok(bincode::encode_to_vec(
+        (&encoded.session_id, inputs),
+        bincode::config::standard(),
+    )?)
+}
+
+/// Counterpart of [`encode_garbler_inputs_message`]
+pub(crate) fn decode_garbler_inputs_message(message: &[u8]) -> Result<EncodedInputs> {
+    let ((session_id, inputs), _): ((SessionId, Vec<(WireId, WireLabel)>), usize) =
+        bincode::decode_from_slice(message, bincode::config::standard())?;
+    Ok(EncodedInputs {
+        inputs: inputs.into_iter().collect(),
+        session_id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::garbler::WireLabel;
+    use crate::ot_simulation::select_inputs;
+    use crate::progress::NoProgress;
+    use std::net::{TcpListener, TcpStream};
+
+    fn test_wire_labels() -> WireLabels {
+        WireLabels {
+            input_labels: (0..10)
+                .map(|wire_id| (wire_id, WireLabel::new([wire_id as u8; 16])))
+                .collect(),
+            output_labels: HashMap::new(),
+            delta: WireLabel::new([0xA5; 16]),
+            garbler_input_wires: vec![0, 1],
+            session_id: SessionId::new([9; 16]),
+        }
+    }
+
+    /// Run the garbler on a background thread and the evaluator on this one
+    fn run_over_tcp(
+        garbler: (OtSecurity, OtExtensionBackend),
+        evaluator: (OtSecurity, OtExtensionBackend),
+        bits: &HashMap<WireId, bool>,
+    ) -> (Result<OtStats>, Result<(OTResult, OtStats)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let wire_labels = test_wire_labels();
+        let garbler_bits = HashMap::from([(0, true), (1, false)]);
+        let encoded = wire_labels.encode_inputs(&garbler_bits).unwrap();
+
+        let garbler = std::thread::spawn(move || {
+            let (stream, _) = listener.accept()?;
+            let mut channel = Channel::new(stream);
+            send_ot(
+                &mut channel,
+                &wire_labels,
+                Some(&encoded),
+                garbler.0,
+                garbler.1,
+                &[1; 32],
+                &NoProgress,
+            )
+        });
+
+        let mut channel = Channel::new(TcpStream::connect(address).unwrap());
+        let received = receive_ot(
+            &mut channel,
+            bits,
+            evaluator.0,
+            evaluator.1,
+            &[2; 32],
+            &NoProgress,
+        );
+        drop(channel);
+        (garbler.join().unwrap(), received)
+    }
+
+    #[test]
+    fn test_ot_over_tcp() -> Result<()> {
+        let mut bits: HashMap<WireId, bool> =
+            (2..10).map(|wire_id| (wire_id, wire_id % 3 == 0)).collect();
+        let soft_spoken = (
+            OtSecurity::Malicious,
+            OtExtensionBackend::SoftSpoken { k: 4 },
+        );
+        let (sent, received) = run_over_tcp(soft_spoken, soft_spoken, &bits);
+        let sent = sent?;
+        let (ot_result, received) = received?;
+        assert_eq!((sent.evaluator_inputs, sent.garbler_inputs), (8, 2));
+        assert_eq!((received.evaluator_inputs, received.garbler_inputs), (8, 2));
+        assert_eq!(sent.bytes_sent, received.bytes_received);
+        assert_eq!(sent.bytes_received, received.bytes_sent);
+
+        bits.extend([(0, true), (1, false)]);
+        let expected = select_inputs(&test_wire_labels(), &bits)?;
+        for (wire_id, label) in expected.inputs() {
+            assert_eq!(ot_result.input_label(wire_id), Some(label));
+        }
+        for (wire_id, input) in &expected.selected_inputs {
+            assert_eq!(
+                ot_result.selected_inputs[wire_id].bit_value,
+                input.bit_value
+            );
+        }
+        assert_eq!(ot_result.garbler_inputs.len(), 2);
+        assert_eq!(ot_result.session_id, SessionId::new([9; 16]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_garbler_inputs_message_carries_labels_only() -> Result<()> {
+        let wire_labels = test_wire_labels();
+        let zeros = wire_labels.encode_inputs(&HashMap::from([(0, false), (1, false)]))?;
+        let ones = wire_labels.encode_inputs(&HashMap::from([(0, true), (1, true)]))?;
+        let message = encode_garbler_inputs_message(&zeros)?;
+
+        // Session id, input count, then per input a one-byte wire id and a label
+        assert_eq!(message.len(), 16 + 1 + 2 * (1 + 16));
+        assert_eq!(message.len(), encode_garbler_inputs_message(&ones)?.len());
+
+        let decoded = decode_garbler_inputs_message(&message)?;
+        assert_eq!(decoded.inputs, zeros.inputs);
+        assert_eq!(decoded.session_id, wire_labels.session_id);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ot_over_tcp_rejects_mismatched_settings() {
+        let bits: HashMap<WireId, bool> = (2..10).map(|wire_id| (wire_id, true)).collect();
+        let iknp = OtExtensionBackend::Iknp;
+        let (sent, received) = run_over_tcp(
+            (OtSecurity::SemiHonest, iknp),
+            (OtSecurity::Malicious, iknp),
+            &bits,
+        );
+        let error_msg = received.unwrap_err().to_string();
+        assert!(error_msg.contains("Garbler runs semi-honest OT, evaluator expects malicious"));
+        assert!(matches!(sent, Err(GcError::Disconnected(_))));
+
+        let (_, received) = run_over_tcp(
+            (OtSecurity::SemiHonest, iknp),
+            (
+                OtSecurity::SemiHonest,
+                OtExtensionBackend::SoftSpoken { k: 2 },
+            ),
+            &bits,
+        );
+        let error_msg = received.unwrap_err().to_string();
+        assert!(error_msg.contains("Garbler extends OT with iknp, evaluator expects softspoken-2"));
+    }
+}
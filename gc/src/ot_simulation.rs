@@ -1,23 +1,50 @@
-use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::error::{GcError, Result};
 use rand::{RngCore, SeedableRng};
 use rand_chacha::ChaCha12Rng;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
+use std::time::Instant;
 
-use crate::constants::PROGRESS_UPDATE_INTERVAL;
+use crate::constants::{
+    BUFFER_SIZE, ENCODED_INPUTS_MAGIC, ENCODED_INPUTS_VERSION, OT_RESULT_MAGIC, OT_RESULT_VERSION,
+    PROGRESS_UPDATE_INTERVAL,
+};
 use crate::garbler::{WireLabel, WireLabels};
+use crate::gate::WireId;
+use crate::progress::ProgressObserver;
+use crate::serde_helpers::serialize_sorted_map;
+use crate::session::SessionId;
 
-/// OT simulation result containing selected input labels and their bit values
-#[derive(Debug, Serialize, Deserialize)]
+/// Domain separation tag for the pads masking labels in a split OT simulation
+const PAD_DOMAIN: &[u8] = b"gc-ot-simulation-pad-v1";
+
+/// OT simulation result containing selected input labels
+///
+/// Holds every input label the evaluator needs: the evaluator's own inputs
+/// obtained through OT, with the bits it chose, and the garbler's inputs,
+/// whose labels the garbler sends directly without their bits.
+#[derive(Debug, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
 pub struct OTResult {
-    /// Selected input labels: wire_id -> (selected_label, bit_value)
-    pub selected_inputs: HashMap<u32, SelectedInput>,
+    /// Evaluator input labels selected through OT: wire_id -> (selected_label, bit_value)
+    #[serde(serialize_with = "serialize_sorted_map")]
+    pub selected_inputs: HashMap<WireId, SelectedInput>,
+    /// Garbler input labels sent without OT: wire_id -> label
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map"
+    )]
+    pub garbler_inputs: HashMap<WireId, WireLabel>,
+    /// Garbling run the selected labels belong to
+    pub session_id: SessionId,
 }
 
 /// A selected input from OT simulation
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
 pub struct SelectedInput {
     /// The selected wire label
     pub label: WireLabel,
@@ -25,6 +52,34 @@ pub struct SelectedInput {
     pub bit_value: bool,
 }
 
+/// Statistics of a single OT run
+///
+/// Returned by the networked OT functions of [`crate::ot_remote`] and by
+/// [`simulate_ot_streaming`], for callers that report on their own dashboards
+/// rather than through a [`ProgressObserver`].
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct OtStats {
+    /// Number of evaluator inputs transferred through OT
+    pub evaluator_inputs: u64,
+    /// Number of garbler inputs sent without OT
+    pub garbler_inputs: u64,
+    /// Bytes sent to the other party, framing included; zero for simulations
+    pub bytes_sent: u64,
+    /// Bytes received from the other party, framing included; zero for simulations
+    pub bytes_received: u64,
+    /// Wall-clock time of the run in seconds
+    pub wall_time_secs: f64,
+}
+
+impl OtStats {
+    /// Save statistics as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
 impl OTResult {
     /// Save OT result as JSON
     pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
@@ -40,87 +95,817 @@ impl OTResult {
         Ok(ot_result)
     }
 
-    /// Get the selected label for a wire
-    pub fn get_selected_input(&self, wire_id: u32) -> Option<&SelectedInput> {
-        self.selected_inputs.get(&wire_id)
+    /// Save OT result in the compact binary format
+    ///
+    /// Layout: [`OT_RESULT_MAGIC`], the format version as a little-endian
+    /// `u32`, then the bincode-encoded result: per evaluator input wire a
+    /// varint wire ID, the 16-byte label and the chosen bit, then per garbler
+    /// input wire a varint wire ID and the 16-byte label.
+    pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::with_capacity(BUFFER_SIZE, file);
+        writer.write_all(&OT_RESULT_MAGIC)?;
+        writer.write_all(&OT_RESULT_VERSION.to_le_bytes())?;
+        bincode::encode_into_std_write(self, &mut writer, bincode::config::standard())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load OT result from the binary format
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        let mut reader = BufReader::with_capacity(BUFFER_SIZE, file);
+
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        if header[..4] != OT_RESULT_MAGIC {
+            return Err(GcError::invalid_format(
+                "binary OT result file",
+                "bad magic bytes",
+            ));
+        }
+        let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if version != OT_RESULT_VERSION {
+            return Err(GcError::FormatVersionMismatch {
+                format: "OT result",
+                found: version,
+                expected: OT_RESULT_VERSION,
+            });
+        }
+
+        let ot_result = bincode::decode_from_std_read(&mut reader, bincode::config::standard())?;
+        Ok(ot_result)
+    }
+
+    /// Load OT result from either format, detected by the magic bytes
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        let bytes_read = File::open(path.as_ref())?.read(&mut magic)?;
+        if bytes_read == magic.len() && magic == OT_RESULT_MAGIC {
+            Self::load_binary(path)
+        } else {
+            Self::load_json(path)
+        }
+    }
+
+    /// Get the input label of a wire, whichever party owns it
+    pub fn input_label(&self, wire_id: WireId) -> Option<WireLabel> {
+        self.selected_inputs
+            .get(&wire_id)
+            .map(|selected_input| selected_input.label)
+            .or_else(|| self.garbler_inputs.get(&wire_id).copied())
+    }
+
+    /// All input labels: the evaluator's from OT followed by the garbler's
+    pub fn inputs(&self) -> impl Iterator<Item = (WireId, WireLabel)> + '_ {
+        let evaluator_inputs = self
+            .selected_inputs
+            .iter()
+            .map(|(&wire_id, selected_input)| (wire_id, selected_input.label));
+        let garbler_inputs = self
+            .garbler_inputs
+            .iter()
+            .map(|(&wire_id, &label)| (wire_id, label));
+        evaluator_inputs.chain(garbler_inputs)
+    }
+
+    /// Replace the garbler's input labels with the ones it shipped
+    ///
+    /// Fails if the encoded inputs come from a different garbling session,
+    /// or if any shipped wire was already selected through OT, i.e. is one
+    /// of the evaluator's inputs.
+    pub fn set_garbler_inputs(&mut self, encoded: EncodedInputs) -> Result<()> {
+        encoded
+            .session_id
+            .check(&self.session_id, "Garbler's encoded inputs")?;
+        if let Some(wire_id) = encoded
+            .inputs
+            .keys()
+            .find(|wire_id| self.selected_inputs.contains_key(wire_id))
+        {
+            return Err(GcError::InvalidArgument(format!(
+                "Garbler sent a label for wire {}, which is an evaluator input",
+                wire_id
+            )));
+        }
+        self.garbler_inputs = encoded.inputs;
+        Ok(())
+    }
+}
+
+/// Labels for the garbler's own input bits, shipped to the evaluator
+///
+/// Produced by [`WireLabels::encode_inputs`]. The garbler knows these bits,
+/// so no OT is needed; the evaluator merges them into its [`OTResult`] with
+/// [`OTResult::set_garbler_inputs`]. Only the labels are shipped, and the
+/// evaluator can't tell which bit a label stands for.
+#[derive(Debug, Default, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
+pub struct EncodedInputs {
+    /// Labels of the garbler's input wires: wire_id -> label
+    #[serde(serialize_with = "serialize_sorted_map")]
+    pub inputs: HashMap<WireId, WireLabel>,
+    /// Garbling run the labels belong to
+    pub session_id: SessionId,
+}
+
+impl EncodedInputs {
+    /// Save encoded inputs as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Load encoded inputs from JSON
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let encoded = serde_json::from_str(&data)?;
+        Ok(encoded)
+    }
+
+    /// Save encoded inputs in the compact binary format
+    ///
+    /// Layout: [`ENCODED_INPUTS_MAGIC`], the format version as a
+    /// little-endian `u32`, then the bincode-encoded inputs.
+    pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::with_capacity(BUFFER_SIZE, file);
+        writer.write_all(&ENCODED_INPUTS_MAGIC)?;
+        writer.write_all(&ENCODED_INPUTS_VERSION.to_le_bytes())?;
+        bincode::encode_into_std_write(self, &mut writer, bincode::config::standard())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load encoded inputs from the binary format
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        let mut reader = BufReader::with_capacity(BUFFER_SIZE, file);
+
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        if header[..4] != ENCODED_INPUTS_MAGIC {
+            return Err(GcError::invalid_format(
+                "binary encoded inputs file",
+                "bad magic bytes",
+            ));
+        }
+        let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if version != ENCODED_INPUTS_VERSION {
+            return Err(GcError::FormatVersionMismatch {
+                format: "encoded inputs",
+                found: version,
+                expected: ENCODED_INPUTS_VERSION,
+            });
+        }
+
+        let encoded = bincode::decode_from_std_read(&mut reader, bincode::config::standard())?;
+        Ok(encoded)
     }
+
+    /// Load encoded inputs from either format, detected by the magic bytes
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        let bytes_read = File::open(path.as_ref())?.read(&mut magic)?;
+        if bytes_read == magic.len() && magic == ENCODED_INPUTS_MAGIC {
+            Self::load_binary(path)
+        } else {
+            Self::load_json(path)
+        }
+    }
+}
+
+/// Label for `bit_value` given a wire's label_0
+pub(crate) fn select_label(
+    wire_labels: &WireLabels,
+    label_0: WireLabel,
+    bit_value: bool,
+) -> SelectedInput {
+    let label = if bit_value {
+        // bit_value = 1 -> select label_1 = label_0 XOR delta
+        label_0.xor(&wire_labels.delta)
+    } else {
+        // bit_value = 0 -> select label_0
+        label_0
+    };
+    SelectedInput { label, bit_value }
 }
 
 /// Simulate OT protocol by randomly selecting input wire labels
 ///
 /// For each primary input wire, randomly chooses between label_0 (bit=0) or label_1 (bit=1)
-/// using a cryptographically secure random number generator.
+/// using a cryptographically secure random number generator. Only the
+/// evaluator's inputs go through OT; the garbler's inputs (see
+/// [`WireLabels::garbler_input_wires`]) get random bits too, but are encoded
+/// with [`WireLabels::encode_inputs`] into [`OTResult::garbler_inputs`], as
+/// if the garbler had sent them.
 ///
 /// # Arguments
 /// * `wire_labels` - Wire labels from garbler output containing input/output labels and delta
 /// * `seed_data` - 32 bytes of random seed for CSPRNG
+/// * `progress` - Receives progress updates
 ///
 /// # Returns
 /// * `Ok(OTResult)` - Selected input labels with their corresponding bit values
-/// * `Err(anyhow::Error)` - Serialization error
-pub fn simulate_ot(wire_labels: &WireLabels, seed_data: &[u8; 32]) -> Result<OTResult> {
+/// * `Err(GcError)` - Serialization error
+#[tracing::instrument(name = "ot", skip_all)]
+pub fn simulate_ot(
+    wire_labels: &WireLabels,
+    seed_data: &[u8; 32],
+    progress: &dyn ProgressObserver,
+) -> Result<OTResult> {
     // Initialize CSPRNG with provided seed
     let mut rng = ChaCha12Rng::from_seed(*seed_data);
 
     let mut selected_inputs = HashMap::new();
-    let total_inputs: u32 = wire_labels.input_labels.len() as u32;
+    let mut garbler_bits = HashMap::new();
+    let total_inputs: u64 =
+        (wire_labels.input_labels.len() - wire_labels.garbler_input_wires.len()) as u64;
 
     // Create progress bar for OT simulation
-    let pb = ProgressBar::new(total_inputs as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template(
-                "{bar:40.green/blue} {pos}/{len} inputs [{elapsed_precise}<{eta_precise}] {msg}",
-            )
-            .unwrap()
-            .progress_chars("#>-"),
-    );
-    pb.set_message("Simulating OT protocol");
+    progress.start(Some(total_inputs), "Simulating OT protocol");
+
+    let mut processed: u64 = 0;
 
-    let mut processed: u32 = 0;
+    // Draw bits in wire order so the same seed always gives the same result
+    let mut input_wires: Vec<(&WireId, &WireLabel)> = wire_labels.input_labels.iter().collect();
+    input_wires.sort_unstable_by_key(|&(&wire_id, _)| wire_id);
 
     // For each input wire, randomly select bit value and corresponding label
-    for (&wire_id, &label_0) in &wire_labels.input_labels {
+    for (&wire_id, &label_0) in input_wires {
         // Generate random bit (0 or 1)
         let bit_value = (rng.next_u32() & 1) == 1;
+        if wire_labels.is_garbler_input(wire_id) {
+            garbler_bits.insert(wire_id, bit_value);
+            continue;
+        }
 
         // Select the appropriate label based on the bit value
-        let selected_label = if bit_value {
-            // bit_value = 1 -> select label_1 = label_0 XOR delta
-            label_0.xor(&wire_labels.delta)
-        } else {
-            // bit_value = 0 -> select label_0
-            label_0
-        };
-
-        selected_inputs.insert(
-            wire_id,
-            SelectedInput {
-                label: selected_label,
-                bit_value,
-            },
-        );
+        selected_inputs.insert(wire_id, select_label(wire_labels, label_0, bit_value));
 
         processed += 1;
 
         // Update progress bar periodically for better performance
         if processed.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
-            pb.set_position(processed as u64);
+            progress.update(processed);
         }
     }
 
     // Finish progress bar with final position
-    pb.set_position(processed as u64);
-    pb.finish_with_message(format!("✓ Simulated OT for {total_inputs} input wires"));
+    progress.update(processed);
+    progress.finish(&format!("✓ Simulated OT for {total_inputs} input wires"));
+    let garbler_inputs = wire_labels.encode_inputs(&garbler_bits)?.inputs;
+    tracing::info!(
+        evaluator_inputs = selected_inputs.len(),
+        garbler_inputs = garbler_inputs.len(),
+        "OT simulation finished"
+    );
+
+    Ok(OTResult {
+        selected_inputs,
+        garbler_inputs,
+        session_id: wire_labels.session_id,
+    })
+}
+
+/// Simulate OT like [`simulate_ot`], writing the result straight to a file
+///
+/// Gives the same OT result for the same seed without ever holding it in
+/// memory: input wires are processed in ascending order and each selected
+/// label is written as soon as its bit is drawn, so memory stays bounded by
+/// the wire labels themselves. Both formats store the garbler's inputs after
+/// the evaluator's, so they are written in a second pass over the wires with
+/// the generator reseeded.
+///
+/// # Arguments
+/// * `wire_labels` - Wire labels from garbler output containing input/output labels and delta
+/// * `seed_data` - 32 bytes of random seed for CSPRNG
+/// * `path` - Output file
+/// * `binary` - Write the format of [`OTResult::save_binary`] instead of JSON
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(OtStats)` - Number of evaluator and garbler inputs written, and the time taken
+/// * `Err(GcError)` - I/O or serialization error
+#[tracing::instrument(name = "ot", skip_all)]
+pub fn simulate_ot_streaming<P: AsRef<Path>>(
+    wire_labels: &WireLabels,
+    seed_data: &[u8; 32],
+    path: P,
+    binary: bool,
+    progress: &dyn ProgressObserver,
+) -> Result<OtStats> {
+    let start = Instant::now();
+    let mut input_wires: Vec<WireId> = wire_labels.input_labels.keys().copied().collect();
+    input_wires.sort_unstable();
+    let garbler_count = input_wires
+        .iter()
+        .filter(|&&wire_id| wire_labels.is_garbler_input(wire_id))
+        .count();
+    let evaluator_count = input_wires.len() - garbler_count;
+
+    let file = File::create(path)?;
+    let mut writer = OtResultWriter::new(BufWriter::with_capacity(BUFFER_SIZE, file), binary)?;
+    progress.start(Some(evaluator_count as u64), "Simulating OT protocol");
+
+    // One pass per map, each drawing the same bits in the same order as simulate_ot
+    for garbler_pass in [false, true] {
+        let mut rng = ChaCha12Rng::from_seed(*seed_data);
+        let count = if garbler_pass {
+            garbler_count
+        } else {
+            evaluator_count
+        };
+        writer.begin_map(
+            if garbler_pass {
+                "garbler_inputs"
+            } else {
+                "selected_inputs"
+            },
+            count,
+        )?;
+
+        let mut processed: u64 = 0;
+        for &wire_id in &input_wires {
+            let bit_value = (rng.next_u32() & 1) == 1;
+            if wire_labels.is_garbler_input(wire_id) != garbler_pass {
+                continue;
+            }
+            let selected = select_label(wire_labels, wire_labels.input_labels[&wire_id], bit_value);
+            if garbler_pass {
+                writer.entry(wire_id, &selected.label)?;
+            } else {
+                writer.entry(wire_id, &selected)?;
+            }
+
+            processed += 1;
+            if !garbler_pass && processed.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+                progress.update(processed);
+            }
+        }
+        writer.end_map()?;
+    }
+    writer.finish(&wire_labels.session_id)?;
+
+    progress.update(evaluator_count as u64);
+    progress.finish(&format!("✓ Simulated OT for {evaluator_count} input wires"));
+    tracing::info!(
+        evaluator_inputs = evaluator_count,
+        garbler_inputs = garbler_count,
+        "Streaming OT simulation finished"
+    );
+    Ok(OtStats {
+        evaluator_inputs: evaluator_count as u64,
+        garbler_inputs: garbler_count as u64,
+        wall_time_secs: start.elapsed().as_secs_f64(),
+        ..OtStats::default()
+    })
+}
+
+/// Incremental writer of an [`OTResult`] in either file format
+enum OtResultWriter<W: Write> {
+    /// Compact JSON, readable by [`OTResult::load_json`]
+    Json { writer: W, first_entry: bool },
+    /// The layout of [`OTResult::save_binary`]
+    Binary { writer: W },
+}
+
+impl<W: Write> OtResultWriter<W> {
+    /// Write the file header
+    fn new(mut writer: W, binary: bool) -> Result<Self> {
+        if binary {
+            writer.write_all(&OT_RESULT_MAGIC)?;
+            writer.write_all(&OT_RESULT_VERSION.to_le_bytes())?;
+            Ok(OtResultWriter::Binary { writer })
+        } else {
+            writer.write_all(b"{")?;
+            Ok(OtResultWriter::Json {
+                writer,
+                first_entry: true,
+            })
+        }
+    }
+
+    /// Start the map `name` holding `count` entries
+    fn begin_map(&mut self, name: &str, count: usize) -> Result<()> {
+        match self {
+            OtResultWriter::Json {
+                writer,
+                first_entry,
+            } => {
+                write!(writer, "\"{}\":{{", name)?;
+                *first_entry = true;
+            }
+            OtResultWriter::Binary { writer } => {
+                bincode::encode_into_std_write(count as u64, writer, bincode::config::standard())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write one map entry
+    fn entry<T: Serialize + bincode::Encode>(&mut self, wire_id: WireId, input: &T) -> Result<()> {
+        match self {
+            OtResultWriter::Json {
+                writer,
+                first_entry,
+            } => {
+                if !std::mem::take(first_entry) {
+                    writer.write_all(b",")?;
+                }
+                write!(writer, "\"{}\":", wire_id)?;
+                serde_json::to_writer(writer, input)?;
+            }
+            OtResultWriter::Binary { writer } => {
+                let config = bincode::config::standard();
+                bincode::encode_into_std_write(wire_id, writer, config)?;
+                bincode::encode_into_std_write(input, writer, config)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Close the current map
+    fn end_map(&mut self) -> Result<()> {
+        if let OtResultWriter::Json { writer, .. } = self {
+            writer.write_all(b"},")?;
+        }
+        Ok(())
+    }
+
+    /// Write the session id and flush
+    fn finish(self, session_id: &SessionId) -> Result<()> {
+        match self {
+            OtResultWriter::Json { mut writer, .. } => {
+                writer.write_all(b"\"session_id\":")?;
+                serde_json::to_writer(&mut writer, session_id)?;
+                writer.write_all(b"}")?;
+                writer.flush()?;
+            }
+            OtResultWriter::Binary { mut writer } => {
+                bincode::encode_into_std_write(
+                    session_id,
+                    &mut writer,
+                    bincode::config::standard(),
+                )?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Select input wire labels for known input bits
+///
+/// Deterministic counterpart of [`simulate_ot`]: instead of drawing random
+/// bits, the choice for each evaluator input wire is taken from `inputs`.
+/// Bits for the garbler's inputs are optional; if given, they are encoded
+/// into [`OTResult::garbler_inputs`] like [`simulate_ot`] does, otherwise the
+/// garbler's labels are left for [`OTResult::set_garbler_inputs`].
+///
+/// # Arguments
+/// * `wire_labels` - Wire labels from garbler output containing input/output labels and delta
+/// * `inputs` - Bit value for every evaluator input wire, and optionally every garbler input wire: wire_id -> bit
+///
+/// # Returns
+/// * `Ok(OTResult)` - Selected input labels with their corresponding bit values
+/// * `Err(GcError)` - An evaluator input wire has no bit in `inputs`, or only some garbler input wires have one
+#[tracing::instrument(name = "ot", skip_all)]
+pub fn select_inputs(wire_labels: &WireLabels, inputs: &HashMap<WireId, bool>) -> Result<OTResult> {
+    let evaluator_wires = wire_labels.evaluator_input_wires();
+    let mut selected_inputs = HashMap::with_capacity(evaluator_wires.len());
+
+    for wire_id in evaluator_wires {
+        let bit_value = *inputs.get(&wire_id).ok_or_else(|| {
+            GcError::missing_wire(
+                wire_id,
+                format!("No input bit given for input wire {}", wire_id),
+            )
+        })?;
+        let label_0 = wire_labels.input_labels[&wire_id];
+        selected_inputs.insert(wire_id, select_label(wire_labels, label_0, bit_value));
+    }
+
+    Ok(OTResult {
+        selected_inputs,
+        garbler_inputs: encode_garbler_inputs(wire_labels, inputs)?,
+        session_id: wire_labels.session_id,
+    })
+}
+
+/// Encode the garbler's bits among `inputs`, as the garbler would send them
+///
+/// Bits for evaluator input wires are ignored. Returns no labels if `inputs`
+/// has no bit for any garbler input wire.
+pub(crate) fn encode_garbler_inputs(
+    wire_labels: &WireLabels,
+    inputs: &HashMap<WireId, bool>,
+) -> Result<HashMap<WireId, WireLabel>> {
+    let garbler_bits: HashMap<WireId, bool> = wire_labels
+        .garbler_input_wires
+        .iter()
+        .filter_map(|wire_id| inputs.get(wire_id).map(|&bit| (*wire_id, bit)))
+        .collect();
+    if garbler_bits.is_empty() {
+        return Ok(HashMap::new());
+    }
+    Ok(wire_labels.encode_inputs(&garbler_bits)?.inputs)
+}
+
+/// Evaluator's message in a split OT simulation: its choice bits
+///
+/// Simulating the two roles separately gives one file per message:
+///
+/// 1. The evaluator writes its choices with [`OtChoices::new`]
+/// 2. The garbler answers with the masked chosen labels, [`OtSenderMessage::respond`]
+/// 3. The evaluator unmasks them into an [`OTResult`] with [`OtChoices::receive`]
+///
+/// Like [`simulate_ot`], this is not a secure OT: the garbler sees the
+/// choices in the clear. Only the chosen labels travel, masked with pads
+/// derived from [`OtChoices::pad_seed`], so a real OT can replace both
+/// messages without touching the consumers of the [`OTResult`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OtChoices {
+    /// Choice bit per evaluator input wire: wire_id -> bit
+    #[serde(serialize_with = "serialize_sorted_map")]
+    pub choices: HashMap<WireId, bool>,
+    /// Seed of the one-time pads masking each chosen label
+    pub pad_seed: [u8; 32],
+}
+
+impl OtChoices {
+    /// Create the evaluator's choices
+    ///
+    /// # Arguments
+    /// * `choices` - Bit value for every evaluator input wire: wire_id -> bit
+    /// * `seed_data` - 32 bytes of random seed for the pads
+    pub fn new(choices: HashMap<WireId, bool>, seed_data: &[u8; 32]) -> Self {
+        let mut rng = ChaCha12Rng::from_seed(*seed_data);
+        let mut pad_seed = [0u8; 32];
+        rng.fill_bytes(&mut pad_seed);
+        Self { choices, pad_seed }
+    }
+
+    /// Save choices as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Load choices from JSON
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let choices = serde_json::from_str(&data)?;
+        Ok(choices)
+    }
+
+    /// Unmask the garbler's answer into the evaluator's OT result
+    ///
+    /// # Arguments
+    /// * `message` - The garbler's answer to these choices
+    ///
+    /// # Returns
+    /// * `Ok(OTResult)` - The chosen labels with their bit values, plus the garbler's input labels
+    /// * `Err(GcError)` - The answer carries a label for a wire without a choice, or a garbler input overlaps the evaluator's
+    pub fn receive(&self, message: &OtSenderMessage) -> Result<OTResult> {
+        let mut selected_inputs = HashMap::with_capacity(message.masked_labels.len());
+        for (&wire_id, masked_label) in &message.masked_labels {
+            let bit_value = *self.choices.get(&wire_id).ok_or_else(|| {
+                GcError::missing_wire(
+                    wire_id,
+                    format!(
+                        "Garbler sent a label for wire {}, which has no choice",
+                        wire_id
+                    ),
+                )
+            })?;
+            let label = masked_label.xor(&choice_pad(&self.pad_seed, wire_id));
+            selected_inputs.insert(wire_id, SelectedInput { label, bit_value });
+        }
+
+        let mut ot_result = OTResult {
+            selected_inputs,
+            garbler_inputs: HashMap::new(),
+            session_id: message.session_id,
+        };
+        ot_result.set_garbler_inputs(EncodedInputs {
+            inputs: message.garbler_inputs.clone(),
+            session_id: message.session_id,
+        })?;
+        Ok(ot_result)
+    }
+}
+
+/// Garbler's message in a split OT simulation: the masked chosen labels
+///
+/// Answer to an [`OtChoices`] message; see there for the message flow.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OtSenderMessage {
+    /// Chosen label of every evaluator input wire, masked: wire_id -> label
+    #[serde(serialize_with = "serialize_sorted_map")]
+    pub masked_labels: HashMap<WireId, WireLabel>,
+    /// Garbler input labels sent without OT: wire_id -> label
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map"
+    )]
+    pub garbler_inputs: HashMap<WireId, WireLabel>,
+    /// Garbling run the labels belong to
+    pub session_id: SessionId,
+}
+
+impl OtSenderMessage {
+    /// Answer the evaluator's choices, as the garbler
+    ///
+    /// Choices for wires that are not evaluator inputs are ignored.
+    ///
+    /// # Arguments
+    /// * `wire_labels` - Wire labels from garbler output containing input/output labels and delta
+    /// * `choices` - The evaluator's choices
+    /// * `garbler_inputs` - Encoded labels of the garbler's own inputs, from [`WireLabels::encode_inputs`]
+    ///
+    /// # Returns
+    /// * `Ok(OtSenderMessage)` - The masked label of every evaluator input wire
+    /// * `Err(GcError)` - An evaluator input wire has no choice, or the garbler inputs belong to another session
+    pub fn respond(
+        wire_labels: &WireLabels,
+        choices: &OtChoices,
+        garbler_inputs: Option<&EncodedInputs>,
+    ) -> Result<Self> {
+        let mut masked_labels = HashMap::new();
+        for wire_id in wire_labels.evaluator_input_wires() {
+            let bit_value = *choices.choices.get(&wire_id).ok_or_else(|| {
+                GcError::missing_wire(
+                    wire_id,
+                    format!("No choice given for evaluator input wire {}", wire_id),
+                )
+            })?;
+            let selected = select_label(wire_labels, wire_labels.input_labels[&wire_id], bit_value);
+            masked_labels.insert(
+                wire_id,
+                selected.label.xor(&choice_pad(&choices.pad_seed, wire_id)),
+            );
+        }
 
-    Ok(OTResult { selected_inputs })
+        let garbler_inputs = match garbler_inputs {
+            Some(encoded) => {
+                encoded
+                    .session_id
+                    .check(&wire_labels.session_id, "Garbler's encoded inputs")?;
+                encoded.inputs.clone()
+            }
+            None => HashMap::new(),
+        };
+        Ok(Self {
+            masked_labels,
+            garbler_inputs,
+            session_id: wire_labels.session_id,
+        })
+    }
+
+    /// Save the message as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Load the message from JSON
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let message = serde_json::from_str(&data)?;
+        Ok(message)
+    }
+}
+
+/// One-time pad masking the chosen label of `wire_id`
+fn choice_pad(pad_seed: &[u8; 32], wire_id: WireId) -> WireLabel {
+    let mut hasher = Sha256::new();
+    hasher.update(PAD_DOMAIN);
+    hasher.update(pad_seed);
+    hasher.update(wire_id.to_le_bytes());
+    let digest = hasher.finalize();
+    WireLabel::new(digest[..16].try_into().unwrap())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::progress::NoProgress;
     use std::collections::HashMap;
 
+    #[test]
+    fn test_select_inputs() -> Result<()> {
+        let label_0 = WireLabel::new([0x01; 16]);
+        let delta = WireLabel::new([0xFF; 16]);
+        let wire_labels = WireLabels {
+            input_labels: HashMap::from([(0, label_0), (1, label_0)]),
+            output_labels: HashMap::new(),
+            delta,
+            garbler_input_wires: Vec::new(),
+            session_id: SessionId::default(),
+        };
+
+        let ot_result = select_inputs(&wire_labels, &HashMap::from([(0, false), (1, true)]))?;
+        assert_eq!(ot_result.selected_inputs[&0].label, label_0);
+        assert!(!ot_result.selected_inputs[&0].bit_value);
+        assert_eq!(ot_result.selected_inputs[&1].label, label_0.xor(&delta));
+        assert!(ot_result.selected_inputs[&1].bit_value);
+
+        let error_msg = select_inputs(&wire_labels, &HashMap::from([(0, true)]))
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("No input bit given for input wire 1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_garbler_inputs_skip_ot() -> Result<()> {
+        let label_0 = WireLabel::new([0x01; 16]);
+        let delta = WireLabel::new([0xFF; 16]);
+        let wire_labels = WireLabels {
+            input_labels: HashMap::from([(0, label_0), (1, label_0), (2, label_0)]),
+            output_labels: HashMap::new(),
+            delta,
+            garbler_input_wires: vec![1],
+            session_id: SessionId::default(),
+        };
+        assert_eq!(wire_labels.evaluator_input_wires(), vec![0, 2]);
+
+        let bits = HashMap::from([(0, false), (1, true), (2, false)]);
+        let ot_result = select_inputs(&wire_labels, &bits)?;
+        let mut ot_wires: Vec<WireId> = ot_result.selected_inputs.keys().copied().collect();
+        ot_wires.sort_unstable();
+        assert_eq!(ot_wires, vec![0, 2]);
+        assert_eq!(ot_result.garbler_inputs.len(), 1);
+        assert_eq!(ot_result.garbler_inputs[&1], label_0.xor(&delta));
+        assert_eq!(ot_result.input_label(1), Some(label_0.xor(&delta)));
+        assert_eq!(ot_result.inputs().count(), 3);
+
+        let simulated = simulate_ot(&wire_labels, &[3; 32], &NoProgress)?;
+        assert_eq!(simulated.selected_inputs.len(), 2);
+        assert!(simulated.garbler_inputs.contains_key(&1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_garbler_inputs() -> Result<()> {
+        let label_0 = WireLabel::new([0x01; 16]);
+        let delta = WireLabel::new([0xFF; 16]);
+        let wire_labels = WireLabels {
+            input_labels: HashMap::from([(0, label_0), (1, label_0), (2, label_0)]),
+            output_labels: HashMap::new(),
+            delta,
+            garbler_input_wires: vec![1, 2],
+            session_id: SessionId::default(),
+        };
+
+        let encoded = wire_labels.encode_inputs(&HashMap::from([(1, true), (2, false)]))?;
+        assert_eq!(encoded.inputs[&1], label_0.xor(&delta));
+        assert_eq!(encoded.inputs[&2], label_0);
+
+        let error_msg = wire_labels
+            .encode_inputs(&HashMap::from([(1, true)]))
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("No input bit given for garbler input wire 2"));
+        let error_msg = wire_labels
+            .encode_inputs(&HashMap::from([(0, true), (1, true), (2, true)]))
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Wire 0 is an evaluator input"));
+
+        // Both file formats roundtrip
+        let json_file = tempfile::NamedTempFile::new()?;
+        encoded.save_json(json_file.path())?;
+        let binary_file = tempfile::NamedTempFile::new()?;
+        encoded.save_binary(binary_file.path())?;
+        for path in [json_file.path(), binary_file.path()] {
+            let loaded = EncodedInputs::load(path)?;
+            assert_eq!(loaded.inputs[&1], encoded.inputs[&1]);
+            assert_eq!(loaded.inputs.len(), 2);
+        }
+
+        // The evaluator merges them with its own OT-selected inputs
+        let mut ot_result = simulate_ot(&wire_labels, &[5; 32], &NoProgress)?;
+        ot_result.set_garbler_inputs(EncodedInputs::load(binary_file.path())?)?;
+        assert_eq!(ot_result.input_label(2), Some(label_0));
+
+        let mut evaluator_input = EncodedInputs::default();
+        evaluator_input.inputs.insert(0, label_0.xor(&delta));
+        let error_msg = ot_result
+            .set_garbler_inputs(evaluator_input)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Garbler sent a label for wire 0, which is an evaluator input"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_ot_simulation_deterministic() {
         // Create test wire labels
@@ -128,23 +913,26 @@ mod tests {
         let label_0 = WireLabel::new([0x01; 16]);
         let delta = WireLabel::new([0xFF; 16]);
 
-        input_labels.insert(0, label_0);
-        input_labels.insert(1, label_0);
+        for wire_id in 0..64 {
+            input_labels.insert(wire_id, label_0);
+        }
 
         let wire_labels = WireLabels {
             input_labels,
             output_labels: HashMap::new(),
             delta,
+            garbler_input_wires: Vec::new(),
+            session_id: SessionId::default(),
         };
 
         // Use fixed seed for deterministic test
         let seed = [0u8; 32];
 
         // Run OT simulation
-        let ot_result = simulate_ot(&wire_labels, &seed).unwrap();
+        let ot_result = simulate_ot(&wire_labels, &seed, &NoProgress).unwrap();
 
-        // Verify we got results for both input wires
-        assert_eq!(ot_result.selected_inputs.len(), 2);
+        // Verify we got results for all input wires
+        assert_eq!(ot_result.selected_inputs.len(), 64);
         assert!(ot_result.selected_inputs.contains_key(&0));
         assert!(ot_result.selected_inputs.contains_key(&1));
 
@@ -159,20 +947,31 @@ mod tests {
                 assert_eq!(selected_input.label, expected_label_0);
             }
         }
+
+        // Reruns with the same seed give byte-identical JSON
+        let rerun = simulate_ot(&wire_labels, &seed, &NoProgress).unwrap();
+        assert_eq!(
+            serde_json::to_string(&rerun).unwrap(),
+            serde_json::to_string(&ot_result).unwrap()
+        );
     }
 
     #[test]
-    fn test_ot_result_serialization() -> anyhow::Result<()> {
+    fn test_ot_result_serialization() -> Result<()> {
         let mut selected_inputs = HashMap::new();
         selected_inputs.insert(
-            0u32,
+            0u64,
             SelectedInput {
                 label: WireLabel::new([0x42; 16]),
                 bit_value: true,
             },
         );
 
-        let ot_result = OTResult { selected_inputs };
+        let ot_result = OTResult {
+            selected_inputs,
+            garbler_inputs: HashMap::new(),
+            session_id: SessionId::default(),
+        };
 
         // Test JSON serialization round-trip using temporary file
         let temp_file = tempfile::NamedTempFile::new()?;
@@ -180,23 +979,43 @@ mod tests {
         let loaded_result = OTResult::load_json(temp_file.path())?;
 
         assert_eq!(loaded_result.selected_inputs.len(), 1);
-        let selected = loaded_result.get_selected_input(0u32).unwrap();
+        let selected = &loaded_result.selected_inputs[&0];
         assert_eq!(selected.label, WireLabel::new([0x42; 16]));
         assert!(selected.bit_value);
 
+        // Binary round-trip, detected by magic bytes
+        ot_result.save_binary(temp_file.path())?;
+        let loaded_result = OTResult::load(temp_file.path())?;
+        let selected = &loaded_result.selected_inputs[&0];
+        assert_eq!(selected.label, WireLabel::new([0x42; 16]));
+        assert!(selected.bit_value);
+        assert_eq!(loaded_result.session_id, ot_result.session_id);
+
+        // Wrong version
+        let mut bytes = std::fs::read(temp_file.path())?;
+        bytes[4] = 0xFF;
+        std::fs::write(temp_file.path(), &bytes)?;
+        assert!(matches!(
+            OTResult::load(temp_file.path()),
+            Err(GcError::FormatVersionMismatch { .. })
+        ));
+        assert!(OTResult::load_binary(Path::new("/nonexistent/ot.bin")).is_err());
+
         Ok(())
     }
 
     #[test]
-    fn test_ot_simulation_empty_inputs() -> anyhow::Result<()> {
+    fn test_ot_simulation_empty_inputs() -> Result<()> {
         let wire_labels = WireLabels {
             input_labels: HashMap::new(),
             output_labels: HashMap::new(),
             delta: WireLabel::new([0xFF; 16]),
+            garbler_input_wires: Vec::new(),
+            session_id: SessionId::default(),
         };
 
         let seed = [0x42; 32];
-        let ot_result = simulate_ot(&wire_labels, &seed)?;
+        let ot_result = simulate_ot(&wire_labels, &seed, &NoProgress)?;
 
         assert_eq!(ot_result.selected_inputs.len(), 0);
 
@@ -204,13 +1023,13 @@ mod tests {
     }
 
     #[test]
-    fn test_ot_simulation_multiple_inputs() -> anyhow::Result<()> {
+    fn test_ot_simulation_multiple_inputs() -> Result<()> {
         let mut input_labels = HashMap::new();
         let label_0 = WireLabel::new([0x11; 16]);
         let delta = WireLabel::new([0xAA; 16]);
 
         // Add multiple input wires
-        for i in 0..100u32 {
+        for i in 0..100u64 {
             input_labels.insert(i, label_0);
         }
 
@@ -218,17 +1037,19 @@ mod tests {
             input_labels,
             output_labels: HashMap::new(),
             delta,
+            garbler_input_wires: Vec::new(),
+            session_id: SessionId::default(),
         };
 
         let seed = [0x99; 32];
-        let ot_result = simulate_ot(&wire_labels, &seed)?;
+        let ot_result = simulate_ot(&wire_labels, &seed, &NoProgress)?;
 
         // Should have selected labels for all 100 inputs
         assert_eq!(ot_result.selected_inputs.len(), 100);
 
         // Verify each selection is valid
-        for i in 0..100u32 {
-            let selected = ot_result.get_selected_input(i).unwrap();
+        for i in 0..100u64 {
+            let selected = &ot_result.selected_inputs[&i];
             let expected_label_0 = wire_labels.input_labels[&i];
             let expected_label_1 = expected_label_0.xor(&delta);
 
@@ -250,13 +1071,15 @@ mod tests {
     fn test_ot_result_get_nonexistent_wire() {
         let ot_result = OTResult {
             selected_inputs: HashMap::new(),
+            garbler_inputs: HashMap::new(),
+            session_id: SessionId::default(),
         };
 
-        assert!(ot_result.get_selected_input(999u32).is_none());
+        assert!(ot_result.input_label(999u64).is_none());
     }
 
     #[test]
-    fn test_ot_simulation_label_consistency() -> anyhow::Result<()> {
+    fn test_ot_simulation_label_consistency() -> Result<()> {
         // Test that the XOR relationship holds: label_1 = label_0 XOR delta
         let mut input_labels = HashMap::new();
         let label_0 = WireLabel::new([
@@ -268,20 +1091,22 @@ mod tests {
             0x0F, 0x10,
         ]);
 
-        input_labels.insert(42u32, label_0);
+        input_labels.insert(42u64, label_0);
 
         let wire_labels = WireLabels {
             input_labels,
             output_labels: HashMap::new(),
             delta,
+            garbler_input_wires: Vec::new(),
+            session_id: SessionId::default(),
         };
 
         // Run OT simulation multiple times to test both bit values
         for seed_byte in 0..10u8 {
             let seed = [seed_byte; 32];
-            let ot_result = simulate_ot(&wire_labels, &seed)?;
+            let ot_result = simulate_ot(&wire_labels, &seed, &NoProgress)?;
 
-            let selected = ot_result.get_selected_input(42u32).unwrap();
+            let selected = &ot_result.selected_inputs[&42];
 
             if selected.bit_value {
                 // Should be label_1 = label_0 XOR delta
@@ -295,4 +1120,107 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_split_ot_simulation_matches_select_inputs() -> Result<()> {
+        let label_0 = WireLabel::new([0x01; 16]);
+        let wire_labels = WireLabels {
+            input_labels: (0..6).map(|wire_id| (wire_id, label_0)).collect(),
+            output_labels: HashMap::new(),
+            delta: WireLabel::new([0xFF; 16]),
+            garbler_input_wires: vec![4, 5],
+            session_id: SessionId::new([3; 16]),
+        };
+        let bits: HashMap<WireId, bool> =
+            (0..6).map(|wire_id| (wire_id, wire_id % 2 == 1)).collect();
+        let encoded = wire_labels.encode_inputs(&HashMap::from([(4, false), (5, true)]))?;
+
+        let choices = OtChoices::new(bits.clone(), &[1; 32]);
+        let message = OtSenderMessage::respond(&wire_labels, &choices, Some(&encoded))?;
+        assert_eq!(message.masked_labels.len(), 4);
+        assert_ne!(message.masked_labels[&0], label_0);
+
+        let ot_result = choices.receive(&message)?;
+        let expected = select_inputs(&wire_labels, &bits)?;
+        for (wire_id, label) in expected.inputs() {
+            assert_eq!(ot_result.input_label(wire_id), Some(label));
+        }
+        for (wire_id, input) in &expected.selected_inputs {
+            assert_eq!(
+                ot_result.selected_inputs[wire_id].bit_value,
+                input.bit_value
+            );
+        }
+        assert_eq!(ot_result.selected_inputs.len(), 4);
+        assert_eq!(ot_result.session_id, wire_labels.session_id);
+
+        // Each role rejects a message that doesn't cover its wires
+        let partial = OtChoices::new(HashMap::from([(0, true)]), &[1; 32]);
+        let error_msg = OtSenderMessage::respond(&wire_labels, &partial, None)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("No choice given for evaluator input wire 1"));
+        assert!(partial.receive(&message).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_streaming_ot_simulation_matches_simulate_ot() -> Result<()> {
+        let wire_labels = WireLabels {
+            input_labels: (0..50)
+                .map(|wire_id| (wire_id, WireLabel::new([wire_id as u8; 16])))
+                .collect(),
+            output_labels: HashMap::new(),
+            delta: WireLabel::new([0xFF; 16]),
+            garbler_input_wires: vec![3, 17, 40],
+            session_id: SessionId::new([5; 16]),
+        };
+        let expected = simulate_ot(&wire_labels, &[9; 32], &NoProgress)?;
+        let dir = tempfile::tempdir()?;
+
+        for binary in [false, true] {
+            let path = dir.path().join(if binary { "ot.bin" } else { "ot.json" });
+            let stats = simulate_ot_streaming(&wire_labels, &[9; 32], &path, binary, &NoProgress)?;
+            assert_eq!((stats.evaluator_inputs, stats.garbler_inputs), (47, 3));
+
+            let streamed = OTResult::load(&path)?;
+            assert_eq!(streamed.selected_inputs.len(), 47);
+            assert_eq!(streamed.garbler_inputs.len(), 3);
+            assert_eq!(streamed.session_id, expected.session_id);
+            for (wire_id, label) in expected.inputs() {
+                assert_eq!(streamed.input_label(wire_id), Some(label));
+            }
+            for (wire_id, input) in &expected.selected_inputs {
+                assert_eq!(streamed.selected_inputs[wire_id].bit_value, input.bit_value);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_inputs_leaves_garbler_inputs_to_encode_inputs() -> Result<()> {
+        let label_0 = WireLabel::new([0x01; 16]);
+        let wire_labels = WireLabels {
+            input_labels: (0..4).map(|wire_id| (wire_id, label_0)).collect(),
+            output_labels: HashMap::new(),
+            delta: WireLabel::new([0xFF; 16]),
+            garbler_input_wires: vec![2, 3],
+            session_id: SessionId::default(),
+        };
+
+        // Evaluator bits alone are enough; the garbler's labels come separately
+        let mut ot_result = select_inputs(&wire_labels, &HashMap::from([(0, true), (1, false)]))?;
+        assert_eq!(ot_result.selected_inputs.len(), 2);
+        assert!(ot_result.garbler_inputs.is_empty());
+        ot_result.set_garbler_inputs(
+            wire_labels.encode_inputs(&HashMap::from([(2, true), (3, true)]))?,
+        )?;
+        assert_eq!(ot_result.inputs().count(), 4);
+
+        // A partial garbler assignment is rejected like in encode_inputs
+        let bits = HashMap::from([(0, true), (1, false), (2, true)]);
+        let error_msg = select_inputs(&wire_labels, &bits).unwrap_err().to_string();
+        assert!(error_msg.contains("No input bit given for garbler input wire 3"));
+        Ok(())
+    }
 }
@@ -0,0 +1,516 @@
+use crate::error::{GcError, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Cursor, ErrorKind, Read, Write};
+use std::path::Path;
+
+use crate::channel::{Channel, FrameHeader, RecordedMessage};
+use crate::constants::{
+    BUFFER_SIZE, FRAME_HEADER_SIZE, OT_TRANSCRIPT_MAGIC, OT_TRANSCRIPT_VERSION,
+};
+use crate::garbler::WireLabels;
+use crate::gate::WireId;
+use crate::ot_extension::{OtExtensionBackend, OtSecurity};
+use crate::ot_remote::{decode_garbler_inputs_message, receive_ot, send_ot};
+use crate::ot_simulation::{OTResult, select_label};
+use crate::progress::NoProgress;
+use crate::session::SessionId;
+
+/// One of the two parties of an OT run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtParty {
+    /// The OT sender, holding the wire labels
+    Garbler,
+    /// The OT receiver, holding the choice bits
+    Evaluator,
+}
+
+impl fmt::Display for OtParty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtParty::Garbler => write!(f, "garbler"),
+            OtParty::Evaluator => write!(f, "evaluator"),
+        }
+    }
+}
+
+/// A message of an OT transcript
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscriptMessage {
+    /// The party that sent the message
+    pub from: OtParty,
+    /// The message bytes
+    pub bytes: Vec<u8>,
+}
+
+/// Every message exchanged in one run of [`send_ot`] and [`receive_ot`], in order
+///
+/// Recorded by either party through [`Channel::with_recording`], and checked
+/// after the fact by [`verify_ot_transcript`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OtTranscript {
+    /// The messages in the order they were sent
+    pub messages: Vec<TranscriptMessage>,
+}
+
+impl OtTranscript {
+    /// Build a transcript from the messages a channel recorded
+    ///
+    /// # Arguments
+    /// * `recorded` - Messages recorded by the channel, from [`Channel::recorded`]
+    /// * `recorder` - The party that owned the channel
+    pub fn from_recording(recorded: &[RecordedMessage], recorder: OtParty) -> Self {
+        let other = match recorder {
+            OtParty::Garbler => OtParty::Evaluator,
+            OtParty::Evaluator => OtParty::Garbler,
+        };
+        let messages = recorded
+            .iter()
+            .map(|message| TranscriptMessage {
+                from: if message.sent { recorder } else { other },
+                bytes: message.bytes.clone(),
+            })
+            .collect();
+        Self { messages }
+    }
+
+    /// Save the transcript
+    ///
+    /// Layout: [`OT_TRANSCRIPT_MAGIC`], the format version as a
+    /// little-endian `u32`, then per message the sender (0 for the garbler,
+    /// 1 for the evaluator), the length as a little-endian `u64` and the bytes.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::with_capacity(BUFFER_SIZE, file);
+        writer.write_all(&OT_TRANSCRIPT_MAGIC)?;
+        writer.write_all(&OT_TRANSCRIPT_VERSION.to_le_bytes())?;
+        for message in &self.messages {
+            writer.write_all(&[match message.from {
+                OtParty::Garbler => 0,
+                OtParty::Evaluator => 1,
+            }])?;
+            writer.write_all(&(message.bytes.len() as u64).to_le_bytes())?;
+            writer.write_all(&message.bytes)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load a transcript saved by [`OtTranscript::save`]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        let mut reader = BufReader::with_capacity(BUFFER_SIZE, file);
+
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        if header[..4] != OT_TRANSCRIPT_MAGIC {
+            return Err(GcError::invalid_format(
+                "OT transcript file",
+                "bad magic bytes",
+            ));
+        }
+        let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if version != OT_TRANSCRIPT_VERSION {
+            return Err(GcError::FormatVersionMismatch {
+                format: "OT transcript",
+                found: version,
+                expected: OT_TRANSCRIPT_VERSION,
+            });
+        }
+
+        let mut messages = Vec::new();
+        loop {
+            let mut from = [0u8; 1];
+            match reader.read_exact(&mut from) {
+                Ok(()) => {}
+                Err(error) if error.kind() == ErrorKind::UnexpectedEof => break,
+                Err(error) => return Err(error.into()),
+            }
+            let from = match from[0] {
+                0 => OtParty::Garbler,
+                1 => OtParty::Evaluator,
+                other => {
+                    return Err(GcError::invalid_format(
+                        "OT transcript file",
+                        format!("unknown sender {} of message {}", other, messages.len() + 1),
+                    ));
+                }
+            };
+            let mut length = [0u8; 8];
+            reader.read_exact(&mut length)?;
+            let mut bytes = Vec::new();
+            (&mut reader)
+                .take(u64::from_le_bytes(length))
+                .read_to_end(&mut bytes)?;
+            if bytes.len() as u64 != u64::from_le_bytes(length) {
+                return Err(GcError::invalid_format(
+                    "OT transcript file",
+                    format!("message {} is truncated", messages.len() + 1),
+                ));
+            }
+            messages.push(TranscriptMessage { from, bytes });
+        }
+        Ok(Self { messages })
+    }
+}
+
+/// Outcome of a successful [`verify_ot_transcript`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscriptReport {
+    /// Number of OTs in the run
+    pub ots: usize,
+    /// Security level announced by the garbler
+    pub security: OtSecurity,
+    /// OT extension backend announced by the garbler
+    pub backend: OtExtensionBackend,
+    /// Number of garbler input labels sent along with the OTs
+    pub garbler_inputs: usize,
+    /// Number of evaluator labels checked, if the evaluator's side was replayed
+    pub evaluator_inputs: Option<usize>,
+}
+
+/// Check an OT transcript against the committed wire labels and revealed seeds
+///
+/// Replays [`send_ot`] with the garbler's seed against the evaluator's
+/// messages in the transcript: every garbler message must come out byte for
+/// byte as recorded, which shows the garbler transferred exactly the
+/// committed labels. The garbler's input labels, sent without
+/// OT, are checked against the committed labels too.
+///
+/// Given the evaluator's seed and the OT result it produced, the evaluator's
+/// side is replayed the same way with the bits of that result, and every
+/// label it received must match both the result and the committed labels.
+///
+/// # Arguments
+/// * `transcript` - Messages of the run
+/// * `wire_labels` - The garbler's committed wire labels
+/// * `garbler_seed` - The seed the garbler passed to [`send_ot`]
+/// * `evaluator` - The seed the evaluator passed to [`receive_ot`] and the OT result it obtained, if revealed
+///
+/// # Returns
+/// * `Ok(TranscriptReport)` - Every check passed
+/// * `Err(GcError)` - A message differs from the replay, a label differs from the committed ones, or the transcript belongs to another session
+#[tracing::instrument(name = "ot_verify", skip_all)]
+pub fn verify_ot_transcript(
+    transcript: &OtTranscript,
+    wire_labels: &WireLabels,
+    garbler_seed: &[u8; 32],
+    evaluator: Option<(&[u8; 32], &OTResult)>,
+) -> Result<TranscriptReport> {
+    // The hello announces the session, the security level and the backend
+    let hello = transcript
+        .messages
+        .first()
+        .filter(|message| message.from == OtParty::Garbler && message.bytes.len() >= 18)
+        .ok_or_else(|| check_failed("the transcript does not start with the garbler's hello"))?;
+    SessionId::new(hello.bytes[..16].try_into().unwrap())
+        .check(&wire_labels.session_id, "OT transcript")?;
+    let security = OtSecurity::from_id(hello.bytes[16])
+        .ok_or_else(|| check_failed("the hello announces an unknown security level"))?;
+    let backend = OtExtensionBackend::from_id(hello.bytes[17])
+        .ok_or_else(|| check_failed("the hello announces an unknown extension backend"))?;
+
+    // The garbler's last message carries its own input labels
+    let encoded_message = transcript
+        .messages
+        .iter()
+        .rev()
+        .find(|message| message.from == OtParty::Garbler)
+        .unwrap();
+    let encoded = decode_garbler_inputs_message(&encoded_message.bytes)
+        .map_err(|_| check_failed("the garbler's last message is not its encoded inputs"))?;
+    if let Some(wire_id) = encoded
+        .inputs
+        .iter()
+        .find(|&(&wire_id, label)| wire_labels.input_bit(wire_id, label).is_err())
+        .map(|(wire_id, _)| wire_id)
+    {
+        return Err(check_failed(&format!(
+            "the garbler's label for its input wire {} is not a committed label",
+            wire_id
+        )));
+    }
+
+    let mut channel = Channel::new(ReplayStream::new(&transcript.messages, OtParty::Garbler));
+    let sent = send_ot(
+        &mut channel,
+        wire_labels,
+        (!encoded.inputs.is_empty()).then_some(&encoded),
+        security,
+        backend,
+        garbler_seed,
+        &NoProgress,
+    );
+    let ots = channel.into_inner().finish(sent)?.evaluator_inputs as usize;
+
+    let evaluator_inputs = match evaluator {
+        Some((evaluator_seed, ot_result)) => {
+            let input_bits: HashMap<WireId, bool> = ot_result
+                .selected_inputs
+                .iter()
+                .map(|(&wire_id, input)| (wire_id, input.bit_value))
+                .collect();
+            let mut channel =
+                Channel::new(ReplayStream::new(&transcript.messages, OtParty::Evaluator));
+            let received = receive_ot(
+                &mut channel,
+                &input_bits,
+                security,
+                backend,
+                evaluator_seed,
+                &NoProgress,
+            );
+            let (replayed, _) = channel.into_inner().finish(received)?;
+
+            for (&wire_id, input) in &replayed.selected_inputs {
+                let committed = select_label(
+                    wire_labels,
+                    wire_labels.input_labels[&wire_id],
+                    input.bit_value,
+                );
+                if input.label != committed.label {
+                    return Err(check_failed(&format!(
+                        "the evaluator received a label for wire {} that is not a committed label",
+                        wire_id
+                    )));
+                }
+                if ot_result.selected_inputs[&wire_id].label != input.label {
+                    return Err(check_failed(&format!(
+                        "the OT result's label for wire {} differs from the one the transcript delivers",
+                        wire_id
+                    )));
+                }
+            }
+            Some(replayed.selected_inputs.len())
+        }
+        None => None,
+    };
+
+    tracing::info!(ots, %security, %backend, "OT transcript verified");
+    Ok(TranscriptReport {
+        ots,
+        security,
+        backend,
+        garbler_inputs: encoded.inputs.len(),
+        evaluator_inputs,
+    })
+}
+
+/// Stream replaying one party of a transcript
+///
+/// Reads return the other party's recorded messages, framed as a
+/// [`Channel`] expects them. Each flush, which ends every [`Channel::send`],
+/// compares what the party wrote against its next recorded message.
+struct ReplayStream<'a> {
+    messages: &'a [TranscriptMessage],
+    party: OtParty,
+    next: usize,
+    incoming: Cursor<Vec<u8>>,
+    outgoing: Vec<u8>,
+    mismatch: Option<String>,
+}
+
+impl<'a> ReplayStream<'a> {
+    fn new(messages: &'a [TranscriptMessage], party: OtParty) -> Self {
+        Self {
+            messages,
+            party,
+            next: 0,
+            incoming: Cursor::new(Vec::new()),
+            outgoing: Vec::new(),
+            mismatch: None,
+        }
+    }
+
+    /// Remember the first deviation from the transcript and fail the I/O call
+    fn deviate(&mut self, reason: String) -> io::Error {
+        let error = io::Error::other(reason.clone());
+        self.mismatch.get_or_insert(reason);
+        error
+    }
+
+    /// Turn the replayed party's outcome into the verification outcome
+    fn finish<T>(self, outcome: Result<T>) -> Result<T> {
+        if let Some(mismatch) = self.mismatch {
+            return Err(check_failed(&mismatch));
+        }
+        let value = outcome?;
+        if self.next != self.messages.len() {
+            return Err(check_failed(&format!(
+                "the {} finished after message {} of {}",
+                self.party,
+                self.next,
+                self.messages.len()
+            )));
+        }
+        Ok(value)
+    }
+}
+
+impl Read for ReplayStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.incoming.position() == self.incoming.get_ref().len() as u64 {
+            let Some(message) = self.messages.get(self.next) else {
+                return Ok(0);
+            };
+            if message.from == self.party {
+                return Err(self.deviate(format!(
+                    "the {} waits for message {}, which the transcript has it send",
+                    self.party,
+                    self.next + 1
+                )));
+            }
+            let mut framed = FrameHeader::message(message.bytes.len())
+                .to_bytes()
+                .to_vec();
+            framed.extend_from_slice(&message.bytes);
+            self.incoming = Cursor::new(framed);
+            self.next += 1;
+        }
+        self.incoming.read(buf)
+    }
+}
+
+impl Write for ReplayStream<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.outgoing.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let written = std::mem::take(&mut self.outgoing);
+        let index = self.next + 1;
+        let matches = self.messages.get(self.next).is_some_and(|message| {
+            message.from == self.party
+                && written.len() == FRAME_HEADER_SIZE + message.bytes.len()
+                && written[FRAME_HEADER_SIZE..] == message.bytes[..]
+        });
+        if !matches {
+            return Err(self.deviate(format!(
+                "message {} recomputed by the {} differs from the transcript",
+                index, self.party
+            )));
+        }
+        self.next += 1;
+        Ok(())
+    }
+}
+
+/// Error for a transcript that fails a check
+fn check_failed(reason: &str) -> GcError {
+    GcError::Protocol(format!("OT transcript check failed: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::garbler::WireLabel;
+    use std::net::{TcpListener, TcpStream};
+
+    fn test_wire_labels() -> WireLabels {
+        WireLabels {
+            input_labels: (0..12)
+                .map(|wire_id| (wire_id, WireLabel::new([wire_id as u8 + 1; 16])))
+                .collect(),
+            output_labels: HashMap::new(),
+            delta: WireLabel::new([0x3C; 16]),
+            garbler_input_wires: vec![0, 1, 2],
+            session_id: SessionId::new([4; 16]),
+        }
+    }
+
+    /// Run OT over TCP, recording the evaluator's channel
+    fn recorded_run(bits: &HashMap<WireId, bool>) -> Result<(OtTranscript, OTResult)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let garbler = std::thread::spawn(move || {
+            let wire_labels = test_wire_labels();
+            let encoded =
+                wire_labels.encode_inputs(&HashMap::from([(0, true), (1, false), (2, true)]))?;
+            let (stream, _) = listener.accept()?;
+            send_ot(
+                &mut Channel::new(stream),
+                &wire_labels,
+                Some(&encoded),
+                OtSecurity::Malicious,
+                OtExtensionBackend::SoftSpoken { k: 2 },
+                &[1; 32],
+                &NoProgress,
+            )
+        });
+
+        let mut channel = Channel::new(TcpStream::connect(address)?).with_recording();
+        let (ot_result, _) = receive_ot(
+            &mut channel,
+            bits,
+            OtSecurity::Malicious,
+            OtExtensionBackend::SoftSpoken { k: 2 },
+            &[2; 32],
+            &NoProgress,
+        )?;
+        garbler.join().unwrap()?;
+        Ok((
+            OtTranscript::from_recording(channel.recorded(), OtParty::Evaluator),
+            ot_result,
+        ))
+    }
+
+    #[test]
+    fn test_verify_ot_transcript() -> Result<()> {
+        let bits: HashMap<WireId, bool> =
+            (3..12).map(|wire_id| (wire_id, wire_id % 2 == 0)).collect();
+        let (transcript, ot_result) = recorded_run(&bits)?;
+        let wire_labels = test_wire_labels();
+
+        // Round trip through a file
+        let file = tempfile::NamedTempFile::new()?;
+        transcript.save(file.path())?;
+        let transcript = OtTranscript::load(file.path())?;
+        assert_eq!(transcript.messages.len(), 6);
+
+        let report = verify_ot_transcript(
+            &transcript,
+            &wire_labels,
+            &[1; 32],
+            Some((&[2; 32], &ot_result)),
+        )?;
+        assert_eq!(report.ots, 9);
+        assert_eq!(report.garbler_inputs, 3);
+        assert_eq!(report.evaluator_inputs, Some(9));
+        assert_eq!(report.backend, OtExtensionBackend::SoftSpoken { k: 2 });
+
+        // A wrong seed or different labels don't reproduce the garbler's messages
+        let error_msg = verify_ot_transcript(&transcript, &wire_labels, &[9; 32], None)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("recomputed by the garbler differs from the transcript"));
+        let mut other_labels = test_wire_labels();
+        other_labels
+            .input_labels
+            .insert(7, WireLabel::new([0xEE; 16]));
+        assert!(verify_ot_transcript(&transcript, &other_labels, &[1; 32], None).is_err());
+
+        // A tampered transfer message is caught
+        let mut tampered = transcript.clone();
+        tampered.messages[4].bytes[5] ^= 1;
+        let error_msg = verify_ot_transcript(&tampered, &wire_labels, &[1; 32], None)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("message 5 recomputed by the garbler differs"));
+
+        // An OT result that doesn't match what the transcript delivers
+        let mut forged: OTResult = serde_json::from_str(&serde_json::to_string(&ot_result)?)?;
+        forged.selected_inputs.get_mut(&3).unwrap().label = WireLabel::new([0; 16]);
+        assert!(
+            verify_ot_transcript(
+                &transcript,
+                &wire_labels,
+                &[1; 32],
+                Some((&[2; 32], &forged))
+            )
+            .is_err()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,697 @@
+use crate::error::{GcError, Result};
+
+use crate::gate::{Gate, WireId};
+use crate::stream::BufferedLineStream;
+use std::io::{Read, Write};
+use std::ops::Range;
+
+/// Header of a Bristol circuit (`<num_gates> <num_wires>`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitHeader {
+    /// Number of gate lines that follow the header
+    pub num_gates: u64,
+    /// Total number of wires in the circuit
+    pub num_wires: u64,
+    /// Input and output declarations of a Bristol Fashion header, if present
+    pub io: Option<IoDeclaration>,
+    /// Number of lines the header takes, so the first gate is on line `lines + 1`
+    pub lines: u64,
+}
+
+/// Input and output declarations of a Bristol Fashion circuit
+///
+/// Bristol Fashion headers follow `<num_gates> <num_wires>` with a line
+/// `<count> <width>...` for the inputs and one for the outputs, then a blank
+/// line. The inputs take the first wires in declaration order and the
+/// outputs the last ones, e.g. `2 64 64` and `1 64` for a 64-bit adder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IoDeclaration {
+    /// Bit width of each input, in wire order
+    pub input_widths: Vec<u64>,
+    /// Bit width of each output, in wire order
+    pub output_widths: Vec<u64>,
+}
+
+impl IoDeclaration {
+    /// Number of input wires over all inputs
+    pub fn num_input_wires(&self) -> u64 {
+        self.input_widths.iter().sum()
+    }
+
+    /// Number of output wires over all outputs
+    pub fn num_output_wires(&self) -> u64 {
+        self.output_widths.iter().sum()
+    }
+
+    /// Wires holding the inputs, `0..num_input_wires`
+    pub fn input_wires(&self) -> Range<WireId> {
+        0..self.num_input_wires()
+    }
+
+    /// Wires holding the outputs, the last `num_output_wires` of the circuit
+    pub fn output_wires(&self, num_wires: u64) -> Range<WireId> {
+        num_wires - self.num_output_wires()..num_wires
+    }
+
+    /// Declarations of a circuit with `num_wires` wires
+    ///
+    /// # Returns
+    /// * `Ok(IoDeclaration)` - The declarations
+    /// * `Err(GcError)` - The inputs and outputs take more than `num_wires` wires
+    pub fn new(input_widths: Vec<u64>, output_widths: Vec<u64>, num_wires: u64) -> Result<Self> {
+        let io = Self {
+            input_widths,
+            output_widths,
+        };
+        let declared = io.num_input_wires() + io.num_output_wires();
+        if declared > num_wires {
+            return Err(GcError::parse(
+                3,
+                format!(
+                    "Declared inputs and outputs take {} wires, but the circuit has {}",
+                    declared, num_wires
+                ),
+            ));
+        }
+        Ok(io)
+    }
+}
+
+/// Read and parse the header of a Bristol circuit stream
+///
+/// Accepts plain Bristol headers (`<num_gates> <num_wires>`) and Bristol
+/// Fashion headers with input and output declarations. After this call the
+/// stream is positioned at the first gate line, which is line
+/// `header.lines + 1` of the file.
+pub fn read_header<R: Read>(stream: &mut BufferedLineStream<R>) -> Result<CircuitHeader> {
+    let header_line = stream
+        .next_line()
+        .ok_or_else(|| GcError::parse(1, "Missing header"))??;
+    let mut header = parse_header_line(header_line)?;
+
+    let Some(line) = stream.next_line() else {
+        return Ok(header);
+    };
+    let Some(input_widths) = parse_declaration_line(line?, 2, "input")? else {
+        // Plain Bristol: line 2 is already a gate
+        stream.push_back();
+        return Ok(header);
+    };
+    let line = stream
+        .next_line()
+        .ok_or_else(|| GcError::parse(3, "Missing output declaration"))??;
+    let output_widths = parse_declaration_line(line, 3, "output")?
+        .ok_or_else(|| GcError::parse(3, "Missing output declaration"))?;
+    header.lines = 3;
+
+    // Blank lines separate the declarations from the gates
+    while let Some(line) = stream.next_line() {
+        if !line?.is_empty() {
+            stream.push_back();
+            break;
+        }
+        header.lines += 1;
+    }
+
+    header.io = Some(IoDeclaration::new(
+        input_widths,
+        output_widths,
+        header.num_wires,
+    )?);
+    Ok(header)
+}
+
+/// Write the header of a Bristol circuit, as read back by [`read_header`]
+///
+/// # Arguments
+/// * `writer` - Destination of the text
+/// * `num_gates` - Number of gate lines that will follow
+/// * `num_wires` - Total number of wires in the circuit
+/// * `io` - Bristol Fashion declarations, if any
+pub fn write_header<W: Write>(
+    writer: &mut W,
+    num_gates: u64,
+    num_wires: u64,
+    io: Option<&IoDeclaration>,
+) -> Result<()> {
+    writeln!(writer, "{} {}", num_gates, num_wires)?;
+    if let Some(io) = io {
+        for widths in [&io.input_widths, &io.output_widths] {
+            write!(writer, "{}", widths.len())?;
+            for width in widths {
+                write!(writer, " {}", width)?;
+            }
+            writeln!(writer)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Writes a Bristol circuit one gate at a time
+///
+/// The header is written up front, so the number of gates must be known;
+/// [`finish`](Self::finish) checks that exactly that many were written. The
+/// counterpart of [`BinaryCircuitWriter`](crate::binary_circuit::BinaryCircuitWriter)
+/// for Bristol text.
+#[derive(Debug)]
+pub struct BristolWriter<W: Write> {
+    writer: W,
+    num_gates: u64,
+    num_wires: u64,
+    gates_written: u64,
+}
+
+impl<W: Write> BristolWriter<W> {
+    /// Write the header and start writing gates into `writer`
+    ///
+    /// # Arguments
+    /// * `writer` - Destination of the text
+    /// * `num_gates` - Number of gates that will be written
+    /// * `num_wires` - Total number of wires in the circuit
+    /// * `io` - Bristol Fashion declarations, if any
+    pub fn new(
+        mut writer: W,
+        num_gates: u64,
+        num_wires: u64,
+        io: Option<&IoDeclaration>,
+    ) -> Result<Self> {
+        write_header(&mut writer, num_gates, num_wires, io)?;
+        Ok(Self {
+            writer,
+            num_gates,
+            num_wires,
+            gates_written: 0,
+        })
+    }
+
+    /// Append a gate line
+    ///
+    /// # Returns
+    /// * `Ok(())` - The gate is written
+    /// * `Err(GcError)` - A wire exceeds `num_wires`, the header's gate count is exceeded, or
+    ///   writing failed
+    pub fn write_gate(&mut self, gate: &Gate) -> Result<()> {
+        if let Some(wire_id) = gate
+            .input_wires()
+            .chain([gate.output_wire])
+            .find(|&wire_id| wire_id >= self.num_wires)
+        {
+            return Err(GcError::InvalidCircuit(format!(
+                "Wire {} exceeds num_wires ({})",
+                wire_id, self.num_wires
+            )));
+        }
+        if self.gates_written == self.num_gates {
+            return Err(GcError::InvalidCircuit(format!(
+                "More gates than the {} declared in the header",
+                self.num_gates
+            )));
+        }
+        writeln!(self.writer, "{}", gate)?;
+        self.gates_written += 1;
+        Ok(())
+    }
+
+    /// Append every gate of `gates`
+    pub fn write_gates(&mut self, gates: impl IntoIterator<Item = Gate>) -> Result<()> {
+        gates
+            .into_iter()
+            .try_for_each(|gate| self.write_gate(&gate))
+    }
+
+    /// Number of gates written so far
+    pub fn gates_written(&self) -> u64 {
+        self.gates_written
+    }
+
+    /// The underlying writer, e.g. to drain a buffer as gates are written
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Flush the text after checking the gate count
+    ///
+    /// # Returns
+    /// * `Ok(W)` - The underlying writer, flushed
+    /// * `Err(GcError)` - Fewer gates were written than the header declares
+    pub fn finish(mut self) -> Result<W> {
+        if self.gates_written != self.num_gates {
+            return Err(GcError::InvalidCircuit(format!(
+                "The header declares {} gates, but {} were written",
+                self.num_gates, self.gates_written
+            )));
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Parse a Bristol header line (`<num_gates> <num_wires>`)
+///
+/// The returned header has no input and output declarations; those are on
+/// the following lines, which [`read_header`] reads as well.
+pub fn parse_header_line(header_line: &str) -> Result<CircuitHeader> {
+    let header_tokens: Vec<&str> = header_line.split_whitespace().collect();
+    if header_tokens.len() != 2 {
+        return Err(GcError::parse(
+            1,
+            format!(
+                "Invalid header: expected '<num_gates> <num_wires>', got: '{}'",
+                header_line
+            ),
+        ));
+    }
+
+    let num_gates: u64 = header_tokens[0]
+        .parse()
+        .map_err(|_| GcError::parse(1, format!("Invalid num_gates: '{}'", header_tokens[0])))?;
+    let num_wires: u64 = header_tokens[1]
+        .parse()
+        .map_err(|_| GcError::parse(1, format!("Invalid num_wires: '{}'", header_tokens[1])))?;
+
+    Ok(CircuitHeader {
+        num_gates,
+        num_wires,
+        io: None,
+        lines: 1,
+    })
+}
+
+/// Parse a Bristol Fashion declaration line (`<count> <width>...`)
+///
+/// Gate lines always end in a gate type, so a nonblank line of numbers only
+/// is a declaration.
+///
+/// # Arguments
+/// * `line` - Line 2 or 3 of the circuit
+/// * `line_number` - Line number used in error messages
+/// * `kind` - `input` or `output`, for error messages
+///
+/// # Returns
+/// * `Ok(Some(widths))` - Bit width of every declared input or output
+/// * `Ok(None)` - The line is not a declaration
+/// * `Err(GcError)` - The count does not match the number of widths
+pub fn parse_declaration_line(
+    line: &str,
+    line_number: u64,
+    kind: &str,
+) -> Result<Option<Vec<u64>>> {
+    let Ok(numbers) = line
+        .split_whitespace()
+        .map(str::parse::<u64>)
+        .collect::<std::result::Result<Vec<u64>, _>>()
+    else {
+        return Ok(None);
+    };
+    if numbers.is_empty() {
+        return Ok(None);
+    }
+    match numbers.split_first() {
+        Some((&count, widths)) if count == widths.len() as u64 => Ok(Some(widths.to_vec())),
+        _ => Err(GcError::parse(
+            line_number,
+            format!(
+                "Invalid {} declaration: expected '<count> <width>...', got: '{}'",
+                kind, line
+            ),
+        )),
+    }
+}
+
+/// Parse a single Bristol gate line
+///
+/// Accepts `2 1 <in1> <in2> <out> XOR|AND`, `1 1 <in> <out> EQW|BUF` and
+/// `1 1 <0|1> <out> EQ`. `line_number` is only used in error messages.
+pub fn parse_gate_line(line: &str, line_number: u64) -> Result<Gate> {
+    if line.trim().is_empty() {
+        return Err(GcError::parse(line_number, "Empty line"));
+    }
+
+    // Parse gate line directly using iterator (PERFORMANCE CRITICAL)
+    let mut tokens = line.split_whitespace();
+
+    // Parse num_inputs and num_outputs
+    let num_inputs: u32 = tokens
+        .next()
+        .ok_or_else(|| GcError::parse(line_number, "Missing num_inputs"))?
+        .parse()
+        .map_err(|_| GcError::parse(line_number, format!("Invalid num_inputs: '{}'", line)))?;
+
+    let num_outputs: u32 = tokens
+        .next()
+        .ok_or_else(|| GcError::parse(line_number, "Missing num_outputs"))?
+        .parse()
+        .map_err(|_| GcError::parse(line_number, format!("Invalid num_outputs: '{}'", line)))?;
+
+    // Validate gate format (1 or 2 inputs, 1 output)
+    if !(1..=2).contains(&num_inputs) || num_outputs != 1 {
+        return Err(GcError::parse(
+            line_number,
+            format!(
+                "Gate must have 1 or 2 inputs and 1 output, got {} inputs, {} outputs",
+                num_inputs, num_outputs
+            ),
+        ));
+    }
+
+    // Input tokens are parsed once the gate type is known, since the input
+    // of an EQ gate is a constant rather than a wire (no Vec allocation)
+    let mut input_tokens = [""; 2];
+    for (i, input_token) in input_tokens
+        .iter_mut()
+        .take(num_inputs as usize)
+        .enumerate()
+    {
+        *input_token = tokens
+            .next()
+            .ok_or_else(|| GcError::parse(line_number, format!("Missing input wire {}", i + 1)))?;
+    }
+    let parse_input = |i: usize| -> Result<WireId> {
+        input_tokens[i].parse().map_err(|_| {
+            GcError::parse(
+                line_number,
+                format!("Invalid input wire {}: '{}'", i + 1, line),
+            )
+        })
+    };
+
+    // Parse output wire directly (no Vec allocation)
+    let output_wire: WireId = tokens
+        .next()
+        .ok_or_else(|| GcError::parse(line_number, "Missing output wire"))?
+        .parse()
+        .map_err(|_| GcError::parse(line_number, format!("Invalid output wire: '{}'", line)))?;
+
+    // Parse gate type (NO .to_string() allocation)
+    let gate_type = tokens
+        .next()
+        .ok_or_else(|| GcError::parse(line_number, format!("Missing gate type: '{}'", line)))?;
+
+    // Validate no extra tokens
+    if tokens.next().is_some() {
+        return Err(GcError::parse(
+            line_number,
+            format!("Too many tokens: '{}'", line),
+        ));
+    }
+
+    let gate = match (num_inputs, gate_type) {
+        (2, "XOR") => Gate::xor(parse_input(0)?, parse_input(1)?, output_wire),
+        (2, "AND") => Gate::and(parse_input(0)?, parse_input(1)?, output_wire),
+        (1, "EQW" | "BUF") => Gate::buf(parse_input(0)?, output_wire),
+        (1, "EQ") => {
+            let value = match input_tokens[0] {
+                "0" => false,
+                "1" => true,
+                _ => {
+                    return Err(GcError::parse(
+                        line_number,
+                        format!(
+                            "Invalid constant: expected 0 or 1, got '{}'",
+                            input_tokens[0]
+                        ),
+                    ));
+                }
+            };
+            Gate::constant(value, output_wire)
+        }
+        (_, "XOR" | "AND" | "EQW" | "BUF" | "EQ") => {
+            return Err(GcError::parse(
+                line_number,
+                format!("Gate {} cannot have {} inputs", gate_type, num_inputs),
+            ));
+        }
+        _ => {
+            return Err(GcError::parse(
+                line_number,
+                format!("Unsupported gate type: {}", gate_type),
+            ));
+        }
+    };
+
+    Ok(gate)
+}
+
+/// A wire referenced by a gate line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireRef {
+    /// Wire read by the gate
+    Input(WireId),
+    /// Wire written by the gate
+    Output(WireId),
+}
+
+impl WireRef {
+    /// Fail if the wire is not below `num_wires`
+    pub fn check_range(self, num_wires: u64, line_number: u64) -> Result<()> {
+        let (role, wire_id) = match self {
+            WireRef::Input(wire_id) => ("input", wire_id),
+            WireRef::Output(wire_id) => ("output", wire_id),
+        };
+        if wire_id >= num_wires {
+            return Err(GcError::parse(
+                line_number,
+                format!(
+                    "Invalid {} wire ID {} exceeds num_wires {}",
+                    role, wire_id, num_wires
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Walk the wires of a gate line without interpreting its gate type
+///
+/// Unlike [`parse_gate_line`] this accepts any gate type and any number of
+/// inputs and outputs, for analysis passes that only follow wires. `visit`
+/// sees the input wires in order, then the output wires; the constant input
+/// of an `EQ` gate is not a wire and is skipped.
+///
+/// # Arguments
+/// * `line` - The gate line
+/// * `line_number` - Line number used in error messages
+/// * `visit` - Called for every wire of the gate
+///
+/// # Returns
+/// * `Ok(&str)` - The gate type token
+/// * `Err(GcError)` - Malformed line, or an error from `visit`
+pub fn visit_gate_wires(
+    line: &str,
+    line_number: u64,
+    mut visit: impl FnMut(WireRef) -> Result<()>,
+) -> Result<&str> {
+    if line.trim().is_empty() {
+        return Err(GcError::parse(line_number, "Empty line"));
+    }
+
+    let mut tokens = line.split_whitespace();
+
+    let num_inputs: u32 = tokens
+        .next()
+        .ok_or_else(|| GcError::parse(line_number, "Missing num_inputs"))?
+        .parse()
+        .map_err(|_| GcError::parse(line_number, format!("Invalid num_inputs: '{}'", line)))?;
+
+    let num_outputs: u32 = tokens
+        .next()
+        .ok_or_else(|| GcError::parse(line_number, "Missing num_outputs"))?
+        .parse()
+        .map_err(|_| GcError::parse(line_number, format!("Invalid num_outputs: '{}'", line)))?;
+
+    // The input of a constant gate is a value, not a wire
+    let inputs_are_wires = line.split_whitespace().next_back() != Some("EQ");
+
+    for i in 0..num_inputs {
+        let token = tokens
+            .next()
+            .ok_or_else(|| GcError::parse(line_number, format!("Missing input wire {}", i)))?;
+        if !inputs_are_wires {
+            continue;
+        }
+        let wire_id: WireId = token.parse().map_err(|_| {
+            GcError::parse(line_number, format!("Invalid input wire ID: '{}'", line))
+        })?;
+        visit(WireRef::Input(wire_id))?;
+    }
+
+    for i in 0..num_outputs {
+        let wire_id: WireId = tokens
+            .next()
+            .ok_or_else(|| GcError::parse(line_number, format!("Missing output wire {}", i)))?
+            .parse()
+            .map_err(|_| {
+                GcError::parse(line_number, format!("Invalid output wire ID: '{}'", line))
+            })?;
+        visit(WireRef::Output(wire_id))?;
+    }
+
+    let gate_type = tokens
+        .next()
+        .ok_or_else(|| GcError::parse(line_number, format!("Missing gate type: '{}'", line)))?;
+
+    if tokens.next().is_some() {
+        return Err(GcError::parse(
+            line_number,
+            format!("Too many tokens: '{}'", line),
+        ));
+    }
+
+    Ok(gate_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_header_line() -> Result<()> {
+        assert_eq!(
+            parse_header_line("3 10")?,
+            CircuitHeader {
+                num_gates: 3,
+                num_wires: 10,
+                io: None,
+                lines: 1,
+            }
+        );
+        assert!(parse_header_line("3").is_err());
+        assert!(parse_header_line("3 10 2").is_err());
+        let error_msg = parse_header_line("x 10").unwrap_err().to_string();
+        assert!(error_msg.contains("Invalid num_gates: 'x'"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_bristol_fashion_header() -> Result<()> {
+        let read = |text: &str| -> Result<(CircuitHeader, Option<String>)> {
+            let mut file = tempfile::NamedTempFile::new()?;
+            std::io::Write::write_all(&mut file, text.as_bytes())?;
+            let mut stream = BufferedLineStream::new(file.reopen()?);
+            let header = read_header(&mut stream)?;
+            let next_line = stream.next_line().transpose()?.map(str::to_string);
+            Ok((header, next_line))
+        };
+
+        // Two 2-bit inputs and one 1-bit output
+        let (header, first_gate) = read("1 5\n2 2 2\n1 1\n\n2 1 0 2 4 AND\n")?;
+        let io = header.io.unwrap();
+        assert_eq!(io.input_widths, vec![2, 2]);
+        assert_eq!(io.output_widths, vec![1]);
+        assert_eq!(io.input_wires(), 0..4);
+        assert_eq!(io.output_wires(header.num_wires), 4..5);
+        assert_eq!(header.lines, 4);
+        assert_eq!(first_gate.as_deref(), Some("2 1 0 2 4 AND"));
+
+        // Plain Bristol leaves the first gate line in the stream
+        let (header, first_gate) = read("1 3\n2 1 0 1 2 AND\n")?;
+        assert_eq!(header.io, None);
+        assert_eq!(header.lines, 1);
+        assert_eq!(first_gate.as_deref(), Some("2 1 0 1 2 AND"));
+
+        let error_msg = read("1 3\n2 1\n1 1\n").unwrap_err().to_string();
+        assert!(error_msg.contains("Invalid input declaration"));
+        assert!(read("1 3\n2 1 1\n2 1 0 1 2 AND\n").is_err());
+        let error_msg = read("1 3\n2 2 2\n1 1\n").unwrap_err().to_string();
+        assert!(error_msg.contains("take 5 wires, but the circuit has 3"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_gate_line() -> Result<()> {
+        assert_eq!(parse_gate_line("2 1 0 1 2 AND", 1)?, Gate::and(0, 1, 2));
+        assert_eq!(parse_gate_line("2 1 3 4 5 XOR", 1)?, Gate::xor(3, 4, 5));
+
+        let error_msg = parse_gate_line("2 1 0 1 2 OR", 7).unwrap_err().to_string();
+        assert!(error_msg.contains("Unsupported gate type: OR at line 7"));
+        assert!(parse_gate_line("1 1 0 2 INV", 1).is_err());
+        assert!(parse_gate_line("2 1 0 1 2 EQW", 1).is_err());
+
+        // Single-input copy and constant gates
+        assert_eq!(parse_gate_line("1 1 3 7 EQW", 1)?, Gate::buf(3, 7));
+        assert_eq!(parse_gate_line("1 1 3 7 BUF", 1)?, Gate::buf(3, 7));
+        assert_eq!(parse_gate_line("1 1 1 7 EQ", 1)?, Gate::constant(true, 7));
+        assert_eq!(parse_gate_line("1 1 0 7 EQ", 1)?, Gate::constant(false, 7));
+        let error = parse_gate_line("1 1 2 7 EQ", 3).unwrap_err();
+        assert!(matches!(error, GcError::Parse { line: 3, .. }));
+        assert!(error.to_string().contains("expected 0 or 1, got '2'"));
+        assert_eq!(Gate::buf(3, 7).input_wires().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(Gate::constant(true, 7).input_wires().count(), 0);
+        assert!(parse_gate_line("2 1 0 1 2 AND extra", 1).is_err());
+
+        // Wire IDs beyond the 32-bit range
+        assert_eq!(
+            parse_gate_line("2 1 4294967296 1 5000000000 XOR", 1)?,
+            Gate::xor(1 << 32, 1, 5_000_000_000)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_visit_gate_wires() -> Result<()> {
+        let mut wires = Vec::new();
+        let gate_type = visit_gate_wires("2 2 0 1 2 3 SPLIT", 2, |wire| {
+            wires.push(wire);
+            Ok(())
+        })?;
+        assert_eq!(gate_type, "SPLIT");
+        assert_eq!(
+            wires,
+            vec![
+                WireRef::Input(0),
+                WireRef::Input(1),
+                WireRef::Output(2),
+                WireRef::Output(3)
+            ]
+        );
+
+        // The constant of an EQ gate is not reported as a wire
+        wires.clear();
+        visit_gate_wires("1 1 1 7 EQ", 2, |wire| {
+            wires.push(wire);
+            Ok(())
+        })?;
+        assert_eq!(wires, vec![WireRef::Output(7)]);
+
+        let error_msg = visit_gate_wires("2 1 0 9 2 AND", 4, |wire| wire.check_range(5, 4))
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Invalid input wire ID 9 exceeds num_wires 5 at line 4"));
+        assert!(visit_gate_wires("2 1 0 1 AND", 2, |_| Ok(())).is_err());
+        assert!(visit_gate_wires("2 1 0 1 2 AND extra", 2, |_| Ok(())).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bristol_writer() -> Result<()> {
+        let io = IoDeclaration::new(vec![1, 1], vec![1], 4)?;
+        let mut writer = BristolWriter::new(Vec::new(), 2, 4, Some(&io))?;
+        writer.write_gates([Gate::and(0, 1, 2), Gate::buf(2, 3)])?;
+        let text = writer.finish()?;
+        assert_eq!(
+            std::str::from_utf8(&text).unwrap(),
+            "2 4\n2 1 1\n1 1\n\n2 1 0 1 2 AND\n1 1 2 3 EQW\n"
+        );
+
+        let mut writer = BristolWriter::new(Vec::new(), 1, 4, None)?;
+        let error_msg = writer
+            .write_gate(&Gate::xor(0, 4, 2))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error_msg.contains("Wire 4 exceeds num_wires (4)"),
+            "{}",
+            error_msg
+        );
+        let error_msg = writer.finish().unwrap_err().to_string();
+        assert!(
+            error_msg.contains("declares 1 gates, but 0 were written"),
+            "{}",
+            error_msg
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use crate::error::{GcError, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::gate::WireId;
+
+/// Half-open range of wire IDs `start..end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WireRange {
+    /// First wire in the range
+    pub start: WireId,
+    /// One past the last wire in the range
+    pub end: WireId,
+}
+
+impl WireRange {
+    /// Whether `wire` lies in the range
+    pub fn contains(&self, wire: WireId) -> bool {
+        (self.start..self.end).contains(&wire)
+    }
+}
+
+impl fmt::Display for WireRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+impl FromStr for WireRange {
+    type Err = GcError;
+
+    /// Parse `start..end`, or a single wire ID
+    fn from_str(text: &str) -> Result<Self> {
+        let parse_wire = |token: &str| -> Result<WireId> {
+            token.trim().parse().map_err(|_| {
+                GcError::InvalidArgument(format!("Invalid wire ID in range '{}'", text))
+            })
+        };
+
+        let range = match text.split_once("..") {
+            Some((start, end)) => WireRange {
+                start: parse_wire(start)?,
+                end: parse_wire(end)?,
+            },
+            None => {
+                let wire = parse_wire(text)?;
+                WireRange {
+                    start: wire,
+                    end: wire.checked_add(1).ok_or_else(|| {
+                        GcError::InvalidArgument(format!("Invalid wire ID in range '{}'", text))
+                    })?,
+                }
+            }
+        };
+
+        if range.start >= range.end {
+            return Err(GcError::InvalidArgument(format!(
+                "Empty wire range '{}'",
+                text
+            )));
+        }
+        Ok(range)
+    }
+}
+
+/// Assignment of primary input wires to the two parties
+///
+/// In a two-party execution the garbler knows its own input bits and sends
+/// the matching labels directly, while the evaluator's inputs go through
+/// oblivious transfer. Inputs not listed as garbler inputs belong to the
+/// evaluator, so the default partition sends every input through OT.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputPartition {
+    /// Ranges of primary input wires owned by the garbler
+    pub garbler_inputs: Vec<WireRange>,
+}
+
+impl InputPartition {
+    /// Parse a comma-separated list of garbler input ranges, e.g. `0..64,128`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let garbler_inputs = spec
+            .split(',')
+            .filter(|token| !token.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<WireRange>>>()?;
+        Ok(Self { garbler_inputs })
+    }
+
+    /// Save the partition as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Load a partition from JSON
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let partition = serde_json::from_str(&data)?;
+        Ok(partition)
+    }
+
+    /// Whether `wire` is one of the garbler's inputs
+    pub fn is_garbler_input(&self, wire: WireId) -> bool {
+        self.garbler_inputs.iter().any(|range| range.contains(wire))
+    }
+
+    /// Garbler inputs among `primary_inputs`, in ascending order
+    ///
+    /// Fails if a range covers none of the primary inputs, which usually
+    /// means the partition was written for a different circuit.
+    pub fn garbler_input_wires(&self, primary_inputs: &[WireId]) -> Result<Vec<WireId>> {
+        for range in &self.garbler_inputs {
+            if !primary_inputs.iter().any(|&wire| range.contains(wire)) {
+                return Err(GcError::InvalidArgument(format!(
+                    "Garbler input range {} contains no primary input wires",
+                    range
+                )));
+            }
+        }
+
+        let mut wires: Vec<WireId> = primary_inputs
+            .iter()
+            .copied()
+            .filter(|&wire| self.is_garbler_input(wire))
+            .collect();
+        wires.sort_unstable();
+        Ok(wires)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_partition() -> Result<()> {
+        let partition = InputPartition::parse("0..4, 10")?;
+        assert_eq!(
+            partition.garbler_inputs,
+            vec![
+                WireRange { start: 0, end: 4 },
+                WireRange { start: 10, end: 11 }
+            ]
+        );
+        assert!(partition.is_garbler_input(3));
+        assert!(!partition.is_garbler_input(4));
+        assert!(partition.is_garbler_input(10));
+
+        let primary_inputs = [12, 10, 3, 0, 5];
+        assert_eq!(
+            partition.garbler_input_wires(&primary_inputs)?,
+            vec![0, 3, 10]
+        );
+
+        let error_msg = InputPartition::parse("5..5").unwrap_err().to_string();
+        assert!(error_msg.contains("Empty wire range '5..5'"));
+        let error_msg = InputPartition::parse("0..x").unwrap_err().to_string();
+        assert!(error_msg.contains("Invalid wire ID in range '0..x'"));
+
+        let error_msg = InputPartition::parse("20..30")?
+            .garbler_input_wires(&primary_inputs)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Garbler input range 20..30 contains no primary input wires"));
+
+        Ok(())
+    }
+}
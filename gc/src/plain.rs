@@ -0,0 +1,309 @@
+use crate::error::{GcError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::bus::{Bus, BusValue, assemble_buses, parse_radix_bits};
+use crate::evaluator::EvaluationResult;
+use crate::garbler::WireLabels;
+use crate::gate::{Circuit, GateType, WireId};
+use crate::input_assignment::{InputAssignment, resolve_keyed, resolve_packed};
+use crate::ot_simulation::OTResult;
+use crate::wire_analyzer::WireUsageReport;
+
+/// Output bits of a cleartext evaluation
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlainResult {
+    /// Bit value of every primary output wire: wire_id -> bit
+    pub output_bits: BTreeMap<WireId, bool>,
+}
+
+impl PlainResult {
+    /// Save plain result as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Load plain result from JSON
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let result = serde_json::from_str(&data)?;
+        Ok(result)
+    }
+
+    /// Values of output buses
+    ///
+    /// # Returns
+    /// * `Ok(BTreeMap<String, BusValue>)` - Bus name to value
+    /// * `Err(GcError)` - A bus wire is not a primary output
+    pub fn bus_values(&self, buses: &[Bus]) -> Result<BTreeMap<String, BusValue>> {
+        assemble_buses(buses, |bus, wire_id| {
+            self.output_bits.get(&wire_id).copied().ok_or_else(|| {
+                GcError::missing_wire(
+                    wire_id,
+                    format!("Output wire {} of bus '{}' not found", wire_id, bus.name),
+                )
+            })
+        })
+    }
+
+    /// Output wires where a garbled evaluation disagrees with this result
+    ///
+    /// Outputs missing from `evaluation` or without a bit value count as
+    /// mismatches. Returned in ascending wire order.
+    pub fn mismatched_outputs(&self, evaluation: &EvaluationResult) -> Vec<WireId> {
+        self.output_bits
+            .iter()
+            .filter(|&(wire_id, &bit)| {
+                evaluation
+                    .output_results
+                    .get(wire_id)
+                    .and_then(|output| output.bit_value)
+                    != Some(bit)
+            })
+            .map(|(&wire_id, _)| wire_id)
+            .collect()
+    }
+}
+
+/// Load input bits from a JSON object mapping wire IDs to booleans
+///
+/// E.g. `{"0": true, "1": false}`.
+pub fn load_input_bits<P: AsRef<Path>>(path: P) -> Result<HashMap<WireId, bool>> {
+    let data = std::fs::read_to_string(path)?;
+    let inputs = serde_json::from_str(&data)?;
+    Ok(inputs)
+}
+
+/// Load input bits given either as JSON or as a hex number
+///
+/// A file whose content starts with `{` is read like [`load_input_bits`],
+/// except that wires may also take 0 or 1. Anything else is parsed as a hex
+/// number (optionally `0x`-prefixed, `_` allowed as a separator) or a
+/// `0b`-prefixed binary number whose bit `i`, counting from the least
+/// significant bit, is the value of `wire_ids[i]` - the same little-endian
+/// order in which decoded output buses are printed. See [`InputAssignment`]
+/// for assignments that also name buses.
+///
+/// # Arguments
+/// * `path` - File with the input assignment
+/// * `wire_ids` - Input wires in the order their bits appear in a packed number
+///
+/// # Returns
+/// * `Ok(HashMap<WireId, bool>)` - Bit value per input wire: wire_id -> bit
+/// * `Err(GcError)` - The file is neither valid JSON nor a number, or the number has more bits than there are wires
+pub fn load_input_assignment<P: AsRef<Path>>(
+    path: P,
+    wire_ids: &[WireId],
+) -> Result<HashMap<WireId, bool>> {
+    match InputAssignment::load(path)? {
+        InputAssignment::Packed(bits) => resolve_packed(&bits, wire_ids),
+        InputAssignment::Keyed(assignment) => resolve_keyed(&assignment, None, &[]),
+    }
+}
+
+/// Parse a hex number into one bit per wire, least significant bit first
+///
+/// See [`load_input_assignment`] for the format.
+pub fn parse_hex_input_bits(hex: &str, wire_ids: &[WireId]) -> Result<HashMap<WireId, bool>> {
+    let digits = hex
+        .strip_prefix("0x")
+        .or_else(|| hex.strip_prefix("0X"))
+        .unwrap_or(hex);
+    let bits = parse_radix_bits(digits, 4).ok_or_else(|| {
+        GcError::InvalidArgument(format!("Invalid hex number '{}' in input assignment", hex))
+    })?;
+    resolve_packed(&bits, wire_ids)
+}
+
+/// Input bits chosen during OT: wire_id -> bit
+///
+/// Evaluating in the clear with these gives the ground truth for a garbled
+/// evaluation that used the same OT result. The evaluator's bits are the
+/// ones it chose; the garbler's inputs only carry labels, so their bits are
+/// looked up in the garbler's `wire_labels`.
+///
+/// # Returns
+/// * `Ok(HashMap<WireId, bool>)` - Bit value of every input wire in the OT result
+/// * `Err(GcError)` - The OT result has garbler inputs but no wire labels are given, or a garbler label is not one of its wire's labels
+pub fn input_bits_from_ot(
+    ot_result: &OTResult,
+    wire_labels: Option<&WireLabels>,
+) -> Result<HashMap<WireId, bool>> {
+    let mut bits: HashMap<WireId, bool> = ot_result
+        .selected_inputs
+        .iter()
+        .map(|(&wire_id, selected_input)| (wire_id, selected_input.bit_value))
+        .collect();
+    if ot_result.garbler_inputs.is_empty() {
+        return Ok(bits);
+    }
+    let wire_labels = wire_labels.ok_or_else(|| {
+        GcError::InvalidArgument(
+            "OT result holds labels of garbler inputs; their bits need the garbler's wire labels"
+                .into(),
+        )
+    })?;
+    for (&wire_id, label) in &ot_result.garbler_inputs {
+        bits.insert(wire_id, wire_labels.input_bit(wire_id, label)?);
+    }
+    Ok(bits)
+}
+
+/// Evaluate a circuit in the clear
+///
+/// # Arguments
+/// * `circuit` - The circuit to evaluate
+/// * `wire_report` - Wire usage analysis naming the primary input and output wires
+/// * `inputs` - Bit value for every primary input wire: wire_id -> bit
+///
+/// # Returns
+/// * `Ok(HashMap<WireId, bool>)` - Bit value of every primary output wire
+/// * `Err(GcError)` - Missing input bit or gate reading an unset wire
+#[tracing::instrument(name = "plain_evaluation", skip_all)]
+pub fn evaluate_plain(
+    circuit: &Circuit,
+    wire_report: &WireUsageReport,
+    inputs: &HashMap<WireId, bool>,
+) -> Result<HashMap<WireId, bool>> {
+    let mut wire_values: Vec<Option<bool>> = vec![None; circuit.num_wires as usize];
+
+    for &wire_id in &wire_report.primary_input_wires {
+        let bit = *inputs.get(&wire_id).ok_or_else(|| {
+            GcError::missing_wire(
+                wire_id,
+                format!("No input bit given for input wire {}", wire_id),
+            )
+        })?;
+        if let Some(value) = wire_values.get_mut(wire_id as usize) {
+            *value = Some(bit);
+        }
+    }
+
+    for (gate_index, gate) in circuit.gates.iter().enumerate() {
+        if gate.output_wire >= circuit.num_wires {
+            return Err(GcError::InvalidCircuit(format!(
+                "Wire ID {} exceeds num_wires {} at gate {}",
+                gate.output_wire, circuit.num_wires, gate_index
+            )));
+        }
+        let value = |wire_id: WireId| -> Result<bool> {
+            match wire_values.get(wire_id as usize) {
+                Some(Some(bit)) => Ok(*bit),
+                _ => Err(GcError::missing_wire(
+                    wire_id,
+                    format!(
+                        "Gate {} reads wire {}, which has no value yet",
+                        gate_index, wire_id
+                    ),
+                )),
+            }
+        };
+
+        let output = match gate.gate_type {
+            GateType::Xor => value(gate.input_wire_1)? ^ value(gate.input_wire_2)?,
+            GateType::And => value(gate.input_wire_1)? & value(gate.input_wire_2)?,
+            GateType::Buf => value(gate.input_wire_1)?,
+            GateType::Const(bit) => bit,
+        };
+        wire_values[gate.output_wire as usize] = Some(output);
+    }
+
+    wire_report
+        .primary_output_wires
+        .iter()
+        .map(|&wire_id| {
+            let bit = wire_values
+                .get(wire_id as usize)
+                .copied()
+                .flatten()
+                .ok_or_else(|| {
+                    GcError::missing_wire(wire_id, format!("Output wire {} has no value", wire_id))
+                })?;
+            Ok((wire_id, bit))
+        })
+        .collect()
+}
+
+/// Circuit of the two-party session tests
+///
+/// out_5 = (a AND b) XOR c, out_6 = b AND d; the garbler owns a and b
+#[cfg(test)]
+pub(crate) const SESSION_CIRCUIT: &str = "3 7\n2 1 0 1 4 AND\n2 1 4 2 5 XOR\n2 1 1 3 6 AND\n";
+
+/// Write [`SESSION_CIRCUIT`] to a temporary file
+#[cfg(test)]
+pub(crate) fn session_circuit_file() -> Result<tempfile::NamedTempFile> {
+    let circuit_file = tempfile::NamedTempFile::new()?;
+    std::fs::write(circuit_file.path(), SESSION_CIRCUIT)?;
+    Ok(circuit_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::OutputResult;
+    use crate::garbler::WireLabel;
+    use crate::wire_analyzer::analyze_gates;
+
+    #[test]
+    fn test_evaluate_plain() -> Result<()> {
+        // out = (a AND b) XOR c
+        let circuit = Circuit::parse("2 5\n2 1 0 1 3 AND\n2 1 3 2 4 XOR\n")?;
+        let wire_report = analyze_gates(&circuit.gates, circuit.num_wires)?;
+
+        let inputs = HashMap::from([(0, true), (1, true), (2, true)]);
+        let outputs = evaluate_plain(&circuit, &wire_report, &inputs)?;
+        assert_eq!(outputs, HashMap::from([(4, false)]));
+
+        let plain = PlainResult {
+            output_bits: outputs.into_iter().collect(),
+        };
+        let mut evaluation = EvaluationResult {
+            output_results: HashMap::from([(
+                4,
+                OutputResult {
+                    label: WireLabel::new([0; 16]),
+                    bit_value: Some(false),
+                },
+            )]),
+            stats: None,
+        };
+        assert!(plain.mismatched_outputs(&evaluation).is_empty());
+        evaluation.output_results.get_mut(&4).unwrap().bit_value = Some(true);
+        assert_eq!(plain.mismatched_outputs(&evaluation), vec![4]);
+
+        let error_msg = evaluate_plain(&circuit, &wire_report, &HashMap::new())
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("No input bit given for input wire 0"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_hex_input_bits() -> Result<()> {
+        let wire_ids = [10, 11, 12, 13, 14];
+        let inputs = parse_hex_input_bits("0x1_a", &wire_ids)?;
+        assert_eq!(
+            inputs,
+            HashMap::from([(10, false), (11, true), (12, false), (13, true), (14, true)])
+        );
+        assert!(
+            parse_hex_input_bits("0", &wire_ids)?
+                .values()
+                .all(|&bit| !bit)
+        );
+
+        // Leading zeros are fine, bits past the last wire are not
+        assert_eq!(parse_hex_input_bits("001a", &wire_ids)?, inputs);
+        let error_msg = parse_hex_input_bits("0x20", &wire_ids)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("sets bit 5 but the circuit has only 5 input wires"));
+        assert!(parse_hex_input_bits("0xg", &wire_ids).is_err());
+        Ok(())
+    }
+}
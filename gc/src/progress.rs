@@ -0,0 +1,147 @@
+#[cfg(feature = "cli")]
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "cli")]
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Receives progress callbacks from a long-running pass over a circuit
+///
+/// Library functions never draw to the terminal themselves; they report
+/// progress to an observer supplied by the caller. All methods default to
+/// doing nothing, so implementors only override the callbacks they care
+/// about. With the `cli` feature, `TerminalProgress` draws them as terminal
+/// progress bars. A single observer may see several tasks in a row (e.g. an
+/// analysis pass followed by evaluation), each bracketed by
+/// [`start`](Self::start) and [`finish`](Self::finish).
+pub trait ProgressObserver {
+    /// A task started
+    ///
+    /// `total` is the number of steps (usually gates) if known up front.
+    fn start(&self, _total: Option<u64>, _message: &str) {}
+
+    /// Number of steps completed so far in the current task
+    ///
+    /// Called every [`PROGRESS_UPDATE_INTERVAL`](crate::constants::PROGRESS_UPDATE_INTERVAL)
+    /// steps rather than on every step.
+    fn update(&self, _position: u64) {}
+
+    /// The current task finished, with a short summary
+    fn finish(&self, _message: &str) {}
+}
+
+/// Observer that ignores all progress
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoProgress;
+
+impl ProgressObserver for NoProgress {}
+
+/// Emit a `tracing` event with the throughput of a running phase
+///
+/// Called next to [`ProgressObserver::update`] so telemetry sees the same
+/// cadence as progress bars.
+pub(crate) fn trace_throughput(phase: &'static str, gates: u64, start: Instant) {
+    let elapsed_secs = start.elapsed().as_secs_f64();
+    let gates_per_sec = if elapsed_secs > 0.0 {
+        gates as f64 / elapsed_secs
+    } else {
+        0.0
+    };
+    tracing::debug!(phase, gates, elapsed_secs, gates_per_sec, "throughput");
+}
+
+/// Draws an indicatif progress bar (or a spinner when the total is unknown)
+/// for every task reported by the library
+///
+/// Only available with the `cli` feature.
+#[cfg(feature = "cli")]
+#[derive(Debug, Default)]
+pub struct TerminalProgress {
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+#[cfg(feature = "cli")]
+impl ProgressObserver for TerminalProgress {
+    fn start(&self, total: Option<u64>, message: &str) {
+        let pb = match total {
+            Some(total) => {
+                let pb = ProgressBar::new(total);
+                pb.set_style(
+                    ProgressStyle::default_bar()
+                        .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
+                        .unwrap()
+                        .progress_chars("##-"),
+                );
+                pb
+            }
+            None => {
+                let pb = ProgressBar::new_spinner();
+                pb.set_style(
+                    ProgressStyle::default_spinner()
+                        .template("{spinner:.green} [{elapsed_precise}] {msg}")
+                        .unwrap(),
+                );
+                pb
+            }
+        };
+        pb.set_message(message.to_string());
+        *self.bar.lock().unwrap() = Some(pb);
+    }
+
+    fn update(&self, position: u64) {
+        if let Some(pb) = self.bar.lock().unwrap().as_ref() {
+            pb.set_position(position);
+        }
+    }
+
+    fn finish(&self, message: &str) {
+        if let Some(pb) = self.bar.lock().unwrap().take() {
+            pb.finish_with_message(message.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use crate::stream::BufferedLineStream;
+    use crate::wire_analyzer::analyze_wire_usage;
+    use std::cell::RefCell;
+    use std::fs::File;
+    use std::io::Write;
+
+    #[derive(Debug, Default)]
+    struct RecordingObserver {
+        events: RefCell<Vec<String>>,
+    }
+
+    impl ProgressObserver for RecordingObserver {
+        fn start(&self, total: Option<u64>, message: &str) {
+            self.events
+                .borrow_mut()
+                .push(format!("start {total:?} {message}"));
+        }
+
+        fn finish(&self, message: &str) {
+            self.events.borrow_mut().push(format!("finish {message}"));
+        }
+    }
+
+    #[test]
+    fn test_observer_receives_callbacks() -> Result<()> {
+        let mut temp_file = tempfile::NamedTempFile::new()?;
+        temp_file.write_all(b"2 5\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n")?;
+        temp_file.flush()?;
+
+        let observer = RecordingObserver::default();
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+        analyze_wire_usage(&mut stream, &observer)?;
+
+        let events = observer.events.borrow();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], "start Some(2) Analyzing wire usage");
+        assert!(events[1].starts_with("finish ✓ Analyzed 2 gates"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,292 @@
+use crate::error::{GcError, Result};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::gate::{Circuit, Gate, WireId};
+use crate::parser::IoDeclaration;
+
+/// How the gates of a [`RandomCircuit`] pick the wires they read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FanOut {
+    /// Every earlier wire is equally likely, so fan-out is roughly even
+    Uniform,
+    /// Only the given number of most recent wires, so wires die young and
+    /// few are live at once
+    Local(u64),
+    /// Earlier wires are more likely, the more so the larger the exponent,
+    /// so a few wires, the inputs above all, reach a very large fan-out
+    Skewed(f64),
+}
+
+impl FanOut {
+    /// Pick one of the wires `0..end`
+    fn pick(self, rng: &mut ChaCha12Rng, end: WireId) -> WireId {
+        match self {
+            FanOut::Uniform => rng.random_range(0..end),
+            FanOut::Local(window) => rng.random_range(end.saturating_sub(window)..end),
+            FanOut::Skewed(exponent) => {
+                let position = rng.random::<f64>().powf(exponent);
+                ((position * end as f64) as WireId).min(end - 1)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for FanOut {
+    type Err = GcError;
+
+    /// Parse `uniform`, `local:<window>` or `skewed:<exponent>`
+    fn from_str(spec: &str) -> Result<Self> {
+        let invalid = || {
+            GcError::InvalidArgument(format!(
+                "Invalid fan-out '{}': expected uniform, local:<window> or skewed:<exponent>",
+                spec
+            ))
+        };
+        match spec.split_once(':') {
+            None if spec == "uniform" => Ok(FanOut::Uniform),
+            Some(("local", window)) => match window.parse() {
+                Ok(window) if window > 0 => Ok(FanOut::Local(window)),
+                _ => Err(invalid()),
+            },
+            Some(("skewed", exponent)) => match exponent.parse() {
+                Ok(exponent) if exponent >= 1.0 => Ok(FanOut::Skewed(exponent)),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Shape of a [`RandomCircuit`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RandomCircuitParams {
+    /// Number of gates
+    pub num_gates: u64,
+    /// Number of primary input wires
+    pub num_inputs: u64,
+    /// Number of primary output wires, written by the last gates
+    pub num_outputs: u64,
+    /// Fraction of AND gates; the others are XOR gates
+    pub and_ratio: f64,
+    /// Number of gate layers, the length of the longest path through the circuit
+    pub depth: u64,
+    /// How gates pick the wires they read
+    pub fan_out: FanOut,
+    /// Seed of the generator; the same parameters and seed give the same circuit
+    pub seed: u64,
+}
+
+impl Default for RandomCircuitParams {
+    fn default() -> Self {
+        Self {
+            num_gates: 10_000,
+            num_inputs: 128,
+            num_outputs: 128,
+            and_ratio: 0.3,
+            depth: 100,
+            fan_out: FanOut::Uniform,
+            seed: 0,
+        }
+    }
+}
+
+/// Gates of a random circuit, generated one at a time
+///
+/// The gates are split into `depth` layers of nearly equal size. Each gate
+/// reads one wire of the layer before its own, the inputs for the first
+/// layer, so the circuit has exactly `depth` layers, and one wire picked by
+/// the [`FanOut`] among all wires before its layer. Every gate writes a new
+/// wire, so the circuit is valid Bristol Fashion: the inputs are the first
+/// wires, the outputs the last ones, and gates come in topological order.
+/// Only the position in the circuit is kept, so circuits of any size can be
+/// streamed to a file.
+#[derive(Debug)]
+pub struct RandomCircuit {
+    params: RandomCircuitParams,
+    rng: ChaCha12Rng,
+    gates_generated: u64,
+    /// Layer of the next gate, 1-based; layer 0 holds the inputs
+    layer: u64,
+    /// First wire of the layer before the current one
+    previous_layer_start: WireId,
+    /// First wire of the current layer
+    layer_start: WireId,
+    /// Wire after the last one of the current layer
+    layer_end: WireId,
+}
+
+impl RandomCircuit {
+    /// Start generating a circuit
+    ///
+    /// # Arguments
+    /// * `params` - Size, gate mix, depth and fan-out of the circuit
+    ///
+    /// # Returns
+    /// * `Ok(RandomCircuit)` - Iterator over the gates
+    /// * `Err(GcError)` - No gates or inputs, more outputs than gates, an AND ratio outside
+    ///   `0..=1`, or a depth of 0 or above the number of gates
+    pub fn new(params: RandomCircuitParams) -> Result<Self> {
+        let invalid = |message: String| Err(GcError::InvalidArgument(message));
+        if params.num_gates == 0 || params.num_inputs == 0 {
+            return invalid("A random circuit needs at least one gate and one input".to_string());
+        }
+        if params.num_outputs > params.num_gates {
+            return invalid(format!(
+                "{} outputs need at least as many gates, got {}",
+                params.num_outputs, params.num_gates
+            ));
+        }
+        if !(0.0..=1.0).contains(&params.and_ratio) {
+            return invalid(format!(
+                "AND ratio must be between 0 and 1, got {}",
+                params.and_ratio
+            ));
+        }
+        if params.depth == 0 || params.depth > params.num_gates {
+            return invalid(format!(
+                "Depth must be between 1 and the number of gates, {}, got {}",
+                params.num_gates, params.depth
+            ));
+        }
+
+        let mut circuit = Self {
+            rng: ChaCha12Rng::seed_from_u64(params.seed),
+            gates_generated: 0,
+            layer: 1,
+            previous_layer_start: 0,
+            layer_start: params.num_inputs,
+            layer_end: params.num_inputs,
+            params,
+        };
+        circuit.layer_end += circuit.layer_size(1);
+        Ok(circuit)
+    }
+
+    /// Number of gates in the circuit
+    pub fn num_gates(&self) -> u64 {
+        self.params.num_gates
+    }
+
+    /// Total number of wires in the circuit
+    pub fn num_wires(&self) -> u64 {
+        self.params.num_inputs + self.params.num_gates
+    }
+
+    /// Bristol Fashion declaration of one input and one output value
+    pub fn io(&self) -> IoDeclaration {
+        IoDeclaration {
+            input_widths: vec![self.params.num_inputs],
+            output_widths: [self.params.num_outputs]
+                .into_iter()
+                .filter(|&width| width > 0)
+                .collect(),
+        }
+    }
+
+    /// Collect the gates into a whole circuit, before any was taken
+    pub fn into_circuit(self) -> Circuit {
+        let num_wires = self.num_wires();
+        let io = Some(self.io());
+        Circuit {
+            num_wires,
+            gates: self.collect(),
+            io,
+        }
+    }
+
+    /// Number of gates of `layer`, the first layers taking one more if the
+    /// gates do not split evenly
+    fn layer_size(&self, layer: u64) -> u64 {
+        let RandomCircuitParams {
+            num_gates, depth, ..
+        } = self.params;
+        num_gates / depth + u64::from(layer <= num_gates % depth)
+    }
+}
+
+impl Iterator for RandomCircuit {
+    type Item = Gate;
+
+    fn next(&mut self) -> Option<Gate> {
+        if self.gates_generated == self.params.num_gates {
+            return None;
+        }
+        let output_wire = self.params.num_inputs + self.gates_generated;
+        if output_wire == self.layer_end {
+            self.layer += 1;
+            self.previous_layer_start = self.layer_start;
+            self.layer_start = self.layer_end;
+            self.layer_end += self.layer_size(self.layer);
+        }
+        self.gates_generated += 1;
+
+        let deep = self
+            .rng
+            .random_range(self.previous_layer_start..self.layer_start);
+        let other = self.params.fan_out.pick(&mut self.rng, self.layer_start);
+        let (input_1, input_2) = if self.rng.random_bool(0.5) {
+            (deep, other)
+        } else {
+            (other, deep)
+        };
+        Some(if self.rng.random_bool(self.params.and_ratio) {
+            Gate::and(input_1, input_2, output_wire)
+        } else {
+            Gate::xor(input_1, input_2, output_wire)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.params.num_gates - self.gates_generated) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gate::GateType;
+    use crate::layering::analyze_layers;
+    use crate::progress::NoProgress;
+    use crate::stream::BufferedLineStream;
+
+    #[test]
+    fn test_random_circuit() -> Result<()> {
+        let params = RandomCircuitParams {
+            num_gates: 1000,
+            num_inputs: 16,
+            num_outputs: 8,
+            and_ratio: 0.25,
+            depth: 30,
+            fan_out: "local:50".parse()?,
+            seed: 7,
+        };
+        let circuit = RandomCircuit::new(params.clone())?.into_circuit();
+        assert_eq!(circuit, RandomCircuit::new(params.clone())?.into_circuit());
+        assert_eq!(circuit.num_wires, 1016);
+        let and_gates = circuit
+            .gates
+            .iter()
+            .filter(|gate| gate.gate_type == GateType::And)
+            .count();
+        assert!((200..300).contains(&and_gates), "{}", and_gates);
+
+        // Valid Bristol Fashion of exactly the requested depth
+        let mut text = Vec::new();
+        circuit.write_bristol(&mut text)?;
+        let layers = analyze_layers(
+            &mut BufferedLineStream::from_reader(text.as_slice()),
+            &NoProgress,
+        )?;
+        assert_eq!(layers.num_layers, 30);
+
+        assert!("local:0".parse::<FanOut>().is_err());
+        let too_deep = RandomCircuitParams {
+            depth: 1001,
+            ..params
+        };
+        assert!(RandomCircuit::new(too_deep).is_err());
+        Ok(())
+    }
+}
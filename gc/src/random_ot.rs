@@ -0,0 +1,355 @@
+use crate::error::{GcError, Result};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::garbler::{WireLabel, WireLabels};
+use crate::gate::WireId;
+use crate::ot::{add_garbler_inputs, evaluator_label_pairs, input_choices, received_ot_result};
+use crate::ot_extension::{OtExtensionReceiver, OtExtensionSender, OtSecurity};
+use crate::ot_simulation::OTResult;
+
+/// Bytes of the derandomization message before the packed bits: the OT count
+const DERANDOMIZATION_HEADER_SIZE: usize = 8;
+
+/// Sender's output of a random OT: two random messages per OT
+///
+/// Produced by [`OtExtensionSender::finish_random`] before any label exists.
+/// Once the receiver's [`RandomOtReceiver::derandomize`] message arrives,
+/// [`RandomOtSender::transfer`] masks the real labels with the random
+/// messages. Every random OT must be derandomized only once.
+#[derive(Debug, Clone, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
+pub struct RandomOtSender {
+    pairs: Vec<(WireLabel, WireLabel)>,
+}
+
+impl RandomOtSender {
+    /// Wrap the random message pairs derived by the OT extension
+    pub(crate) fn new(pairs: Vec<(WireLabel, WireLabel)>) -> Self {
+        Self { pairs }
+    }
+
+    /// Number of precomputed OTs
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Whether no OT was precomputed
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Both random messages of every OT, indexed by choice bit
+    pub fn pairs(&self) -> &[(WireLabel, WireLabel)] {
+        &self.pairs
+    }
+
+    /// Answer a derandomization message with the real labels
+    ///
+    /// For OT `i` with flip bit `d_i` the message holds
+    /// `label_0 xor m_{d_i}` and `label_1 xor m_{1 xor d_i}`, so the receiver
+    /// unmasks exactly the label of its real choice.
+    ///
+    /// # Arguments
+    /// * `pairs` - The two labels offered in each OT, indexed by choice bit
+    /// * `derandomization` - The message produced by [`RandomOtReceiver::derandomize`]
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - Both masked labels of every OT
+    /// * `Err(GcError)` - The message is malformed, or asks for another number of OTs than `pairs` holds or more than were precomputed
+    pub fn transfer(
+        &self,
+        pairs: &[(WireLabel, WireLabel)],
+        derandomization: &[u8],
+    ) -> Result<Vec<u8>> {
+        let flips = self.parse_derandomization(derandomization)?;
+        if flips.len() != pairs.len() {
+            return Err(GcError::Protocol(format!(
+                "Receiver derandomizes {} OTs but the sender has {} pairs",
+                flips.len(),
+                pairs.len()
+            )));
+        }
+
+        let mut message = Vec::with_capacity(pairs.len() * 32);
+        for (((label_0, label_1), (random_0, random_1)), flip) in
+            pairs.iter().zip(&self.pairs).zip(flips)
+        {
+            let (mask_0, mask_1) = if flip {
+                (random_1, random_0)
+            } else {
+                (random_0, random_1)
+            };
+            message.extend_from_slice(label_0.xor(mask_0).as_bytes());
+            message.extend_from_slice(label_1.xor(mask_1).as_bytes());
+        }
+        Ok(message)
+    }
+
+    /// Answer a derandomization message with the evaluator's input labels
+    ///
+    /// Wires are ordered by wire ID, as returned by
+    /// [`WireLabels::evaluator_input_wires`].
+    pub fn transfer_wire_labels(
+        &self,
+        wire_labels: &WireLabels,
+        derandomization: &[u8],
+    ) -> Result<Vec<u8>> {
+        self.transfer(&evaluator_label_pairs(wire_labels), derandomization)
+    }
+
+    /// Unpack the flip bits of a derandomization message
+    fn parse_derandomization(&self, derandomization: &[u8]) -> Result<Vec<bool>> {
+        if derandomization.len() < DERANDOMIZATION_HEADER_SIZE {
+            return Err(GcError::Protocol(format!(
+                "Derandomization message has {} bytes, expected at least {}",
+                derandomization.len(),
+                DERANDOMIZATION_HEADER_SIZE
+            )));
+        }
+        let (header, packed) = derandomization.split_at(DERANDOMIZATION_HEADER_SIZE);
+        let count = u64::from_le_bytes(header.try_into().unwrap());
+        if count > self.pairs.len() as u64 {
+            return Err(GcError::Protocol(format!(
+                "Receiver derandomizes {} OTs but only {} were precomputed",
+                count,
+                self.pairs.len()
+            )));
+        }
+        let count = count as usize;
+        if packed.len() != count.div_ceil(8) {
+            return Err(GcError::Protocol(format!(
+                "Derandomization message has {} bytes, expected {} for {} OTs",
+                derandomization.len(),
+                DERANDOMIZATION_HEADER_SIZE + count.div_ceil(8),
+                count
+            )));
+        }
+        Ok((0..count)
+            .map(|i| (packed[i / 8] >> (i % 8)) & 1 == 1)
+            .collect())
+    }
+}
+
+/// Receiver's output of a random OT: a random choice bit and its message per OT
+///
+/// Produced by [`OtExtensionReceiver::finish_random`]. Once the real choice
+/// bits are known, [`RandomOtReceiver::derandomize`] tells the sender which
+/// random choices to flip and [`RandomOtReceiver::finish`] unmasks the
+/// labels of its answer.
+#[derive(Debug, Clone, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
+pub struct RandomOtReceiver {
+    choices: Vec<bool>,
+    labels: Vec<WireLabel>,
+}
+
+impl RandomOtReceiver {
+    /// Wrap the random choices and messages derived by the OT extension
+    pub(crate) fn new(choices: Vec<bool>, labels: Vec<WireLabel>) -> Self {
+        Self { choices, labels }
+    }
+
+    /// Number of precomputed OTs
+    pub fn len(&self) -> usize {
+        self.choices.len()
+    }
+
+    /// Whether no OT was precomputed
+    pub fn is_empty(&self) -> bool {
+        self.choices.is_empty()
+    }
+
+    /// The random choice bit of every OT
+    pub fn choices(&self) -> &[bool] {
+        &self.choices
+    }
+
+    /// The random message selected by each random choice
+    pub fn labels(&self) -> &[WireLabel] {
+        &self.labels
+    }
+
+    /// Tell the sender how the real choice bits differ from the random ones
+    ///
+    /// Uses the first `bits.len()` precomputed OTs. The flip bits
+    /// `d_i = b_i xor c_i` are uniformly random to the sender, so they reveal
+    /// nothing about the real choices.
+    ///
+    /// # Arguments
+    /// * `bits` - The real choice bit of each OT
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - The OT count followed by the packed flip bits
+    /// * `Err(GcError)` - More bits than precomputed OTs
+    pub fn derandomize(&self, bits: &[bool]) -> Result<Vec<u8>> {
+        self.check_count(bits.len())?;
+        let mut message = vec![0u8; DERANDOMIZATION_HEADER_SIZE + bits.len().div_ceil(8)];
+        message[..DERANDOMIZATION_HEADER_SIZE].copy_from_slice(&(bits.len() as u64).to_le_bytes());
+        for (i, (&bit, &choice)) in bits.iter().zip(&self.choices).enumerate() {
+            if bit != choice {
+                message[DERANDOMIZATION_HEADER_SIZE + i / 8] |= 1 << (i % 8);
+            }
+        }
+        Ok(message)
+    }
+
+    /// Unmask the label of every real choice
+    ///
+    /// # Arguments
+    /// * `bits` - The real choice bits passed to [`RandomOtReceiver::derandomize`]
+    /// * `transfer` - The message produced by [`RandomOtSender::transfer`]
+    ///
+    /// # Returns
+    /// * `Ok(Vec<WireLabel>)` - The chosen label of each OT, in order
+    /// * `Err(GcError)` - The message does not hold two labels per bit, or more bits than precomputed OTs
+    pub fn finish(&self, bits: &[bool], transfer: &[u8]) -> Result<Vec<WireLabel>> {
+        self.check_count(bits.len())?;
+        if transfer.len() != bits.len() * 32 {
+            return Err(GcError::Protocol(format!(
+                "Transfer message has {} bytes, expected {} for {} OTs",
+                transfer.len(),
+                bits.len() * 32,
+                bits.len()
+            )));
+        }
+
+        let labels = transfer
+            .chunks_exact(32)
+            .zip(bits.iter().zip(&self.labels))
+            .map(|(words, (&bit, random))| {
+                let offset = if bit { 16 } else { 0 };
+                let masked: [u8; 16] = words[offset..offset + 16].try_into().unwrap();
+                WireLabel::new(masked).xor(random)
+            })
+            .collect();
+        Ok(labels)
+    }
+
+    /// Error unless `count` OTs were precomputed
+    fn check_count(&self, count: usize) -> Result<()> {
+        if count > self.choices.len() {
+            return Err(GcError::InvalidArgument(format!(
+                "{} choice bits given but only {} random OTs were precomputed",
+                count,
+                self.choices.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Run random OT and its derandomization for the evaluator's inputs in one process
+///
+/// The offline phase runs the OT extension for as many random OTs as the
+/// garbler has evaluator input wires, before either party looks at a label
+/// or input bit; the online phase then derandomizes them.
+///
+/// # Arguments
+/// * `wire_labels` - Wire labels from garbler output containing input/output labels and delta
+/// * `input_bits` - Bit value for every evaluator input wire, and optionally every garbler input wire: wire_id -> bit
+/// * `security` - Security level of the extension
+/// * `seed_data` - 32 bytes of random seed, split into the sender's and the receiver's
+///
+/// # Returns
+/// * `Ok(OTResult)` - Selected input labels with their corresponding bit values
+/// * `Err(GcError)` - An evaluator input wire has no bit in `input_bits`, or only some garbler input wires have one
+#[tracing::instrument(name = "ot", skip_all)]
+pub fn run_random_ot(
+    wire_labels: &WireLabels,
+    input_bits: &HashMap<WireId, bool>,
+    security: OtSecurity,
+    seed_data: &[u8; 32],
+) -> Result<OTResult> {
+    let mut rng = ChaCha12Rng::from_seed(*seed_data);
+    let mut sender_seed = [0u8; 32];
+    let mut receiver_seed = [0u8; 32];
+    rng.fill_bytes(&mut sender_seed);
+    rng.fill_bytes(&mut receiver_seed);
+
+    // Offline: only the number of OTs is needed
+    let wire_ids = wire_labels.evaluator_input_wires();
+    let mut sender = OtExtensionSender::new_random(wire_ids.len(), security, &sender_seed);
+    let mut receiver = OtExtensionReceiver::new_random(wire_ids.len(), security, &receiver_seed);
+    let base_setup = receiver.setup()?;
+    let base_choices = sender.choose(&base_setup)?;
+    let extension = receiver.extend(&base_choices)?;
+    let random_sender = sender.finish_random(&extension)?;
+    let random_receiver = receiver.finish_random()?;
+
+    // Online: adjust the random OTs to the real bits and labels
+    let bits = input_choices(&wire_ids, input_bits)?;
+    let derandomization = random_receiver.derandomize(&bits)?;
+    let transfer = random_sender.transfer_wire_labels(wire_labels, &derandomization)?;
+    let labels = random_receiver.finish(&bits, &transfer)?;
+
+    let mut ot_result = received_ot_result(&wire_ids, input_bits, labels, wire_labels.session_id)?;
+    add_garbler_inputs(&mut ot_result, wire_labels, input_bits)?;
+
+    tracing::info!(
+        evaluator_inputs = ot_result.selected_inputs.len(),
+        garbler_inputs = ot_result.garbler_inputs.len(),
+        ?security,
+        "Random OT finished"
+    );
+    Ok(ot_result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_pairs(count: usize) -> Vec<(WireLabel, WireLabel)> {
+        (0..count)
+            .map(|i| {
+                let mut bytes = [0u8; 16];
+                bytes[..8].copy_from_slice(&(i as u64).to_le_bytes());
+                let label_0 = WireLabel::new(bytes);
+                bytes[15] = 0xFF;
+                (label_0, WireLabel::new(bytes))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_random_ot_derandomization() -> Result<()> {
+        for security in [OtSecurity::SemiHonest, OtSecurity::Malicious] {
+            // Precompute more OTs than are used
+            let mut sender = OtExtensionSender::new_random(40, security, &[1; 32]);
+            let mut receiver = OtExtensionReceiver::new_random(40, security, &[2; 32]);
+            let base_setup = receiver.setup()?;
+            let base_choices = sender.choose(&base_setup)?;
+            let extension = receiver.extend(&base_choices)?;
+            let random_sender = sender.finish_random(&extension)?;
+            let random_receiver = receiver.finish_random()?;
+
+            assert_eq!(random_sender.len(), 40);
+            for ((random_0, random_1), (&choice, label)) in random_sender.pairs().iter().zip(
+                random_receiver
+                    .choices()
+                    .iter()
+                    .zip(random_receiver.labels()),
+            ) {
+                assert_eq!(label, if choice { random_1 } else { random_0 });
+            }
+
+            let pairs = test_pairs(25);
+            let bits: Vec<bool> = (0..25).map(|i| i % 3 == 0).collect();
+            let derandomization = random_receiver.derandomize(&bits)?;
+            let transfer = random_sender.transfer(&pairs, &derandomization)?;
+            let labels = random_receiver.finish(&bits, &transfer)?;
+            for ((label, &bit), (label_0, label_1)) in labels.iter().zip(&bits).zip(&pairs) {
+                assert_eq!(label, if bit { label_1 } else { label_0 });
+            }
+
+            // The sender refuses labels that do not match the derandomized count
+            assert!(
+                random_sender
+                    .transfer(&test_pairs(24), &derandomization)
+                    .is_err()
+            );
+            assert!(random_receiver.derandomize(&[false; 41]).is_err());
+        }
+        Ok(())
+    }
+}
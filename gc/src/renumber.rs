@@ -0,0 +1,178 @@
+use crate::error::{GcError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::gate::{Circuit, Gate, WireId};
+use crate::wire_analyzer::analyze_circuit;
+
+/// Marks a wire without a new ID in the renumbering table
+pub(crate) const UNMAPPED: WireId = WireId::MAX;
+
+/// Marks a wire numbered after the gates, so gate order skips it
+pub(crate) const PENDING: WireId = UNMAPPED - 1;
+
+/// New IDs of the primary inputs and outputs of a renumbered circuit
+///
+/// Saved as JSON next to the rewritten circuit, so input bits and output
+/// values given by the old wire IDs can be translated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WireRemap {
+    /// Number of wires before renumbering
+    pub original_wires: u64,
+    /// New ID of each primary input wire, by old ID
+    pub inputs: BTreeMap<WireId, WireId>,
+    /// New ID of each primary output wire, by old ID
+    pub outputs: BTreeMap<WireId, WireId>,
+}
+
+impl WireRemap {
+    /// Load a remap table from JSON
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let remap = serde_json::from_str(&data)?;
+        Ok(remap)
+    }
+
+    /// Save the remap table as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Drop unused wires and renumber the rest into a gap-free range
+///
+/// The primary inputs take the first wires and the primary outputs the last
+/// ones, each in the order [`analyze_circuit`] lists them; every other wire a
+/// gate writes is numbered in gate order in between. Wires no gate reads or
+/// writes, and which are not declared inputs, disappear. Gates keep their
+/// order, and Bristol Fashion declarations stay valid, since they only fix
+/// the number of input and output wires.
+///
+/// # Arguments
+/// * `circuit` - The circuit to renumber
+///
+/// # Returns
+/// * `Ok((Circuit, WireRemap))` - The renumbered circuit and the new IDs of its inputs and outputs
+/// * `Err(GcError)` - A gate references a wire outside `0..num_wires`, writes a declared input, or
+///   reads an undeclared wire no gate writes
+pub fn compact_wires(circuit: &Circuit) -> Result<(Circuit, WireRemap)> {
+    let report = analyze_circuit(circuit)?;
+    let mut new_ids = vec![UNMAPPED; circuit.num_wires as usize];
+    let mut next_id: WireId = 0;
+
+    for &wire_id in &report.primary_input_wires {
+        new_ids[wire_id as usize] = next_id;
+        next_id += 1;
+    }
+    for &wire_id in &report.primary_output_wires {
+        new_ids[wire_id as usize] = PENDING;
+    }
+    for gate in &circuit.gates {
+        let new_id = &mut new_ids[gate.output_wire as usize];
+        if *new_id == UNMAPPED {
+            *new_id = next_id;
+            next_id += 1;
+        }
+    }
+    for &wire_id in &report.primary_output_wires {
+        new_ids[wire_id as usize] = next_id;
+        next_id += 1;
+    }
+
+    let gates = circuit
+        .gates
+        .iter()
+        .enumerate()
+        .map(|(index, gate)| renumber_gate(index, gate, &new_ids))
+        .collect::<Result<_>>()?;
+    let new_id = |wire_id: WireId| new_ids[wire_id as usize];
+    let remap = WireRemap {
+        original_wires: circuit.num_wires,
+        inputs: report
+            .primary_input_wires
+            .iter()
+            .map(|&wire_id| (wire_id, new_id(wire_id)))
+            .collect(),
+        outputs: report
+            .primary_output_wires
+            .iter()
+            .map(|&wire_id| (wire_id, new_id(wire_id)))
+            .collect(),
+    };
+    tracing::info!(
+        wires = circuit.num_wires,
+        compacted = next_id,
+        "renumbered circuit wires"
+    );
+    Ok((
+        Circuit {
+            num_wires: next_id,
+            gates,
+            io: circuit.io.clone(),
+        },
+        remap,
+    ))
+}
+
+/// Rewrite the wires of gate `index` through the table of new IDs
+///
+/// # Returns
+/// * `Ok(Gate)` - The gate on its new wires
+/// * `Err(GcError)` - The gate reads a wire without a new ID
+pub(crate) fn renumber_gate(index: usize, gate: &Gate, new_ids: &[WireId]) -> Result<Gate> {
+    let renumbered = gate.map_wires(|wire_id| new_ids[wire_id as usize]);
+    if renumbered.input_wires().any(|wire_id| wire_id == UNMAPPED) {
+        return Err(GcError::InvalidCircuit(format!(
+            "Gate {} reads a wire that is neither an input nor written by a gate",
+            index
+        )));
+    }
+    Ok(renumbered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plain::evaluate_plain;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_compact_wires() -> Result<()> {
+        let circuit = Circuit::parse("3 10\n2 1 0 5 7 AND\n2 1 7 1 9 XOR\n1 1 9 8 EQW\n")?;
+        let (compacted, remap) = compact_wires(&circuit)?;
+        assert_eq!(compacted.num_wires, 6);
+        assert_eq!(remap.original_wires, 10);
+        assert_eq!(remap.inputs, BTreeMap::from([(0, 0), (1, 1), (5, 2)]));
+        assert_eq!(remap.outputs, BTreeMap::from([(8, 5)]));
+        assert_eq!(compacted.gates[0], Gate::and(0, 2, 3));
+
+        // Both circuits compute the same function through the remap
+        let original_report = analyze_circuit(&circuit)?;
+        let compacted_report = analyze_circuit(&compacted)?;
+        for bits in 0..8u8 {
+            let inputs: HashMap<WireId, bool> = remap
+                .inputs
+                .keys()
+                .enumerate()
+                .map(|(i, &wire_id)| (wire_id, bits >> i & 1 == 1))
+                .collect();
+            let remapped_inputs = inputs
+                .iter()
+                .map(|(wire_id, &bit)| (remap.inputs[wire_id], bit))
+                .collect();
+            let outputs = evaluate_plain(&circuit, &original_report, &inputs)?;
+            let compacted_outputs =
+                evaluate_plain(&compacted, &compacted_report, &remapped_inputs)?;
+            assert_eq!(compacted_outputs[&5], outputs[&8]);
+        }
+
+        // Declared circuits keep their declarations, and gaps inside them are errors
+        let declared = Circuit::parse("1 5\n2 1 1\n1 1\n\n2 1 0 3 4 AND\n")?;
+        let error_msg = compact_wires(&declared).unwrap_err().to_string();
+        assert!(error_msg.contains("Gate 0 reads a wire"), "{}", error_msg);
+        Ok(())
+    }
+}
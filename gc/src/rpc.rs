@@ -0,0 +1,872 @@
+use crate::error::{GcError, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, watch};
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::async_tables::AsyncTableSource;
+use crate::channel::{Channel, FrameHeader};
+use crate::chunk::ChunkWriter;
+use crate::constants::FRAME_HEADER_SIZE;
+use crate::decoder::DecodingInfo;
+use crate::evaluator::{EvaluationResult, TableCursor};
+use crate::garbler::{GarbledTable, GarblerBuilder, TablesHeader, WireLabel, WireLabels};
+use crate::gate::{WireId, hash_circuit};
+use crate::ot_remote::{receive_ot, send_ot};
+use crate::ot_simulation::{EncodedInputs, OTResult};
+use crate::partition::InputPartition;
+use crate::progress::NoProgress;
+use crate::sink::GarbledTableSink;
+use crate::two_party::{
+    TABLES_BATCH, TABLES_END, TwoPartyOptions, evaluate_file_with_tables, open_circuit, split_seed,
+};
+use crate::wire_analyzer::{WireUsageReport, analyze_wire_usage};
+
+pub use service::garbler_client::GarblerClient;
+pub use service::garbler_server::{Garbler, GarblerServer};
+
+/// Service plumbing generated by `build.rs`
+mod service {
+    #![allow(missing_docs, unreachable_pub, clippy::all)]
+    include!(concat!(env!("OUT_DIR"), "/gc.rpc.Garbler.rs"));
+}
+
+/// OT messages buffered in each direction; OT has a handful of steps
+const OT_QUEUE: usize = 4;
+
+/// Table batches buffered between the garbling thread and the response stream
+const TABLES_IN_FLIGHT: usize = 4;
+
+/// Request of `SubmitInputs`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubmitInputsRequest {
+    /// Bit value for every garbler input wire: wire_id -> bit
+    #[prost(map = "uint64, bool", tag = "1")]
+    pub inputs: HashMap<u64, bool>,
+}
+
+/// Response of `SubmitInputs`: parameters of the session
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SessionInfo {
+    /// Digest of the circuit's gates (32 bytes)
+    #[prost(bytes = "vec", tag = "1")]
+    pub circuit_digest: Vec<u8>,
+    /// Session id of the garbling run (16 bytes)
+    #[prost(bytes = "vec", tag = "2")]
+    pub session_id: Vec<u8>,
+    /// Input wires the evaluator provides through OT, ascending
+    #[prost(uint64, repeated, tag = "3")]
+    pub evaluator_input_wires: Vec<u64>,
+}
+
+/// One message of the OT protocol, in either direction of `RunOt`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OtMessage {
+    /// Message bytes, as sent over a [`Channel`] by [`send_ot`] and [`receive_ot`]
+    #[prost(bytes = "vec", tag = "1")]
+    pub payload: Vec<u8>,
+}
+
+/// Request of `StreamTables`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StreamTablesRequest {
+    /// Digest of the evaluator's circuit, which must match the garbler's
+    #[prost(bytes = "vec", tag = "1")]
+    pub circuit_digest: Vec<u8>,
+}
+
+/// One message of the `StreamTables` response stream
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TableChunk {
+    /// A batch of tables, or the end of the stream
+    #[prost(oneof = "table_chunk::Chunk", tags = "1, 2")]
+    pub chunk: Option<table_chunk::Chunk>,
+}
+
+/// Contents of a [`TableChunk`]
+pub mod table_chunk {
+    /// A batch of tables, or the end of the stream
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Chunk {
+        /// Garbled tables of 64 bytes each, in AND gate order, framed by a
+        /// [`ChunkWriter`](crate::chunk::ChunkWriter) with a sequence number
+        /// and BLAKE3 checksum
+        #[prost(bytes, tag = "1")]
+        Tables(Vec<u8>),
+        /// Sent once, after the last table
+        #[prost(message, tag = "2")]
+        End(super::TablesEnd),
+    }
+}
+
+/// The tables header, closing a `StreamTables` response
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TablesEnd {
+    /// Number of tables sent
+    #[prost(uint64, tag = "1")]
+    pub num_tables: u64,
+    /// Digest of the garbled circuit's gates (32 bytes)
+    #[prost(bytes = "vec", tag = "2")]
+    pub circuit_digest: Vec<u8>,
+    /// Session id of the garbling run (16 bytes)
+    #[prost(bytes = "vec", tag = "3")]
+    pub session_id: Vec<u8>,
+    /// The same header as [`TablesHeader::to_bytes`] encodes it
+    #[prost(bytes = "vec", tag = "4")]
+    pub header: Vec<u8>,
+}
+
+/// Request of `GetResult`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetResultRequest {
+    /// Label of every primary output wire: wire_id -> 16-byte label
+    #[prost(map = "uint64, bytes", tag = "1")]
+    pub output_labels: HashMap<u64, Vec<u8>>,
+}
+
+/// Response of `GetResult`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetResultResponse {
+    /// Bit value of every primary output wire: wire_id -> bit
+    #[prost(map = "uint64, bool", tag = "1")]
+    pub outputs: HashMap<u64, bool>,
+}
+
+/// Garbler side of the gRPC service, for one session
+///
+/// Serve it with [`GarblerServer`] on a tonic server; clones share the
+/// session. The circuit is analyzed and the input labels drawn up front, and
+/// tables are garbled while `StreamTables` streams them, so they never touch
+/// the disk. See `proto/garbler.proto` for the order of the calls.
+#[derive(Debug, Clone)]
+pub struct GarblerService {
+    shared: Arc<ServiceState>,
+}
+
+/// Everything a [`GarblerService`] knows about its session
+#[derive(Debug)]
+struct ServiceState {
+    circuit_path: PathBuf,
+    partition: InputPartition,
+    options: TwoPartyOptions,
+    garbling_seed: [u8; 32],
+    ot_seed: [u8; 32],
+    wire_report: WireUsageReport,
+    circuit_digest: [u8; 32],
+    input_labels: WireLabels,
+    progress: Mutex<SessionProgress>,
+    /// Whether OT succeeded, once its thread has returned
+    ot_finished: watch::Sender<Option<bool>>,
+    /// Decoded outputs, once `GetResult` succeeded
+    outputs: watch::Sender<Option<BTreeMap<WireId, bool>>>,
+}
+
+/// How far the session got
+#[derive(Debug, Default)]
+struct SessionProgress {
+    garbler_inputs: Option<Arc<EncodedInputs>>,
+    ot_started: bool,
+    tables_started: bool,
+    decoding_info: Option<Arc<DecodingInfo>>,
+}
+
+impl GarblerService {
+    /// Prepare a session garbling `circuit_path`
+    ///
+    /// # Arguments
+    /// * `circuit_path` - Path to the Bristol circuit file
+    /// * `partition` - Which primary inputs belong to the garbler
+    /// * `seed_data` - 32 bytes of random seed, split into the garbling seed and the OT seed
+    /// * `options` - OT settings, which must match the evaluator's
+    ///
+    /// # Returns
+    /// * `Ok(GarblerService)` - The service, waiting for the garbler's inputs
+    /// * `Err(GcError)` - Parse or I/O error, or a range of the partition covers no primary input
+    pub fn new(
+        circuit_path: impl Into<PathBuf>,
+        partition: InputPartition,
+        seed_data: &[u8; 32],
+        options: TwoPartyOptions,
+    ) -> Result<Self> {
+        let circuit_path = circuit_path.into();
+        let (garbling_seed, ot_seed) = split_seed(seed_data);
+        let wire_report = analyze_wire_usage(
+            &mut open_circuit(&circuit_path, options.lenient)?,
+            &NoProgress,
+        )?;
+        let circuit_digest = hash_circuit(std::fs::File::open(&circuit_path)?)?;
+        let input_labels = GarblerBuilder::new(garbling_seed)
+            .input_partition(&partition)
+            .input_wire_labels(&wire_report)?;
+        Ok(Self {
+            shared: Arc::new(ServiceState {
+                circuit_path,
+                partition,
+                options,
+                garbling_seed,
+                ot_seed,
+                wire_report,
+                circuit_digest,
+                input_labels,
+                progress: Mutex::new(SessionProgress::default()),
+                ot_finished: watch::channel(None).0,
+                outputs: watch::channel(None).0,
+            }),
+        })
+    }
+
+    /// Set the garbler's input bits now instead of waiting for `SubmitInputs`
+    pub fn with_inputs(self, inputs: &HashMap<WireId, bool>) -> Result<Self> {
+        let encoded = self.shared.input_labels.encode_inputs(inputs)?;
+        self.shared.progress.lock().unwrap().garbler_inputs = Some(Arc::new(encoded));
+        Ok(self)
+    }
+
+    /// Parameters of the session, as `SubmitInputs` returns them
+    pub fn session_info(&self) -> SessionInfo {
+        SessionInfo {
+            circuit_digest: self.shared.circuit_digest.to_vec(),
+            session_id: self.shared.input_labels.session_id.as_bytes().to_vec(),
+            evaluator_input_wires: self.shared.input_labels.evaluator_input_wires(),
+        }
+    }
+
+    /// Serve the session on `listener` until the evaluator got its result
+    ///
+    /// # Arguments
+    /// * `listener` - Bound socket the evaluator connects to
+    ///
+    /// # Returns
+    /// * `Ok(BTreeMap<WireId, bool>)` - Bit value of every primary output wire
+    /// * `Err(GcError)` - The server failed
+    pub async fn serve(self, listener: TcpListener) -> Result<BTreeMap<WireId, bool>> {
+        let session = self.clone();
+        Server::builder()
+            .add_service(GarblerServer::new(self))
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                session.outputs().await;
+            })
+            .await
+            .map_err(|e| GcError::Disconnected(format!("gRPC server failed: {}", e)))?;
+        Ok(session.outputs().await)
+    }
+
+    /// Wait until the evaluator got its result, and return the decoded outputs
+    pub async fn outputs(&self) -> BTreeMap<WireId, bool> {
+        let mut outputs = self.shared.outputs.subscribe();
+        let outputs = outputs
+            .wait_for(Option::is_some)
+            .await
+            .expect("the service holds the sender");
+        outputs.clone().unwrap()
+    }
+}
+
+#[tonic::async_trait]
+impl Garbler for GarblerService {
+    async fn submit_inputs(
+        &self,
+        request: Request<SubmitInputsRequest>,
+    ) -> std::result::Result<Response<SessionInfo>, Status> {
+        let encoded = self
+            .shared
+            .input_labels
+            .encode_inputs(&request.into_inner().inputs)
+            .map_err(to_status)?;
+        let mut progress = self.shared.progress.lock().unwrap();
+        if progress.garbler_inputs.is_some() {
+            return Err(Status::already_exists("Garbler inputs were already given"));
+        }
+        progress.garbler_inputs = Some(Arc::new(encoded));
+        Ok(Response::new(self.session_info()))
+    }
+
+    type RunOtStream = ReceiverStream<std::result::Result<OtMessage, Status>>;
+
+    async fn run_ot(
+        &self,
+        request: Request<Streaming<OtMessage>>,
+    ) -> std::result::Result<Response<Self::RunOtStream>, Status> {
+        let garbler_inputs = {
+            let mut progress = self.shared.progress.lock().unwrap();
+            let Some(garbler_inputs) = progress.garbler_inputs.clone() else {
+                return Err(Status::failed_precondition(
+                    "Garbler inputs were not given yet",
+                ));
+            };
+            if progress.ot_started {
+                return Err(Status::failed_precondition("OT already ran"));
+            }
+            progress.ot_started = true;
+            garbler_inputs
+        };
+
+        let (to_ot, from_evaluator) = mpsc::channel(OT_QUEUE);
+        let (to_evaluator, mut from_ot) = mpsc::channel(OT_QUEUE);
+        let (responses, response_stream) = mpsc::channel(OT_QUEUE);
+        tokio::spawn(forward_ot_messages(request.into_inner(), to_ot));
+
+        let shared = self.shared.clone();
+        let ot = tokio::task::spawn_blocking(move || {
+            send_ot(
+                &mut Channel::new(MessagePipe::new(from_evaluator, to_evaluator)),
+                &shared.input_labels,
+                Some(&garbler_inputs),
+                shared.options.security,
+                shared.options.backend,
+                &shared.ot_seed,
+                &NoProgress,
+            )
+        });
+        let shared = self.shared.clone();
+        tokio::spawn(async move {
+            while let Some(payload) = from_ot.recv().await {
+                if responses.send(Ok(OtMessage { payload })).await.is_err() {
+                    return;
+                }
+            }
+            // The OT thread dropped its end of the pipe: it has finished
+            match ot.await {
+                Ok(Ok(stats)) => {
+                    shared.ot_finished.send_replace(Some(true));
+                    tracing::info!(
+                        evaluator_inputs = stats.evaluator_inputs,
+                        "gRPC OT finished"
+                    );
+                }
+                Ok(Err(e)) => {
+                    shared.ot_finished.send_replace(Some(false));
+                    let _ = responses.send(Err(to_status(e))).await;
+                }
+                Err(e) => {
+                    shared.ot_finished.send_replace(Some(false));
+                    let _ = responses.send(Err(Status::internal(e.to_string()))).await;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(response_stream)))
+    }
+
+    type StreamTablesStream = ReceiverStream<std::result::Result<TableChunk, Status>>;
+
+    async fn stream_tables(
+        &self,
+        request: Request<StreamTablesRequest>,
+    ) -> std::result::Result<Response<Self::StreamTablesStream>, Status> {
+        if request.into_inner().circuit_digest != self.shared.circuit_digest {
+            return Err(Status::failed_precondition(
+                "The evaluator's circuit differs from the garbler's",
+            ));
+        }
+        if !self.shared.progress.lock().unwrap().ot_started {
+            return Err(Status::failed_precondition("OT has not completed yet"));
+        }
+        // The evaluator may be done with OT before the garbler's OT thread
+        // has returned, so wait for it rather than refuse the call
+        let ot_succeeded = self
+            .shared
+            .ot_finished
+            .subscribe()
+            .wait_for(Option::is_some)
+            .await
+            .is_ok_and(|finished| *finished == Some(true));
+        if !ot_succeeded {
+            return Err(Status::failed_precondition("OT failed"));
+        }
+        {
+            let mut progress = self.shared.progress.lock().unwrap();
+            if progress.tables_started {
+                return Err(Status::failed_precondition("Tables were already streamed"));
+            }
+            progress.tables_started = true;
+        }
+
+        let (chunks, chunk_stream) = mpsc::channel(TABLES_IN_FLIGHT);
+        let shared = self.shared.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut sink = ChunkSink {
+                chunks: chunks.clone(),
+                framing: ChunkWriter::new(),
+                header: None,
+            };
+            let garbled = open_circuit(&shared.circuit_path, shared.options.lenient).and_then(
+                |mut stream| {
+                    GarblerBuilder::new(shared.garbling_seed)
+                        .input_partition(&shared.partition)
+                        .sink(&mut sink)
+                        .garble(&mut stream, &shared.wire_report)
+                },
+            );
+            let chunk = garbled.map(|garbling| {
+                // Decoding information is in place before the evaluator sees the end
+                shared.progress.lock().unwrap().decoding_info = Some(Arc::new(
+                    DecodingInfo::from_wire_labels(&garbling.wire_labels),
+                ));
+                let header = sink.header.expect("garbling finished the sink");
+                TableChunk {
+                    chunk: Some(table_chunk::Chunk::End(TablesEnd {
+                        num_tables: header.num_tables,
+                        circuit_digest: header.circuit_digest.to_vec(),
+                        session_id: header.session_id.as_bytes().to_vec(),
+                        header: header.to_bytes().to_vec(),
+                    })),
+                }
+            });
+            let _ = chunks.blocking_send(chunk.map_err(to_status));
+        });
+        Ok(Response::new(ReceiverStream::new(chunk_stream)))
+    }
+
+    async fn get_result(
+        &self,
+        request: Request<GetResultRequest>,
+    ) -> std::result::Result<Response<GetResultResponse>, Status> {
+        let Some(decoding_info) = self.shared.progress.lock().unwrap().decoding_info.clone() else {
+            return Err(Status::failed_precondition(
+                "Tables have not been streamed yet",
+            ));
+        };
+        let output_labels = request.into_inner().output_labels;
+        let mut outputs = BTreeMap::new();
+        for &wire_id in &self.shared.wire_report.primary_output_wires {
+            let label = output_labels
+                .get(&wire_id)
+                .and_then(|label| <[u8; 16]>::try_from(label.as_slice()).ok())
+                .ok_or_else(|| {
+                    Status::invalid_argument(format!(
+                        "No 16-byte label for output wire {}",
+                        wire_id
+                    ))
+                })?;
+            let bit = decoding_info
+                .decode_label(wire_id, &WireLabel::new(label))
+                .map_err(to_status)?;
+            outputs.insert(wire_id, bit);
+        }
+        self.shared.outputs.send_replace(Some(outputs.clone()));
+        Ok(Response::new(GetResultResponse {
+            outputs: outputs.into_iter().collect(),
+        }))
+    }
+}
+
+/// Evaluate a circuit garbled by a [`GarblerService`]
+///
+/// Runs the evaluator's side of a session: OT for `inputs`, evaluation of
+/// the tables as they stream in, and decoding of the outputs by the
+/// garbler. The garbler's inputs must have been given already.
+///
+/// # Arguments
+/// * `endpoint` - URI of the garbler, e.g. `http://127.0.0.1:7742`
+/// * `circuit_path` - Path to the Bristol circuit file, which must match the garbler's
+/// * `inputs` - Bit value for every evaluator input wire: wire_id -> bit
+/// * `seed_data` - 32 bytes of random seed for the evaluator's OT secrets
+/// * `options` - OT settings, which must match the garbler's
+///
+/// # Returns
+/// * `Ok((EvaluationResult, BTreeMap<WireId, bool>))` - The output labels, and the output bits the garbler decoded
+/// * `Err(GcError)` - The garbler is unreachable or refused a call, or evaluation failed
+pub async fn evaluate_with_garbler(
+    endpoint: String,
+    circuit_path: &Path,
+    inputs: &HashMap<WireId, bool>,
+    seed_data: &[u8; 32],
+    options: &TwoPartyOptions,
+) -> Result<(EvaluationResult, BTreeMap<WireId, bool>)> {
+    let mut client = GarblerClient::connect(endpoint)
+        .await
+        .map_err(|e| GcError::Disconnected(format!("Cannot reach the garbler: {}", e)))?;
+    let circuit_path = circuit_path.to_path_buf();
+    let analysis_path = circuit_path.clone();
+    let lenient = options.lenient;
+    let (wire_report, circuit_digest) = tokio::task::spawn_blocking(move || -> Result<_> {
+        let wire_report =
+            analyze_wire_usage(&mut open_circuit(&analysis_path, lenient)?, &NoProgress)?;
+        Ok((
+            wire_report,
+            hash_circuit(std::fs::File::open(&analysis_path)?)?,
+        ))
+    })
+    .await
+    .map_err(join_error)??;
+
+    let ot_result = receive_ot_from_garbler(&mut client, inputs, seed_data, options).await?;
+
+    // Evaluate the tables as they arrive, with a bounded queue in between
+    let mut chunks = client
+        .stream_tables(StreamTablesRequest {
+            circuit_digest: circuit_digest.to_vec(),
+        })
+        .await
+        .map_err(status_error)?
+        .into_inner();
+    let (queue, mut source) = AsyncTableSource::bounded(TABLES_IN_FLIGHT);
+    let session_id = ot_result.session_id;
+    let evaluation = tokio::task::spawn_blocking(move || -> Result<_> {
+        let evaluation = evaluate_file_with_tables(
+            &circuit_path,
+            lenient,
+            &wire_report,
+            &ot_result,
+            || source.next_table(),
+            &NoProgress,
+        )?;
+        Ok((evaluation, source.finish()?))
+    });
+    while let Some(chunk) = chunks.message().await.map_err(status_error)? {
+        let (kind, bytes) = match chunk.chunk {
+            Some(table_chunk::Chunk::Tables(tables)) => (TABLES_BATCH, tables),
+            Some(table_chunk::Chunk::End(end)) => (TABLES_END, end.header),
+            None => {
+                return Err(GcError::Protocol("Empty garbled tables chunk".to_string()));
+            }
+        };
+        let mut message = Vec::with_capacity(1 + bytes.len());
+        message.push(kind);
+        message.extend_from_slice(&bytes);
+        // A closed queue means evaluation failed; its error is reported below
+        if queue.send(message).await.is_err() || kind == TABLES_END {
+            break;
+        }
+    }
+    drop(queue);
+    let (evaluation, tables_header) = evaluation.await.map_err(join_error)??;
+    tables_header.check_circuit(&circuit_digest)?;
+    tables_header.check_session(&session_id, "OT result")?;
+
+    let output_labels = evaluation
+        .output_results
+        .iter()
+        .map(|(&wire_id, output)| (wire_id, output.label.as_bytes().to_vec()))
+        .collect();
+    let outputs = client
+        .get_result(GetResultRequest { output_labels })
+        .await
+        .map_err(status_error)?
+        .into_inner()
+        .outputs;
+    Ok((evaluation, outputs.into_iter().collect()))
+}
+
+/// Run the evaluator's side of OT over `RunOt`
+///
+/// The blocking OT code is driven through a pipe of messages. Returns as
+/// soon as the evaluator has its labels, which may be before the garbler's
+/// side of OT has returned.
+async fn receive_ot_from_garbler(
+    client: &mut GarblerClient<tonic::transport::Channel>,
+    inputs: &HashMap<WireId, bool>,
+    seed_data: &[u8; 32],
+    options: &TwoPartyOptions,
+) -> Result<OTResult> {
+    let (to_garbler, requests) = mpsc::channel(OT_QUEUE);
+    let responses = client
+        .run_ot(ReceiverStream::new(requests).map(|payload| OtMessage { payload }))
+        .await
+        .map_err(status_error)?
+        .into_inner();
+    let (to_ot, from_garbler) = mpsc::channel(OT_QUEUE);
+    tokio::spawn(forward_ot_messages(responses, to_ot));
+    let (inputs, seed_data, options) = (inputs.clone(), *seed_data, *options);
+    let (ot_result, _) = tokio::task::spawn_blocking(move || {
+        receive_ot(
+            &mut Channel::new(MessagePipe::new(from_garbler, to_garbler)),
+            &inputs,
+            options.security,
+            options.backend,
+            &seed_data,
+            &NoProgress,
+        )
+    })
+    .await
+    .map_err(join_error)??;
+    Ok(ot_result)
+}
+
+/// Sends each batch of garbled tables as a chunk of the response stream
+struct ChunkSink {
+    chunks: mpsc::Sender<std::result::Result<TableChunk, Status>>,
+    /// Numbers and checksums each batch
+    framing: ChunkWriter,
+    /// Header of the tables, sent once the decoding information is stored
+    header: Option<TablesHeader>,
+}
+
+impl GarbledTableSink for ChunkSink {
+    fn write_tables(&mut self, tables: &[GarbledTable]) -> Result<()> {
+        let tables: Vec<u8> = tables.iter().flat_map(|table| table.as_binary()).collect();
+        let mut bytes = Vec::new();
+        self.framing.write_chunk(&tables, &mut bytes);
+        self.chunks
+            .blocking_send(Ok(TableChunk {
+                chunk: Some(table_chunk::Chunk::Tables(bytes)),
+            }))
+            .map_err(|_| GcError::Disconnected("The evaluator stopped reading tables".to_string()))
+    }
+
+    fn finish(&mut self, header: &TablesHeader) -> Result<()> {
+        self.header = Some(*header);
+        Ok(())
+    }
+}
+
+/// Forward the messages of a gRPC stream to the OT thread's pipe
+async fn forward_ot_messages(
+    mut messages: Streaming<OtMessage>,
+    pipe: mpsc::Sender<std::result::Result<Vec<u8>, String>>,
+) {
+    loop {
+        let next = match messages.message().await {
+            Ok(Some(message)) => Ok(message.payload),
+            Ok(None) => return,
+            Err(status) => Err(status.message().to_string()),
+        };
+        let failed = next.is_err();
+        if pipe.send(next).await.is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// Byte stream for a [`Channel`] whose messages travel as separate gRPC messages
+///
+/// Each flushed write is one channel message, frame header included, and
+/// becomes one outgoing message without the header; each incoming message is
+/// read back with its header restored. gRPC versions the service itself, so
+/// the framing version is never negotiated here. Runs on a blocking thread.
+struct MessagePipe {
+    incoming: mpsc::Receiver<std::result::Result<Vec<u8>, String>>,
+    outgoing: mpsc::Sender<Vec<u8>>,
+    /// Incoming bytes not read yet, with the frame header
+    read_buffer: Vec<u8>,
+    read_position: usize,
+    /// Bytes written since the last flush
+    write_buffer: Vec<u8>,
+}
+
+impl MessagePipe {
+    fn new(
+        incoming: mpsc::Receiver<std::result::Result<Vec<u8>, String>>,
+        outgoing: mpsc::Sender<Vec<u8>>,
+    ) -> Self {
+        Self {
+            incoming,
+            outgoing,
+            read_buffer: Vec::new(),
+            read_position: 0,
+            write_buffer: Vec::new(),
+        }
+    }
+}
+
+impl Read for MessagePipe {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.read_position == self.read_buffer.len() {
+            let message = match self.incoming.blocking_recv() {
+                Some(Ok(message)) => message,
+                Some(Err(status)) => return Err(std::io::Error::other(status)),
+                None => return Ok(0),
+            };
+            self.read_buffer.clear();
+            self.read_buffer
+                .extend_from_slice(&FrameHeader::message(message.len()).to_bytes());
+            self.read_buffer.extend_from_slice(&message);
+            self.read_position = 0;
+        }
+        let available = &self.read_buffer[self.read_position..];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.read_position += count;
+        Ok(count)
+    }
+}
+
+impl Write for MessagePipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let mut frames = self.write_buffer.as_slice();
+        while let Some((header, rest)) = frames.split_first_chunk::<FRAME_HEADER_SIZE>() {
+            let length = FrameHeader::from_bytes(header)
+                .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?
+                .length as usize;
+            if rest.len() < length {
+                break;
+            }
+            let (message, rest) = rest.split_at(length);
+            self.outgoing
+                .blocking_send(message.to_vec())
+                .map_err(|_| std::io::Error::new(ErrorKind::BrokenPipe, "peer went away"))?;
+            frames = rest;
+        }
+        let consumed = self.write_buffer.len() - frames.len();
+        self.write_buffer.drain(..consumed);
+        Ok(())
+    }
+}
+
+/// Status a gRPC client sees for a library error
+fn to_status(error: GcError) -> Status {
+    match error {
+        GcError::InvalidArgument(_)
+        | GcError::MissingWire { .. }
+        | GcError::InvalidLabel { .. }
+        | GcError::Protocol(_) => Status::invalid_argument(error.to_string()),
+        GcError::Disconnected(_) => Status::aborted(error.to_string()),
+        _ => Status::internal(error.to_string()),
+    }
+}
+
+/// Library error for a status returned by the garbler
+fn status_error(status: Status) -> GcError {
+    GcError::Protocol(format!(
+        "Garbler answered {:?}: {}",
+        status.code(),
+        status.message()
+    ))
+}
+
+fn join_error(error: tokio::task::JoinError) -> GcError {
+    GcError::Disconnected(format!("A worker task failed: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gate::Circuit;
+    use crate::plain::{SESSION_CIRCUIT, evaluate_plain, session_circuit_file};
+
+    #[test]
+    fn test_session_over_grpc() -> Result<()> {
+        let circuit_file = session_circuit_file()?;
+        let circuit_path = circuit_file.path().to_path_buf();
+        let garbler_bits = HashMap::from([(0, true), (1, true)]);
+        let evaluator_bits = HashMap::from([(2, false), (3, true)]);
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let service = GarblerService::new(
+                &circuit_path,
+                InputPartition::parse("0..2")?,
+                &[4; 32],
+                TwoPartyOptions::default(),
+            )?;
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let endpoint = format!("http://{}", listener.local_addr()?);
+            let server = tokio::spawn(service.clone().serve(listener));
+
+            // Calls out of order are refused
+            let mut client = GarblerClient::connect(endpoint.clone()).await.unwrap();
+            let status = client
+                .stream_tables(StreamTablesRequest {
+                    circuit_digest: service.session_info().circuit_digest,
+                })
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+            let info = client
+                .submit_inputs(SubmitInputsRequest {
+                    inputs: garbler_bits.clone(),
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(info.evaluator_input_wires, vec![2, 3]);
+
+            let (evaluation, outputs) = evaluate_with_garbler(
+                endpoint,
+                &circuit_path,
+                &evaluator_bits,
+                &[5; 32],
+                &TwoPartyOptions::default(),
+            )
+            .await?;
+            assert_eq!(server.await.unwrap()?, outputs);
+            assert_eq!(evaluation.output_results.len(), 2);
+
+            let circuit = Circuit::parse(SESSION_CIRCUIT)?;
+            let mut all_bits = garbler_bits;
+            all_bits.extend(evaluator_bits);
+            let expected = evaluate_plain(&circuit, &service.shared.wire_report, &all_bits)?;
+            assert_eq!(outputs, expected.into_iter().collect());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_stream_tables_right_after_ot() -> Result<()> {
+        let circuit_file = session_circuit_file()?;
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let service = GarblerService::new(
+                circuit_file.path(),
+                InputPartition::parse("0..2")?,
+                &[4; 32],
+                TwoPartyOptions::default(),
+            )?
+            .with_inputs(&HashMap::from([(0, true), (1, false)]))?;
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let endpoint = format!("http://{}", listener.local_addr()?);
+            tokio::spawn(service.clone().serve(listener));
+
+            // The evaluator holds its labels before the garbler's OT thread
+            // returns; asking for the tables straight away must still work
+            let mut client = GarblerClient::connect(endpoint).await.unwrap();
+            receive_ot_from_garbler(
+                &mut client,
+                &HashMap::from([(2, false), (3, true)]),
+                &[5; 32],
+                &TwoPartyOptions::default(),
+            )
+            .await?;
+            let mut chunks = client
+                .stream_tables(StreamTablesRequest {
+                    circuit_digest: service.session_info().circuit_digest,
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            let mut ended = false;
+            while let Some(chunk) = chunks.message().await.unwrap() {
+                ended |= matches!(chunk.chunk, Some(table_chunk::Chunk::End(_)));
+            }
+            assert!(ended);
+
+            // Deterministically: the call waits for the garbler's OT thread
+            let service = GarblerService::new(
+                circuit_file.path(),
+                InputPartition::parse("0..2")?,
+                &[4; 32],
+                TwoPartyOptions::default(),
+            )?;
+            service.shared.progress.lock().unwrap().ot_started = true;
+            let request = StreamTablesRequest {
+                circuit_digest: service.session_info().circuit_digest,
+            };
+            let waiting = tokio::spawn({
+                let service = service.clone();
+                async move { service.stream_tables(Request::new(request)).await }
+            });
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            assert!(!waiting.is_finished());
+            service.shared.ot_finished.send_replace(Some(true));
+            assert!(waiting.await.unwrap().is_ok());
+            Ok(())
+        })
+    }
+}
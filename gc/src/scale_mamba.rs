@@ -0,0 +1,186 @@
+use crate::error::{GcError, Result};
+use std::io::Read;
+
+use crate::gate::{Circuit, Gate, WireId};
+use crate::parser::{parse_gate_line, read_header};
+use crate::stream::BufferedLineStream;
+
+/// Placeholder for the constant-one wire until its ID is known
+const ONE_WIRE: WireId = WireId::MAX;
+
+/// Import a circuit in the SCALE-MAMBA format
+///
+/// SCALE-MAMBA circuits are Bristol Fashion files that may also contain
+/// `INV` gates (`1 1 <in> <out> INV`) and `MAND` gates computing several
+/// ANDs at once (`<2m> <m> <a_1..a_m> <b_1..b_m> <out_1..out_m> MAND`).
+/// Each `MAND` gate becomes `m` AND gates. Each `INV` gate becomes an XOR
+/// with a constant-one wire, free like any XOR; that wire is added right
+/// before the outputs, which stay the last wires of the circuit as the
+/// Bristol Fashion declarations require. All other gates are read as in
+/// [`parse_gate_line`].
+///
+/// # Arguments
+/// * `stream` - SCALE-MAMBA circuit
+///
+/// # Returns
+/// * `Ok(Circuit)` - The circuit with only gates the garbler supports
+/// * `Err(GcError)` - Malformed header or gate line, or an unsupported gate type
+pub fn import_scale_mamba<R: Read>(stream: &mut BufferedLineStream<R>) -> Result<Circuit> {
+    let header = read_header(stream)?;
+
+    let mut gates = Vec::with_capacity(header.num_gates as usize);
+    let mut line_number = header.lines; // Already processed header
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        let line = line_result?;
+        match line.split_whitespace().last() {
+            Some("INV") => {
+                let wires = parse_wires(line, line_number, 1, 1)?;
+                gates.push(Gate::xor(wires[0], ONE_WIRE, wires[1]));
+            }
+            Some("MAND") => {
+                let num_ands = parse_counts(line, line_number)?.1;
+                let wires = parse_wires(line, line_number, 2 * num_ands, num_ands)?;
+                let (inputs, outputs) = wires.split_at(2 * num_ands);
+                let (left, right) = inputs.split_at(num_ands);
+                for i in 0..num_ands {
+                    gates.push(Gate::and(left[i], right[i], outputs[i]));
+                }
+            }
+            _ => gates.push(parse_gate_line(line, line_number)?),
+        }
+    }
+
+    let mut num_wires = header.num_wires;
+    let uses_one = gates
+        .iter()
+        .any(|gate| gate.input_wires().any(|wire_id| wire_id == ONE_WIRE));
+    if uses_one {
+        // The constant-one wire takes the place of the first output wire
+        let one_wire = match &header.io {
+            Some(io) => io.output_wires(num_wires).start,
+            None => num_wires,
+        };
+        let renumber = |wire_id: WireId| match wire_id {
+            ONE_WIRE => one_wire,
+            wire_id if wire_id >= one_wire => wire_id + 1,
+            wire_id => wire_id,
+        };
+        for gate in &mut gates {
+            let num_inputs = gate.gate_type.num_inputs();
+            if num_inputs >= 1 {
+                gate.input_wire_1 = renumber(gate.input_wire_1);
+            }
+            if num_inputs == 2 {
+                gate.input_wire_2 = renumber(gate.input_wire_2);
+            }
+            gate.output_wire = renumber(gate.output_wire);
+        }
+        gates.insert(0, Gate::constant(true, one_wire));
+        num_wires += 1;
+    }
+
+    Ok(Circuit {
+        num_wires,
+        gates,
+        io: header.io,
+    })
+}
+
+/// Number of inputs and outputs at the start of a gate line
+fn parse_counts(line: &str, line_number: u64) -> Result<(usize, usize)> {
+    let mut tokens = line.split_whitespace();
+    let mut count = |what: &str| -> Result<usize> {
+        tokens
+            .next()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| GcError::parse(line_number, format!("Invalid {}: '{}'", what, line)))
+    };
+    Ok((count("num_inputs")?, count("num_outputs")?))
+}
+
+/// Wire IDs of a gate line with `num_inputs` inputs and `num_outputs` outputs
+fn parse_wires(
+    line: &str,
+    line_number: u64,
+    num_inputs: usize,
+    num_outputs: usize,
+) -> Result<Vec<WireId>> {
+    if parse_counts(line, line_number)? != (num_inputs, num_outputs) {
+        return Err(GcError::parse(
+            line_number,
+            format!(
+                "Expected {} inputs and {} outputs: '{}'",
+                num_inputs, num_outputs, line
+            ),
+        ));
+    }
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let wire_tokens = &tokens[2..tokens.len() - 1];
+    if wire_tokens.len() != num_inputs + num_outputs {
+        return Err(GcError::parse(
+            line_number,
+            format!(
+                "Expected {} wire IDs, got {}: '{}'",
+                num_inputs + num_outputs,
+                wire_tokens.len(),
+                line
+            ),
+        ));
+    }
+    wire_tokens
+        .iter()
+        .map(|token| {
+            token.parse().map_err(|_| {
+                GcError::parse(
+                    line_number,
+                    format!("Invalid wire ID '{}': '{}'", token, line),
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plain::evaluate_plain;
+    use crate::wire_analyzer::analyze_gates;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_import_scale_mamba() -> Result<()> {
+        // out_6 = NOT(a AND b), out_7 = c AND d, for inputs a, b and c, d
+        let text = "3 8\n2 2 2\n2 1 1\n\n4 2 0 2 1 3 4 7 MAND\n1 1 4 5 INV\n1 1 5 6 EQW\n";
+        let circuit = import_scale_mamba(&mut BufferedLineStream::from_reader(text.as_bytes()))?;
+
+        // The constant-one wire 6 shifts the outputs to wires 7 and 8
+        assert_eq!(circuit.num_wires, 9);
+        assert_eq!(
+            circuit.gates,
+            vec![
+                Gate::constant(true, 6),
+                Gate::and(0, 1, 4),
+                Gate::and(2, 3, 8),
+                Gate::xor(4, 6, 5),
+                Gate::buf(5, 7),
+            ]
+        );
+        let io = circuit.io.as_ref().unwrap();
+        assert_eq!(io.output_wires(circuit.num_wires), 7..9);
+
+        let inputs = HashMap::from([(0, true), (1, true), (2, true), (3, true)]);
+        let wire_report = analyze_gates(&circuit.gates, circuit.num_wires)?;
+        let outputs = evaluate_plain(&circuit, &wire_report, &inputs)?;
+        assert_eq!(outputs.get(&7), Some(&false));
+        assert_eq!(outputs.get(&8), Some(&true));
+
+        let error_msg = import_scale_mamba(&mut BufferedLineStream::from_reader(
+            "1 5\n4 2 0 1 2 3 4 MAND\n".as_bytes(),
+        ))
+        .unwrap_err()
+        .to_string();
+        assert!(error_msg.contains("Expected 6 wire IDs, got 5"));
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+use crate::error::{GcError, Result};
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::constants::{
+    GARBLED_TABLE_SIZE, SEEK_TABLE_ENTRY_SIZE, SEEK_TABLE_FOOTER_SIZE, ZSTD_SEEK_TABLE_MAGIC,
+    ZSTD_SEEKABLE_MAGIC,
+};
+
+/// Kind of file named in seek table errors
+const FORMAT: &str = "garbled tables seek table";
+
+/// Descriptor bits the seekable format reserves, which must be zero
+const RESERVED_DESCRIPTOR_BITS: u8 = 0x7c;
+
+/// Descriptor bit set when every entry carries a frame checksum
+const CHECKSUM_DESCRIPTOR_BIT: u8 = 0x80;
+
+/// One zstd frame of compressed garbled tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeekFrame {
+    /// Offset of the frame from the start of the compressed data
+    pub offset: u64,
+    /// Index of the first table in the frame, i.e. its first AND gate
+    pub first_table: u64,
+    /// Number of tables in the frame
+    pub num_tables: u64,
+}
+
+/// Index of the zstd frames of a compressed garbled tables file
+///
+/// [`CompressedTableWriter`](crate::sink::CompressedTableWriter) compresses
+/// every [`ZSTD_FLUSH_INTERVAL`](crate::constants::ZSTD_FLUSH_INTERVAL)
+/// tables into a frame of their own and ends the stream with this table in
+/// the zstd seekable format: a skippable frame, ignored by plain zstd
+/// decoders, listing the compressed and decompressed size of each frame as
+/// little-endian `u32`s, followed by the frame count, a descriptor byte and
+/// [`ZSTD_SEEKABLE_MAGIC`]. Table `n` belongs to the `n`th AND gate, so a
+/// reader resuming at, or evaluating only from, some AND gate starts decoding
+/// at the frame holding it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeekTable {
+    frames: Vec<SeekFrame>,
+    /// Size of the compressed data the frames cover
+    compressed_size: u64,
+}
+
+impl SeekTable {
+    /// Record the next frame, of `compressed_size` bytes holding `num_tables` tables
+    pub(crate) fn push_frame(&mut self, compressed_size: u64, num_tables: u64) {
+        self.frames.push(SeekFrame {
+            offset: self.compressed_size,
+            first_table: self.num_tables(),
+            num_tables,
+        });
+        self.compressed_size += compressed_size;
+    }
+
+    /// Frames in file order
+    pub fn frames(&self) -> &[SeekFrame] {
+        &self.frames
+    }
+
+    /// Total number of tables in the frames
+    pub fn num_tables(&self) -> u64 {
+        self.frames
+            .last()
+            .map_or(0, |frame| frame.first_table + frame.num_tables)
+    }
+
+    /// The frame holding table `table`, or `None` past the last table
+    pub fn frame_for_table(&self, table: u64) -> Option<&SeekFrame> {
+        let index = self
+            .frames
+            .partition_point(|frame| frame.first_table + frame.num_tables <= table);
+        self.frames.get(index)
+    }
+
+    /// Encode the table as the skippable frame that ends the compressed data
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - The encoded seek table
+    /// * `Err(GcError)` - A frame is too large for the seekable format's 32-bit sizes
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let too_large = |_| {
+            GcError::InvalidArgument(
+                "zstd frame exceeds 4GB, too large for the seekable format".into(),
+            )
+        };
+        let payload_size = self.frames.len() * SEEK_TABLE_ENTRY_SIZE + SEEK_TABLE_FOOTER_SIZE;
+        let mut bytes = Vec::with_capacity(8 + payload_size);
+        bytes.extend_from_slice(&ZSTD_SEEK_TABLE_MAGIC.to_le_bytes());
+        bytes.extend_from_slice(
+            &u32::try_from(payload_size)
+                .map_err(too_large)?
+                .to_le_bytes(),
+        );
+        for (index, frame) in self.frames.iter().enumerate() {
+            let end = self
+                .frames
+                .get(index + 1)
+                .map_or(self.compressed_size, |next| next.offset);
+            let decompressed_size = frame.num_tables * GARBLED_TABLE_SIZE as u64;
+            bytes.extend_from_slice(
+                &u32::try_from(end - frame.offset)
+                    .map_err(too_large)?
+                    .to_le_bytes(),
+            );
+            bytes.extend_from_slice(
+                &u32::try_from(decompressed_size)
+                    .map_err(too_large)?
+                    .to_le_bytes(),
+            );
+        }
+        bytes.extend_from_slice(
+            &u32::try_from(self.frames.len())
+                .map_err(too_large)?
+                .to_le_bytes(),
+        );
+        bytes.push(0);
+        bytes.extend_from_slice(&ZSTD_SEEKABLE_MAGIC.to_le_bytes());
+        Ok(bytes)
+    }
+
+    /// Read the seek table ending a compressed tables file, if it has one
+    ///
+    /// Files compressed as a single stream, e.g. by older versions, have no
+    /// seek table. Leaves the reader at an unspecified position.
+    ///
+    /// # Arguments
+    /// * `reader` - The compressed tables file
+    /// * `data_offset` - Offset of the first zstd frame, past any [`TablesHeader`](crate::garbler::TablesHeader)
+    ///
+    /// # Returns
+    /// * `Ok(Some(SeekTable))` - The frames of the file
+    /// * `Ok(None)` - The file does not end with a seek table
+    /// * `Err(GcError)` - The seek table is malformed or does not match the file size
+    pub fn read_from<R: Read + Seek>(reader: &mut R, data_offset: u64) -> Result<Option<Self>> {
+        let data_size = reader.seek(SeekFrom::End(0))?.saturating_sub(data_offset);
+        if data_size < (8 + SEEK_TABLE_FOOTER_SIZE) as u64 {
+            return Ok(None);
+        }
+        let mut footer = [0u8; SEEK_TABLE_FOOTER_SIZE];
+        reader.seek(SeekFrom::End(-(SEEK_TABLE_FOOTER_SIZE as i64)))?;
+        reader.read_exact(&mut footer)?;
+        if u32::from_le_bytes(footer[5..9].try_into().unwrap()) != ZSTD_SEEKABLE_MAGIC {
+            return Ok(None);
+        }
+
+        let num_frames = u32::from_le_bytes(footer[0..4].try_into().unwrap()) as u64;
+        let descriptor = footer[4];
+        if descriptor & RESERVED_DESCRIPTOR_BITS != 0 {
+            return Err(GcError::invalid_format(
+                FORMAT,
+                format!("reserved descriptor bits set in {:#04x}", descriptor),
+            ));
+        }
+        let entry_size = if descriptor & CHECKSUM_DESCRIPTOR_BIT != 0 {
+            SEEK_TABLE_ENTRY_SIZE + 4
+        } else {
+            SEEK_TABLE_ENTRY_SIZE
+        } as u64;
+        let payload_size = num_frames * entry_size + SEEK_TABLE_FOOTER_SIZE as u64;
+        if 8 + payload_size > data_size {
+            return Err(GcError::invalid_format(
+                FORMAT,
+                format!("{} frames do not fit in {} bytes", num_frames, data_size),
+            ));
+        }
+
+        reader.seek(SeekFrom::End(-((8 + payload_size) as i64)))?;
+        let mut table = vec![0u8; 8 + payload_size as usize - SEEK_TABLE_FOOTER_SIZE];
+        reader.read_exact(&mut table)?;
+        let read_u32 = |at: usize| u32::from_le_bytes(table[at..at + 4].try_into().unwrap());
+        if read_u32(0) != ZSTD_SEEK_TABLE_MAGIC || read_u32(4) as u64 != payload_size {
+            return Err(GcError::invalid_format(
+                FORMAT,
+                "footer does not follow a seek table frame",
+            ));
+        }
+
+        let mut seek_table = Self::default();
+        for entry in table[8..].chunks_exact(entry_size as usize) {
+            let compressed_size = u32::from_le_bytes(entry[0..4].try_into().unwrap());
+            let decompressed_size = u32::from_le_bytes(entry[4..8].try_into().unwrap());
+            if !(decompressed_size as usize).is_multiple_of(GARBLED_TABLE_SIZE) {
+                return Err(GcError::invalid_format(
+                    FORMAT,
+                    format!(
+                        "frame {} holds {} bytes, not whole tables",
+                        seek_table.frames.len(),
+                        decompressed_size
+                    ),
+                ));
+            }
+            seek_table.push_frame(
+                compressed_size as u64,
+                (decompressed_size as usize / GARBLED_TABLE_SIZE) as u64,
+            );
+        }
+        if seek_table.compressed_size + 8 + payload_size != data_size {
+            return Err(GcError::invalid_format(
+                FORMAT,
+                format!(
+                    "frames cover {} bytes of {} bytes of compressed tables",
+                    seek_table.compressed_size,
+                    data_size - 8 - payload_size
+                ),
+            ));
+        }
+        Ok(Some(seek_table))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::GARBLED_TABLES_HEADER_SIZE;
+    use crate::evaluator::{GarbledTableReader, TableAccess};
+    use crate::garbler::{GarbledTable, GarblingScheme, TablesHeader};
+    use crate::session::SessionId;
+    use crate::sink::{CompressedTableWriter, GarbledTableSink, tables_checksum};
+    use std::fs::File;
+
+    #[test]
+    fn test_seek_to_table() -> Result<()> {
+        let tables: Vec<GarbledTable> = (0..5u8)
+            .map(|i| GarbledTable {
+                ciphertexts: [[i; 16], [i + 1; 16], [i + 2; 16], [i + 3; 16]],
+            })
+            .collect();
+        let tables_file = tempfile::NamedTempFile::new()?;
+        let mut writer =
+            CompressedTableWriter::create(tables_file.path(), 3)?.with_flush_interval(2);
+        writer.write_tables(&tables)?;
+        writer.finish(&TablesHeader {
+            scheme: GarblingScheme::PointAndPermute,
+            num_tables: 5,
+            circuit_digest: [9; 32],
+            session_id: SessionId::new([7; 16]),
+            tables_checksum: Some(tables_checksum(&tables)),
+        })?;
+
+        let seek_table = SeekTable::read_from(
+            &mut File::open(tables_file.path())?,
+            GARBLED_TABLES_HEADER_SIZE as u64,
+        )?
+        .unwrap();
+        let first_tables: Vec<u64> = seek_table.frames().iter().map(|f| f.first_table).collect();
+        assert_eq!(first_tables, [0, 2, 4]);
+        assert_eq!(seek_table.frame_for_table(3), Some(&seek_table.frames()[1]));
+        assert_eq!(seek_table.frame_for_table(5), None);
+
+        // Skipping jumps to the frame holding the next table
+        let mut reader = GarbledTableReader::open(tables_file.path(), TableAccess::Buffered)?;
+        reader.next_table()?;
+        reader.skip_tables(2)?;
+        assert_eq!(reader.next_table()?.ciphertexts, tables[3].ciphertexts);
+        assert_eq!(reader.next_table()?.ciphertexts, tables[4].ciphertexts);
+
+        // Streaming decoders skip the seek table
+        let mut bytes = std::fs::read(tables_file.path())?;
+        let frames = bytes[GARBLED_TABLES_HEADER_SIZE..].to_vec();
+        let mut reader = GarbledTableReader::from_reader(std::io::Cursor::new(frames))?;
+        for table in &tables {
+            assert_eq!(reader.next_table()?.ciphertexts, table.ciphertexts);
+        }
+        let error_msg = reader.next_table().unwrap_err().to_string();
+        assert!(
+            error_msg.contains("need at least 6, have 5"),
+            "{}",
+            error_msg
+        );
+
+        // A header that disagrees with the frames is rejected
+        bytes[12] = 4;
+        std::fs::write(tables_file.path(), &bytes)?;
+        let error_msg = GarbledTableReader::open(tables_file.path(), TableAccess::Buffered)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("header promises 4 tables, its frames hold 5"));
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+
+/// Serialize a `HashMap` with its entries sorted by key
+///
+/// `HashMap` iteration order changes from run to run, so without sorting the
+/// same labels or results would be written as different JSON. Use with
+/// `#[serde(serialize_with = "serialize_sorted_map")]`; deserializing into
+/// the `HashMap` needs no counterpart.
+pub(crate) fn serialize_sorted_map<K, V, S>(
+    map: &HashMap<K, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    let mut entries: Vec<(&K, &V)> = map.iter().collect();
+    entries.sort_unstable_by_key(|&(key, _)| key);
+    serializer.collect_map(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+
+    #[derive(Serialize)]
+    struct Sorted {
+        #[serde(serialize_with = "serialize_sorted_map")]
+        map: HashMap<u64, bool>,
+    }
+
+    #[test]
+    fn test_serialize_sorted_map() -> Result<()> {
+        let map: HashMap<u64, bool> = (0..100).rev().map(|wire| (wire, wire % 3 == 0)).collect();
+        let json = serde_json::to_string(&Sorted { map })?;
+
+        let expected: Vec<String> = (0..100)
+            .map(|wire| format!("\"{}\":{}", wire, wire % 3 == 0))
+            .collect();
+        assert_eq!(json, format!("{{\"map\":{{{}}}}}", expected.join(",")));
+
+        Ok(())
+    }
+}
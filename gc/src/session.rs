@@ -0,0 +1,316 @@
+use crate::error::{GcError, Result};
+use crate::evaluator::{GarbledTableReader, TableAccess};
+use crate::gate::hash_circuit;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Domain separation tag for session ids derived from a garbling seed
+const SESSION_ID_DOMAIN: &[u8] = b"gc-session-id-v1";
+
+/// Format version of session manifests
+const MANIFEST_VERSION: u32 = 2;
+
+/// Identifier of a single garbling run
+///
+/// Set when a circuit is garbled and stored in the garbled tables header,
+/// the wire labels and every OT result or encoded input derived from them.
+/// The evaluator refuses to combine artifacts whose session ids differ, so
+/// tables from one run can't silently be evaluated with labels from another.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct SessionId([u8; 16]);
+
+impl SessionId {
+    /// Create a session id from raw bytes
+    pub fn new(bytes: [u8; 16]) -> Self {
+        SessionId(bytes)
+    }
+
+    /// Derive the session id of a garbling run from its seed
+    ///
+    /// Garbling is deterministic in the seed, so runs with the same seed
+    /// produce interchangeable artifacts and share a session id.
+    pub fn from_seed(seed: &[u8; 32]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(SESSION_ID_DOMAIN);
+        hasher.update(seed);
+        let hash = hasher.finalize();
+        SessionId(hash[..16].try_into().unwrap())
+    }
+
+    /// Get the raw bytes of this session id
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// Fail unless `artifact` (e.g. "OT result") belongs to the `expected` session
+    pub fn check(&self, expected: &SessionId, artifact: &str) -> Result<()> {
+        if self != expected {
+            return Err(GcError::SessionMismatch {
+                artifact: artifact.to_string(),
+                found: *self,
+                expected: *expected,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Kind of file recorded in a [`SessionManifest`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Artifact {
+    /// The circuit, in Bristol or binary form, identified by its fingerprint
+    Circuit,
+    /// Binary wire analysis of the circuit
+    WireAnalysis,
+    /// Garbled tables, identified by their header: table count, circuit digest and session id
+    TablesHeader,
+    /// Wire labels; the digest commits the garbler to them
+    Labels,
+    /// Recorded OT transcript
+    OtTranscript,
+    /// Evaluation results with the output labels
+    Outputs,
+}
+
+impl Artifact {
+    /// Digest of the artifact stored at `path`
+    ///
+    /// The BLAKE3 digest of the whole file, except for two artifacts. A
+    /// circuit is identified by its canonical fingerprint (see
+    /// [`hash_circuit`]), the digest its garbled tables record, so the same
+    /// circuit in Bristol or binary form matches one entry. For garbled tables
+    /// only the header is hashed: it names the circuit and the garbling run,
+    /// so it tells tables apart without reading gigabytes, while the tables
+    /// themselves are covered by chunk checksums in transit.
+    ///
+    /// # Returns
+    /// * `Ok([u8; 32])` - The digest
+    /// * `Err(GcError)` - The file cannot be read, the circuit is malformed, or garbled tables have no header
+    pub fn digest<P: AsRef<Path>>(self, path: P) -> Result<[u8; 32]> {
+        let digest = match self {
+            Artifact::Circuit => return hash_circuit(File::open(path)?),
+            Artifact::TablesHeader => {
+                let reader = GarbledTableReader::open(&path, TableAccess::Buffered)?;
+                let header = reader.header().ok_or_else(|| {
+                    GcError::invalid_format("garbled tables file", "no header to identify it by")
+                })?;
+                blake3::hash(&header.to_bytes())
+            }
+            _ => blake3::Hasher::new()
+                .update_reader(File::open(path)?)?
+                .finalize(),
+        };
+        Ok(*digest.as_bytes())
+    }
+
+    /// Human-readable name, e.g. "wire analysis"
+    pub fn description(self) -> &'static str {
+        match self {
+            Artifact::Circuit => "circuit",
+            Artifact::WireAnalysis => "wire analysis",
+            Artifact::TablesHeader => "garbled tables header",
+            Artifact::Labels => "wire labels",
+            Artifact::OtTranscript => "OT transcript",
+            Artifact::Outputs => "evaluation outputs",
+        }
+    }
+}
+
+impl fmt::Display for Artifact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+/// Digest of one artifact in a [`SessionManifest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactDigest {
+    /// File the digest was taken of, for the reader's information
+    pub path: PathBuf,
+    /// Lowercase hex digest, see [`Artifact::digest`]
+    pub digest: String,
+}
+
+/// Digests of the files making up one session
+///
+/// Usually saved as `manifest.json` next to the artifacts. Each command that reads
+/// an artifact checks it against the recorded digest before running, so a
+/// circuit, wire analysis, tables file, labels file, transcript or result
+/// from another run is refused up front instead of failing halfway or,
+/// worse, producing wrong outputs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionManifest {
+    /// Format version
+    pub version: u32,
+    /// Recorded digests by artifact
+    pub artifacts: BTreeMap<Artifact, ArtifactDigest>,
+}
+
+impl SessionManifest {
+    /// Create an empty manifest
+    pub fn new() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            artifacts: BTreeMap::new(),
+        }
+    }
+
+    /// Load a manifest from a JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let manifest: Self = serde_json::from_slice(&std::fs::read(path)?)?;
+        if manifest.version != MANIFEST_VERSION {
+            return Err(GcError::FormatVersionMismatch {
+                format: "session manifest",
+                found: manifest.version,
+                expected: MANIFEST_VERSION,
+            });
+        }
+        Ok(manifest)
+    }
+
+    /// Load a manifest, or start an empty one if `path` does not exist yet
+    pub fn load_or_new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if path.as_ref().exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::new())
+        }
+    }
+
+    /// Save the manifest as JSON
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Record the digest of `artifact` stored at `path`, replacing any earlier one
+    pub fn record<P: AsRef<Path>>(&mut self, artifact: Artifact, path: P) -> Result<()> {
+        let digest = hex(&artifact.digest(&path)?);
+        self.artifacts.insert(
+            artifact,
+            ArtifactDigest {
+                path: path.as_ref().to_path_buf(),
+                digest,
+            },
+        );
+        Ok(())
+    }
+
+    /// Check the file at `path` against the recorded digest of `artifact`
+    ///
+    /// # Returns
+    /// * `Ok(true)` - The file matches
+    /// * `Ok(false)` - The manifest records no such artifact, so there is nothing to check
+    /// * `Err(GcError::ManifestMismatch)` - The file differs from the recorded one
+    pub fn verify<P: AsRef<Path>>(&self, artifact: Artifact, path: P) -> Result<bool> {
+        let Some(recorded) = self.artifacts.get(&artifact) else {
+            return Ok(false);
+        };
+        if hex(&artifact.digest(&path)?) != recorded.digest.to_ascii_lowercase() {
+            return Err(GcError::ManifestMismatch {
+                artifact: artifact.description(),
+                path: path.as_ref().display().to_string(),
+                recorded: recorded.path.display().to_string(),
+            });
+        }
+        Ok(true)
+    }
+}
+
+impl Default for SessionManifest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lowercase hex encoding of a digest
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(2 * bytes.len());
+    for byte in bytes {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_id_from_seed() {
+        let session = SessionId::from_seed(&[1u8; 32]);
+        assert_eq!(session, SessionId::from_seed(&[1u8; 32]));
+        assert_ne!(session, SessionId::from_seed(&[2u8; 32]));
+        assert_eq!(session.to_string().len(), 32);
+
+        assert!(session.check(&session, "OT result").is_ok());
+        let error_msg = session
+            .check(&SessionId::default(), "OT result")
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Session id mismatch: OT result belongs to session"));
+        assert!(error_msg.contains("expected session 00000000000000000000000000000000"));
+    }
+
+    #[test]
+    fn test_manifest_detects_swapped_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let circuit = dir.path().join("adder.bristol");
+        let other = dir.path().join("other.bristol");
+        std::fs::write(&circuit, "1 3\n2 1 0 1 2 AND\n")?;
+        std::fs::write(&other, "1 3\n2 1 0 1 2 XOR\n")?;
+
+        let mut manifest = SessionManifest::new();
+        assert!(!manifest.verify(Artifact::Circuit, &circuit)?);
+        manifest.record(Artifact::Circuit, &circuit)?;
+
+        let path = dir.path().join("manifest.json");
+        manifest.save(&path)?;
+        let manifest = SessionManifest::load_or_new(&path)?;
+        assert!(manifest.verify(Artifact::Circuit, &circuit)?);
+        let error_msg = manifest
+            .verify(Artifact::Circuit, &other)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("does not match the circuit recorded in the session manifest"));
+
+        // Reformatting the circuit keeps its fingerprint
+        std::fs::write(&other, "1 3\n\n2 1  0 1 2 AND\n")?;
+        assert!(manifest.verify(Artifact::Circuit, &other)?);
+
+        let json = std::fs::read_to_string(&path)?;
+        assert!(json.contains("\"circuit\""));
+        assert_eq!(
+            manifest.artifacts[&Artifact::Circuit].digest,
+            hex(&Artifact::Circuit.digest(&circuit)?)
+        );
+        Ok(())
+    }
+}
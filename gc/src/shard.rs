@@ -0,0 +1,531 @@
+use crate::error::{GcError, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+
+use crate::constants::SHARD_MANIFEST_VERSION;
+use crate::evaluator::{GarbledTableReader, TableAccess};
+use crate::garbler::{GarbledTable, GarblingScheme, TablesHeader};
+use crate::session::SessionId;
+use crate::sink::{
+    CompressedTableWriter, GarbledTableSink, TableWriter, patch_header, placeholder_header,
+};
+
+/// One shard of a sharded garbled tables set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardInfo {
+    /// Shard file, relative to the manifest's directory unless absolute
+    pub path: PathBuf,
+    /// Index of the shard's first table among all AND gates
+    pub first_table: u64,
+    /// Number of tables in the shard
+    pub num_tables: u64,
+}
+
+/// Manifest mapping shard files to ranges of AND-gate tables
+///
+/// Shard `i` holds the tables of AND gates `first_table..first_table +
+/// num_tables`; the ranges are contiguous and in garbling order. Every shard
+/// is a complete garbled tables file with its own [`TablesHeader`], so shards
+/// can live on different disks and be streamed or checked independently.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardManifest {
+    /// Manifest format version
+    pub version: u32,
+    /// Identifier of the [`GarblingScheme`] of all shards
+    pub scheme_id: u32,
+    /// Total number of tables across all shards
+    pub num_tables: u64,
+    /// Digest of the circuit's gates
+    pub circuit_digest: [u8; 32],
+    /// Garbling run that produced the tables
+    pub session_id: SessionId,
+    /// Shards in table order
+    pub shards: Vec<ShardInfo>,
+}
+
+impl ShardManifest {
+    /// Save the manifest as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Load a manifest from JSON, checking its version and shard ranges
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let manifest: Self = serde_json::from_str(&data)?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// Header describing the whole table set
+    pub fn header(&self) -> Result<TablesHeader> {
+        let scheme = GarblingScheme::from_id(self.scheme_id).ok_or_else(|| {
+            GcError::invalid_format(
+                "shard manifest",
+                format!("unsupported garbling scheme id {}", self.scheme_id),
+            )
+        })?;
+        Ok(TablesHeader {
+            scheme,
+            num_tables: self.num_tables,
+            circuit_digest: self.circuit_digest,
+            session_id: self.session_id,
+            // Each shard's header holds the checksum of its own tables
+            tables_checksum: None,
+        })
+    }
+
+    /// Location of shard `index` for a manifest stored at `manifest_path`
+    pub fn shard_path(&self, manifest_path: &Path, index: usize) -> PathBuf {
+        let path = &self.shards[index].path;
+        match manifest_path.parent() {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.clone(),
+        }
+    }
+
+    /// Index of the shard holding table `table_index`
+    pub fn shard_of_table(&self, table_index: u64) -> Option<usize> {
+        self.shards.iter().position(|shard| {
+            (shard.first_table..shard.first_table + shard.num_tables).contains(&table_index)
+        })
+    }
+
+    /// Check the expected header of shard `index` against the one on disk
+    pub fn check_shard_header(&self, index: usize, header: Option<&TablesHeader>) -> Result<()> {
+        let shard = &self.shards[index];
+        let Some(header) = header else {
+            return Err(GcError::invalid_format(
+                "garbled tables shard",
+                format!(
+                    "shard {} ({}) has no garbled tables header",
+                    index,
+                    shard.path.display()
+                ),
+            ));
+        };
+        if header.num_tables != shard.num_tables {
+            return Err(GcError::TableCountMismatch {
+                expected: shard.num_tables,
+                found: header.num_tables,
+                message: format!(
+                    "Shard {} ({}) holds {} tables, manifest expects {}",
+                    index,
+                    shard.path.display(),
+                    header.num_tables,
+                    shard.num_tables
+                ),
+            });
+        }
+        if header.scheme.id() != self.scheme_id
+            || header.circuit_digest != self.circuit_digest
+            || header.session_id != self.session_id
+        {
+            return Err(GcError::invalid_format(
+                "garbled tables shard",
+                format!(
+                    "shard {} ({}) does not belong to this manifest",
+                    index,
+                    shard.path.display()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.version != SHARD_MANIFEST_VERSION {
+            return Err(GcError::FormatVersionMismatch {
+                format: "shard manifest",
+                found: self.version,
+                expected: SHARD_MANIFEST_VERSION,
+            });
+        }
+        let mut next_table = 0;
+        for (index, shard) in self.shards.iter().enumerate() {
+            if shard.first_table != next_table {
+                return Err(GcError::invalid_format(
+                    "shard manifest",
+                    format!(
+                        "shard {} starts at table {}, expected {}",
+                        index, shard.first_table, next_table
+                    ),
+                ));
+            }
+            next_table += shard.num_tables;
+        }
+        if next_table != self.num_tables {
+            return Err(GcError::TableCountMismatch {
+                expected: self.num_tables,
+                found: next_table,
+                message: format!(
+                    "Shards hold {} tables, manifest expects {}",
+                    next_table, self.num_tables
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Splits garbled tables into shard files of a fixed number of tables
+///
+/// Shard `i` of a manifest `dv.garbled.json` is written to `dv.garbled.0000`,
+/// `dv.garbled.0001` and so on, next to the manifest or spread round-robin
+/// over the directories given with [`with_shard_dirs`](Self::with_shard_dirs).
+/// Each shard gets its own [`TablesHeader`]; the manifest is written by
+/// [`finish`](GarbledTableSink::finish).
+pub struct ShardedTableWriter {
+    manifest_path: PathBuf,
+    tables_per_shard: u64,
+    /// zstd level for compressed shards
+    compression_level: Option<i32>,
+    /// Directories shards are spread over; empty for the manifest's directory
+    shard_dirs: Vec<PathBuf>,
+    /// Shards written so far, including the open one
+    shards: Vec<ShardInfo>,
+    /// Full paths of the shards in `shards`
+    shard_paths: Vec<PathBuf>,
+    /// Shard currently being written
+    current: Option<Box<dyn GarbledTableSink>>,
+    /// Checksum of the tables of the open shard so far
+    shard_hasher: blake3::Hasher,
+    /// Table checksums of the closed shards
+    shard_checksums: Vec<[u8; 32]>,
+    tables_written: u64,
+}
+
+impl std::fmt::Debug for ShardedTableWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShardedTableWriter")
+            .field("manifest_path", &self.manifest_path)
+            .field("tables_per_shard", &self.tables_per_shard)
+            .field("shards", &self.shards.len())
+            .field("tables_written", &self.tables_written)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ShardedTableWriter {
+    /// Write shards of `tables_per_shard` tables described by the manifest at
+    /// `manifest_path`
+    pub fn create<P: AsRef<Path>>(manifest_path: P, tables_per_shard: u64) -> Self {
+        Self {
+            manifest_path: manifest_path.as_ref().to_path_buf(),
+            tables_per_shard: tables_per_shard.max(1),
+            compression_level: None,
+            shard_dirs: Vec::new(),
+            shards: Vec::new(),
+            shard_paths: Vec::new(),
+            current: None,
+            shard_hasher: blake3::Hasher::new(),
+            shard_checksums: Vec::new(),
+            tables_written: 0,
+        }
+    }
+
+    /// Compress every shard with zstd at `level`
+    pub fn compressed(mut self, level: i32) -> Self {
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// Spread shards round-robin over `dirs`, e.g. one directory per disk
+    pub fn with_shard_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.shard_dirs = dirs;
+        self
+    }
+
+    /// Number of shards started so far
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Start the next shard file
+    fn open_shard(&mut self) -> Result<()> {
+        let index = self.shards.len();
+        let base_name = self
+            .manifest_path
+            .with_extension("")
+            .file_name()
+            .ok_or_else(|| {
+                GcError::InvalidArgument(format!(
+                    "Invalid manifest path: {}",
+                    self.manifest_path.display()
+                ))
+            })?
+            .to_os_string();
+        let mut file_name = base_name;
+        file_name.push(format!(".{index:04}"));
+
+        let (full_path, manifest_entry) = if self.shard_dirs.is_empty() {
+            let dir = self.manifest_path.parent().unwrap_or(Path::new(""));
+            (dir.join(&file_name), PathBuf::from(&file_name))
+        } else {
+            let dir = &self.shard_dirs[index % self.shard_dirs.len()];
+            let path = dir.join(&file_name);
+            let entry = std::path::absolute(&path)?;
+            (path, entry)
+        };
+
+        self.current = Some(match self.compression_level {
+            Some(level) => Box::new(CompressedTableWriter::create(&full_path, level)?),
+            None => Box::new(TableWriter::create(&full_path)?),
+        });
+        self.shards.push(ShardInfo {
+            path: manifest_entry,
+            first_table: self.tables_written,
+            num_tables: 0,
+        });
+        self.shard_paths.push(full_path);
+        Ok(())
+    }
+
+    /// Finish the open shard with a header claiming its table count and checksum
+    fn close_shard(&mut self, header: &TablesHeader) -> Result<()> {
+        if let (Some(mut current), Some(shard)) = (self.current.take(), self.shards.last()) {
+            let checksum = *std::mem::take(&mut self.shard_hasher).finalize().as_bytes();
+            self.shard_checksums.push(checksum);
+            current.finish(&TablesHeader {
+                num_tables: shard.num_tables,
+                tables_checksum: Some(checksum),
+                ..*header
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl GarbledTableSink for ShardedTableWriter {
+    fn write_tables(&mut self, mut tables: &[GarbledTable]) -> Result<()> {
+        while !tables.is_empty() {
+            if self.current.is_none() {
+                self.open_shard()?;
+            }
+            let shard = self.shards.last_mut().expect("a shard is open");
+            let room = (self.tables_per_shard - shard.num_tables) as usize;
+            let (batch, rest) = tables.split_at(room.min(tables.len()));
+
+            self.current
+                .as_mut()
+                .expect("a shard is open")
+                .write_tables(batch)?;
+            for table in batch {
+                self.shard_hasher.update(&table.as_binary());
+            }
+            shard.num_tables += batch.len() as u64;
+            self.tables_written += batch.len() as u64;
+            tables = rest;
+
+            if shard.num_tables == self.tables_per_shard {
+                // The digest is unknown until the end; headers are rewritten
+                // once the garbler finishes
+                self.close_shard(&placeholder_header())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, header: &TablesHeader) -> Result<()> {
+        self.close_shard(header)?;
+
+        // Earlier shards were closed before the circuit digest was known
+        for ((shard, path), checksum) in self
+            .shards
+            .iter()
+            .zip(&self.shard_paths)
+            .zip(&self.shard_checksums)
+        {
+            let mut file = OpenOptions::new().write(true).open(path)?;
+            patch_header(
+                &mut file,
+                &TablesHeader {
+                    num_tables: shard.num_tables,
+                    tables_checksum: Some(*checksum),
+                    ..*header
+                },
+            )?;
+            file.sync_all()?;
+        }
+
+        let manifest = ShardManifest {
+            version: SHARD_MANIFEST_VERSION,
+            scheme_id: header.scheme.id(),
+            num_tables: self.tables_written,
+            circuit_digest: header.circuit_digest,
+            session_id: header.session_id,
+            shards: self.shards.clone(),
+        };
+        manifest.save_json(&self.manifest_path)
+    }
+}
+
+/// Whether `path` names a shard manifest rather than a tables file
+pub fn is_manifest_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "json")
+}
+
+/// Garbled tables read shard after shard, in table order
+///
+/// Each shard's header is checked against the manifest when the shard is
+/// opened, so a missing, swapped or truncated shard is reported by name.
+#[derive(Debug)]
+pub(crate) struct ShardedTables {
+    manifest: ShardManifest,
+    manifest_path: PathBuf,
+    access: TableAccess,
+    /// Index of the shard `current` reads
+    shard_index: usize,
+    current: Option<GarbledTableReader>,
+}
+
+impl ShardedTables {
+    pub(crate) fn open(manifest_path: &Path, access: TableAccess) -> Result<(Self, TablesHeader)> {
+        let manifest = ShardManifest::load_json(manifest_path)?;
+        let header = manifest.header()?;
+        Ok((
+            Self {
+                manifest,
+                manifest_path: manifest_path.to_path_buf(),
+                access,
+                shard_index: 0,
+                current: None,
+            },
+            header,
+        ))
+    }
+
+    /// Next table, moving on to the next shard once the current one is used up
+    pub(crate) fn next_table(&mut self) -> Result<Option<GarbledTable>> {
+        loop {
+            if let Some(current) = &mut self.current {
+                if current.remaining_tables() != Some(0) {
+                    return current.next_table().map(Some);
+                }
+                self.shard_index += 1;
+            }
+            if self.shard_index >= self.manifest.shards.len() {
+                return Ok(None);
+            }
+            let path = self
+                .manifest
+                .shard_path(&self.manifest_path, self.shard_index);
+            let reader = GarbledTableReader::open(&path, self.access).map_err(|e| match e {
+                GcError::Io(e) => GcError::Io(std::io::Error::new(
+                    e.kind(),
+                    format!("Failed to open shard {}: {}", path.display(), e),
+                )),
+                e => e,
+            })?;
+            self.manifest
+                .check_shard_header(self.shard_index, reader.header())?;
+            self.current = Some(reader);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::evaluate_circuit;
+    use crate::garbler::GarblerBuilder;
+    use crate::garbler::garble_circuit;
+    use crate::ot_simulation::simulate_ot;
+    use crate::progress::NoProgress;
+    use crate::stream::BufferedLineStream;
+    use crate::wire_analyzer::analyze_wire_usage;
+    use std::fs::File;
+
+    #[test]
+    fn test_sharded_tables_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let circuit_path = dir.path().join("circuit.bristol");
+        std::fs::write(
+            &circuit_path,
+            "4 9\n2 1 0 1 2 AND\n2 1 2 3 4 AND\n2 1 4 5 6 XOR\n2 1 6 7 8 AND\n",
+        )?;
+        let open_stream = || -> Result<BufferedLineStream> {
+            Ok(BufferedLineStream::new(File::open(&circuit_path)?))
+        };
+        let wire_report = analyze_wire_usage(&mut open_stream()?, &NoProgress)?;
+        let expected = garble_circuit(&mut open_stream()?, &wire_report, &[1; 32])?;
+
+        let manifest_path = dir.path().join("circuit.garbled.json");
+        let mut writer = ShardedTableWriter::create(&manifest_path, 2);
+        let garbling = GarblerBuilder::new([1; 32])
+            .sink(&mut writer)
+            .garble(&mut open_stream()?, &wire_report)?;
+        assert_eq!(writer.num_shards(), 2);
+
+        let manifest = ShardManifest::load_json(&manifest_path)?;
+        assert_eq!(manifest.num_tables, 3);
+        assert_eq!(manifest.circuit_digest, garbling.circuit_digest);
+        assert_eq!(
+            manifest.shards,
+            vec![
+                ShardInfo {
+                    path: PathBuf::from("circuit.garbled.0000"),
+                    first_table: 0,
+                    num_tables: 2
+                },
+                ShardInfo {
+                    path: PathBuf::from("circuit.garbled.0001"),
+                    first_table: 2,
+                    num_tables: 1
+                },
+            ]
+        );
+        assert_eq!(manifest.shard_of_table(1), Some(0));
+        assert_eq!(manifest.shard_of_table(2), Some(1));
+        assert_eq!(manifest.shard_of_table(3), None);
+
+        // Every shard is a standalone tables file with the circuit digest
+        let shard = GarbledTableReader::open(
+            manifest.shard_path(&manifest_path, 1),
+            TableAccess::Buffered,
+        )?;
+        manifest.check_shard_header(1, shard.header())?;
+
+        // Reading through the manifest yields the tables in garbling order
+        let mut reader = GarbledTableReader::open(&manifest_path, TableAccess::Mmap)?;
+        assert_eq!(reader.remaining_tables(), Some(3));
+        for expected_table in &expected.garbled_tables {
+            assert_eq!(reader.next_table()?.ciphertexts, expected_table.ciphertexts);
+        }
+        assert!(reader.next_table().is_err());
+
+        let ot_result = simulate_ot(&garbling.wire_labels, &[2; 32], &NoProgress)?;
+        let result = evaluate_circuit(
+            &mut open_stream()?,
+            &wire_report,
+            &ot_result,
+            &manifest_path,
+        )?;
+        let output = &result.output_results[&8];
+        let output_labels = garbling.wire_labels.get_wire_labels(8).unwrap();
+        assert!(output_labels.contains(&output.label));
+
+        // A shard swapped for another one is reported by name
+        std::fs::copy(
+            manifest.shard_path(&manifest_path, 0),
+            manifest.shard_path(&manifest_path, 1),
+        )?;
+        let error_msg = evaluate_circuit(
+            &mut open_stream()?,
+            &wire_report,
+            &ot_result,
+            &manifest_path,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            error_msg.contains("Shard 1 (circuit.garbled.0001) holds 2 tables, manifest expects 1")
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use crate::error::Result;
+use std::collections::HashMap;
+
+use crate::decoder::{DecodingInfo, decode_outputs};
+use crate::evaluator::{EvaluationOptions, EvaluationResult, evaluate_circuit_in_memory};
+use crate::garbler::{GarblingResult, garble_circuit_in_memory};
+use crate::gate::{Circuit, WireId};
+use crate::ot_simulation::{OTResult, select_inputs};
+use crate::plain::{PlainResult, evaluate_plain};
+use crate::wire_analyzer::{WireUsageReport, analyze_circuit};
+
+/// Everything produced by an end-to-end run of the protocol
+#[derive(Debug)]
+pub struct EndToEndResult {
+    /// Wire usage analysis of the circuit
+    pub wire_report: WireUsageReport,
+    /// Garbler output: wire labels and garbled tables
+    pub garbling: GarblingResult,
+    /// Input labels selected for the given inputs
+    pub ot_result: OTResult,
+    /// Garbled evaluation result, decoded with the garbler's output labels
+    pub evaluation: EvaluationResult,
+    /// Output bits from evaluating the circuit in the clear
+    pub plain: PlainResult,
+}
+
+impl EndToEndResult {
+    /// Output wires where the garbled and plain evaluations disagree
+    ///
+    /// Empty when the garbling is correct. Returned in ascending wire order.
+    pub fn mismatched_outputs(&self) -> Vec<WireId> {
+        self.plain.mismatched_outputs(&self.evaluation)
+    }
+}
+
+/// Run wire analysis, garbling, OT selection, evaluation and decoding entirely in memory
+///
+/// Useful for tests and quick experiments: no intermediate files are written
+/// and the plain evaluation is returned alongside the garbled one, so a
+/// caller can check the garbling with [`EndToEndResult::mismatched_outputs`].
+///
+/// # Arguments
+/// * `circuit` - The circuit to run
+/// * `inputs` - Bit value for every primary input wire: wire_id -> bit
+/// * `seed_data` - 32 bytes of random seed for the garbler's CSPRNG
+///
+/// # Returns
+/// * `Ok(EndToEndResult)` - Results of every stage
+/// * `Err(GcError)` - Invalid circuit or missing input bits
+pub fn run_end_to_end(
+    circuit: &Circuit,
+    inputs: &HashMap<WireId, bool>,
+    seed_data: &[u8; 32],
+) -> Result<EndToEndResult> {
+    let wire_report = analyze_circuit(circuit)?;
+    let plain = PlainResult {
+        output_bits: evaluate_plain(circuit, &wire_report, inputs)?
+            .into_iter()
+            .collect(),
+    };
+
+    let garbling = garble_circuit_in_memory(&circuit.gates, &wire_report, seed_data)?;
+    let ot_result = select_inputs(&garbling.wire_labels, inputs)?;
+    let evaluation = evaluate_circuit_in_memory(
+        &circuit.gates,
+        &wire_report,
+        &ot_result,
+        &garbling.garbled_tables,
+        &EvaluationOptions::default(),
+    )?;
+    let evaluation = decode_outputs(
+        &evaluation,
+        &DecodingInfo::from_wire_labels(&garbling.wire_labels),
+    )?;
+
+    Ok(EndToEndResult {
+        wire_report,
+        garbling,
+        ot_result,
+        evaluation,
+        plain,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_run_end_to_end_matches_plain() -> Result<()> {
+        // out = (a AND b) XOR (c AND d)
+        let circuit = Circuit::parse("3 7\n2 1 0 1 4 AND\n2 1 2 3 5 AND\n2 1 4 5 6 XOR\n")?;
+
+        for pattern in 0u32..16 {
+            let inputs: HashMap<WireId, bool> = (0..4)
+                .map(|wire| (wire, (pattern >> wire) & 1 == 1))
+                .collect();
+            let result = run_end_to_end(&circuit, &inputs, &[7; 32])?;
+
+            let expected = (inputs[&0] & inputs[&1]) ^ (inputs[&2] & inputs[&3]);
+            assert_eq!(result.plain.output_bits, BTreeMap::from([(6, expected)]));
+            assert!(result.mismatched_outputs().is_empty());
+            assert_eq!(result.garbling.garbled_tables.len(), 2);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_end_to_end_copy_and_constant_gates() -> Result<()> {
+        // out_8 = copy((copy(a) AND 1) AND (b XOR 0)), out_9 = 1
+        let circuit = Circuit::parse(
+            "8 10\n1 1 0 2 EQW\n1 1 1 3 EQ\n1 1 0 4 EQ\n2 1 2 3 5 AND\n\
+             2 1 1 4 6 XOR\n2 1 5 6 7 AND\n1 1 7 8 BUF\n1 1 1 9 EQ\n",
+        )?;
+
+        for pattern in 0u32..4 {
+            let inputs: HashMap<WireId, bool> = (0..2)
+                .map(|wire| (wire, (pattern >> wire) & 1 == 1))
+                .collect();
+            let result = run_end_to_end(&circuit, &inputs, &[7; 32])?;
+
+            assert_eq!(result.wire_report.primary_input_wires, vec![0, 1]);
+            assert_eq!(
+                result.plain.output_bits,
+                BTreeMap::from([(8, inputs[&0] & inputs[&1]), (9, true)])
+            );
+            assert!(result.mismatched_outputs().is_empty());
+            assert_eq!(result.garbling.garbled_tables.len(), 2);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_end_to_end_bristol_fashion() -> Result<()> {
+        // Outputs w3 = a AND b and w4 = w3 XOR a; w3 is also read by a gate,
+        // so only the declarations make it an output
+        let circuit = Circuit::parse("2 5\n2 1 1\n1 2\n\n2 1 0 1 3 AND\n2 1 3 0 4 XOR\n")?;
+
+        for pattern in 0u32..4 {
+            let inputs: HashMap<WireId, bool> = (0..2)
+                .map(|wire| (wire, (pattern >> wire) & 1 == 1))
+                .collect();
+            let result = run_end_to_end(&circuit, &inputs, &[7; 32])?;
+
+            let and = inputs[&0] & inputs[&1];
+            assert_eq!(result.wire_report.primary_output_wires, vec![3, 4]);
+            assert_eq!(
+                result.plain.output_bits,
+                BTreeMap::from([(3, and), (4, and ^ inputs[&0])])
+            );
+            assert!(result.mismatched_outputs().is_empty());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_end_to_end_missing_input() -> Result<()> {
+        let circuit = Circuit::parse("1 3\n2 1 0 1 2 AND\n")?;
+        let error_msg = run_end_to_end(&circuit, &HashMap::from([(0, true)]), &[0; 32])
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("No input bit given for input wire 1"));
+
+        Ok(())
+    }
+}
@@ -1,8 +1,10 @@
-use anyhow::{Result, bail};
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::error::Result;
 use serde::{Deserialize, Serialize};
+use std::io::Read;
 
 use crate::constants::PROGRESS_UPDATE_INTERVAL;
+use crate::parser::{WireRef, read_header, visit_gate_wires};
+use crate::progress::ProgressObserver;
 use crate::stream::BufferedLineStream;
 use crate::wire_analyzer::WireUsageReport;
 
@@ -30,6 +32,7 @@ pub struct SingleUseGateAnalysis {
 /// # Arguments
 /// * `stream` - Buffered stream of Bristol circuit format
 /// * `wire_report` - Wire usage analysis results
+/// * `progress` - Receives progress updates
 ///
 /// # Returns
 /// * `SingleUseGateAnalysis` with counts of AND/XOR gates producing single-use wires
@@ -39,119 +42,40 @@ pub struct SingleUseGateAnalysis {
 /// 2. For each gate, check if output wires have usage count = 1
 /// 3. Count gate types (AND/XOR) that produce single-use wires
 /// 4. Return analysis with detailed counts
-pub fn analyze_single_use_gates(
-    stream: &mut BufferedLineStream,
+#[tracing::instrument(name = "single_use_analysis", skip_all)]
+pub fn analyze_single_use_gates<R: Read>(
+    stream: &mut BufferedLineStream<R>,
     wire_report: &WireUsageReport,
+    progress: &dyn ProgressObserver,
 ) -> Result<SingleUseGateAnalysis> {
-    // Parse and validate header line (matches garbler.rs pattern)
-    let header_line = stream
-        .next_line()
-        .ok_or_else(|| anyhow::anyhow!("Missing header line"))??;
-
-    let header_tokens: Vec<&str> = header_line.split_whitespace().collect();
-    if header_tokens.len() != 2 {
-        bail!(
-            "Invalid header: expected '<num_gates> <num_wires>', got: '{}'",
-            header_line
-        );
-    }
-
-    // Parse header values - use num_gates for progress bar
-    let num_gates: u32 = header_tokens[0]
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid num_gates: '{}'", header_tokens[0]))?;
-    let _num_wires: u32 = header_tokens[1]
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid num_wires: '{}'", header_tokens[1]))?;
+    let num_gates = read_header(stream)?.num_gates;
 
     let mut single_use_and_gates = 0;
     let mut single_use_xor_gates = 0;
     let mut total_single_use_wires = 0;
-    let mut line_number: u32 = 0;
+    let mut line_number: u64 = 0;
 
     // Create progress bar for gate processing (use actual count from header)
-    let pb = ProgressBar::new(num_gates as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
-            .unwrap()
-            .progress_chars("##-"),
-    );
-    pb.set_message("Analyzing single-use gate types...");
+    progress.start(Some(num_gates), "Analyzing single-use gate types...");
 
     // Process each gate as we read it (streaming approach - matches garbler.rs)
     while let Some(line_result) = stream.next_line() {
         line_number += 1;
         let line = line_result?;
 
-        if line.trim().is_empty() {
-            bail!("Empty line at line number {}", line_number);
-        }
-
-        // Parse gate line directly using iterator (more efficient - matches garbler.rs)
-        let mut tokens = line.split_whitespace();
-
-        // Parse num_inputs and num_outputs
-        let num_inputs: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing num_inputs at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid num_inputs at line {}: '{}'", line_number, line)
-            })?;
-
-        let num_outputs: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing num_outputs at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid num_outputs at line {}: '{}'", line_number, line)
-            })?;
-
-        // Skip input wire IDs (we don't need them for this analysis)
-        for i in 0..num_inputs {
-            let _input_wire: u32 = tokens
-                .next()
-                .ok_or_else(|| anyhow::anyhow!("Missing input wire {} at line {}", i, line_number))?
-                .parse()
-                .map_err(|_| {
-                    anyhow::anyhow!("Invalid input wire ID at line {}: '{}'", line_number, line)
-                })?;
-        }
-
-        // Process output wires directly (no intermediate allocation)
+        // Only output wires matter for this analysis
         let mut gate_has_single_use_output = false;
-        for i in 0..num_outputs {
-            let output_wire: u32 = tokens
-                .next()
-                .ok_or_else(|| {
-                    anyhow::anyhow!("Missing output wire {} at line {}", i, line_number)
-                })?
-                .parse()
-                .map_err(|_| {
-                    anyhow::anyhow!("Invalid output wire ID at line {}: '{}'", line_number, line)
-                })?;
-
-            // Add bounds checking for array access
-            if (output_wire as usize) < wire_report.wire_usage_counts.len() {
-                // If this output wire has usage count = 1
-                if wire_report.wire_usage_counts[output_wire as usize] == 1 {
-                    total_single_use_wires += 1;
-                    gate_has_single_use_output = true;
-                }
+        let gate_type = visit_gate_wires(line, line_number, |wire| {
+            // If this output wire has usage count = 1
+            if let WireRef::Output(output_wire) = wire
+                && wire_report.wire_usage_counts.get(output_wire) == 1
+            {
+                total_single_use_wires += 1;
+                gate_has_single_use_output = true;
             }
-        }
-
-        // Parse gate type (last token) - only if gate has single-use outputs
-        let gate_type = tokens.next().ok_or_else(|| {
-            anyhow::anyhow!("Missing gate type at line {}: '{}'", line_number, line)
+            Ok(())
         })?;
 
-        // Validate no extra tokens
-        if tokens.next().is_some() {
-            bail!("Too many tokens at line {}: '{}'", line_number, line);
-        }
-
         // Count gate types only if they produce single-use wires
         if gate_has_single_use_output {
             match gate_type {
@@ -163,18 +87,16 @@ pub fn analyze_single_use_gates(
             }
         }
 
-        let gate_index: u32 = line_number - 1;
+        let gate_index: u64 = line_number - 1;
 
         // Update progress bar periodically for better performance
         if gate_index.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
-            pb.set_position(gate_index as u64);
-            // Avoid string allocation - use static message
-            pb.set_message("Analyzing single-use gate types...");
+            progress.update(gate_index);
         }
     }
 
     // Finish progress bar
-    pb.finish_with_message(format!(
+    progress.finish(&format!(
         "✓ Analyzed {line_number} gates, found {total_single_use_wires} single-use wires ({single_use_and_gates} AND, {single_use_xor_gates} XOR)"
     ));
 
@@ -188,25 +110,20 @@ pub fn analyze_single_use_gates(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::progress::NoProgress;
     use crate::stream::BufferedLineStream;
+    use crate::test_util::create_test_file;
+    use crate::wire_analyzer::UsageCounts;
     use std::fs::File;
-    use std::io::Write;
-
-    fn create_test_file(content: &str) -> Result<tempfile::NamedTempFile> {
-        let mut temp_file = tempfile::NamedTempFile::new()?;
-        temp_file.write_all(content.as_bytes())?;
-        temp_file.flush()?;
-        Ok(temp_file)
-    }
 
     fn create_mock_wire_report(usage_counts: Vec<u8>) -> WireUsageReport {
         WireUsageReport {
-            total_wires: usage_counts.len() as u32,
+            total_wires: usage_counts.len() as u64,
             primary_inputs: 0,
             intermediate_wires: 0,
             primary_outputs: 0,
             missing_wires_count: 0,
-            wire_usage_counts: usage_counts,
+            wire_usage_counts: UsageCounts::Dense(usage_counts),
             primary_input_wires: vec![],
             primary_output_wires: vec![],
         }
@@ -222,7 +139,7 @@ mod tests {
         // Setting 1 for all wires here to simulate single-use counting for intermediate wires
         let wire_report = create_mock_wire_report(vec![1, 1, 1, 1, 1, 1, 1, 1, 1]);
 
-        let analysis = analyze_single_use_gates(&mut stream, &wire_report)?;
+        let analysis = analyze_single_use_gates(&mut stream, &wire_report, &NoProgress)?;
 
         assert_eq!(analysis.single_use_and_gates, 1);
         assert_eq!(analysis.single_use_xor_gates, 2);
@@ -241,7 +158,7 @@ mod tests {
         // All wires have usage > 1
         let wire_report = create_mock_wire_report(vec![2, 2, 2, 2, 2, 2]);
 
-        let analysis = analyze_single_use_gates(&mut stream, &wire_report)?;
+        let analysis = analyze_single_use_gates(&mut stream, &wire_report, &NoProgress)?;
 
         assert_eq!(analysis.single_use_and_gates, 0);
         assert_eq!(analysis.single_use_xor_gates, 0);
@@ -260,7 +177,7 @@ mod tests {
         // Only AND and XOR outputs are single-use
         let wire_report = create_mock_wire_report(vec![0, 0, 2, 0, 0, 2, 0, 0, 1, 0, 0, 2]);
 
-        let analysis = analyze_single_use_gates(&mut stream, &wire_report)?;
+        let analysis = analyze_single_use_gates(&mut stream, &wire_report, &NoProgress)?;
 
         assert_eq!(analysis.single_use_and_gates, 0);
         assert_eq!(analysis.single_use_xor_gates, 1);
@@ -0,0 +1,470 @@
+use crate::error::{GcError, Result};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::mpsc::{Sender, SyncSender};
+
+use crate::constants::{BUFFER_SIZE, GARBLED_TABLE_SIZE, ZSTD_FLUSH_INTERVAL};
+use crate::garbler::{GarbledTable, GarblingScheme, TablesHeader};
+use crate::seek_table::SeekTable;
+use crate::session::SessionId;
+
+/// Destination for garbled tables as the garbler produces them
+///
+/// Tables arrive in batches, in circuit order, so a sink can write them out
+/// immediately instead of holding every table in memory. The garbler calls
+/// [`finish`](Self::finish) once after the last batch.
+pub trait GarbledTableSink {
+    /// Accept the next batch of tables
+    fn write_tables(&mut self, tables: &[GarbledTable]) -> Result<()>;
+
+    /// Flush any buffered data once all tables were written
+    ///
+    /// `header` describes the complete set of tables; file sinks record it
+    /// at the start of the file, streaming sinks may ignore it.
+    fn finish(&mut self, _header: &TablesHeader) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Collects tables in memory, e.g. for tests or small circuits
+impl GarbledTableSink for Vec<GarbledTable> {
+    fn write_tables(&mut self, tables: &[GarbledTable]) -> Result<()> {
+        self.extend_from_slice(tables);
+        Ok(())
+    }
+}
+
+/// Sends each batch as one chunk of bytes, e.g. to a [`ChannelReader`](crate::evaluator::ChannelReader)
+impl GarbledTableSink for Sender<Vec<u8>> {
+    fn write_tables(&mut self, tables: &[GarbledTable]) -> Result<()> {
+        self.send(tables_to_bytes(tables))
+            .map_err(|_| GcError::Disconnected("Garbled table receiver hung up".into()))
+    }
+}
+
+/// Sends each batch as one chunk of bytes, blocking while the channel is full
+impl GarbledTableSink for SyncSender<Vec<u8>> {
+    fn write_tables(&mut self, tables: &[GarbledTable]) -> Result<()> {
+        self.send(tables_to_bytes(tables))
+            .map_err(|_| GcError::Disconnected("Garbled table receiver hung up".into()))
+    }
+}
+
+/// Forwards tables to another sink and records their checksum in its header
+///
+/// The garbler wraps every sink in one, so each destination receives a
+/// [`TablesHeader`] whose `tables_checksum` covers exactly the tables it got.
+pub(crate) struct ChecksumSink<'a> {
+    inner: &'a mut dyn GarbledTableSink,
+    hasher: blake3::Hasher,
+}
+
+impl<'a> ChecksumSink<'a> {
+    pub(crate) fn new(inner: &'a mut dyn GarbledTableSink) -> Self {
+        Self {
+            inner,
+            hasher: blake3::Hasher::new(),
+        }
+    }
+}
+
+impl GarbledTableSink for ChecksumSink<'_> {
+    fn write_tables(&mut self, tables: &[GarbledTable]) -> Result<()> {
+        for table in tables {
+            self.hasher.update(&table.as_binary());
+        }
+        self.inner.write_tables(tables)
+    }
+
+    fn finish(&mut self, header: &TablesHeader) -> Result<()> {
+        self.inner.finish(&TablesHeader {
+            tables_checksum: Some(*self.hasher.finalize().as_bytes()),
+            ..*header
+        })
+    }
+}
+
+/// BLAKE3 checksum of tables in their binary form, as recorded in a [`TablesHeader`]
+pub(crate) fn tables_checksum(tables: &[GarbledTable]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    for table in tables {
+        hasher.update(&table.as_binary());
+    }
+    *hasher.finalize().as_bytes()
+}
+
+/// Writes the final header over the placeholder at the start of a file
+type HeaderPatcher<W> = fn(&mut W, &TablesHeader) -> std::io::Result<()>;
+
+/// Writes plain 64-byte tables to any writer (file, socket, pipe)
+#[derive(Debug)]
+pub struct TableWriter<W: Write> {
+    writer: W,
+    tables_written: usize,
+    /// Set for files, which start with a [`TablesHeader`]
+    patch_header: Option<HeaderPatcher<W>>,
+}
+
+impl TableWriter<BufWriter<File>> {
+    /// Create a garbled tables file
+    ///
+    /// The file starts with a placeholder [`TablesHeader`] that is filled in
+    /// by [`finish`](GarbledTableSink::finish).
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::with_capacity(BUFFER_SIZE, file);
+        writer.write_all(&placeholder_header().to_bytes())?;
+        Ok(Self {
+            writer,
+            tables_written: 0,
+            patch_header: Some(patch_header),
+        })
+    }
+}
+
+impl<W: Write> TableWriter<W> {
+    /// Write headerless tables to `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            tables_written: 0,
+            patch_header: None,
+        }
+    }
+
+    /// Number of tables written so far
+    pub fn tables_written(&self) -> usize {
+        self.tables_written
+    }
+
+    /// Unwrap the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> GarbledTableSink for TableWriter<W> {
+    fn write_tables(&mut self, tables: &[GarbledTable]) -> Result<()> {
+        for table in tables {
+            self.writer.write_all(&table.as_binary())?;
+        }
+        self.tables_written += tables.len();
+        Ok(())
+    }
+
+    fn finish(&mut self, header: &TablesHeader) -> Result<()> {
+        if let Some(patch_header) = self.patch_header {
+            patch_header(&mut self.writer, header)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes tables as a sequence of zstd frames
+///
+/// The evaluator detects the compressed form automatically and decodes the
+/// tables on the fly. Files keep their [`TablesHeader`] uncompressed in
+/// front of the frames. Every [`ZSTD_FLUSH_INTERVAL`] tables are compressed
+/// into a frame of their own and written out, so a receiver decoding the
+/// stream as it arrives can evaluate those tables without waiting for the
+/// end. A [`SeekTable`] of the frames ends the stream, letting readers jump
+/// to the frame holding any table.
+pub struct CompressedTableWriter<W: Write> {
+    writer: W,
+    compressor: zstd::bulk::Compressor<'static>,
+    /// Binary tables of the frame being filled
+    frame: Vec<u8>,
+    tables_written: usize,
+    patch_header: Option<HeaderPatcher<W>>,
+    /// Tables per frame
+    flush_interval: usize,
+    /// Frames written so far
+    seek_table: SeekTable,
+}
+
+impl<W: Write> std::fmt::Debug for CompressedTableWriter<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompressedTableWriter")
+            .field("tables_written", &self.tables_written)
+            .field("flush_interval", &self.flush_interval)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CompressedTableWriter<BufWriter<File>> {
+    /// Create a compressed garbled tables file
+    pub fn create<P: AsRef<Path>>(path: P, level: i32) -> Result<Self> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::with_capacity(BUFFER_SIZE, file);
+        writer.write_all(&placeholder_header().to_bytes())?;
+        let mut compressed = Self::new(writer, level)?;
+        compressed.patch_header = Some(patch_header);
+        Ok(compressed)
+    }
+}
+
+impl<W: Write> CompressedTableWriter<W> {
+    /// Compress headerless tables into `writer` at the given zstd level
+    pub fn new(writer: W, level: i32) -> Result<Self> {
+        let mut compressor = zstd::bulk::Compressor::new(level)?;
+        compressor.set_parameter(zstd::stream::raw::CParameter::ChecksumFlag(true))?;
+        Ok(Self {
+            writer,
+            compressor,
+            frame: Vec::new(),
+            tables_written: 0,
+            patch_header: None,
+            flush_interval: ZSTD_FLUSH_INTERVAL,
+            seek_table: SeekTable::default(),
+        })
+    }
+
+    /// End a frame every `tables` tables instead of every
+    /// [`ZSTD_FLUSH_INTERVAL`]
+    ///
+    /// Shorter intervals lower the latency of a streaming receiver and let
+    /// readers seek more finely, at a small cost in compression ratio.
+    pub fn with_flush_interval(mut self, tables: usize) -> Self {
+        self.flush_interval = tables.max(1);
+        self
+    }
+
+    /// Number of tables written so far
+    pub fn tables_written(&self) -> usize {
+        self.tables_written
+    }
+
+    /// Compress the buffered tables into a frame and write it out
+    fn write_frame(&mut self) -> Result<()> {
+        let compressed = self.compressor.compress(&self.frame)?;
+        self.writer.write_all(&compressed)?;
+        self.writer.flush()?;
+        self.seek_table.push_frame(
+            compressed.len() as u64,
+            (self.frame.len() / GARBLED_TABLE_SIZE) as u64,
+        );
+        self.frame.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> GarbledTableSink for CompressedTableWriter<W> {
+    fn write_tables(&mut self, tables: &[GarbledTable]) -> Result<()> {
+        for table in tables {
+            self.frame.extend_from_slice(&table.as_binary());
+            if self.frame.len() == self.flush_interval * GARBLED_TABLE_SIZE {
+                self.write_frame()?;
+            }
+        }
+        self.tables_written += tables.len();
+        Ok(())
+    }
+
+    fn finish(&mut self, header: &TablesHeader) -> Result<()> {
+        // Without tables, an empty frame still marks the data as compressed
+        if !self.frame.is_empty() || self.seek_table.frames().is_empty() {
+            self.write_frame()?;
+        }
+        self.writer.write_all(&self.seek_table.to_bytes()?)?;
+        if let Some(patch_header) = self.patch_header {
+            patch_header(&mut self.writer, header)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Header written before any tables, overwritten once they are known
+///
+/// Claims zero tables, so a file left behind by an aborted run is rejected as
+/// truncated.
+pub(crate) fn placeholder_header() -> TablesHeader {
+    TablesHeader {
+        scheme: GarblingScheme::PointAndPermute,
+        num_tables: 0,
+        circuit_digest: [0; 32],
+        session_id: SessionId::default(),
+        tables_checksum: None,
+    }
+}
+
+/// Overwrite the header at the start of `writer` and return to the end
+pub(crate) fn patch_header<W: Write + Seek>(
+    writer: &mut W,
+    header: &TablesHeader,
+) -> std::io::Result<()> {
+    writer.seek(SeekFrom::Start(0))?;
+    writer.write_all(&header.to_bytes())?;
+    writer.seek(SeekFrom::End(0))?;
+    Ok(())
+}
+
+/// Concatenate the binary form of tables
+fn tables_to_bytes(tables: &[GarbledTable]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(tables.len() * GARBLED_TABLE_SIZE);
+    for table in tables {
+        bytes.extend_from_slice(&table.as_binary());
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::GARBLED_TABLES_HEADER_SIZE;
+    use crate::evaluator::{ChannelReader, GarbledTableReader, TableAccess};
+    use std::sync::mpsc::channel;
+
+    fn test_tables() -> Vec<GarbledTable> {
+        (0..3u8)
+            .map(|i| GarbledTable {
+                ciphertexts: [[i; 16], [i + 1; 16], [i + 2; 16], [i + 3; 16]],
+            })
+            .collect()
+    }
+
+    fn test_header(num_tables: u64) -> TablesHeader {
+        TablesHeader {
+            scheme: GarblingScheme::PointAndPermute,
+            num_tables,
+            circuit_digest: [9; 32],
+            session_id: SessionId::new([7; 16]),
+            tables_checksum: Some(tables_checksum(&test_tables()[..num_tables as usize])),
+        }
+    }
+
+    fn read_all(mut reader: GarbledTableReader) -> Vec<[[u8; 16]; 4]> {
+        let mut tables = Vec::new();
+        while let Ok(table) = reader.next_table() {
+            tables.push(table.ciphertexts);
+        }
+        tables
+    }
+
+    #[test]
+    fn test_sinks_roundtrip_through_reader() -> Result<()> {
+        let tables = test_tables();
+        let expected: Vec<_> = tables.iter().map(|table| table.ciphertexts).collect();
+        let header = test_header(3);
+
+        let plain_file = tempfile::NamedTempFile::new()?;
+        let mut writer = TableWriter::create(plain_file.path())?;
+        writer.write_tables(&tables[..1])?;
+        writer.write_tables(&tables[1..])?;
+        writer.finish(&header)?;
+        assert_eq!(writer.tables_written(), 3);
+        let reader = GarbledTableReader::open(plain_file.path(), TableAccess::Buffered)?;
+        assert_eq!(reader.header(), Some(&header));
+        assert_eq!(read_all(reader), expected);
+        let reader = GarbledTableReader::open(plain_file.path(), TableAccess::Mmap)?;
+        assert_eq!(read_all(reader), expected);
+
+        let compressed_file = tempfile::NamedTempFile::new()?;
+        let mut writer = CompressedTableWriter::create(compressed_file.path(), 3)?;
+        writer.write_tables(&tables)?;
+        writer.finish(&header)?;
+        let reader = GarbledTableReader::open(compressed_file.path(), TableAccess::Buffered)?;
+        assert_eq!(reader.header(), Some(&header));
+        assert_eq!(read_all(reader), expected);
+
+        let (mut sender, receiver) = channel();
+        sender.write_tables(&tables)?;
+        drop(sender);
+        let reader = GarbledTableReader::from_reader(ChannelReader::new(receiver))?;
+        assert_eq!(reader.header(), None);
+        assert_eq!(read_all(reader), expected);
+
+        // A file copied into a stream keeps its header
+        let reader = GarbledTableReader::from_reader(File::open(plain_file.path())?)?;
+        assert_eq!(reader.header(), Some(&header));
+        assert_eq!(read_all(reader), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressed_writer_flushes_periodically() -> Result<()> {
+        let tables = test_tables();
+        let expected: Vec<_> = tables.iter().map(|table| table.ciphertexts).collect();
+
+        let mut writer = CompressedTableWriter::new(Vec::new(), 3)?.with_flush_interval(2);
+        writer.write_tables(&tables)?;
+
+        // The first two tables are decodable before the stream is finished
+        let partial = writer.writer.clone();
+        let mut reader = GarbledTableReader::from_reader(std::io::Cursor::new(partial))?;
+        assert_eq!(reader.next_table()?.ciphertexts, expected[0]);
+        assert_eq!(reader.next_table()?.ciphertexts, expected[1]);
+        assert!(reader.next_table().is_err());
+
+        writer.finish(&test_header(3))?;
+        let finished = writer.writer.clone();
+        let reader = GarbledTableReader::from_reader(std::io::Cursor::new(finished))?;
+        assert_eq!(read_all(reader), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tables_header_validation() -> Result<()> {
+        let tables = test_tables();
+        let tables_file = tempfile::NamedTempFile::new()?;
+        let mut writer = TableWriter::create(tables_file.path())?;
+        writer.write_tables(&tables)?;
+        writer.finish(&test_header(3))?;
+        let bytes = std::fs::read(tables_file.path())?;
+
+        // Truncated table data
+        std::fs::write(tables_file.path(), &bytes[..bytes.len() - 10])?;
+        let error_msg = GarbledTableReader::open(tables_file.path(), TableAccess::Buffered)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("truncated or corrupt: header promises 3 tables"));
+
+        // Corrupt table data fails the checksum once the last table is read
+        let mut corrupt = bytes.clone();
+        corrupt[GARBLED_TABLES_HEADER_SIZE + 70] ^= 1;
+        std::fs::write(tables_file.path(), &corrupt)?;
+        let mut reader = GarbledTableReader::open(tables_file.path(), TableAccess::Buffered)?;
+        reader.next_table()?;
+        reader.next_table()?;
+        let error = reader.next_table().unwrap_err();
+        assert!(
+            matches!(error, GcError::ChecksumMismatch { .. }),
+            "{}",
+            error
+        );
+
+        // Unknown format version
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 99;
+        std::fs::write(tables_file.path(), &bad_version)?;
+        let error_msg = GarbledTableReader::open(tables_file.path(), TableAccess::Buffered)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Unsupported garbled tables format version 99"));
+
+        // Unknown scheme
+        let mut bad_scheme = bytes.clone();
+        bad_scheme[8] = 7;
+        std::fs::write(tables_file.path(), &bad_scheme)?;
+        let error =
+            GarbledTableReader::open(tables_file.path(), TableAccess::Buffered).unwrap_err();
+        assert!(matches!(error, GcError::InvalidFormat { .. }));
+        assert!(
+            error
+                .to_string()
+                .contains("unsupported garbling scheme id 7")
+        );
+
+        // Legacy files without a header are still read
+        std::fs::write(tables_file.path(), &bytes[GARBLED_TABLES_HEADER_SIZE..])?;
+        let reader = GarbledTableReader::open(tables_file.path(), TableAccess::Buffered)?;
+        assert_eq!(reader.header(), None);
+        assert_eq!(read_all(reader).len(), 3);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use sha2::{Digest, Sha256};
+
+use crate::garbler::WireLabel;
+use crate::ot_extension::{expand, xor_into};
+
+/// Domain separation tag for the expansion of GGM tree nodes
+const TREE_DOMAIN: &[u8] = b"gc-soft-spoken-ggm-v1";
+
+/// GGM tree of depth `depth` grown from `root`
+///
+/// Leaf `x` is reached by taking the right child at level `l` whenever bit
+/// `depth - 1 - l` of `x` is set.
+///
+/// # Returns
+/// The `2^depth` leaves, and per level the XOR of all left children and the
+/// XOR of all right children: one base OT pair per level, from which the
+/// holder of all but one leaf is rebuilt by [`punctured_tree`]
+pub(crate) fn expand_tree(
+    root: &WireLabel,
+    depth: usize,
+) -> (Vec<WireLabel>, Vec<(WireLabel, WireLabel)>) {
+    let mut nodes = vec![*root];
+    let mut level_sums = Vec::with_capacity(depth);
+    for _ in 0..depth {
+        let mut children = Vec::with_capacity(nodes.len() * 2);
+        let mut left_sum = WireLabel::new([0; 16]);
+        let mut right_sum = WireLabel::new([0; 16]);
+        for node in &nodes {
+            let (left, right) = expand_node(node);
+            left_sum = left_sum.xor(&left);
+            right_sum = right_sum.xor(&right);
+            children.push(left);
+            children.push(right);
+        }
+        level_sums.push((left_sum, right_sum));
+        nodes = children;
+    }
+    (nodes, level_sums)
+}
+
+/// Rebuild every leaf but `punctured` from one level sum per level
+///
+/// # Arguments
+/// * `punctured` - The leaf to leave out
+/// * `depth` - Depth of the tree
+/// * `level_keys` - Per level, the sum of the children on the side off the path to `punctured`
+///
+/// # Returns
+/// The `2^depth` leaves, with `None` at `punctured`
+pub(crate) fn punctured_tree(
+    punctured: usize,
+    depth: usize,
+    level_keys: &[WireLabel],
+) -> Vec<Option<WireLabel>> {
+    let mut nodes: Vec<Option<WireLabel>> = vec![None];
+    for (level, key) in level_keys.iter().enumerate().take(depth) {
+        let mut children = vec![None; nodes.len() * 2];
+        for (i, node) in nodes.iter().enumerate() {
+            if let Some(node) = node {
+                let (left, right) = expand_node(node);
+                children[2 * i] = Some(left);
+                children[2 * i + 1] = Some(right);
+            }
+        }
+
+        // The off-path child of the path node is the key minus its known siblings
+        let path_node = punctured >> (depth - level);
+        let side = 1 - ((punctured >> (depth - 1 - level)) & 1);
+        let missing = children
+            .iter()
+            .skip(side)
+            .step_by(2)
+            .flatten()
+            .fold(*key, |sum, child| sum.xor(child));
+        children[2 * path_node + side] = Some(missing);
+        nodes = children;
+    }
+    nodes
+}
+
+/// Receiver's half of the small-field VOLE over one block of columns
+///
+/// # Arguments
+/// * `leaves` - All `2^k` leaves of the block's GGM tree
+/// * `column_size` - Bytes per matrix column
+///
+/// # Returns
+/// `u`, the XOR of every leaf's expansion, and for each bit `j` of the
+/// field element the column `v_j`, the XOR of the expansions of leaves with
+/// bit `j` set
+pub(crate) fn receiver_vole(leaves: &[WireLabel], column_size: usize) -> (Vec<u8>, Vec<Vec<u8>>) {
+    let field_bits = leaves.len().trailing_zeros() as usize;
+    let mut u = vec![0u8; column_size];
+    let mut v = vec![vec![0u8; column_size]; field_bits];
+    for (x, leaf) in leaves.iter().enumerate() {
+        let expansion = expand(leaf, column_size);
+        xor_into(&mut u, &expansion);
+        for (j, column) in v.iter_mut().enumerate() {
+            if (x >> j) & 1 == 1 {
+                xor_into(column, &expansion);
+            }
+        }
+    }
+    (u, v)
+}
+
+/// Sender's half of the small-field VOLE over one block of columns
+///
+/// Computes `w_j = v_j xor delta_j * u` for each bit `j` of `delta`, without
+/// knowing the expansion of the punctured leaf: its coefficient `x xor delta`
+/// is zero.
+///
+/// # Arguments
+/// * `leaves` - The block's leaves as rebuilt by [`punctured_tree`]
+/// * `delta` - The punctured leaf, the sender's secret field element
+/// * `column_size` - Bytes per matrix column
+pub(crate) fn sender_vole(
+    leaves: &[Option<WireLabel>],
+    delta: usize,
+    column_size: usize,
+) -> Vec<Vec<u8>> {
+    let field_bits = leaves.len().trailing_zeros() as usize;
+    let mut w = vec![vec![0u8; column_size]; field_bits];
+    for (x, leaf) in leaves.iter().enumerate() {
+        let Some(leaf) = leaf else { continue };
+        let expansion = expand(leaf, column_size);
+        for (j, column) in w.iter_mut().enumerate() {
+            if ((x ^ delta) >> j) & 1 == 1 {
+                xor_into(column, &expansion);
+            }
+        }
+    }
+    w
+}
+
+/// Left and right child of a GGM tree node
+fn expand_node(node: &WireLabel) -> (WireLabel, WireLabel) {
+    let mut hasher = Sha256::new();
+    hasher.update(TREE_DOMAIN);
+    hasher.update(node.as_bytes());
+    let digest = hasher.finalize();
+    (
+        WireLabel::new(digest[..16].try_into().unwrap()),
+        WireLabel::new(digest[16..].try_into().unwrap()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_punctured_tree_and_vole() {
+        let depth = 4;
+        let column_size = 24;
+        let (leaves, level_sums) = expand_tree(&WireLabel::new([7; 16]), depth);
+        let (u, v) = receiver_vole(&leaves, column_size);
+
+        for delta in [0, 5, 15] {
+            // The sender learns the off-path sum of each level
+            let level_keys: Vec<WireLabel> = level_sums
+                .iter()
+                .enumerate()
+                .map(|(level, &(left, right))| {
+                    if (delta >> (depth - 1 - level)) & 1 == 1 {
+                        left
+                    } else {
+                        right
+                    }
+                })
+                .collect();
+            let rebuilt = punctured_tree(delta, depth, &level_keys);
+            for (x, leaf) in rebuilt.iter().enumerate() {
+                assert_eq!(*leaf, (x != delta).then_some(leaves[x]));
+            }
+
+            // w_j = v_j xor delta_j * u
+            let w = sender_vole(&rebuilt, delta, column_size);
+            for (j, column) in w.iter().enumerate() {
+                let mut expected = v[j].clone();
+                if (delta >> j) & 1 == 1 {
+                    xor_into(&mut expected, &u);
+                }
+                assert_eq!(*column, expected);
+            }
+        }
+    }
+}
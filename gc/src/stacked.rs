@@ -0,0 +1,1597 @@
+use crate::error::{GcError, Result};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::constants::{BUFFER_SIZE, STACKED_MATERIAL_MAGIC, STACKED_MATERIAL_VERSION};
+use crate::evaluator::{EvaluationResult, OutputResult, evaluate_gate};
+use crate::garbler::{GarbledTable, WireLabel, WireLabels, constant_label, garble_and_gate};
+use crate::gate::{Circuit, Gate, GateType, WireId};
+use crate::ot_simulation::OTResult;
+use crate::parser::{parse_gate_line, parse_header_line};
+use crate::partition::InputPartition;
+use crate::session::SessionId;
+
+/// Domain separation tag for the pads of stacked garbling
+const PAD_DOMAIN: &[u8] = b"gc-stacked-pad-v1";
+
+/// Domain separation tag for the fingerprint of a stacked circuit
+const DIGEST_DOMAIN: &[u8] = b"gc-stacked-circuit-v1";
+
+/// Gate type token of a conditional line
+const CONDITIONAL_TOKEN: &str = "COND";
+
+/// Line starting the section of one branch
+const BRANCH_TOKEN: &str = "BRANCH";
+
+/// A conditional: mutually exclusive branches of which exactly one runs
+///
+/// Each branch has a condition wire, and exactly one of them must be 1 at
+/// run time; the branch it belongs to computes the outputs. Every branch is
+/// a Bristol circuit reading the conditional's `k` inputs from its wires
+/// `0..k` and writing the `m` outputs to its last `m` wires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conditional {
+    /// Tag naming the conditional and its branch sections in the circuit file
+    pub tag: String,
+    /// Condition wire of every branch
+    pub condition_wires: Vec<WireId>,
+    /// Wires passed to every branch
+    pub input_wires: Vec<WireId>,
+    /// Wires written by the branch that runs
+    pub output_wires: Vec<WireId>,
+    /// The branches, in the order of their condition wires
+    pub branches: Vec<Circuit>,
+}
+
+impl Conditional {
+    /// Number of AND gates of each branch
+    fn branch_and_gates(&self) -> impl Iterator<Item = usize> + '_ {
+        self.branches.iter().map(|branch| {
+            branch
+                .gates
+                .iter()
+                .filter(|gate| gate.gate_type == GateType::And)
+                .count()
+        })
+    }
+
+    /// Garbled tables of the stacked branches: as many as the largest branch has
+    pub fn stacked_tables(&self) -> usize {
+        self.branch_and_gates().max().unwrap_or(0)
+    }
+
+    /// Check that every branch reads only its inputs and wires it wrote, and writes its outputs
+    fn check_branches(&self) -> Result<()> {
+        let num_inputs = self.input_wires.len() as WireId;
+        for (index, branch) in self.branches.iter().enumerate() {
+            let context = |message: String| {
+                GcError::InvalidCircuit(format!(
+                    "Branch {} of conditional '{}': {}",
+                    index, self.tag, message
+                ))
+            };
+            if branch.num_wires < num_inputs.max(self.output_wires.len() as WireId) {
+                return Err(context(format!(
+                    "{} wires cannot hold {} inputs and {} outputs",
+                    branch.num_wires,
+                    num_inputs,
+                    self.output_wires.len()
+                )));
+            }
+            let mut written: HashSet<WireId> = (0..num_inputs).collect();
+            for gate in &branch.gates {
+                if let Some(wire) = gate.input_wires().find(|wire| !written.contains(wire)) {
+                    return Err(context(format!(
+                        "wire {} is read before it is written",
+                        wire
+                    )));
+                }
+                written.insert(gate.output_wire);
+            }
+            if let Some(wire) = branch_output_wires(branch, self.output_wires.len())
+                .find(|wire| !written.contains(wire))
+            {
+                return Err(context(format!("output wire {} is never written", wire)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Last `num_outputs` wires of a branch, where it leaves its outputs
+fn branch_output_wires(branch: &Circuit, num_outputs: usize) -> std::ops::Range<WireId> {
+    branch.num_wires - num_outputs as WireId..branch.num_wires
+}
+
+/// One step of a stacked circuit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StackedStep {
+    /// An ordinary gate
+    Gate(Gate),
+    /// A conditional whose branches are garbled stacked
+    Conditional(Conditional),
+}
+
+impl StackedStep {
+    /// Wires the step reads
+    fn input_wires(&self) -> Box<dyn Iterator<Item = WireId> + '_> {
+        match self {
+            StackedStep::Gate(gate) => Box::new(gate.input_wires()),
+            StackedStep::Conditional(conditional) => Box::new(
+                conditional
+                    .condition_wires
+                    .iter()
+                    .chain(&conditional.input_wires)
+                    .copied(),
+            ),
+        }
+    }
+
+    /// Wires the step writes
+    fn output_wires(&self) -> Box<dyn Iterator<Item = WireId> + '_> {
+        match self {
+            StackedStep::Gate(gate) => Box::new(std::iter::once(gate.output_wire)),
+            StackedStep::Conditional(conditional) => {
+                Box::new(conditional.output_wires.iter().copied())
+            }
+        }
+    }
+}
+
+/// A circuit with conditionals whose branches are tagged sub-circuits
+///
+/// Written as a Bristol circuit whose gate lines may also be conditionals,
+///
+/// ```text
+/// <b + k> <m> <b condition wires> <k input wires> <m output wires> COND <tag>
+/// ```
+///
+/// followed by one section per branch: a `BRANCH <tag>` line and the
+/// branch as a Bristol circuit. The branches of a conditional are taken in
+/// the order of their sections, and their number `b` tells the condition
+/// wires from the inputs. A section ends after as many gates as its header
+/// declares; gates and conditionals of the circuit itself all come before
+/// the first section. Blank lines are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackedCircuit {
+    /// Total number of wires outside the branches, from the header
+    pub num_wires: u64,
+    /// Gates and conditionals in topological order
+    pub steps: Vec<StackedStep>,
+}
+
+/// A conditional line before its branches are known
+struct PendingConditional {
+    line_number: u64,
+    tag: String,
+    inputs: Vec<WireId>,
+    outputs: Vec<WireId>,
+}
+
+/// A parsed step, with conditionals waiting for their branch sections
+enum ParsedStep {
+    Gate(Gate),
+    Conditional(PendingConditional),
+}
+
+/// The section of one branch, as read so far
+struct BranchSection {
+    tag: String,
+    /// Line of the `BRANCH` line
+    line_number: u64,
+    num_wires: u64,
+    /// Gates declared by the branch header, `None` until it is read
+    num_gates: Option<u64>,
+    gates: Vec<Gate>,
+}
+
+impl BranchSection {
+    /// Check that the section has all the gates its header declares
+    fn finish(self) -> Result<(String, Circuit)> {
+        let Some(num_gates) = self.num_gates else {
+            return Err(GcError::parse(
+                self.line_number,
+                format!("Branch of conditional '{}' has no header", self.tag),
+            ));
+        };
+        if self.gates.len() as u64 != num_gates {
+            return Err(GcError::parse(
+                self.line_number,
+                format!(
+                    "Branch of conditional '{}' declares {} gates but has {}",
+                    self.tag,
+                    num_gates,
+                    self.gates.len()
+                ),
+            ));
+        }
+        Ok((
+            self.tag,
+            Circuit {
+                num_wires: self.num_wires,
+                gates: self.gates,
+                io: None,
+            },
+        ))
+    }
+}
+
+impl StackedCircuit {
+    /// Parse a stacked circuit from its text
+    ///
+    /// # Returns
+    /// * `Ok(StackedCircuit)` - The circuit with its branches
+    /// * `Err(GcError)` - A line is malformed or comes after the branch sections, a conditional has fewer than two branches or a branch section no conditional, or a branch reads a wire it does not have
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index as u64 + 1, line))
+            .filter(|(_, line)| !line.trim().is_empty());
+        let (_, header_line) = lines
+            .next()
+            .ok_or_else(|| GcError::parse(1, "Missing header"))?;
+        let num_wires = parse_header_line(header_line)?.num_wires;
+
+        let mut steps = Vec::new();
+        let mut sections: Vec<BranchSection> = Vec::new();
+        let mut branches: HashMap<String, Vec<Circuit>> = HashMap::new();
+        for (line_number, line) in lines {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens[0] == BRANCH_TOKEN {
+                let [_, tag] = tokens[..] else {
+                    return Err(GcError::parse(
+                        line_number,
+                        format!("Expected '{} <tag>', got '{}'", BRANCH_TOKEN, line),
+                    ));
+                };
+                if let Some(section) = sections.pop() {
+                    let (tag, branch) = section.finish()?;
+                    branches.entry(tag).or_default().push(branch);
+                }
+                sections.push(BranchSection {
+                    tag: tag.to_string(),
+                    line_number,
+                    num_wires: 0,
+                    num_gates: None,
+                    gates: Vec::new(),
+                });
+            } else if let Some(section) = sections.last_mut() {
+                match section.num_gates {
+                    None => {
+                        let header = parse_header_line(line).map_err(|e| {
+                            GcError::parse(
+                                line_number,
+                                format!("Branch of conditional '{}': {}", section.tag, e),
+                            )
+                        })?;
+                        section.num_gates = Some(header.num_gates);
+                        section.num_wires = header.num_wires;
+                    }
+                    Some(num_gates) if (section.gates.len() as u64) < num_gates => {
+                        section.gates.push(parse_gate_line(line, line_number)?);
+                    }
+                    Some(num_gates) => {
+                        return Err(GcError::parse(
+                            line_number,
+                            format!(
+                                "Line after the {} gates of a branch of conditional '{}'; gates and conditionals of the circuit go before the branch sections",
+                                num_gates, section.tag
+                            ),
+                        ));
+                    }
+                }
+            } else if tokens.len() >= 2 && tokens[tokens.len() - 2] == CONDITIONAL_TOKEN {
+                steps.push(ParsedStep::Conditional(parse_conditional_line(
+                    &tokens,
+                    line_number,
+                )?));
+            } else {
+                steps.push(ParsedStep::Gate(parse_gate_line(line, line_number)?));
+            }
+        }
+        if let Some(section) = sections.pop() {
+            let (tag, branch) = section.finish()?;
+            branches.entry(tag).or_default().push(branch);
+        }
+
+        let mut resolved = Vec::with_capacity(steps.len());
+        for step in steps {
+            let pending = match step {
+                ParsedStep::Gate(gate) => {
+                    resolved.push(StackedStep::Gate(gate));
+                    continue;
+                }
+                ParsedStep::Conditional(pending) => pending,
+            };
+            let Some(conditional_branches) = branches.remove(&pending.tag) else {
+                return Err(GcError::parse(
+                    pending.line_number,
+                    format!(
+                        "Conditional '{}' has no branches, or its tag is used twice",
+                        pending.tag
+                    ),
+                ));
+            };
+            let num_branches = conditional_branches.len();
+            if num_branches < 2 {
+                return Err(GcError::InvalidCircuit(format!(
+                    "Conditional '{}' has {} branch, needs at least 2",
+                    pending.tag, num_branches
+                )));
+            }
+            if pending.inputs.len() < num_branches {
+                return Err(GcError::parse(
+                    pending.line_number,
+                    format!(
+                        "Conditional '{}' has {} branches but only {} inputs for their condition wires",
+                        pending.tag,
+                        num_branches,
+                        pending.inputs.len()
+                    ),
+                ));
+            }
+            let mut input_wires = pending.inputs;
+            let condition_wires: Vec<WireId> = input_wires.drain(..num_branches).collect();
+            let conditional = Conditional {
+                tag: pending.tag,
+                condition_wires,
+                input_wires,
+                output_wires: pending.outputs,
+                branches: conditional_branches,
+            };
+            conditional.check_branches()?;
+            resolved.push(StackedStep::Conditional(conditional));
+        }
+        if let Some(tag) = branches.keys().next() {
+            return Err(GcError::InvalidCircuit(format!(
+                "Branch sections for '{}' belong to no conditional",
+                tag
+            )));
+        }
+
+        Ok(StackedCircuit {
+            num_wires,
+            steps: resolved,
+        })
+    }
+
+    /// Load a stacked circuit from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Conditionals of the circuit, in order
+    pub fn conditionals(&self) -> impl Iterator<Item = &Conditional> {
+        self.steps.iter().filter_map(|step| match step {
+            StackedStep::Conditional(conditional) => Some(conditional),
+            StackedStep::Gate(_) => None,
+        })
+    }
+
+    /// Primary input wires in ascending order: wires read before any step writes them
+    pub fn input_wires(&self) -> Vec<WireId> {
+        let mut written = HashSet::new();
+        let mut inputs = BTreeSet::new();
+        for step in &self.steps {
+            inputs.extend(step.input_wires().filter(|wire| !written.contains(wire)));
+            written.extend(step.output_wires());
+        }
+        inputs.into_iter().collect()
+    }
+
+    /// Primary output wires in ascending order: wires written and never read
+    pub fn output_wires(&self) -> Vec<WireId> {
+        let read: HashSet<WireId> = self
+            .steps
+            .iter()
+            .flat_map(|step| step.input_wires())
+            .collect();
+        let outputs: BTreeSet<WireId> = self
+            .steps
+            .iter()
+            .flat_map(|step| step.output_wires())
+            .filter(|wire| !read.contains(wire))
+            .collect();
+        outputs.into_iter().collect()
+    }
+
+    /// Garbled tables of the circuit, garbled stacked and side by side
+    pub fn cost(&self) -> StackedCost {
+        let mut cost = StackedCost::default();
+        for step in &self.steps {
+            match step {
+                StackedStep::Gate(gate) => {
+                    cost.and_tables += u64::from(gate.gate_type == GateType::And);
+                }
+                StackedStep::Conditional(conditional) => {
+                    let num_branches = conditional.branches.len();
+                    cost.stacked_tables += conditional.stacked_tables() as u64;
+                    cost.unstacked_tables += conditional.branch_and_gates().sum::<usize>() as u64;
+                    cost.interface_tables += (num_branches
+                        * (conditional.input_wires.len() + conditional.output_wires.len()))
+                        as u64;
+                }
+            }
+        }
+        cost
+    }
+
+    /// Canonical fingerprint of the circuit, branches included
+    ///
+    /// Identifies the circuit a [`StackedMaterial`] was garbled for.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(DIGEST_DOMAIN);
+        hasher.update(self.num_wires.to_le_bytes());
+        let update_wires = |hasher: &mut Sha256, wires: &[WireId]| {
+            hasher.update((wires.len() as u64).to_le_bytes());
+            for wire in wires {
+                hasher.update(wire.to_le_bytes());
+            }
+        };
+        for step in &self.steps {
+            match step {
+                StackedStep::Gate(gate) => hasher.update(format!("{}\n", gate)),
+                StackedStep::Conditional(conditional) => {
+                    hasher.update(format!("{} {}\n", CONDITIONAL_TOKEN, conditional.tag));
+                    update_wires(&mut hasher, &conditional.condition_wires);
+                    update_wires(&mut hasher, &conditional.input_wires);
+                    update_wires(&mut hasher, &conditional.output_wires);
+                    for branch in &conditional.branches {
+                        hasher.update(format!("{} {}\n", BRANCH_TOKEN, branch.num_wires));
+                        for gate in &branch.gates {
+                            hasher.update(format!("{}\n", gate));
+                        }
+                    }
+                }
+            }
+        }
+        hasher.finalize().into()
+    }
+
+    /// Evaluate the circuit in the clear, as ground truth for garbled runs
+    ///
+    /// # Arguments
+    /// * `inputs` - Bit value for every primary input wire: wire_id -> bit
+    ///
+    /// # Returns
+    /// * `Ok(HashMap<WireId, bool>)` - Bit value of every primary output wire
+    /// * `Err(GcError)` - An input bit is missing, or a conditional has no or several conditions set
+    pub fn evaluate_plain(&self, inputs: &HashMap<WireId, bool>) -> Result<HashMap<WireId, bool>> {
+        let mut values = inputs.clone();
+        for step in &self.steps {
+            match step {
+                StackedStep::Gate(gate) => {
+                    let value = plain_gate(gate, &values)?;
+                    values.insert(gate.output_wire, value);
+                }
+                StackedStep::Conditional(conditional) => {
+                    let conditions = conditional
+                        .condition_wires
+                        .iter()
+                        .map(|&wire| value_of(&values, wire))
+                        .collect::<Result<Vec<bool>>>()?;
+                    let active: Vec<usize> = (0..conditions.len())
+                        .filter(|&index| conditions[index])
+                        .collect();
+                    let [active] = active[..] else {
+                        return Err(GcError::InvalidArgument(format!(
+                            "Conditional '{}' needs exactly one condition wire set, {} are",
+                            conditional.tag,
+                            active.len()
+                        )));
+                    };
+                    let branch = &conditional.branches[active];
+                    let mut branch_values = HashMap::new();
+                    for (index, &wire) in conditional.input_wires.iter().enumerate() {
+                        branch_values.insert(index as WireId, value_of(&values, wire)?);
+                    }
+                    for gate in &branch.gates {
+                        let value = plain_gate(gate, &branch_values)?;
+                        branch_values.insert(gate.output_wire, value);
+                    }
+                    let branch_outputs =
+                        branch_output_wires(branch, conditional.output_wires.len());
+                    for (&wire, branch_wire) in conditional.output_wires.iter().zip(branch_outputs)
+                    {
+                        values.insert(wire, value_of(&branch_values, branch_wire)?);
+                    }
+                }
+            }
+        }
+        self.output_wires()
+            .into_iter()
+            .map(|wire| Ok((wire, value_of(&values, wire)?)))
+            .collect()
+    }
+}
+
+/// Parse `<n_in> <n_out> <inputs> <outputs> COND <tag>`, already split into tokens
+fn parse_conditional_line(tokens: &[&str], line_number: u64) -> Result<PendingConditional> {
+    let count = |index: usize, what: &str| -> Result<usize> {
+        tokens[index].parse().map_err(|_| {
+            GcError::parse(
+                line_number,
+                format!("Invalid {}: '{}'", what, tokens[index]),
+            )
+        })
+    };
+    let num_inputs = count(0, "num_inputs")?;
+    let num_outputs = count(1, "num_outputs")?;
+    if tokens.len() != 2 + num_inputs + num_outputs + 2 {
+        return Err(GcError::parse(
+            line_number,
+            format!(
+                "Conditional with {} inputs and {} outputs needs {} tokens, got {}",
+                num_inputs,
+                num_outputs,
+                num_inputs + num_outputs + 4,
+                tokens.len()
+            ),
+        ));
+    }
+    let wires = tokens[2..2 + num_inputs + num_outputs]
+        .iter()
+        .map(|token| {
+            token
+                .parse()
+                .map_err(|_| GcError::parse(line_number, format!("Invalid wire: '{}'", token)))
+        })
+        .collect::<Result<Vec<WireId>>>()?;
+    Ok(PendingConditional {
+        line_number,
+        tag: tokens[tokens.len() - 1].to_string(),
+        inputs: wires[..num_inputs].to_vec(),
+        outputs: wires[num_inputs..].to_vec(),
+    })
+}
+
+fn value_of(values: &HashMap<WireId, bool>, wire: WireId) -> Result<bool> {
+    values
+        .get(&wire)
+        .copied()
+        .ok_or_else(|| GcError::missing_wire(wire, format!("No value for wire {}", wire)))
+}
+
+fn plain_gate(gate: &Gate, values: &HashMap<WireId, bool>) -> Result<bool> {
+    Ok(match gate.gate_type {
+        GateType::Xor => {
+            value_of(values, gate.input_wire_1)? ^ value_of(values, gate.input_wire_2)?
+        }
+        GateType::And => {
+            value_of(values, gate.input_wire_1)? & value_of(values, gate.input_wire_2)?
+        }
+        GateType::Buf => value_of(values, gate.input_wire_1)?,
+        GateType::Const(value) => value,
+    })
+}
+
+/// Garbled tables of a stacked circuit
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StackedCost {
+    /// Tables of the AND gates outside conditionals
+    pub and_tables: u64,
+    /// Tables of the conditionals' branches once stacked: the largest branch of each
+    pub stacked_tables: u64,
+    /// Tables the conditionals' branches would take garbled side by side
+    pub unstacked_tables: u64,
+    /// Tables moving labels into and out of the branches, one per branch and input or output
+    pub interface_tables: u64,
+}
+
+/// Result of garbling a stacked circuit
+#[derive(Debug)]
+pub struct StackedGarbling {
+    /// Labels of the primary inputs and outputs, as for an ordinary circuit
+    pub wire_labels: WireLabels,
+    /// Everything the evaluator needs besides its input labels
+    pub material: StackedMaterial,
+}
+
+/// Garbled material of a stacked circuit
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct StackedMaterial {
+    /// Fingerprint of the circuit, from [`StackedCircuit::digest`]
+    pub circuit_digest: [u8; 32],
+    /// Garbling run the material belongs to
+    pub session_id: SessionId,
+    /// Tables of the AND gates outside conditionals, in circuit order
+    pub and_tables: Vec<GarbledTable>,
+    /// Material of every conditional, in circuit order
+    pub conditionals: Vec<ConditionalMaterial>,
+}
+
+/// Garbled material of one conditional
+///
+/// Tables are indexed branch by branch: entry `branch * k + input` of
+/// `demux_tables` and `branch * m + output` of `mux_tables`.
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct ConditionalMaterial {
+    /// XOR of the tables of all branches, as many as the largest branch has
+    pub stacked_tables: Vec<GarbledTable>,
+    /// Per branch, its seed under the condition label for 0 and a decoy seed
+    /// under the label for 1, ordered by point-and-permute bit
+    pub seed_rows: Vec<[[u8; 32]; 2]>,
+    /// Per branch and input, the branch's input label under the condition
+    /// and input labels, or a decoy if the condition is 0
+    pub demux_tables: Vec<GarbledTable>,
+    /// Per branch and output, the conditional's output label under the
+    /// condition and branch output labels
+    pub mux_tables: Vec<GarbledTable>,
+}
+
+impl StackedMaterial {
+    /// Save the material in its binary format
+    ///
+    /// Layout: [`STACKED_MATERIAL_MAGIC`], the format version as a
+    /// little-endian `u32`, then the bincode-encoded material.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::with_capacity(BUFFER_SIZE, file);
+        writer.write_all(&STACKED_MATERIAL_MAGIC)?;
+        writer.write_all(&STACKED_MATERIAL_VERSION.to_le_bytes())?;
+        bincode::encode_into_std_write(self, &mut writer, bincode::config::standard())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load material saved with [`save`](Self::save)
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        let mut reader = BufReader::with_capacity(BUFFER_SIZE, file);
+
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        if header[..4] != STACKED_MATERIAL_MAGIC {
+            return Err(GcError::invalid_format(
+                "stacked material file",
+                "bad magic bytes",
+            ));
+        }
+        let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if version != STACKED_MATERIAL_VERSION {
+            return Err(GcError::FormatVersionMismatch {
+                format: "stacked material",
+                found: version,
+                expected: STACKED_MATERIAL_VERSION,
+            });
+        }
+
+        let material = bincode::decode_from_std_read(&mut reader, bincode::config::standard())?;
+        Ok(material)
+    }
+}
+
+/// What a pad encrypts, for domain separation
+#[derive(Debug, Clone, Copy)]
+enum PadPurpose {
+    Seed = 1,
+    Demux = 2,
+    Mux = 3,
+}
+
+/// Pad keyed by `labels` for one entry of one conditional's material
+fn pad(
+    purpose: PadPurpose,
+    labels: &[WireLabel],
+    conditional: usize,
+    branch: usize,
+    index: usize,
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(PAD_DOMAIN);
+    hasher.update([purpose as u8]);
+    for label in labels {
+        hasher.update(label.as_bytes());
+    }
+    for position in [conditional, branch, index] {
+        hasher.update((position as u64).to_le_bytes());
+    }
+    hasher.finalize().into()
+}
+
+/// 16-byte pad for a label, see [`pad`]
+fn label_pad(
+    purpose: PadPurpose,
+    labels: &[WireLabel],
+    conditional: usize,
+    branch: usize,
+    index: usize,
+) -> WireLabel {
+    WireLabel::new(
+        pad(purpose, labels, conditional, branch, index)[..16]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+fn xor_seed(seed: &[u8; 32], pad: &[u8; 32]) -> [u8; 32] {
+    std::array::from_fn(|i| seed[i] ^ pad[i])
+}
+
+/// Label of `bit` on a wire whose label for 0 is `label_0`
+fn label_for(label_0: WireLabel, bit: bool, delta: &WireLabel) -> WireLabel {
+    if bit { label_0.xor(delta) } else { label_0 }
+}
+
+/// Row of a 4-row table picked by the point-and-permute bits of two labels
+fn row(first: &WireLabel, second: &WireLabel) -> usize {
+    2 * first.permute_bit() as usize + second.permute_bit() as usize
+}
+
+/// Label held for `wire`
+fn label_of(labels: &HashMap<WireId, WireLabel>, wire: WireId) -> Result<WireLabel> {
+    labels
+        .get(&wire)
+        .copied()
+        .ok_or_else(|| GcError::missing_wire(wire, format!("No label for wire {}", wire)))
+}
+
+/// Garble a gate, storing the label for 0 of its output
+fn garble_gate(
+    gate: &Gate,
+    labels: &mut HashMap<WireId, WireLabel>,
+    delta: &WireLabel,
+    rng: &mut ChaCha12Rng,
+    tables: &mut Vec<GarbledTable>,
+) -> Result<()> {
+    let output = match gate.gate_type {
+        GateType::Xor => {
+            label_of(labels, gate.input_wire_1)?.xor(&label_of(labels, gate.input_wire_2)?)
+        }
+        GateType::And => {
+            let input_1 = label_of(labels, gate.input_wire_1)?;
+            let input_2 = label_of(labels, gate.input_wire_2)?;
+            let output = WireLabel::random(rng);
+            tables.push(garble_and_gate(
+                &[[input_1, input_1.xor(delta)], [input_2, input_2.xor(delta)]],
+                &[output, output.xor(delta)],
+            ));
+            output
+        }
+        GateType::Buf => label_of(labels, gate.input_wire_1)?,
+        GateType::Const(value) => label_for(constant_label(gate.output_wire), value, delta),
+    };
+    labels.insert(gate.output_wire, output);
+    Ok(())
+}
+
+/// A branch garbled from its own seed
+struct BranchGarbling {
+    delta: WireLabel,
+    /// Labels for 0 of the branch's inputs
+    input_labels: Vec<WireLabel>,
+    /// Labels for 0 of the branch's outputs
+    output_labels: Vec<WireLabel>,
+    tables: Vec<GarbledTable>,
+}
+
+/// Garble a branch with a delta and labels drawn from `seed` alone
+///
+/// The evaluator regarbles every branch whose seed it learns, so nothing
+/// else may go into it.
+fn garble_branch(
+    branch: &Circuit,
+    num_inputs: usize,
+    num_outputs: usize,
+    seed: &[u8; 32],
+) -> Result<BranchGarbling> {
+    let mut rng = ChaCha12Rng::from_seed(*seed);
+    let delta = WireLabel::random_delta(&mut rng);
+    let input_labels: Vec<WireLabel> = (0..num_inputs)
+        .map(|_| WireLabel::random(&mut rng))
+        .collect();
+    let mut labels: HashMap<WireId, WireLabel> = input_labels
+        .iter()
+        .enumerate()
+        .map(|(wire, &label)| (wire as WireId, label))
+        .collect();
+    let mut tables = Vec::new();
+    for gate in &branch.gates {
+        garble_gate(gate, &mut labels, &delta, &mut rng, &mut tables)?;
+    }
+    let output_labels = branch_output_wires(branch, num_outputs)
+        .map(|wire| label_of(&labels, wire))
+        .collect::<Result<_>>()?;
+    Ok(BranchGarbling {
+        delta,
+        input_labels,
+        output_labels,
+        tables,
+    })
+}
+
+/// Evaluate a branch on one label per input with the given tables
+fn evaluate_branch(
+    branch: &Circuit,
+    input_labels: &[WireLabel],
+    num_outputs: usize,
+    tables: &[GarbledTable],
+) -> Result<Vec<WireLabel>> {
+    let mut labels: HashMap<WireId, WireLabel> = input_labels
+        .iter()
+        .enumerate()
+        .map(|(wire, &label)| (wire as WireId, label))
+        .collect();
+    let mut tables = tables.iter();
+    for gate in &branch.gates {
+        let output = evaluate_gate(
+            gate,
+            |wire| label_of(&labels, wire),
+            || {
+                tables.next().ok_or_else(|| {
+                    GcError::InvalidCircuit("Branch has more AND gates than tables".to_string())
+                })
+            },
+        )?;
+        labels.insert(gate.output_wire, output);
+    }
+    branch_output_wires(branch, num_outputs)
+        .map(|wire| label_of(&labels, wire))
+        .collect()
+}
+
+/// Tables for evaluating branch `guess` as if it were the one running
+///
+/// The stack with the tables regarbled for every other branch XORed out.
+/// For the branch that runs, these are exactly its tables; for any other
+/// branch they are garbage.
+fn unstack(
+    stacked: &[GarbledTable],
+    regarbled: &[&[GarbledTable]],
+    guess: usize,
+) -> Vec<GarbledTable> {
+    let mut tables = stacked[..regarbled[guess].len()].to_vec();
+    for (branch, branch_tables) in regarbled.iter().enumerate() {
+        if branch == guess {
+            continue;
+        }
+        for (table, other) in tables.iter_mut().zip(branch_tables.iter()) {
+            xor_table(table, other);
+        }
+    }
+    tables
+}
+
+fn xor_table(table: &mut GarbledTable, other: &GarbledTable) {
+    for (ciphertext, other) in table.ciphertexts.iter_mut().zip(&other.ciphertexts) {
+        for (byte, other) in ciphertext.iter_mut().zip(other) {
+            *byte ^= other;
+        }
+    }
+}
+
+/// Decrypt the row of a 4-row table picked by two labels
+fn decrypt_row(
+    table: &GarbledTable,
+    purpose: PadPurpose,
+    keys: [WireLabel; 2],
+    conditional: usize,
+    branch: usize,
+    index: usize,
+) -> WireLabel {
+    WireLabel::new(table.ciphertexts[row(&keys[0], &keys[1])]).xor(&label_pad(
+        purpose,
+        &keys,
+        conditional,
+        branch,
+        index,
+    ))
+}
+
+/// Encrypt `plaintext` into the row of a 4-row table picked by two labels
+fn encrypt_row(
+    table: &mut GarbledTable,
+    plaintext: WireLabel,
+    purpose: PadPurpose,
+    keys: [WireLabel; 2],
+    conditional: usize,
+    branch: usize,
+    index: usize,
+) {
+    table.ciphertexts[row(&keys[0], &keys[1])] = *plaintext
+        .xor(&label_pad(purpose, &keys, conditional, branch, index))
+        .as_bytes();
+}
+
+/// Garble a stacked circuit
+///
+/// Gates outside conditionals are garbled as usual. Every branch of a
+/// conditional is garbled from its own seed, with its own delta, and the
+/// branches' tables are XORed into one stack as long as the largest branch,
+/// so a conditional costs the tables of its largest branch instead of the
+/// sum of all of them.
+///
+/// The evaluator learns the seed of every branch whose condition is 0 and
+/// a decoy for the one that runs, without telling them apart. It regarbles
+/// every branch from what it learned and evaluates each one on the stack
+/// with the others XORed out: only the branch that runs gets its real tables
+/// and inputs. The garbler replays the evaluator on the other branches to
+/// know the garbage labels they produce, and the output tables cancel them,
+/// so the evaluator ends up with the output labels of the branch that runs
+/// without learning which one it was.
+///
+/// # Arguments
+/// * `circuit` - The stacked circuit
+/// * `seed_data` - 32 bytes of random seed for CSPRNG
+/// * `partition` - Which primary inputs belong to the garbler; all go through OT if `None`
+///
+/// # Returns
+/// * `Ok(StackedGarbling)` - Wire labels of the primary inputs and outputs, and the material
+/// * `Err(GcError)` - A wire is read before it is written, or a range of the partition covers no primary input
+pub fn garble_stacked(
+    circuit: &StackedCircuit,
+    seed_data: &[u8; 32],
+    partition: Option<&InputPartition>,
+) -> Result<StackedGarbling> {
+    let mut rng = ChaCha12Rng::from_seed(*seed_data);
+    let delta = WireLabel::random_delta(&mut rng);
+    let input_wires = circuit.input_wires();
+    let mut labels: HashMap<WireId, WireLabel> = input_wires
+        .iter()
+        .map(|&wire| (wire, WireLabel::random(&mut rng)))
+        .collect();
+    let input_labels = labels.clone();
+
+    let mut and_tables = Vec::new();
+    let mut conditionals = Vec::new();
+    for step in &circuit.steps {
+        match step {
+            StackedStep::Gate(gate) => {
+                garble_gate(gate, &mut labels, &delta, &mut rng, &mut and_tables)?
+            }
+            StackedStep::Conditional(conditional) => {
+                let (material, output_labels) =
+                    garble_conditional(conditional, conditionals.len(), &labels, &delta, &mut rng)?;
+                labels.extend(conditional.output_wires.iter().copied().zip(output_labels));
+                conditionals.push(material);
+            }
+        }
+    }
+
+    let output_labels = circuit
+        .output_wires()
+        .into_iter()
+        .map(|wire| Ok((wire, label_of(&labels, wire)?)))
+        .collect::<Result<_>>()?;
+    let garbler_input_wires = match partition {
+        Some(partition) => partition.garbler_input_wires(&input_wires)?,
+        None => Vec::new(),
+    };
+    let session_id = SessionId::from_seed(seed_data);
+    Ok(StackedGarbling {
+        wire_labels: WireLabels {
+            input_labels,
+            output_labels,
+            delta,
+            garbler_input_wires,
+            session_id,
+        },
+        material: StackedMaterial {
+            circuit_digest: circuit.digest(),
+            session_id,
+            and_tables,
+            conditionals,
+        },
+    })
+}
+
+/// Garble one conditional, returning its material and the labels for 0 of its outputs
+fn garble_conditional(
+    conditional: &Conditional,
+    index: usize,
+    labels: &HashMap<WireId, WireLabel>,
+    delta: &WireLabel,
+    rng: &mut ChaCha12Rng,
+) -> Result<(ConditionalMaterial, Vec<WireLabel>)> {
+    let num_branches = conditional.branches.len();
+    let (num_inputs, num_outputs) = (
+        conditional.input_wires.len(),
+        conditional.output_wires.len(),
+    );
+    let mut draw_seed = || {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        seed
+    };
+    let seeds: Vec<[u8; 32]> = (0..num_branches).map(|_| draw_seed()).collect();
+    let decoy_seeds: Vec<[u8; 32]> = (0..num_branches).map(|_| draw_seed()).collect();
+    let garbage_inputs: Vec<Vec<WireLabel>> = (0..num_branches)
+        .map(|_| (0..num_inputs).map(|_| WireLabel::random(rng)).collect())
+        .collect();
+    let output_labels: Vec<WireLabel> = (0..num_outputs).map(|_| WireLabel::random(rng)).collect();
+
+    let garblings = conditional
+        .branches
+        .iter()
+        .zip(&seeds)
+        .map(|(branch, seed)| garble_branch(branch, num_inputs, num_outputs, seed))
+        .collect::<Result<Vec<_>>>()?;
+    let mut stacked_tables = vec![
+        GarbledTable {
+            ciphertexts: [[0u8; 16]; 4]
+        };
+        conditional.stacked_tables()
+    ];
+    for garbling in &garblings {
+        for (table, branch_table) in stacked_tables.iter_mut().zip(&garbling.tables) {
+            xor_table(table, branch_table);
+        }
+    }
+
+    let conditions = conditional
+        .condition_wires
+        .iter()
+        .map(|&wire| label_of(labels, wire))
+        .collect::<Result<Vec<_>>>()?;
+    let inputs = conditional
+        .input_wires
+        .iter()
+        .map(|&wire| label_of(labels, wire))
+        .collect::<Result<Vec<_>>>()?;
+
+    // A branch's seed is revealed when its condition is 0, a decoy when it is 1
+    let seed_rows = (0..num_branches)
+        .map(|branch| {
+            let mut rows = [[0u8; 32]; 2];
+            for condition in [false, true] {
+                let condition_label = label_for(conditions[branch], condition, delta);
+                let seed = if condition {
+                    &decoy_seeds[branch]
+                } else {
+                    &seeds[branch]
+                };
+                rows[condition_label.permute_bit() as usize] = xor_seed(
+                    seed,
+                    &pad(PadPurpose::Seed, &[condition_label], index, branch, 0),
+                );
+            }
+            rows
+        })
+        .collect();
+
+    // The branch that runs gets its input labels, the others garbage
+    let mut demux_tables = Vec::with_capacity(num_branches * num_inputs);
+    for (branch, garbling) in garblings.iter().enumerate() {
+        for input in 0..num_inputs {
+            let mut table = GarbledTable {
+                ciphertexts: [[0u8; 16]; 4],
+            };
+            for condition in [false, true] {
+                for bit in [false, true] {
+                    let plaintext = if condition {
+                        label_for(garbling.input_labels[input], bit, &garbling.delta)
+                    } else {
+                        garbage_inputs[branch][input]
+                    };
+                    let keys = [
+                        label_for(conditions[branch], condition, delta),
+                        label_for(inputs[input], bit, delta),
+                    ];
+                    encrypt_row(
+                        &mut table,
+                        plaintext,
+                        PadPurpose::Demux,
+                        keys,
+                        index,
+                        branch,
+                        input,
+                    );
+                }
+            }
+            demux_tables.push(table);
+        }
+    }
+
+    // Rows for condition 0 are random: whatever the evaluator decrypts from
+    // them is garbage the rows for condition 1 of the running branch cancel
+    let mut mux_tables: Vec<GarbledTable> = (0..num_branches * num_outputs)
+        .map(|_| {
+            let mut table = GarbledTable {
+                ciphertexts: [[0u8; 16]; 4],
+            };
+            for ciphertext in &mut table.ciphertexts {
+                rng.fill_bytes(ciphertext);
+            }
+            table
+        })
+        .collect();
+
+    // Replay the evaluator for every branch that may run: the garbage it
+    // gets from the mux tables of the other branches, per output
+    let decoy_tables = conditional
+        .branches
+        .iter()
+        .zip(&decoy_seeds)
+        .map(|(branch, seed)| Ok(garble_branch(branch, num_inputs, num_outputs, seed)?.tables))
+        .collect::<Result<Vec<_>>>()?;
+    let mut corrections = vec![vec![WireLabel::new([0u8; 16]); num_outputs]; num_branches];
+    for (active, correction) in corrections.iter_mut().enumerate() {
+        let regarbled: Vec<&[GarbledTable]> = (0..num_branches)
+            .map(|branch| {
+                if branch == active {
+                    decoy_tables[branch].as_slice()
+                } else {
+                    garblings[branch].tables.as_slice()
+                }
+            })
+            .collect();
+        for (branch, branch_circuit) in conditional.branches.iter().enumerate() {
+            if branch == active {
+                continue;
+            }
+            let tables = unstack(&stacked_tables, &regarbled, branch);
+            let garbage_outputs = evaluate_branch(
+                branch_circuit,
+                &garbage_inputs[branch],
+                num_outputs,
+                &tables,
+            )?;
+            for (output, garbage) in garbage_outputs.into_iter().enumerate() {
+                let share = decrypt_row(
+                    &mux_tables[branch * num_outputs + output],
+                    PadPurpose::Mux,
+                    [conditions[branch], garbage],
+                    index,
+                    branch,
+                    output,
+                );
+                correction[output] = correction[output].xor(&share);
+            }
+        }
+    }
+
+    // Rows for condition 1 translate the running branch's output labels
+    for (branch, garbling) in garblings.iter().enumerate() {
+        let condition_label = conditions[branch].xor(delta);
+        for output in 0..num_outputs {
+            for bit in [false, true] {
+                let plaintext =
+                    label_for(output_labels[output], bit, delta).xor(&corrections[branch][output]);
+                let keys = [
+                    condition_label,
+                    label_for(garbling.output_labels[output], bit, &garbling.delta),
+                ];
+                encrypt_row(
+                    &mut mux_tables[branch * num_outputs + output],
+                    plaintext,
+                    PadPurpose::Mux,
+                    keys,
+                    index,
+                    branch,
+                    output,
+                );
+            }
+        }
+    }
+
+    Ok((
+        ConditionalMaterial {
+            stacked_tables,
+            seed_rows,
+            demux_tables,
+            mux_tables,
+        },
+        output_labels,
+    ))
+}
+
+/// Evaluate a garbled stacked circuit
+///
+/// # Arguments
+/// * `circuit` - The stacked circuit the material was garbled for
+/// * `material` - The garbler's material
+/// * `ot_result` - A label for every primary input
+///
+/// # Returns
+/// * `Ok(EvaluationResult)` - The label of every primary output; decode them with the garbler's [`DecodingInfo`](crate::decoder::DecodingInfo)
+/// * `Err(GcError)` - The material belongs to another circuit or session, does not have the circuit's shape, or an input label is missing
+pub fn evaluate_stacked(
+    circuit: &StackedCircuit,
+    material: &StackedMaterial,
+    ot_result: &OTResult,
+) -> Result<EvaluationResult> {
+    if material.circuit_digest != circuit.digest() {
+        return Err(GcError::CircuitMismatch);
+    }
+    ot_result
+        .session_id
+        .check(&material.session_id, "OT result")?;
+    let cost = circuit.cost();
+    if material.and_tables.len() as u64 != cost.and_tables {
+        return Err(GcError::TableCountMismatch {
+            expected: cost.and_tables,
+            found: material.and_tables.len() as u64,
+            message: format!(
+                "Stacked material has {} tables for the {} AND gates outside conditionals",
+                material.and_tables.len(),
+                cost.and_tables
+            ),
+        });
+    }
+    if material.conditionals.len() != circuit.conditionals().count() {
+        return Err(GcError::invalid_format(
+            "stacked material",
+            format!(
+                "material for {} conditionals, the circuit has {}",
+                material.conditionals.len(),
+                circuit.conditionals().count()
+            ),
+        ));
+    }
+
+    let mut labels = HashMap::new();
+    for wire in circuit.input_wires() {
+        let label = ot_result.input_label(wire).ok_or_else(|| {
+            GcError::missing_wire(wire, format!("No input label for wire {}", wire))
+        })?;
+        labels.insert(wire, label);
+    }
+
+    let mut and_tables = material.and_tables.iter();
+    let mut conditional_materials = material.conditionals.iter().enumerate();
+    for step in &circuit.steps {
+        match step {
+            StackedStep::Gate(gate) => {
+                let output = evaluate_gate(
+                    gate,
+                    |wire| label_of(&labels, wire),
+                    || Ok(and_tables.next().expect("table count checked")),
+                )?;
+                labels.insert(gate.output_wire, output);
+            }
+            StackedStep::Conditional(conditional) => {
+                let (index, conditional_material) = conditional_materials
+                    .next()
+                    .expect("conditional count checked");
+                let outputs =
+                    evaluate_conditional(conditional, conditional_material, index, &labels)?;
+                labels.extend(conditional.output_wires.iter().copied().zip(outputs));
+            }
+        }
+    }
+
+    let output_results = circuit
+        .output_wires()
+        .into_iter()
+        .map(|wire| {
+            Ok((
+                wire,
+                OutputResult {
+                    label: label_of(&labels, wire)?,
+                    bit_value: None,
+                },
+            ))
+        })
+        .collect::<Result<_>>()?;
+    Ok(EvaluationResult {
+        output_results,
+        stats: None,
+    })
+}
+
+/// Evaluate one conditional, returning the labels of its outputs
+fn evaluate_conditional(
+    conditional: &Conditional,
+    material: &ConditionalMaterial,
+    index: usize,
+    labels: &HashMap<WireId, WireLabel>,
+) -> Result<Vec<WireLabel>> {
+    let num_branches = conditional.branches.len();
+    let (num_inputs, num_outputs) = (
+        conditional.input_wires.len(),
+        conditional.output_wires.len(),
+    );
+    if material.stacked_tables.len() != conditional.stacked_tables()
+        || material.seed_rows.len() != num_branches
+        || material.demux_tables.len() != num_branches * num_inputs
+        || material.mux_tables.len() != num_branches * num_outputs
+    {
+        return Err(GcError::invalid_format(
+            "stacked material",
+            format!(
+                "material of conditional '{}' does not fit its branches",
+                conditional.tag
+            ),
+        ));
+    }
+    let conditions = conditional
+        .condition_wires
+        .iter()
+        .map(|&wire| label_of(labels, wire))
+        .collect::<Result<Vec<_>>>()?;
+    let inputs = conditional
+        .input_wires
+        .iter()
+        .map(|&wire| label_of(labels, wire))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Regarble every branch from the seed its condition label reveals
+    let regarbled = conditional
+        .branches
+        .iter()
+        .enumerate()
+        .map(|(branch, branch_circuit)| {
+            let condition_label = conditions[branch];
+            let seed = xor_seed(
+                &material.seed_rows[branch][condition_label.permute_bit() as usize],
+                &pad(PadPurpose::Seed, &[condition_label], index, branch, 0),
+            );
+            Ok(garble_branch(branch_circuit, num_inputs, num_outputs, &seed)?.tables)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let regarbled: Vec<&[GarbledTable]> = regarbled.iter().map(Vec::as_slice).collect();
+
+    let mut outputs = vec![WireLabel::new([0u8; 16]); num_outputs];
+    for (branch, branch_circuit) in conditional.branches.iter().enumerate() {
+        let branch_inputs: Vec<WireLabel> = (0..num_inputs)
+            .map(|input| {
+                decrypt_row(
+                    &material.demux_tables[branch * num_inputs + input],
+                    PadPurpose::Demux,
+                    [conditions[branch], inputs[input]],
+                    index,
+                    branch,
+                    input,
+                )
+            })
+            .collect();
+        let tables = unstack(&material.stacked_tables, &regarbled, branch);
+        let branch_outputs = evaluate_branch(branch_circuit, &branch_inputs, num_outputs, &tables)?;
+        for (output, branch_output) in branch_outputs.into_iter().enumerate() {
+            let share = decrypt_row(
+                &material.mux_tables[branch * num_outputs + output],
+                PadPurpose::Mux,
+                [conditions[branch], branch_output],
+                index,
+                branch,
+                output,
+            );
+            outputs[output] = outputs[output].xor(&share);
+        }
+    }
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::{DecodingInfo, decode_outputs};
+    use crate::ot_simulation::select_inputs;
+    use tempfile::NamedTempFile;
+
+    /// Inputs 0..4, conditions 4..7; a three-way conditional between outer gates
+    const CIRCUIT: &str = "\
+5 13
+2 1 0 1 7 AND
+6 2 4 5 6 7 2 3 8 9 COND f
+2 1 8 9 10 XOR
+2 1 10 0 11 AND
+2 1 8 3 12 AND
+
+BRANCH f
+2 5
+2 1 0 1 3 AND
+2 1 3 2 4 AND
+
+BRANCH f
+3 6
+2 1 0 2 3 XOR
+1 1 1 4 EQ
+2 1 3 1 5 AND
+
+BRANCH f
+2 5
+2 1 0 1 3 XOR
+1 1 2 4 EQW
+";
+
+    fn run(circuit: &StackedCircuit, inputs: &HashMap<WireId, bool>) -> HashMap<WireId, bool> {
+        let garbling = garble_stacked(circuit, &[7u8; 32], None).unwrap();
+        let ot_result = select_inputs(&garbling.wire_labels, inputs).unwrap();
+        let result = evaluate_stacked(circuit, &garbling.material, &ot_result).unwrap();
+        let decoded = decode_outputs(
+            &result,
+            &DecodingInfo::from_wire_labels(&garbling.wire_labels),
+        )
+        .unwrap();
+        decoded
+            .output_results
+            .into_iter()
+            .map(|(wire, output)| (wire, output.bit_value.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let circuit = StackedCircuit::parse(CIRCUIT).unwrap();
+        assert_eq!(circuit.steps.len(), 5);
+        let conditional = circuit.conditionals().next().unwrap();
+        assert_eq!(conditional.condition_wires, vec![4, 5, 6]);
+        assert_eq!(conditional.input_wires, vec![7, 2, 3]);
+        assert_eq!(conditional.output_wires, vec![8, 9]);
+        assert_eq!(conditional.branches.len(), 3);
+        assert_eq!(circuit.input_wires(), vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(circuit.output_wires(), vec![11, 12]);
+    }
+
+    #[test]
+    fn test_stacked_cost_is_largest_branch() {
+        let circuit = StackedCircuit::parse(CIRCUIT).unwrap();
+        let cost = circuit.cost();
+        assert_eq!(cost.and_tables, 3);
+        assert_eq!(cost.stacked_tables, 2);
+        assert_eq!(cost.unstacked_tables, 3);
+        assert_eq!(cost.interface_tables, 15);
+
+        let garbling = garble_stacked(&circuit, &[1u8; 32], None).unwrap();
+        let material = &garbling.material.conditionals[0];
+        assert_eq!(material.stacked_tables.len(), 2);
+        assert_eq!(material.demux_tables.len(), 9);
+        assert_eq!(material.mux_tables.len(), 6);
+    }
+
+    #[test]
+    fn test_garbled_matches_plain_for_every_branch() {
+        let circuit = StackedCircuit::parse(CIRCUIT).unwrap();
+        for active in 0..3 {
+            for data in 0..16u8 {
+                let mut inputs: HashMap<WireId, bool> =
+                    (0..4).map(|wire| (wire, data >> wire & 1 == 1)).collect();
+                for branch in 0..3 {
+                    inputs.insert(4 + branch, branch == active as WireId);
+                }
+                let expected = circuit.evaluate_plain(&inputs).unwrap();
+                assert_eq!(
+                    run(&circuit, &inputs),
+                    expected,
+                    "branch {}, data {}",
+                    active,
+                    data
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_two_branches_with_partition() {
+        let circuit = StackedCircuit::parse(
+            "1 5\n3 1 0 1 2 3 COND g\nBRANCH g\n1 2\n1 1 0 1 EQW\nBRANCH g\n1 2\n1 1 1 1 EQ\n",
+        )
+        .unwrap();
+        let partition = InputPartition::parse("0").unwrap();
+        let garbling = garble_stacked(&circuit, &[3u8; 32], Some(&partition)).unwrap();
+        assert_eq!(garbling.wire_labels.garbler_input_wires, vec![0]);
+        for (condition, data, expected) in [
+            (true, false, false),
+            (true, true, true),
+            (false, false, true),
+        ] {
+            let inputs = HashMap::from([(0, condition), (1, !condition), (2, data)]);
+            assert_eq!(run(&circuit, &inputs), HashMap::from([(3, expected)]));
+        }
+    }
+
+    #[test]
+    fn test_plain_needs_one_condition() {
+        let circuit = StackedCircuit::parse(CIRCUIT).unwrap();
+        let mut inputs: HashMap<WireId, bool> = (0..7).map(|wire| (wire, false)).collect();
+        assert!(matches!(
+            circuit.evaluate_plain(&inputs),
+            Err(GcError::InvalidArgument(_))
+        ));
+        inputs.insert(4, true);
+        inputs.insert(6, true);
+        assert!(matches!(
+            circuit.evaluate_plain(&inputs),
+            Err(GcError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        // A single branch
+        assert!(matches!(
+            StackedCircuit::parse("1 3\n2 1 0 1 2 COND h\nBRANCH h\n1 2\n2 1 0 0 1 XOR\n"),
+            Err(GcError::InvalidCircuit(_))
+        ));
+        // Branch sections for an unknown tag
+        let orphan = "1 3\n2 1 0 1 2 AND\nBRANCH h\n1 1\n2 1 0 0 0 XOR\n";
+        assert!(matches!(
+            StackedCircuit::parse(orphan),
+            Err(GcError::InvalidCircuit(_))
+        ));
+        // A conditional without branches
+        assert!(matches!(
+            StackedCircuit::parse("1 4\n3 1 0 1 2 3 COND h\n"),
+            Err(GcError::Parse { .. })
+        ));
+        // A branch reading a wire that is not one of its inputs
+        let reads_unwritten =
+            "1 5\n3 1 0 1 2 3 COND h\nBRANCH h\n1 3\n2 1 0 1 2 XOR\nBRANCH h\n1 2\n2 1 0 0 1 XOR\n";
+        assert!(matches!(
+            StackedCircuit::parse(reads_unwritten),
+            Err(GcError::InvalidCircuit(_))
+        ));
+        // Wrong number of wires on the conditional line
+        assert!(matches!(
+            StackedCircuit::parse("1 5\n3 1 0 1 2 COND h\n"),
+            Err(GcError::Parse { .. })
+        ));
+        // A branch with fewer gates than its header declares
+        let short_branch =
+            "1 5\n3 1 0 1 2 3 COND h\nBRANCH h\n2 2\n2 1 0 0 1 XOR\nBRANCH h\n1 2\n2 1 0 0 1 XOR\n";
+        assert!(matches!(
+            StackedCircuit::parse(short_branch),
+            Err(GcError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_outer_lines_after_branches_are_rejected() {
+        let branches = "BRANCH h\n1 2\n2 1 0 0 1 XOR\nBRANCH h\n1 2\n2 1 0 0 1 AND\n";
+        let circuit = format!("2 6\n3 1 0 1 2 3 COND h\n2 1 3 2 4 XOR\n{}", branches);
+        assert!(StackedCircuit::parse(&circuit).is_ok());
+
+        // The same outer gate after the branch sections would otherwise
+        // become a gate of the last branch
+        let gate_after = format!("2 6\n3 1 0 1 2 3 COND h\n{}2 1 3 2 4 XOR\n", branches);
+        assert!(matches!(
+            StackedCircuit::parse(&gate_after),
+            Err(GcError::Parse { ref message, .. }) if message.contains("before the branch sections")
+        ));
+
+        let conditional_after = format!("1 6\n{}3 1 0 1 2 3 COND h\n", branches);
+        assert!(matches!(
+            StackedCircuit::parse(&conditional_after),
+            Err(GcError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_evaluate_rejects_other_circuit() {
+        let circuit = StackedCircuit::parse(CIRCUIT).unwrap();
+        let other =
+            StackedCircuit::parse(&CIRCUIT.replace("2 1 8 3 12 AND", "2 1 8 2 12 AND")).unwrap();
+        let garbling = garble_stacked(&circuit, &[5u8; 32], None).unwrap();
+        let inputs: HashMap<WireId, bool> = (0..7).map(|wire| (wire, wire == 4)).collect();
+        let ot_result = select_inputs(&garbling.wire_labels, &inputs).unwrap();
+        assert!(matches!(
+            evaluate_stacked(&other, &garbling.material, &ot_result),
+            Err(GcError::CircuitMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_material_save_load() {
+        let circuit = StackedCircuit::parse(CIRCUIT).unwrap();
+        let garbling = garble_stacked(&circuit, &[9u8; 32], None).unwrap();
+        let file = NamedTempFile::new().unwrap();
+        garbling.material.save(file.path()).unwrap();
+        let loaded = StackedMaterial::load(file.path()).unwrap();
+        assert_eq!(loaded.circuit_digest, garbling.material.circuit_digest);
+        assert_eq!(loaded.session_id, garbling.material.session_id);
+        assert_eq!(
+            loaded.conditionals[0].seed_rows,
+            garbling.material.conditionals[0].seed_rows
+        );
+
+        let inputs: HashMap<WireId, bool> = (0..7).map(|wire| (wire, wire == 5)).collect();
+        let ot_result = select_inputs(&garbling.wire_labels, &inputs).unwrap();
+        let result = evaluate_stacked(&circuit, &loaded, &ot_result).unwrap();
+        assert_eq!(result.output_results.len(), 2);
+
+        std::fs::write(file.path(), b"XXXX\x01\x00\x00\x00").unwrap();
+        assert!(matches!(
+            StackedMaterial::load(file.path()),
+            Err(GcError::InvalidFormat { .. })
+        ));
+    }
+}
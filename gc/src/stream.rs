@@ -1,16 +1,44 @@
+use memmap2::Mmap;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Read};
+use std::ops::Range;
+use std::sync::Arc;
 
 /// Default buffer size
 const DEFAULT_BUFFER_SIZE: usize = 1024 * 1024 * 1024; // 1 GB
 
 /// High-performance streaming line reader
+///
+/// Reads from a [`File`] by default, but any [`Read`] source works, e.g. a
+/// byte slice holding a circuit in memory or a network stream, via
+/// [`from_reader`](Self::from_reader). A file can also be memory-mapped with
+/// [`mmap`](Self::mmap), so lines are taken straight from the mapped bytes.
 #[derive(Debug)]
-pub struct BufferedLineStream {
-    /// Buffered reader with large buffer for efficient IO
-    reader: BufReader<File>,
+pub struct BufferedLineStream<R = File> {
+    /// Where the lines come from
+    source: LineSource<R>,
     /// Reused string buffer to avoid allocations per line
     line_buffer: String,
+    /// The last line was pushed back and is returned again
+    replay: bool,
+    /// Skip blank and comment lines
+    lenient: bool,
+}
+
+/// Backing storage of a [`BufferedLineStream`]
+#[derive(Debug)]
+enum LineSource<R> {
+    /// Buffered reader with large buffer for efficient IO; lines are copied
+    /// into `line_buffer`
+    Buffered(BufReader<R>),
+    /// Read-only memory mapping of a file, read from `position` up to
+    /// `end`; `line` is the byte range of the last line returned
+    Mapped {
+        mmap: Arc<Mmap>,
+        position: usize,
+        end: usize,
+        line: Range<usize>,
+    },
 }
 
 impl BufferedLineStream {
@@ -18,16 +46,85 @@ impl BufferedLineStream {
     pub fn new(file: File) -> Self {
         Self::with_buffer_size(file, DEFAULT_BUFFER_SIZE)
     }
+}
+
+impl<R: Read> BufferedLineStream<R> {
+    /// Create a stream over any reader with default buffer size
+    pub fn from_reader(reader: R) -> Self {
+        Self::with_buffer_size(reader, DEFAULT_BUFFER_SIZE)
+    }
 
     /// Create a new stream with custom buffer size
-    pub fn with_buffer_size(file: File, buffer_size: usize) -> Self {
+    pub fn with_buffer_size(reader: R, buffer_size: usize) -> Self {
         Self {
-            reader: BufReader::with_capacity(buffer_size, file),
+            source: LineSource::Buffered(BufReader::with_capacity(buffer_size, reader)),
             // Pre-allocate reasonable line buffer (most Bristol lines < 1KB)
             line_buffer: String::with_capacity(1024),
+            replay: false,
+            lenient: false,
         }
     }
 
+    /// Create a stream over a memory mapping of `file`
+    ///
+    /// Lines are tokenized directly from the mapped bytes: no read syscalls,
+    /// no buffer allocation and no copy per line, and the page cache is
+    /// shared by every pass and process reading the same file. The stream
+    /// never reads from an `R`; where nothing else fixes it, name it as in
+    /// `BufferedLineStream::<File>::mmap(&file)`.
+    ///
+    /// # Arguments
+    /// * `file` - Circuit file, which must not be modified while the stream is in use
+    ///
+    /// # Returns
+    /// * `Ok(BufferedLineStream)` - Stream over the mapped file
+    /// * `Err(io::Error)` - The file could not be mapped
+    pub fn mmap(file: &File) -> io::Result<Self> {
+        // SAFETY: the mapping is read-only and the circuit file is not
+        // expected to be modified while it is being read.
+        let mmap = unsafe { Mmap::map(file)? };
+        #[cfg(unix)]
+        mmap.advise(memmap2::Advice::Sequential)?;
+        let end = mmap.len();
+        Ok(Self::from_mapped(Arc::new(mmap), 0..end))
+    }
+
+    /// Create a stream over the lines in `range` of a mapped file, which
+    /// must start at a line boundary
+    pub(crate) fn from_mapped(mmap: Arc<Mmap>, range: Range<usize>) -> Self {
+        Self {
+            source: LineSource::Mapped {
+                mmap,
+                position: range.start,
+                end: range.end,
+                line: range.start..range.start,
+            },
+            line_buffer: String::new(),
+            replay: false,
+            lenient: false,
+        }
+    }
+
+    /// Byte offset in the mapped file of the next line to be returned, or
+    /// `None` for a buffered stream
+    pub(crate) fn mapped_offset(&self) -> Option<usize> {
+        match &self.source {
+            LineSource::Buffered(_) => None,
+            LineSource::Mapped { line, .. } if self.replay => Some(line.start),
+            LineSource::Mapped { position, .. } => Some(*position),
+        }
+    }
+
+    /// Skip blank lines and comment lines (starting with `#` or `//`)
+    ///
+    /// Many circuit toolchains emit those, and every pass reading the stream
+    /// fails on them otherwise. Line numbers in error messages then count
+    /// only the lines that were not skipped.
+    pub fn with_lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
     /// Get the next line as a string slice (zero-copy)
     ///
     /// Returns None at EOF, or Some(Result) for each line.
@@ -38,22 +135,128 @@ impl BufferedLineStream {
     /// - Newlines are stripped from returned string
     /// - Internal buffer is reused for each line
     pub fn next_line(&mut self) -> Option<Result<&str, io::Error>> {
-        // Clear buffer but keep allocated capacity
-        self.line_buffer.clear();
-
-        match self.reader.read_line(&mut self.line_buffer) {
-            // EOF reached
-            Ok(0) => None,
-
-            // Line read successfully
-            Ok(_) => {
-                // Remove trailing newline characters (\n and \r\n)
-                let line = self.line_buffer.trim_end();
-                Some(Ok(line))
+        if !self.replay {
+            loop {
+                match self.advance() {
+                    // EOF reached
+                    Ok(false) => return None,
+
+                    // Skipped line in lenient mode
+                    Ok(true) if self.lenient && is_skippable(self.raw_line()) => {}
+
+                    // Line read successfully
+                    Ok(true) => break,
+
+                    // IO error occurred
+                    Err(e) => return Some(Err(e)),
+                }
             }
+        }
+        self.replay = false;
 
-            // IO error occurred
-            Err(e) => Some(Err(e)),
+        // Remove trailing newline characters (\n and \r\n)
+        match &self.source {
+            LineSource::Buffered(_) => Some(Ok(self.line_buffer.trim_end())),
+            LineSource::Mapped { mmap, line, .. } => Some(
+                std::str::from_utf8(&mmap[line.clone()])
+                    .map(str::trim_end)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            ),
         }
     }
+
+    /// Move to the next line, returning false at EOF
+    fn advance(&mut self) -> io::Result<bool> {
+        match &mut self.source {
+            LineSource::Buffered(reader) => {
+                // Clear buffer but keep allocated capacity
+                self.line_buffer.clear();
+                Ok(reader.read_line(&mut self.line_buffer)? > 0)
+            }
+            LineSource::Mapped {
+                mmap,
+                position,
+                end,
+                line,
+            } => {
+                if *position == *end {
+                    return Ok(false);
+                }
+                let rest = &mmap[*position..*end];
+                let length = rest.iter().position(|&byte| byte == b'\n');
+                *line = *position..*position + length.unwrap_or(rest.len());
+                *position += length.map_or(rest.len(), |length| length + 1);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Bytes of the current line, with its line ending
+    fn raw_line(&self) -> &[u8] {
+        match &self.source {
+            LineSource::Buffered(_) => self.line_buffer.as_bytes(),
+            LineSource::Mapped { mmap, line, .. } => &mmap[line.clone()],
+        }
+    }
+
+    /// Return the last line again from the next call to [`next_line`](Self::next_line)
+    ///
+    /// Lets a reader look one line ahead, e.g. to tell optional header lines
+    /// from the first gate line. Only the last line read can be pushed back.
+    pub fn push_back(&mut self) {
+        self.replay = true;
+    }
+}
+
+/// Whether lenient parsing skips a line: blank, or a `#` or `//` comment
+fn is_skippable(line: &[u8]) -> bool {
+    let line = line.trim_ascii_start();
+    line.is_empty() || line.starts_with(b"#") || line.starts_with(b"//")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// All lines of a stream, pushing the second one back once
+    fn collect_lines<R: Read>(stream: &mut BufferedLineStream<R>) -> io::Result<Vec<String>> {
+        let mut lines = Vec::new();
+        while let Some(line) = stream.next_line() {
+            lines.push(line?.to_string());
+            if lines.len() == 2 {
+                stream.push_back();
+                assert_eq!(stream.next_line().transpose()?, Some(lines[1].as_str()));
+            }
+        }
+        Ok(lines)
+    }
+
+    #[test]
+    fn test_mmap_matches_buffered() -> io::Result<()> {
+        let content = "2 5\r\n# comment\n2 1 0 1 2 XOR\n\n  // note\n2 1 2 3 4 AND";
+        let mut circuit_file = tempfile::NamedTempFile::new()?;
+        circuit_file.write_all(content.as_bytes())?;
+        circuit_file.flush()?;
+
+        for lenient in [false, true] {
+            let buffered = collect_lines(
+                &mut BufferedLineStream::from_reader(content.as_bytes()).with_lenient(lenient),
+            )?;
+            let mapped = collect_lines(
+                &mut BufferedLineStream::<File>::mmap(circuit_file.as_file())?
+                    .with_lenient(lenient),
+            )?;
+            assert_eq!(mapped, buffered);
+        }
+        let lenient = collect_lines(
+            &mut BufferedLineStream::<File>::mmap(circuit_file.as_file())?.with_lenient(true),
+        )?;
+        assert_eq!(lenient, ["2 5", "2 1 0 1 2 XOR", "2 1 2 3 4 AND"]);
+
+        let empty_file = tempfile::NamedTempFile::new()?;
+        let mut empty = BufferedLineStream::<File>::mmap(empty_file.as_file())?;
+        assert!(empty.next_line().is_none());
+        Ok(())
+    }
 }
@@ -0,0 +1,445 @@
+use crate::error::{GcError, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::channel::Channel;
+use crate::chunk::{ChunkReader, ChunkWriter};
+use crate::constants::{GARBLED_TABLE_SIZE, GARBLED_TABLES_HEADER_SIZE, GARBLING_BATCH_SIZE};
+use crate::evaluator::{GarbledTableReader, TableAccess};
+use crate::garbler::{GarbledTable, TablesHeader};
+use crate::progress::ProgressObserver;
+use crate::two_party::{TABLES_BATCH, TABLES_END, tables_batch_message, tables_end_message};
+
+/// Version of the transfer protocol, the first byte of the evaluator's request
+const TRANSFER_VERSION: u8 = 2;
+
+/// Size of the evaluator's request: version, first table wanted and the
+/// header of its partial download (all zeros when starting afresh)
+const REQUEST_SIZE: usize = 1 + 8 + GARBLED_TABLES_HEADER_SIZE;
+
+/// Garbler's answer to the request: go ahead, followed by the tables header
+const VERDICT_ACCEPT: u8 = 0;
+
+/// Garbler's answer to the request: refused, followed by the reason
+const VERDICT_REJECT: u8 = 1;
+
+/// Suffix of the file a download is written to until it is complete
+const PARTIAL_SUFFIX: &str = ".part";
+
+/// Statistics of one transfer of a garbled tables file, as seen by one party
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TransferReport {
+    /// Number of tables in the file
+    pub total_tables: u64,
+    /// Index of the first table sent in this transfer; 0 unless it resumed a download
+    pub first_table: u64,
+    /// Number of tables sent in this transfer
+    pub tables_transferred: u64,
+    /// Bytes sent, framing included
+    pub bytes_sent: u64,
+    /// Bytes received, framing included
+    pub bytes_received: u64,
+    /// Wall-clock time of the transfer in seconds
+    pub wall_time_secs: f64,
+}
+
+impl TransferReport {
+    /// Save the report as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Send a garbled tables file to an evaluator, from the table it asks for
+///
+/// Counterpart of [`receive_tables`]. The evaluator names the first table
+/// it is missing along with the header of what it has so far, and the
+/// garbler refuses unless that header is its own, so a resumed download
+/// never mixes tables of two garbling runs. Tables are then sent in batches
+/// of [`GARBLING_BATCH_SIZE`], ending with the tables header.
+///
+/// # Arguments
+/// * `channel` - Connection to the evaluator
+/// * `tables_path` - Garbled tables file with a [`TablesHeader`], plain or compressed
+/// * `progress` - Receives the number of tables sent
+///
+/// # Returns
+/// * `Ok(TransferReport)` - Statistics of the transfer
+/// * `Err(GcError)` - I/O error, the file has no header, or the request does not fit the file
+#[tracing::instrument(name = "send_tables", skip_all)]
+pub fn send_tables<S: Read + Write>(
+    channel: &mut Channel<S>,
+    tables_path: &Path,
+    progress: &dyn ProgressObserver,
+) -> Result<TransferReport> {
+    let start = Instant::now();
+    let (bytes_sent, bytes_received) = (channel.bytes_sent(), channel.bytes_received());
+    let mut reader = GarbledTableReader::open(tables_path, TableAccess::Buffered)?;
+    let header = *reader.header().ok_or_else(|| {
+        GcError::InvalidArgument(format!(
+            "{} has no tables header to resume against",
+            tables_path.display()
+        ))
+    })?;
+
+    let request = channel.receive()?;
+    let first_table = match parse_request(&request, &header) {
+        Ok(first_table) => first_table,
+        Err(reason) => {
+            let mut verdict = vec![VERDICT_REJECT];
+            verdict.extend_from_slice(reason.as_bytes());
+            channel.send(&verdict)?;
+            return Err(GcError::Protocol(format!(
+                "Refused the evaluator's request: {}",
+                reason
+            )));
+        }
+    };
+    let mut verdict = vec![VERDICT_ACCEPT];
+    verdict.extend_from_slice(&header.to_bytes());
+    channel.send(&verdict)?;
+
+    reader.skip_tables(first_table as usize)?;
+    let remaining = header.num_tables - first_table;
+    progress.start(
+        Some(remaining),
+        &format!("Sending tables from table {}", first_table),
+    );
+    let mut chunks = ChunkWriter::new();
+    let mut batch: Vec<GarbledTable> = Vec::with_capacity(GARBLING_BATCH_SIZE);
+    let mut sent = 0;
+    while sent < remaining {
+        batch.clear();
+        let batch_size = (remaining - sent).min(GARBLING_BATCH_SIZE as u64);
+        for _ in 0..batch_size {
+            batch.push(reader.next_table()?);
+        }
+        channel.send(&tables_batch_message(&mut chunks, &batch))?;
+        sent += batch_size;
+        progress.update(sent);
+    }
+    channel.send(&tables_end_message(&header))?;
+    progress.finish(&format!("Sent {} tables", sent));
+
+    let report = TransferReport {
+        total_tables: header.num_tables,
+        first_table,
+        tables_transferred: sent,
+        bytes_sent: channel.bytes_sent() - bytes_sent,
+        bytes_received: channel.bytes_received() - bytes_received,
+        wall_time_secs: start.elapsed().as_secs_f64(),
+    };
+    tracing::info!(
+        first_table,
+        tables = sent,
+        bytes_sent = report.bytes_sent,
+        "Table transfer sent"
+    );
+    Ok(report)
+}
+
+/// Download a garbled tables file from a garbler, resuming an earlier attempt
+///
+/// Tables are written to `output_path` with `.part` appended and
+/// the file is renamed to `output_path` once every table has arrived. If a
+/// partial file is left over from a broken connection, the tables it holds
+/// in full are kept and the garbler is asked for the rest, so calling this
+/// again after a disconnect picks up where the last call stopped. Batches
+/// are only written once their checksum verifies, so a batch corrupted in
+/// transit is fetched again by the next call.
+///
+/// # Arguments
+/// * `channel` - Connection to the garbler running [`send_tables`]
+/// * `output_path` - Where to write the tables file
+/// * `progress` - Receives the number of tables received
+///
+/// # Returns
+/// * `Ok(TransferReport)` - Statistics of the transfer
+/// * `Err(GcError)` - I/O error, the garbler refused the request or disconnected, a batch is corrupt (the partial file is kept in these cases), or it sent a different number of tables than its header claims
+#[tracing::instrument(name = "receive_tables", skip_all)]
+pub fn receive_tables<S: Read + Write>(
+    channel: &mut Channel<S>,
+    output_path: &Path,
+    progress: &dyn ProgressObserver,
+) -> Result<TransferReport> {
+    let start = Instant::now();
+    let (bytes_sent, bytes_received) = (channel.bytes_sent(), channel.bytes_received());
+    let partial_path = partial_path(output_path);
+    let (mut file, partial_header, first_table) = open_partial(&partial_path)?;
+
+    let mut request = Vec::with_capacity(REQUEST_SIZE);
+    request.push(TRANSFER_VERSION);
+    request.extend_from_slice(&first_table.to_le_bytes());
+    request.extend_from_slice(&partial_header.unwrap_or([0; GARBLED_TABLES_HEADER_SIZE]));
+    channel.send(&request)?;
+
+    let verdict = channel.receive()?;
+    let header = match verdict.split_first() {
+        Some((&VERDICT_ACCEPT, header)) if header.len() == GARBLED_TABLES_HEADER_SIZE => {
+            TablesHeader::from_bytes(header.try_into().unwrap())?
+        }
+        Some((&VERDICT_REJECT, reason)) => {
+            return Err(GcError::Protocol(format!(
+                "Garbler refused the transfer: {}",
+                String::from_utf8_lossy(reason)
+            )));
+        }
+        _ => {
+            return Err(GcError::Protocol(
+                "Malformed answer to the transfer request".to_string(),
+            ));
+        }
+    };
+    if partial_header.is_none() {
+        file.write_all(&header.to_bytes())?;
+    }
+    if first_table > 0 {
+        tracing::info!(first_table, "Resuming table download");
+    }
+
+    let remaining = header.num_tables - first_table;
+    progress.start(
+        Some(remaining),
+        &format!("Receiving tables from table {}", first_table),
+    );
+    // A disconnect leaves at most a partial table at the end of the file,
+    // which the next attempt cuts off
+    let mut writer = BufWriter::new(file);
+    let mut chunks = ChunkReader::new();
+    let mut received = 0;
+    loop {
+        let message = channel.receive()?;
+        match message.split_first() {
+            Some((&TABLES_BATCH, chunk)) => {
+                let tables = chunks.read_chunk(chunk)?;
+                if !tables.len().is_multiple_of(GARBLED_TABLE_SIZE) {
+                    return Err(GcError::Protocol(format!(
+                        "Batch of {} bytes does not hold whole garbled tables",
+                        tables.len()
+                    )));
+                }
+                writer.write_all(tables)?;
+                received += (tables.len() / GARBLED_TABLE_SIZE) as u64;
+                progress.update(received);
+            }
+            Some((&TABLES_END, end)) if end == header.to_bytes() => break,
+            _ => {
+                return Err(GcError::Protocol(
+                    "Malformed garbled tables message".to_string(),
+                ));
+            }
+        }
+    }
+    if received != remaining {
+        return Err(GcError::TableCountMismatch {
+            expected: remaining,
+            found: received,
+            message: format!(
+                "Garbler sent {} tables from table {}, its header promises {}",
+                received, first_table, header.num_tables
+            ),
+        });
+    }
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&partial_path, output_path)?;
+    progress.finish(&format!("Received {} tables", received));
+
+    let report = TransferReport {
+        total_tables: header.num_tables,
+        first_table,
+        tables_transferred: received,
+        bytes_sent: channel.bytes_sent() - bytes_sent,
+        bytes_received: channel.bytes_received() - bytes_received,
+        wall_time_secs: start.elapsed().as_secs_f64(),
+    };
+    tracing::info!(
+        first_table,
+        tables = received,
+        bytes_received = report.bytes_received,
+        "Table transfer received"
+    );
+    Ok(report)
+}
+
+/// Number of tables held in full by the partial download of `output_path`
+///
+/// # Returns
+/// * `Ok(Some(u64))` - Tables received so far; the next transfer starts at this index
+/// * `Ok(None)` - No partial download exists
+/// * `Err(GcError)` - I/O error
+pub fn resume_point(output_path: &Path) -> Result<Option<u64>> {
+    match std::fs::metadata(partial_path(output_path)) {
+        Ok(metadata) => Ok(Some(tables_in_partial(metadata.len()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Check the evaluator's request against the file being served
+///
+/// Returns the index of the first table to send, or why the request is refused.
+fn parse_request(request: &[u8], header: &TablesHeader) -> std::result::Result<u64, String> {
+    if request.len() != REQUEST_SIZE {
+        return Err(format!(
+            "request has {} bytes, expected {}",
+            request.len(),
+            REQUEST_SIZE
+        ));
+    }
+    if request[0] != TRANSFER_VERSION {
+        return Err(format!(
+            "evaluator speaks transfer version {}, garbler speaks {}",
+            request[0], TRANSFER_VERSION
+        ));
+    }
+    let first_table = u64::from_le_bytes(request[1..9].try_into().unwrap());
+    let partial_header = &request[9..];
+    if partial_header.iter().any(|&byte| byte != 0) && partial_header != header.to_bytes() {
+        return Err("the partial download holds tables of another garbling run".to_string());
+    }
+    if first_table > header.num_tables {
+        return Err(format!(
+            "table {} asked for, the file has {}",
+            first_table, header.num_tables
+        ));
+    }
+    Ok(first_table)
+}
+
+/// Path of the partial download of `output_path`
+fn partial_path(output_path: &Path) -> PathBuf {
+    let mut path = output_path.as_os_str().to_owned();
+    path.push(PARTIAL_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Tables held in full by a partial download of `len` bytes
+fn tables_in_partial(len: u64) -> u64 {
+    len.saturating_sub(GARBLED_TABLES_HEADER_SIZE as u64) / GARBLED_TABLE_SIZE as u64
+}
+
+/// Open the partial download, cutting off a partially received table
+///
+/// Returns the file positioned at its end, the header it starts with (none
+/// for a new or headerless file, which is started afresh) and the number of
+/// tables it holds.
+fn open_partial(path: &Path) -> Result<(File, Option<[u8; GARBLED_TABLES_HEADER_SIZE]>, u64)> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    let len = file.metadata()?.len();
+    if len < GARBLED_TABLES_HEADER_SIZE as u64 {
+        file.set_len(0)?;
+        return Ok((file, None, 0));
+    }
+    let mut header = [0u8; GARBLED_TABLES_HEADER_SIZE];
+    file.read_exact(&mut header)?;
+    let tables = tables_in_partial(len);
+    file.set_len(GARBLED_TABLES_HEADER_SIZE as u64 + tables * GARBLED_TABLE_SIZE as u64)?;
+    file.seek(SeekFrom::End(0))?;
+    Ok((file, Some(header), tables))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::garbler::GarblerBuilder;
+    use crate::progress::NoProgress;
+    use crate::sink::TableWriter;
+    use crate::stream::BufferedLineStream;
+    use crate::wire_analyzer::analyze_wire_usage;
+    use std::net::{TcpListener, TcpStream};
+
+    /// Stream that fails every write after `budget` bytes, like a dropped link
+    struct FlakyStream {
+        stream: TcpStream,
+        budget: usize,
+    }
+
+    impl Read for FlakyStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.stream.read(buf)
+        }
+    }
+
+    impl Write for FlakyStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.budget == 0 {
+                return Err(std::io::ErrorKind::ConnectionReset.into());
+            }
+            let count = buf.len().min(self.budget);
+            self.budget -= count;
+            self.stream.write(&buf[..count])
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.stream.flush()
+        }
+    }
+
+    #[test]
+    fn test_resume_after_disconnect() -> Result<()> {
+        // 10000 AND gates chained over inputs 0 and 1: several batches of tables
+        let gates = 10_000u64;
+        let mut circuit = format!("{} {}\n", gates, gates + 2);
+        for gate in 0..gates {
+            circuit.push_str(&format!("2 1 {} {} {} AND\n", gate + 1, gate % 2, gate + 2));
+        }
+        let dir = tempfile::tempdir()?;
+        let circuit_path = dir.path().join("chain.bristol");
+        std::fs::write(&circuit_path, circuit)?;
+        let tables_path = dir.path().join("chain.garbled");
+        let open = || -> Result<BufferedLineStream> {
+            Ok(BufferedLineStream::new(File::open(&circuit_path)?))
+        };
+        let wire_report = analyze_wire_usage(&mut open()?, &NoProgress)?;
+        let mut writer = TableWriter::create(&tables_path)?;
+        GarblerBuilder::new([3; 32])
+            .sink(&mut writer)
+            .garble(&mut open()?, &wire_report)?;
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let garbler_path = tables_path.clone();
+        let garbler = std::thread::spawn(move || -> Result<Vec<Result<TransferReport>>> {
+            // The first connection drops in the middle of the second batch
+            let budgets = [GARBLING_BATCH_SIZE * GARBLED_TABLE_SIZE * 3 / 2, usize::MAX];
+            let mut reports = Vec::new();
+            for budget in budgets {
+                let (stream, _) = listener.accept()?;
+                let mut channel = Channel::new(FlakyStream { stream, budget });
+                reports.push(send_tables(&mut channel, &garbler_path, &NoProgress));
+            }
+            Ok(reports)
+        });
+
+        let output_path = dir.path().join("received.garbled");
+        let mut channel = Channel::new(TcpStream::connect(address)?);
+        let error = receive_tables(&mut channel, &output_path, &NoProgress).unwrap_err();
+        assert!(matches!(error, GcError::Disconnected(_)), "{}", error);
+        let resumed_from = resume_point(&output_path)?.unwrap();
+        assert!(resumed_from > 0 && resumed_from < gates);
+
+        let mut channel = Channel::new(TcpStream::connect(address)?);
+        let report = receive_tables(&mut channel, &output_path, &NoProgress)?;
+        assert_eq!(report.first_table, resumed_from);
+        assert_eq!(report.first_table + report.tables_transferred, gates);
+        assert_eq!(resume_point(&output_path)?, None);
+        assert_eq!(std::fs::read(&output_path)?, std::fs::read(&tables_path)?);
+
+        let reports = garbler.join().unwrap()?;
+        assert!(reports[0].is_err());
+        assert_eq!(reports[1].as_ref().unwrap().first_table, resumed_from);
+        Ok(())
+    }
+}
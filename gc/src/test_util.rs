@@ -0,0 +1,12 @@
+//! Fixtures shared by the unit tests
+
+use crate::error::Result;
+use std::io::Write;
+
+/// Write `content` to a temporary file, e.g. a Bristol circuit or garbled tables
+pub(crate) fn create_test_file(content: impl AsRef<[u8]>) -> Result<tempfile::NamedTempFile> {
+    let mut temp_file = tempfile::NamedTempFile::new()?;
+    temp_file.write_all(content.as_ref())?;
+    temp_file.flush()?;
+    Ok(temp_file)
+}
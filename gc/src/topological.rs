@@ -0,0 +1,232 @@
+use crate::error::{GcError, Result};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::io::Read;
+
+use crate::constants::PROGRESS_UPDATE_INTERVAL;
+use crate::gate::Circuit;
+use crate::gate::WireId;
+use crate::parser::{WireRef, read_header, visit_gate_wires};
+use crate::progress::ProgressObserver;
+use crate::stream::BufferedLineStream;
+
+/// Number of violations an [`OrderReport`] lists; the rest are only counted
+pub const MAX_REPORTED_VIOLATIONS: usize = 100;
+
+/// A gate reading a wire that a later gate writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct OrderViolation {
+    /// First gate reading the wire, 0-based in file order
+    pub gate: u64,
+    /// The wire read too early
+    pub wire: WireId,
+    /// Gate writing the wire
+    pub producer: u64,
+}
+
+/// Result of [`check_topological_order`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct OrderReport {
+    /// Number of gates checked
+    pub num_gates: u64,
+    /// Number of wires read before the gate writing them
+    pub num_violations: u64,
+    /// The first [`MAX_REPORTED_VIOLATIONS`] violations, by producing gate
+    pub violations: Vec<OrderViolation>,
+}
+
+impl OrderReport {
+    /// Whether every wire is written before it is read
+    pub fn is_topological(&self) -> bool {
+        self.num_violations == 0
+    }
+}
+
+/// Check that a Bristol circuit lists its gates in topological order
+///
+/// The garbler and evaluator stream gates in file order and need every
+/// wire a gate reads to be a primary input or written by an earlier gate.
+/// A wire read before any gate writes it is taken as a primary input until
+/// a later gate writes it, which is a use before definition.
+///
+/// # Arguments
+/// * `stream` - The line stream to process
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(OrderReport)` - The wires read before they are written, if any
+/// * `Err(GcError)` - Parse error or IO error
+#[tracing::instrument(name = "check_order", skip_all)]
+pub fn check_topological_order<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    progress: &dyn ProgressObserver,
+) -> Result<OrderReport> {
+    let header = read_header(stream)?;
+    let mut written = vec![false; header.num_wires as usize];
+    // First gate reading each wire that was not written yet
+    let mut early_reads: HashMap<WireId, u64> = HashMap::new();
+    let mut report = OrderReport::default();
+
+    progress.start(Some(header.num_gates), "Checking gate order");
+    let mut line_number = header.lines;
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        let gate_index = report.num_gates;
+        visit_gate_wires(line_result?, line_number, |wire| {
+            wire.check_range(header.num_wires, line_number)?;
+            match wire {
+                WireRef::Input(wire_id) if !written[wire_id as usize] => {
+                    early_reads.entry(wire_id).or_insert(gate_index);
+                }
+                WireRef::Input(_) => {}
+                WireRef::Output(wire_id) => {
+                    written[wire_id as usize] = true;
+                    if let Some(gate) = early_reads.remove(&wire_id) {
+                        report.num_violations += 1;
+                        if report.violations.len() < MAX_REPORTED_VIOLATIONS {
+                            report.violations.push(OrderViolation {
+                                gate,
+                                wire: wire_id,
+                                producer: gate_index,
+                            });
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        report.num_gates += 1;
+
+        if report.num_gates.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            progress.update(report.num_gates);
+        }
+    }
+
+    progress.update(report.num_gates);
+    progress.finish(&format!(
+        "✓ Checked {} gates, {} read wires before they are written",
+        report.num_gates, report.num_violations
+    ));
+    Ok(report)
+}
+
+/// Reorder the gates of a circuit so every wire is written before it is read
+///
+/// Among the gates whose inputs are ready, the one listed first goes next,
+/// so a circuit already in topological order comes back unchanged and the
+/// rest move as little as needed. Wires and declarations are not touched.
+///
+/// # Arguments
+/// * `circuit` - The circuit, whose gates may be listed in any order
+///
+/// # Returns
+/// * `Ok(Circuit)` - The circuit with its gates in topological order
+/// * `Err(GcError)` - A wire is written by two gates, or the gates depend on each other in a cycle
+#[tracing::instrument(name = "topological_sort", skip_all)]
+pub fn topological_sort(circuit: &Circuit) -> Result<Circuit> {
+    let gates = &circuit.gates;
+    let mut producers = vec![usize::MAX; circuit.num_wires as usize];
+    for (index, gate) in gates.iter().enumerate() {
+        let producer = &mut producers[gate.output_wire as usize];
+        if *producer != usize::MAX {
+            return Err(GcError::InvalidCircuit(format!(
+                "Wire {} is written by gates {} and {}, so the order of the gates matters",
+                gate.output_wire, *producer, index
+            )));
+        }
+        *producer = index;
+    }
+
+    // Gates reading each gate's output, as offsets into one array
+    let mut pending_inputs = vec![0u8; gates.len()];
+    let mut offsets = vec![0usize; gates.len() + 1];
+    let producer_of =
+        |wire_id: WireId| Some(producers[wire_id as usize]).filter(|&p| p != usize::MAX);
+    for (index, gate) in gates.iter().enumerate() {
+        for producer in gate.input_wires().filter_map(producer_of) {
+            offsets[producer + 1] += 1;
+            pending_inputs[index] += 1;
+        }
+    }
+    for index in 0..gates.len() {
+        offsets[index + 1] += offsets[index];
+    }
+    let mut readers = vec![0usize; offsets[gates.len()]];
+    let mut next_reader = offsets.clone();
+    for (index, gate) in gates.iter().enumerate() {
+        for producer in gate.input_wires().filter_map(producer_of) {
+            readers[next_reader[producer]] = index;
+            next_reader[producer] += 1;
+        }
+    }
+
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..gates.len())
+        .filter(|&index| pending_inputs[index] == 0)
+        .map(Reverse)
+        .collect();
+    let mut sorted = Vec::with_capacity(gates.len());
+    while let Some(Reverse(index)) = ready.pop() {
+        sorted.push(gates[index]);
+        for &reader in &readers[offsets[index]..offsets[index + 1]] {
+            pending_inputs[reader] -= 1;
+            if pending_inputs[reader] == 0 {
+                ready.push(Reverse(reader));
+            }
+        }
+    }
+    if let Some(index) = pending_inputs.iter().position(|&pending| pending > 0) {
+        return Err(GcError::InvalidCircuit(format!(
+            "Gate {} depends on its own output through a cycle of {} gates",
+            index,
+            gates.len() - sorted.len()
+        )));
+    }
+
+    Ok(Circuit {
+        num_wires: circuit.num_wires,
+        gates: sorted,
+        io: circuit.io.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+
+    #[test]
+    fn test_topological_order() -> Result<()> {
+        // Gate 0 reads wire 5 before gate 2 writes it
+        let text = "3 7\n2 1 5 2 6 XOR\n2 1 0 1 4 AND\n2 1 4 3 5 AND\n";
+        let report = check_topological_order(
+            &mut BufferedLineStream::from_reader(text.as_bytes()),
+            &NoProgress,
+        )?;
+        assert!(!report.is_topological());
+        assert_eq!(
+            report.violations,
+            vec![OrderViolation {
+                gate: 0,
+                wire: 5,
+                producer: 2
+            }]
+        );
+
+        let sorted = topological_sort(&Circuit::parse(text)?)?;
+        let mut sorted_text = Vec::new();
+        sorted.write_bristol(&mut sorted_text)?;
+        assert_eq!(sorted.gates[2], Circuit::parse(text)?.gates[0]);
+        let report = check_topological_order(
+            &mut BufferedLineStream::from_reader(sorted_text.as_slice()),
+            &NoProgress,
+        )?;
+        assert!(report.is_topological());
+        assert_eq!(topological_sort(&sorted)?, sorted);
+
+        let cycle = Circuit::parse("2 4\n2 1 0 3 2 AND\n2 1 2 1 3 XOR\n")?;
+        let error_msg = topological_sort(&cycle).unwrap_err().to_string();
+        assert!(error_msg.contains("cycle of 2 gates"), "{}", error_msg);
+        Ok(())
+    }
+}
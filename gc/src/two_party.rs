@@ -0,0 +1,739 @@
+use crate::error::{GcError, Result};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::channel::Channel;
+use crate::chunk::{CHUNK_FRAMING_SIZE, ChunkReader, ChunkWriter};
+use crate::constants::{GARBLED_TABLE_SIZE, GARBLED_TABLES_HEADER_SIZE, PROGRESS_UPDATE_INTERVAL};
+use crate::decoder::{DecodingInfo, decode_outputs};
+use crate::evaluator::{EvaluationOptions, EvaluationResult, EvaluatorSession, OutputResult};
+use crate::garbler::{GarbledTable, GarblerBuilder, TablesHeader, WireLabel};
+use crate::gate::{Circuit, CircuitDigest, Gate, WireId, hash_circuit};
+use crate::ot_extension::{OtExtensionBackend, OtSecurity};
+use crate::ot_remote::{receive_ot, send_ot};
+use crate::ot_simulation::{OTResult, OtStats};
+use crate::parser::{parse_gate_line, read_header};
+use crate::partition::InputPartition;
+use crate::progress::ProgressObserver;
+use crate::session::SessionId;
+use crate::sink::GarbledTableSink;
+use crate::stream::BufferedLineStream;
+use crate::wire_analyzer::{WireUsageReport, analyze_circuit, analyze_wire_usage};
+
+/// Version of the session protocol, the first byte of the garbler's hello
+const PROTOCOL_VERSION: u8 = 4;
+
+/// Size of the garbler's hello: version, circuit digest and session id
+const HELLO_SIZE: usize = 1 + 32 + 16;
+
+/// Evaluator's answer to the hello: go ahead
+const VERDICT_ACCEPT: u8 = 0;
+
+/// Evaluator's answer to the hello: refused, followed by the reason
+const VERDICT_REJECT: u8 = 1;
+
+/// Table message kind: a batch of garbled tables, framed as a [`ChunkWriter`] chunk
+pub(crate) const TABLES_BATCH: u8 = 0;
+
+/// Table message kind: the last table message, carrying the tables header
+pub(crate) const TABLES_END: u8 = 1;
+
+/// Settings of a session; the OT settings must match the other party's
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TwoPartyOptions {
+    /// Security level of the OT extension
+    pub security: OtSecurity,
+    /// OT extension backend
+    pub backend: OtExtensionBackend,
+    /// Skip blank and comment lines in the circuit file (see
+    /// [`BufferedLineStream::with_lenient`])
+    pub lenient: bool,
+}
+
+/// Outcome of a session, as seen by one party
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TwoPartyReport {
+    /// Decoded value of every primary output wire: wire_id -> bit
+    pub outputs: BTreeMap<WireId, bool>,
+    /// Number of garbled tables streamed
+    pub tables: u64,
+    /// Statistics of the OT for the evaluator's inputs
+    pub ot: OtStats,
+    /// Bytes sent over the whole session, framing included
+    pub bytes_sent: u64,
+    /// Bytes received over the whole session, framing included
+    pub bytes_received: u64,
+    /// Wall-clock time of the session in seconds
+    pub wall_time_secs: f64,
+}
+
+impl TwoPartyReport {
+    /// Save the report as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Garble a circuit for a connected evaluator and learn the outputs
+///
+/// Runs a whole session on `channel` against [`connect_and_evaluate`]:
+///
+/// 1. The garbler sends the protocol version, the [`CircuitDigest`] of its
+///    circuit and the session id; the evaluator refuses to go on unless its
+///    circuit has the same digest
+/// 2. [`send_ot`] transfers the labels of the evaluator's inputs and sends
+///    the labels of the garbler's own inputs
+/// 3. The circuit is garbled with the tables streamed in batches as they are
+///    produced, ending with the tables header
+/// 4. The garbler sends the [`DecodingInfo`] of the outputs
+/// 5. The evaluator sends back its output labels, which the garbler decodes
+///    itself, so a forged output bit is caught
+///
+/// Input labels only depend on the seed and the wire analysis, so they are
+/// known before garbling and the evaluator evaluates the tables as they
+/// arrive, without either side writing them to disk.
+///
+/// # Arguments
+/// * `channel` - Connection to the evaluator
+/// * `circuit_path` - Path to the Bristol circuit file
+/// * `partition` - Which primary inputs belong to the garbler
+/// * `inputs` - Bit value for every garbler input wire: wire_id -> bit
+/// * `seed_data` - 32 bytes of random seed, split into the garbling seed and the OT seed
+/// * `options` - OT settings, which must match the evaluator's
+/// * `progress` - Receives progress updates of every phase
+///
+/// # Returns
+/// * `Ok(TwoPartyReport)` - The decoded outputs and statistics of the session
+/// * `Err(GcError)` - Parse or I/O error, the evaluator refused the circuit or disconnected, or its output labels are invalid
+#[tracing::instrument(name = "serve", skip_all)]
+pub fn garble_and_serve<S: Read + Write>(
+    channel: &mut Channel<S>,
+    circuit_path: &Path,
+    partition: &InputPartition,
+    inputs: &HashMap<WireId, bool>,
+    seed_data: &[u8; 32],
+    options: &TwoPartyOptions,
+    progress: &dyn ProgressObserver,
+) -> Result<TwoPartyReport> {
+    let start = Instant::now();
+    let (bytes_sent, bytes_received) = (channel.bytes_sent(), channel.bytes_received());
+    let (garbling_seed, ot_seed) = split_seed(seed_data);
+
+    let wire_report =
+        analyze_wire_usage(&mut open_circuit(circuit_path, options.lenient)?, progress)?;
+    let circuit_digest = hash_circuit(File::open(circuit_path)?)?;
+    let garbler = GarblerBuilder::new(garbling_seed)
+        .input_partition(partition)
+        .progress(progress);
+    let input_labels = garbler.input_wire_labels(&wire_report)?;
+    let encoded = input_labels.encode_inputs(inputs)?;
+
+    let mut hello = Vec::with_capacity(HELLO_SIZE);
+    hello.push(PROTOCOL_VERSION);
+    hello.extend_from_slice(&circuit_digest);
+    hello.extend_from_slice(input_labels.session_id.as_bytes());
+    channel.send(&hello)?;
+    let verdict = channel.receive()?;
+    match verdict.split_first() {
+        Some((&VERDICT_ACCEPT, [])) => {}
+        Some((&VERDICT_REJECT, reason)) => {
+            return Err(GcError::Protocol(format!(
+                "Evaluator refused the session: {}",
+                String::from_utf8_lossy(reason)
+            )));
+        }
+        _ => {
+            return Err(GcError::Protocol(
+                "Malformed answer to the session hello".to_string(),
+            ));
+        }
+    }
+
+    let ot = send_ot(
+        channel,
+        &input_labels,
+        Some(&encoded),
+        options.security,
+        options.backend,
+        &ot_seed,
+        progress,
+    )?;
+
+    let mut sink = ChannelTableSink {
+        channel,
+        chunks: ChunkWriter::new(),
+    };
+    let garbling = garbler.sink(&mut sink).garble(
+        &mut open_circuit(circuit_path, options.lenient)?,
+        &wire_report,
+    )?;
+
+    let decoding_info = DecodingInfo::from_wire_labels(&garbling.wire_labels);
+    channel.send(&encode_decoding_info(&decoding_info)?)?;
+
+    let output_labels = decode_output_labels(&channel.receive()?)?;
+    let mut outputs = BTreeMap::new();
+    for &wire_id in &wire_report.primary_output_wires {
+        let label = output_labels.get(&wire_id).ok_or_else(|| {
+            GcError::missing_wire(
+                wire_id,
+                format!("Evaluator sent no label for output wire {}", wire_id),
+            )
+        })?;
+        outputs.insert(wire_id, decoding_info.decode_label(wire_id, label)?);
+    }
+
+    let report = TwoPartyReport {
+        outputs,
+        tables: garbling.stats.tables_written,
+        ot,
+        bytes_sent: channel.bytes_sent() - bytes_sent,
+        bytes_received: channel.bytes_received() - bytes_received,
+        wall_time_secs: start.elapsed().as_secs_f64(),
+    };
+    tracing::info!(
+        outputs = report.outputs.len(),
+        tables = report.tables,
+        bytes_sent = report.bytes_sent,
+        bytes_received = report.bytes_received,
+        "Garbler session finished"
+    );
+    Ok(report)
+}
+
+/// Evaluate a circuit garbled by a connected garbler
+///
+/// Counterpart of [`garble_and_serve`]; see there for the message flow. The
+/// evaluator holds one label per wire and picks the row of each garbled
+/// table by the labels' point-and-permute bits, so it never learns the bits
+/// of intermediate wires; it learns the outputs through the garbler's
+/// [`DecodingInfo`].
+///
+/// # Arguments
+/// * `channel` - Connection to the garbler
+/// * `circuit_path` - Path to the Bristol circuit file, which must match the garbler's
+/// * `inputs` - Bit value for every evaluator input wire: wire_id -> bit
+/// * `seed_data` - 32 bytes of random seed for the evaluator's OT secrets
+/// * `options` - OT settings, which must match the garbler's
+/// * `progress` - Receives progress updates of every phase
+///
+/// # Returns
+/// * `Ok((EvaluationResult, TwoPartyReport))` - The decoded output labels, and the decoded outputs with statistics of the session
+/// * `Err(GcError)` - Parse or I/O error, a different circuit, the garbler deviated from the protocol, or an input bit is missing
+#[tracing::instrument(name = "connect", skip_all)]
+pub fn connect_and_evaluate<S: Read + Write>(
+    channel: &mut Channel<S>,
+    circuit_path: &Path,
+    inputs: &HashMap<WireId, bool>,
+    seed_data: &[u8; 32],
+    options: &TwoPartyOptions,
+    progress: &dyn ProgressObserver,
+) -> Result<(EvaluationResult, TwoPartyReport)> {
+    let wire_report =
+        analyze_wire_usage(&mut open_circuit(circuit_path, options.lenient)?, progress)?;
+    let circuit_digest = hash_circuit(File::open(circuit_path)?)?;
+    run_evaluator(
+        channel,
+        &circuit_digest,
+        inputs,
+        seed_data,
+        options,
+        progress,
+        |ot_result, next_table| {
+            evaluate_file_with_tables(
+                circuit_path,
+                options.lenient,
+                &wire_report,
+                ot_result,
+                next_table,
+                progress,
+            )
+        },
+    )
+}
+
+/// Evaluate a circuit held in memory, garbled by a connected garbler
+///
+/// Same session as [`connect_and_evaluate`], for evaluators that hold the
+/// circuit in memory instead of in a file, e.g. one they fetched or built.
+///
+/// # Arguments
+/// * `channel` - Connection to the garbler
+/// * `circuit` - The circuit, which must match the garbler's
+/// * `inputs` - Bit value for every evaluator input wire: wire_id -> bit
+/// * `seed_data` - 32 bytes of random seed for the evaluator's OT secrets
+/// * `options` - OT settings, which must match the garbler's
+/// * `progress` - Receives progress updates of every phase
+///
+/// # Returns
+/// * `Ok((EvaluationResult, TwoPartyReport))` - The decoded output labels, and the decoded outputs with statistics of the session
+/// * `Err(GcError)` - A different circuit, the garbler deviated from the protocol, or an input bit is missing
+#[tracing::instrument(name = "connect", skip_all)]
+pub fn connect_and_evaluate_circuit<S: Read + Write>(
+    channel: &mut Channel<S>,
+    circuit: &Circuit,
+    inputs: &HashMap<WireId, bool>,
+    seed_data: &[u8; 32],
+    options: &TwoPartyOptions,
+    progress: &dyn ProgressObserver,
+) -> Result<(EvaluationResult, TwoPartyReport)> {
+    let wire_report = analyze_circuit(circuit)?;
+    run_evaluator(
+        channel,
+        &CircuitDigest::of_gates(&circuit.gates),
+        inputs,
+        seed_data,
+        options,
+        progress,
+        |ot_result, next_table| {
+            evaluate_gates_with_tables(
+                &circuit.gates,
+                &wire_report,
+                ot_result,
+                next_table,
+                progress,
+            )
+        },
+    )
+}
+
+/// Evaluator side of a session, whatever the circuit is read from
+///
+/// `evaluate` runs the gates against the tables as they arrive.
+fn run_evaluator<S: Read + Write>(
+    channel: &mut Channel<S>,
+    circuit_digest: &[u8; 32],
+    inputs: &HashMap<WireId, bool>,
+    seed_data: &[u8; 32],
+    options: &TwoPartyOptions,
+    progress: &dyn ProgressObserver,
+    evaluate: impl FnOnce(
+        &OTResult,
+        &mut dyn FnMut() -> Result<GarbledTable>,
+    ) -> Result<EvaluationResult>,
+) -> Result<(EvaluationResult, TwoPartyReport)> {
+    let start = Instant::now();
+    let (bytes_sent, bytes_received) = (channel.bytes_sent(), channel.bytes_received());
+
+    let hello = channel.receive()?;
+    let refusal = if hello.len() != HELLO_SIZE {
+        Some(format!(
+            "hello has {} bytes, expected {}",
+            hello.len(),
+            HELLO_SIZE
+        ))
+    } else if hello[0] != PROTOCOL_VERSION {
+        Some(format!(
+            "garbler speaks protocol version {}, evaluator speaks {}",
+            hello[0], PROTOCOL_VERSION
+        ))
+    } else if hello[1..33] != *circuit_digest {
+        Some("the circuits differ".to_string())
+    } else {
+        None
+    };
+    if let Some(reason) = refusal {
+        let mut verdict = vec![VERDICT_REJECT];
+        verdict.extend_from_slice(reason.as_bytes());
+        channel.send(&verdict)?;
+        return Err(GcError::Protocol(format!(
+            "Refused the garbler's session: {}",
+            reason
+        )));
+    }
+    let session_id = SessionId::new(hello[33..].try_into().unwrap());
+    channel.send(&[VERDICT_ACCEPT])?;
+
+    let (ot_result, ot) = receive_ot(
+        channel,
+        inputs,
+        options.security,
+        options.backend,
+        seed_data,
+        progress,
+    )?;
+    session_id.check(&ot_result.session_id, "OT result")?;
+
+    // Tables are decoded batch by batch as the gates need them
+    let mut tables = TableMessageSource::new(&mut *channel);
+    let evaluation = evaluate(&ot_result, &mut || tables.next_table())?;
+
+    let tables_header = tables.finish()?;
+    tables_header.check_circuit(circuit_digest)?;
+    tables_header.check_session(&session_id, "Session hello")?;
+
+    let decoding_info = decode_decoding_info(&channel.receive()?)?;
+    let evaluation = decode_outputs(&evaluation, &decoding_info)?;
+    channel.send(&encode_output_labels(&evaluation.output_results)?)?;
+
+    let report = TwoPartyReport {
+        outputs: evaluation
+            .output_results
+            .iter()
+            .map(|(&wire_id, output)| (wire_id, output.bit_value == Some(true)))
+            .collect(),
+        tables: tables_header.num_tables,
+        ot,
+        bytes_sent: channel.bytes_sent() - bytes_sent,
+        bytes_received: channel.bytes_received() - bytes_received,
+        wall_time_secs: start.elapsed().as_secs_f64(),
+    };
+    tracing::info!(
+        outputs = report.outputs.len(),
+        tables = report.tables,
+        bytes_sent = report.bytes_sent,
+        bytes_received = report.bytes_received,
+        "Evaluator session finished"
+    );
+    Ok((evaluation, report))
+}
+
+/// Sends each batch of garbled tables as one message
+struct ChannelTableSink<'c, S> {
+    channel: &'c mut Channel<S>,
+    chunks: ChunkWriter,
+}
+
+impl<S: Read + Write> GarbledTableSink for ChannelTableSink<'_, S> {
+    fn write_tables(&mut self, tables: &[GarbledTable]) -> Result<()> {
+        self.channel
+            .send(&tables_batch_message(&mut self.chunks, tables))
+    }
+
+    fn finish(&mut self, header: &TablesHeader) -> Result<()> {
+        self.channel.send(&tables_end_message(header))
+    }
+}
+
+/// Message carrying one batch of garbled tables as the next chunk of `chunks`
+pub(crate) fn tables_batch_message(chunks: &mut ChunkWriter, tables: &[GarbledTable]) -> Vec<u8> {
+    let tables: Vec<u8> = tables.iter().flat_map(|table| table.as_binary()).collect();
+    let mut message = Vec::with_capacity(1 + CHUNK_FRAMING_SIZE + tables.len());
+    message.push(TABLES_BATCH);
+    chunks.write_chunk(&tables, &mut message);
+    message
+}
+
+/// Last table message, carrying the tables header
+pub(crate) fn tables_end_message(header: &TablesHeader) -> Vec<u8> {
+    let mut message = Vec::with_capacity(1 + GARBLED_TABLES_HEADER_SIZE);
+    message.push(TABLES_END);
+    message.extend_from_slice(&header.to_bytes());
+    message
+}
+
+/// Where [`TableMessageSource`] takes the table messages from
+pub(crate) trait TableMessages {
+    /// Next table message, failing once the garbler is gone
+    fn next_message(&mut self) -> Result<Vec<u8>>;
+}
+
+impl<S: Read + Write> TableMessages for &mut Channel<S> {
+    fn next_message(&mut self) -> Result<Vec<u8>> {
+        self.receive()
+    }
+}
+
+/// Hands out the tables of batch messages one at a time, as they are needed
+#[derive(Debug)]
+pub(crate) struct TableMessageSource<M> {
+    messages: M,
+    /// Verifies the checksum and sequence number of every batch
+    chunks: ChunkReader,
+    /// Tables of the current batch not consumed yet
+    batch: std::vec::IntoIter<GarbledTable>,
+    /// Number of tables received so far
+    received: u64,
+}
+
+impl<M: TableMessages> TableMessageSource<M> {
+    /// Read tables from `messages`
+    pub(crate) fn new(messages: M) -> Self {
+        Self {
+            messages,
+            chunks: ChunkReader::new(),
+            batch: Vec::new().into_iter(),
+            received: 0,
+        }
+    }
+
+    /// Next table, taking the next batch once the current one is used up
+    pub(crate) fn next_table(&mut self) -> Result<GarbledTable> {
+        loop {
+            if let Some(table) = self.batch.next() {
+                return Ok(table);
+            }
+            let message = self.messages.next_message()?;
+            match message.split_first() {
+                Some((&TABLES_BATCH, chunk)) => {
+                    let tables = self.chunks.read_chunk(chunk)?;
+                    if !tables.len().is_multiple_of(GARBLED_TABLE_SIZE) {
+                        return Err(GcError::Protocol(format!(
+                            "Batch of {} bytes does not hold whole garbled tables",
+                            tables.len()
+                        )));
+                    }
+                    self.received += (tables.len() / GARBLED_TABLE_SIZE) as u64;
+                    self.batch = tables
+                        .chunks_exact(GARBLED_TABLE_SIZE)
+                        .map(|table| GarbledTable::from_binary(table.try_into().unwrap()))
+                        .collect::<Vec<_>>()
+                        .into_iter();
+                }
+                Some((&TABLES_END, _)) => {
+                    return Err(GcError::TableCountMismatch {
+                        expected: self.received + 1,
+                        found: self.received,
+                        message: format!(
+                            "Garbler sent only {} tables, the circuit needs more",
+                            self.received
+                        ),
+                    });
+                }
+                _ => {
+                    return Err(GcError::Protocol(
+                        "Malformed garbled tables message".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Take the tables header, failing if tables are left over
+    pub(crate) fn finish(mut self) -> Result<TablesHeader> {
+        let leftover = self.batch.len() as u64;
+        if leftover > 0 {
+            return Err(extra_tables(self.received - leftover, leftover));
+        }
+        let message = self.messages.next_message()?;
+        let header = match message.split_first() {
+            Some((&TABLES_END, header)) if header.len() == GARBLED_TABLES_HEADER_SIZE => {
+                TablesHeader::from_bytes(header.try_into().unwrap())?
+            }
+            Some((&TABLES_BATCH, chunk)) => {
+                return Err(extra_tables(
+                    self.received,
+                    (chunk.len().saturating_sub(CHUNK_FRAMING_SIZE) / GARBLED_TABLE_SIZE) as u64,
+                ));
+            }
+            _ => {
+                return Err(GcError::Protocol(
+                    "Malformed garbled tables message".to_string(),
+                ));
+            }
+        };
+        if header.num_tables != self.received {
+            return Err(GcError::TableCountMismatch {
+                expected: header.num_tables,
+                found: self.received,
+                message: format!(
+                    "Garbler announced {} tables but sent {}",
+                    header.num_tables, self.received
+                ),
+            });
+        }
+        Ok(header)
+    }
+}
+
+/// Error for tables beyond the circuit's AND gates
+fn extra_tables(used: u64, extra: u64) -> GcError {
+    GcError::TableCountMismatch {
+        expected: used,
+        found: used + extra,
+        message: format!(
+            "Garbler sent {} more tables than the circuit's {} AND gates",
+            extra, used
+        ),
+    }
+}
+
+/// Open a circuit file for one pass over its lines
+pub(crate) fn open_circuit(circuit_path: &Path, lenient: bool) -> Result<BufferedLineStream> {
+    Ok(BufferedLineStream::new(File::open(circuit_path)?).with_lenient(lenient))
+}
+
+/// Evaluate a Bristol file with tables from `next_table`
+pub(crate) fn evaluate_file_with_tables(
+    circuit_path: &Path,
+    lenient: bool,
+    wire_report: &WireUsageReport,
+    ot_result: &OTResult,
+    next_table: impl FnMut() -> Result<GarbledTable>,
+    progress: &dyn ProgressObserver,
+) -> Result<EvaluationResult> {
+    let options = EvaluationOptions::default();
+    let mut stream = open_circuit(circuit_path, lenient)?;
+    let header = read_header(&mut stream)?;
+    progress.start(Some(header.num_gates), "Evaluating circuit...");
+    let mut session = EvaluatorSession::new(wire_report, ot_result, next_table, &options)?;
+    let mut line_number = 0;
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        session.feed_gate(parse_gate_line(line_result?, line_number)?)?;
+        if line_number.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            progress.update(line_number);
+        }
+    }
+    let evaluation = session.finish()?;
+    progress.finish(&format!("✓ Evaluated {} gates", line_number));
+    Ok(evaluation)
+}
+
+/// Evaluate in-memory gates with tables from `next_table`
+pub(crate) fn evaluate_gates_with_tables(
+    gates: &[Gate],
+    wire_report: &WireUsageReport,
+    ot_result: &OTResult,
+    next_table: impl FnMut() -> Result<GarbledTable>,
+    progress: &dyn ProgressObserver,
+) -> Result<EvaluationResult> {
+    let options = EvaluationOptions::default();
+    progress.start(Some(gates.len() as u64), "Evaluating circuit...");
+    let mut session = EvaluatorSession::new(wire_report, ot_result, next_table, &options)?;
+    for (index, &gate) in gates.iter().enumerate() {
+        session.feed_gate(gate)?;
+        if (index as u64 + 1).is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            progress.update(index as u64 + 1);
+        }
+    }
+    let evaluation = session.finish()?;
+    progress.finish(&format!("✓ Evaluated {} gates", gates.len()));
+    Ok(evaluation)
+}
+
+/// Split one seed into the garbling seed and the OT seed
+pub(crate) fn split_seed(seed_data: &[u8; 32]) -> ([u8; 32], [u8; 32]) {
+    let mut rng = ChaCha12Rng::from_seed(*seed_data);
+    let mut garbling_seed = [0u8; 32];
+    let mut ot_seed = [0u8; 32];
+    rng.fill_bytes(&mut garbling_seed);
+    rng.fill_bytes(&mut ot_seed);
+    (garbling_seed, ot_seed)
+}
+
+/// The decoding information, sorted by wire so it always encodes the same
+fn encode_decoding_info(decoding_info: &DecodingInfo) -> Result<Vec<u8>> {
+    let mut hashes: Vec<(&WireId, &[[u8; 16]; 2])> = decoding_info.output_hashes.iter().collect();
+    hashes.sort_unstable_by_key(|&(&wire_id, _)| wire_id);
+    Ok(bincode::encode_to_vec(hashes, bincode::config::standard())?)
+}
+
+/// Counterpart of [`encode_decoding_info`]
+fn decode_decoding_info(message: &[u8]) -> Result<DecodingInfo> {
+    let hashes: Vec<(WireId, [[u8; 16]; 2])> =
+        bincode::decode_from_slice(message, bincode::config::standard())?.0;
+    Ok(DecodingInfo {
+        output_hashes: hashes.into_iter().collect(),
+    })
+}
+
+/// The evaluator's output labels, sorted by wire
+fn encode_output_labels(outputs: &HashMap<WireId, OutputResult>) -> Result<Vec<u8>> {
+    let mut labels: Vec<(WireId, WireLabel)> = outputs
+        .iter()
+        .map(|(&wire_id, output)| (wire_id, output.label))
+        .collect();
+    labels.sort_unstable_by_key(|&(wire_id, _)| wire_id);
+    Ok(bincode::encode_to_vec(labels, bincode::config::standard())?)
+}
+
+/// Counterpart of [`encode_output_labels`]
+fn decode_output_labels(message: &[u8]) -> Result<HashMap<WireId, WireLabel>> {
+    let (labels, _): (Vec<(WireId, WireLabel)>, usize) =
+        bincode::decode_from_slice(message, bincode::config::standard())?;
+    Ok(labels.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plain::session_circuit_file;
+    use crate::progress::NoProgress;
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn test_session_over_tcp() -> Result<()> {
+        let circuit_file = session_circuit_file()?;
+        let circuit_path = circuit_file.path().to_path_buf();
+
+        for pattern in [0b0111u32, 0b1010, 0b1111] {
+            let bit = move |wire: WireId| (pattern >> wire) & 1 == 1;
+            let listener = TcpListener::bind("127.0.0.1:0")?;
+            let address = listener.local_addr()?;
+            let garbler_path = circuit_path.clone();
+            let garbler = std::thread::spawn(move || {
+                let (stream, _) = listener.accept()?;
+                garble_and_serve(
+                    &mut Channel::new(stream),
+                    &garbler_path,
+                    &InputPartition::parse("0..2")?,
+                    &HashMap::from([(0, bit(0)), (1, bit(1))]),
+                    &[4; 32],
+                    &TwoPartyOptions::default(),
+                    &NoProgress,
+                )
+            });
+
+            let (evaluation, report) = connect_and_evaluate(
+                &mut Channel::new(TcpStream::connect(address)?),
+                &circuit_path,
+                &HashMap::from([(2, bit(2)), (3, bit(3))]),
+                &[5; 32],
+                &TwoPartyOptions::default(),
+                &NoProgress,
+            )?;
+            let garbler_report = garbler.join().unwrap()?;
+
+            let expected = BTreeMap::from([(5, (bit(0) & bit(1)) ^ bit(2)), (6, bit(1) & bit(3))]);
+            assert_eq!(report.outputs, expected);
+            assert_eq!(garbler_report.outputs, expected);
+            assert_eq!(evaluation.output_results[&5].bit_value, Some(expected[&5]));
+            assert_eq!((report.tables, garbler_report.tables), (2, 2));
+            assert_eq!(report.ot.evaluator_inputs, 2);
+            assert_eq!(garbler_report.bytes_sent, report.bytes_received);
+        }
+
+        // A different circuit is refused before any OT runs
+        let mut other_file = tempfile::NamedTempFile::new()?;
+        other_file.write_all(b"1 3\n2 1 0 1 2 XOR\n")?;
+        other_file.flush()?;
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let garbler = std::thread::spawn(move || {
+            let (stream, _) = listener.accept()?;
+            garble_and_serve(
+                &mut Channel::new(stream),
+                &circuit_path,
+                &InputPartition::default(),
+                &HashMap::new(),
+                &[4; 32],
+                &TwoPartyOptions::default(),
+                &NoProgress,
+            )
+        });
+        let error_msg = connect_and_evaluate(
+            &mut Channel::new(TcpStream::connect(address)?),
+            other_file.path(),
+            &HashMap::new(),
+            &[5; 32],
+            &TwoPartyOptions::default(),
+            &NoProgress,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error_msg.contains("the circuits differ"));
+        let error_msg = garbler.join().unwrap().unwrap_err().to_string();
+        assert!(error_msg.contains("Evaluator refused the session: the circuits differ"));
+
+        Ok(())
+    }
+}
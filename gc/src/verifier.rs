@@ -0,0 +1,324 @@
+use crate::error::{GcError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use crate::constants::PROGRESS_UPDATE_INTERVAL;
+use crate::evaluator::{GarbledTableReader, TableAccess};
+use crate::garbler::{GarbledTable, WireLabel, WireLabels, constant_label, garbling_hash};
+use crate::gate::{CircuitDigest, GateType, WireId};
+use crate::parser::{parse_gate_line, read_header};
+use crate::progress::ProgressObserver;
+use crate::stream::BufferedLineStream;
+use crate::wire_analyzer::WireUsageReport;
+
+/// Result of checking garbled tables against the garbler's wire labels
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GarblingVerificationReport {
+    /// Number of AND tables decrypted with all four input combinations
+    pub and_gates_checked: usize,
+    /// Gate indices (0-based) of AND gates whose table failed to decrypt correctly
+    pub failed_gates: Vec<u64>,
+    /// Primary output wires whose recomputed label_0 differs from the garbler's labels
+    pub output_mismatches: Vec<WireId>,
+    /// Number of tables left in the file after the last AND gate (if known)
+    pub unused_tables: usize,
+}
+
+impl GarblingVerificationReport {
+    /// Whether every table and output label checked out
+    pub fn is_valid(&self) -> bool {
+        self.failed_gates.is_empty() && self.output_mismatches.is_empty() && self.unused_tables == 0
+    }
+}
+
+/// Decrypt one row of a garbled table with the given input labels
+fn decrypt_row(table: &GarbledTable, row_index: usize, input_labels: [WireLabel; 2]) -> WireLabel {
+    let key = garbling_hash(&input_labels);
+    let ciphertext = &table.ciphertexts[row_index];
+    let mut plaintext = [0u8; 16];
+    for i in 0..16 {
+        plaintext[i] = ciphertext[i] ^ key[i];
+    }
+    WireLabel::new(plaintext)
+}
+
+/// Check a single AND table, returning the output label_0 it encodes
+///
+/// Rows are ordered by the point-and-permute bits of the input labels. The
+/// row of both label_0s defines the candidate output label_0. The table is
+/// consistent if the rows of the other combinations with a 0 decrypt to the
+/// same label and the row of both label_1s decrypts to label_0 XOR delta.
+fn check_and_table(
+    table: &GarbledTable,
+    input1_label_0: WireLabel,
+    input2_label_0: WireLabel,
+    delta: &WireLabel,
+) -> (WireLabel, bool) {
+    let input1_labels = [input1_label_0, input1_label_0.xor(delta)];
+    let input2_labels = [input2_label_0, input2_label_0.xor(delta)];
+    let decrypt = |in1_bit: usize, in2_bit: usize| {
+        let input_labels = [input1_labels[in1_bit], input2_labels[in2_bit]];
+        let row_index =
+            2 * input_labels[0].permute_bit() as usize + input_labels[1].permute_bit() as usize;
+        decrypt_row(table, row_index, input_labels)
+    };
+
+    let output_label_0 = decrypt(0, 0);
+    let output_label_1 = output_label_0.xor(delta);
+
+    // The labels of each input must also land on distinct rows
+    let is_valid = delta.permute_bit()
+        && [(0, 1), (1, 0), (1, 1)]
+            .into_iter()
+            .all(|(in1_bit, in2_bit)| {
+                let expected = if in1_bit == 1 && in2_bit == 1 {
+                    output_label_1
+                } else {
+                    output_label_0
+                };
+                decrypt(in1_bit, in2_bit) == expected
+            });
+
+    (output_label_0, is_valid)
+}
+
+/// Verify garbled tables by decrypting every row with both labels of each input
+///
+/// This is an offline self-test for the garbler: starting from the input
+/// labels and global delta in `wire_labels`, every AND table is decrypted with
+/// all four input-label combinations and checked against the AND truth table.
+/// XOR gates are propagated with free XOR. Finally the recomputed labels of the
+/// primary outputs are compared against `wire_labels.output_labels`.
+///
+/// Expected format:
+/// First line: `<num_gates> <num_wires>`
+/// Followed by gate lines in Bristol format
+///
+/// # Arguments
+/// * `stream` - The line stream to process Bristol circuit gates
+/// * `wire_report` - Wire usage analysis for memory optimization
+/// * `wire_labels` - Wire labels (including delta) produced by the garbler
+/// * `garbled_tables_path` - Path to the garbled tables file (plain or compressed)
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(GarblingVerificationReport)` - Per-gate and per-output verification results
+/// * `Err(GcError)` - Parse error, file error, or missing tables/wires
+#[tracing::instrument(name = "verify", skip_all)]
+pub fn verify_garbled_tables<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    wire_report: &WireUsageReport,
+    wire_labels: &WireLabels,
+    garbled_tables_path: &Path,
+    progress: &dyn ProgressObserver,
+) -> Result<GarblingVerificationReport> {
+    let num_gates = read_header(stream)?.num_gates;
+
+    let mut garbled_tables = GarbledTableReader::open(garbled_tables_path, TableAccess::Buffered)?;
+    garbled_tables.check_session(&wire_labels.session_id, "Wire labels file")?;
+    let delta = wire_labels.delta;
+
+    // Initialize usage counts for runtime tracking (clone from wire analysis)
+    let mut remaining_usage = wire_report.wire_usage_counts.clone();
+
+    // Active wire labels start with the garbler's input labels
+    let mut active_wire_labels: HashMap<WireId, WireLabel> = HashMap::new();
+    for &input_wire_id in &wire_report.primary_input_wires {
+        let label_0 = wire_labels
+            .input_labels
+            .get(&input_wire_id)
+            .ok_or_else(|| {
+                GcError::missing_wire(
+                    input_wire_id,
+                    format!("Input wire {} missing from wire labels", input_wire_id),
+                )
+            })?;
+        active_wire_labels.insert(input_wire_id, *label_0);
+    }
+
+    let mut report = GarblingVerificationReport::default();
+    let mut circuit_digest = CircuitDigest::new();
+    let mut line_number = 0;
+
+    // Create progress bar for gate processing (use actual count from header)
+    progress.start(Some(num_gates), "Verifying garbled tables...");
+
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        let gate = parse_gate_line(line_result?, line_number)?;
+        circuit_digest.update(&gate);
+        let gate_index: u64 = line_number - 1;
+
+        let label_0 = |wire_id: WireId| -> Result<WireLabel> {
+            active_wire_labels.get(&wire_id).copied().ok_or_else(|| {
+                GcError::missing_wire(wire_id, format!("Input wire {} not found", wire_id))
+            })
+        };
+
+        let output_label_0 = match gate.gate_type {
+            GateType::Xor => label_0(gate.input_wire_1)?.xor(&label_0(gate.input_wire_2)?),
+            GateType::And => {
+                let table = garbled_tables.next_table()?;
+                let (output_label_0, is_valid) = check_and_table(
+                    &table,
+                    label_0(gate.input_wire_1)?,
+                    label_0(gate.input_wire_2)?,
+                    &delta,
+                );
+                report.and_gates_checked += 1;
+                if !is_valid {
+                    report.failed_gates.push(gate_index);
+                }
+                output_label_0
+            }
+            GateType::Buf => label_0(gate.input_wire_1)?,
+            GateType::Const(value) => {
+                let public_label = constant_label(gate.output_wire);
+                if value {
+                    public_label.xor(&delta)
+                } else {
+                    public_label
+                }
+            }
+        };
+
+        active_wire_labels.insert(gate.output_wire, output_label_0);
+
+        // Process input wires: decrement usage and remove if no longer needed
+        for input_wire in gate.input_wires() {
+            if remaining_usage.release(input_wire) {
+                active_wire_labels.remove(&input_wire);
+            }
+        }
+
+        if gate_index.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            progress.update(gate_index);
+        }
+    }
+
+    progress.finish(&format!(
+        "✓ Verified {} AND tables, {} failed",
+        report.and_gates_checked,
+        report.failed_gates.len()
+    ));
+    tracing::info!(
+        and_tables = report.and_gates_checked,
+        failed = report.failed_gates.len(),
+        "verification finished"
+    );
+    garbled_tables.check_circuit(&circuit_digest.finalize())?;
+
+    // Compare recomputed output labels against the garbler's output labels
+    for &output_wire_id in &wire_report.primary_output_wires {
+        let label_0 = active_wire_labels.get(&output_wire_id).ok_or_else(|| {
+            GcError::missing_wire(
+                output_wire_id,
+                format!("Output wire {} not found in active labels", output_wire_id),
+            )
+        })?;
+        if wire_labels.output_labels.get(&output_wire_id) != Some(label_0) {
+            report.output_mismatches.push(output_wire_id);
+        }
+    }
+
+    report.unused_tables = garbled_tables.remaining_tables().unwrap_or(0);
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::garbler::garble_circuit;
+    use crate::progress::NoProgress;
+    use crate::test_util::create_test_file;
+    use crate::wire_analyzer::analyze_wire_usage;
+    use std::fs::File;
+
+    fn open_stream(temp_file: &tempfile::NamedTempFile) -> Result<BufferedLineStream> {
+        Ok(BufferedLineStream::new(File::open(temp_file.path())?))
+    }
+
+    const CIRCUIT: &str = "3 7\n2 1 0 1 2 AND\n2 1 2 3 4 XOR\n2 1 4 5 6 AND\n";
+
+    #[test]
+    fn test_verify_valid_garbling() -> Result<()> {
+        let circuit_file = create_test_file(CIRCUIT.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[7; 32])?;
+
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save(labels_file.path(), tables_file.path())?;
+
+        let report = verify_garbled_tables(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &garbling.wire_labels,
+            tables_file.path(),
+            &NoProgress,
+        )?;
+
+        assert_eq!(report.and_gates_checked, 2);
+        assert!(report.is_valid());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_copy_and_constant_gates() -> Result<()> {
+        let circuit = "5 8\n1 1 0 2 EQW\n1 1 1 3 EQ\n2 1 2 3 4 AND\n2 1 4 1 5 AND\n1 1 5 6 BUF\n";
+        let circuit_file = create_test_file(circuit.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+        assert_eq!(wire_report.primary_input_wires, vec![0, 1]);
+        let garbling = garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[7; 32])?;
+
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save(labels_file.path(), tables_file.path())?;
+
+        let report = verify_garbled_tables(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &garbling.wire_labels,
+            tables_file.path(),
+            &NoProgress,
+        )?;
+
+        assert_eq!(report.and_gates_checked, 2);
+        assert!(report.is_valid());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_detects_corrupted_table() -> Result<()> {
+        let circuit_file = create_test_file(CIRCUIT.as_bytes())?;
+        let wire_report = analyze_wire_usage(&mut open_stream(&circuit_file)?, &NoProgress)?;
+        let mut garbling =
+            garble_circuit(&mut open_stream(&circuit_file)?, &wire_report, &[7; 32])?;
+
+        // Flip a bit in the (1,1) row of the second AND table
+        garbling.garbled_tables[1].ciphertexts[3][0] ^= 1;
+
+        let labels_file = tempfile::NamedTempFile::new()?;
+        let tables_file = tempfile::NamedTempFile::new()?;
+        garbling.save(labels_file.path(), tables_file.path())?;
+
+        let report = verify_garbled_tables(
+            &mut open_stream(&circuit_file)?,
+            &wire_report,
+            &garbling.wire_labels,
+            tables_file.path(),
+            &NoProgress,
+        )?;
+
+        assert_eq!(report.failed_gates, vec![2]);
+        assert!(report.output_mismatches.is_empty());
+        assert!(!report.is_valid());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use crate::error::{GcError, Result};
+use std::io::{self, Read, Write};
+use tungstenite::error::ProtocolError;
+use tungstenite::protocol::WebSocketConfig;
+use tungstenite::{Message, WebSocket};
+
+/// Largest WebSocket message sent at once; longer writes are split
+///
+/// The receiving side only sees a byte stream, so splitting is invisible to
+/// it, and it keeps every message well under the default size limits of
+/// browsers and WebSocket libraries.
+const MAX_WEBSOCKET_MESSAGE: usize = 1024 * 1024;
+
+/// Byte stream carried in binary WebSocket messages
+///
+/// Lets every protocol that runs over a [`Channel`](crate::channel::Channel),
+/// e.g. [`garble_and_serve`](crate::two_party::garble_and_serve) or
+/// [`send_tables`](crate::table_transfer::send_tables), talk to a peer that
+/// can only be reached over WebSockets, e.g. through an HTTP proxy. The
+/// stream is blocking, so it does not fit a browser's WebSocket API. Writes
+/// are collected until the stream is flushed and then sent as one binary
+/// message, so each channel message arrives as one WebSocket message (split
+/// only past 1 MiB); reads concatenate the received binary messages. Pings
+/// are answered automatically, and a close from the peer reads as the end of
+/// the stream.
+#[derive(Debug)]
+pub struct WebSocketStream<S> {
+    socket: WebSocket<S>,
+    /// Payload of the last received message and how much of it was read
+    incoming: Vec<u8>,
+    incoming_read: usize,
+    /// Bytes written since the last message was sent
+    outgoing: Vec<u8>,
+}
+
+impl<S: Read + Write> WebSocketStream<S> {
+    /// Run the server side of the WebSocket handshake on an accepted connection
+    ///
+    /// # Returns
+    /// * `Ok(WebSocketStream)` - Stream to wrap in a [`Channel`](crate::channel::Channel)
+    /// * `Err(GcError)` - The peer did not send a valid WebSocket upgrade request
+    pub fn accept(stream: S) -> Result<Self> {
+        let socket = tungstenite::accept_with_config(stream, Some(websocket_config()))
+            .map_err(|e| GcError::Protocol(format!("WebSocket handshake failed: {}", e)))?;
+        Ok(Self::new(socket))
+    }
+
+    /// Run the client side of the WebSocket handshake on a connected stream
+    ///
+    /// # Arguments
+    /// * `url` - `ws://` or `wss://` URL of the server; TLS, if any, must already run on `stream`
+    /// * `stream` - Connection to the server
+    ///
+    /// # Returns
+    /// * `Ok(WebSocketStream)` - Stream to wrap in a [`Channel`](crate::channel::Channel)
+    /// * `Err(GcError)` - Invalid URL, or the server refused the upgrade
+    pub fn connect(url: &str, stream: S) -> Result<Self> {
+        let (socket, _response) =
+            tungstenite::client::client_with_config(url, stream, Some(websocket_config()))
+                .map_err(|e| GcError::Protocol(format!("WebSocket handshake failed: {}", e)))?;
+        Ok(Self::new(socket))
+    }
+
+    fn new(socket: WebSocket<S>) -> Self {
+        Self {
+            socket,
+            incoming: Vec::new(),
+            incoming_read: 0,
+            outgoing: Vec::new(),
+        }
+    }
+
+    /// Send the pending bytes, if any, as one binary message
+    fn send_outgoing(&mut self) -> io::Result<()> {
+        if self.outgoing.is_empty() {
+            return Ok(());
+        }
+        let message = Message::binary(std::mem::take(&mut self.outgoing));
+        self.socket.send(message).map_err(into_io_error)
+    }
+
+    /// Send any pending bytes and close the connection
+    pub fn close(mut self) -> Result<()> {
+        self.send_outgoing()?;
+        match self.socket.close(None) {
+            Ok(()) | Err(tungstenite::Error::ConnectionClosed) => {}
+            Err(e) => return Err(into_io_error(e).into()),
+        }
+        // Wait for the peer's close frame
+        loop {
+            match self.socket.read() {
+                Ok(_) => {}
+                Err(
+                    tungstenite::Error::ConnectionClosed
+                    | tungstenite::Error::AlreadyClosed
+                    | tungstenite::Error::Protocol(ProtocolError::ResetWithoutClosingHandshake),
+                ) => return Ok(()),
+                Err(e) => return Err(into_io_error(e).into()),
+            }
+        }
+    }
+
+    /// Unwrap the underlying stream
+    pub fn into_inner(self) -> S {
+        self.socket.into_inner()
+    }
+}
+
+impl<S: Read + Write> Read for WebSocketStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.incoming_read == self.incoming.len() {
+            match self.socket.read() {
+                Ok(Message::Binary(data)) => {
+                    self.incoming = data.into();
+                    self.incoming_read = 0;
+                }
+                Ok(Message::Text(_)) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "WebSocket peer sent a text message, expected binary",
+                    ));
+                }
+                Ok(Message::Close(_))
+                | Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                    return Ok(0);
+                }
+                // Pongs to our pings are queued by tungstenite and sent with the next write
+                Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_)) => {}
+                Err(e) => return Err(into_io_error(e)),
+            }
+        }
+        let count = buf.len().min(self.incoming.len() - self.incoming_read);
+        buf[..count].copy_from_slice(&self.incoming[self.incoming_read..][..count]);
+        self.incoming_read += count;
+        Ok(count)
+    }
+}
+
+impl<S: Read + Write> Write for WebSocketStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = buf.len().min(MAX_WEBSOCKET_MESSAGE - self.outgoing.len());
+        self.outgoing.extend_from_slice(&buf[..count]);
+        if self.outgoing.len() == MAX_WEBSOCKET_MESSAGE {
+            self.send_outgoing()?;
+        }
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_outgoing()?;
+        self.socket.flush().map_err(into_io_error)
+    }
+}
+
+/// Message limits that fit [`MAX_WEBSOCKET_MESSAGE`] with room to spare
+fn websocket_config() -> WebSocketConfig {
+    WebSocketConfig::default()
+        .max_message_size(Some(4 * MAX_WEBSOCKET_MESSAGE))
+        .max_frame_size(Some(4 * MAX_WEBSOCKET_MESSAGE))
+}
+
+/// Keep I/O errors as they are, so a reset connection still reads as a disconnect
+fn into_io_error(error: tungstenite::Error) -> io::Error {
+    match error {
+        tungstenite::Error::Io(e) => e,
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => {
+            io::Error::new(io::ErrorKind::BrokenPipe, "WebSocket connection closed")
+        }
+        e => io::Error::other(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::Channel;
+    use crate::gate::Circuit;
+    use crate::partition::InputPartition;
+    use crate::plain::{SESSION_CIRCUIT, session_circuit_file};
+    use crate::progress::NoProgress;
+    use crate::two_party::{TwoPartyOptions, connect_and_evaluate_circuit, garble_and_serve};
+    use std::collections::{BTreeMap, HashMap};
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn test_session_over_websocket() -> Result<()> {
+        let circuit_file = session_circuit_file()?;
+        let circuit_path = circuit_file.path().to_path_buf();
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let garbler = std::thread::spawn(move || {
+            let (stream, _) = listener.accept()?;
+            let mut channel = Channel::new(WebSocketStream::accept(stream)?);
+            let report = garble_and_serve(
+                &mut channel,
+                &circuit_path,
+                &InputPartition::parse("0..2")?,
+                &HashMap::from([(0, true), (1, true)]),
+                &[4; 32],
+                &TwoPartyOptions::default(),
+                &NoProgress,
+            )?;
+            channel.into_inner().close()?;
+            Ok::<_, GcError>(report)
+        });
+
+        // The evaluator never touches the file system
+        let stream = TcpStream::connect(address)?;
+        let mut channel = Channel::new(WebSocketStream::connect(
+            &format!("ws://{}/session", address),
+            stream,
+        )?);
+        let (_, report) = connect_and_evaluate_circuit(
+            &mut channel,
+            &Circuit::parse(SESSION_CIRCUIT)?,
+            &HashMap::from([(2, false), (3, true)]),
+            &[5; 32],
+            &TwoPartyOptions::default(),
+            &NoProgress,
+        )?;
+        channel.into_inner().close()?;
+        let garbler_report = garbler.join().unwrap()?;
+
+        let expected = BTreeMap::from([(5, true), (6, true)]);
+        assert_eq!(report.outputs, expected);
+        assert_eq!(garbler_report.outputs, expected);
+        assert_eq!(garbler_report.bytes_sent, report.bytes_received);
+        Ok(())
+    }
+}
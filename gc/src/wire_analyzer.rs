@@ -1,81 +1,269 @@
-use anyhow::{Result, bail};
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::error::{GcError, Result};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufReader, Read, Write};
 use std::path::Path;
-
-use crate::constants::{BUFFER_SIZE, PROGRESS_UPDATE_INTERVAL};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::chunked_parse::ChunkedCircuit;
+use crate::constants::{
+    BUFFER_SIZE, PROGRESS_UPDATE_INTERVAL, WIRE_ANALYSIS_MAGIC, WIRE_ANALYSIS_VERSION,
+};
+use crate::gate::{Circuit, Gate, WireId};
+use crate::parser::{CircuitHeader, IoDeclaration, WireRef, read_header, visit_gate_wires};
+use crate::progress::ProgressObserver;
 use crate::stream::BufferedLineStream;
 
 /// Wire usage analysis results.
 /// can be exported as binary for fast loading and summarized as JSON for human readable reports.
-#[derive(Debug, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
 pub struct WireUsageReport {
     /// Total number of wires in the circuit
-    pub total_wires: u32,
+    pub total_wires: u64,
     /// Number of primary input wires
-    pub primary_inputs: u32,
+    pub primary_inputs: u64,
     /// Number of intermediate wires
-    pub intermediate_wires: u32,
+    pub intermediate_wires: u64,
     /// Number of primary output wires
-    pub primary_outputs: u32,
+    pub primary_outputs: u64,
     /// Number of missing/unused wires (gaps in wire numbering)
-    pub missing_wires_count: u32,
-    /// Wire usage counts: index = wire_id, value = usage_count (capped at 255)
-    /// Vec is used since our wire values are continuous and have no gaps.
+    pub missing_wires_count: u64,
+    /// Wire usage counts: wire_id -> usage_count (capped at 255)
+    /// Stored densely by default since our wire values are continuous and have no gaps.
     /// It is capped at 255 to save space, Wires used more than 255 times will be counted as 255.
     /// Wire used 255 times will be held in storage throughout.
     /// Analysis on actual circuit shows these are a tiny fraction of total wires.
-    pub wire_usage_counts: Vec<u8>,
+    pub wire_usage_counts: UsageCounts,
     /// List of primary input wire IDs
-    pub primary_input_wires: Vec<u32>,
+    pub primary_input_wires: Vec<WireId>,
     /// List of primary output wire IDs
-    pub primary_output_wires: Vec<u32>,
+    pub primary_output_wires: Vec<WireId>,
+}
+
+/// How [`UsageCounts`] are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UsageStorage {
+    /// One byte per wire ID, best when wire IDs are contiguous
+    #[default]
+    Dense,
+    /// A hash map of the wires that are actually read, for circuits whose
+    /// wire IDs are spread over a much larger range than they use
+    Sparse,
+}
+
+/// Number of gates reading each wire, capped at 255
+///
+/// The garbler and evaluator decrement these as gates are processed and drop
+/// a wire's label once its count reaches zero. A count of 255 marks a
+/// permanent wire that is never decremented.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, bincode::Encode, bincode::Decode)]
+pub enum UsageCounts {
+    /// index = wire_id
+    Dense(Vec<u8>),
+    /// Only wires with a nonzero count are present
+    Sparse(HashMap<WireId, u8>),
+}
+
+impl UsageCounts {
+    /// All-zero counts for wires `0..num_wires`
+    pub fn new(storage: UsageStorage, num_wires: u64) -> Self {
+        match storage {
+            UsageStorage::Dense => Self::Dense(vec![0; num_wires as usize]),
+            UsageStorage::Sparse => Self::Sparse(HashMap::new()),
+        }
+    }
+
+    /// Which storage these counts use
+    pub fn storage(&self) -> UsageStorage {
+        match self {
+            Self::Dense(_) => UsageStorage::Dense,
+            Self::Sparse(_) => UsageStorage::Sparse,
+        }
+    }
+
+    /// Current count of a wire, 0 for wires that are never read
+    pub fn get(&self, wire_id: WireId) -> u8 {
+        match self {
+            Self::Dense(counts) => counts.get(wire_id as usize).copied().unwrap_or(0),
+            Self::Sparse(counts) => counts.get(&wire_id).copied().unwrap_or(0),
+        }
+    }
+
+    /// Keep a wire's label for the whole run by giving it the permanent count 255
+    fn make_permanent(&mut self, wire_id: WireId) {
+        match self {
+            Self::Dense(counts) => counts[wire_id as usize] = 255,
+            Self::Sparse(counts) => {
+                counts.insert(wire_id, 255);
+            }
+        }
+    }
+
+    /// Count one more reader of a wire, saturating at 255
+    fn add_reader(&mut self, wire_id: WireId) {
+        let count = match self {
+            Self::Dense(counts) => &mut counts[wire_id as usize],
+            Self::Sparse(counts) => counts.entry(wire_id).or_insert(0),
+        };
+        *count = count.saturating_add(1);
+    }
+
+    /// Record that a gate read a wire
+    ///
+    /// # Returns
+    /// * `true` - This was the last reader, so the wire's label can be dropped
+    /// * `false` - The wire has readers left, is permanent, or was never counted
+    pub fn release(&mut self, wire_id: WireId) -> bool {
+        match self {
+            Self::Dense(counts) => match counts.get_mut(wire_id as usize) {
+                Some(count) => release_count(count),
+                None => false,
+            },
+            Self::Sparse(counts) => {
+                let Some(count) = counts.get_mut(&wire_id) else {
+                    return false;
+                };
+                let last_reader = release_count(count);
+                if last_reader {
+                    counts.remove(&wire_id);
+                }
+                last_reader
+            }
+        }
+    }
+
+    /// Wires with a nonzero count and their count, in no particular order
+    fn read_wires(&self) -> Box<dyn Iterator<Item = (WireId, u8)> + '_> {
+        match self {
+            Self::Dense(counts) => Box::new(
+                counts
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &count)| count > 0)
+                    .map(|(wire_id, &count)| (wire_id as WireId, count)),
+            ),
+            Self::Sparse(counts) => {
+                Box::new(counts.iter().map(|(&wire_id, &count)| (wire_id, count)))
+            }
+        }
+    }
+
+    /// Number of wires in `0..num_wires` having each count
+    pub fn distribution(&self, num_wires: u64) -> BTreeMap<u8, u64> {
+        let mut distribution = BTreeMap::new();
+        match self {
+            Self::Dense(counts) => {
+                for &count in counts {
+                    *distribution.entry(count).or_insert(0) += 1;
+                }
+            }
+            Self::Sparse(counts) => {
+                for &count in counts.values() {
+                    *distribution.entry(count).or_insert(0) += 1;
+                }
+                let unread = num_wires - counts.len() as u64;
+                if unread > 0 {
+                    distribution.insert(0, unread);
+                }
+            }
+        }
+        distribution
+    }
+}
+
+/// Decrement a nonzero, non-permanent count, returning whether it hit zero
+fn release_count(count: &mut u8) -> bool {
+    if *count == 0 {
+        return false;
+    }
+    // Wires with count 255 are never decremented (permanent wires)
+    if *count < 255 {
+        *count -= 1;
+    }
+    *count == 0
+}
+
+/// Lend a report to the garbler or evaluator, which copy its usage counts
+impl<'a> From<&'a WireUsageReport> for Cow<'a, WireUsageReport> {
+    fn from(report: &'a WireUsageReport) -> Self {
+        Cow::Borrowed(report)
+    }
+}
+
+/// Hand a report to the garbler or evaluator, which reuse its usage counts
+/// instead of copying them
+impl From<WireUsageReport> for Cow<'_, WireUsageReport> {
+    fn from(report: WireUsageReport) -> Self {
+        Cow::Owned(report)
+    }
+}
+
+/// Usage counts to decrement while processing gates
+///
+/// Moves the counts out of an owned report, leaving it with an empty
+/// `wire_usage_counts`, and only copies them from a borrowed one.
+pub(crate) fn take_usage_counts(report: &mut Cow<'_, WireUsageReport>) -> UsageCounts {
+    match report {
+        Cow::Borrowed(report) => report.wire_usage_counts.clone(),
+        Cow::Owned(report) => std::mem::replace(
+            &mut report.wire_usage_counts,
+            UsageCounts::Dense(Vec::new()),
+        ),
+    }
 }
 
 impl WireUsageReport {
     /// Save the report to a binary file for fast loading in processing pipelines
+    ///
+    /// Layout: [`WIRE_ANALYSIS_MAGIC`], the format version as a little-endian
+    /// `u32`, the bincode-encoded report, then the BLAKE3 checksum of
+    /// everything before it.
     pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        // Create a spinner progress bar for serialization
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} [{elapsed_precise}] {msg}")
-                .unwrap(),
-        );
-        pb.set_message("Serializing wire analysis data...");
-
-        let encoded = bincode::encode_to_vec(self, bincode::config::standard())?;
-
-        pb.set_message("Writing binary file...");
+        let mut encoded = Vec::new();
+        encoded.extend_from_slice(&WIRE_ANALYSIS_MAGIC);
+        encoded.extend_from_slice(&WIRE_ANALYSIS_VERSION.to_le_bytes());
+        bincode::encode_into_std_write(self, &mut encoded, bincode::config::standard())?;
+        let checksum = blake3::hash(&encoded);
+        encoded.extend_from_slice(checksum.as_bytes());
         std::fs::write(path, encoded)?;
-
-        pb.finish_with_message("✓ Binary file saved");
         Ok(())
     }
 
-    /// Load a report from a binary file
+    /// Load a report from a binary file, verifying its checksum
     pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Self> {
         let file = File::open(path.as_ref())?;
 
         // Use large buffered reading for performance
         let mut reader = BufReader::with_capacity(BUFFER_SIZE, file);
 
-        // Add progress indication for loading
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} [{elapsed_precise}] {msg}")
-                .unwrap(),
-        );
-        pb.set_message("Loading binary wire analysis data...");
-
         let mut buffer = Vec::new();
         reader.read_to_end(&mut buffer)?;
-        let (report, _) = bincode::decode_from_slice(&buffer, bincode::config::standard())?;
-
-        pb.finish_with_message("✓ Binary file loaded");
+        if buffer.len() < 8 + blake3::OUT_LEN || buffer[..4] != WIRE_ANALYSIS_MAGIC {
+            return Err(GcError::invalid_format(
+                "binary wire analysis file",
+                "bad magic bytes",
+            ));
+        }
+        let version = u32::from_le_bytes(buffer[4..8].try_into().unwrap());
+        if version != WIRE_ANALYSIS_VERSION {
+            return Err(GcError::FormatVersionMismatch {
+                format: "wire analysis",
+                found: version,
+                expected: WIRE_ANALYSIS_VERSION,
+            });
+        }
+        let (contents, checksum) = buffer.split_at(buffer.len() - blake3::OUT_LEN);
+        let checksum: [u8; blake3::OUT_LEN] = checksum.try_into().unwrap();
+        // blake3::Hash compares in constant time
+        if blake3::hash(contents) != checksum {
+            return Err(GcError::ChecksumMismatch {
+                artifact: "wire analysis file",
+            });
+        }
+        let (report, _) = bincode::decode_from_slice(&contents[8..], bincode::config::standard())?;
         Ok(report)
     }
 
@@ -99,37 +287,17 @@ impl WireUsageReport {
     /// Export wire usage count distribution as CSV
     /// Shows how many wires have usage count 0, 1, 2, etc.
     pub fn export_usage_distribution_csv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} [{elapsed_precise}] {msg}")
-                .unwrap(),
-        );
-        pb.set_message("Computing wire usage distribution...");
-
-        // Count frequency of each usage count
-        let mut usage_distribution: std::collections::HashMap<u8, usize> =
-            std::collections::HashMap::new();
-
-        for &usage_count in &self.wire_usage_counts {
-            *usage_distribution.entry(usage_count).or_insert(0) += 1;
-        }
-
-        pb.set_message("Writing distribution CSV...");
+        // Count frequency of each usage count, sorted by usage count
+        let usage_distribution = self.wire_usage_counts.distribution(self.total_wires);
 
         // Write CSV file
         let mut file = File::create(path)?;
         writeln!(file, "usage_count,wire_count")?;
 
-        // Sort by usage count for consistent output
-        let mut sorted_distribution: Vec<_> = usage_distribution.into_iter().collect();
-        sorted_distribution.sort_by_key(|&(usage_count, _)| usage_count);
-
-        for (usage_count, wire_count) in sorted_distribution {
+        for (usage_count, wire_count) in usage_distribution {
             writeln!(file, "{usage_count},{wire_count}")?;
         }
 
-        pb.finish_with_message("✓ Usage distribution CSV saved");
         Ok(())
     }
 }
@@ -142,201 +310,677 @@ impl WireUsageReport {
 ///
 /// # Arguments
 /// * `stream` - The line stream to process
+/// * `progress` - Receives progress updates
 ///
 /// # Returns  
 /// * `Ok(WireUsageReport)` - Complete wire usage analysis
-/// * `Err(anyhow::Error)` - Parse error or IO error
-pub fn analyze_wire_usage(stream: &mut BufferedLineStream) -> Result<WireUsageReport> {
-    // Parse the header line
-    let header_line = stream
-        .next_line()
-        .ok_or_else(|| anyhow::anyhow!("Missing header line"))??;
-
-    let header_tokens: Vec<&str> = header_line.split_whitespace().collect();
-    if header_tokens.len() != 2 {
-        bail!(
-            "Invalid header: expected '<num_gates> <num_wires>', got: '{}'",
-            header_line
-        );
-    }
-
-    let num_gates: u32 = header_tokens[0]
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid num_gates: '{}'", header_tokens[0]))?;
-    let num_wires: u32 = header_tokens[1]
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid num_wires: '{}'", header_tokens[1]))?;
+/// * `Err(GcError)` - Parse error or IO error
+pub fn analyze_wire_usage<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    progress: &dyn ProgressObserver,
+) -> Result<WireUsageReport> {
+    analyze_wire_usage_with_storage(stream, UsageStorage::Dense, progress)
+}
 
-    // Pre-allocate vectors with exact size needed
-    let mut wire_usage_counts = vec![0u8; num_wires as usize];
-    let mut wire_has_producer = vec![false; num_wires as usize];
-    let mut line_number = 1; // Already processed header
-    let mut gates_processed: u32 = 0; // Track actual gates processed
+/// Analyze wire usage, choosing how the usage counts are stored
+///
+/// With [`UsageStorage::Sparse`] memory grows with the number of wires the
+/// circuit actually uses rather than with `num_wires`, and the garbler and
+/// evaluator keep that storage for their own counts.
+///
+/// # Arguments
+/// * `stream` - The line stream to process
+/// * `storage` - Storage of the usage counts in the report
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(WireUsageReport)` - Complete wire usage analysis
+/// * `Err(GcError)` - Parse error or IO error
+#[tracing::instrument(name = "wire_analysis", skip_all)]
+pub fn analyze_wire_usage_with_storage<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    storage: UsageStorage,
+    progress: &dyn ProgressObserver,
+) -> Result<WireUsageReport> {
+    let CircuitHeader {
+        num_gates,
+        num_wires,
+        io,
+        lines,
+    } = read_header(stream)?;
+
+    let mut tally = UsageTally::new(storage, num_wires);
+    let mut line_number = lines; // Already processed header
+    let mut gates_processed: u64 = 0; // Track actual gates processed
 
     // Create a progress bar with known total gates
-    let pb = ProgressBar::new(num_gates as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template(
-                "{bar:40.green/blue} {pos}/{len} gates [{elapsed_precise}<{eta_precise}] {msg}",
-            )
-            .unwrap()
-            .progress_chars("#>-"),
-    );
-    pb.set_message("Analyzing wire usage");
+    progress.start(Some(num_gates), "Analyzing wire usage");
 
     // Process each gate
     while let Some(line_result) = stream.next_line() {
         line_number += 1;
         let line = line_result?;
 
-        if line.trim().is_empty() {
-            bail!("Empty line at line number {}", line_number);
-        }
-
-        // Parse gate line directly using iterator (more efficient)
-        let mut tokens = line.split_whitespace();
-
-        // Parse num_inputs and num_outputs
-        let num_inputs: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing num_inputs at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid num_inputs at line {}: '{}'", line_number, line)
-            })?;
-
-        let num_outputs: u32 = tokens
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing num_outputs at line {}", line_number))?
-            .parse()
-            .map_err(|_| {
-                anyhow::anyhow!("Invalid num_outputs at line {}: '{}'", line_number, line)
-            })?;
-
-        // Process input wires directly (no intermediate allocation)
-        for i in 0..num_inputs {
-            let wire_id: u32 = tokens
-                .next()
-                .ok_or_else(|| anyhow::anyhow!("Missing input wire {} at line {}", i, line_number))?
-                .parse()
-                .map_err(|_| {
-                    anyhow::anyhow!("Invalid input wire ID at line {}: '{}'", line_number, line)
-                })?;
+        tally_gate_line(line, line_number, num_wires, |wire| tally.record(wire))?;
+
+        gates_processed += 1;
+
+        // Update progress bar periodically for better performance
+        if gates_processed.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            progress.update(gates_processed);
+        }
+    }
+
+    // Finish progress bar with final position
+    progress.update(gates_processed);
+    progress.finish(&format!(
+        "✓ Analyzed {gates_processed} gates, found {num_wires} wires"
+    ));
+
+    // Validate that we processed the expected number of gates
+    if gates_processed != num_gates {
+        tracing::warn!(
+            expected = num_gates,
+            processed = gates_processed,
+            "gate count differs from circuit header"
+        );
+    }
+    tracing::info!(gates = gates_processed, num_wires, "wire analysis finished");
+
+    tally.classify(num_wires, io.as_ref())
+}
+
+/// Analyze wire usage on several threads
+///
+/// Same report as [`analyze_wire_usage_with_storage`], from chunks of the
+/// mapped circuit file scanned in parallel. Dense usage counts are updated
+/// in place by all threads at once, so memory stays that of a sequential
+/// analysis; sparse counts are gathered per thread and merged.
+///
+/// # Arguments
+/// * `circuit` - Mapped circuit file
+/// * `storage` - Storage of the usage counts in the report
+/// * `threads` - Worker threads
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(WireUsageReport)` - Complete wire usage analysis
+/// * `Err(GcError)` - Parse error or IO error
+#[tracing::instrument(name = "wire_analysis", skip_all)]
+pub fn analyze_wire_usage_parallel(
+    circuit: &ChunkedCircuit,
+    storage: UsageStorage,
+    threads: usize,
+    progress: &dyn ProgressObserver,
+) -> Result<WireUsageReport> {
+    let CircuitHeader {
+        num_gates,
+        num_wires,
+        ref io,
+        ..
+    } = *circuit.header();
+
+    progress.start(Some(num_gates), "Analyzing wire usage");
+
+    let (tally, gates_processed) = match storage {
+        UsageStorage::Dense => {
+            let shared = SharedTally::new(num_wires);
+            let (_, gates_processed) = circuit.scan(
+                threads,
+                progress,
+                || (),
+                |_, line, line_number| {
+                    tally_gate_line(line, line_number, num_wires, |wire| shared.record(wire))
+                },
+            )?;
+            (shared.into_tally(), gates_processed)
+        }
+        UsageStorage::Sparse => {
+            let (tallies, gates_processed) = circuit.scan(
+                threads,
+                progress,
+                || UsageTally::new(storage, num_wires),
+                |tally, line, line_number| {
+                    tally_gate_line(line, line_number, num_wires, |wire| tally.record(wire))
+                },
+            )?;
+            let tally = tallies
+                .into_iter()
+                .reduce(UsageTally::merge)
+                .unwrap_or_else(|| UsageTally::new(storage, num_wires));
+            (tally, gates_processed)
+        }
+    };
+
+    progress.update(gates_processed);
+    progress.finish(&format!(
+        "✓ Analyzed {gates_processed} gates, found {num_wires} wires"
+    ));
+
+    if gates_processed != num_gates {
+        tracing::warn!(
+            expected = num_gates,
+            processed = gates_processed,
+            "gate count differs from circuit header"
+        );
+    }
+    tracing::info!(
+        gates = gates_processed,
+        num_wires,
+        threads,
+        "wire analysis finished"
+    );
+
+    tally.classify(num_wires, io.as_ref())
+}
+
+/// Check the wires of one gate line and pass them to `record`
+fn tally_gate_line(
+    line: &str,
+    line_number: u64,
+    num_wires: u64,
+    mut record: impl FnMut(WireRef),
+) -> Result<()> {
+    visit_gate_wires(line, line_number, |wire| {
+        wire.check_range(num_wires, line_number)?;
+        record(wire);
+        Ok(())
+    })?;
+    Ok(())
+}
 
+/// Analyze wire usage of a parsed circuit
+///
+/// Same as [`analyze_gates`], except that the inputs and outputs declared by
+/// a Bristol Fashion header take precedence over the inferred ones.
+///
+/// # Returns
+/// * `Ok(WireUsageReport)` - Complete wire usage analysis
+/// * `Err(GcError)` - A gate references a wire outside `0..num_wires`, or
+///   writes a declared input
+pub fn analyze_circuit(circuit: &Circuit) -> Result<WireUsageReport> {
+    analyze_gates_with_io(&circuit.gates, circuit.num_wires, circuit.io.as_ref())
+}
+
+/// Analyze wire usage of a circuit held in memory
+///
+/// Same classification as [`analyze_wire_usage`], for gates that were already
+/// parsed or built programmatically.
+///
+/// # Arguments
+/// * `gates` - Circuit gates
+/// * `num_wires` - Total number of wires in the circuit
+///
+/// # Returns
+/// * `Ok(WireUsageReport)` - Complete wire usage analysis
+/// * `Err(GcError)` - A gate references a wire outside `0..num_wires`
+pub fn analyze_gates(gates: &[Gate], num_wires: u64) -> Result<WireUsageReport> {
+    analyze_gates_with_io(gates, num_wires, None)
+}
+
+#[tracing::instrument(name = "wire_analysis", skip_all)]
+fn analyze_gates_with_io(
+    gates: &[Gate],
+    num_wires: u64,
+    io: Option<&IoDeclaration>,
+) -> Result<WireUsageReport> {
+    let mut tally = UsageTally::new(UsageStorage::Dense, num_wires);
+
+    for (gate_index, gate) in gates.iter().enumerate() {
+        for wire_id in gate.input_wires().chain([gate.output_wire]) {
             if wire_id >= num_wires {
-                bail!(
-                    "Invalid input wire ID {} exceeds num_wires {} at line {}",
-                    wire_id,
-                    num_wires,
-                    line_number
-                );
+                return Err(GcError::InvalidCircuit(format!(
+                    "Wire ID {} exceeds num_wires {} at gate {}",
+                    wire_id, num_wires, gate_index
+                )));
             }
-            wire_usage_counts[wire_id as usize] =
-                wire_usage_counts[wire_id as usize].saturating_add(1);
-        }
-
-        // Process output wires directly (no intermediate allocation)
-        for i in 0..num_outputs {
-            let wire_id: u32 = tokens
-                .next()
-                .ok_or_else(|| {
-                    anyhow::anyhow!("Missing output wire {} at line {}", i, line_number)
-                })?
-                .parse()
-                .map_err(|_| {
-                    anyhow::anyhow!("Invalid output wire ID at line {}: '{}'", line_number, line)
-                })?;
+        }
+        for wire_id in gate.input_wires() {
+            tally.read(wire_id);
+        }
+        tally.produce(gate.output_wire);
+    }
 
-            if wire_id >= num_wires {
-                bail!(
-                    "Invalid output wire ID {} exceeds num_wires {} at line {}",
-                    wire_id,
-                    num_wires,
-                    line_number
+    tally.classify(num_wires, io)
+}
+
+/// Usage counts and producers gathered while scanning the gates
+pub(crate) struct UsageTally {
+    /// Readers of every wire
+    usage_counts: UsageCounts,
+    /// Wires that are the output of some gate
+    producers: Producers,
+}
+
+/// Dense usage counts and producers updated by several threads at once
+struct SharedTally {
+    /// Readers of every wire, index = wire_id
+    usage_counts: Vec<AtomicU8>,
+    /// Whether each wire is the output of some gate, index = wire_id
+    producers: Vec<AtomicBool>,
+}
+
+impl SharedTally {
+    fn new(num_wires: u64) -> Self {
+        Self {
+            usage_counts: (0..num_wires).map(|_| AtomicU8::new(0)).collect(),
+            producers: (0..num_wires).map(|_| AtomicBool::new(false)).collect(),
+        }
+    }
+
+    /// A gate line references `wire`
+    fn record(&self, wire: WireRef) {
+        match wire {
+            WireRef::Input(wire_id) => {
+                // Saturates at 255 like UsageCounts::add_reader
+                let _ = self.usage_counts[wire_id as usize].fetch_update(
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                    |count| count.checked_add(1),
                 );
             }
-            wire_has_producer[wire_id as usize] = true;
+            WireRef::Output(wire_id) => {
+                self.producers[wire_id as usize].store(true, Ordering::Relaxed);
+            }
         }
+    }
 
-        // Validate we have the gate type token (but don't need to parse it)
-        if tokens.next().is_none() {
-            bail!("Missing gate type at line {}: '{}'", line_number, line);
+    /// The counts once every thread is done
+    fn into_tally(self) -> UsageTally {
+        UsageTally {
+            usage_counts: UsageCounts::Dense(
+                self.usage_counts
+                    .into_iter()
+                    .map(AtomicU8::into_inner)
+                    .collect(),
+            ),
+            producers: Producers::Dense(
+                self.producers
+                    .into_iter()
+                    .map(AtomicBool::into_inner)
+                    .collect(),
+            ),
         }
+    }
+}
 
-        // Validate no extra tokens
-        if tokens.next().is_some() {
-            bail!("Too many tokens at line {}: '{}'", line_number, line);
+/// Set of produced wires, stored like the usage counts
+enum Producers {
+    /// index = wire_id
+    Dense(Vec<bool>),
+    /// Produced wire IDs
+    Sparse(HashSet<WireId>),
+}
+
+impl UsageTally {
+    pub(crate) fn new(storage: UsageStorage, num_wires: u64) -> Self {
+        let producers = match storage {
+            UsageStorage::Dense => Producers::Dense(vec![false; num_wires as usize]),
+            UsageStorage::Sparse => Producers::Sparse(HashSet::new()),
+        };
+        Self {
+            usage_counts: UsageCounts::new(storage, num_wires),
+            producers,
         }
+    }
 
-        gates_processed += 1;
+    /// A gate reads `wire_id`
+    fn read(&mut self, wire_id: WireId) {
+        self.usage_counts.add_reader(wire_id);
+    }
 
-        // Update progress bar periodically for better performance
-        if gates_processed.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
-            pb.set_position(gates_processed as u64);
+    /// A gate writes `wire_id`
+    fn produce(&mut self, wire_id: WireId) {
+        match &mut self.producers {
+            Producers::Dense(produced) => produced[wire_id as usize] = true,
+            Producers::Sparse(produced) => {
+                produced.insert(wire_id);
+            }
+        }
+    }
+
+    /// A gate line references `wire`
+    pub(crate) fn record(&mut self, wire: WireRef) {
+        match wire {
+            WireRef::Input(wire_id) => self.read(wire_id),
+            WireRef::Output(wire_id) => self.produce(wire_id),
+        }
+    }
+
+    /// Add the counts and producers of a tally of other gates, with the same storage
+    fn merge(mut self, other: Self) -> Self {
+        match (&mut self.usage_counts, other.usage_counts) {
+            (UsageCounts::Dense(counts), UsageCounts::Dense(other_counts)) => {
+                for (count, other_count) in counts.iter_mut().zip(other_counts) {
+                    *count = count.saturating_add(other_count);
+                }
+            }
+            (UsageCounts::Sparse(counts), UsageCounts::Sparse(other_counts)) => {
+                for (wire_id, other_count) in other_counts {
+                    let count = counts.entry(wire_id).or_insert(0);
+                    *count = count.saturating_add(other_count);
+                }
+            }
+            _ => unreachable!("tallies of one analysis share their storage"),
+        }
+        match (&mut self.producers, other.producers) {
+            (Producers::Dense(produced), Producers::Dense(other_produced)) => {
+                for (written, other_written) in produced.iter_mut().zip(other_produced) {
+                    *written |= other_written;
+                }
+            }
+            (Producers::Sparse(produced), Producers::Sparse(other_produced)) => {
+                produced.extend(other_produced);
+            }
+            _ => unreachable!("tallies of one analysis share their storage"),
+        }
+        self
+    }
+
+    /// Whether some gate writes `wire_id`
+    fn is_produced(&self, wire_id: WireId) -> bool {
+        match &self.producers {
+            Producers::Dense(produced) => produced[wire_id as usize],
+            Producers::Sparse(produced) => produced.contains(&wire_id),
         }
     }
 
-    // Classify wires
-    let mut primary_input_wires: Vec<u32> = Vec::new();
-    let mut primary_output_wires: Vec<u32> = Vec::new();
-    let mut intermediate_count: u32 = 0;
-    let mut missing_wires_count: u32 = 0;
+    /// Classify every wire as primary input, intermediate, primary output or missing
+    ///
+    /// Without declarations, primary inputs are the wires read but never
+    /// written and primary outputs the wires written but never read. Declared
+    /// inputs and outputs replace both lists; declared outputs that gates
+    /// also read are made permanent so their labels outlive their readers.
+    pub(crate) fn classify(
+        self,
+        num_wires: u64,
+        io: Option<&IoDeclaration>,
+    ) -> Result<WireUsageReport> {
+        if let Some(io) = io {
+            return self.classify_declared(num_wires, io);
+        }
 
-    for wire_id in 0..num_wires {
-        let usage_count = wire_usage_counts[wire_id as usize];
-        let has_producer = wire_has_producer[wire_id as usize];
+        // Sparse tallies only visit the wires that appear in some gate
+        let wires_seen: Vec<WireId> = match &self.producers {
+            Producers::Dense(_) => (0..num_wires).collect(),
+            Producers::Sparse(produced) => {
+                let UsageCounts::Sparse(counts) = &self.usage_counts else {
+                    unreachable!("sparse producers come with sparse usage counts")
+                };
+                let mut wires: Vec<WireId> = counts.keys().chain(produced).copied().collect();
+                wires.sort_unstable();
+                wires.dedup();
+                wires
+            }
+        };
+
+        // Classify wires
+        let mut primary_input_wires: Vec<WireId> = Vec::new();
+        let mut primary_output_wires: Vec<WireId> = Vec::new();
+        let mut intermediate_count: u64 = 0;
+        let mut missing_wires_count: u64 = num_wires - wires_seen.len() as u64;
+
+        for wire_id in wires_seen {
+            let usage_count = self.usage_counts.get(wire_id);
+            let has_producer = self.is_produced(wire_id);
+
+            // Missing wire: never referenced as input or output in any gate
+            if usage_count == 0 && !has_producer {
+                missing_wires_count += 1;
+            } else if !has_producer {
+                // Wire used as input but never produced -> primary input
+                primary_input_wires.push(wire_id);
+            } else if usage_count == 0 {
+                // Wire produced but never used -> primary output
+                primary_output_wires.push(wire_id);
+            } else {
+                // Wire produced and used -> intermediate
+                intermediate_count += 1;
+            }
+        }
 
-        // Missing wire: never referenced as input or output in any gate
-        if usage_count == 0 && !has_producer {
-            missing_wires_count += 1;
-        } else if !has_producer {
-            // Wire used as input but never produced -> primary input
-            primary_input_wires.push(wire_id);
-        } else if usage_count == 0 {
-            // Wire produced but never used -> primary output
-            primary_output_wires.push(wire_id);
+        Ok(WireUsageReport {
+            total_wires: num_wires,
+            primary_inputs: primary_input_wires.len() as u64,
+            intermediate_wires: intermediate_count,
+            primary_outputs: primary_output_wires.len() as u64,
+            missing_wires_count,
+            wire_usage_counts: self.usage_counts,
+            primary_input_wires,
+            primary_output_wires,
+        })
+    }
+
+    /// Classify with the inputs and outputs of a Bristol Fashion header
+    fn classify_declared(mut self, num_wires: u64, io: &IoDeclaration) -> Result<WireUsageReport> {
+        let input_wires = io.input_wires();
+        let output_wires = io.output_wires(num_wires);
+
+        if let Some(wire_id) = input_wires
+            .clone()
+            .find(|&wire_id| self.is_produced(wire_id))
+        {
+            return Err(GcError::InvalidCircuit(format!(
+                "Declared input wire {} is written by a gate",
+                wire_id
+            )));
+        }
+        if let Some(wire_id) = output_wires
+            .clone()
+            .find(|&wire_id| !self.is_produced(wire_id))
+        {
+            return Err(GcError::InvalidCircuit(format!(
+                "Declared output wire {} is not written by any gate",
+                wire_id
+            )));
+        }
+        for wire_id in output_wires.clone() {
+            if self.usage_counts.get(wire_id) > 0 {
+                self.usage_counts.make_permanent(wire_id);
+            }
+        }
+
+        // Wires written by a gate, other than the outputs, are intermediate
+        // even if nothing reads them
+        let produced = match &self.producers {
+            Producers::Dense(produced) => produced.iter().filter(|&&written| written).count(),
+            Producers::Sparse(produced) => produced.len(),
+        } as u64;
+        let produced_outputs = output_wires
+            .clone()
+            .filter(|&wire_id| self.is_produced(wire_id))
+            .count() as u64;
+        let intermediate_count = produced - produced_outputs;
+
+        let primary_input_wires: Vec<WireId> = input_wires.collect();
+        let primary_output_wires: Vec<WireId> = output_wires.collect();
+        let primary_inputs = primary_input_wires.len() as u64;
+        let primary_outputs = primary_output_wires.len() as u64;
+        Ok(WireUsageReport {
+            total_wires: num_wires,
+            primary_inputs,
+            intermediate_wires: intermediate_count,
+            primary_outputs,
+            missing_wires_count: num_wires - primary_inputs - primary_outputs - intermediate_count,
+            wire_usage_counts: self.usage_counts,
+            primary_input_wires,
+            primary_output_wires,
+        })
+    }
+}
+
+/// Number of highest fan-out wires a [`FanOutReport`] lists unless told otherwise
+pub const DEFAULT_TOP_FAN_OUT_WIRES: usize = 20;
+
+/// A wire among the most read of a circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighFanOutWire {
+    /// The wire
+    pub wire: WireId,
+    /// Number of gate inputs reading it, not capped
+    pub fan_out: u64,
+    /// Gate writing it, 0-based in file order; `None` for primary inputs
+    pub producer: Option<u64>,
+}
+
+/// Fan-out distribution of a circuit, see [`analyze_fan_out`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FanOutReport {
+    /// Number of wires having each fan-out: fan_out -> wire_count
+    pub histogram: BTreeMap<u64, u64>,
+    /// Wires of highest fan-out, highest first and ties by wire ID
+    pub top_wires: Vec<HighFanOutWire>,
+}
+
+impl FanOutReport {
+    /// Save the report as JSON
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Export the fan-out histogram as CSV
+    ///
+    /// # CSV Format
+    /// ```csv
+    /// fan_out,wire_count
+    /// 0,128
+    /// 1,250000
+    /// 3000,1
+    /// ```
+    pub fn export_histogram_csv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "fan_out,wire_count")?;
+        for (fan_out, wire_count) in &self.histogram {
+            writeln!(file, "{fan_out},{wire_count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Exact fan-out distribution of a circuit and its wires of highest fan-out
+///
+/// Usage counts stop at 255, so a wire analysis cannot tell a wire read 300
+/// times from one read a million times, nor which gate writes it. These
+/// permanent wires hold their labels for the whole run. This second pass
+/// counts the reads of every wire whose count is capped, and records the
+/// gate writing those and the `top` wires of highest count below the cap,
+/// the only other wires that can make the list. Memory grows with the number
+/// of capped wires, a tiny fraction of the wires of most circuits.
+///
+/// # Arguments
+/// * `stream` - The line stream of the circuit `wire_report` was made from
+/// * `wire_report` - Wire usage analysis of the circuit
+/// * `top` - Number of highest fan-out wires to list
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(FanOutReport)` - Exact fan-out histogram and the `top` wires of highest fan-out
+/// * `Err(GcError)` - Parse error or IO error
+#[tracing::instrument(name = "fan_out_analysis", skip_all)]
+pub fn analyze_fan_out<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    wire_report: &WireUsageReport,
+    top: usize,
+    progress: &dyn ProgressObserver,
+) -> Result<FanOutReport> {
+    let header = read_header(stream)?;
+    let usage_counts = &wire_report.wire_usage_counts;
+
+    // Every capped wire, and the `top` others of highest count
+    let mut candidates: HashMap<WireId, HighFanOutWire> = HashMap::new();
+    let mut highest_uncapped = BinaryHeap::with_capacity(top + 1);
+    for (wire_id, count) in usage_counts.read_wires() {
+        if count == u8::MAX {
+            candidates.insert(
+                wire_id,
+                HighFanOutWire {
+                    wire: wire_id,
+                    fan_out: 0,
+                    producer: None,
+                },
+            );
         } else {
-            // Wire produced and used -> intermediate
-            intermediate_count += 1;
+            highest_uncapped.push(Reverse((count, Reverse(wire_id))));
+            if highest_uncapped.len() > top {
+                highest_uncapped.pop();
+            }
         }
     }
+    let num_capped = candidates.len();
+    for Reverse((count, Reverse(wire_id))) in highest_uncapped {
+        candidates.insert(
+            wire_id,
+            HighFanOutWire {
+                wire: wire_id,
+                fan_out: u64::from(count),
+                producer: None,
+            },
+        );
+    }
 
-    // Finish progress bar with final position
-    pb.set_position(gates_processed as u64);
-    pb.finish_with_message(format!(
-        "✓ Analyzed {gates_processed} gates, found {num_wires} wires"
+    progress.start(Some(header.num_gates), "Analyzing fan-out");
+    let mut line_number = header.lines;
+    let mut gates_processed = 0u64;
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        visit_gate_wires(line_result?, line_number, |wire| {
+            match wire {
+                WireRef::Input(wire_id) if usage_counts.get(wire_id) == u8::MAX => {
+                    if let Some(candidate) = candidates.get_mut(&wire_id) {
+                        candidate.fan_out += 1;
+                    }
+                }
+                WireRef::Input(_) => {}
+                WireRef::Output(wire_id) => {
+                    if let Some(candidate) = candidates.get_mut(&wire_id) {
+                        candidate.producer = Some(gates_processed);
+                    }
+                }
+            }
+            Ok(())
+        })?;
+
+        gates_processed += 1;
+        if gates_processed.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            progress.update(gates_processed);
+        }
+    }
+    progress.update(gates_processed);
+    progress.finish(&format!(
+        "✓ Analyzed {gates_processed} gates, {num_capped} wires read 255 times or more"
     ));
 
-    // Validate that we processed the expected number of gates
-    if gates_processed != num_gates {
-        eprintln!("Warning: Expected {num_gates} gates but processed {gates_processed}");
-    }
-
-    Ok(WireUsageReport {
-        total_wires: num_wires,
-        primary_inputs: primary_input_wires.len() as u32,
-        intermediate_wires: intermediate_count,
-        primary_outputs: primary_output_wires.len() as u32,
-        missing_wires_count,
-        wire_usage_counts,
-        primary_input_wires,
-        primary_output_wires,
+    // Counts below the cap are exact; the capped wires were just counted
+    let mut histogram: BTreeMap<u64, u64> = usage_counts
+        .distribution(wire_report.total_wires)
+        .into_iter()
+        .filter(|&(count, _)| count < u8::MAX)
+        .map(|(count, wire_count)| (u64::from(count), wire_count))
+        .collect();
+    for candidate in candidates.values() {
+        if usage_counts.get(candidate.wire) == u8::MAX {
+            *histogram.entry(candidate.fan_out).or_insert(0) += 1;
+        }
+    }
+
+    let mut top_wires: Vec<HighFanOutWire> = candidates.into_values().collect();
+    top_wires.sort_unstable_by_key(|wire| (Reverse(wire.fan_out), wire.wire));
+    top_wires.truncate(top);
+
+    Ok(FanOutReport {
+        histogram,
+        top_wires,
     })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::progress::NoProgress;
+    use crate::test_util::create_test_file;
     use std::fs::File;
-    use std::io::Write;
-
-    fn create_test_file(content: &str) -> Result<tempfile::NamedTempFile> {
-        let mut temp_file = tempfile::NamedTempFile::new()?;
-        temp_file.write_all(content.as_bytes())?;
-        temp_file.flush()?;
-        Ok(temp_file)
-    }
 
     #[test]
     fn test_analyze_wire_usage_basic() -> Result<()> {
@@ -345,7 +989,7 @@ mod tests {
         let file = File::open(temp_file.path())?;
         let mut stream = BufferedLineStream::new(file);
 
-        let report = analyze_wire_usage(&mut stream)?;
+        let report = analyze_wire_usage(&mut stream, &NoProgress)?;
 
         assert_eq!(report.total_wires, 6);
         assert_eq!(report.primary_inputs, 3); // wires 0, 1, 3
@@ -354,7 +998,155 @@ mod tests {
         assert_eq!(report.missing_wires_count, 0);
         assert_eq!(report.primary_input_wires, vec![0, 1, 3]);
         assert_eq!(report.primary_output_wires, vec![5]);
-        assert_eq!(report.wire_usage_counts.len(), 6);
+        assert_eq!(
+            report.wire_usage_counts,
+            UsageCounts::Dense(vec![1, 1, 1, 2, 1, 0])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bristol_fashion_declarations() -> Result<()> {
+        // Inputs w0 and w1 (w1 unused), outputs w3 and w4 (w3 also read),
+        // and w2 missing; inference alone would find only w0 and w4
+        let circuit_data = "2 5\n2 1 1\n1 2\n\n2 1 0 0 3 AND\n2 1 3 0 4 XOR\n";
+        let temp_file = create_test_file(circuit_data)?;
+        for storage in [UsageStorage::Dense, UsageStorage::Sparse] {
+            let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+            let report = analyze_wire_usage_with_storage(&mut stream, storage, &NoProgress)?;
+            assert_eq!(report.primary_input_wires, vec![0, 1]);
+            assert_eq!(report.primary_output_wires, vec![3, 4]);
+            assert_eq!(report.intermediate_wires, 0);
+            assert_eq!(report.missing_wires_count, 1);
+            // The output read by a gate is kept for the whole run
+            assert_eq!(report.wire_usage_counts.get(3), 255);
+        }
+
+        let error_msg = analyze_circuit(&Circuit::parse("1 3\n1 2\n1 1\n2 1 0 0 1 AND\n")?)
+            .unwrap_err()
+            .to_string();
+        assert!(error_msg.contains("Declared input wire 1 is written by a gate"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyze_gates_matches_stream() -> Result<()> {
+        let circuit_data = "3 6\n2 1 0 1 2 XOR\n2 1 2 3 4 AND\n2 1 3 4 5 XOR\n";
+        let temp_file = create_test_file(circuit_data)?;
+        let mut stream = BufferedLineStream::new(File::open(temp_file.path())?);
+        let from_stream = analyze_wire_usage(&mut stream, &NoProgress)?;
+
+        let gates = [Gate::xor(0, 1, 2), Gate::and(2, 3, 4), Gate::xor(3, 4, 5)];
+        let in_memory = analyze_gates(&gates, 6)?;
+
+        assert_eq!(
+            in_memory.primary_input_wires,
+            from_stream.primary_input_wires
+        );
+        assert_eq!(
+            in_memory.primary_output_wires,
+            from_stream.primary_output_wires
+        );
+        assert_eq!(in_memory.wire_usage_counts, from_stream.wire_usage_counts);
+        assert_eq!(in_memory.intermediate_wires, from_stream.intermediate_wires);
+
+        let error_msg = analyze_gates(&gates, 5).unwrap_err().to_string();
+        assert!(error_msg.contains("Wire ID 5 exceeds num_wires 5 at gate 2"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_take_usage_counts() -> Result<()> {
+        let wire_report = analyze_gates(&[Gate::and(0, 1, 2), Gate::xor(2, 0, 3)], 4)?;
+
+        let mut borrowed = Cow::from(&wire_report);
+        let counts = UsageCounts::Dense(vec![2, 1, 1, 0]);
+        assert_eq!(take_usage_counts(&mut borrowed), counts);
+        assert_eq!(borrowed.wire_usage_counts, counts);
+
+        let mut owned = Cow::from(wire_report);
+        assert_eq!(take_usage_counts(&mut owned), counts);
+        assert_eq!(owned.wire_usage_counts, UsageCounts::Dense(Vec::new()));
+        assert_eq!(owned.primary_output_wires, vec![3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sparse_storage_matches_dense() -> Result<()> {
+        // Wire 6 is missing, wire 0 is read twice
+        let circuit_data = "3 8\n2 1 0 1 2 XOR\n2 1 2 0 3 AND\n2 1 3 4 5 XOR\n";
+        let temp_file = create_test_file(circuit_data)?;
+        let dense = analyze_wire_usage(
+            &mut BufferedLineStream::new(File::open(temp_file.path())?),
+            &NoProgress,
+        )?;
+        let sparse = analyze_wire_usage_with_storage(
+            &mut BufferedLineStream::new(File::open(temp_file.path())?),
+            UsageStorage::Sparse,
+            &NoProgress,
+        )?;
+
+        assert_eq!(sparse.wire_usage_counts.storage(), UsageStorage::Sparse);
+        assert_eq!(sparse.primary_input_wires, dense.primary_input_wires);
+        assert_eq!(sparse.primary_output_wires, dense.primary_output_wires);
+        assert_eq!(sparse.intermediate_wires, dense.intermediate_wires);
+        assert_eq!(sparse.missing_wires_count, dense.missing_wires_count);
+        assert_eq!(sparse.missing_wires_count, 2);
+        for wire_id in 0..8 {
+            assert_eq!(
+                sparse.wire_usage_counts.get(wire_id),
+                dense.wire_usage_counts.get(wire_id)
+            );
+        }
+        assert_eq!(
+            sparse.wire_usage_counts.distribution(8),
+            dense.wire_usage_counts.distribution(8)
+        );
+
+        // Releasing the last reader drops the entry
+        let mut counts = sparse.wire_usage_counts;
+        assert!(!counts.release(0));
+        assert!(counts.release(0));
+        assert!(!counts.release(0));
+        assert_eq!(counts.get(0), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() -> Result<()> {
+        // Wire 0 is read by every gate, past the 255 cap; even gates also
+        // read the previous gate's output
+        let mut circuit_data = String::from("400 801\n");
+        for gate in 1..=400 {
+            let second_input = if gate % 2 == 0 { 400 + gate - 1 } else { gate };
+            circuit_data.push_str(&format!("2 1 0 {} {} XOR\n", second_input, 400 + gate));
+        }
+        let temp_file = create_test_file(&circuit_data)?;
+        let chunked = ChunkedCircuit::open(temp_file.path(), false)?;
+
+        for storage in [UsageStorage::Dense, UsageStorage::Sparse] {
+            let sequential = analyze_wire_usage_with_storage(
+                &mut BufferedLineStream::new(File::open(temp_file.path())?),
+                storage,
+                &NoProgress,
+            )?;
+            let parallel = analyze_wire_usage_parallel(&chunked, storage, 4, &NoProgress)?;
+
+            assert_eq!(parallel.wire_usage_counts, sequential.wire_usage_counts);
+            assert_eq!(parallel.wire_usage_counts.get(0), 255);
+            assert_eq!(parallel.primary_input_wires, sequential.primary_input_wires);
+            assert_eq!(
+                parallel.primary_output_wires,
+                sequential.primary_output_wires
+            );
+            assert_eq!(parallel.intermediate_wires, sequential.intermediate_wires);
+            assert_eq!(parallel.missing_wires_count, sequential.missing_wires_count);
+        }
 
         Ok(())
     }
@@ -366,7 +1158,7 @@ mod tests {
         let file = File::open(temp_file.path())?;
         let mut stream = BufferedLineStream::new(file);
 
-        let report = analyze_wire_usage(&mut stream)?;
+        let report = analyze_wire_usage(&mut stream, &NoProgress)?;
 
         assert_eq!(report.total_wires, 3);
         assert_eq!(report.primary_inputs, 2); // wires 0, 1
@@ -386,14 +1178,14 @@ mod tests {
         let file = File::open(temp_file.path())?;
         let mut stream = BufferedLineStream::new(file);
 
-        let report = analyze_wire_usage(&mut stream)?;
+        let report = analyze_wire_usage(&mut stream, &NoProgress)?;
 
         assert_eq!(report.total_wires, 0);
         assert_eq!(report.primary_inputs, 0);
         assert_eq!(report.intermediate_wires, 0);
         assert_eq!(report.primary_outputs, 0);
         assert_eq!(report.missing_wires_count, 0);
-        assert_eq!(report.wire_usage_counts.len(), 0);
+        assert_eq!(report.wire_usage_counts, UsageCounts::Dense(Vec::new()));
 
         Ok(())
     }
@@ -406,7 +1198,7 @@ mod tests {
         let file = File::open(temp_file.path())?;
         let mut stream = BufferedLineStream::new(file);
 
-        let report = analyze_wire_usage(&mut stream)?;
+        let report = analyze_wire_usage(&mut stream, &NoProgress)?;
 
         assert_eq!(report.total_wires, 10);
         assert_eq!(report.primary_inputs, 4); // wires 0, 1, 5, 6
@@ -424,7 +1216,7 @@ mod tests {
         let file = File::open(temp_file.path()).unwrap();
         let mut stream = BufferedLineStream::new(file);
 
-        let result = analyze_wire_usage(&mut stream);
+        let result = analyze_wire_usage(&mut stream, &NoProgress);
 
         assert!(result.is_err());
         let error_msg = result.unwrap_err().to_string();
@@ -438,11 +1230,12 @@ mod tests {
         let file = File::open(temp_file.path()).unwrap();
         let mut stream = BufferedLineStream::new(file);
 
-        let result = analyze_wire_usage(&mut stream);
+        let result = analyze_wire_usage(&mut stream, &NoProgress);
 
         assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("Missing header line"));
+        let error = result.unwrap_err();
+        assert!(matches!(error, GcError::Parse { line: 1, .. }));
+        assert!(error.to_string().contains("Missing header"));
     }
 
     #[test]
@@ -453,7 +1246,7 @@ mod tests {
         let file = File::open(temp_file.path()).unwrap();
         let mut stream = BufferedLineStream::new(file);
 
-        let result = analyze_wire_usage(&mut stream);
+        let result = analyze_wire_usage(&mut stream, &NoProgress);
 
         assert!(result.is_err());
         let error_msg = result.unwrap_err().to_string();
@@ -467,11 +1260,12 @@ mod tests {
         let file = File::open(temp_file.path()).unwrap();
         let mut stream = BufferedLineStream::new(file);
 
-        let result = analyze_wire_usage(&mut stream);
+        let result = analyze_wire_usage(&mut stream, &NoProgress);
 
         assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("Empty line at line number 3"));
+        let error = result.unwrap_err();
+        assert!(matches!(error, GcError::Parse { line: 3, .. }));
+        assert_eq!(error.to_string(), "Empty line at line 3");
     }
 
     #[test]
@@ -481,11 +1275,12 @@ mod tests {
         let file = File::open(temp_file.path()).unwrap();
         let mut stream = BufferedLineStream::new(file);
 
-        let result = analyze_wire_usage(&mut stream);
+        let result = analyze_wire_usage(&mut stream, &NoProgress);
 
         assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("Invalid num_inputs at line 2"));
+        let error = result.unwrap_err();
+        assert!(matches!(error, GcError::Parse { line: 2, .. }));
+        assert!(error.to_string().starts_with("Invalid num_inputs"));
     }
 
     #[test]
@@ -503,14 +1298,60 @@ mod tests {
         let file = File::open(temp_file.path())?;
         let mut stream = BufferedLineStream::new(file);
 
-        let report = analyze_wire_usage(&mut stream)?;
+        let report = analyze_wire_usage(&mut stream, &NoProgress)?;
 
         assert_eq!(report.total_wires, 3);
         assert_eq!(report.primary_inputs, 1); // wire 0
         assert_eq!(report.primary_outputs, 2); // wires 1, 2
 
         // Wire 0 usage should be saturated at 255 (u8::MAX)
-        assert_eq!(report.wire_usage_counts[0], 255);
+        assert_eq!(report.wire_usage_counts.get(0), 255);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyze_fan_out() -> Result<()> {
+        // Gate 0 writes wire 2, which the next 300 gates read along with input 0
+        let mut circuit_data = String::from("301 303\n2 1 0 1 2 AND\n");
+        for i in 0..300 {
+            circuit_data.push_str(&format!("2 1 2 0 {} XOR\n", 3 + i));
+        }
+        let stream = || BufferedLineStream::from_reader(circuit_data.as_bytes());
+        let report = analyze_wire_usage(&mut stream(), &NoProgress)?;
+
+        let fan_out = analyze_fan_out(&mut stream(), &report, 3, &NoProgress)?;
+        assert_eq!(
+            fan_out.histogram,
+            BTreeMap::from([(0, 300), (1, 1), (300, 1), (301, 1)])
+        );
+        assert_eq!(
+            fan_out.top_wires,
+            vec![
+                HighFanOutWire {
+                    wire: 0,
+                    fan_out: 301,
+                    producer: None
+                },
+                HighFanOutWire {
+                    wire: 2,
+                    fan_out: 300,
+                    producer: Some(0)
+                },
+                HighFanOutWire {
+                    wire: 1,
+                    fan_out: 1,
+                    producer: None
+                },
+            ]
+        );
+
+        let csv_file = tempfile::NamedTempFile::new()?;
+        fan_out.export_histogram_csv(csv_file.path())?;
+        assert_eq!(
+            std::fs::read_to_string(csv_file.path())?,
+            "fan_out,wire_count\n0,300\n1,1\n300,1\n301,1\n"
+        );
 
         Ok(())
     }
@@ -522,7 +1363,7 @@ mod tests {
         let file = File::open(temp_file.path())?;
         let mut stream = BufferedLineStream::new(file);
 
-        let report = analyze_wire_usage(&mut stream)?;
+        let report = analyze_wire_usage(&mut stream, &NoProgress)?;
 
         // Test JSON export
         let json_file = tempfile::NamedTempFile::new()?;
@@ -542,6 +1383,17 @@ mod tests {
         assert_eq!(loaded_report.primary_inputs, report.primary_inputs);
         assert_eq!(loaded_report.wire_usage_counts, report.wire_usage_counts);
 
+        // A flipped bit fails the checksum instead of decoding to a wrong report
+        let mut bytes = std::fs::read(binary_file.path())?;
+        bytes[10] ^= 1;
+        std::fs::write(binary_file.path(), &bytes)?;
+        let error = WireUsageReport::load_binary(binary_file.path()).unwrap_err();
+        assert!(
+            matches!(error, GcError::ChecksumMismatch { .. }),
+            "{}",
+            error
+        );
+
         Ok(())
     }
 }
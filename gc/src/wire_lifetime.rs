@@ -0,0 +1,311 @@
+use crate::error::Result;
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::constants::{BUFFER_SIZE, PROGRESS_UPDATE_INTERVAL};
+use crate::gate::WireId;
+use crate::parser::{CircuitHeader, WireRef, read_header, visit_gate_wires};
+use crate::progress::ProgressObserver;
+use crate::stream::BufferedLineStream;
+
+/// Gate index of a [`WireLifetimes`] entry that no gate fills: the producer
+/// of a wire no gate writes, or the last use of a wire no gate reads
+pub const NO_GATE: u64 = u64::MAX;
+
+/// Gate writing every wire and gate reading it last
+///
+/// Gate indices are 0-based in file order. Lifetimes are given in points of
+/// the run, point `t` being after the first `t` gates: a primary input is
+/// held from point 0, a gate output from the point after its gate, and both
+/// until the point after their last use, or to the end for primary outputs
+/// and wires no gate reads. That is when an evaluator freeing each label at
+/// its last use holds it; the memory simulation keeps wires read 255 times
+/// or more to the end as well, so its peaks may be higher.
+#[derive(Debug, Clone, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub struct WireLifetimes {
+    /// Number of gates in the circuit
+    pub num_gates: u64,
+    /// Gate writing each wire: index = wire ID, [`NO_GATE`] for wires no gate writes
+    pub produced: Vec<u64>,
+    /// Last gate reading each wire: index = wire ID, [`NO_GATE`] for wires no gate reads
+    pub last_use: Vec<u64>,
+    /// Primary input wires in ascending order
+    pub primary_input_wires: Vec<WireId>,
+    /// Primary output wires in ascending order
+    pub primary_output_wires: Vec<WireId>,
+}
+
+impl WireLifetimes {
+    /// Save the lifetimes to a binary file
+    pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let encoded = bincode::encode_to_vec(self, bincode::config::standard())?;
+        std::fs::write(path, encoded)?;
+        Ok(())
+    }
+
+    /// Load lifetimes from a binary file
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        let mut reader = BufReader::with_capacity(BUFFER_SIZE, file);
+
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        let (lifetimes, _) = bincode::decode_from_slice(&buffer, bincode::config::standard())?;
+        Ok(lifetimes)
+    }
+
+    /// Total number of wires in the circuit
+    pub fn num_wires(&self) -> u64 {
+        self.produced.len() as u64
+    }
+
+    /// Points of the run at which the label of a wire is held
+    ///
+    /// # Returns
+    /// * `Some(Range<u64>)` - From the point the wire is written or 0 for a primary input, to
+    ///   the point after its last use or `num_gates + 1` if it is kept to the end
+    /// * `None` - The wire is neither a primary input nor written by a gate
+    pub fn live_range(&self, wire_id: WireId) -> Option<Range<u64>> {
+        let produced = *self.produced.get(wire_id as usize)?;
+        let start = if produced != NO_GATE {
+            produced + 1
+        } else if self.primary_input_wires.binary_search(&wire_id).is_ok() {
+            0
+        } else {
+            return None;
+        };
+        let last_use = self.last_use[wire_id as usize];
+        let end =
+            if last_use == NO_GATE || self.primary_output_wires.binary_search(&wire_id).is_ok() {
+                self.num_gates + 1
+            } else {
+                last_use + 1
+            };
+        // A wire read before it is written is held from the point it is written
+        Some(start..end.max(start + 1))
+    }
+
+    /// Point of the run at which the most labels are held, the earliest if several are
+    ///
+    /// # Returns
+    /// * `(u64, u64)` - The point, after that many gates, and the number of labels held there
+    pub fn peak(&self) -> (u64, u64) {
+        // Change in the number of labels held at each point
+        let mut deltas = vec![0i32; self.num_gates as usize + 2];
+        for wire_id in 0..self.num_wires() {
+            if let Some(range) = self.live_range(wire_id) {
+                deltas[range.start as usize] += 1;
+                deltas[range.end as usize] -= 1;
+            }
+        }
+
+        let mut live = 0i64;
+        let mut peak = (0, 0);
+        for (point, &delta) in deltas[..=self.num_gates as usize].iter().enumerate() {
+            live += i64::from(delta);
+            if live as u64 > peak.1 {
+                peak = (point as u64, live as u64);
+            }
+        }
+        peak
+    }
+
+    /// Wires whose labels are held at a point of the run, longest held first
+    ///
+    /// # Arguments
+    /// * `point` - Point of the run, after that many gates
+    ///
+    /// # Returns
+    /// * `Vec<WireId>` - The wires, by the point they were written and then by wire ID
+    pub fn live_wires_at(&self, point: u64) -> Vec<WireId> {
+        let mut live: Vec<(u64, WireId)> = (0..self.num_wires())
+            .filter_map(|wire_id| {
+                let range = self.live_range(wire_id)?;
+                range.contains(&point).then_some((range.start, wire_id))
+            })
+            .collect();
+        live.sort_unstable();
+        live.into_iter().map(|(_, wire_id)| wire_id).collect()
+    }
+
+    /// Export the number of wires of each lifetime as CSV, in power-of-two buckets
+    ///
+    /// A wire's lifetime is the number of points its label is held, see
+    /// [`live_range`](Self::live_range).
+    ///
+    /// # CSV Format
+    /// ```csv
+    /// min_lifetime,max_lifetime,wire_count
+    /// 1,1,250000
+    /// 2,3,120000
+    /// 4,7,40000
+    /// ```
+    pub fn export_summary_csv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut buckets = vec![0u64; 65];
+        for wire_id in 0..self.num_wires() {
+            if let Some(range) = self.live_range(wire_id) {
+                buckets[(range.end - range.start).ilog2() as usize] += 1;
+            }
+        }
+
+        let mut file = File::create(path)?;
+        writeln!(file, "min_lifetime,max_lifetime,wire_count")?;
+        let last_bucket = buckets.iter().rposition(|&count| count > 0).unwrap_or(0);
+        for (bucket, &wire_count) in buckets[..=last_bucket].iter().enumerate() {
+            let min_lifetime = 1u128 << bucket;
+            writeln!(
+                file,
+                "{},{},{}",
+                min_lifetime,
+                2 * min_lifetime - 1,
+                wire_count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the gate writing and the gate last reading every wire of a Bristol circuit
+///
+/// The primary inputs and outputs are the wires a Bristol Fashion header
+/// declares, or else the wires that gates read but never write, and write
+/// but never read. The pass keeps 16 bytes per wire.
+///
+/// # Arguments
+/// * `stream` - The line stream to process
+/// * `progress` - Receives progress updates
+///
+/// # Returns
+/// * `Ok(WireLifetimes)` - Producer and last use of every wire
+/// * `Err(GcError)` - Parse error, IO error, or a wire outside `0..num_wires`
+#[tracing::instrument(name = "wire_lifetimes", skip_all)]
+pub fn analyze_wire_lifetimes<R: Read>(
+    stream: &mut BufferedLineStream<R>,
+    progress: &dyn ProgressObserver,
+) -> Result<WireLifetimes> {
+    let CircuitHeader {
+        num_gates,
+        num_wires,
+        io,
+        lines,
+    } = read_header(stream)?;
+
+    let mut produced = vec![NO_GATE; num_wires as usize];
+    let mut last_use = vec![NO_GATE; num_wires as usize];
+    let mut gates_processed = 0u64;
+    let mut line_number = lines;
+
+    progress.start(Some(num_gates), "Analyzing wire lifetimes");
+
+    while let Some(line_result) = stream.next_line() {
+        line_number += 1;
+        visit_gate_wires(line_result?, line_number, |wire| {
+            wire.check_range(num_wires, line_number)?;
+            match wire {
+                WireRef::Input(wire_id) => last_use[wire_id as usize] = gates_processed,
+                WireRef::Output(wire_id) => produced[wire_id as usize] = gates_processed,
+            }
+            Ok(())
+        })?;
+
+        gates_processed += 1;
+        if gates_processed.is_multiple_of(PROGRESS_UPDATE_INTERVAL) {
+            progress.update(gates_processed);
+        }
+    }
+
+    progress.update(gates_processed);
+    progress.finish(&format!(
+        "✓ Analyzed {gates_processed} gates, {num_wires} wires"
+    ));
+
+    let (primary_input_wires, primary_output_wires) = match &io {
+        Some(io) => (
+            io.input_wires().collect(),
+            io.output_wires(num_wires).collect(),
+        ),
+        None => {
+            let wires = |keep: fn(u64, u64) -> bool| -> Vec<WireId> {
+                (0..num_wires)
+                    .filter(|&wire_id| keep(produced[wire_id as usize], last_use[wire_id as usize]))
+                    .collect()
+            };
+            (
+                wires(|produced, last_use| produced == NO_GATE && last_use != NO_GATE),
+                wires(|produced, last_use| produced != NO_GATE && last_use == NO_GATE),
+            )
+        }
+    };
+
+    Ok(WireLifetimes {
+        num_gates: gates_processed,
+        produced,
+        last_use,
+        primary_input_wires,
+        primary_output_wires,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_simulation::simulate_memory_usage;
+    use crate::progress::NoProgress;
+    use crate::random_circuit::{RandomCircuit, RandomCircuitParams};
+    use crate::wire_analyzer::analyze_wire_usage;
+
+    #[test]
+    fn test_wire_lifetimes() -> Result<()> {
+        // Input 0 is read up to gate 3 and wire 2 up to gate 2, so three
+        // labels are held after gate 1; wire 6 is never used
+        let text = "4 7\n2 1 0 1 2 AND\n2 1 0 2 3 XOR\n2 1 2 3 4 AND\n2 1 0 4 5 XOR\n";
+        let lifetimes = analyze_wire_lifetimes(
+            &mut BufferedLineStream::from_reader(text.as_bytes()),
+            &NoProgress,
+        )?;
+        assert_eq!(lifetimes.produced, [NO_GATE, NO_GATE, 0, 1, 2, 3, NO_GATE]);
+        assert_eq!(lifetimes.last_use, [3, 0, 2, 2, 3, NO_GATE, NO_GATE]);
+        assert_eq!(lifetimes.primary_input_wires, [0, 1]);
+        assert_eq!(lifetimes.primary_output_wires, [5]);
+        assert_eq!(lifetimes.live_range(0), Some(0..4));
+        assert_eq!(lifetimes.live_range(2), Some(1..3));
+        assert_eq!(lifetimes.live_range(5), Some(4..5));
+        assert_eq!(lifetimes.live_range(6), None);
+        assert_eq!(lifetimes.peak(), (2, 3));
+        assert_eq!(lifetimes.live_wires_at(2), [0, 2, 3]);
+
+        let binary_file = tempfile::NamedTempFile::new()?;
+        lifetimes.save_binary(binary_file.path())?;
+        assert_eq!(WireLifetimes::load_binary(binary_file.path())?, lifetimes);
+        let csv_file = tempfile::NamedTempFile::new()?;
+        lifetimes.export_summary_csv(csv_file.path())?;
+        assert_eq!(
+            std::fs::read_to_string(csv_file.path())?,
+            "min_lifetime,max_lifetime,wire_count\n1,1,4\n2,3,1\n4,7,1\n"
+        );
+
+        // Without wires read 255 times, the peak is the memory simulation's
+        let params = RandomCircuitParams {
+            num_gates: 20_000,
+            num_inputs: 64,
+            num_outputs: 100,
+            depth: 200,
+            fan_out: "local:500".parse()?,
+            ..RandomCircuitParams::default()
+        };
+        let mut text = Vec::new();
+        RandomCircuit::new(params)?
+            .into_circuit()
+            .write_bristol(&mut text)?;
+        let stream = || BufferedLineStream::from_reader(text.as_slice());
+        let lifetimes = analyze_wire_lifetimes(&mut stream(), &NoProgress)?;
+        let wire_report = analyze_wire_usage(&mut stream(), &NoProgress)?;
+        let memory = simulate_memory_usage(&mut stream(), &wire_report, &NoProgress)?;
+        let (point, live_wires) = lifetimes.peak();
+        assert_eq!(live_wires, memory.max_live_wires as u64);
+        assert_eq!(lifetimes.live_wires_at(point).len() as u64, live_wires);
+        Ok(())
+    }
+}